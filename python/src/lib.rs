@@ -0,0 +1,178 @@
+//! Python bindings for remote-package, built with maturin:
+//!
+//! ```text
+//! cd python && maturin develop
+//! ```
+//!
+//! ```python
+//! import remote_package
+//!
+//! package = remote_package.from_url("https://example.com/hello_1.0_amd64.deb")
+//! print(package.name, package.version, package.dependencies)
+//!
+//! url = remote_package.apt_latest(
+//!     "http://archive.ubuntu.com/ubuntu", "jammy", ["main"], "amd64", "hello"
+//! )
+//! ```
+//!
+//! Requests are made with the blocking client, with the GIL released, and
+//! failures raise `remote_package.PackageError`.
+#![deny(missing_docs)]
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use remote_package::metadata::PackageMetadata;
+use remote_package::repo_deb::AptRepo;
+use remote_package::repo_rpm::YumRepo;
+use remote_package::{PkgError, RemotePackage as _};
+
+create_exception!(
+    remote_package,
+    PackageError,
+    PyException,
+    "A package couldn't be fetched or parsed."
+);
+
+/// Convert an error to its message, so that it can leave the thread that
+/// released the GIL.
+fn message(err: PkgError) -> String {
+    err.to_string()
+}
+
+/// The metadata of a package.
+#[pyclass(name = "Package", module = "remote_package")]
+struct Package {
+    metadata: PackageMetadata,
+}
+
+#[pymethods]
+impl Package {
+    /// The package type, `deb` or `rpm`.
+    #[getter]
+    fn package_type(&self) -> &str {
+        &self.metadata.package_type
+    }
+
+    /// The package name.
+    #[getter]
+    fn name(&self) -> &str {
+        &self.metadata.name
+    }
+
+    /// The package version.
+    #[getter]
+    fn version(&self) -> &str {
+        &self.metadata.version
+    }
+
+    /// The package iteration, if the package has one.
+    #[getter]
+    fn iteration(&self) -> Option<&str> {
+        self.metadata.iteration.as_deref()
+    }
+
+    /// The package architecture.
+    #[getter]
+    fn arch(&self) -> &str {
+        &self.metadata.arch
+    }
+
+    /// The declared license, if the package format records one.
+    #[getter]
+    fn license(&self) -> Option<&str> {
+        self.metadata.license.as_deref()
+    }
+
+    /// The names of the declared dependencies.
+    #[getter]
+    fn dependencies(&self) -> Vec<String> {
+        self.metadata.dependencies.clone()
+    }
+
+    /// The paths of the files installed, if known from the metadata.
+    #[getter]
+    fn files(&self) -> Vec<String> {
+        self.metadata.files.clone()
+    }
+
+    /// The paths of the configuration files.
+    #[getter]
+    fn config_files(&self) -> Vec<String> {
+        self.metadata.config_files.clone()
+    }
+
+    /// Serialize the metadata as JSON, e.g. for `json.loads`.
+    fn to_json(&self) -> PyResult<String> {
+        self.metadata
+            .to_json()
+            .map_err(|err| PackageError::new_err(err.to_string()))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Package {} {} {}>",
+            self.metadata.name, self.metadata.version, self.metadata.arch
+        )
+    }
+}
+
+/// Fetch the metadata of the package at a URL, stopping the download once
+/// it has been parsed.
+#[pyfunction]
+fn from_url(py: Python<'_>, url: &str) -> PyResult<Package> {
+    py.allow_threads(|| {
+        remote_package::from_url(url)
+            .and_then(|package| package.to_metadata())
+            .map_err(message)
+    })
+    .map(|metadata| Package { metadata })
+    .map_err(PackageError::new_err)
+}
+
+/// Find the download URL of the newest version of a package in an apt
+/// repository, or `None` if it has no such package.
+#[pyfunction]
+fn apt_latest(
+    py: Python<'_>,
+    base_url: &str,
+    dist: &str,
+    components: Vec<String>,
+    arch: &str,
+    name: &str,
+) -> PyResult<Option<String>> {
+    py.allow_threads(|| {
+        AptRepo::new(base_url, dist, &components, arch)
+            .latest(name)
+            .map(|found| found.map(|resolved| resolved.url))
+            .map_err(message)
+    })
+    .map_err(PackageError::new_err)
+}
+
+/// Find the download URL of the newest version of a package for an
+/// architecture in a Yum/DNF repository, or `None` if it has no such
+/// package.
+#[pyfunction]
+fn yum_latest(py: Python<'_>, base_url: &str, name: &str, arch: &str) -> PyResult<Option<String>> {
+    py.allow_threads(|| {
+        YumRepo::new(base_url)
+            .latest(name, arch)
+            .map(|found| found.map(|resolved| resolved.url))
+            .map_err(message)
+    })
+    .map_err(PackageError::new_err)
+}
+
+/// The `remote_package` Python module.
+#[pymodule]
+#[pyo3(name = "remote_package")]
+fn python_module(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("PackageError", py.get_type::<PackageError>())?;
+    m.add_class::<Package>()?;
+    m.add_function(wrap_pyfunction!(from_url, m)?)?;
+    m.add_function(wrap_pyfunction!(apt_latest, m)?)?;
+    m.add_function(wrap_pyfunction!(yum_latest, m)?)?;
+    Ok(())
+}
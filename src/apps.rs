@@ -0,0 +1,551 @@
+//! Extraction of the applications a package provides, from the desktop
+//! entries and AppStream metainfo in its payload, as software centers use to
+//! list them.
+//!
+//! See the [Desktop Entry](https://specifications.freedesktop.org/desktop-entry-spec/latest/)
+//! and [AppStream](https://www.freedesktop.org/software/appstream/docs/)
+//! specifications.
+use std::io::{BufRead, Read};
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+use crate::payload::{Entries, EntryKind};
+use crate::xml::attribute;
+use crate::PkgError;
+
+/// Desktop entries and metainfo files larger than this are skipped.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// A desktop entry, i.e. a `.desktop` file describing how to launch an
+/// application.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesktopEntry {
+    /// The installed path, e.g. `/usr/share/applications/org.example.Hello.desktop`.
+    pub path: String,
+
+    /// The desktop file ID, e.g. `org.example.Hello.desktop`. Entries in
+    /// subdirectories have the directories joined with `-`.
+    pub id: String,
+
+    /// The type of entry, usually `Application`.
+    pub kind: Option<String>,
+
+    /// The untranslated name of the application.
+    pub name: Option<String>,
+
+    /// The icon, as a theme icon name or an absolute path.
+    pub icon: Option<String>,
+
+    /// The command line the application is started with.
+    pub exec: Option<String>,
+
+    /// The menu categories, e.g. `Utility`.
+    pub categories: Vec<String>,
+
+    /// Whether the entry is hidden from menus.
+    pub no_display: bool,
+}
+
+impl DesktopEntry {
+    /// Parse the `[Desktop Entry]` group of a desktop entry installed at
+    /// `path`. Translated keys such as `Name[de]` are skipped.
+    pub fn parse(path: &str, text: &str) -> Self {
+        let mut entry = Self {
+            path: path.to_owned(),
+            id: desktop_id(path),
+            kind: None,
+            name: None,
+            icon: None,
+            exec: None,
+            categories: Vec::new(),
+            no_display: false,
+        };
+
+        let mut in_group = false;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                in_group = line == "[Desktop Entry]";
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) if in_group => (key.trim(), unescape(value.trim())),
+                _ => continue,
+            };
+            match key {
+                "Type" => entry.kind = Some(value),
+                "Name" => entry.name = Some(value),
+                "Icon" => entry.icon = Some(value),
+                "Exec" => entry.exec = Some(value),
+                "Categories" => {
+                    entry.categories = value
+                        .split(';')
+                        .filter(|category| !category.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                }
+                "NoDisplay" => entry.no_display = value == "true",
+                _ => {}
+            }
+        }
+        entry
+    }
+}
+
+/// An AppStream component, as described by a metainfo file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppStreamComponent {
+    /// The installed path, e.g. `/usr/share/metainfo/org.example.Hello.metainfo.xml`.
+    pub path: String,
+
+    /// The component ID, e.g. `org.example.Hello`.
+    pub id: Option<String>,
+
+    /// The type of component, e.g. `desktop-application`.
+    pub kind: Option<String>,
+
+    /// The untranslated name.
+    pub name: Option<String>,
+
+    /// The untranslated one line summary.
+    pub summary: Option<String>,
+
+    /// The first icon listed, as a theme icon name, a file name or a URL.
+    pub icon: Option<String>,
+
+    /// The desktop file ID the component is launched with, if any.
+    pub launchable: Option<String>,
+
+    /// The screenshots, in the order listed.
+    pub screenshots: Vec<Screenshot>,
+
+    /// The releases, newest first as the specification asks.
+    pub releases: Vec<Release>,
+}
+
+/// A screenshot of an AppStream component.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Screenshot {
+    /// Whether this is the screenshot to show first.
+    pub default: bool,
+
+    /// The untranslated caption.
+    pub caption: Option<String>,
+
+    /// The URL of the image at its original size, or of the first image
+    /// listed if none is marked as the source.
+    pub image: Option<String>,
+}
+
+/// A release of an AppStream component.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Release {
+    /// The version, e.g. `1.2.0`.
+    pub version: Option<String>,
+
+    /// The release date, e.g. `2023-05-01`.
+    pub date: Option<String>,
+
+    /// The release time in seconds since the Unix epoch, which older files
+    /// give in place of the date.
+    pub timestamp: Option<u64>,
+
+    /// The type of release, `stable` or `development`.
+    pub kind: Option<String>,
+}
+
+impl Release {
+    /// Read a `<release>` element's attributes.
+    fn from_element(e: &BytesStart<'_>) -> Result<Self, PkgError> {
+        Ok(Self {
+            version: attribute(e, b"version")?,
+            date: attribute(e, b"date")?,
+            timestamp: attribute(e, b"timestamp")?.and_then(|t| t.parse().ok()),
+            kind: attribute(e, b"type")?,
+        })
+    }
+}
+
+impl AppStreamComponent {
+    /// Parse a metainfo file installed at `path`. Only the elements directly
+    /// under the root `<component>` are read, and the screenshots and
+    /// releases.
+    ///
+    /// Fails with [`PkgError::InvalidPackage`] if the file has no root
+    /// component or is cut short.
+    pub fn parse<R: BufRead>(path: &str, reader: R) -> Result<Self, PkgError> {
+        let mut reader = Reader::from_reader(reader);
+        let _ = reader.trim_text(true);
+
+        let mut component = Self {
+            path: path.to_owned(),
+            id: None,
+            kind: None,
+            name: None,
+            summary: None,
+            icon: None,
+            launchable: None,
+            screenshots: Vec::new(),
+            releases: Vec::new(),
+        };
+        // The element whose text is being read, if it's wanted.
+        let mut field: Option<Vec<u8>> = None;
+        let mut depth = 0;
+        let mut root = false;
+        // The list under the root being read, `screenshots` or `releases`.
+        let mut list: Option<Vec<u8>> = None;
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(e) => {
+                    depth += 1;
+                    let name = e.local_name().as_ref().to_vec();
+                    // Old files have an `<application>` root.
+                    if depth == 1 && (name == b"component" || name == b"application") {
+                        component.kind = attribute(&e, b"type")?;
+                        root = true;
+                    }
+                    let translated = attribute(&e, b"xml:lang")?.is_some();
+                    if depth == 2 && (name == b"screenshots" || name == b"releases") {
+                        list = Some(name.clone());
+                    }
+                    match (list.as_deref(), depth, name.as_slice()) {
+                        (Some(b"screenshots"), 3, b"screenshot") => {
+                            component.screenshots.push(Screenshot {
+                                default: attribute(&e, b"type")?.as_deref() == Some("default"),
+                                ..Screenshot::default()
+                            })
+                        }
+                        (Some(b"releases"), 3, b"release") => {
+                            component.releases.push(Release::from_element(&e)?)
+                        }
+                        _ => {}
+                    }
+                    field = match name.as_slice() {
+                        b"caption" | b"image"
+                            if depth == 4
+                                && list.as_deref() == Some(b"screenshots")
+                                && !translated =>
+                        {
+                            if attribute(&e, b"type")?.as_deref() == Some("source") {
+                                Some(b"source".to_vec())
+                            } else {
+                                Some(name)
+                            }
+                        }
+                        b"launchable"
+                            if attribute(&e, b"type")?.as_deref() != Some("desktop-id") =>
+                        {
+                            None
+                        }
+                        b"id" | b"name" | b"summary" | b"icon" | b"launchable"
+                            if depth == 2 && !translated =>
+                        {
+                            Some(name)
+                        }
+                        _ => None,
+                    };
+                }
+                Event::Text(t) => {
+                    let text = t.unescape()?.trim().to_owned();
+                    let screenshot = component.screenshots.last_mut();
+                    let value = match (field.as_deref(), screenshot) {
+                        // The source image is preferred over any other.
+                        (Some(b"source"), Some(screenshot)) if !text.is_empty() => {
+                            screenshot.image = None;
+                            &mut screenshot.image
+                        }
+                        (Some(b"caption"), Some(screenshot)) => &mut screenshot.caption,
+                        (Some(b"image"), Some(screenshot)) => &mut screenshot.image,
+                        (Some(b"id"), _) => &mut component.id,
+                        (Some(b"name"), _) => &mut component.name,
+                        (Some(b"summary"), _) => &mut component.summary,
+                        (Some(b"icon"), _) => &mut component.icon,
+                        (Some(b"launchable"), _) => &mut component.launchable,
+                        _ => {
+                            buf.clear();
+                            continue;
+                        }
+                    };
+                    if value.is_none() && !text.is_empty() {
+                        *value = Some(text);
+                    }
+                }
+                Event::Empty(e)
+                    if depth == 2
+                        && list.as_deref() == Some(b"releases")
+                        && e.local_name().as_ref() == b"release" =>
+                {
+                    component.releases.push(Release::from_element(&e)?);
+                }
+                Event::End(_) => {
+                    if depth == 2 {
+                        list = None;
+                    }
+                    depth -= 1;
+                    field = None;
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        if !root || depth != 0 {
+            return Err(PkgError::InvalidPackage(format!(
+                "invalid AppStream metainfo: {}",
+                path
+            )));
+        }
+        Ok(component)
+    }
+}
+
+/// The applications found in a package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Apps {
+    /// The desktop entries, in payload order.
+    pub desktop_entries: Vec<DesktopEntry>,
+
+    /// The AppStream components, in payload order.
+    pub components: Vec<AppStreamComponent>,
+}
+
+/// Whether a path is a desktop entry for an application menu.
+pub fn is_desktop_entry(path: &str) -> bool {
+    path.strip_prefix("/usr/share/applications/")
+        .is_some_and(|name| name.ends_with(".desktop"))
+}
+
+/// Whether a path is an AppStream metainfo file, including the older
+/// `appdata` location.
+pub fn is_metainfo(path: &str) -> bool {
+    ["/usr/share/metainfo/", "/usr/share/appdata/"]
+        .iter()
+        .filter_map(|dir| path.strip_prefix(dir))
+        .any(|name| !name.contains('/') && name.ends_with(".xml"))
+}
+
+/// Find and parse the desktop entries and AppStream metainfo in the payload
+/// of a package, reading it as a stream.
+///
+/// Files over 1MiB are skipped, and metainfo that can't be parsed is left
+/// out.
+pub fn find_apps<R: Read>(reader: R) -> Result<Apps, PkgError> {
+    let mut apps = Apps::default();
+    let mut entries = Entries::new(reader)?;
+    while let Some(mut entry) = entries.next_entry()? {
+        let path = entry.path().to_owned();
+        let wanted = is_desktop_entry(&path) || is_metainfo(&path);
+        if *entry.kind() != EntryKind::File || !wanted || entry.size() > MAX_FILE_SIZE {
+            continue;
+        }
+
+        let mut contents = Vec::new();
+        let _ = entry.read_to_end(&mut contents)?;
+        if is_desktop_entry(&path) {
+            let text = String::from_utf8_lossy(&contents);
+            apps.desktop_entries.push(DesktopEntry::parse(&path, &text));
+        } else if let Ok(component) = AppStreamComponent::parse(&path, contents.as_slice()) {
+            apps.components.push(component);
+        }
+    }
+    Ok(apps)
+}
+
+/// Find and parse the applications in a package from a URL; see
+/// [`find_apps`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn find_apps_from_url(url: &str) -> Result<Apps, PkgError> {
+    find_apps(crate::http::get_package(url)?)
+}
+
+/// The desktop file ID of an entry installed at `path`.
+fn desktop_id(path: &str) -> String {
+    path.strip_prefix("/usr/share/applications/")
+        .unwrap_or_else(|| path.rsplit('/').next().unwrap_or(path))
+        .replace('/', "-")
+}
+
+/// Remove the escapes allowed in desktop entry string values.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => {
+                // Other escapes, such as `\;` in lists, are kept.
+                if c != '\\' {
+                    unescaped.push('\\');
+                }
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESKTOP_ENTRY: &str = "[Desktop Entry]
+# A comment
+Type=Application
+Name=Hello
+Name[de]=Hallo
+Exec=hello --greeting=Hi\\sthere %F
+Icon=org.example.Hello
+Categories=Utility;GTK;
+
+[Desktop Action New]
+Name=New Window
+";
+
+    const METAINFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<component type="desktop-application">
+  <id>org.example.Hello</id>
+  <name>Hello</name>
+  <name xml:lang="de">Hallo</name>
+  <summary>Say hello</summary>
+  <description><p><name>Not</name> a name</p></description>
+  <launchable type="desktop-id">org.example.Hello.desktop</launchable>
+  <icon type="stock">org.example.Hello</icon>
+  <screenshots>
+    <screenshot type="default">
+      <caption>The main window</caption>
+      <caption xml:lang="de">Das Hauptfenster</caption>
+      <image type="thumbnail" width="224" height="126">https://example.org/main-small.png</image>
+      <image type="source">https://example.org/main.png</image>
+    </screenshot>
+    <screenshot>
+      <image>https://example.org/settings.png</image>
+    </screenshot>
+  </screenshots>
+  <releases>
+    <release version="1.1" date="2023-05-01" type="development">
+      <description><p>Faster greetings</p></description>
+    </release>
+    <release version="1.0" timestamp="1672531200"/>
+  </releases>
+</component>
+"#;
+
+    #[test]
+    fn test_parse_desktop_entry() {
+        let entry = DesktopEntry::parse(
+            "/usr/share/applications/kde/org.example.Hello.desktop",
+            DESKTOP_ENTRY,
+        );
+        assert_eq!(
+            entry,
+            DesktopEntry {
+                path: "/usr/share/applications/kde/org.example.Hello.desktop".to_string(),
+                id: "kde-org.example.Hello.desktop".to_string(),
+                kind: Some("Application".to_string()),
+                name: Some("Hello".to_string()),
+                icon: Some("org.example.Hello".to_string()),
+                exec: Some("hello --greeting=Hi there %F".to_string()),
+                categories: vec!["Utility".to_string(), "GTK".to_string()],
+                no_display: false,
+            }
+        );
+        assert_eq!(unescape("a\\;b\\\\c\\"), "a\\;b\\c\\");
+    }
+
+    #[test]
+    fn test_parse_metainfo() {
+        let path = "/usr/share/metainfo/org.example.Hello.metainfo.xml";
+        let component = AppStreamComponent::parse(path, METAINFO.as_bytes()).unwrap();
+        assert_eq!(
+            component,
+            AppStreamComponent {
+                path: path.to_string(),
+                id: Some("org.example.Hello".to_string()),
+                kind: Some("desktop-application".to_string()),
+                name: Some("Hello".to_string()),
+                summary: Some("Say hello".to_string()),
+                icon: Some("org.example.Hello".to_string()),
+                launchable: Some("org.example.Hello.desktop".to_string()),
+                screenshots: vec![
+                    Screenshot {
+                        default: true,
+                        caption: Some("The main window".to_string()),
+                        image: Some("https://example.org/main.png".to_string()),
+                    },
+                    Screenshot {
+                        default: false,
+                        caption: None,
+                        image: Some("https://example.org/settings.png".to_string()),
+                    },
+                ],
+                releases: vec![
+                    Release {
+                        version: Some("1.1".to_string()),
+                        date: Some("2023-05-01".to_string()),
+                        timestamp: None,
+                        kind: Some("development".to_string()),
+                    },
+                    Release {
+                        version: Some("1.0".to_string()),
+                        date: None,
+                        timestamp: Some(1672531200),
+                        kind: None,
+                    },
+                ],
+            }
+        );
+
+        assert!(AppStreamComponent::parse(path, &b"<component><id>"[..]).is_err());
+        assert!(AppStreamComponent::parse(path, &b"<metadata/>"[..]).is_err());
+
+        assert!(is_metainfo(path));
+        assert!(is_metainfo("/usr/share/appdata/hello.appdata.xml"));
+        assert!(!is_metainfo("/usr/share/metainfo/hello.xml.d/extra.xml"));
+        assert!(is_desktop_entry("/usr/share/applications/hello.desktop"));
+        assert!(!is_desktop_entry("/etc/xdg/autostart/hello.desktop"));
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_find_apps() {
+        let rpm = crate::payload::tests::build_rpm_with_payload(&[
+            ("./usr/bin/hello", b"binary"),
+            (
+                "./usr/share/applications/org.example.Hello.desktop",
+                DESKTOP_ENTRY.as_bytes(),
+            ),
+            (
+                "./usr/share/metainfo/org.example.Hello.metainfo.xml",
+                METAINFO.as_bytes(),
+            ),
+            (
+                "./usr/share/metainfo/broken.metainfo.xml",
+                b"<component><id>",
+            ),
+        ]);
+
+        let apps = find_apps(rpm.as_slice()).unwrap();
+        assert_eq!(apps.desktop_entries.len(), 1);
+        assert_eq!(apps.desktop_entries[0].id, "org.example.Hello.desktop");
+        assert_eq!(apps.components.len(), 1);
+        assert_eq!(apps.components[0].name.as_deref(), Some("Hello"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Architecture compatibility, including Debian's `Multi-Arch` rules for
+//! which packages can satisfy dependencies across architectures and be
+//! installed for several at once.
+//!
+//! See <https://wiki.ubuntu.com/MultiarchSpec>.
+use std::fmt;
+use std::str::FromStr;
+
+use crate::PkgError;
+
+/// The `Multi-Arch` field of a Debian package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MultiArch {
+    /// The package can only satisfy dependencies of its own architecture,
+    /// and only one architecture can be installed. This is the default.
+    #[default]
+    No,
+
+    /// The package can be installed for several architectures at once, and
+    /// only satisfies dependencies of its own architecture.
+    Same,
+
+    /// The package satisfies dependencies of any architecture, and only one
+    /// architecture can be installed.
+    Foreign,
+
+    /// The package satisfies dependencies of any architecture that ask for
+    /// it with `:any`, and otherwise only those of its own architecture.
+    Allowed,
+}
+
+impl MultiArch {
+    /// Get the value of a `Multi-Arch` field, which is [`MultiArch::No`] if
+    /// the field is absent.
+    pub fn from_field(field: Option<&str>) -> Result<Self, PkgError> {
+        field.map_or(Ok(MultiArch::No), str::parse)
+    }
+
+    /// The field value, e.g. `same`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MultiArch::No => "no",
+            MultiArch::Same => "same",
+            MultiArch::Foreign => "foreign",
+            MultiArch::Allowed => "allowed",
+        }
+    }
+}
+
+impl FromStr for MultiArch {
+    type Err = PkgError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "no" => Ok(MultiArch::No),
+            "same" => Ok(MultiArch::Same),
+            "foreign" => Ok(MultiArch::Foreign),
+            "allowed" => Ok(MultiArch::Allowed),
+            other => Err(PkgError::InvalidPackage(format!(
+                "invalid Multi-Arch value: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for MultiArch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Whether an architecture is the architecture-independent `all` (Debian)
+/// or `noarch` (RPM).
+pub fn is_arch_independent(arch: &str) -> bool {
+    arch == "all" || arch == "noarch"
+}
+
+/// Whether a package built for `package_arch` can be installed on a system
+/// of `system_arch`.
+pub fn is_compatible(package_arch: &str, system_arch: &str) -> bool {
+    package_arch == system_arch || is_arch_independent(package_arch)
+}
+
+/// Whether a package of `package_arch` with the given `Multi-Arch` value
+/// satisfies a dependency of a package of `depending_arch`. `any` is whether
+/// the dependency is qualified with `:any`, as in `python3:any`.
+///
+/// Architecture-independent packages, and the dependencies of them, match
+/// any architecture.
+pub fn satisfies(
+    package_arch: &str,
+    multi_arch: MultiArch,
+    depending_arch: &str,
+    any: bool,
+) -> bool {
+    match multi_arch {
+        MultiArch::Foreign => true,
+        MultiArch::Allowed if any => true,
+        _ => is_compatible(package_arch, depending_arch) || is_arch_independent(depending_arch),
+    }
+}
+
+/// Whether a package can be installed for both `arch` and `other_arch` at
+/// once, which needs `Multi-Arch: same` on architecture-dependent packages.
+pub fn co_installable(multi_arch: MultiArch, arch: &str, other_arch: &str) -> bool {
+    multi_arch == MultiArch::Same
+        && arch != other_arch
+        && !is_arch_independent(arch)
+        && !is_arch_independent(other_arch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_arch() {
+        assert_eq!(MultiArch::from_field(None).unwrap(), MultiArch::No);
+        assert_eq!(
+            MultiArch::from_field(Some("same")).unwrap(),
+            MultiArch::Same
+        );
+        assert_eq!(
+            "allowed".parse::<MultiArch>().unwrap().to_string(),
+            "allowed"
+        );
+        assert!("sometimes".parse::<MultiArch>().is_err());
+    }
+
+    #[test]
+    fn test_compatibility() {
+        assert!(is_compatible("amd64", "amd64"));
+        assert!(is_compatible("all", "arm64"));
+        assert!(is_compatible("noarch", "x86_64"));
+        assert!(!is_compatible("i386", "amd64"));
+
+        // A foreign package satisfies dependencies of any architecture.
+        assert!(satisfies("amd64", MultiArch::Foreign, "i386", false));
+        assert!(!satisfies("amd64", MultiArch::No, "i386", false));
+        assert!(satisfies("amd64", MultiArch::No, "amd64", false));
+        assert!(satisfies("all", MultiArch::No, "i386", false));
+        // An allowed package only does if asked for with `:any`.
+        assert!(satisfies("amd64", MultiArch::Allowed, "i386", true));
+        assert!(!satisfies("amd64", MultiArch::Allowed, "i386", false));
+        assert!(!satisfies("amd64", MultiArch::Same, "i386", true));
+
+        assert!(co_installable(MultiArch::Same, "amd64", "i386"));
+        assert!(!co_installable(MultiArch::Same, "amd64", "amd64"));
+        assert!(!co_installable(MultiArch::Same, "all", "i386"));
+        assert!(!co_installable(MultiArch::Foreign, "amd64", "i386"));
+    }
+}
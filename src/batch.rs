@@ -0,0 +1,263 @@
+//! Querying many packages at once, such as every package in a mirror
+//! directory.
+//!
+//! With the `batch` feature, [`from_urls_parallel`] uses the blocking client
+//! and waits until every package has been read. With the `async` feature,
+//! [`query_stream`] uses the async client and returns each result as soon
+//! as it's ready.
+#[cfg(feature = "batch")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "batch")]
+use std::sync::{mpsc, Arc};
+#[cfg(feature = "batch")]
+use std::thread;
+
+#[cfg(feature = "async")]
+use futures_util::stream::{Stream, StreamExt};
+
+use crate::metadata::PackageMetadata;
+#[cfg(any(test, feature = "batch"))]
+use crate::Phase;
+use crate::PkgError;
+
+/// Fetch and parse the packages at many URLs, with up to `concurrency`
+/// downloads at a time. Returns the metadata of each package, or why it
+/// couldn't be read, in the same order as `urls`.
+///
+/// A `concurrency` of zero is treated as one.
+///
+/// Uses a blocking client on a pool of threads, so if using this in an async
+/// environment, surround this with tokio::spawn_blocking.
+#[cfg(feature = "batch")]
+pub fn from_urls_parallel<I, S>(
+    urls: I,
+    concurrency: usize,
+) -> Vec<(String, Result<PackageMetadata, PkgError>)>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let urls: Vec<String> = urls.into_iter().map(Into::into).collect();
+    let results = map_parallel(urls.clone(), concurrency, |url| query(url));
+    urls.into_iter()
+        .zip(results)
+        .map(|(url, result)| (url, result.map_err(PkgError::from)))
+        .collect()
+}
+
+/// Fetch and parse the packages at a stream of URLs, such as those found by
+/// crawling a repository, with up to `concurrency` downloads at a time.
+/// Returns a stream of each URL with the metadata of its package, or why it
+/// couldn't be read, in the order they finish.
+///
+/// URLs are only taken from `urls` as downloads finish, so a slow consumer
+/// holds back the crawl rather than results building up. A `concurrency` of
+/// zero is treated as one.
+///
+/// Each package is read with [`from_url_async`](crate::fetch::from_url_async),
+/// so outside of a browser this must be polled within a tokio runtime.
+#[cfg(feature = "async")]
+pub fn query_stream<U, S>(
+    urls: U,
+    concurrency: usize,
+) -> impl Stream<Item = (S, Result<PackageMetadata, PkgError>)>
+where
+    U: Stream<Item = S>,
+    S: AsRef<str>,
+{
+    urls.map(|url| async move {
+        let result = crate::fetch::from_url_async(url.as_ref())
+            .await
+            .and_then(|package| package.to_metadata());
+        (url, result)
+    })
+    .buffer_unordered(concurrency.max(1))
+}
+
+/// Fetch and parse the package at a URL, on a worker thread.
+#[cfg(feature = "batch")]
+fn query(url: &str) -> Result<PackageMetadata, ThreadError> {
+    crate::from_url(url)
+        .and_then(|package| package.to_metadata())
+        .map_err(ThreadError::from)
+}
+
+/// A [`PkgError`] that can be passed between threads. Some of the errors
+/// from the package libraries can't, so only their messages are kept.
+#[cfg(feature = "batch")]
+#[derive(Debug)]
+enum ThreadError {
+    /// An error from the HTTP client.
+    Http(reqwest::Error),
+
+    /// An I/O error.
+    Io(std::io::Error),
+
+    /// An error with the context it happened in.
+    Context {
+        url: String,
+        phase: Phase,
+        offset: Option<u64>,
+        source: Box<ThreadError>,
+    },
+
+    /// The message of any other error.
+    Other(String),
+}
+
+#[cfg(feature = "batch")]
+impl From<PkgError> for ThreadError {
+    fn from(err: PkgError) -> Self {
+        match err {
+            PkgError::HTTPError(err) => ThreadError::Http(err),
+            PkgError::IoError(err) => ThreadError::Io(err),
+            PkgError::Context {
+                url,
+                phase,
+                offset,
+                source,
+            } => ThreadError::Context {
+                url,
+                phase,
+                offset,
+                source: Box::new(ThreadError::from(*source)),
+            },
+            err => ThreadError::Other(err.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "batch")]
+impl From<ThreadError> for PkgError {
+    fn from(err: ThreadError) -> Self {
+        match err {
+            ThreadError::Http(err) => PkgError::HTTPError(err),
+            ThreadError::Io(err) => PkgError::IoError(err),
+            ThreadError::Context {
+                url,
+                phase,
+                offset,
+                source,
+            } => PkgError::Context {
+                url,
+                phase,
+                offset,
+                source: Box::new(PkgError::from(*source)),
+            },
+            ThreadError::Other(message) => PkgError::QueryFailed(message),
+        }
+    }
+}
+
+/// Apply `f` to each item on up to `concurrency` threads, returning the
+/// results in the same order as the items.
+#[cfg(feature = "batch")]
+fn map_parallel<T, R, F>(items: Vec<T>, concurrency: usize, f: F) -> Vec<R>
+where
+    T: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&T) -> R + Send + Sync + 'static,
+{
+    let count = items.len();
+    let items = Arc::new(items);
+    let f = Arc::new(f);
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..concurrency.max(1).min(count))
+        .map(|_| {
+            let (items, f, next, sender) = (items.clone(), f.clone(), next.clone(), sender.clone());
+            thread::spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                match items.get(index) {
+                    // The receiver outlives the workers.
+                    Some(item) => sender.send((index, f(item))).unwrap(),
+                    None => break,
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut results: Vec<Option<R>> = (0..count).map(|_| None).collect();
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+    for worker in workers {
+        if let Err(panic) = worker.join() {
+            std::panic::resume_unwind(panic);
+        }
+    }
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "batch")]
+    #[test]
+    fn test_map_parallel() {
+        use std::time::Duration;
+
+        let active = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (active_in, most_in) = (active.clone(), most.clone());
+
+        let results = map_parallel((0..20).collect(), 4, move |n: &u64| {
+            let now = active_in.fetch_add(1, Ordering::SeqCst) + 1;
+            let _ = most_in.fetch_max(now, Ordering::SeqCst);
+            // Finish out of order.
+            thread::sleep(Duration::from_millis(20 - n));
+            let _ = active_in.fetch_sub(1, Ordering::SeqCst);
+            n * 2
+        });
+        assert_eq!(results, (0..20).map(|n| n * 2).collect::<Vec<_>>());
+        assert!(most.load(Ordering::SeqCst) <= 4);
+        assert_eq!(active.load(Ordering::SeqCst), 0);
+
+        assert_eq!(map_parallel(vec![1], 0, |n: &i32| n + 1), vec![2]);
+        assert!(map_parallel(Vec::<i32>::new(), 4, |n: &i32| n + 1).is_empty());
+    }
+
+    #[cfg(feature = "batch")]
+    #[test]
+    fn test_from_urls_parallel() {
+        let results = from_urls_parallel(vec!["not a url", "also not a url"], 2);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "not a url");
+        assert_eq!(results[1].0, "also not a url");
+        assert!(results
+            .iter()
+            .all(|(_, result)| matches!(result, Err(err) if err.phase() == Some(Phase::Fetch))));
+
+        let err = PkgError::from(ThreadError::from(PkgError::InferError));
+        assert!(
+            matches!(err, PkgError::QueryFailed(message) if message == "Failed to infer package type")
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_query_stream() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let urls = futures_util::stream::iter(vec!["not a url", "also not a url", "nor this"]);
+
+        // The stream can be moved to another task, e.g. with tokio::spawn.
+        fn assert_send<T: Send>(value: T) -> T {
+            value
+        }
+        let stream = assert_send(query_stream(urls, 2));
+
+        let mut results: Vec<_> = runtime.block_on(stream.collect());
+        results.sort_by_key(|(url, _)| *url);
+        let urls: Vec<_> = results.iter().map(|(url, _)| *url).collect();
+        assert_eq!(urls, ["also not a url", "nor this", "not a url"]);
+        assert!(results
+            .iter()
+            .all(|(_, result)| matches!(result, Err(err) if err.phase() == Some(Phase::Fetch))));
+    }
+}
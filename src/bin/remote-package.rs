@@ -0,0 +1,600 @@
+//! Command line access to remote package metadata.
+//!
+//! ```text
+//! remote-package info [--json] <url>
+//! remote-package files <url>
+//! remote-package extract <url> [path] [-o dir]
+//! remote-package deps [--json] <url>
+//! remote-package verify [--json] [--sha256 hex] [--keyring path] <url>
+//! remote-package compare [--json] <url_a> <url_b>
+//! ```
+//!
+//! Built with the `cli` feature, e.g. `cargo install remote-package
+//! --features cli`.
+use std::path::{Path, PathBuf};
+use std::process;
+
+use remote_package::metadata::PackageMetadata;
+use remote_package::payload::{self, ExtractOptions};
+use remote_package::rpm::RpmRemotePackage;
+use remote_package::verify::{Keyring, VerifyMode};
+use remote_package::{from_url, from_url_verified, Digest, PkgError, RemotePackage};
+use serde_json::json;
+
+/// How to run the program.
+const USAGE: &str = "Usage:
+  remote-package info [--json] <url>
+  remote-package files <url>
+  remote-package extract <url> [path] [-o dir]
+  remote-package deps [--json] <url>
+  remote-package verify [--json] [--sha256 hex] [--keyring path] <url>
+  remote-package compare [--json] <url_a> <url_b>";
+
+/// A command given on the command line.
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    /// Print the metadata of the package at a URL.
+    Info {
+        /// The URL of the package.
+        url: String,
+
+        /// Whether to print the metadata as JSON.
+        json: bool,
+    },
+
+    /// List the entries in the payload of the package at a URL.
+    Files {
+        /// The URL of the package.
+        url: String,
+    },
+
+    /// Unpack the package at a URL, or a single file from it.
+    Extract {
+        /// The URL of the package.
+        url: String,
+
+        /// The installed path of the file to unpack, or `None` for all of
+        /// them.
+        path: Option<String>,
+
+        /// The directory to unpack into.
+        dir: PathBuf,
+    },
+
+    /// Print the dependencies of the package at a URL.
+    Deps {
+        /// The URL of the package.
+        url: String,
+
+        /// Whether to print the dependencies as JSON.
+        json: bool,
+    },
+
+    /// Check the checksum or signature of the package at a URL.
+    Verify {
+        /// The URL of the package.
+        url: String,
+
+        /// The expected SHA-256 of the package, as hex.
+        sha256: Option<String>,
+
+        /// A file or directory of public keys to check the signature with.
+        keyring: Option<PathBuf>,
+
+        /// Whether to print the results as JSON.
+        json: bool,
+    },
+
+    /// Print the differences between two packages.
+    Compare {
+        /// The URL of the old package.
+        url_a: String,
+
+        /// The URL of the new package.
+        url_b: String,
+
+        /// Whether to print the differences as JSON.
+        json: bool,
+    },
+
+    /// Print the usage.
+    Help,
+}
+
+/// Parse the command line arguments, without the program name.
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Err("no command given".to_string()),
+    };
+
+    let mut positional = Vec::new();
+    let mut json = false;
+    let mut dir = None;
+    let mut sha256 = None;
+    let mut keyring = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{} needs a value", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--json" if command != "files" && command != "extract" => json = true,
+            "-o" | "--output" if command == "extract" => dir = Some(value(&arg)?),
+            "--sha256" if command == "verify" => sha256 = Some(value(&arg)?),
+            "--keyring" if command == "verify" => keyring = Some(PathBuf::from(value(&arg)?)),
+            _ if arg.starts_with('-') => return Err(format!("unknown option: {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    // The number of positional arguments each command takes, at least and
+    // at most.
+    let (min, max) = match command.as_str() {
+        "info" | "files" | "deps" | "verify" => (1, 1),
+        "extract" => (1, 2),
+        "compare" => (2, 2),
+        "-h" | "--help" | "help" => return Ok(Command::Help),
+        _ => return Err(format!("unknown command: {}", command)),
+    };
+    if positional.len() < min {
+        return Err("missing URL".to_string());
+    }
+    if positional.len() > max {
+        return Err(format!("unexpected argument: {}", positional[max]));
+    }
+
+    let mut positional = positional.into_iter();
+    let url = positional.next().unwrap_or_default();
+    Ok(match command.as_str() {
+        "info" => Command::Info { url, json },
+        "files" => Command::Files { url },
+        "extract" => Command::Extract {
+            url,
+            path: positional.next(),
+            dir: PathBuf::from(dir.unwrap_or_else(|| ".".to_string())),
+        },
+        "deps" => Command::Deps { url, json },
+        "verify" => {
+            if sha256.is_none() && keyring.is_none() {
+                return Err("verify needs --sha256 or --keyring".to_string());
+            }
+            Command::Verify {
+                url,
+                sha256,
+                keyring,
+                json,
+            }
+        }
+        _ => Command::Compare {
+            url_a: url,
+            url_b: positional.next().unwrap_or_default(),
+            json,
+        },
+    })
+}
+
+/// Print the metadata of a package.
+fn info(package: &dyn RemotePackage, json: bool) -> Result<(), PkgError> {
+    if json {
+        println!("{}", package.to_metadata()?.to_json_pretty()?);
+        return Ok(());
+    }
+
+    println!("name: {}", package.package_name()?);
+    println!("version: {}", package.package_version()?);
+    if let Some(iteration) = package.package_iteration() {
+        println!("iteration: {}", iteration);
+    }
+    println!("arch: {}", package.package_arch()?);
+    Ok(())
+}
+
+/// List the entries in the payload of a package, one per line, with their
+/// mode and size.
+fn files(url: &str) -> Result<(), PkgError> {
+    for file in payload::files_from_url(url)? {
+        match &file.link_target {
+            Some(target) => println!(
+                "{:07o} {:>10} {} -> {}",
+                file.mode, file.size, file.path, target
+            ),
+            None => println!("{:07o} {:>10} {}", file.mode, file.size, file.path),
+        }
+    }
+    Ok(())
+}
+
+/// Unpack a package, or a single file from it, into `dir`. A single file is
+/// written under its file name.
+fn extract(url: &str, path: Option<&str>, dir: &Path) -> Result<(), PkgError> {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let written = payload::extract_to_from_url(url, dir, &ExtractOptions::default())?;
+            println!("{} entries written to {}", written, dir.display());
+            return Ok(());
+        }
+    };
+
+    let name = path.rsplit('/').next().filter(|name| !name.is_empty());
+    let target = dir.join(name.ok_or_else(|| PkgError::FileNotFound(path.to_owned()))?);
+    let contents = payload::extract_file_from_url(url, path)?;
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&target, &contents)?;
+    println!("{} bytes written to {}", contents.len(), target.display());
+    Ok(())
+}
+
+/// Print the names of the dependencies of a package, one per line.
+fn deps(package: &dyn RemotePackage, json: bool) -> Result<(), PkgError> {
+    let dependencies = package.package_dependencies();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&dependencies)?);
+    } else {
+        for dependency in dependencies {
+            println!("{}", dependency);
+        }
+    }
+    Ok(())
+}
+
+/// Check a package against a checksum and a keyring. Returns whether every
+/// check passed.
+///
+/// Signatures can only be checked on RPM packages.
+fn verify(
+    url: &str,
+    sha256: Option<&str>,
+    keyring: Option<&Path>,
+    json: bool,
+) -> Result<bool, PkgError> {
+    let mut passed = true;
+    let mut results = serde_json::Map::new();
+
+    if let Some(sha256) = sha256 {
+        let result = match from_url_verified(url, Digest::Sha256(sha256.to_owned())) {
+            Ok(_) => json!({ "ok": true }),
+            Err(PkgError::ChecksumMismatch {
+                expected, actual, ..
+            }) => json!({ "ok": false, "expected": expected, "actual": actual }),
+            Err(err) => return Err(err),
+        };
+        let ok = result["ok"] == true;
+        passed &= ok;
+        if !json && ok {
+            println!("sha256: ok");
+        } else if !json {
+            println!(
+                "sha256: mismatch: expected {}, got {}",
+                result["expected"].as_str().unwrap_or_default(),
+                result["actual"].as_str().unwrap_or_default()
+            );
+        }
+        let _ = results.insert("sha256".to_string(), result);
+    }
+
+    if let Some(path) = keyring {
+        let keyring = if path.is_dir() {
+            Keyring::from_dir(path)?
+        } else {
+            Keyring::from_file(path)?
+        };
+        let package =
+            RpmRemotePackage::new_from_url_with_keyring(url, &keyring, VerifyMode::Report)?;
+        let status = package
+            .verification_status()
+            .map_or_else(|| "unsigned".to_string(), ToString::to_string);
+        let trusted = package
+            .verification_status()
+            .is_some_and(|status| status.is_trusted());
+        passed &= trusted;
+        if !json {
+            println!("signature: {}", status);
+        }
+        let _ = results.insert(
+            "signature".to_string(),
+            json!({ "ok": trusted, "status": status }),
+        );
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    Ok(passed)
+}
+
+/// The differences between two packages.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Comparison {
+    /// The metadata fields that differ, with their old and new values.
+    fields: Vec<(&'static str, Option<String>, Option<String>)>,
+
+    /// The dependencies only the new package has.
+    added_dependencies: Vec<String>,
+
+    /// The dependencies only the old package has.
+    removed_dependencies: Vec<String>,
+
+    /// The files only the new package has.
+    added_files: Vec<String>,
+
+    /// The files only the old package has.
+    removed_files: Vec<String>,
+}
+
+impl Comparison {
+    /// Compare the metadata and the file paths of two packages.
+    fn new(
+        a: &PackageMetadata,
+        b: &PackageMetadata,
+        files_a: &[String],
+        files_b: &[String],
+    ) -> Self {
+        let only_in = |a: &[String], b: &[String]| -> Vec<String> {
+            a.iter().filter(|item| !b.contains(item)).cloned().collect()
+        };
+
+        let fields = vec![
+            ("type", Some(&a.package_type), Some(&b.package_type)),
+            ("name", Some(&a.name), Some(&b.name)),
+            ("version", Some(&a.version), Some(&b.version)),
+            ("iteration", a.iteration.as_ref(), b.iteration.as_ref()),
+            ("arch", Some(&a.arch), Some(&b.arch)),
+            ("license", a.license.as_ref(), b.license.as_ref()),
+        ];
+        Self {
+            fields: fields
+                .into_iter()
+                .filter(|(_, a, b)| a != b)
+                .map(|(field, a, b)| (field, a.cloned(), b.cloned()))
+                .collect(),
+            added_dependencies: only_in(&b.dependencies, &a.dependencies),
+            removed_dependencies: only_in(&a.dependencies, &b.dependencies),
+            added_files: only_in(files_b, files_a),
+            removed_files: only_in(files_a, files_b),
+        }
+    }
+
+    /// The differences as JSON.
+    fn to_json(&self) -> serde_json::Value {
+        let fields: serde_json::Map<_, _> = self
+            .fields
+            .iter()
+            .map(|(field, a, b)| (field.to_string(), json!([a, b])))
+            .collect();
+        json!({
+            "fields": fields,
+            "dependencies": {
+                "added": self.added_dependencies,
+                "removed": self.removed_dependencies,
+            },
+            "files": {
+                "added": self.added_files,
+                "removed": self.removed_files,
+            },
+        })
+    }
+
+    /// The differences as lines of text, with `+` and `-` for what was added
+    /// and removed.
+    fn to_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(field, a, b)| {
+                let value =
+                    |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+                format!("{}: {} -> {}", field, value(a), value(b))
+            })
+            .collect();
+        let changes = [
+            ("+ dependency", &self.added_dependencies),
+            ("- dependency", &self.removed_dependencies),
+            ("+ file", &self.added_files),
+            ("- file", &self.removed_files),
+        ];
+        for (prefix, items) in &changes {
+            lines.extend(items.iter().map(|item| format!("{} {}", prefix, item)));
+        }
+        lines
+    }
+}
+
+/// Print the differences between two packages. The file lists come from the
+/// payloads, as Debian packages don't list their files in their metadata.
+fn compare(url_a: &str, url_b: &str, json: bool) -> Result<(), PkgError> {
+    let paths = |url: &str| -> Result<Vec<String>, PkgError> {
+        Ok(payload::files_from_url(url)?
+            .into_iter()
+            .map(|file| file.path)
+            .collect())
+    };
+
+    let comparison = Comparison::new(
+        &from_url(url_a)?.to_metadata()?,
+        &from_url(url_b)?.to_metadata()?,
+        &paths(url_a)?,
+        &paths(url_b)?,
+    );
+    if json {
+        println!("{}", serde_json::to_string_pretty(&comparison.to_json())?);
+    } else {
+        let lines = comparison.to_lines();
+        if lines.is_empty() {
+            println!("no differences");
+        }
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+/// Run a command. Returns whether it succeeded, for commands that check
+/// something.
+fn run(command: Command) -> Result<bool, PkgError> {
+    match command {
+        Command::Info { url, json } => info(from_url(&url)?.as_ref(), json)?,
+        Command::Files { url } => files(&url)?,
+        Command::Extract { url, path, dir } => extract(&url, path.as_deref(), &dir)?,
+        Command::Deps { url, json } => deps(from_url(&url)?.as_ref(), json)?,
+        Command::Verify {
+            url,
+            sha256,
+            keyring,
+            json,
+        } => return verify(&url, sha256.as_deref(), keyring.as_deref(), json),
+        Command::Compare { url_a, url_b, json } => compare(&url_a, &url_b, json)?,
+        Command::Help => println!("{}", USAGE),
+    }
+    Ok(true)
+}
+
+fn main() {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("remote-package: {}\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    match run(command) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("remote-package: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse arguments given as string slices.
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let url = "https://example.com/hello.deb";
+        assert_eq!(
+            parse(&["info", url]),
+            Ok(Command::Info {
+                url: url.to_string(),
+                json: false
+            })
+        );
+        assert_eq!(
+            parse(&["info", "--json", url]),
+            Ok(Command::Info {
+                url: url.to_string(),
+                json: true
+            })
+        );
+        assert_eq!(
+            parse(&["files", url]),
+            Ok(Command::Files {
+                url: url.to_string()
+            })
+        );
+        assert_eq!(
+            parse(&["extract", url]),
+            Ok(Command::Extract {
+                url: url.to_string(),
+                path: None,
+                dir: PathBuf::from(".")
+            })
+        );
+        assert_eq!(
+            parse(&["extract", url, "/usr/bin/hello", "-o", "out"]),
+            Ok(Command::Extract {
+                url: url.to_string(),
+                path: Some("/usr/bin/hello".to_string()),
+                dir: PathBuf::from("out")
+            })
+        );
+        assert_eq!(
+            parse(&["deps", "--json", url]),
+            Ok(Command::Deps {
+                url: url.to_string(),
+                json: true
+            })
+        );
+        assert_eq!(
+            parse(&["verify", url, "--sha256", "ab12", "--keyring", "keys"]),
+            Ok(Command::Verify {
+                url: url.to_string(),
+                sha256: Some("ab12".to_string()),
+                keyring: Some(PathBuf::from("keys")),
+                json: false
+            })
+        );
+        assert_eq!(
+            parse(&["compare", url, "b.deb"]),
+            Ok(Command::Compare {
+                url_a: url.to_string(),
+                url_b: "b.deb".to_string(),
+                json: false
+            })
+        );
+        assert_eq!(parse(&["--help"]), Ok(Command::Help));
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["info"]).is_err());
+        assert!(parse(&["info", url, url]).is_err());
+        assert!(parse(&["info", "--yaml", url]).is_err());
+        assert!(parse(&["list", url]).is_err());
+        assert!(parse(&["files", "--json", url]).is_err());
+        assert!(parse(&["extract", url, "-o"]).is_err());
+        assert!(parse(&["verify", url]).is_err());
+        assert!(parse(&["compare", url]).is_err());
+    }
+
+    #[test]
+    fn test_comparison() {
+        let metadata = |version: &str, dependencies: &[&str]| PackageMetadata {
+            package_type: "deb".to_string(),
+            name: "hello".to_string(),
+            version: version.to_string(),
+            iteration: None,
+            arch: "amd64".to_string(),
+            license: None,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            files: vec![],
+            config_files: vec![],
+            scripts: vec![],
+            triggers: vec![],
+        };
+        let files =
+            |files: &[&str]| -> Vec<String> { files.iter().map(|f| f.to_string()).collect() };
+
+        let comparison = Comparison::new(
+            &metadata("1.0", &["libc6", "zlib1g"]),
+            &metadata("1.1", &["libc6", "libssl3"]),
+            &files(&["/usr/bin/hello", "/usr/share/doc/hello"]),
+            &files(&["/usr/bin/hello", "/usr/bin/hello-helper"]),
+        );
+        assert_eq!(
+            comparison.to_lines(),
+            [
+                "version: 1.0 -> 1.1",
+                "+ dependency libssl3",
+                "- dependency zlib1g",
+                "+ file /usr/bin/hello-helper",
+                "- file /usr/share/doc/hello",
+            ]
+        );
+        assert_eq!(
+            comparison.to_json()["fields"]["version"],
+            json!(["1.0", "1.1"])
+        );
+
+        let same = metadata("1.0", &[]);
+        assert!(Comparison::new(&same, &same, &[], &[])
+            .to_lines()
+            .is_empty());
+    }
+}
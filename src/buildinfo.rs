@@ -0,0 +1,296 @@
+//! Parsing of Debian `.buildinfo` files, which record the environment a
+//! package was built in so that the build can be reproduced.
+//!
+//! See <https://wiki.debian.org/ReproducibleBuilds/BuildinfoFiles>. The
+//! equivalent RPM metadata is in the package header; see
+//! [`RpmRemotePackage::build_info`](crate::rpm::RpmRemotePackage::build_info).
+use std::io::{self, Read};
+
+use crate::checksum::{parse_checked, Algorithm};
+use crate::deb822::{strip_signature, Deb822Reader, Paragraph};
+use crate::PkgError;
+
+#[cfg(feature = "verify")]
+use crate::verify::{Keyring, TrustPolicy, VerificationStatus};
+
+/// The checksum fields of a `.buildinfo` file, strongest first.
+const CHECKSUM_FIELDS: [(&str, Algorithm); 3] = [
+    ("Checksums-Sha256", Algorithm::Sha256),
+    ("Checksums-Sha1", Algorithm::Sha1),
+    ("Checksums-Md5", Algorithm::Md5),
+];
+
+/// A file produced by the build, as listed in a `.buildinfo` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildinfoFile {
+    /// The algorithm of the checksum.
+    pub algorithm: Algorithm,
+
+    /// The hex checksum of the file.
+    pub checksum: String,
+
+    /// The size of the file in bytes.
+    pub size: u64,
+
+    /// The file name, e.g. `hello_2.10-3_amd64.deb`.
+    pub name: String,
+}
+
+/// A parsed `.buildinfo` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buildinfo {
+    paragraph: Paragraph,
+    files: Vec<BuildinfoFile>,
+}
+
+impl Buildinfo {
+    /// Parse a `.buildinfo` file. These are usually signed; the signature is
+    /// stripped but not checked.
+    pub fn parse(text: &str) -> Result<Self, PkgError> {
+        let text = strip_signature(text);
+        let paragraph = Deb822Reader::new(text.as_bytes())
+            .next()
+            .ok_or_else(|| PkgError::InvalidPackage("empty .buildinfo file".to_string()))??;
+
+        let invalid =
+            |line: &str| PkgError::InvalidPackage(format!("invalid .buildinfo entry: {}", line));
+        let mut files = Vec::new();
+        for (field, algorithm) in &CHECKSUM_FIELDS {
+            for line in paragraph.get(field).unwrap_or_default().lines() {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                match fields.as_slice() {
+                    [] => continue,
+                    [checksum, size, name] => files.push(BuildinfoFile {
+                        algorithm: *algorithm,
+                        checksum: (*checksum).to_owned(),
+                        size: size.parse().map_err(|_| invalid(line))?,
+                        name: (*name).to_owned(),
+                    }),
+                    _ => return Err(invalid(line)),
+                }
+            }
+        }
+
+        Ok(Self { paragraph, files })
+    }
+
+    /// Parse a signed `.buildinfo` file, checking its signatures against a
+    /// keyring. The status is returned along with the file unless `policy`
+    /// rejects it.
+    #[cfg(feature = "verify")]
+    pub fn parse_verified<P: Into<TrustPolicy>>(
+        text: &str,
+        keyring: &Keyring,
+        policy: P,
+    ) -> Result<(Self, VerificationStatus), PkgError> {
+        let (text, statuses) = keyring.verify_cleartext_all(text)?;
+        let status = policy.into().apply(statuses)?;
+        Ok((Self::parse(&text)?, status))
+    }
+
+    /// Get the source package name.
+    pub fn source(&self) -> Option<&str> {
+        self.paragraph.get("Source")
+    }
+
+    /// Get the version of the source package.
+    pub fn version(&self) -> Option<&str> {
+        self.paragraph.get("Version")
+    }
+
+    /// Get the architectures that were built, e.g. `amd64 source`.
+    pub fn architecture(&self) -> Option<&str> {
+        self.paragraph.get("Architecture")
+    }
+
+    /// Get the names of the binary packages that were built.
+    pub fn binaries(&self) -> Vec<&str> {
+        self.paragraph
+            .get("Binary")
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect()
+    }
+
+    /// Get the distribution the build was made on, e.g. `debian`.
+    pub fn build_origin(&self) -> Option<&str> {
+        self.paragraph.get("Build-Origin")
+    }
+
+    /// Get the architecture of the build machine.
+    pub fn build_architecture(&self) -> Option<&str> {
+        self.paragraph.get("Build-Architecture")
+    }
+
+    /// Get when the build finished, in RFC 2822 format.
+    pub fn build_date(&self) -> Option<&str> {
+        self.paragraph.get("Build-Date")
+    }
+
+    /// Get the directory the build ran in, which can affect the output.
+    pub fn build_path(&self) -> Option<&str> {
+        self.paragraph.get("Build-Path")
+    }
+
+    /// Get the files produced by the build, with every listed checksum.
+    pub fn checksums(&self) -> &[BuildinfoFile] {
+        &self.files
+    }
+
+    /// Get the packages installed during the build as `(name, version)`
+    /// pairs, e.g. `("gcc-12", "12.2.0-14")`.
+    pub fn installed_build_depends(&self) -> Vec<(&str, &str)> {
+        self.paragraph
+            .get("Installed-Build-Depends")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|dep| {
+                let (name, version) = dep.trim().split_once(' ')?;
+                let version = version.trim().strip_prefix("(=")?.strip_suffix(')')?;
+                Some((name, version.trim()))
+            })
+            .collect()
+    }
+
+    /// Get the environment variables set during the build, with their
+    /// quotes removed.
+    pub fn environment(&self) -> Vec<(&str, &str)> {
+        self.paragraph
+            .get("Environment")
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (name, value) = line.trim().split_once('=')?;
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+                Some((name, value))
+            })
+            .collect()
+    }
+
+    /// Get the value of any field in the file. The field name is case
+    /// insensitive.
+    pub fn get(&self, field_name: &str) -> Option<&str> {
+        self.paragraph.get(field_name)
+    }
+
+    /// Check a file produced by the build, such as a rebuilt `.deb`, against
+    /// its size and strongest listed checksum. Files that aren't listed are
+    /// rejected.
+    pub fn check<R: Read>(&self, name: &str, reader: R) -> Result<(), PkgError> {
+        let file = self
+            .files
+            .iter()
+            .find(|file| file.name == name)
+            .ok_or_else(|| PkgError::RepoDataNotFound(format!("{} is not in .buildinfo", name)))?;
+
+        parse_checked(
+            reader,
+            Some(file.size),
+            Some((file.algorithm, &file.checksum)),
+            |reader| Ok(io::copy(reader, &mut io::sink()).map(|_| ())?),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILDINFO: &str = "-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA256
+
+Format: 1.0
+Source: hello
+Binary: hello hello-dbgsym
+Architecture: amd64
+Version: 2.10-3
+Checksums-Md5:
+ 5d41402abc4b2a76b9719d911017c592 5 hello_2.10-3_amd64.deb
+Checksums-Sha256:
+ 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824 5 hello_2.10-3_amd64.deb
+Build-Origin: debian
+Build-Architecture: amd64
+Build-Date: Sun, 26 Feb 2023 10:00:00 +0000
+Build-Path: /build/reproducible-path/hello-2.10
+Installed-Build-Depends:
+ autoconf (= 2.71-3),
+ gcc-12 (= 12.2.0-14),
+ libc6 (= 2.36-8)
+Environment:
+ DEB_BUILD_OPTIONS=\"parallel=4\"
+ LANG=\"C.UTF-8\"
+ SOURCE_DATE_EPOCH=\"1677405600\"
+-----BEGIN PGP SIGNATURE-----
+
+iQIzBAEBCAAdFiEE
+-----END PGP SIGNATURE-----
+";
+
+    #[test]
+    fn test_parse() {
+        let buildinfo = Buildinfo::parse(BUILDINFO).unwrap();
+        assert_eq!(buildinfo.source(), Some("hello"));
+        assert_eq!(buildinfo.version(), Some("2.10-3"));
+        assert_eq!(buildinfo.binaries(), vec!["hello", "hello-dbgsym"]);
+        assert_eq!(buildinfo.build_origin(), Some("debian"));
+        assert_eq!(
+            buildinfo.build_path(),
+            Some("/build/reproducible-path/hello-2.10")
+        );
+        assert_eq!(
+            buildinfo.installed_build_depends(),
+            vec![
+                ("autoconf", "2.71-3"),
+                ("gcc-12", "12.2.0-14"),
+                ("libc6", "2.36-8")
+            ]
+        );
+        assert_eq!(
+            buildinfo.environment(),
+            vec![
+                ("DEB_BUILD_OPTIONS", "parallel=4"),
+                ("LANG", "C.UTF-8"),
+                ("SOURCE_DATE_EPOCH", "1677405600")
+            ]
+        );
+
+        let checksums = buildinfo.checksums();
+        assert_eq!(checksums.len(), 2);
+        assert_eq!(checksums[0].algorithm, Algorithm::Sha256);
+        assert_eq!(checksums[0].size, 5);
+        assert_eq!(checksums[1].algorithm, Algorithm::Md5);
+
+        // Unsigned files are read as they are.
+        let unsigned = strip_signature(BUILDINFO);
+        assert!(unsigned.starts_with("Format: 1.0\n"));
+        assert!(unsigned.ends_with("SOURCE_DATE_EPOCH=\"1677405600\"\n"));
+        assert_eq!(Buildinfo::parse(&unsigned).unwrap(), buildinfo);
+        assert!(Buildinfo::parse("").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        let buildinfo = Buildinfo::parse(BUILDINFO).unwrap();
+        buildinfo
+            .check("hello_2.10-3_amd64.deb", &b"hello"[..])
+            .unwrap();
+        assert!(matches!(
+            buildinfo.check("hello_2.10-3_amd64.deb", &b"jello"[..]),
+            Err(PkgError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            buildinfo.check("hello_2.10-3_amd64.deb", &b"hello!"[..]),
+            Err(PkgError::SizeMismatch {
+                expected: 5,
+                actual: 6
+            })
+        ));
+        assert!(matches!(
+            buildinfo.check("other.deb", &b"hello"[..]),
+            Err(PkgError::RepoDataNotFound(_))
+        ));
+    }
+}
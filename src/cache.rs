@@ -0,0 +1,550 @@
+//! Caches of package metadata, so that packages that haven't changed aren't
+//! downloaded again.
+//!
+//! [`MemoryCache`] keeps the most recently used packages in memory, for
+//! repeated queries within a process. [`MetadataCache`] persists them on
+//! disk between runs.
+//!
+//! Each entry on disk records the [`PackageMetadata`] of a URL along with the
+//! validators the server sent for it: the `ETag`, `Last-Modified` and
+//! `Content-Length`. While an entry is fresh it's returned without any
+//! request. Once it's older than the maximum age, the package is requested
+//! again with `If-None-Match` and `If-Modified-Since`, and the cached
+//! metadata is kept if the server reports it hasn't changed, or sends the
+//! same validators again.
+//!
+//! [`WatchedPackage`] uses the same checks to follow a single URL, such as
+//! one that always points at the latest build, and report when it changes.
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, ETAG, LAST_MODIFIED};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::checksum::Algorithm;
+use crate::metadata::PackageMetadata;
+use crate::PkgError;
+
+/// How long an entry is used without checking the server, by default.
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A cache of package metadata in a directory on disk.
+#[derive(Debug, Clone)]
+pub struct MetadataCache {
+    dir: PathBuf,
+    max_age: Duration,
+    force_refresh: bool,
+}
+
+/// The validators a server sent with a package.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_length: Option<u64>,
+}
+
+impl Validators {
+    /// Read the validators from the headers of a response.
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            content_length: header(CONTENT_LENGTH).and_then(|length| length.parse().ok()),
+        }
+    }
+
+    /// Whether a response with these validators is the same package as one
+    /// with `other`. A matching length alone isn't enough to tell.
+    fn matches(&self, other: &Validators) -> bool {
+        (self.etag.is_some() || self.last_modified.is_some()) && self == other
+    }
+}
+
+/// An entry in the cache, stored as JSON.
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    validators: Validators,
+    /// When the entry was last checked, in seconds since the Unix epoch.
+    checked: u64,
+    metadata: PackageMetadata,
+}
+
+impl MetadataCache {
+    /// Use `dir` for the cache, creating it when an entry is first stored.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            max_age: DEFAULT_MAX_AGE,
+            force_refresh: false,
+        }
+    }
+
+    /// Use entries without checking the server for up to `max_age` after
+    /// they were last checked. Defaults to a day.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Ignore existing entries, always downloading and parsing the package
+    /// and replacing its entry.
+    pub fn with_force_refresh(mut self, force_refresh: bool) -> Self {
+        self.force_refresh = force_refresh;
+        self
+    }
+
+    /// The directory the cache is stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the metadata of the package at a URL, from the cache if possible.
+    ///
+    /// Unreadable entries are treated as missing, and replaced.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn get(&self, url: &str) -> Result<PackageMetadata, PkgError> {
+        let entry = if self.force_refresh {
+            None
+        } else {
+            self.load(url)
+        };
+        if let Some(entry) = &entry {
+            if now().saturating_sub(entry.checked) < self.max_age.as_secs() {
+                return Ok(entry.metadata.clone());
+            }
+        }
+
+        let mut request = crate::http::client().get(url);
+        if let Some(entry) = &entry {
+            if let Some(etag) = &entry.validators.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.validators.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = crate::http::send(request)?;
+
+        if let Some(mut entry) = entry {
+            let validators = Validators::from_headers(response.headers());
+            if response.status() == reqwest::StatusCode::NOT_MODIFIED
+                || (response.status().is_success() && validators.matches(&entry.validators))
+            {
+                // The unread body is dropped rather than downloaded.
+                drop(response);
+                entry.checked = now();
+                self.store(&entry)?;
+                return Ok(entry.metadata);
+            }
+        }
+
+        let response = response.error_for_status()?;
+        let validators = Validators::from_headers(response.headers());
+        let body = crate::http::Transfer::from_response(response)?;
+        let metadata = crate::from_read(body)?.to_metadata()?;
+        let entry = Entry {
+            url: url.to_owned(),
+            validators,
+            checked: now(),
+            metadata,
+        };
+        self.store(&entry)?;
+        Ok(entry.metadata)
+    }
+
+    /// Remove the entry for a URL, if there is one.
+    pub fn remove(&self, url: &str) -> Result<(), PkgError> {
+        match fs::remove_file(self.path(url)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The path of the entry for a URL, named by the SHA-256 of the URL.
+    fn path(&self, url: &str) -> PathBuf {
+        let digest = Sha256::digest(url.as_bytes());
+        let name: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Read the entry for a URL.
+    fn load(&self, url: &str) -> Option<Entry> {
+        let json = fs::read_to_string(self.path(url)).ok()?;
+        serde_json::from_str::<Entry>(&json)
+            .ok()
+            .filter(|entry| entry.url == url)
+    }
+
+    /// Write an entry, replacing any existing one in a single rename so that
+    /// readers never see half of it.
+    fn store(&self, entry: &Entry) -> Result<(), PkgError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&entry.url);
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&partial, serde_json::to_vec(entry)?)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+}
+
+/// An in-memory cache of the metadata of the most recently used packages.
+///
+/// It can be shared between threads. Failed queries aren't cached.
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    ttl: Option<Duration>,
+    state: Mutex<MemoryState>,
+}
+
+/// The entries of a [`MemoryCache`], and a counter to order their use.
+#[derive(Debug, Default)]
+struct MemoryState {
+    entries: HashMap<String, MemoryEntry>,
+    clock: u64,
+}
+
+/// An entry in a [`MemoryCache`].
+#[derive(Debug)]
+struct MemoryEntry {
+    metadata: PackageMetadata,
+    stored: Instant,
+    last_used: u64,
+}
+
+impl MemoryCache {
+    /// Keep up to `capacity` packages, dropping the least recently used when
+    /// it's full. A capacity of zero caches nothing.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ttl: None,
+            state: Mutex::new(MemoryState::default()),
+        }
+    }
+
+    /// Query packages again once they've been cached for `ttl`. By default
+    /// entries are kept until they're dropped for space.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The number of packages cached.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Whether no packages are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop every cached package.
+    pub fn clear(&self) {
+        self.lock().entries.clear();
+    }
+
+    /// Get the metadata of the package at a URL, from the cache if possible.
+    ///
+    /// The lock isn't held while fetching, so concurrent queries for the
+    /// same uncached URL each fetch it.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn get(&self, url: &str) -> Result<PackageMetadata, PkgError> {
+        if let Some(metadata) = self.lookup(url) {
+            return Ok(metadata);
+        }
+        let metadata = crate::from_url(url)?.to_metadata()?;
+        self.insert(url, metadata.clone());
+        Ok(metadata)
+    }
+
+    /// Get a cached package, if it hasn't expired.
+    fn lookup(&self, url: &str) -> Option<PackageMetadata> {
+        let mut state = self.lock();
+        state.clock += 1;
+        let clock = state.clock;
+        let ttl = self.ttl;
+        let entry = state.entries.get_mut(url)?;
+        if ttl.is_some_and(|ttl| entry.stored.elapsed() >= ttl) {
+            let _ = state.entries.remove(url);
+            return None;
+        }
+        entry.last_used = clock;
+        Some(entry.metadata.clone())
+    }
+
+    /// Cache a package, dropping the least recently used if there's no room.
+    fn insert(&self, url: &str, metadata: PackageMetadata) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.lock();
+        state.clock += 1;
+        let entry = MemoryEntry {
+            metadata,
+            stored: Instant::now(),
+            last_used: state.clock,
+        };
+        let _ = state.entries.insert(url.to_owned(), entry);
+        while state.entries.len() > self.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(url, _)| url.clone());
+            if let Some(oldest) = oldest {
+                let _ = state.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// Lock the entries. A panic while they were locked can't leave them
+    /// inconsistent, so a poisoned lock is still used.
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A package at a URL, with what the server sent for it, so that it can be
+/// checked for changes with [`WatchedPackage::refresh`].
+#[derive(Debug, Clone)]
+pub struct WatchedPackage {
+    url: String,
+    validators: Validators,
+    sha256: Option<String>,
+    metadata: PackageMetadata,
+}
+
+impl WatchedPackage {
+    /// Fetch the package at a URL.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn fetch(url: &str) -> Result<Self, PkgError> {
+        let response = crate::http::send(crate::http::client().get(url))
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|err| err.in_context(url, None))?;
+        Self::from_response(url, response)
+    }
+
+    /// The URL of the package.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The metadata of the package, as last fetched.
+    pub fn metadata(&self) -> &PackageMetadata {
+        &self.metadata
+    }
+
+    /// The hex SHA-256 digest of the package, as last fetched.
+    pub fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    /// Request the URL again, conditionally on the `ETag` and
+    /// `Last-Modified` of the last response, and return the new metadata if
+    /// the package changed.
+    ///
+    /// The package is downloaded again if the server doesn't report whether
+    /// it changed, and then counts as changed only if its digest differs.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn refresh(&mut self) -> Result<Option<&PackageMetadata>, PkgError> {
+        let mut request = crate::http::client().get(&self.url);
+        if let Some(etag) = &self.validators.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.validators.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = crate::http::send(request).map_err(|err| err.in_context(&self.url, None))?;
+
+        let validators = Validators::from_headers(response.headers());
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED
+            || (response.status().is_success() && validators.matches(&self.validators))
+        {
+            return Ok(None);
+        }
+
+        let response = response
+            .error_for_status()
+            .map_err(|err| PkgError::from(err).in_context(&self.url, None))?;
+        let fetched = Self::from_response(&self.url, response)?;
+        let changed = fetched.sha256 != self.sha256;
+        *self = fetched;
+        Ok(if changed { Some(&self.metadata) } else { None })
+    }
+
+    /// Parse a package from a successful response, recording its
+    /// validators and digest.
+    fn from_response(url: &str, response: reqwest::blocking::Response) -> Result<Self, PkgError> {
+        let validators = Validators::from_headers(response.headers());
+        let mut transfer = crate::http::Transfer::from_response(response)
+            .map_err(|err| err.in_context(url, None))?;
+        let (package, digests) = crate::from_read_with_digests(&mut transfer, &[Algorithm::Sha256])
+            .map_err(|err| err.in_context(url, Some(transfer.bytes_transferred())))?;
+        Ok(Self {
+            url: url.to_owned(),
+            validators,
+            sha256: digests.sha256().map(str::to_owned),
+            metadata: package.to_metadata()?,
+        })
+    }
+}
+
+/// The current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> PackageMetadata {
+        PackageMetadata {
+            package_type: "deb".to_string(),
+            name: "hello".to_string(),
+            version: "2.10-3".to_string(),
+            iteration: Some("3".to_string()),
+            arch: "amd64".to_string(),
+            license: None,
+            dependencies: vec!["libc6".to_string()],
+            files: vec![],
+            config_files: vec![],
+            scripts: vec![],
+            triggers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("remote-package-cache-{}", std::process::id()));
+        let cache = MetadataCache::new(&dir);
+        let url = "not a url";
+
+        // Nothing is cached, so the package has to be fetched.
+        assert!(matches!(cache.get(url), Err(PkgError::HTTPError(_))));
+
+        let entry = Entry {
+            url: url.to_string(),
+            validators: Validators::default(),
+            checked: now(),
+            metadata: metadata(),
+        };
+        cache.store(&entry).unwrap();
+        assert_eq!(cache.get(url).unwrap(), metadata());
+
+        // Stale entries and forced refreshes go to the server.
+        let stale = cache.clone().with_max_age(Duration::from_secs(0));
+        assert!(matches!(stale.get(url), Err(PkgError::HTTPError(_))));
+        let refresh = cache.clone().with_force_refresh(true);
+        assert!(matches!(refresh.get(url), Err(PkgError::HTTPError(_))));
+
+        cache.remove(url).unwrap();
+        cache.remove(url).unwrap();
+        assert!(cache.load(url).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_cache() {
+        let cache = MemoryCache::new(2);
+        let named = |name: &str| PackageMetadata {
+            name: name.to_string(),
+            ..metadata()
+        };
+        cache.insert("a", named("a"));
+        cache.insert("b", named("b"));
+        assert_eq!(cache.get("a").unwrap().name, "a");
+
+        // "b" is the least recently used, so makes way for "c".
+        cache.insert("c", named("c"));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.lookup("b").is_none());
+        assert_eq!(cache.get("c").unwrap().name, "c");
+        assert!(matches!(
+            cache.get("not a url"),
+            Err(err) if err.phase() == Some(crate::Phase::Fetch)
+        ));
+
+        let cache = MemoryCache::new(2).with_ttl(Duration::from_secs(0));
+        cache.insert("a", named("a"));
+        assert!(cache.lookup("a").is_none());
+        assert!(cache.is_empty());
+
+        let cache = MemoryCache::new(0);
+        cache.insert("a", named("a"));
+        assert!(cache.is_empty());
+    }
+
+    #[cfg(all(feature = "testing", feature = "debian"))]
+    #[test]
+    fn test_watched_package() {
+        use crate::testing::{DebBuilder, MockResponse, MockTransport};
+
+        let url = "https://example.com/hello_latest_all.deb";
+        let serve = |version: &str, etag: &str| {
+            let deb = DebBuilder::new("hello", version, "all").build();
+            MockTransport::new()
+                .with_response(url, MockResponse::new(deb).with_header("ETag", etag))
+                .install()
+        };
+
+        let mock = serve("1.0", "\"1\"");
+        let mut package = WatchedPackage::fetch(url).unwrap();
+        assert_eq!(package.metadata().version, "1.0");
+        assert!(package.refresh().unwrap().is_none());
+        drop(mock);
+
+        // A new ETag for the same bytes isn't a change.
+        let mock = serve("1.0", "\"2\"");
+        assert!(package.refresh().unwrap().is_none());
+        drop(mock);
+
+        let _mock = serve("1.1", "\"3\"");
+        assert_eq!(package.refresh().unwrap().unwrap().version, "1.1");
+        assert_eq!(package.metadata().version, "1.1");
+    }
+
+    #[test]
+    fn test_validators() {
+        let mut headers = HeaderMap::new();
+        let _ = headers.insert(ETAG, "\"abc\"".parse().unwrap());
+        let _ = headers.insert(CONTENT_LENGTH, "1234".parse().unwrap());
+        let validators = Validators::from_headers(&headers);
+        assert_eq!(validators.etag.as_deref(), Some("\"abc\""));
+        assert_eq!(validators.content_length, Some(1234));
+        assert!(validators.matches(&validators.clone()));
+
+        let length_only = Validators {
+            content_length: Some(1234),
+            ..Validators::default()
+        };
+        assert!(!length_only.matches(&length_only.clone()));
+    }
+}
@@ -0,0 +1,175 @@
+//! Support for fetching the changelogs of Debian and Ubuntu packages from
+//! the archives' changelog services, rather than downloading the package
+//! just to read `/usr/share/doc/<name>/changelog.Debian.gz`.
+//!
+//! Changelogs are published per source package, under the pool directory of
+//! the source's component.
+#[cfg(feature = "debian")]
+use crate::debian::DebianRemotePackage;
+#[cfg(feature = "http-blocking")]
+use crate::PkgError;
+
+/// The Debian changelog service at metadata.ftp-master.debian.org.
+pub const DEBIAN_CHANGELOGS_URL: &str = "https://metadata.ftp-master.debian.org/changelogs";
+
+/// The Ubuntu changelog service at changelogs.ubuntu.com.
+pub const UBUNTU_CHANGELOGS_URL: &str = "https://changelogs.ubuntu.com/changelogs";
+
+/// How a changelog service lays out its files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// `<component>/<prefix>/<source>/<source>_<version>_changelog`, as
+    /// Debian serves them.
+    Debian,
+
+    /// `pool/<component>/<prefix>/<source>/<source>_<version>/changelog`,
+    /// as Ubuntu serves them.
+    Ubuntu,
+}
+
+/// A handle on a changelog service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangelogService {
+    base_url: String,
+    layout: Layout,
+}
+
+impl ChangelogService {
+    /// Create a handle on the service at `base_url` with the given layout,
+    /// e.g. for a mirror.
+    pub fn new(base_url: &str, layout: Layout) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            layout,
+        }
+    }
+
+    /// The Debian changelog service, [`DEBIAN_CHANGELOGS_URL`].
+    pub fn debian() -> Self {
+        Self::new(DEBIAN_CHANGELOGS_URL, Layout::Debian)
+    }
+
+    /// The Ubuntu changelog service, [`UBUNTU_CHANGELOGS_URL`].
+    pub fn ubuntu() -> Self {
+        Self::new(UBUNTU_CHANGELOGS_URL, Layout::Ubuntu)
+    }
+
+    /// Get the base URL of the service.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the URL of the changelog of version `version` of the source
+    /// package `source` in `component`, e.g. `main`. Any epoch in the
+    /// version is dropped, as it is from file names in the pool.
+    pub fn url(&self, source: &str, version: &str, component: &str) -> String {
+        let version = version.split_once(':').map_or(version, |(_, v)| v);
+        let prefix = pool_prefix(source);
+        match self.layout {
+            Layout::Debian => format!(
+                "{}/{}/{}/{}/{}_{}_changelog",
+                self.base_url, component, prefix, source, source, version
+            ),
+            Layout::Ubuntu => format!(
+                "{}/pool/{}/{}/{}/{}_{}/changelog",
+                self.base_url, component, prefix, source, source, version
+            ),
+        }
+    }
+
+    /// Fetch the full changelog of a version of a source package.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn fetch(&self, source: &str, version: &str, component: &str) -> Result<String, PkgError> {
+        let url = self.url(source, version, component);
+        let response = crate::http::get(&url).map_err(|err| err.in_context(&url, None))?;
+        Ok(response.text()?)
+    }
+
+    /// Fetch the full changelog of the source of a package, in the
+    /// component its `Section` says.
+    #[cfg(all(feature = "http-blocking", feature = "debian"))]
+    pub fn fetch_for(&self, package: &DebianRemotePackage) -> Result<String, PkgError> {
+        let (source, version) = package.source();
+        self.fetch(source, version, &package_component(package))
+    }
+}
+
+/// Get the directory of the pool a source package lives in: the first
+/// letter of its name, or the first four for `lib` packages.
+pub fn pool_prefix(source: &str) -> &str {
+    let len = if source.starts_with("lib") && source.len() > 3 {
+        4
+    } else {
+        1
+    };
+    source.get(..len).unwrap_or(source)
+}
+
+/// Get the archive component of a package from its `Section`, e.g.
+/// `contrib` for `contrib/utils`, or `main` if the section doesn't say.
+#[cfg(feature = "debian")]
+pub fn package_component(package: &DebianRemotePackage) -> String {
+    package
+        .section()
+        .and_then(|section| section.split_once('/'))
+        .map_or("main", |(component, _)| component)
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url() {
+        assert_eq!(pool_prefix("hello"), "h");
+        assert_eq!(pool_prefix("libfoo"), "libf");
+        assert_eq!(pool_prefix("lib"), "l");
+
+        assert_eq!(
+            ChangelogService::debian().url("hello", "2.10-3", "main"),
+            "https://metadata.ftp-master.debian.org/changelogs/main/h/hello/hello_2.10-3_changelog"
+        );
+        assert_eq!(
+            ChangelogService::ubuntu().url("libxml2", "2.9.13+dfsg-1ubuntu0.3", "main"),
+            "https://changelogs.ubuntu.com/changelogs/pool/main/libx/libxml2/libxml2_2.9.13+dfsg-1ubuntu0.3/changelog"
+        );
+        assert_eq!(
+            ChangelogService::debian().url("openssh", "1:9.2p1-2", "main"),
+            "https://metadata.ftp-master.debian.org/changelogs/main/o/openssh/openssh_9.2p1-2_changelog"
+        );
+    }
+
+    #[cfg(all(feature = "testing", feature = "debian"))]
+    #[test]
+    fn test_fetch_for() {
+        use crate::testing::{DebBuilder, MockResponse, MockTransport};
+
+        let deb = DebBuilder::new("hello-bin", "2.10-3+b1", "amd64")
+            .with_field("Source", "hello (2.10-3)")
+            .with_field("Section", "contrib/utils")
+            .build();
+        let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+        assert_eq!(package_component(&package), "contrib");
+
+        let changelog = "hello (2.10-3) unstable; urgency=medium\n";
+        let service = ChangelogService::new("https://changelogs.example.com/", Layout::Debian);
+        let _mock = MockTransport::new()
+            .with_body(
+                "https://changelogs.example.com/contrib/h/hello/hello_2.10-3_changelog",
+                changelog,
+            )
+            .with_response(
+                "https://changelogs.example.com/main/h/hello/hello_2.9-1_changelog",
+                MockResponse::new("").with_status(404),
+            )
+            .install();
+
+        assert_eq!(service.fetch_for(&package).unwrap(), changelog);
+        let err = service.fetch("hello", "2.9-1", "main").unwrap_err();
+        assert_eq!(err.kind(), crate::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,410 @@
+//! Support for checking downloads against expected checksums, as recorded
+//! in lockfiles and repository indexes.
+use std::io::{self, Read};
+
+// md-5 and sha-1 share the digest 0.9 trait.
+use md5::{Digest as _, Md5};
+use sha1::Sha1;
+use sha2::{Digest as _, Sha256, Sha512};
+
+use crate::PkgError;
+
+/// A hash algorithm that [`Digests`] can compute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// MD5, as still listed by some repository indexes.
+    Md5,
+
+    /// SHA-1.
+    Sha1,
+
+    /// SHA-256.
+    Sha256,
+
+    /// SHA-512.
+    Sha512,
+}
+
+impl Algorithm {
+    /// Every supported algorithm.
+    pub const ALL: [Algorithm; 4] = [
+        Algorithm::Md5,
+        Algorithm::Sha1,
+        Algorithm::Sha256,
+        Algorithm::Sha512,
+    ];
+
+    /// Look up an algorithm by name, as used in repository metadata. `sha`
+    /// is taken to mean SHA-1, as in old Yum repositories.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "md5" => Some(Algorithm::Md5),
+            "sha" | "sha1" => Some(Algorithm::Sha1),
+            "sha256" => Some(Algorithm::Sha256),
+            "sha512" => Some(Algorithm::Sha512),
+            _ => None,
+        }
+    }
+
+    /// The name of the algorithm, e.g. `sha256`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+        }
+    }
+}
+
+/// Hex checksums of a file, for the algorithms that were requested.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Digests {
+    md5: Option<String>,
+    sha1: Option<String>,
+    sha256: Option<String>,
+    sha512: Option<String>,
+}
+
+impl Digests {
+    /// Hash the whole of a stream in one pass with each of `algorithms`.
+    pub fn compute<R: Read>(reader: R, algorithms: &[Algorithm]) -> Result<Self, PkgError> {
+        DigestsReader::new(reader, algorithms).finish()
+    }
+
+    /// Get the checksum for an algorithm, if it was computed.
+    pub fn get(&self, algorithm: Algorithm) -> Option<&str> {
+        match algorithm {
+            Algorithm::Md5 => self.md5.as_deref(),
+            Algorithm::Sha1 => self.sha1.as_deref(),
+            Algorithm::Sha256 => self.sha256.as_deref(),
+            Algorithm::Sha512 => self.sha512.as_deref(),
+        }
+    }
+
+    /// Get the MD5 checksum, if it was computed.
+    pub fn md5(&self) -> Option<&str> {
+        self.get(Algorithm::Md5)
+    }
+
+    /// Get the SHA-1 checksum, if it was computed.
+    pub fn sha1(&self) -> Option<&str> {
+        self.get(Algorithm::Sha1)
+    }
+
+    /// Get the SHA-256 checksum, if it was computed.
+    pub fn sha256(&self) -> Option<&str> {
+        self.get(Algorithm::Sha256)
+    }
+
+    /// Get the SHA-512 checksum, if it was computed.
+    pub fn sha512(&self) -> Option<&str> {
+        self.get(Algorithm::Sha512)
+    }
+
+    /// Check a checksum against an expected hex value, failing with
+    /// [`PkgError::ChecksumMismatch`] if it differs or wasn't computed.
+    pub(crate) fn check(&self, algorithm: Algorithm, expected: &str) -> Result<(), PkgError> {
+        let actual = self.get(algorithm).unwrap_or_default();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(PkgError::ChecksumMismatch {
+                algorithm: algorithm.name().to_string(),
+                expected: expected.to_owned(),
+                actual: actual.to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A reader that hashes everything read through it with several
+/// algorithms at once.
+#[derive(Debug)]
+pub struct DigestsReader<R: Read> {
+    inner: R,
+    hashers: Vec<(Algorithm, Hasher)>,
+    bytes_read: u64,
+}
+
+impl<R: Read> DigestsReader<R> {
+    /// Wrap a reader, hashing with each of `algorithms`.
+    pub fn new(reader: R, algorithms: &[Algorithm]) -> Self {
+        let mut hashers: Vec<(Algorithm, Hasher)> = Vec::new();
+        for algorithm in algorithms {
+            if !hashers.iter().any(|(a, _)| a == algorithm) {
+                hashers.push((*algorithm, Hasher::new(*algorithm)));
+            }
+        }
+        Self {
+            inner: reader,
+            hashers,
+            bytes_read: 0,
+        }
+    }
+
+    /// The number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Read the rest of the stream and return the checksums of everything
+    /// read.
+    pub fn finish(&mut self) -> Result<Digests, PkgError> {
+        let _ = io::copy(self, &mut io::sink())?;
+
+        let mut digests = Digests::default();
+        for (algorithm, hasher) in &self.hashers {
+            let hex = Some(hasher.hex());
+            match algorithm {
+                Algorithm::Md5 => digests.md5 = hex,
+                Algorithm::Sha1 => digests.sha1 = hex,
+                Algorithm::Sha256 => digests.sha256 = hex,
+                Algorithm::Sha512 => digests.sha512 = hex,
+            }
+        }
+        Ok(digests)
+    }
+}
+
+impl<R: Read> Read for DigestsReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for (_, hasher) in &mut self.hashers {
+            hasher.update(&buf[..n]);
+        }
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+/// An expected checksum of a file, as a hex string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Digest {
+    /// A SHA-256 checksum.
+    Sha256(String),
+
+    /// A SHA-512 checksum.
+    Sha512(String),
+}
+
+impl Digest {
+    /// The hash algorithm.
+    pub fn algorithm(&self) -> Algorithm {
+        match self {
+            Digest::Sha256(_) => Algorithm::Sha256,
+            Digest::Sha512(_) => Algorithm::Sha512,
+        }
+    }
+
+    /// The expected hex checksum.
+    pub fn expected(&self) -> &str {
+        match self {
+            Digest::Sha256(hex) | Digest::Sha512(hex) => hex,
+        }
+    }
+
+    /// Check data held in memory against the checksum.
+    pub fn check(&self, data: &[u8]) -> Result<(), PkgError> {
+        let mut reader = self.reader(data);
+        reader.finish()
+    }
+
+    /// Wrap a reader so that everything read through it is hashed; call
+    /// [`DigestReader::finish`] to check the checksum.
+    pub fn reader<R: Read>(&self, reader: R) -> DigestReader<R> {
+        DigestReader {
+            inner: DigestsReader::new(reader, &[self.algorithm()]),
+            expected: self.clone(),
+        }
+    }
+}
+
+/// Parse a package while checking it against the size and checksum declared
+/// by a repository index.
+///
+/// `parse` reads as much as it needs; the rest of the stream is then read
+/// so that the whole file is checked. The parsed package is only returned
+/// if every declared value matches.
+#[cfg(any(
+    feature = "buildinfo",
+    feature = "oci",
+    all(feature = "repo-deb", feature = "debian"),
+    all(feature = "repo-rpm", feature = "rpm")
+))]
+pub(crate) fn parse_checked<R, T, F>(
+    reader: R,
+    size: Option<u64>,
+    checksum: Option<(Algorithm, &str)>,
+    parse: F,
+) -> Result<T, PkgError>
+where
+    R: Read,
+    F: FnOnce(&mut DigestsReader<R>) -> Result<T, PkgError>,
+{
+    let algorithms: Vec<Algorithm> = checksum.iter().map(|(algorithm, _)| *algorithm).collect();
+    let mut reader = DigestsReader::new(reader, &algorithms);
+    let package = parse(&mut reader)?;
+    let digests = reader.finish()?;
+
+    if let Some(size) = size {
+        if size != reader.bytes_read() {
+            return Err(PkgError::SizeMismatch {
+                expected: size,
+                actual: reader.bytes_read(),
+            });
+        }
+    }
+    if let Some((algorithm, expected)) = checksum {
+        digests.check(algorithm, expected)?;
+    }
+    Ok(package)
+}
+
+/// A running hash.
+#[derive(Debug, Clone)]
+enum Hasher {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Md5 => Hasher::Md5(Md5::new()),
+            Algorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The hex hash of everything so far.
+    fn hex(&self) -> String {
+        match self {
+            Hasher::Md5(hasher) => format!("{:x}", hasher.clone().finalize()),
+            Hasher::Sha1(hasher) => format!("{:x}", hasher.clone().finalize()),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.clone().finalize()),
+            Hasher::Sha512(hasher) => format!("{:x}", hasher.clone().finalize()),
+        }
+    }
+}
+
+/// A reader that hashes everything read through it, to check against a
+/// single expected [`Digest`].
+#[derive(Debug)]
+pub struct DigestReader<R: Read> {
+    inner: DigestsReader<R>,
+    expected: Digest,
+}
+
+impl<R: Read> DigestReader<R> {
+    /// Read the rest of the stream and check the checksum of everything
+    /// read, failing with [`PkgError::ChecksumMismatch`] if it differs.
+    pub fn finish(&mut self) -> Result<(), PkgError> {
+        self.inner
+            .finish()?
+            .check(self.expected.algorithm(), self.expected.expected())
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_digest_reader() {
+        let digest = Digest::Sha256(HELLO_SHA256.to_uppercase());
+        digest.check(b"hello").unwrap();
+
+        // Reading part of the stream still checks all of it.
+        let mut reader = digest.reader(&b"hello"[..]);
+        let mut start = [0; 2];
+        reader.read_exact(&mut start).unwrap();
+        reader.finish().unwrap();
+
+        match digest.check(b"hellO") {
+            Err(PkgError::ChecksumMismatch {
+                algorithm,
+                expected,
+                actual,
+            }) => {
+                assert_eq!(algorithm, "sha256");
+                assert_eq!(expected, HELLO_SHA256.to_uppercase());
+                assert_ne!(actual, HELLO_SHA256);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(Digest::Sha512("00".to_string()).check(b"hello").is_err());
+    }
+
+    #[test]
+    fn test_digests() {
+        let digests = Digests::compute(&b"hello"[..], &Algorithm::ALL).unwrap();
+        assert_eq!(digests.md5(), Some("5d41402abc4b2a76b9719d911017c592"));
+        assert_eq!(
+            digests.sha1(),
+            Some("aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d")
+        );
+        assert_eq!(digests.sha256(), Some(HELLO_SHA256));
+        assert_eq!(digests.sha512().map(str::len), Some(128));
+
+        let digests = Digests::compute(&b"hello"[..], &[Algorithm::Sha256]).unwrap();
+        assert_eq!(digests.sha256(), Some(HELLO_SHA256));
+        assert_eq!(digests.md5(), None);
+    }
+
+    #[cfg(any(
+        feature = "buildinfo",
+        all(feature = "repo-deb", feature = "debian"),
+        all(feature = "repo-rpm", feature = "rpm")
+    ))]
+    #[test]
+    fn test_parse_checked() {
+        let first_byte = |reader: &mut DigestsReader<&[u8]>| {
+            let mut byte = [0; 1];
+            reader.read_exact(&mut byte)?;
+            Ok(byte[0])
+        };
+        let sha256 = Some((Algorithm::Sha256, HELLO_SHA256));
+
+        assert_eq!(
+            parse_checked(&b"hello"[..], Some(5), sha256, first_byte).unwrap(),
+            b'h'
+        );
+        assert_eq!(
+            parse_checked(&b"hello"[..], None, None, first_byte).unwrap(),
+            b'h'
+        );
+        assert!(matches!(
+            parse_checked(&b"hello"[..], Some(6), sha256, first_byte),
+            Err(PkgError::SizeMismatch {
+                expected: 6,
+                actual: 5
+            })
+        ));
+        assert!(matches!(
+            parse_checked(&b"hellO"[..], Some(5), sha256, first_byte),
+            Err(PkgError::ChecksumMismatch { algorithm, .. }) if algorithm == "sha256"
+        ));
+        assert_eq!(Algorithm::from_name("SHA"), Some(Algorithm::Sha1));
+    }
+}
@@ -0,0 +1,257 @@
+//! Compression formats, and helpers for reading compressed data.
+//!
+//! [`Compression`] is public as `payload::Compression`, describing how a
+//! package's payload is compressed, e.g. from
+//! `DebianRemotePackage::payload_compression()`. The crate also uses it to
+//! read package payloads, compressed repository metadata and package
+//! downloads sent with a `Content-Encoding`.
+use std::io::{self, BufRead, Read};
+
+use crate::PkgError;
+
+/// Compression formats used by repository metadata files and package
+/// payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Not compressed.
+    None,
+
+    /// gzip.
+    Gzip,
+
+    /// xz.
+    Xz,
+
+    /// The legacy `.lzma` format that preceded xz.
+    Lzma,
+
+    /// Zstandard.
+    Zstd,
+
+    /// bzip2.
+    Bzip2,
+}
+
+impl Compression {
+    /// Work out the compression of a metadata file from its name.
+    #[cfg(any(feature = "repo-deb", feature = "repo-rpm"))]
+    pub(crate) fn from_path(path: &str) -> Self {
+        if path.ends_with(".gz") {
+            Compression::Gzip
+        } else if path.ends_with(".xz") {
+            Compression::Xz
+        } else if path.ends_with(".zst") {
+            Compression::Zstd
+        } else if path.ends_with(".bz2") {
+            Compression::Bzip2
+        } else if path.ends_with(".lzma") {
+            Compression::Lzma
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Work out the compression of a file from its leading bytes.
+    #[cfg(any(
+        feature = "debian",
+        feature = "rpm",
+        feature = "repo-arch",
+        feature = "http-blocking"
+    ))]
+    pub(crate) fn from_magic(buf: &[u8]) -> Self {
+        if infer::archive::is_gz(buf) {
+            Compression::Gzip
+        } else if infer::archive::is_xz(buf) {
+            Compression::Xz
+        } else if infer::archive::is_zst(buf) {
+            Compression::Zstd
+        } else if infer::archive::is_bz2(buf) {
+            Compression::Bzip2
+        } else if buf.starts_with(&[0x5d, 0, 0]) {
+            // The usual properties byte and dictionary size of an lzma
+            // stream, which has no magic of its own.
+            Compression::Lzma
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Wrap a reader so that it yields decompressed bytes, sniffing the
+    /// compression from the start of the stream.
+    #[cfg(any(feature = "debian", feature = "rpm", feature = "repo-arch"))]
+    pub(crate) fn detect_decoder<'a, R: Read + 'a>(
+        reader: R,
+    ) -> Result<Box<dyn Read + 'a>, PkgError> {
+        Self::detect_limited_decoder(reader, None)
+    }
+
+    /// Like [`Compression::detect_decoder`], but bounding the memory the
+    /// decoder may allocate; see [`Compression::limited_decoder`].
+    #[cfg(any(feature = "debian", feature = "rpm", feature = "repo-arch"))]
+    pub(crate) fn detect_limited_decoder<'a, R: Read + 'a>(
+        reader: R,
+        max_memory: Option<u64>,
+    ) -> Result<Box<dyn Read + 'a>, PkgError> {
+        // Read enough bytes to recognise any of the supported formats.
+        let mut reader = reader.take(16);
+        let mut magic = vec![];
+        let _ = reader.read_to_end(&mut magic)?;
+
+        // Using a cursor and chain allows us to reconstruct the original stream.
+        let compression = Self::from_magic(&magic);
+        let stream = io::Cursor::new(magic).chain(reader.into_inner());
+        compression.limited_decoder(stream, max_memory)
+    }
+
+    /// Wrap a reader so that it yields decompressed bytes.
+    #[cfg(any(feature = "repo-deb", feature = "repo-rpm", feature = "http-blocking"))]
+    pub(crate) fn decoder<'a, R: Read + 'a>(
+        self,
+        reader: R,
+    ) -> Result<Box<dyn Read + 'a>, PkgError> {
+        self.limited_decoder(reader, None)
+    }
+
+    /// Wrap a reader so that it yields decompressed bytes, failing with an
+    /// error recognised by [`is_memory_limit_error`] if the decoder would
+    /// need more than `max_memory` bytes.
+    ///
+    /// Only xz, lzma and zstd let the stream pick how much memory the
+    /// decoder needs; gzip and bzip2 use a small fixed amount.
+    pub(crate) fn limited_decoder<'a, R: Read + 'a>(
+        self,
+        reader: R,
+        max_memory: Option<u64>,
+    ) -> Result<Box<dyn Read + 'a>, PkgError> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+            Compression::Xz => {
+                let stream = xz2::stream::Stream::new_stream_decoder(
+                    max_memory.unwrap_or(u64::MAX),
+                    xz2::stream::CONCATENATED,
+                )
+                .map_err(io::Error::from)?;
+                Box::new(xz2::read::XzDecoder::new_stream(reader, stream))
+            }
+            Compression::Lzma => {
+                let stream = xz2::stream::Stream::new_lzma_decoder(max_memory.unwrap_or(u64::MAX))
+                    .map_err(io::Error::from)?;
+                Box::new(xz2::read::XzDecoder::new_stream(reader, stream))
+            }
+            Compression::Zstd => match max_memory {
+                Some(max_memory) => Box::new(ZstdDecoder::new(reader, max_memory)?),
+                None => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            },
+            Compression::Bzip2 => Box::new(bzip2::read::BzDecoder::new(reader)),
+        })
+    }
+}
+
+/// The largest zstd window, as a power of two, that fits in `max_memory`
+/// bytes. zstd won't go below 1 KiB or above 2 GiB.
+fn zstd_window_log(max_memory: u64) -> u32 {
+    let log = 63 - max_memory.max(1).leading_zeros();
+    log.clamp(10, 31)
+}
+
+/// Whether an error from a decoder made by [`Compression::limited_decoder`]
+/// means the memory limit was hit.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+pub(crate) fn is_memory_limit_error(err: &io::Error) -> bool {
+    let inner = err.get_ref();
+    let xz = inner
+        .and_then(|inner| inner.downcast_ref::<xz2::stream::Error>())
+        .is_some_and(|inner| *inner == xz2::stream::Error::MemLimit);
+    xz || inner.is_some_and(|inner| inner.is::<MemoryLimitError>())
+}
+
+/// The error a [`ZstdDecoder`] fails with when a frame needs a bigger window
+/// than its memory limit allows.
+#[derive(Debug)]
+struct MemoryLimitError;
+
+impl std::fmt::Display for MemoryLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("zstd frame requires too much memory for decoding")
+    }
+}
+
+impl std::error::Error for MemoryLimitError {}
+
+/// A zstd decoder with a bounded window.
+///
+/// The decoder in the zstd crate turns every error into a bare message, so
+/// hitting the window limit couldn't be told apart from corrupt data. This
+/// one drives the decompression context directly and reports the limit as a
+/// [`MemoryLimitError`].
+struct ZstdDecoder<R> {
+    reader: io::BufReader<R>,
+    context: zstd::zstd_safe::DCtx<'static>,
+    in_frame: bool,
+}
+
+impl<R: Read> ZstdDecoder<R> {
+    fn new(reader: R, max_memory: u64) -> io::Result<Self> {
+        let mut context = zstd::zstd_safe::DCtx::create();
+        let _ = context
+            .set_parameter(zstd::zstd_safe::DParameter::WindowLogMax(zstd_window_log(
+                max_memory,
+            )))
+            .map_err(zstd_error)?;
+        Ok(Self {
+            reader: io::BufReader::new(reader),
+            context,
+            in_frame: false,
+        })
+    }
+}
+
+impl<R: Read> Read for ZstdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let input = self.reader.fill_buf()?;
+            let eof = input.is_empty();
+            if eof && !self.in_frame {
+                return Ok(0);
+            }
+            let mut src = zstd::zstd_safe::InBuffer::around(input);
+            let mut dst = zstd::zstd_safe::OutBuffer::around(&mut *buf);
+            let hint = self
+                .context
+                .decompress_stream(&mut dst, &mut src)
+                .map_err(zstd_error)?;
+            let (consumed, written) = (src.pos(), dst.pos());
+            self.reader.consume(consumed);
+            // A hint of zero means the frame is decoded and fully flushed.
+            self.in_frame = hint != 0;
+            if written > 0 {
+                return Ok(written);
+            }
+            if eof {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "incomplete zstd frame",
+                ));
+            }
+        }
+    }
+}
+
+/// Turn a zstd error code into an I/O error.
+fn zstd_error(code: usize) -> io::Error {
+    // zstd returns errors as the negated error enum.
+    let window_too_large = 0usize.wrapping_sub(
+        zstd::zstd_safe::zstd_sys::ZSTD_ErrorCode::ZSTD_error_frameParameter_windowTooLarge
+            as usize,
+    );
+    if code == window_too_large {
+        io::Error::other(MemoryLimitError)
+    } else {
+        io::Error::other(zstd::zstd_safe::get_error_name(code))
+    }
+}
@@ -0,0 +1,117 @@
+//! Support for looking up Fedora COPR projects.
+//!
+//! A COPR project builds packages for a number of chroots (e.g.
+//! `fedora-39-x86_64`), each of which is published as a separate Yum
+//! repository. The COPR API lists the repository URL for every chroot.
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::repo_rpm::YumRepo;
+use crate::PkgError;
+
+/// The public Fedora COPR instance.
+pub const FEDORA_COPR_URL: &str = "https://copr.fedorainfracloud.org";
+
+/// A COPR project, as returned by the `api_3/project` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CoprProject {
+    /// The project name.
+    pub name: String,
+
+    /// The project owner; groups are prefixed with `@`.
+    pub ownername: String,
+
+    /// The Yum repository base URL for each chroot, keyed by chroot name.
+    #[serde(default)]
+    pub chroot_repos: BTreeMap<String, String>,
+}
+
+impl CoprProject {
+    /// Parse a response from the COPR project API.
+    pub fn parse(json: &str) -> Result<Self, PkgError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Fetch a project from a COPR instance such as [`FEDORA_COPR_URL`].
+    /// `project` is given as `owner/project`, e.g. `@caddy/caddy`.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn fetch(copr_url: &str, project: &str) -> Result<Self, PkgError> {
+        let (owner, name) = split_project(project)?;
+        let url = format!(
+            "{}/api_3/project?ownername={}&projectname={}",
+            copr_url.trim_end_matches('/'),
+            owner.replace('@', "%40"),
+            name
+        );
+        let response = crate::http::get(&url)?;
+        Self::parse(&response.text()?)
+    }
+
+    /// Get the chroots the project builds for.
+    pub fn chroots(&self) -> impl Iterator<Item = &str> {
+        self.chroot_repos.keys().map(String::as_str)
+    }
+
+    /// Get the Yum repository for a chroot, e.g. `fedora-39-x86_64`.
+    pub fn repo(&self, chroot: &str) -> Result<YumRepo, PkgError> {
+        self.chroot_repos
+            .get(chroot)
+            .map(|base_url| YumRepo::new(base_url))
+            .ok_or_else(|| PkgError::RepoDataNotFound(format!("{}: {}", self.name, chroot)))
+    }
+}
+
+/// Split an `owner/project` specification.
+#[cfg(any(feature = "http-blocking", test))]
+fn split_project(project: &str) -> Result<(&str, &str), PkgError> {
+    match project.split_once('/') {
+        Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+            Ok((owner, name))
+        }
+        _ => Err(PkgError::InvalidRepoSpec(project.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = r#"{
+        "id": 12345,
+        "name": "caddy",
+        "ownername": "@caddy",
+        "full_name": "@caddy/caddy",
+        "chroot_repos": {
+            "epel-9-x86_64": "https://download.copr.fedorainfracloud.org/results/@caddy/caddy/epel-9-x86_64/",
+            "fedora-39-x86_64": "https://download.copr.fedorainfracloud.org/results/@caddy/caddy/fedora-39-x86_64/"
+        }
+    }"#;
+
+    #[test]
+    fn test_project() {
+        let project = CoprProject::parse(PROJECT).unwrap();
+        assert_eq!(project.ownername, "@caddy");
+        assert_eq!(
+            project.chroots().collect::<Vec<_>>(),
+            vec!["epel-9-x86_64", "fedora-39-x86_64"]
+        );
+
+        let repo = project.repo("fedora-39-x86_64").unwrap();
+        assert_eq!(
+            repo.base_url(),
+            "https://download.copr.fedorainfracloud.org/results/@caddy/caddy/fedora-39-x86_64"
+        );
+        assert!(project.repo("fedora-rawhide-aarch64").is_err());
+    }
+
+    #[test]
+    fn test_split_project() {
+        assert_eq!(split_project("@caddy/caddy").unwrap(), ("@caddy", "caddy"));
+        assert!(split_project("caddy").is_err());
+        assert!(split_project("a/b/c").is_err());
+    }
+}
@@ -0,0 +1,315 @@
+//! Verification of cosign signatures on packages in OCI registries.
+//!
+//! `cosign sign --key` stores the signature of a manifest as another
+//! manifest in the same repository, tagged `sha256-<hex>.sig` after the
+//! digest it signs. Each of its layers is a "simple signing" JSON payload
+//! naming the signed digest, with the base64 ECDSA P-256 signature of the
+//! payload in an annotation. [`CosignKey`] checks those signatures against
+//! the public key written by `cosign generate-key-pair`, and
+//! [`OciRepository::package_verified`] does so before fetching a package.
+//!
+//! Only signatures made with a key are checked. Keyless signatures need
+//! Fulcio certificate chains and Rekor transparency log entries, which
+//! aren't supported.
+use std::io::Read;
+use std::path::Path;
+
+use base64::Engine;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use serde::Deserialize;
+
+use crate::oci::{OciManifest, OciRepository};
+use crate::{PkgError, RemotePackage};
+
+/// The media type of a simple signing payload.
+pub const SIMPLE_SIGNING: &str = "application/vnd.dev.cosign.simplesigning.v1+json";
+
+/// The annotation holding the signature of a payload.
+const SIGNATURE_ANNOTATION: &str = "dev.cosignproject.cosign/signature";
+
+/// The type of a simple signing payload for an image.
+const SIGNATURE_TYPE: &str = "cosign container image signature";
+
+/// The largest signature payload read. Real payloads are well under a
+/// kilobyte.
+const MAX_PAYLOAD_SIZE: u64 = 64 * 1024;
+
+/// A cosign public key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CosignKey(VerifyingKey);
+
+impl CosignKey {
+    /// Parse a PEM encoded ECDSA P-256 public key, such as `cosign.pub`.
+    pub fn from_pem(pem: &str) -> Result<Self, PkgError> {
+        VerifyingKey::from_public_key_pem(pem.trim())
+            .map(Self)
+            .map_err(|e| PkgError::SignatureError(format!("invalid cosign key: {}", e)))
+    }
+
+    /// Read a PEM encoded public key from a file; see
+    /// [`CosignKey::from_pem`].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PkgError> {
+        Self::from_pem(&std::fs::read_to_string(path)?)
+    }
+
+    /// Check the base64 signature of a simple signing payload, and that the
+    /// payload signs the manifest with `digest`.
+    pub fn verify_payload(
+        &self,
+        payload: &[u8],
+        signature: &str,
+        digest: &str,
+    ) -> Result<(), PkgError> {
+        let invalid = |reason: String| PkgError::SignatureError(reason);
+        let signature = base64::engine::general_purpose::STANDARD
+            .decode(signature.trim())
+            .map_err(|e| invalid(format!("invalid cosign signature: {}", e)))?;
+        let signature = Signature::from_der(&signature)
+            .map_err(|e| invalid(format!("invalid cosign signature: {}", e)))?;
+        self.0
+            .verify(payload, &signature)
+            .map_err(|_| invalid(format!("bad cosign signature for {}", digest)))?;
+
+        // The signature only says the payload is genuine; the payload says
+        // what was signed.
+        let payload: SimpleSigning = serde_json::from_slice(payload)?;
+        if payload.critical.kind != SIGNATURE_TYPE {
+            return Err(invalid(format!(
+                "unexpected cosign payload type {}",
+                payload.critical.kind
+            )));
+        }
+        if payload.critical.image.docker_manifest_digest != digest {
+            return Err(invalid(format!(
+                "cosign signature is for {}, not {}",
+                payload.critical.image.docker_manifest_digest, digest
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The tag cosign stores the signature of the manifest with `digest` under,
+/// e.g. `sha256-<hex>.sig`.
+pub fn signature_tag(digest: &str) -> String {
+    format!("{}.sig", digest.replacen(':', "-", 1))
+}
+
+/// A simple signing payload; only the fields that are checked.
+#[derive(Debug, Deserialize)]
+struct SimpleSigning {
+    critical: Critical,
+}
+
+#[derive(Debug, Deserialize)]
+struct Critical {
+    image: Image,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Image {
+    #[serde(rename = "docker-manifest-digest")]
+    docker_manifest_digest: String,
+}
+
+impl OciRepository {
+    /// Check that a manifest has a cosign signature made with `key`,
+    /// failing with [`PkgError::SignatureError`] if it has none.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn verify(&self, manifest: &OciManifest, key: &CosignKey) -> Result<(), PkgError> {
+        let signatures = match self.manifest(&signature_tag(&manifest.digest)) {
+            Ok(signatures) => signatures,
+            Err(err) if err.kind() == crate::ErrorKind::NotFound => {
+                return Err(PkgError::SignatureError(format!(
+                    "no cosign signature for {}",
+                    manifest.digest
+                )))
+            }
+            Err(err) => return Err(err),
+        };
+
+        let mut last_error = None;
+        for layer in &signatures.layers {
+            let signature = match layer.annotations.get(SIGNATURE_ANNOTATION) {
+                Some(signature) if layer.media_type == SIMPLE_SIGNING => signature,
+                _ => continue,
+            };
+            if layer.size > MAX_PAYLOAD_SIZE {
+                last_error = Some(PkgError::SizeLimitExceeded(MAX_PAYLOAD_SIZE));
+                continue;
+            }
+
+            // Any one good signature will do.
+            let result = layer.checksum().and_then(|checksum| {
+                let mut payload = Vec::new();
+                let _ = self.blob(layer)?.read_to_end(&mut payload)?;
+                checksum.check(&payload)?;
+                key.verify_payload(&payload, signature, &manifest.digest)
+            });
+            match result {
+                Ok(()) => return Ok(()),
+                Err(err) => last_error = Some(err),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            PkgError::SignatureError(format!("no cosign signature for {}", manifest.digest))
+        }))
+    }
+
+    /// Fetch and parse the package in a manifest as
+    /// [`OciRepository::package`] does, but only once the manifest's cosign
+    /// signature is checked with `key`. The package is then bound to the
+    /// signature by its digest in the manifest.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn package_verified(
+        &self,
+        reference: &str,
+        key: &CosignKey,
+    ) -> Result<Box<dyn RemotePackage>, PkgError> {
+        let manifest = self.manifest(reference)?;
+        self.verify(&manifest, key)?;
+        self.manifest_package(&manifest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::SigningKey;
+    use p256::pkcs8::{EncodePublicKey, LineEnding};
+
+    /// A key pair and a signed payload for `digest`.
+    fn sign(digest: &str) -> (CosignKey, String, Vec<u8>) {
+        let signing_key = SigningKey::from_bytes(&[7; 32].into()).unwrap();
+        let pem = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        let payload = format!(
+            r#"{{"critical": {{"identity": {{"docker-reference": "registry.example.com/org/hello"}},
+                "image": {{"docker-manifest-digest": "{}"}}, "type": "{}"}}, "optional": null}}"#,
+            digest, SIGNATURE_TYPE
+        );
+        let signature: Signature = signing_key.sign(payload.as_bytes());
+        let signature =
+            base64::engine::general_purpose::STANDARD.encode(signature.to_der().as_bytes());
+        (
+            CosignKey::from_pem(&pem).unwrap(),
+            signature,
+            payload.into_bytes(),
+        )
+    }
+
+    #[test]
+    fn test_verify_payload() {
+        let digest = format!("sha256:{}", "a".repeat(64));
+        let (key, signature, payload) = sign(&digest);
+        key.verify_payload(&payload, &signature, &digest).unwrap();
+
+        // The payload names another manifest.
+        let other = format!("sha256:{}", "b".repeat(64));
+        assert!(matches!(
+            key.verify_payload(&payload, &signature, &other),
+            Err(PkgError::SignatureError(_))
+        ));
+
+        // The payload was changed after signing.
+        let mut tampered = payload.clone();
+        tampered.push(b' ');
+        assert!(matches!(
+            key.verify_payload(&tampered, &signature, &digest),
+            Err(PkgError::SignatureError(_))
+        ));
+        assert!(key
+            .verify_payload(&payload, "not base64!", &digest)
+            .is_err());
+        assert!(CosignKey::from_pem("not a key").is_err());
+    }
+
+    #[test]
+    fn test_signature_tag() {
+        let hex = "a".repeat(64);
+        assert_eq!(
+            signature_tag(&format!("sha256:{}", hex)),
+            format!("sha256-{}.sig", hex)
+        );
+    }
+
+    #[cfg(all(feature = "testing", feature = "rpm"))]
+    #[test]
+    fn test_package_verified() {
+        use sha2::{Digest as _, Sha256};
+
+        let rpm = crate::testing::RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+        let blob = format!("sha256:{:x}", Sha256::digest(&rpm));
+        let manifest = format!(
+            r#"{{"schemaVersion": 2, "layers": [{{"mediaType": "application/x-rpm",
+                "digest": "{}", "size": {}}}]}}"#,
+            blob,
+            rpm.len()
+        );
+        let digest = format!("sha256:{:x}", Sha256::digest(manifest.as_bytes()));
+
+        let (key, signature, payload) = sign(&digest);
+        let payload_digest = format!("sha256:{:x}", Sha256::digest(&payload));
+        let signatures = format!(
+            r#"{{"schemaVersion": 2, "layers": [{{"mediaType": "{}", "digest": "{}", "size": {},
+                "annotations": {{"{}": "{}"}}}}]}}"#,
+            SIMPLE_SIGNING,
+            payload_digest,
+            payload.len(),
+            SIGNATURE_ANNOTATION,
+            signature
+        );
+
+        let base = "https://registry.example.com/v2/org/hello";
+        let mock = crate::testing::MockTransport::new()
+            .with_body(&format!("{}/manifests/1.0", base), manifest)
+            .with_body(
+                &format!("{}/manifests/{}", base, signature_tag(&digest)),
+                signatures,
+            )
+            .with_body(&format!("{}/blobs/{}", base, payload_digest), payload)
+            .with_body(&format!("{}/blobs/{}", base, blob), rpm)
+            .install();
+
+        let repository = OciRepository::new("registry.example.com", "org/hello", None);
+        let package = repository.package_verified("1.0", &key).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+
+        // The signature is checked before the package is fetched.
+        let other_key = CosignKey(
+            *SigningKey::from_bytes(&[8; 32].into())
+                .unwrap()
+                .verifying_key(),
+        );
+        let requests = mock.requests().len();
+        assert!(matches!(
+            repository.package_verified("1.0", &other_key),
+            Err(PkgError::SignatureError(_))
+        ));
+        assert!(!mock.requests()[requests..]
+            .iter()
+            .any(|url| url.ends_with(&blob)));
+
+        // An unsigned manifest is rejected.
+        drop(mock);
+        let _mock = crate::testing::MockTransport::new()
+            .with_body(&format!("{}/manifests/1.0", base), "{\"layers\": []}")
+            .install();
+        assert!(matches!(
+            repository.package_verified("1.0", &key),
+            Err(PkgError::SignatureError(_))
+        ));
+    }
+}
@@ -0,0 +1,232 @@
+//! Support for looking up crates in a Cargo sparse registry index.
+//!
+//! Each crate has a file in the index listing every published version as
+//! one JSON object per line. The index's `config.json` describes where the
+//! `.crate` files themselves can be downloaded from.
+use serde::Deserialize;
+
+use crate::repo::join_url;
+use crate::PkgError;
+
+/// The sparse index for crates.io.
+pub const CRATES_IO_INDEX: &str = "https://index.crates.io";
+
+/// A dependency of a published crate version.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrateDependency {
+    /// The dependency name as used in the crate.
+    pub name: String,
+
+    /// The version requirement, e.g. `^1.0`.
+    pub req: String,
+
+    /// The dependency kind: `normal`, `build` or `dev`.
+    #[serde(default)]
+    pub kind: Option<String>,
+
+    /// Whether the dependency is optional.
+    #[serde(default)]
+    pub optional: bool,
+}
+
+/// A published version of a crate, as listed in the index.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CrateVersion {
+    /// The crate name.
+    pub name: String,
+
+    /// The version.
+    pub vers: String,
+
+    /// The SHA256 checksum of the `.crate` file.
+    pub cksum: String,
+
+    /// Whether the version has been yanked.
+    #[serde(default)]
+    pub yanked: bool,
+
+    /// The minimum supported Rust version, if declared.
+    #[serde(default)]
+    pub rust_version: Option<String>,
+
+    /// The crate's dependencies.
+    #[serde(default)]
+    pub deps: Vec<CrateDependency>,
+}
+
+/// Parse the contents of a crate's index file.
+pub fn parse_index_file(text: &str) -> Result<Vec<CrateVersion>, PkgError> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+/// Get the path of a crate's file within the index.
+pub fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    format!("{}/{}", prefix(&name), name)
+}
+
+/// Get the index directory for a crate name, preserving its case.
+fn prefix(name: &str) -> String {
+    match name.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", &name[..1]),
+        _ => format!("{}/{}", &name[..2], &name[2..4]),
+    }
+}
+
+/// The `config.json` of a registry index.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IndexConfig {
+    /// The download URL template for `.crate` files.
+    pub dl: String,
+
+    /// The registry's web API URL, if any.
+    #[serde(default)]
+    pub api: Option<String>,
+}
+
+impl IndexConfig {
+    /// Get the download URL of the `.crate` file for a version.
+    ///
+    /// Expands the `{crate}`, `{version}`, `{prefix}`, `{lowerprefix}` and
+    /// `{sha256-checksum}` markers in the template; if there are none,
+    /// `/{crate}/{version}/download` is appended, as cargo does.
+    pub fn download_url(&self, version: &CrateVersion) -> String {
+        let markers = [
+            "{crate}",
+            "{version}",
+            "{prefix}",
+            "{lowerprefix}",
+            "{sha256-checksum}",
+        ];
+        if !markers.iter().any(|m| self.dl.contains(m)) {
+            return format!(
+                "{}/{}/{}/download",
+                self.dl.trim_end_matches('/'),
+                version.name,
+                version.vers
+            );
+        }
+
+        self.dl
+            .replace("{crate}", &version.name)
+            .replace("{version}", &version.vers)
+            .replace("{prefix}", &prefix(&version.name))
+            .replace("{lowerprefix}", &prefix(&version.name.to_lowercase()))
+            .replace("{sha256-checksum}", &version.cksum)
+    }
+}
+
+/// A remote Cargo sparse registry index.
+#[derive(Debug, Clone)]
+pub struct SparseIndex {
+    index_url: String,
+}
+
+impl SparseIndex {
+    /// Create a handle on the sparse index at `index_url`, e.g.
+    /// [`CRATES_IO_INDEX`]. Any `sparse+` prefix is removed.
+    pub fn new(index_url: &str) -> Self {
+        let index_url = index_url.trim_start_matches("sparse+");
+        Self {
+            index_url: index_url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Get the URL of a crate's file in the index.
+    pub fn crate_url(&self, name: &str) -> String {
+        join_url(&self.index_url, &index_path(name))
+    }
+
+    /// Fetch the index configuration.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn config(&self) -> Result<IndexConfig, PkgError> {
+        let response = crate::http::get(&join_url(&self.index_url, "config.json"))?;
+        Ok(serde_json::from_str(&response.text()?)?)
+    }
+
+    /// Fetch every published version of a crate.
+    #[cfg(feature = "http-blocking")]
+    pub fn versions(&self, name: &str) -> Result<Vec<CrateVersion>, PkgError> {
+        let response = crate::http::get(&self.crate_url(name))?;
+        parse_index_file(&response.text()?)
+    }
+
+    /// Fetch a specific version of a crate along with its download URL.
+    /// Returns `None` if the version has not been published.
+    #[cfg(feature = "http-blocking")]
+    pub fn resolve(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<(CrateVersion, String)>, PkgError> {
+        let found = self.versions(name)?.into_iter().find(|v| v.vers == version);
+        match found {
+            Some(found) => {
+                let url = self.config()?.download_url(&found);
+                Ok(Some((found, url)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INDEX_FILE: &str = r#"{"name":"fez","vers":"0.1.0","deps":[{"name":"nom","req":"^7","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"}],"cksum":"aaaa","features":{},"yanked":true}
+{"name":"fez","vers":"0.8.1","deps":[],"cksum":"bbbb","features":{},"yanked":false,"rust_version":"1.56"}
+"#;
+
+    #[test]
+    fn test_index_path() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("ab"), "2/ab");
+        assert_eq!(index_path("fez"), "3/f/fez");
+        assert_eq!(index_path("Serde"), "se/rd/serde");
+
+        let index = SparseIndex::new("sparse+https://index.crates.io/");
+        assert_eq!(index.crate_url("fez"), "https://index.crates.io/3/f/fez");
+    }
+
+    #[test]
+    fn test_parse_index_file() {
+        let versions = parse_index_file(INDEX_FILE).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert!(versions[0].yanked);
+        assert_eq!(versions[0].deps[0].req, "^7");
+        assert_eq!(versions[1].rust_version.as_deref(), Some("1.56"));
+    }
+
+    #[test]
+    fn test_download_url() {
+        let versions = parse_index_file(INDEX_FILE).unwrap();
+
+        let crates_io = IndexConfig {
+            dl: "https://static.crates.io/crates".to_string(),
+            api: None,
+        };
+        assert_eq!(
+            crates_io.download_url(&versions[1]),
+            "https://static.crates.io/crates/fez/0.8.1/download"
+        );
+
+        let templated = IndexConfig {
+            dl: "https://example.com/{prefix}/{crate}-{version}.crate?sum={sha256-checksum}"
+                .to_string(),
+            api: None,
+        };
+        assert_eq!(
+            templated.download_url(&versions[1]),
+            "https://example.com/3/f/fez-0.8.1.crate?sum=bbbb"
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! Parsing of deb822 style control data, as used by Debian repository
+//! indexes (`Packages`, `Sources`, `Release`) and by `.dsc`, `.changes` and
+//! `.buildinfo` files.
+//!
+//! ```
+//! use remote_package::deb822;
+//!
+//! let paragraphs = deb822::parse("Package: hello\nVersion: 2.10-3\nDepends: libc6, dpkg\n")?;
+//! assert_eq!(paragraphs[0].get("Package"), Some("hello"));
+//! assert_eq!(paragraphs[0].get_list("Depends"), vec!["libc6", "dpkg"]);
+//! # Ok::<(), remote_package::PkgError>(())
+//! ```
+use std::io::BufRead;
+use std::str::FromStr;
+
+use crate::PkgError;
+
+/// The line that starts a cleartext signed message.
+const SIGNED_MESSAGE_BEGIN: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+
+/// The line that ends the signed text of a cleartext signed message.
+const SIGNATURE_BEGIN: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// Parse every paragraph of deb822 data held in memory. Files such as
+/// `InRelease` and `.dsc` are usually signed; the signature is stripped but
+/// not checked.
+pub fn parse(text: &str) -> Result<Vec<Paragraph>, PkgError> {
+    let text = strip_signature(text);
+    Deb822Reader::new(text.as_bytes()).collect()
+}
+
+/// Remove any cleartext signature from a message, returning the signed text
+/// with its dash-escaping removed. Unsigned text is returned as it is.
+pub fn strip_signature(text: &str) -> String {
+    let mut lines = text.lines();
+    if !lines.any(|line| line.trim_end() == SIGNED_MESSAGE_BEGIN) {
+        return text.to_owned();
+    }
+
+    // Skip the armor headers, e.g. `Hash: SHA256`.
+    let mut lines = lines.skip_while(|line| !line.trim().is_empty()).skip(1);
+    let mut stripped = String::new();
+    for line in lines.by_ref() {
+        if line.trim_end() == SIGNATURE_BEGIN {
+            break;
+        }
+        stripped.push_str(line.strip_prefix("- ").unwrap_or(line));
+        stripped.push('\n');
+    }
+    stripped
+}
+
+/// A single paragraph (stanza) of deb822 data.
+///
+/// Field names are matched case-insensitively. Continuation lines of
+/// multi-line fields are joined with newlines, with the leading space of
+/// each continuation line removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Paragraph {
+    fields: Vec<(String, String)>,
+}
+
+impl Paragraph {
+    /// Get the value of a field, if present.
+    pub fn get(&self, field_name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(field_name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parse the value of a field, e.g. the `Size` of a package. Returns
+    /// `Ok(None)` if the field is absent.
+    pub fn get_parsed<T: FromStr>(&self, field_name: &str) -> Result<Option<T>, PkgError> {
+        self.get(field_name)
+            .map(|value| {
+                value.parse().map_err(|_| {
+                    PkgError::InvalidRepoData(format!("invalid {} field: {}", field_name, value))
+                })
+            })
+            .transpose()
+    }
+
+    /// Get the entries of a comma separated field, such as `Depends` or
+    /// `Binary` in a `.dsc`. Empty if the field is absent.
+    pub fn get_list(&self, field_name: &str) -> Vec<&str> {
+        self.get(field_name)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .collect()
+    }
+
+    /// Get the words of a whitespace separated field, such as `Components`
+    /// in a `Release` file or `Binary` in a `.changes`. Empty if the field is
+    /// absent.
+    pub fn get_words(&self, field_name: &str) -> Vec<&str> {
+        self.get(field_name)
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect()
+    }
+
+    /// Get the non-empty lines of a multi-line field, such as `Files` or
+    /// `Checksums-Sha256`, without the value on the first line.
+    pub fn get_lines(&self, field_name: &str) -> Vec<&str> {
+        self.get(field_name)
+            .unwrap_or_default()
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .collect()
+    }
+
+    /// Whether a field is present.
+    pub fn contains(&self, field_name: &str) -> bool {
+        self.get(field_name).is_some()
+    }
+
+    /// Iterate over the field names and values, in the order they appear.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The number of fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Whether the paragraph has no fields.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+/// A streaming reader over the paragraphs of a deb822 file, for indexes too
+/// large to hold in memory. Signatures are not stripped.
+pub struct Deb822Reader<R: BufRead> {
+    reader: R,
+    line: String,
+    done: bool,
+}
+
+impl<R: BufRead> Deb822Reader<R> {
+    /// Create a reader over deb822 data.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            done: false,
+        }
+    }
+
+    fn next_paragraph(&mut self) -> Result<Option<Paragraph>, PkgError> {
+        let mut paragraph = Paragraph::default();
+
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                // End of input; return the final paragraph if there is one.
+                return Ok(if paragraph.is_empty() {
+                    None
+                } else {
+                    Some(paragraph)
+                });
+            }
+
+            let line = self.line.trim_end_matches(&['\r', '\n'][..]);
+
+            if line.trim().is_empty() {
+                // Paragraph separator. Multiple blank lines are allowed.
+                if paragraph.is_empty() {
+                    continue;
+                }
+                return Ok(Some(paragraph));
+            }
+
+            if line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with(' ') || line.starts_with('\t') {
+                // Continuation of the previous field.
+                let (_, value) = paragraph.fields.last_mut().ok_or_else(|| {
+                    PkgError::InvalidRepoData(format!("unexpected continuation line: {}", line))
+                })?;
+                let continuation = &line[1..];
+
+                // A lone "." represents an empty line in a multi-line field.
+                let continuation = if continuation.trim() == "." {
+                    ""
+                } else {
+                    continuation
+                };
+                value.push('\n');
+                value.push_str(continuation);
+                continue;
+            }
+
+            let (name, value) = line.split_once(':').ok_or_else(|| {
+                PkgError::InvalidRepoData(format!("invalid field line: {}", line))
+            })?;
+            paragraph
+                .fields
+                .push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Deb822Reader<R> {
+    type Item = Result<Paragraph, PkgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.next_paragraph().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paragraphs() {
+        let data = "Package: a\nVersion: 1.0\nDescription: short\n long line\n .\n more\n\n\n# comment\npackage: b\nversion: 2.0\n";
+        let paragraphs: Vec<_> = Deb822Reader::new(data.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(paragraphs.len(), 2);
+        assert_eq!(paragraphs[0].get("package"), Some("a"));
+        assert_eq!(
+            paragraphs[0].get("Description"),
+            Some("short\nlong line\n\nmore")
+        );
+        assert_eq!(paragraphs[1].get("Package"), Some("b"));
+        assert_eq!(paragraphs[1].get("Missing"), None);
+    }
+
+    #[test]
+    fn test_typed_fields() {
+        let dsc = "-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA256
+
+Format: 3.0 (quilt)
+Source: hello
+Binary: hello, hello-dbgsym
+Architecture: any
+Testsuite-Triggers: dpkg-dev gcc
+Checksums-Sha256:
+ abcd 1000 hello_2.10.orig.tar.gz
+ ef01 200 hello_2.10-3.debian.tar.xz
+Size: 1200
+Bad-Size: lots
+- -Dashed: yes
+-----BEGIN PGP SIGNATURE-----
+
+iQIz
+-----END PGP SIGNATURE-----
+";
+        let paragraphs = parse(dsc).unwrap();
+        assert_eq!(paragraphs.len(), 1);
+        let dsc = &paragraphs[0];
+        assert_eq!(dsc.get_list("Binary"), vec!["hello", "hello-dbgsym"]);
+        assert_eq!(dsc.get_words("Testsuite-Triggers"), vec!["dpkg-dev", "gcc"]);
+        assert_eq!(
+            dsc.get_lines("Checksums-Sha256"),
+            vec![
+                "abcd 1000 hello_2.10.orig.tar.gz",
+                "ef01 200 hello_2.10-3.debian.tar.xz"
+            ]
+        );
+        assert_eq!(dsc.get_parsed::<u64>("Size").unwrap(), Some(1200));
+        assert_eq!(dsc.get_parsed::<u64>("Missing").unwrap(), None);
+        assert!(dsc.get_parsed::<u64>("Bad-Size").is_err());
+        assert_eq!(dsc.get("-Dashed"), Some("yes"));
+        assert!(dsc.get_list("Missing").is_empty());
+        assert_eq!(dsc.fields().next(), Some(("Format", "3.0 (quilt)")));
+        assert_eq!(dsc.len(), 9);
+        assert!(dsc.contains("format") && !dsc.contains("Files"));
+    }
+
+    #[test]
+    fn test_invalid_line() {
+        let mut reader = Deb822Reader::new(" orphan continuation\n".as_bytes());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+}
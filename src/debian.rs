@@ -1,16 +1,44 @@
 //! Support for remote Debian packages
 
-use std::{convert::TryFrom, io::Read};
+#[cfg(feature = "http-blocking")]
+use std::time::Duration;
+use std::{
+    convert::TryFrom,
+    io::{self, Read},
+    path::Path,
+    sync::Arc,
+};
 
 use debpkg::DebPkg;
 
-use crate::{PkgError, RemotePackage};
+use crate::arch::MultiArch;
+use crate::compression::Compression;
+use crate::limits::{Guard, Limits};
+use crate::payload::{find_ar_member, read_ar_magic, skip};
+use crate::{
+    Distribution, PackageRelation, PackageScript, PackageTrigger, PkgError, RelationKind,
+    RemotePackage,
+};
+
+/// The maintainer scripts that dpkg and debconf run, in the order they run
+/// on install and removal.
+const MAINTAINER_SCRIPTS: [&str; 5] = ["config", "preinst", "postinst", "prerm", "postrm"];
 
 /// A structure representing a remote Debian package.
-#[derive(Debug)]
+///
+/// The parsed control archive is shared, so clones are cheap.
+#[derive(Debug, Clone)]
 pub struct DebianRemotePackage {
     /// Structure containing the control portion of the remote Debian package
-    control: debpkg::Control,
+    control: Arc<debpkg::Control>,
+
+    /// The other files in the control archive, such as maintainer scripts,
+    /// by name.
+    control_files: Arc<[(String, Vec<u8>)]>,
+
+    /// The compression of the data archive, if the package was read past
+    /// the control archive.
+    payload_compression: Option<Compression>,
 }
 
 impl DebianRemotePackage {
@@ -18,41 +46,240 @@ impl DebianRemotePackage {
     ///
     /// Uses a blocking tokio client to download the remote package - if
     /// using this in an async environment, surround this with tokio::spawn_blocking.
-    #[cfg(feature = "http")]
+    #[cfg(feature = "http-blocking")]
     pub fn new_from_url(url: &str) -> Result<Self, PkgError> {
-        let client = reqwest::blocking::Client::new();
+        Self::fetch(url, None)
+    }
+
+    /// Attempts to create a `DebianRemotePackage` from a URL, waiting up to
+    /// `timeout` for the response and for each read of its body in place of
+    /// the read timeout of the client; see [`crate::from_url_with_timeout`].
+    ///
+    /// Uses a blocking tokio client to download the remote package - if
+    /// using this in an async environment, surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn new_from_url_with_timeout(url: &str, timeout: Duration) -> Result<Self, PkgError> {
+        Self::fetch(url, Some(timeout))
+    }
 
+    /// Download and parse a package for [`DebianRemotePackage::new_from_url`].
+    #[cfg(feature = "http-blocking")]
+    fn fetch(url: &str, timeout: Option<Duration>) -> Result<Self, PkgError> {
         // Send an HTTP request for the package and get the Response.
-        let response = client.get(url).send()?;
+        let request = crate::http::with_timeout(crate::http::client().get(url), timeout);
+        let response = crate::http::send(request)
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|err| err.in_context(url, None))?;
 
         // Response impls Read, so pass it to new_from_read().
-        Self::new_from_read(response)
+        let mut transfer = crate::http::Transfer::from_response(response)
+            .map_err(|err| err.in_context(url, None))?;
+        Self::new_from_read(&mut transfer)
+            .map_err(|err| err.in_context(url, Some(transfer.bytes_transferred())))
     }
 
     /// Attempts to create a `DebianRemotePackage` from something that impls
     /// Read.
+    ///
+    /// The default [`Limits`] are applied to the control archive.
     pub fn new_from_read<R: Read>(reader: R) -> Result<Self, PkgError> {
-        let pkg = debpkg::DebPkg::parse(reader)?;
+        Self::new_from_read_with_limits(reader, &Limits::default())
+    }
+
+    /// Attempts to create a `DebianRemotePackage` from something that impls
+    /// Read, applying the given limits to the decompressed control archive.
+    ///
+    /// The package is read as a stream up to the end of the control archive,
+    /// so the data archive is never read and memory use is bounded by the
+    /// limits rather than the size of the package.
+    ///
+    /// The ar and tar archives are read here rather than by `debpkg`, so the
+    /// control archive may be uncompressed or compressed with gzip, xz,
+    /// zstd or bzip2, as found in current distributions.
+    pub fn new_from_read_with_limits<R: Read>(
+        mut reader: R,
+        limits: &Limits,
+    ) -> Result<Self, PkgError> {
+        read_ar_magic(&mut reader)?;
+
+        // The format version comes first, and only version 2 is supported.
+        let size = find_ar_member(&mut reader, "debian-binary")?;
+        let mut version = Vec::new();
+        let _ = (&mut reader).take(size.min(16)).read_to_end(&mut version)?;
+        if !version.starts_with(b"2.") {
+            return Err(debpkg::Error::InvalidVersion.into());
+        }
+        skip(&mut reader, size - version.len() as u64 + size % 2)?;
+
+        let size = find_ar_member(&mut reader, "control.tar")?;
+        let guard = Guard::new(limits);
+        let mut member = (&mut reader).take(size);
+        let result =
+            Compression::detect_limited_decoder(&mut member, guard.max_decompression_memory())
+                .and_then(|archive| read_control_archive(archive, &guard));
+        let mut package = guard.finish(result)?;
+
+        // Only the start of the data archive is read, to find its
+        // compression.
+        let _ = io::copy(&mut member, &mut io::sink())?;
+        skip(&mut reader, size % 2)?;
+        let size = find_ar_member(&mut reader, "data.tar")?;
+        let mut magic = Vec::new();
+        let _ = reader.take(size.min(16)).read_to_end(&mut magic)?;
+        package.payload_compression = Some(Compression::from_magic(&magic));
+        Ok(package)
+    }
+
+    /// Find and parse the control file in the control archive of a package.
+    fn from_debpkg<T: Read>(mut pkg: DebPkg<T>, limits: &Limits) -> Result<Self, PkgError> {
+        let guard = Guard::new(limits);
+        let result = pkg
+            .control()
+            .map_err(PkgError::from)
+            .and_then(|archive| read_control_archive(archive.into_inner(), &guard));
+        guard.finish(result)
+    }
+}
+
+/// Read the files in a decompressed control archive through the guard.
+fn read_control_archive<R: Read>(
+    archive: R,
+    guard: &Guard<'_>,
+) -> Result<DebianRemotePackage, PkgError> {
+    let mut archive = tar::Archive::new(guard.reader(archive));
+
+    let mut control = None;
+    let mut control_files = Vec::new();
+    for entry in archive.entries()? {
+        guard.add_entries(1)?;
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        if path == Path::new("./control") || path == Path::new("control") {
+            // Parse the control information.
+            control = Some(debpkg::Control::parse(entry)?);
+        } else {
+            let mut contents = Vec::new();
+            let _ = entry.read_to_end(&mut contents)?;
+            let name = path.to_string_lossy();
+            control_files.push((name.trim_start_matches("./").to_owned(), contents));
+        }
+    }
+
+    let control = control.ok_or(debpkg::Error::MissingControlFile)?;
+    Ok(DebianRemotePackage {
+        control: Arc::new(control),
+        control_files: control_files.into(),
+        payload_compression: None,
+    })
+}
+
+impl DebianRemotePackage {
+    /// Get the compression of the data archive, e.g. [`Compression::Zstd`]
+    /// for `data.tar.zst`.
+    ///
+    /// This is `None` for a package converted from a [`DebPkg`], which
+    /// doesn't say.
+    pub fn payload_compression(&self) -> Option<Compression> {
+        self.payload_compression
+    }
+
+    /// Get the `Multi-Arch` field, which decides whether the package can be
+    /// installed for several architectures and which architectures it
+    /// satisfies dependencies of; see [`arch`](crate::arch).
+    pub fn multi_arch(&self) -> Result<MultiArch, PkgError> {
+        MultiArch::from_field(self.control.get("Multi-Arch"))
+    }
+
+    /// Get the name and version of the source package the package was built
+    /// from: those in its `Source` field, e.g. `hello (2.10-3)`, or else
+    /// its own.
+    pub fn source(&self) -> (&str, &str) {
+        let version = self.control.version();
+        match self.control.get("Source").map(str::trim) {
+            Some(source) => match source.split_once('(') {
+                Some((name, source_version)) => {
+                    (name.trim(), source_version.trim_end_matches(')').trim())
+                }
+                None => (source, version),
+            },
+            None => (self.control.name(), version),
+        }
+    }
+
+    /// Get the `Section` field, e.g. `utils` or `contrib/net`.
+    pub fn section(&self) -> Option<&str> {
+        self.control_field("Section")
+    }
+
+    /// Get the value of any field of the control file, e.g. `Built-Using`,
+    /// `Essential` or a vendor's `X-` field. The field name is case
+    /// insensitive.
+    pub fn control_field(&self, name: &str) -> Option<&str> {
+        self.control.get(name)
+    }
 
-        // Pass the package to the general constructor
-        Self::try_from(pkg)
+    /// Iterate over the fields of the control file as `(name, value)` pairs,
+    /// in the order they appear.
+    pub fn control_fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.control
+            .tags()
+            .filter_map(move |name| Some((name, self.control.get(name)?)))
+    }
+
+    /// Get the contents of a file in the control archive other than
+    /// `control` itself, e.g. `postinst` or `md5sums`.
+    pub fn control_file(&self, name: &str) -> Option<&[u8]> {
+        self.control_files
+            .iter()
+            .find(|(file, _)| file == name)
+            .map(|(_, contents)| contents.as_slice())
+    }
+
+    /// Get the MD5 digests of the installed files from `md5sums`, as
+    /// `(installed path, hex digest)` pairs. Conffiles aren't usually listed.
+    pub fn md5sums(&self) -> Vec<(String, String)> {
+        parse_md5sums(&String::from_utf8_lossy(
+            self.control_file("md5sums").unwrap_or_default(),
+        ))
+    }
+
+    /// Get a maintainer script, e.g. `preinst`, `postinst`, `prerm` or
+    /// `postrm`, as text.
+    pub fn maintainer_script(&self, name: &str) -> Option<String> {
+        self.control_file(name)
+            .map(|contents| String::from_utf8_lossy(contents).into_owned())
     }
 }
 
+/// Parse the `<md5>  <path>` lines of an `md5sums` file. Paths are relative
+/// to the root, so are made absolute.
+pub(crate) fn parse_md5sums(text: &str) -> Vec<(String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let (md5, path) = line.split_once(char::is_whitespace)?;
+            let path = path
+                .trim_start()
+                .trim_start_matches("./")
+                .trim_start_matches('/');
+            if path.is_empty() {
+                return None;
+            }
+            Some((format!("/{}", path), md5.to_owned()))
+        })
+        .collect()
+}
+
 impl<T> TryFrom<DebPkg<T>> for DebianRemotePackage
 where
     T: Read,
 {
     type Error = PkgError;
 
-    fn try_from(mut pkg: DebPkg<T>) -> Result<Self, Self::Error> {
-        // Get the control archive from the package.
-        let archive = pkg.control()?;
-
-        // Parse the control information.
-        let control = debpkg::Control::extract(archive)?;
-
-        Ok(Self { control })
+    fn try_from(pkg: DebPkg<T>) -> Result<Self, Self::Error> {
+        Self::from_debpkg(pkg, &Limits::default())
     }
 }
 
@@ -70,12 +297,125 @@ impl RemotePackage for DebianRemotePackage {
         Ok(self.control.version())
     }
 
+    /// The epoch before the `:` in the version, if there is one.
+    fn package_epoch(&self) -> Option<u32> {
+        let (epoch, _) = self.control.version().split_once(':')?;
+        epoch.parse().ok()
+    }
+
     fn package_arch(&self) -> Result<&str, PkgError> {
         self.control
             .get("Architecture")
             .ok_or_else(|| PkgError::DebianControlFieldNotFound("Architecture".to_string()))
     }
 
+    /// The synopsis on the first line, followed by the extended description.
+    fn package_description(&self) -> Option<&str> {
+        self.control.get("Description")
+    }
+
+    /// The `Origin` field, which few packages set.
+    fn package_vendor(&self) -> Option<&str> {
+        self.control.get("Origin")
+    }
+
+    /// Combines `Pre-Depends` and `Depends`, including every alternative.
+    fn package_dependencies(&self) -> Vec<String> {
+        let mut dependencies: Vec<String> = Vec::new();
+        let relations = ["Pre-Depends", "Depends"]
+            .iter()
+            .filter_map(|field| self.control.get(field))
+            .flat_map(|field| field.split([',', '|']));
+        for relation in relations {
+            // Strip the version constraint, architecture restriction and any
+            // `:any` qualifier.
+            let name = relation
+                .trim()
+                .split(|c: char| c.is_whitespace() || c == '(' || c == '[' || c == ':')
+                .next()
+                .unwrap_or_default();
+            if !name.is_empty() && !dependencies.iter().any(|d| d == name) {
+                dependencies.push(name.to_owned());
+            }
+        }
+        dependencies
+    }
+
+    /// Each entry of `Pre-Depends` and `Depends`, then of the `Recommends`,
+    /// `Suggests` and `Enhances` fields, with its alternatives.
+    fn package_relations(&self) -> Vec<PackageRelation> {
+        let fields = [
+            ("Pre-Depends", RelationKind::Depends),
+            ("Depends", RelationKind::Depends),
+            ("Recommends", RelationKind::Recommends),
+            ("Suggests", RelationKind::Suggests),
+            ("Enhances", RelationKind::Enhances),
+        ];
+        fields
+            .iter()
+            .filter_map(|(field, kind)| Some((self.control.get(field)?, *kind)))
+            .flat_map(|(value, kind)| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|relation| !relation.is_empty())
+                    .map(move |relation| PackageRelation {
+                        kind,
+                        relation: relation.to_owned(),
+                    })
+            })
+            .collect()
+    }
+
+    /// The paths listed in `conffiles`. Any flags before a path, such as
+    /// `remove-on-upgrade`, are dropped.
+    fn package_config_files(&self) -> Vec<String> {
+        let conffiles = String::from_utf8_lossy(self.control_file("conffiles").unwrap_or_default());
+        conffiles
+            .lines()
+            .filter_map(|line| line.split_whitespace().last())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// The interpreter is taken from the `#!` line of each script.
+    fn package_scripts(&self) -> Vec<PackageScript> {
+        MAINTAINER_SCRIPTS
+            .iter()
+            .filter_map(|name| {
+                let contents = self.maintainer_script(name)?;
+                let interpreter = contents
+                    .lines()
+                    .next()
+                    .and_then(|line| line.strip_prefix("#!"))
+                    .map(|line| line.trim().to_owned());
+                Some(PackageScript {
+                    name: (*name).to_owned(),
+                    interpreter,
+                    contents,
+                })
+            })
+            .collect()
+    }
+
+    /// The directives in the `triggers` control file, such as
+    /// `interest-noawait /usr/share/icons`. Comments are left out.
+    fn package_triggers(&self) -> Vec<PackageTrigger> {
+        let triggers = String::from_utf8_lossy(self.control_file("triggers").unwrap_or_default());
+        triggers
+            .lines()
+            .filter_map(|line| {
+                let line = line.split('#').next().unwrap_or_default();
+                let (kind, condition) = line.trim().split_once(char::is_whitespace)?;
+                Some(PackageTrigger {
+                    kind: kind.to_owned(),
+                    condition: condition.trim().to_owned(),
+                    script: None,
+                })
+            })
+            .collect()
+    }
+
     /// For Debian, the package iteration is the debian_revision.
     fn package_iteration(&self) -> Option<&str> {
         // Start by getting the version.
@@ -85,17 +425,415 @@ impl RemotePackage for DebianRemotePackage {
         // the matched suffix.
         version.rsplit_once('-').map(|(_prefix, suffix)| suffix)
     }
+
+    /// The suffix of the Debian revision from its first letter, e.g.
+    /// `ubuntu22.04` in `2.10-3ubuntu22.04` or `deb12u1` in `1.0-1+deb12u1`.
+    /// Debian packages don't record the name of their distribution.
+    fn package_distribution(&self) -> Distribution {
+        let tag = self.package_iteration().and_then(|revision| {
+            revision
+                .find(|c: char| c.is_ascii_alphabetic())
+                .map(|start| revision[start..].to_owned())
+        });
+        Distribution { name: None, tag }
+    }
+}
+
+/// Build an ar archive of `members`, as `(name, data)` pairs.
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn build_deb_members(members: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut deb = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name,
+            0,
+            0,
+            0,
+            100644,
+            data.len()
+        );
+        deb.extend_from_slice(header.as_bytes());
+        deb.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            deb.push(b'\n');
+        }
+    }
+    deb
+}
+
+/// Build an uncompressed tar of regular files.
+///
+/// Panics if a path can't be stored in a tar archive, e.g. one with `..`.
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn build_tar(files: Vec<&(&str, &[u8])>) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    for (path, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, path, *data).unwrap();
+    }
+    builder.into_inner().unwrap()
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
-    #[cfg(feature = "http")]
+    /// Control file of the package built by [`build_deb`].
+    pub(crate) const CONTROL: &str = "Package: hello
+Version: 2.10-3
+Architecture: amd64
+Pre-Depends: dpkg (>= 1.15)
+Depends: libc6 (>= 2.34), python3:any | python3-minimal [amd64], dpkg
+";
+
+    /// Build a minimal package whose control archive holds `files` followed by
+    /// [`CONTROL`].
+    pub(crate) fn build_deb(files: &[(&str, &[u8])]) -> Vec<u8> {
+        build_deb_with_data(files, &[])
+    }
+
+    /// Build a minimal package like [`build_deb`], with `data` in its data
+    /// archive.
+    pub(crate) fn build_deb_with_data(files: &[(&str, &[u8])], data: &[(&str, &[u8])]) -> Vec<u8> {
+        build_deb_with_data_tar(files, &build_tar(data.iter().collect()))
+    }
+
+    /// Build a minimal package like [`build_deb`], with an already built
+    /// data archive.
+    pub(crate) fn build_deb_with_data_tar(files: &[(&str, &[u8])], data_tar: &[u8]) -> Vec<u8> {
+        build_deb_members(&[
+            ("debian-binary", b"2.0\n"),
+            ("control.tar", &build_control_tar(files)),
+            ("data.tar", data_tar),
+        ])
+    }
+
+    /// Build an uncompressed control archive holding `files` followed by
+    /// [`CONTROL`].
+    fn build_control_tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let control = ("./control", CONTROL.as_bytes());
+        build_tar(files.iter().chain(std::iter::once(&control)).collect())
+    }
+
+    #[test]
+    fn test_package_from_read() {
+        let deb = build_deb(&[("./md5sums", b"")]);
+        let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(package.package_arch().unwrap(), "amd64");
+        assert_eq!(package.package_iteration(), Some("3"));
+        assert_eq!(
+            package.package_dependencies(),
+            vec!["dpkg", "libc6", "python3", "python3-minimal"]
+        );
+        assert_eq!(package.package_distribution(), Distribution::default());
+        assert_eq!(package.package_vendor(), None);
+        assert_eq!(package.multi_arch().unwrap(), MultiArch::No);
+
+        let relations: Vec<_> = package
+            .package_relations()
+            .into_iter()
+            .map(|relation| (relation.kind, relation.relation))
+            .collect();
+        assert_eq!(
+            relations,
+            vec![
+                (RelationKind::Depends, "dpkg (>= 1.15)".to_string()),
+                (RelationKind::Depends, "libc6 (>= 2.34)".to_string()),
+                (
+                    RelationKind::Depends,
+                    "python3:any | python3-minimal [amd64]".to_string()
+                ),
+                (RelationKind::Depends, "dpkg".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_package_distribution() {
+        for (version, tag) in &[
+            ("2.10-3ubuntu22.04", Some("ubuntu22.04")),
+            ("1.0-1+deb12u1", Some("deb12u1")),
+            ("1.0-1~bpo11+1", Some("bpo11+1")),
+            ("1.0", None),
+        ] {
+            let control = format!(
+                "Package: hello\nVersion: {}\nArchitecture: all\nOrigin: Ubuntu\nSuggests: hello-doc\nMulti-Arch: foreign\n",
+                version
+            );
+            let deb = build_deb_members(&[
+                ("debian-binary", b"2.0\n"),
+                (
+                    "control.tar",
+                    &build_tar(vec![&("./control", control.as_bytes())]),
+                ),
+                ("data.tar", &build_tar(Vec::new())),
+            ]);
+            let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+            assert_eq!(package.package_distribution().tag.as_deref(), *tag);
+            assert_eq!(package.package_vendor(), Some("Ubuntu"));
+            let relations = package.package_relations();
+            assert_eq!(relations[0].kind, RelationKind::Suggests);
+            assert!(relations[0].kind.is_weak());
+            assert_eq!(package.multi_arch().unwrap(), MultiArch::Foreign);
+        }
+    }
+
+    #[test]
+    fn test_control_fields() {
+        let control = "Package: hello\nVersion: 1.0\nArchitecture: all\nEssential: yes\nBuilt-Using: gcc-12 (= 12.2.0-14)\nXB-Vendor-Thing: value\n";
+        let deb = build_deb_members(&[
+            ("debian-binary", b"2.0\n"),
+            (
+                "control.tar",
+                &build_tar(vec![&("./control", control.as_bytes())]),
+            ),
+            ("data.tar", &build_tar(Vec::new())),
+        ]);
+        let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+
+        assert_eq!(
+            package.control_field("built-using"),
+            Some("gcc-12 (= 12.2.0-14)")
+        );
+        assert_eq!(package.control_field("XB-Vendor-Thing"), Some("value"));
+        assert_eq!(package.control_field("Section"), None);
+        let names: Vec<_> = package.control_fields().map(|(name, _)| name).collect();
+        assert_eq!(
+            names,
+            vec![
+                "Package",
+                "Version",
+                "Architecture",
+                "Essential",
+                "Built-Using",
+                "XB-Vendor-Thing"
+            ]
+        );
+        assert!(package
+            .control_fields()
+            .any(|field| field == ("Essential", "yes")));
+    }
+
+    #[test]
+    fn test_package_scripts() {
+        let deb = build_deb(&[
+            ("./md5sums", b""),
+            ("./postinst", b"#!/bin/sh -e\nldconfig\n"),
+            ("./prerm", b"echo bye\n"),
+            (
+                "./triggers",
+                b"# Rebuild the cache\ninterest-noawait /usr/share/hello\n\nactivate ldconfig\n",
+            ),
+            (
+                "./conffiles",
+                b"/etc/hello.conf\nremove-on-upgrade /etc/hello/old.conf\n",
+            ),
+        ]);
+        let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+
+        assert_eq!(
+            package.package_config_files(),
+            vec!["/etc/hello.conf", "/etc/hello/old.conf"]
+        );
+
+        assert_eq!(package.control_file("md5sums"), Some(&b""[..]));
+        assert!(package.md5sums().is_empty());
+        assert_eq!(
+            parse_md5sums("9d7183f16acce70658f686ae7f1a4d20  usr/bin/hello\n\n"),
+            vec![(
+                "/usr/bin/hello".to_string(),
+                "9d7183f16acce70658f686ae7f1a4d20".to_string()
+            )]
+        );
+        assert_eq!(
+            package.package_triggers(),
+            vec![
+                PackageTrigger {
+                    kind: "interest-noawait".to_string(),
+                    condition: "/usr/share/hello".to_string(),
+                    script: None,
+                },
+                PackageTrigger {
+                    kind: "activate".to_string(),
+                    condition: "ldconfig".to_string(),
+                    script: None,
+                },
+            ]
+        );
+        assert_eq!(package.control_file("control"), None);
+        assert_eq!(
+            package.package_scripts(),
+            vec![
+                PackageScript {
+                    name: "postinst".to_string(),
+                    interpreter: Some("/bin/sh -e".to_string()),
+                    contents: "#!/bin/sh -e\nldconfig\n".to_string(),
+                },
+                PackageScript {
+                    name: "prerm".to_string(),
+                    interpreter: None,
+                    contents: "echo bye\n".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_package_limits() {
+        let padding = vec![0; 4096];
+        let deb = build_deb(&[("./md5sums", &padding)]);
+
+        let limits = Limits {
+            max_metadata_size: Some(4096),
+            ..Limits::unlimited()
+        };
+        assert!(matches!(
+            DebianRemotePackage::new_from_read_with_limits(deb.as_slice(), &limits),
+            Err(PkgError::SizeLimitExceeded(4096))
+        ));
+
+        let limits = Limits {
+            max_entries: Some(1),
+            ..Limits::unlimited()
+        };
+        assert!(matches!(
+            DebianRemotePackage::new_from_read_with_limits(deb.as_slice(), &limits),
+            Err(PkgError::EntryLimitExceeded(1))
+        ));
+
+        let limits = Limits {
+            max_entries: Some(2),
+            ..Limits::unlimited()
+        };
+        assert!(DebianRemotePackage::new_from_read_with_limits(deb.as_slice(), &limits).is_ok());
+    }
+
+    #[test]
+    fn test_package_from_read_bounded() {
+        // Declare a huge data archive; only the members before it are read.
+        let mut deb = build_deb(&[]);
+        let header = deb.windows(8).position(|name| name == b"data.tar").unwrap();
+        deb[header + 48..header + 58].copy_from_slice(b"9999999999");
+        let mut reader = deb.as_slice().chain(io::repeat(0).take(u64::MAX));
+        let package = DebianRemotePackage::new_from_read(&mut reader).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(reader.get_ref().1.limit(), u64::MAX);
+
+        let deb = build_deb_members(&[("debian-binary", b"3.0\n")]);
+        assert!(matches!(
+            DebianRemotePackage::new_from_read(deb.as_slice()),
+            Err(PkgError::DebPkgError(debpkg::Error::InvalidVersion))
+        ));
+    }
+
+    #[test]
+    fn test_package_control_compression() {
+        use std::io::Write;
+
+        let control_tar = build_control_tar(&[]);
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&control_tar).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(&control_tar).unwrap();
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(&control_tar).unwrap();
+
+        let members = [
+            ("control.tar", control_tar.clone()),
+            ("control.tar.gz", gzip.finish().unwrap()),
+            ("control.tar.xz", xz.finish().unwrap()),
+            (
+                "control.tar.zst",
+                zstd::encode_all(control_tar.as_slice(), 19).unwrap(),
+            ),
+            ("control.tar.bz2", bzip2.finish().unwrap()),
+        ];
+        for (name, control) in &members {
+            let deb = build_deb_members(&[
+                ("debian-binary", b"2.0\n"),
+                (name, control),
+                ("data.tar", &build_tar(Vec::new())),
+            ]);
+            let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+            assert_eq!(package.package_name().unwrap(), "hello", "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_package_memory_limit() {
+        use std::io::Write;
+
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 1);
+        encoder.write_all(&build_control_tar(&[])).unwrap();
+        let control_tar = encoder.finish().unwrap();
+        let deb = build_deb_members(&[
+            ("debian-binary", b"2.0\n"),
+            ("control.tar.xz", &control_tar),
+            ("data.tar", &build_tar(Vec::new())),
+        ]);
+
+        let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+
+        let limits = Limits {
+            max_decompression_memory: Some(64 * 1024),
+            ..Limits::unlimited()
+        };
+        assert!(matches!(
+            DebianRemotePackage::new_from_read_with_limits(deb.as_slice(), &limits),
+            Err(PkgError::MemoryLimitExceeded(65536))
+        ));
+    }
+
+    #[test]
+    fn test_package_zstd_memory_limit() {
+        use std::io::Write;
+
+        let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.include_contentsize(false).unwrap();
+        encoder.window_log(20).unwrap();
+        encoder.write_all(&build_control_tar(&[])).unwrap();
+        let control_tar = encoder.finish().unwrap();
+        let build = |control_tar: &[u8]| {
+            build_deb_members(&[
+                ("debian-binary", b"2.0\n"),
+                ("control.tar.zst", control_tar),
+                ("data.tar", &build_tar(Vec::new())),
+            ])
+        };
+        let deb = build(&control_tar);
+
+        let package = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+
+        let limits = Limits {
+            max_decompression_memory: Some(64 * 1024),
+            ..Limits::unlimited()
+        };
+        assert!(matches!(
+            DebianRemotePackage::new_from_read_with_limits(deb.as_slice(), &limits),
+            Err(PkgError::MemoryLimitExceeded(65536))
+        ));
+
+        // A corrupt frame is not mistaken for the memory limit.
+        let deb = build(&control_tar[..control_tar.len() / 2]);
+        let err = DebianRemotePackage::new_from_read(deb.as_slice()).unwrap_err();
+        assert!(!matches!(err, PkgError::MemoryLimitExceeded(_)), "{}", err);
+    }
+
+    #[cfg(feature = "testing")]
     #[test]
     fn test_package() {
-        // Pick a random small package from the Ubuntu focal LTS
         let url = "http://cz.archive.ubuntu.com/ubuntu/pool/universe/d/debian-faq/debian-faq_10.1_all.deb";
+        let deb = crate::testing::DebBuilder::new("debian-faq", "10.1", "all").build();
+        let _mock = crate::testing::MockTransport::new()
+            .with_body(url, deb)
+            .install();
 
         let deb = DebianRemotePackage::new_from_url(url).expect("Failed to download package");
         assert_eq!(deb.package_name().unwrap(), "debian-faq");
@@ -0,0 +1,302 @@
+//! Support for finding historical Debian packages on snapshot.debian.org.
+//!
+//! The snapshot archive keeps every file ever published to the Debian
+//! archives. Its machine-readable API maps a binary package name and version
+//! to the files that were published, and each archive can be browsed as an
+//! ordinary apt repository as it was at any point in time.
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::PkgError;
+
+/// The public snapshot.debian.org service.
+pub const SNAPSHOT_URL: &str = "https://snapshot.debian.org";
+
+/// A published version of a binary package.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SnapshotBinary {
+    /// The binary package name.
+    pub name: String,
+
+    /// The binary package version.
+    pub binary_version: String,
+
+    /// The source package the binary was built from.
+    pub source: String,
+
+    /// The source package version.
+    pub version: String,
+}
+
+/// A `.deb` file known to the snapshot archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotFile {
+    /// The SHA1 hash of the file, which identifies it in the archive.
+    pub hash: String,
+
+    /// The package architecture, e.g. `amd64` or `all`.
+    pub architecture: String,
+
+    /// The file name, e.g. `hello_2.10-2_amd64.deb`.
+    pub name: Option<String>,
+
+    /// The archive the file was first seen in, e.g. `debian`.
+    pub archive_name: Option<String>,
+
+    /// The directory of the file within the archive.
+    pub path: Option<String>,
+
+    /// When the file was first seen, e.g. `20160302T092015Z`.
+    pub first_seen: Option<String>,
+
+    /// The file size in bytes.
+    pub size: Option<u64>,
+}
+
+impl SnapshotFile {
+    /// Get the URL of the file in the snapshot archive at `base_url`.
+    ///
+    /// When the file's location is known this is the path within the
+    /// archive at the time it was first seen, which preserves the original
+    /// file name; otherwise the file is addressed by its hash.
+    pub fn url(&self, base_url: &str) -> String {
+        let base_url = base_url.trim_end_matches('/');
+        match (&self.archive_name, &self.first_seen, &self.path, &self.name) {
+            (Some(archive), Some(first_seen), Some(path), Some(name)) => format!(
+                "{}/archive/{}/{}/{}/{}",
+                base_url,
+                archive,
+                first_seen,
+                path.trim_matches('/'),
+                name
+            ),
+            _ => format!("{}/file/{}", base_url, self.hash),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResult<T> {
+    result: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinFile {
+    hash: String,
+    architecture: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BinFilesResult {
+    result: Vec<BinFile>,
+
+    #[serde(default)]
+    fileinfo: BTreeMap<String, Vec<FileInfo>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FileInfo {
+    name: String,
+    archive_name: String,
+    path: String,
+    first_seen: String,
+    size: u64,
+}
+
+/// Parse the response listing the versions of a binary package
+/// (`/mr/binary/<name>/`).
+pub fn parse_binary_versions(json: &str) -> Result<Vec<SnapshotBinary>, PkgError> {
+    let result: ApiResult<SnapshotBinary> = serde_json::from_str(json)?;
+    Ok(result.result)
+}
+
+/// Parse the response listing the files of a binary package version
+/// (`/mr/binary/<name>/<version>/binfiles?fileinfo=1`).
+pub fn parse_binfiles(json: &str) -> Result<Vec<SnapshotFile>, PkgError> {
+    let BinFilesResult { result, fileinfo } = serde_json::from_str(json)?;
+
+    let files = result
+        .into_iter()
+        .map(|file| {
+            // A file can appear in several archives; take the first listed.
+            let info = fileinfo
+                .get(&file.hash)
+                .and_then(|infos| infos.first())
+                .cloned();
+            SnapshotFile {
+                name: info.as_ref().map(|i| i.name.clone()),
+                archive_name: info.as_ref().map(|i| i.archive_name.clone()),
+                path: info.as_ref().map(|i| i.path.clone()),
+                first_seen: info.as_ref().map(|i| i.first_seen.clone()),
+                size: info.map(|i| i.size),
+                hash: file.hash,
+                architecture: file.architecture,
+            }
+        })
+        .collect();
+    Ok(files)
+}
+
+/// Get the URL of an archive as it was at `timestamp`, which can be used as
+/// the base URL of an apt repository.
+///
+/// Timestamps are in the form `YYYYMMDDTHHMMSSZ`, e.g. `20200101T000000Z`;
+/// the snapshot at or before that time is used.
+pub fn archive_url(base_url: &str, archive: &str, timestamp: &str) -> String {
+    format!(
+        "{}/archive/{}/{}",
+        base_url.trim_end_matches('/'),
+        archive,
+        timestamp
+    )
+}
+
+/// Pick the file for `arch` from the files of a package version, falling
+/// back to an architecture-independent (`all`) file.
+pub fn select_file(files: Vec<SnapshotFile>, arch: &str) -> Option<SnapshotFile> {
+    let (exact, rest): (Vec<_>, Vec<_>) = files.into_iter().partition(|f| f.architecture == arch);
+    exact
+        .into_iter()
+        .next()
+        .or_else(|| rest.into_iter().find(|f| f.architecture == "all"))
+}
+
+/// A handle on a snapshot.debian.org service.
+#[derive(Debug, Clone)]
+pub struct DebianSnapshot {
+    base_url: String,
+}
+
+impl DebianSnapshot {
+    /// Create a handle on the snapshot service at `base_url`, e.g.
+    /// [`SNAPSHOT_URL`].
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Get the base URL of the snapshot service.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get an apt repository for `archive` (e.g. `debian`) as it was at
+    /// `timestamp`. See [`archive_url`] for the timestamp format.
+    #[cfg(feature = "repo-deb")]
+    pub fn repo_at<S: AsRef<str>>(
+        &self,
+        archive: &str,
+        timestamp: &str,
+        dist: &str,
+        components: &[S],
+        arch: &str,
+    ) -> crate::repo_deb::AptRepo {
+        let base_url = archive_url(&self.base_url, archive, timestamp);
+        crate::repo_deb::AptRepo::new(&base_url, dist, components, arch)
+    }
+
+    /// Fetch every version of a binary package known to the archive.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn binary_versions(&self, name: &str) -> Result<Vec<SnapshotBinary>, PkgError> {
+        let url = format!("{}/mr/binary/{}/", self.base_url, name);
+        let response = crate::http::get(&url)?;
+        parse_binary_versions(&response.text()?)
+    }
+
+    /// Fetch the files published for a version of a binary package.
+    #[cfg(feature = "http-blocking")]
+    pub fn files(&self, name: &str, version: &str) -> Result<Vec<SnapshotFile>, PkgError> {
+        let url = format!(
+            "{}/mr/binary/{}/{}/binfiles?fileinfo=1",
+            self.base_url, name, version
+        );
+        let response = crate::http::get(&url)?;
+        parse_binfiles(&response.text()?)
+    }
+
+    /// Find the `.deb` for a version of a binary package on `arch`, falling
+    /// back to an `all` package. Returns the file and its download URL, or
+    /// `None` if the archive has no matching file.
+    #[cfg(feature = "http-blocking")]
+    pub fn resolve(
+        &self,
+        name: &str,
+        version: &str,
+        arch: &str,
+    ) -> Result<Option<(SnapshotFile, String)>, PkgError> {
+        let file = select_file(self.files(name, version)?, arch);
+        Ok(file.map(|file| {
+            let url = file.url(&self.base_url);
+            (file, url)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINFILES: &str = r#"{
+        "_comment": "foo",
+        "binary": "hello",
+        "binary_version": "2.10-2",
+        "fileinfo": {
+            "aaaa": [{"archive_name": "debian", "first_seen": "20160302T092015Z",
+                      "name": "hello_2.10-2_amd64.deb", "path": "/pool/main/h/hello", "size": 56132}],
+            "bbbb": [{"archive_name": "debian", "first_seen": "20160302T092015Z",
+                      "name": "hello_2.10-2_i386.deb", "path": "/pool/main/h/hello", "size": 56908}]
+        },
+        "result": [
+            {"architecture": "amd64", "hash": "aaaa"},
+            {"architecture": "i386", "hash": "bbbb"},
+            {"architecture": "arm64", "hash": "cccc"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_binary_versions() {
+        let versions = parse_binary_versions(
+            r#"{"_comment": "foo", "binary": "hello", "result": [
+                {"binary_version": "2.10-2", "name": "hello", "source": "hello", "version": "2.10-2"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(versions[0].binary_version, "2.10-2");
+        assert_eq!(versions[0].source, "hello");
+    }
+
+    #[test]
+    fn test_binfiles() {
+        let files = parse_binfiles(BINFILES).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].size, Some(56132));
+        assert_eq!(
+            files[0].url(SNAPSHOT_URL),
+            "https://snapshot.debian.org/archive/debian/20160302T092015Z/pool/main/h/hello/hello_2.10-2_amd64.deb"
+        );
+
+        // Without file information, fall back to the hash.
+        assert_eq!(files[2].name, None);
+        assert_eq!(
+            files[2].url(SNAPSHOT_URL),
+            "https://snapshot.debian.org/file/cccc"
+        );
+    }
+
+    #[test]
+    fn test_select_file() {
+        let files = parse_binfiles(BINFILES).unwrap();
+        assert_eq!(select_file(files.clone(), "i386").unwrap().hash, "bbbb");
+        assert!(select_file(files, "riscv64").is_none());
+
+        assert_eq!(
+            archive_url(SNAPSHOT_URL, "debian", "20200101T000000Z"),
+            "https://snapshot.debian.org/archive/debian/20200101T000000Z"
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Comparison of two packages, such as two builds of the same package, to
+//! report what changed between them.
+//!
+//! [`diff`] compares the metadata of the packages, including the paths of
+//! the files they install when the metadata lists them. Comparing the
+//! contents of files needs the entries of both payloads; see
+//! [`PackageDiff::with_file_entries`].
+use std::collections::BTreeSet;
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use crate::payload::FileEntry;
+use crate::RemotePackage;
+
+/// A metadata field whose value differs between two packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    /// The name of the field, e.g. `version`.
+    pub field: &'static str,
+
+    /// The value in the old package, if it has one.
+    pub old: Option<String>,
+
+    /// The value in the new package, if it has one.
+    pub new: Option<String>,
+}
+
+/// A file installed by both packages whose metadata or contents differ.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// The entry in the old package.
+    pub old: FileEntry,
+
+    /// The entry in the new package.
+    pub new: FileEntry,
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+impl FileChange {
+    /// The installed path of the file.
+    pub fn path(&self) -> &str {
+        &self.new.path
+    }
+
+    /// Whether the contents changed, judged by the digests when both are
+    /// known with the same algorithm, or else by the sizes.
+    pub fn contents_changed(&self) -> bool {
+        match (&self.old.digest, &self.new.digest) {
+            (Some((old, old_algorithm)), Some((new, new_algorithm)))
+                if old_algorithm == new_algorithm =>
+            {
+                old != new
+            }
+            _ => self.old.size != self.new.size,
+        }
+    }
+}
+
+/// The differences between the files of two packages.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileDiff {
+    /// The paths only the new package installs, sorted.
+    pub added: Vec<String>,
+
+    /// The paths only the old package installs, sorted.
+    pub removed: Vec<String>,
+
+    /// The files both packages install that differ, sorted by path. This is
+    /// only filled in by [`PackageDiff::with_file_entries`].
+    #[cfg(any(feature = "debian", feature = "rpm"))]
+    pub changed: Vec<FileChange>,
+}
+
+impl FileDiff {
+    /// Whether the files are the same.
+    pub fn is_empty(&self) -> bool {
+        #[cfg(any(feature = "debian", feature = "rpm"))]
+        if !self.changed.is_empty() {
+            return false;
+        }
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The differences between two packages; see [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageDiff {
+    /// The metadata fields that differ, in a fixed order.
+    pub fields: Vec<FieldChange>,
+
+    /// The dependencies only the new package declares, sorted.
+    pub added_dependencies: Vec<String>,
+
+    /// The dependencies only the old package declares, sorted.
+    pub removed_dependencies: Vec<String>,
+
+    /// The differences between the installed files.
+    pub files: FileDiff,
+}
+
+impl PackageDiff {
+    /// Compare the files of the packages by their payload entries, as read
+    /// with [`payload::files`](crate::payload::files), replacing the
+    /// comparison of paths made from the metadata. Files are compared by
+    /// digest, size, mode, owner and link target; modification times are
+    /// ignored, as they differ between otherwise identical builds.
+    #[cfg(any(feature = "debian", feature = "rpm"))]
+    pub fn with_file_entries(mut self, old: &[FileEntry], new: &[FileEntry]) -> Self {
+        let mut files = FileDiff::default();
+        for entry in new {
+            match old.iter().find(|old_entry| old_entry.path == entry.path) {
+                Some(old_entry) => {
+                    let change = FileChange {
+                        old: old_entry.clone(),
+                        new: entry.clone(),
+                    };
+                    if change.contents_changed()
+                        || old_entry.mode != entry.mode
+                        || old_entry.user != entry.user
+                        || old_entry.group != entry.group
+                        || old_entry.link_target != entry.link_target
+                    {
+                        files.changed.push(change);
+                    }
+                }
+                None => files.added.push(entry.path.clone()),
+            }
+        }
+        files.removed = old
+            .iter()
+            .filter(|old_entry| !new.iter().any(|entry| entry.path == old_entry.path))
+            .map(|old_entry| old_entry.path.clone())
+            .collect();
+
+        files.added.sort();
+        files.removed.sort();
+        files.changed.sort_by(|a, b| a.path().cmp(b.path()));
+        self.files = files;
+        self
+    }
+
+    /// Whether the packages are the same, as far as was compared.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.added_dependencies.is_empty()
+            && self.removed_dependencies.is_empty()
+            && self.files.is_empty()
+    }
+}
+
+/// Compare the metadata of two packages: the name, version, iteration,
+/// architecture, license, vendor and description, the declared dependencies, and
+/// the paths of the installed files when the metadata lists them.
+pub fn diff(old: &dyn RemotePackage, new: &dyn RemotePackage) -> PackageDiff {
+    let field_values = |package: &dyn RemotePackage| -> [(&'static str, Option<String>); 7] {
+        [
+            ("name", package.package_name().ok().map(str::to_owned)),
+            ("version", package.package_version().ok().map(str::to_owned)),
+            ("iteration", package.package_iteration().map(str::to_owned)),
+            ("arch", package.package_arch().ok().map(str::to_owned)),
+            ("license", package.package_license().map(str::to_owned)),
+            ("vendor", package.package_vendor().map(str::to_owned)),
+            (
+                "description",
+                package.package_description().map(str::to_owned),
+            ),
+        ]
+    };
+    let fields = field_values(old)
+        .iter()
+        .zip(field_values(new).iter())
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| FieldChange {
+            field,
+            old: old.clone(),
+            new: new.clone(),
+        })
+        .collect();
+
+    let (added_dependencies, removed_dependencies) =
+        set_diff(old.package_dependencies(), new.package_dependencies());
+    let (added, removed) = set_diff(old.package_files(), new.package_files());
+
+    PackageDiff {
+        fields,
+        added_dependencies,
+        removed_dependencies,
+        files: FileDiff {
+            added,
+            removed,
+            #[cfg(any(feature = "debian", feature = "rpm"))]
+            changed: Vec::new(),
+        },
+    }
+}
+
+/// Get the sorted values only in `new`, and those only in `old`.
+fn set_diff(old: Vec<String>, new: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let old: BTreeSet<String> = old.into_iter().collect();
+    let new: BTreeSet<String> = new.into_iter().collect();
+    (
+        new.difference(&old).cloned().collect(),
+        old.difference(&new).cloned().collect(),
+    )
+}
+
+#[cfg(all(test, feature = "rpm"))]
+mod tests {
+    use super::*;
+    use crate::{PkgError, RemotePackageType};
+
+    /// A package with just the metadata the comparison reads.
+    struct Package {
+        version: &'static str,
+        dependencies: &'static [&'static str],
+        files: &'static [&'static str],
+    }
+
+    impl RemotePackage for Package {
+        fn package_type(&self) -> RemotePackageType {
+            RemotePackageType::Rpm
+        }
+
+        fn package_name(&self) -> Result<&str, PkgError> {
+            Ok("hello")
+        }
+
+        fn package_version(&self) -> Result<&str, PkgError> {
+            Ok(self.version)
+        }
+
+        fn package_iteration(&self) -> Option<&str> {
+            None
+        }
+
+        fn package_arch(&self) -> Result<&str, PkgError> {
+            Ok("x86_64")
+        }
+
+        fn package_files(&self) -> Vec<String> {
+            self.files.iter().map(|file| file.to_string()).collect()
+        }
+
+        fn package_dependencies(&self) -> Vec<String> {
+            self.dependencies
+                .iter()
+                .map(|dep| dep.to_string())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = Package {
+            version: "1.0",
+            dependencies: &["glibc", "libfoo"],
+            files: &["/usr/bin/hello", "/usr/share/doc/hello"],
+        };
+        let new = Package {
+            version: "1.1",
+            dependencies: &["glibc", "libbar"],
+            files: &["/usr/bin/hello", "/usr/lib/libhello.so"],
+        };
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes.fields,
+            vec![FieldChange {
+                field: "version",
+                old: Some("1.0".to_string()),
+                new: Some("1.1".to_string()),
+            }]
+        );
+        assert_eq!(changes.added_dependencies, vec!["libbar"]);
+        assert_eq!(changes.removed_dependencies, vec!["libfoo"]);
+        assert_eq!(changes.files.added, vec!["/usr/lib/libhello.so"]);
+        assert_eq!(changes.files.removed, vec!["/usr/share/doc/hello"]);
+        assert!(!changes.is_empty());
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_with_file_entries() {
+        let entry = |path: &str, digest: &str, mode: u32| FileEntry {
+            path: path.to_string(),
+            mode,
+            user: Some("root".to_string()),
+            group: Some("root".to_string()),
+            uid: None,
+            gid: None,
+            size: 6,
+            mtime: 0,
+            digest: Some((digest.to_string(), "sha256".to_string())),
+            link_target: None,
+            hardlinks: Vec::new(),
+        };
+        let old = vec![
+            entry("/usr/bin/hello", "aa", 0o100755),
+            entry("/usr/lib/old.so", "bb", 0o100644),
+            entry("/etc/hello.conf", "cc", 0o100644),
+        ];
+        let mut new = vec![
+            entry("/usr/bin/hello", "dd", 0o100755),
+            entry("/usr/lib/new.so", "bb", 0o100644),
+            entry("/etc/hello.conf", "cc", 0o100600),
+        ];
+        new[2].mtime = 1;
+
+        let files = PackageDiff::default().with_file_entries(&old, &new).files;
+        assert_eq!(files.added, vec!["/usr/lib/new.so"]);
+        assert_eq!(files.removed, vec!["/usr/lib/old.so"]);
+        let changed: Vec<_> = files
+            .changed
+            .iter()
+            .map(|change| (change.path(), change.contents_changed()))
+            .collect();
+        assert_eq!(
+            changed,
+            vec![("/etc/hello.conf", false), ("/usr/bin/hello", true)]
+        );
+
+        let same = PackageDiff::default().with_file_entries(&old, &old);
+        assert!(same.is_empty());
+    }
+}
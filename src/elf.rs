@@ -0,0 +1,1143 @@
+//! Inspection of the ELF binaries and shared libraries in a package, to get
+//! the libraries they need and the sonames they provide.
+//!
+//! This gives the same automatic dependencies that rpmbuild generates with
+//! `elfdeps`, e.g. `libc.so.6(GLIBC_2.34)(64bit)`, so that they can be
+//! checked against what a package declares. Only the dynamic section is
+//! read; symbols and debug information aren't.
+//!
+//! [`find_bundled_libraries`] builds on this to flag files that carry their
+//! own copies of common libraries.
+use std::convert::TryFrom;
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use std::io::Read;
+
+use crate::PkgError;
+
+/// The magic at the start of every ELF file.
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// Program header types.
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+/// Dynamic section tags.
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_STRSZ: u64 = 10;
+const DT_SONAME: u64 = 14;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+const DT_FLAGS_1: u64 = 0x6fff_fffb;
+const DT_VERDEF: u64 = 0x6fff_fffc;
+const DT_VERDEFNUM: u64 = 0x6fff_fffd;
+const DT_VERNEED: u64 = 0x6fff_fffe;
+const DT_VERNEEDNUM: u64 = 0x6fff_ffff;
+
+/// The `DT_FLAGS_1` flag of a position independent executable.
+const DF_1_PIE: u64 = 0x0800_0000;
+
+/// The version definition flag of the definition naming the file itself.
+const VER_FLG_BASE: u16 = 1;
+
+/// More version entries than this are taken to be a loop.
+const MAX_VERSIONS: u64 = 4096;
+
+/// The type of an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfType {
+    /// An object file, e.g. a `.o` or a member of a static library.
+    Relocatable,
+
+    /// An executable at a fixed address.
+    Executable,
+
+    /// A shared library, or a position independent executable.
+    SharedObject,
+
+    /// A core dump.
+    Core,
+
+    /// Any other type.
+    Other(u16),
+}
+
+/// The dynamic linking information of an ELF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfInfo {
+    /// Whether the file is 64-bit.
+    pub is_64: bool,
+
+    /// The `e_machine` architecture, e.g. 62 for x86-64.
+    pub machine: u16,
+
+    /// The type of the file.
+    pub elf_type: ElfType,
+
+    /// The dynamic linker the file is run with, e.g.
+    /// `/lib64/ld-linux-x86-64.so.2`.
+    pub interpreter: Option<String>,
+
+    /// The soname of a shared library, e.g. `libhello.so.1`.
+    pub soname: Option<String>,
+
+    /// The `DT_NEEDED` libraries, in link order.
+    pub needed: Vec<String>,
+
+    /// The symbol versions needed from each library, as `(library,
+    /// version)` pairs, e.g. `("libc.so.6", "GLIBC_2.34")`.
+    pub needed_versions: Vec<(String, String)>,
+
+    /// The symbol versions the file defines, e.g. `HELLO_1.0`.
+    pub defined_versions: Vec<String>,
+
+    /// The library search paths from `DT_RUNPATH` and `DT_RPATH`.
+    pub runpath: Vec<String>,
+
+    /// Whether the file is a position independent executable.
+    pub pie: bool,
+}
+
+impl ElfInfo {
+    /// Whether data starts with the ELF magic.
+    pub fn is_elf(data: &[u8]) -> bool {
+        data.starts_with(ELF_MAGIC)
+    }
+
+    /// Parse the headers and dynamic section of an ELF file.
+    ///
+    /// Fails with [`PkgError::InvalidElf`] if the file isn't ELF or points
+    /// outside of `data`, which can happen if it was cut short.
+    pub fn parse(data: &[u8]) -> Result<Self, PkgError> {
+        let elf = Elf::new(data)?;
+        let invalid = || PkgError::InvalidElf("header out of range".to_string());
+
+        let e_type = elf.u16(16).ok_or_else(invalid)?;
+        let machine = elf.u16(18).ok_or_else(invalid)?;
+        let (phoff, phentsize, phnum) = if elf.is_64 {
+            (elf.u64(32), elf.u16(54), elf.u16(56))
+        } else {
+            (elf.u32(28).map(u64::from), elf.u16(42), elf.u16(44))
+        };
+        let (phoff, phentsize, phnum) = (
+            phoff.ok_or_else(invalid)? as usize,
+            phentsize.ok_or_else(invalid)? as usize,
+            phnum.ok_or_else(invalid)?,
+        );
+
+        let mut segments = Vec::new();
+        for i in 0..usize::from(phnum) {
+            let offset = i
+                .checked_mul(phentsize)
+                .and_then(|o| o.checked_add(phoff))
+                .ok_or_else(invalid)?;
+            segments.push(elf.segment(offset).ok_or_else(invalid)?);
+        }
+
+        let mut info = Self {
+            is_64: elf.is_64,
+            machine,
+            elf_type: match e_type {
+                1 => ElfType::Relocatable,
+                2 => ElfType::Executable,
+                3 => ElfType::SharedObject,
+                4 => ElfType::Core,
+                other => ElfType::Other(other),
+            },
+            interpreter: None,
+            soname: None,
+            needed: Vec::new(),
+            needed_versions: Vec::new(),
+            defined_versions: Vec::new(),
+            runpath: Vec::new(),
+            pie: false,
+        };
+
+        if let Some(interp) = segments.iter().find(|s| s.kind == PT_INTERP) {
+            let bytes = elf
+                .slice(interp.offset, interp.file_size)
+                .ok_or_else(invalid)?;
+            let bytes = bytes.split(|&b| b == 0).next().unwrap_or_default();
+            info.interpreter = Some(String::from_utf8_lossy(bytes).into_owned());
+        }
+
+        if let Some(dynamic) = segments.iter().find(|s| s.kind == PT_DYNAMIC) {
+            elf.read_dynamic(dynamic, &segments, &mut info)?;
+        }
+        Ok(info)
+    }
+
+    /// Whether the file is an executable that doesn't load any shared
+    /// libraries, including static position independent executables.
+    pub fn is_statically_linked(&self) -> bool {
+        let executable = match self.elf_type {
+            ElfType::Executable => true,
+            ElfType::SharedObject => self.pie,
+            _ => false,
+        };
+        executable && self.interpreter.is_none() && self.needed.is_empty()
+    }
+
+    /// The marker rpm adds to dependencies of 64-bit files.
+    fn marker(&self) -> &'static str {
+        if self.is_64 {
+            "(64bit)"
+        } else {
+            ""
+        }
+    }
+
+    /// The capabilities rpmbuild would generate as provided by this file:
+    /// its soname, and the soname with each version it defines.
+    pub fn rpm_provides(&self) -> Vec<String> {
+        let soname = match &self.soname {
+            Some(soname) => soname,
+            None => return Vec::new(),
+        };
+        let marker = self.marker();
+        let mut provides = vec![format!("{}(){}", soname, marker)];
+        provides.extend(
+            self.defined_versions
+                .iter()
+                .map(|version| format!("{}({}){}", soname, version, marker)),
+        );
+        provides
+    }
+
+    /// The capabilities rpmbuild would generate as required by this file:
+    /// each needed library, and the library with each version needed from
+    /// it.
+    pub fn rpm_requires(&self) -> Vec<String> {
+        let marker = self.marker();
+        let mut requires: Vec<String> = self
+            .needed
+            .iter()
+            .map(|library| format!("{}(){}", library, marker))
+            .collect();
+        requires.extend(
+            self.needed_versions
+                .iter()
+                .map(|(library, version)| format!("{}({}){}", library, version, marker)),
+        );
+        requires
+    }
+}
+
+/// An ELF file found in a package.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfFile {
+    /// The installed path, e.g. `/usr/lib/libhello.so.1.0`.
+    pub path: String,
+
+    /// The dynamic linking information of the file.
+    pub info: ElfInfo,
+}
+
+/// Dependencies computed from the ELF files in a package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AutoDeps {
+    /// The sonames provided, in rpm's format, sorted.
+    pub provides: Vec<String>,
+
+    /// The libraries required, in rpm's format, sorted. Libraries that the
+    /// package provides itself are included, as rpmbuild does.
+    pub requires: Vec<String>,
+}
+
+/// Compute the automatic provides and requires of a set of ELF files, as
+/// rpmbuild would.
+pub fn auto_deps<'a, I: IntoIterator<Item = &'a ElfInfo>>(files: I) -> AutoDeps {
+    let mut deps = AutoDeps::default();
+    for info in files {
+        deps.provides.extend(info.rpm_provides());
+        deps.requires.extend(info.rpm_requires());
+    }
+    for list in [&mut deps.provides, &mut deps.requires] {
+        list.sort();
+        list.dedup();
+    }
+    deps
+}
+
+/// Find and parse the ELF files in the payload of a package, reading it as
+/// a stream.
+///
+/// At most `max_size` bytes of each file are read. The dynamic section is
+/// usually near the start, but files that can't be parsed within the limit
+/// are left out, as are files that aren't valid ELF.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+pub fn scan_elf<R: Read>(reader: R, max_size: u64) -> Result<Vec<ElfFile>, PkgError> {
+    let mut files = Vec::new();
+    walk_elf(reader, max_size, |path, _, info| {
+        files.push(ElfFile {
+            path: path.to_owned(),
+            info,
+        })
+    })?;
+    Ok(files)
+}
+
+/// Call `visit` with the installed path, the contents read and the parsed
+/// information of each ELF file in the payload of a package.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+fn walk_elf<R, F>(reader: R, max_size: u64, mut visit: F) -> Result<(), PkgError>
+where
+    R: Read,
+    F: FnMut(&str, &[u8], ElfInfo),
+{
+    crate::payload::walk(reader, |path, entry| {
+        let mut magic = [0; 4];
+        let magic_len = read_full(entry, &mut magic)?;
+        if !ElfInfo::is_elf(&magic[..magic_len]) {
+            return Ok(true);
+        }
+        let mut data = magic.to_vec();
+        let _ = entry
+            .take(max_size.saturating_sub(4))
+            .read_to_end(&mut data)?;
+        if let Ok(info) = ElfInfo::parse(&data) {
+            visit(path, &data, info);
+        }
+        Ok(true)
+    })
+}
+
+/// A library that's commonly bundled, with the sonames it's installed as
+/// and the prefix of the version string it embeds, if it has one.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+struct KnownLibrary {
+    name: &'static str,
+    sonames: &'static [&'static str],
+    marker: Option<&'static [u8]>,
+}
+
+/// The libraries looked for by [`find_bundled_libraries`]. These are the
+/// ones with a history of security fixes that a bundled copy would miss.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+const KNOWN_LIBRARIES: [KnownLibrary; 9] = [
+    KnownLibrary {
+        name: "openssl",
+        sonames: &["libssl.so", "libcrypto.so"],
+        marker: Some(b"OpenSSL "),
+    },
+    KnownLibrary {
+        name: "zlib",
+        sonames: &["libz.so"],
+        marker: Some(b"deflate "),
+    },
+    KnownLibrary {
+        name: "libpng",
+        sonames: &["libpng"],
+        marker: Some(b"libpng version "),
+    },
+    KnownLibrary {
+        name: "curl",
+        sonames: &["libcurl.so", "libcurl-gnutls.so"],
+        marker: Some(b"libcurl/"),
+    },
+    KnownLibrary {
+        name: "expat",
+        sonames: &["libexpat.so"],
+        marker: Some(b"expat_"),
+    },
+    KnownLibrary {
+        name: "sqlite",
+        sonames: &["libsqlite3.so"],
+        marker: None,
+    },
+    KnownLibrary {
+        name: "libxml2",
+        sonames: &["libxml2.so"],
+        marker: None,
+    },
+    KnownLibrary {
+        name: "libjpeg",
+        sonames: &["libjpeg.so", "libturbojpeg.so"],
+        marker: None,
+    },
+    KnownLibrary {
+        name: "libffi",
+        sonames: &["libffi.so"],
+        marker: None,
+    },
+];
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+impl KnownLibrary {
+    /// Whether a soname or library file name is one of this library's.
+    fn matches(&self, name: &str) -> bool {
+        self.sonames.iter().any(|soname| name.starts_with(soname))
+    }
+}
+
+/// Why a file was flagged by [`find_bundled_libraries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundledKind {
+    /// A copy of a common shared library installed outside of the
+    /// system library directories, e.g. `/opt/hello/lib/libssl.so.3`.
+    VendoredLibrary,
+
+    /// An executable that doesn't load any shared libraries, so has all of
+    /// its libraries built in.
+    StaticallyLinked,
+
+    /// A file containing the version string of a common library that it
+    /// doesn't load as a shared library, so has it built in.
+    EmbeddedLibrary,
+}
+
+/// A file that may carry its own copy of a library, as found by
+/// [`find_bundled_libraries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundledFinding {
+    /// The installed path of the file.
+    pub path: String,
+
+    /// Why the file was flagged.
+    pub kind: BundledKind,
+
+    /// The library, e.g. `openssl`, unless the file is only statically
+    /// linked.
+    pub library: Option<String>,
+
+    /// The version of the library, if the file embeds a version string.
+    pub version: Option<String>,
+}
+
+/// Whether a path is directly in a system library directory, including the
+/// multiarch directories, e.g. `/usr/lib/x86_64-linux-gnu`.
+pub fn is_system_library_path(path: &str) -> bool {
+    let dir = match path.rsplit_once('/') {
+        Some((dir, _)) => dir,
+        None => return false,
+    };
+    let dir = dir.strip_prefix("/usr").unwrap_or(dir);
+    match dir {
+        "/lib" | "/lib64" | "/lib32" | "/libx32" => true,
+        _ => dir
+            .strip_prefix("/lib/")
+            .is_some_and(|triplet| triplet.contains("-linux-") && !triplet.contains('/')),
+    }
+}
+
+/// Find a version string after `marker`, e.g. `1.1.1k` after `OpenSSL `.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+fn embedded_version(data: &[u8], marker: &[u8]) -> Option<String> {
+    let mut start = 0;
+    while let Some(found) = find(&data[start..], marker) {
+        let after = start + found + marker.len();
+        let version: Vec<u8> = data[after..]
+            .iter()
+            .copied()
+            .take_while(|b| b.is_ascii_alphanumeric() || *b == b'.' || *b == b'-')
+            .take(32)
+            .collect();
+        if version.first().is_some_and(u8::is_ascii_digit) && version.contains(&b'.') {
+            return Some(String::from_utf8_lossy(&version).into_owned());
+        }
+        start = after;
+    }
+    None
+}
+
+/// Find the first position of `needle` in `haystack`.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Flag the files in a package that may carry their own copies of common
+/// libraries, reading it as a stream, for review of whether they get
+/// security fixes.
+///
+/// These are heuristics. Vendored libraries are found by soname, and
+/// embedded ones by version string, so renamed or stripped copies are
+/// missed. At most `max_size` bytes of each ELF file are searched.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+pub fn find_bundled_libraries<R: Read>(
+    reader: R,
+    max_size: u64,
+) -> Result<Vec<BundledFinding>, PkgError> {
+    let mut findings = Vec::new();
+    walk_elf(reader, max_size, |path, data, info| {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let own = info.soname.as_deref().unwrap_or(name);
+        let finding = |kind, library: Option<&KnownLibrary>, version| BundledFinding {
+            path: path.to_owned(),
+            kind,
+            library: library.map(|library| library.name.to_owned()),
+            version,
+        };
+
+        let itself = KNOWN_LIBRARIES.iter().find(|library| library.matches(own));
+        if let Some(library) = itself {
+            if !is_system_library_path(path) {
+                let version = library
+                    .marker
+                    .and_then(|marker| embedded_version(data, marker));
+                findings.push(finding(
+                    BundledKind::VendoredLibrary,
+                    Some(library),
+                    version,
+                ));
+            }
+        }
+        if info.is_statically_linked() {
+            findings.push(finding(BundledKind::StaticallyLinked, None, None));
+        }
+
+        for library in &KNOWN_LIBRARIES {
+            let linked = info.needed.iter().any(|needed| library.matches(needed));
+            if linked || itself.is_some_and(|itself| itself.name == library.name) {
+                continue;
+            }
+            if let Some(version) = library
+                .marker
+                .and_then(|marker| embedded_version(data, marker))
+            {
+                findings.push(finding(
+                    BundledKind::EmbeddedLibrary,
+                    Some(library),
+                    Some(version),
+                ));
+            }
+        }
+    })?;
+    Ok(findings)
+}
+
+/// Flag the files in a package from a URL that may carry their own copies
+/// of common libraries; see [`find_bundled_libraries`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(all(feature = "http-blocking", any(feature = "debian", feature = "rpm")))]
+pub fn find_bundled_libraries_from_url(
+    url: &str,
+    max_size: u64,
+) -> Result<Vec<BundledFinding>, PkgError> {
+    find_bundled_libraries(crate::http::get_package(url)?, max_size)
+}
+
+/// Find and parse the ELF files in a package from a URL; see [`scan_elf`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(all(feature = "http-blocking", any(feature = "debian", feature = "rpm")))]
+pub fn scan_elf_from_url(url: &str, max_size: u64) -> Result<Vec<ElfFile>, PkgError> {
+    scan_elf(crate::http::get_package(url)?, max_size)
+}
+
+/// Read into `buf` until it's full or the reader ends, returning how much
+/// was read.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+fn read_full(reader: &mut dyn Read, buf: &mut [u8]) -> Result<usize, PkgError> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    Ok(len)
+}
+
+/// A program header.
+struct Segment {
+    kind: u32,
+    offset: u64,
+    vaddr: u64,
+    file_size: u64,
+}
+
+/// An ELF file being parsed, with its class and byte order.
+struct Elf<'d> {
+    data: &'d [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+impl<'d> Elf<'d> {
+    fn new(data: &'d [u8]) -> Result<Self, PkgError> {
+        if !ElfInfo::is_elf(data) || data.len() < 16 {
+            return Err(PkgError::InvalidElf("not an ELF file".to_string()));
+        }
+        let is_64 = match data[4] {
+            1 => false,
+            2 => true,
+            class => return Err(PkgError::InvalidElf(format!("unknown class {}", class))),
+        };
+        let big_endian = match data[5] {
+            1 => false,
+            2 => true,
+            order => {
+                return Err(PkgError::InvalidElf(format!(
+                    "unknown byte order {}",
+                    order
+                )))
+            }
+        };
+        Ok(Self {
+            data,
+            is_64,
+            big_endian,
+        })
+    }
+
+    /// Get `len` bytes at a file offset.
+    fn slice(&self, offset: u64, len: u64) -> Option<&'d [u8]> {
+        let start = usize::try_from(offset).ok()?;
+        let end = start.checked_add(usize::try_from(len).ok()?)?;
+        self.data.get(start..end)
+    }
+
+    /// Read an integer of `N` bytes at a file offset.
+    fn int<const N: usize>(&self, offset: usize) -> Option<u64> {
+        let bytes = self.data.get(offset..offset.checked_add(N)?)?;
+        let value = |n: u64, &b: &u8| n << 8 | u64::from(b);
+        Some(if self.big_endian {
+            bytes.iter().fold(0, value)
+        } else {
+            bytes.iter().rev().fold(0, value)
+        })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        self.int::<2>(offset).map(|v| v as u16)
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        self.int::<4>(offset).map(|v| v as u32)
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        self.int::<8>(offset)
+    }
+
+    /// Read an address or size, which is the size of the class.
+    fn word(&self, offset: usize) -> Option<u64> {
+        if self.is_64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    /// Read the program header at a file offset.
+    fn segment(&self, offset: usize) -> Option<Segment> {
+        let kind = self.u32(offset)?;
+        Some(if self.is_64 {
+            Segment {
+                kind,
+                offset: self.u64(offset + 8)?,
+                vaddr: self.u64(offset + 16)?,
+                file_size: self.u64(offset + 32)?,
+            }
+        } else {
+            Segment {
+                kind,
+                offset: u64::from(self.u32(offset + 4)?),
+                vaddr: u64::from(self.u32(offset + 8)?),
+                file_size: u64::from(self.u32(offset + 16)?),
+            }
+        })
+    }
+
+    /// Read the dynamic section, and the strings and version entries it
+    /// points to.
+    fn read_dynamic(
+        &self,
+        dynamic: &Segment,
+        segments: &[Segment],
+        info: &mut ElfInfo,
+    ) -> Result<(), PkgError> {
+        let invalid = |what: &str| PkgError::InvalidElf(format!("{} out of range", what));
+        let entry_size = if self.is_64 { 16 } else { 8 };
+        let data = self
+            .slice(dynamic.offset, dynamic.file_size)
+            .ok_or_else(|| invalid("dynamic section"))?;
+
+        let mut entries = Vec::new();
+        for i in 0..data.len() / entry_size {
+            let offset = dynamic.offset as usize + i * entry_size;
+            let tag = self.word(offset).ok_or_else(|| invalid("dynamic entry"))?;
+            let value = self
+                .word(offset + entry_size / 2)
+                .ok_or_else(|| invalid("dynamic entry"))?;
+            if tag == DT_NULL {
+                break;
+            }
+            entries.push((tag, value));
+        }
+        let value = |tag: u64| entries.iter().find(|(t, _)| *t == tag).map(|(_, v)| *v);
+
+        // Addresses in the dynamic section are virtual, so are mapped back
+        // to file offsets through the loaded segments.
+        let file_offset = |addr: u64| {
+            segments
+                .iter()
+                .filter(|s| s.kind == PT_LOAD)
+                .find(|s| addr >= s.vaddr && addr - s.vaddr < s.file_size)
+                .map(|s| s.offset + (addr - s.vaddr))
+        };
+        let strtab = value(DT_STRTAB)
+            .and_then(file_offset)
+            .and_then(|offset| self.slice(offset, value(DT_STRSZ).unwrap_or_default()))
+            .unwrap_or_default();
+        let string = |offset: u64| -> Result<String, PkgError> {
+            let bytes = usize::try_from(offset)
+                .ok()
+                .and_then(|offset| strtab.get(offset..))
+                .ok_or_else(|| invalid("string"))?;
+            let bytes = bytes.split(|&b| b == 0).next().unwrap_or_default();
+            Ok(String::from_utf8_lossy(bytes).into_owned())
+        };
+
+        for (tag, value) in &entries {
+            match *tag {
+                DT_NEEDED => info.needed.push(string(*value)?),
+                DT_SONAME => info.soname = Some(string(*value)?),
+                DT_RPATH | DT_RUNPATH => info.runpath.extend(
+                    string(*value)?
+                        .split(':')
+                        .filter(|path| !path.is_empty())
+                        .map(str::to_owned),
+                ),
+                DT_FLAGS_1 => info.pie = value & DF_1_PIE != 0,
+                _ => {}
+            }
+        }
+
+        // Each needed file has a list of the versions needed from it.
+        if let Some(mut offset) = value(DT_VERNEED).and_then(file_offset) {
+            let count = value(DT_VERNEEDNUM).unwrap_or_default().min(MAX_VERSIONS);
+            for _ in 0..count {
+                let at = |delta: u64| usize::try_from(offset + delta).ok();
+                let file = at(4).and_then(|o| self.u32(o));
+                let aux = at(8).and_then(|o| self.u32(o));
+                let next = at(12).and_then(|o| self.u32(o));
+                let aux_count = at(2).and_then(|o| self.u16(o));
+                let (file, aux, next, aux_count) = match (file, aux, next, aux_count) {
+                    (Some(f), Some(a), Some(n), Some(c)) => (f, a, n, c),
+                    _ => return Err(invalid("version needs")),
+                };
+                let library = string(u64::from(file))?;
+
+                let mut aux_offset = offset + u64::from(aux);
+                for _ in 0..aux_count {
+                    let at = |delta: u64| usize::try_from(aux_offset + delta).ok();
+                    let name = at(8).and_then(|o| self.u32(o));
+                    let aux_next = at(12).and_then(|o| self.u32(o));
+                    let (name, aux_next) = name.zip(aux_next).ok_or_else(|| invalid("version"))?;
+                    info.needed_versions
+                        .push((library.clone(), string(u64::from(name))?));
+                    if aux_next == 0 {
+                        break;
+                    }
+                    aux_offset += u64::from(aux_next);
+                }
+
+                if next == 0 {
+                    break;
+                }
+                offset += u64::from(next);
+            }
+        }
+
+        // Each definition has its name first, then the versions it inherits.
+        if let Some(mut offset) = value(DT_VERDEF).and_then(file_offset) {
+            let count = value(DT_VERDEFNUM).unwrap_or_default().min(MAX_VERSIONS);
+            for _ in 0..count {
+                let at = |delta: u64| usize::try_from(offset + delta).ok();
+                let flags = at(2).and_then(|o| self.u16(o));
+                let aux = at(12).and_then(|o| self.u32(o));
+                let next = at(16).and_then(|o| self.u32(o));
+                let (flags, aux, next) = match (flags, aux, next) {
+                    (Some(f), Some(a), Some(n)) => (f, a, n),
+                    _ => return Err(invalid("version definitions")),
+                };
+                if flags & VER_FLG_BASE == 0 {
+                    let name = usize::try_from(offset + u64::from(aux))
+                        .ok()
+                        .and_then(|o| self.u32(o))
+                        .ok_or_else(|| invalid("version"))?;
+                    info.defined_versions.push(string(u64::from(name))?);
+                }
+
+                if next == 0 {
+                    break;
+                }
+                offset += u64::from(next);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// The options for [`build_elf`].
+    #[derive(Default)]
+    pub(crate) struct TestElf<'a> {
+        pub(crate) is_64: bool,
+        pub(crate) big_endian: bool,
+        pub(crate) elf_type: u16,
+        pub(crate) interpreter: Option<&'a str>,
+        pub(crate) soname: Option<&'a str>,
+        pub(crate) needed: &'a [&'a str],
+        /// Versions needed from the first needed library.
+        pub(crate) needed_versions: &'a [&'a str],
+        pub(crate) defined_versions: &'a [&'a str],
+        pub(crate) pie: bool,
+        /// Extra data at the end of the file, such as version strings.
+        pub(crate) trailer: &'a [u8],
+    }
+
+    /// Build a minimal ELF file, loaded at a different address to its file
+    /// offsets.
+    pub(crate) fn build_elf(spec: &TestElf<'_>) -> Vec<u8> {
+        const BASE: u64 = 0x40_0000;
+        let word = if spec.is_64 { 8 } else { 4 };
+        let (ehsize, phentsize) = if spec.is_64 { (64, 56) } else { (52, 32) };
+        let put = |out: &mut Vec<u8>, value: u64, len: usize| {
+            let bytes = value.to_le_bytes();
+            if spec.big_endian {
+                out.extend(bytes[..len].iter().rev());
+            } else {
+                out.extend_from_slice(&bytes[..len]);
+            }
+        };
+
+        // The string table, with the offset of each string.
+        let mut strtab = vec![0u8];
+        let mut add_string = |s: &str| {
+            let offset = strtab.len() as u64;
+            strtab.extend_from_slice(s.as_bytes());
+            strtab.push(0);
+            offset
+        };
+        let needed: Vec<u64> = spec.needed.iter().map(|s| add_string(s)).collect();
+        let soname = spec.soname.map(&mut add_string);
+        let needed_versions: Vec<u64> =
+            spec.needed_versions.iter().map(|s| add_string(s)).collect();
+        let defined_versions: Vec<u64> = spec
+            .defined_versions
+            .iter()
+            .map(|s| add_string(s))
+            .collect();
+
+        let segments = 2 + usize::from(spec.interpreter.is_some());
+        let interp_offset = ehsize + segments * phentsize;
+        let interp = spec
+            .interpreter
+            .map(|i| format!("{}\0", i).into_bytes())
+            .unwrap_or_default();
+        let strtab_offset = interp_offset + interp.len();
+
+        // One version needs entry for the first library, then the
+        // definitions: the base, then the others.
+        let mut versions = Vec::new();
+        let verneed_offset = strtab_offset + strtab.len();
+        if !needed_versions.is_empty() {
+            put(&mut versions, 1, 2);
+            put(&mut versions, needed_versions.len() as u64, 2);
+            put(&mut versions, needed[0], 4);
+            put(&mut versions, 16, 4);
+            put(&mut versions, 0, 4);
+            for (i, name) in needed_versions.iter().enumerate() {
+                put(&mut versions, 0, 4);
+                put(&mut versions, 0, 2);
+                put(&mut versions, 0, 2);
+                put(&mut versions, *name, 4);
+                let next = if i + 1 < needed_versions.len() { 16 } else { 0 };
+                put(&mut versions, next, 4);
+            }
+        }
+        let verdef_offset = verneed_offset + versions.len();
+        let mut definitions = vec![(VER_FLG_BASE, soname.unwrap_or_default())];
+        definitions.extend(defined_versions.iter().map(|name| (0, *name)));
+        if !defined_versions.is_empty() {
+            for (i, (flags, name)) in definitions.iter().enumerate() {
+                put(&mut versions, 1, 2);
+                put(&mut versions, u64::from(*flags), 2);
+                put(&mut versions, i as u64 + 1, 2);
+                put(&mut versions, 1, 2);
+                put(&mut versions, 0, 4);
+                put(&mut versions, 20, 4);
+                let next = if i + 1 < definitions.len() { 28 } else { 0 };
+                put(&mut versions, next, 4);
+                put(&mut versions, *name, 4);
+                put(&mut versions, 0, 4);
+            }
+        }
+
+        let mut dynamic_entries: Vec<(u64, u64)> =
+            needed.iter().map(|offset| (DT_NEEDED, *offset)).collect();
+        if let Some(soname) = soname {
+            dynamic_entries.push((DT_SONAME, soname));
+        }
+        dynamic_entries.push((DT_STRTAB, BASE + strtab_offset as u64));
+        dynamic_entries.push((DT_STRSZ, strtab.len() as u64));
+        if !needed_versions.is_empty() {
+            dynamic_entries.push((DT_VERNEED, BASE + verneed_offset as u64));
+            dynamic_entries.push((DT_VERNEEDNUM, 1));
+        }
+        if !defined_versions.is_empty() {
+            dynamic_entries.push((DT_VERDEF, BASE + verdef_offset as u64));
+            dynamic_entries.push((DT_VERDEFNUM, definitions.len() as u64));
+        }
+        if spec.pie {
+            dynamic_entries.push((DT_FLAGS_1, DF_1_PIE));
+        }
+        dynamic_entries.push((DT_NULL, 0));
+        let dynamic_offset = verneed_offset + versions.len();
+        let dynamic_size = dynamic_entries.len() * word * 2;
+        let file_size = dynamic_offset + dynamic_size + spec.trailer.len();
+
+        let mut elf = ELF_MAGIC.to_vec();
+        elf.push(if spec.is_64 { 2 } else { 1 });
+        elf.push(if spec.big_endian { 2 } else { 1 });
+        elf.push(1);
+        elf.resize(16, 0);
+        put(&mut elf, u64::from(spec.elf_type), 2);
+        put(&mut elf, 62, 2);
+        put(&mut elf, 1, 4);
+        put(&mut elf, BASE, word);
+        put(&mut elf, ehsize as u64, word);
+        put(&mut elf, 0, word);
+        put(&mut elf, 0, 4);
+        put(&mut elf, ehsize as u64, 2);
+        put(&mut elf, phentsize as u64, 2);
+        put(&mut elf, segments as u64, 2);
+        put(&mut elf, 0, 6);
+        assert_eq!(elf.len(), ehsize);
+
+        let mut segment = |kind: u32, offset: usize, size: usize| {
+            let (offset, size) = (offset as u64, size as u64);
+            put(&mut elf, u64::from(kind), 4);
+            if spec.is_64 {
+                put(&mut elf, 4, 4);
+            }
+            put(&mut elf, offset, word);
+            put(&mut elf, BASE + offset, word);
+            put(&mut elf, BASE + offset, word);
+            put(&mut elf, size, word);
+            put(&mut elf, size, word);
+            if !spec.is_64 {
+                put(&mut elf, 4, 4);
+            }
+            put(&mut elf, 8, word);
+        };
+        segment(PT_LOAD, 0, file_size);
+        if spec.interpreter.is_some() {
+            segment(PT_INTERP, interp_offset, interp.len());
+        }
+        segment(PT_DYNAMIC, dynamic_offset, dynamic_size);
+
+        elf.extend(interp);
+        elf.extend(strtab);
+        elf.extend(versions);
+        for (tag, value) in dynamic_entries {
+            put(&mut elf, tag, word);
+            put(&mut elf, value, word);
+        }
+        elf.extend_from_slice(spec.trailer);
+        assert_eq!(elf.len(), file_size);
+        elf
+    }
+
+    #[test]
+    fn test_parse() {
+        let library = build_elf(&TestElf {
+            is_64: true,
+            elf_type: 3,
+            soname: Some("libhello.so.1"),
+            needed: &["libc.so.6"],
+            needed_versions: &["GLIBC_2.2.5", "GLIBC_2.34"],
+            defined_versions: &["HELLO_1.0"],
+            ..TestElf::default()
+        });
+        let info = ElfInfo::parse(&library).unwrap();
+        assert_eq!(
+            info,
+            ElfInfo {
+                is_64: true,
+                machine: 62,
+                elf_type: ElfType::SharedObject,
+                interpreter: None,
+                soname: Some("libhello.so.1".to_string()),
+                needed: vec!["libc.so.6".to_string()],
+                needed_versions: vec![
+                    ("libc.so.6".to_string(), "GLIBC_2.2.5".to_string()),
+                    ("libc.so.6".to_string(), "GLIBC_2.34".to_string()),
+                ],
+                defined_versions: vec!["HELLO_1.0".to_string()],
+                runpath: vec![],
+                pie: false,
+            }
+        );
+        assert_eq!(
+            info.rpm_provides(),
+            vec!["libhello.so.1()(64bit)", "libhello.so.1(HELLO_1.0)(64bit)"]
+        );
+        assert_eq!(
+            info.rpm_requires(),
+            vec![
+                "libc.so.6()(64bit)",
+                "libc.so.6(GLIBC_2.2.5)(64bit)",
+                "libc.so.6(GLIBC_2.34)(64bit)"
+            ]
+        );
+        assert!(!info.is_statically_linked());
+
+        // Truncated files point outside of the data.
+        assert!(ElfInfo::parse(&library[..library.len() - 8]).is_err());
+        assert!(ElfInfo::parse(b"\x7fELF\x03").is_err());
+        assert!(ElfInfo::parse(b"#!/bin/sh\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_executables() {
+        let executable = build_elf(&TestElf {
+            big_endian: true,
+            elf_type: 3,
+            interpreter: Some("/lib/ld.so.1"),
+            needed: &["libhello.so.1", "libc.so.6"],
+            pie: true,
+            ..TestElf::default()
+        });
+        let info = ElfInfo::parse(&executable).unwrap();
+        assert!(!info.is_64);
+        assert_eq!(info.interpreter.as_deref(), Some("/lib/ld.so.1"));
+        assert!(info.pie);
+        assert!(!info.is_statically_linked());
+        assert!(info.rpm_provides().is_empty());
+        assert_eq!(info.rpm_requires(), vec!["libhello.so.1()", "libc.so.6()"]);
+
+        let static_pie = build_elf(&TestElf {
+            is_64: true,
+            elf_type: 3,
+            pie: true,
+            ..TestElf::default()
+        });
+        assert!(ElfInfo::parse(&static_pie).unwrap().is_statically_linked());
+
+        let deps = auto_deps(&[
+            ElfInfo::parse(&executable).unwrap(),
+            ElfInfo::parse(&executable).unwrap(),
+        ]);
+        assert_eq!(deps.requires, vec!["libc.so.6()", "libhello.so.1()"]);
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_scan_elf() {
+        let library = build_elf(&TestElf {
+            is_64: true,
+            elf_type: 3,
+            soname: Some("libhello.so.1"),
+            ..TestElf::default()
+        });
+        let rpm = crate::payload::tests::build_rpm_with_payload(&[
+            ("./usr/lib64/libhello.so.1", &library),
+            ("./usr/bin/hello.sh", b"#!/bin/sh\n"),
+            ("./usr/lib64/broken.so", b"\x7fELF"),
+        ]);
+
+        let files = scan_elf(rpm.as_slice(), 1024 * 1024).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "/usr/lib64/libhello.so.1");
+        assert_eq!(files[0].info.soname.as_deref(), Some("libhello.so.1"));
+
+        // Too little of the file to find the dynamic section.
+        assert!(scan_elf(rpm.as_slice(), 64).unwrap().is_empty());
+    }
+
+    #[cfg(any(feature = "debian", feature = "rpm"))]
+    #[test]
+    fn test_heuristics() {
+        assert!(is_system_library_path("/usr/lib64/libz.so.1"));
+        assert!(is_system_library_path("/lib/x86_64-linux-gnu/libz.so.1"));
+        assert!(!is_system_library_path("/usr/lib/hello/libz.so.1"));
+        assert!(!is_system_library_path("/opt/hello/lib/libz.so.1"));
+
+        let data = b"xx OpenSSL 3 OpenSSL 1.1.1k  25 Mar 2021\0";
+        assert_eq!(
+            embedded_version(data, b"OpenSSL "),
+            Some("1.1.1k".to_string())
+        );
+        assert_eq!(embedded_version(b"deflate stream", b"deflate "), None);
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_find_bundled_libraries() {
+        let vendored = build_elf(&TestElf {
+            is_64: true,
+            elf_type: 3,
+            soname: Some("libssl.so.1.1"),
+            needed: &["libc.so.6"],
+            trailer: b"OpenSSL 1.1.1k  25 Mar 2021\0",
+            ..TestElf::default()
+        });
+        let system = build_elf(&TestElf {
+            is_64: true,
+            elf_type: 3,
+            soname: Some("libz.so.1"),
+            trailer: b"deflate 1.2.13 Copyright\0",
+            ..TestElf::default()
+        });
+        let linked = build_elf(&TestElf {
+            is_64: true,
+            elf_type: 3,
+            interpreter: Some("/lib64/ld-linux-x86-64.so.2"),
+            needed: &["libz.so.1"],
+            pie: true,
+            trailer: b"deflate 1.2.13 Copyright\0",
+            ..TestElf::default()
+        });
+        let static_bin = build_elf(&TestElf {
+            is_64: true,
+            elf_type: 2,
+            trailer: b"inflate 1.2.11\0deflate 1.2.11 Copyright\0",
+            ..TestElf::default()
+        });
+        let rpm = crate::payload::tests::build_rpm_with_payload(&[
+            ("./opt/hello/lib/libssl.so.1.1", &vendored),
+            ("./usr/lib64/libz.so.1", &system),
+            ("./usr/bin/hello", &linked),
+            ("./usr/bin/hello-static", &static_bin),
+        ]);
+
+        assert_eq!(
+            find_bundled_libraries(rpm.as_slice(), 1024 * 1024).unwrap(),
+            vec![
+                BundledFinding {
+                    path: "/opt/hello/lib/libssl.so.1.1".to_string(),
+                    kind: BundledKind::VendoredLibrary,
+                    library: Some("openssl".to_string()),
+                    version: Some("1.1.1k".to_string()),
+                },
+                BundledFinding {
+                    path: "/usr/bin/hello-static".to_string(),
+                    kind: BundledKind::StaticallyLinked,
+                    library: None,
+                    version: None,
+                },
+                BundledFinding {
+                    path: "/usr/bin/hello-static".to_string(),
+                    kind: BundledKind::EmbeddedLibrary,
+                    library: Some("zlib".to_string()),
+                    version: Some("1.2.11".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! Fetching packages with reqwest's async client, for async services that
+//! can't use the blocking client, and for WebAssembly, where reqwest uses
+//! the browser's `fetch` API.
+//!
+//! Build without `http-blocking` to leave out the blocking client and its
+//! runtime, e.g. `--no-default-features --features http-async,debian,rpm`,
+//! or `wasm` in place of `http-async` for the browser. The xz and zstd
+//! decoders are C libraries, so need a C compiler that targets wasm32, such
+//! as clang.
+//!
+//! The parsers are the same as for the blocking client. The response body
+//! is collected until the metadata can be parsed from it, and the download
+//! is then dropped. Browsers don't let reqwest stream a response body, so
+//! on wasm32 the start of the package is fetched with HTTP range requests
+//! of doubling size instead; servers that ignore ranges send the whole
+//! package.
+use std::cell::Cell;
+use std::io::{self, Read};
+
+use crate::limits::Limits;
+use crate::{PkgError, RemotePackage};
+
+/// The bytes to receive before the first attempt to parse a package. Each
+/// later attempt waits for twice as many.
+const INITIAL_PREFIX: u64 = 64 * 1024;
+
+/// Create a RemotePackage from a URL with an async client.
+///
+/// The default [`Limits`] are applied while parsing. Outside of a browser,
+/// this must be polled within a tokio runtime. Time limits aren't supported
+/// in a browser, where there is no clock to check them against.
+pub async fn from_url_async(url: &str) -> Result<Box<dyn RemotePackage>, PkgError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let result = fetch(url, None);
+    #[cfg(target_arch = "wasm32")]
+    let result = fetch(url);
+    #[cfg(feature = "tracing")]
+    let result = tracing::Instrument::instrument(result, tracing::info_span!("from_url", url));
+    let result = result.await.map_err(|err| err.in_context(url, None));
+
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        tracing::warn!(error = %err, "failed to fetch package");
+    }
+    result
+}
+
+/// Create a RemotePackage from a URL with an async client like
+/// [`from_url_async`], giving up if the whole request takes longer than
+/// `timeout`, in place of
+/// [`ClientConfig::with_read_timeout`](crate::http::ClientConfig::with_read_timeout).
+/// The connect timeout is that of the client.
+///
+/// Not available in a browser, where requests can't be timed out.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn from_url_async_with_timeout(
+    url: &str,
+    timeout: std::time::Duration,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    let result = fetch(url, Some(timeout));
+    #[cfg(feature = "tracing")]
+    let result = tracing::Instrument::instrument(result, tracing::info_span!("from_url", url));
+    let result = result.await.map_err(|err| err.in_context(url, None));
+
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        tracing::warn!(error = %err, "failed to fetch package");
+    }
+    result
+}
+
+/// Fetch and parse a package for [`from_url_async`], reading the body as it
+/// arrives, with the read timeout of the client unless `timeout` is given.
+#[cfg(not(target_arch = "wasm32"))]
+async fn fetch(
+    url: &str,
+    timeout: Option<std::time::Duration>,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    let request = crate::http::with_async_timeout(crate::http::async_client().get(url), timeout);
+    let mut response = crate::http::send_async(request).await?.error_for_status()?;
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        status = response.status().as_u16(),
+        content_length = ?response.content_length(),
+        "received response"
+    );
+    let mut parser = PrefixParser::new(Limits::default());
+    let mut next_attempt = INITIAL_PREFIX;
+    while let Some(chunk) = response.chunk().await? {
+        parser.push(&chunk)?;
+        if parser.len() < next_attempt {
+            continue;
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            bytes_received = parser.len(),
+            "trying to parse received bytes"
+        );
+        if let Some(package) = parser.parse()? {
+            return Ok(package);
+        }
+        next_attempt = parser.len().saturating_mul(2);
+    }
+    parser.finish()
+}
+
+/// Fetch and parse a package for [`from_url_async`], with range requests.
+#[cfg(target_arch = "wasm32")]
+async fn fetch(url: &str) -> Result<Box<dyn RemotePackage>, PkgError> {
+    let client = crate::http::async_client();
+    let mut parser = PrefixParser::new(Limits::default());
+    let mut end = INITIAL_PREFIX;
+    loop {
+        let start = parser.len();
+        let request = client.get(url).header(
+            reqwest::header::RANGE,
+            format!("bytes={}-{}", start, end - 1),
+        );
+        let response = crate::http::send_async(request).await?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            status = response.status().as_u16(),
+            start,
+            end,
+            "received response"
+        );
+
+        // The previous range ended exactly at the end of the package.
+        if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+            return parser.finish();
+        }
+        let response = response.error_for_status()?;
+        let partial = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+        if !partial {
+            parser.clear();
+        }
+
+        let body = response.bytes().await?;
+        let complete = !partial || (body.len() as u64) < end - start;
+        parser.push(&body)?;
+        if complete {
+            return parser.finish();
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            bytes_received = parser.len(),
+            "trying to parse received bytes"
+        );
+        if let Some(package) = parser.parse()? {
+            return Ok(package);
+        }
+        end = end.saturating_mul(2);
+    }
+}
+
+/// Parses a package from the start of it, as more of it arrives.
+///
+/// The parsers read synchronously, so each attempt reads everything
+/// received so far. An attempt that runs out of data means more is needed.
+#[derive(Debug)]
+pub(crate) struct PrefixParser {
+    buf: Vec<u8>,
+    limits: Limits,
+}
+
+impl PrefixParser {
+    /// Start parsing a package with the given limits.
+    pub(crate) fn new(limits: Limits) -> Self {
+        Self {
+            buf: Vec::new(),
+            limits,
+        }
+    }
+
+    /// The number of bytes received so far.
+    pub(crate) fn len(&self) -> u64 {
+        self.buf.len() as u64
+    }
+
+    /// Forget the bytes received so far, to start again from the beginning.
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    /// Add the next bytes of the package.
+    ///
+    /// The metadata is at the start of a package, so more than
+    /// [`Limits::max_metadata_size`] bytes are never needed.
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Result<(), PkgError> {
+        self.buf.extend_from_slice(bytes);
+        match self.limits.max_metadata_size {
+            Some(limit) if self.len() > limit => Err(PkgError::SizeLimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Try to parse the package from the bytes received so far, returning
+    /// `None` if more are needed.
+    pub(crate) fn parse(&self) -> Result<Option<Box<dyn RemotePackage>>, PkgError> {
+        let exhausted = Cell::new(false);
+        let reader = PrefixReader {
+            remaining: &self.buf,
+            exhausted: &exhausted,
+        };
+        match crate::from_read_with_limits(reader, &self.limits) {
+            Ok(package) => Ok(Some(package)),
+            Err(_) if exhausted.get() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Parse the package once all of it has been received.
+    pub(crate) fn finish(&self) -> Result<Box<dyn RemotePackage>, PkgError> {
+        crate::from_read_with_limits(self.buf.as_slice(), &self.limits)
+    }
+}
+
+/// A reader over the start of a package that fails, and records that it
+/// did, on reaching the end of the bytes received so far.
+struct PrefixReader<'a> {
+    remaining: &'a [u8],
+    exhausted: &'a Cell<bool>,
+}
+
+impl Read for PrefixReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining.is_empty() && !buf.is_empty() {
+            self.exhausted.set(true);
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining.read(buf)
+    }
+}
+
+#[cfg(all(test, feature = "rpm"))]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_from_url_async() {
+        use crate::testing::{MockResponse, MockTransport, RpmBuilder};
+
+        let url = "https://example.com/hello.rpm";
+        let rpm = RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+        let mock = MockTransport::new()
+            .with_response(
+                url,
+                MockResponse::new("slow down")
+                    .with_status(429)
+                    .with_header("Retry-After", "0"),
+            )
+            .with_body(url, rpm)
+            .install();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        // Requests go through the same transport as the blocking client's,
+        // and are sent again when throttled.
+        let package = runtime.block_on(from_url_async(url)).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(mock.requests(), vec![url, url]);
+    }
+
+    #[test]
+    fn test_prefix_parser() {
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+
+        // The package is parsed as soon as its headers have arrived.
+        let mut parser = PrefixParser::new(Limits::default());
+        let mut chunks = rpm.chunks(100);
+        let package = loop {
+            parser.push(chunks.next().unwrap_or(&[0; 100])).unwrap();
+            if let Some(package) = parser.parse().unwrap() {
+                break package;
+            }
+        };
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert!(parser.len() <= rpm.len() as u64 + 1024);
+
+        // A truncated package fails once it's known to be complete.
+        let mut parser = PrefixParser::new(Limits::default());
+        parser.push(&rpm[..rpm.len() / 2]).unwrap();
+        assert!(parser.parse().unwrap().is_none());
+        assert!(parser.finish().is_err());
+
+        let limits = Limits {
+            max_metadata_size: Some(16),
+            ..Limits::unlimited()
+        };
+        let mut parser = PrefixParser::new(limits);
+        assert!(matches!(
+            parser.push(&rpm),
+            Err(PkgError::SizeLimitExceeded(16))
+        ));
+    }
+}
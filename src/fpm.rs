@@ -0,0 +1,166 @@
+//! Generation of [fpm](https://fpm.readthedocs.io/) arguments, for
+//! repackaging a package with the same metadata.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use crate::RemotePackageType;
+use crate::{PkgError, RemotePackage};
+
+/// The metadata fpm needs to rebuild a package, as given on its command
+/// line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FpmArgs {
+    /// The package type, `deb` or `rpm`, for fpm's `-s`.
+    pub input_type: String,
+
+    /// The package name, for `--name`.
+    pub name: String,
+
+    /// The version, without the epoch or iteration, for `--version`.
+    pub version: String,
+
+    /// The epoch, if the version has one, for `--epoch`.
+    pub epoch: Option<String>,
+
+    /// The iteration, if the package has one, for `--iteration`.
+    pub iteration: Option<String>,
+
+    /// The package architecture, for `--architecture`. fpm translates
+    /// architecture names between package types.
+    pub arch: String,
+
+    /// The names of the dependencies, each for a `--depends`.
+    pub depends: Vec<String>,
+
+    /// The description, if the package has one, for `--description`.
+    pub description: Option<String>,
+}
+
+impl FpmArgs {
+    /// Get the fpm arguments for a package.
+    #[cfg_attr(
+        not(any(feature = "debian", feature = "rpm")),
+        allow(unreachable_code, unused_variables)
+    )]
+    pub fn from_package<P: RemotePackage + ?Sized>(package: &P) -> Result<Self, PkgError> {
+        let iteration = package.package_iteration().map(str::to_owned);
+        let (input_type, epoch, version): (&str, _, String) = match package.package_type() {
+            // Debian versions include the epoch and iteration.
+            #[cfg(feature = "debian")]
+            RemotePackageType::Deb => {
+                let version = package.package_version()?;
+                let (_, upstream, _) = crate::version::split_debian(version);
+                let epoch = version.split_once(':').map(|(epoch, _)| epoch.to_owned());
+                ("deb", epoch, upstream.to_owned())
+            }
+            #[cfg(feature = "rpm")]
+            RemotePackageType::Rpm => ("rpm", None, package.package_version()?.to_owned()),
+        };
+
+        Ok(Self {
+            input_type: input_type.to_owned(),
+            name: package.package_name()?.to_owned(),
+            version,
+            epoch,
+            iteration,
+            arch: package.package_arch()?.to_owned(),
+            depends: package.package_dependencies(),
+            description: package.package_description().map(str::to_owned),
+        })
+    }
+
+    /// The arguments for fpm, without the output type or the input files.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "-s".to_string(),
+            self.input_type.clone(),
+            "--name".to_string(),
+            self.name.clone(),
+            "--version".to_string(),
+            self.version.clone(),
+        ];
+        if let Some(epoch) = &self.epoch {
+            args.extend(vec!["--epoch".to_string(), epoch.clone()]);
+        }
+        if let Some(iteration) = &self.iteration {
+            args.extend(vec!["--iteration".to_string(), iteration.clone()]);
+        }
+        args.extend(vec!["--architecture".to_string(), self.arch.clone()]);
+        for depend in &self.depends {
+            args.extend(vec!["--depends".to_string(), depend.clone()]);
+        }
+        if let Some(description) = &self.description {
+            args.extend(vec!["--description".to_string(), description.clone()]);
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_from_package() {
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+        let package = crate::rpm::RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+
+        let args = package.to_fpm_args().unwrap();
+        assert_eq!(args.input_type, "rpm");
+        assert_eq!(args.version, "1.0");
+        assert_eq!(args.epoch, None);
+        assert_eq!(args.iteration.as_deref(), Some("1"));
+        assert_eq!(args.description, None);
+        assert_eq!(
+            args.to_args()[..10],
+            [
+                "-s",
+                "rpm",
+                "--name",
+                "hello",
+                "--version",
+                "1.0",
+                "--iteration",
+                "1",
+                "--architecture",
+                "noarch"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_args() {
+        let args = FpmArgs {
+            input_type: "deb".to_string(),
+            name: "hello".to_string(),
+            version: "2.10".to_string(),
+            epoch: Some("1".to_string()),
+            iteration: Some("3".to_string()),
+            arch: "amd64".to_string(),
+            depends: vec!["libc6".to_string(), "zlib1g".to_string()],
+            description: Some("example package".to_string()),
+        };
+        assert_eq!(
+            args.to_args(),
+            [
+                "-s",
+                "deb",
+                "--name",
+                "hello",
+                "--version",
+                "2.10",
+                "--epoch",
+                "1",
+                "--iteration",
+                "3",
+                "--architecture",
+                "amd64",
+                "--depends",
+                "libc6",
+                "--depends",
+                "zlib1g",
+                "--description",
+                "example package",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1556 @@
+//! HTTP support shared by the package and repository modules.
+//!
+//! The `http-blocking` feature provides the blocking client most of the
+//! crate uses, and `http-async` provides an async client for
+//! [`fetch`](crate::fetch). Either can be enabled without the other.
+//!
+//! Every request, from the blocking and async clients alike and for
+//! packages and repository indexes alike, goes through one shared client of
+//! its kind, which applies the [`RateLimit`] set with [`set_rate_limit`] and
+//! any limit on the connections to each host, waits for servers that
+//! throttle it, and then hands the request to the [`Transport`], normally
+//! reqwest itself. Both clients are built from the [`ClientConfig`] set
+//! with [`set_client_config`].
+//!
+//! In a browser, where the async client uses the `fetch` API, the browser
+//! manages connections itself and there is no clock to wait with, so the
+//! requests are sent as they are.
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
+use std::fs;
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io;
+#[cfg(feature = "http-blocking")]
+use std::io::Read;
+use std::net::IpAddr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+use std::pin::Pin;
+#[cfg(feature = "http-blocking")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "http-blocking")]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Condvar;
+use std::sync::{Mutex, RwLock};
+#[cfg(not(target_arch = "wasm32"))]
+use std::task::Waker;
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+use std::task::{Context, Poll};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Instant, SystemTime};
+
+use once_cell::sync::Lazy;
+
+#[cfg(feature = "http-blocking")]
+use crate::compression::Compression;
+use crate::PkgError;
+
+/// Credentials for fetching from a private registry or feed.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Auth {
+    /// Send an `Authorization: Bearer <token>` header.
+    Bearer(String),
+
+    /// Use HTTP basic authentication.
+    Basic {
+        /// The user name.
+        username: String,
+
+        /// The password, if any.
+        password: Option<String>,
+    },
+
+    /// Send the token in a custom header, e.g. NuGet's `X-NuGet-ApiKey`.
+    Header {
+        /// The header name.
+        name: String,
+
+        /// The header value.
+        value: String,
+    },
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the secrets themselves.
+        match self {
+            Auth::Bearer(_) => f.write_str("Bearer(..)"),
+            Auth::Basic { username, .. } => write!(f, "Basic({}, ..)", username),
+            Auth::Header { name, .. } => write!(f, "Header({}, ..)", name),
+        }
+    }
+}
+
+/// How often the clients may send requests to each host.
+///
+/// Requests over the limit wait for their turn rather than failing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimit {
+    /// The requests per second to any host without its own limit.
+    per_host: Option<f64>,
+
+    /// Limits for particular hosts, by host name.
+    hosts: Vec<(String, f64)>,
+}
+
+impl RateLimit {
+    /// No limit on requests. This is the default.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Allow up to `requests_per_second` to each host.
+    pub fn per_host(requests_per_second: f64) -> Self {
+        Self {
+            per_host: Some(requests_per_second),
+            hosts: Vec::new(),
+        }
+    }
+
+    /// Allow up to `requests_per_second` to `host`, e.g. `deb.debian.org`,
+    /// in place of the limit for every host.
+    pub fn with_host(mut self, host: &str, requests_per_second: f64) -> Self {
+        self.hosts.retain(|(name, _)| name != host);
+        self.hosts
+            .push((host.to_ascii_lowercase(), requests_per_second));
+        self
+    }
+
+    /// The least time between requests to `host`, if it's limited.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn interval(&self, host: &str) -> Option<Duration> {
+        let rate = self
+            .hosts
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(host))
+            .map(|(_, rate)| *rate)
+            .or(self.per_host)?;
+        (rate > 0.0).then(|| Duration::from_secs_f64(1.0 / rate))
+    }
+}
+
+/// The rate limit, and when each host may next be sent a request, along
+/// with the connections open to each host; see
+/// [`ClientConfig::with_max_connections_per_host`].
+#[derive(Debug, Default)]
+struct Limiter {
+    limit: RateLimit,
+    #[cfg(not(target_arch = "wasm32"))]
+    next: HashMap<String, Instant>,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    max_connections: Option<usize>,
+    #[cfg(not(target_arch = "wasm32"))]
+    open: HashMap<String, usize>,
+
+    /// The async requests waiting for a connection to close.
+    #[cfg(not(target_arch = "wasm32"))]
+    waiting: Vec<Waker>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Limiter {
+    /// Book the next request to `host`, returning how long to wait before
+    /// sending it.
+    fn reserve(&mut self, host: &str, now: Instant) -> Duration {
+        let interval = match self.limit.interval(host) {
+            Some(interval) => interval,
+            None => return Duration::from_secs(0),
+        };
+        let slot = match self.next.get(host) {
+            Some(&next) if next > now => next,
+            _ => now,
+        };
+        let _ = self.next.insert(host.to_owned(), slot + interval);
+        slot - now
+    }
+
+    /// Count a connection to `host`, returning false instead if as many
+    /// are open as the limit allows.
+    fn try_open(&mut self, host: &str) -> bool {
+        let open = self.open.get(host).copied().unwrap_or(0);
+        if self.max_connections.is_some_and(|max| open >= max) {
+            return false;
+        }
+        let _ = self.open.insert(host.to_owned(), open + 1);
+        true
+    }
+
+    /// Stop counting a connection to `host`, waking the requests waiting
+    /// for one.
+    fn close(&mut self, host: &str) {
+        if let Some(open) = self.open.get_mut(host) {
+            *open -= 1;
+            if *open == 0 {
+                let _ = self.open.remove(host);
+            }
+        }
+        for waker in self.waiting.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+/// A connection counted against the limit for its host until it's dropped,
+/// which happens when the response it was sent for is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+struct Connection(Option<String>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(host) = &self.0 {
+            LIMITER
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .close(host);
+            CONNECTION_CLOSED.notify_all();
+        }
+    }
+}
+
+/// Waits for a connection to the host of a request to be allowed under the
+/// limit, without blocking.
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+struct OpenConnection(Option<String>);
+
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+impl Future for OpenConnection {
+    type Output = Connection;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Connection> {
+        let host = match self.0.clone() {
+            Some(host) => host,
+            None => return Poll::Ready(Connection(None)),
+        };
+        let mut limiter = LIMITER
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if limiter.max_connections.is_none() {
+            Poll::Ready(Connection(None))
+        } else if limiter.try_open(&host) {
+            Poll::Ready(Connection(self.0.take()))
+        } else {
+            limiter.waiting.push(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// The IP versions the clients connect over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AddressFamily {
+    /// Connect over either, trying the addresses in the order they resolve
+    /// to. This is the default.
+    #[default]
+    Any,
+
+    /// Only connect over IPv4.
+    V4Only,
+
+    /// Only connect over IPv6.
+    V6Only,
+
+    /// Connect over IPv4, and only over IPv6 if that fails, for hosts with
+    /// broken IPv6 addresses.
+    PreferV4,
+}
+
+/// Connection settings for the clients; see [`set_client_config`].
+///
+/// When scanning many packages from one host, keeping connections open and
+/// multiplexing requests over HTTP/2 can matter more than anything else.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientConfig {
+    http2_prior_knowledge: bool,
+    pool_idle_timeout: Option<Duration>,
+    pool_max_idle_per_host: usize,
+    max_connections_per_host: Option<usize>,
+    tcp_keepalive: Option<Duration>,
+    content_decoding: bool,
+    max_redirects: usize,
+    cross_host_redirects: bool,
+    root_certificates: Vec<Vec<u8>>,
+    address_family: AddressFamily,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    max_retry_wait: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            http2_prior_knowledge: false,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            max_connections_per_host: None,
+            tcp_keepalive: None,
+            content_decoding: true,
+            max_redirects: 10,
+            cross_host_redirects: true,
+            root_certificates: Vec::new(),
+            address_family: AddressFamily::Any,
+            connect_timeout: None,
+            read_timeout: Some(Duration::from_secs(30)),
+            max_retry_wait: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Apply the settings of a [`ClientConfig`] to a reqwest client builder.
+/// The blocking and async builders have the same methods for these, but no
+/// trait in common.
+#[cfg(not(target_arch = "wasm32"))]
+macro_rules! configure_builder {
+    ($config:expr, $builder:expr, $local_address:expr) => {{
+        let config: &ClientConfig = $config;
+        let mut builder = $builder
+            .local_address($local_address)
+            .redirect(config.redirect_policy())
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .tcp_keepalive(config.tcp_keepalive);
+        if config.http2_prior_knowledge {
+            builder = builder.http2_prior_knowledge();
+        }
+        for bundle in &config.root_certificates {
+            for pem in pem_certificates(bundle)? {
+                builder = builder.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+            }
+        }
+        builder
+    }};
+}
+
+impl ClientConfig {
+    /// The default settings: HTTP/2 only where TLS negotiates it, idle
+    /// connections kept for 90 seconds with no limit on how many, no limit
+    /// on open connections, no TCP keep-alive, package downloads decoded,
+    /// up to 10 redirects followed, including to other hosts, only the
+    /// built-in root certificates trusted, [`AddressFamily::Any`], no
+    /// connect timeout, a 30 second read timeout, and up to a minute spent
+    /// waiting for servers that throttle requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Speak HTTP/2 to every server without negotiating it first. Only use
+    /// this when every server the client talks to supports HTTP/2, as
+    /// others will fail. Otherwise HTTP/2 is used where TLS negotiates it.
+    pub fn with_http2_prior_knowledge(mut self, enabled: bool) -> Self {
+        self.http2_prior_knowledge = enabled;
+        self
+    }
+
+    /// Close idle connections after `timeout`, or never with `None`.
+    /// Defaults to 90 seconds.
+    pub fn with_pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = timeout;
+        self
+    }
+
+    /// Keep up to `max` idle connections open to each host for reuse.
+    /// Defaults to no limit. This doesn't limit the connections in use; see
+    /// [`ClientConfig::with_max_connections_per_host`] for that.
+    pub fn with_pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = max;
+        self
+    }
+
+    /// Have at most `max` requests to each host in flight at once, or any
+    /// number with `None`, the default. Other requests wait for their turn.
+    /// A request counts until its response is dropped, or read in full with
+    /// methods such as `text`, as its connection is in use until then.
+    ///
+    /// A thread that keeps a response open while sending another request to
+    /// the same host waits for itself if the limit is reached, so `max` must
+    /// allow for that. A `max` of zero is taken as one.
+    pub fn with_max_connections_per_host(mut self, max: Option<usize>) -> Self {
+        self.max_connections_per_host = max.map(|max| max.max(1));
+        self
+    }
+
+    /// Send TCP keep-alive probes on idle connections every `interval`, or
+    /// not at all with `None`, the default.
+    pub fn with_tcp_keepalive(mut self, interval: Option<Duration>) -> Self {
+        self.tcp_keepalive = interval;
+        self
+    }
+
+    /// Decode package downloads sent with a gzip or zstd `Content-Encoding`,
+    /// so that packages are parsed and hashed as the files themselves. This
+    /// is the default. Otherwise the bytes are read as sent.
+    ///
+    /// Some servers mark files as encoded without encoding them again, so a
+    /// body is only decoded if it starts with the magic of its encoding.
+    pub fn with_content_decoding(mut self, enabled: bool) -> Self {
+        self.content_decoding = enabled;
+        self
+    }
+
+    /// Follow up to `max` redirects, failing after that. Zero follows none.
+    /// Defaults to 10.
+    pub fn with_max_redirects(mut self, max: usize) -> Self {
+        self.max_redirects = max;
+        self
+    }
+
+    /// Whether to follow redirects to other hosts, such as those from a
+    /// mirror redirector. This is the default. Otherwise they fail.
+    pub fn with_cross_host_redirects(mut self, enabled: bool) -> Self {
+        self.cross_host_redirects = enabled;
+        self
+    }
+
+    /// Trust the root certificates in a PEM bundle, such as that of a
+    /// private CA, as well as the built-in roots. The bundle is checked when
+    /// the client is built.
+    pub fn with_root_certificates_pem<B: Into<Vec<u8>>>(mut self, pem: B) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Trust the root certificates in a PEM bundle file; see
+    /// [`ClientConfig::with_root_certificates_pem`].
+    pub fn with_root_certificates_file<P: AsRef<Path>>(self, path: P) -> Result<Self, PkgError> {
+        Ok(self.with_root_certificates_pem(fs::read(path)?))
+    }
+
+    /// Connect over the given IP versions. Defaults to
+    /// [`AddressFamily::Any`].
+    pub fn with_address_family(mut self, address_family: AddressFamily) -> Self {
+        self.address_family = address_family;
+        self
+    }
+
+    /// Give up connecting to a server after `timeout`, or never with
+    /// `None`, the default. This covers the TCP and TLS handshakes.
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Give up waiting for a response, or for each read of its body, after
+    /// `timeout`, or never with `None`. A slow download doesn't time out as
+    /// long as it keeps making progress. Defaults to 30 seconds.
+    ///
+    /// The async client has no timeout for each read, so for it this limits
+    /// the whole request instead. A single download can be given its own
+    /// timeout with `from_url_with_timeout` or `from_url_async_with_timeout`.
+    pub fn with_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Wait up to `max` in total for servers that throttle requests with a
+    /// 429 or 503 status and a `Retry-After` header, sending the request
+    /// again when asked. If a server asks for longer, the request fails with
+    /// [`PkgError::Throttled`]. Zero never waits. Defaults to a minute.
+    pub fn with_max_retry_wait(mut self, max: Duration) -> Self {
+        self.max_retry_wait = max;
+        self
+    }
+
+    /// The policy for following redirects.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn redirect_policy(&self) -> reqwest::redirect::Policy {
+        let max_redirects = self.max_redirects;
+        let cross_host_redirects = self.cross_host_redirects;
+        reqwest::redirect::Policy::custom(move |attempt| {
+            let cross_host = attempt
+                .previous()
+                .first()
+                .is_some_and(|first| first.host_str() != attempt.url().host_str());
+            if attempt.previous().len() > max_redirects {
+                attempt.error("too many redirects")
+            } else if cross_host && !cross_host_redirects {
+                let error = format!("redirect to another host: {}", attempt.url());
+                attempt.error(error)
+            } else {
+                attempt.follow()
+            }
+        })
+    }
+
+    /// Build the blocking clients for these settings.
+    #[cfg(feature = "http-blocking")]
+    fn build(&self) -> Result<SharedClient<reqwest::blocking::Client>, PkgError> {
+        self.build_with(Self::build_client)
+    }
+
+    /// Build the async clients for these settings.
+    #[cfg(feature = "http-async")]
+    fn build_async(&self) -> Result<SharedClient<reqwest::Client>, PkgError> {
+        self.build_with(Self::build_async_client)
+    }
+
+    /// Build the clients for these settings with `build_client`, which
+    /// builds a client connecting from the given local address, if any.
+    fn build_with<C>(
+        &self,
+        build_client: fn(&Self, Option<IpAddr>) -> Result<C, PkgError>,
+    ) -> Result<SharedClient<C>, PkgError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let (client, fallback) = {
+            let v4 = Some(IpAddr::from(Ipv4Addr::UNSPECIFIED));
+            let v6 = Some(IpAddr::from(Ipv6Addr::UNSPECIFIED));
+            match self.address_family {
+                AddressFamily::Any => (build_client(self, None)?, None),
+                AddressFamily::V4Only => (build_client(self, v4)?, None),
+                AddressFamily::V6Only => (build_client(self, v6)?, None),
+                AddressFamily::PreferV4 => {
+                    (build_client(self, v4)?, Some(build_client(self, None)?))
+                }
+            }
+        };
+        // The browser picks the addresses to connect to.
+        #[cfg(target_arch = "wasm32")]
+        let (client, fallback) = (build_client(self, None)?, None);
+        Ok(SharedClient {
+            config: self.clone(),
+            client,
+            fallback,
+        })
+    }
+
+    /// Build a blocking client with these settings, connecting from
+    /// `local_address` if given, which limits it to that address's IP
+    /// version.
+    #[cfg(feature = "http-blocking")]
+    fn build_client(
+        &self,
+        local_address: Option<IpAddr>,
+    ) -> Result<reqwest::blocking::Client, PkgError> {
+        let builder = configure_builder!(self, reqwest::blocking::Client::builder(), local_address)
+            .connect_timeout(self.connect_timeout)
+            .timeout(self.read_timeout);
+        Ok(builder.build()?)
+    }
+
+    /// Build an async client with these settings, connecting from
+    /// `local_address` if given, which limits it to that address's IP
+    /// version.
+    #[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+    fn build_async_client(
+        &self,
+        local_address: Option<IpAddr>,
+    ) -> Result<reqwest::Client, PkgError> {
+        let mut builder = configure_builder!(self, reqwest::Client::builder(), local_address);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.timeout(timeout);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Build an async client for the browser, which manages connections
+    /// itself.
+    #[cfg(all(feature = "http-async", target_arch = "wasm32"))]
+    fn build_async_client(
+        &self,
+        _local_address: Option<IpAddr>,
+    ) -> Result<reqwest::Client, PkgError> {
+        Ok(reqwest::Client::builder().build()?)
+    }
+}
+
+/// Split a PEM bundle into its certificates, failing if it has none.
+#[cfg(not(target_arch = "wasm32"))]
+fn pem_certificates(bundle: &[u8]) -> Result<Vec<&[u8]>, PkgError> {
+    const END: &[u8] = b"-----END CERTIFICATE-----";
+    let mut certificates = Vec::new();
+    let mut rest = bundle;
+    while let Some(start) = find(rest, b"-----BEGIN CERTIFICATE-----") {
+        let end = match find(&rest[start..], END) {
+            Some(end) => start + end + END.len(),
+            None => break,
+        };
+        certificates.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    if certificates.is_empty() {
+        let error = "no PEM certificates in root certificate bundle";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, error).into());
+    }
+    Ok(certificates)
+}
+
+/// The offset of the first occurrence of `needle` in `haystack`.
+#[cfg(not(target_arch = "wasm32"))]
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Sends the requests of the clients.
+///
+/// The default sends them over the network with reqwest. Another transport,
+/// such as [`MockTransport`](crate::testing::MockTransport), can be set
+/// with [`set_transport`] to answer them some other way. Requests of the
+/// async client are sent through it too, as blocking requests, and the whole
+/// body of each response is read before it's returned.
+#[cfg(feature = "http-blocking")]
+pub trait Transport: Send + Sync {
+    /// Send a request, returning the response whatever its status.
+    fn send(
+        &self,
+        request: reqwest::blocking::Request,
+    ) -> Result<reqwest::blocking::Response, PkgError>;
+}
+
+/// The clients of one kind, blocking or async.
+#[derive(Debug, Clone)]
+struct SharedClient<C> {
+    /// The settings the clients were built with.
+    config: ClientConfig,
+
+    client: C,
+
+    /// The client to retry with when the first can't connect, for
+    /// [`AddressFamily::PreferV4`].
+    fallback: Option<C>,
+}
+
+/// The client shared by every blocking request, so that connections are
+/// reused.
+#[cfg(feature = "http-blocking")]
+static CLIENT: Lazy<RwLock<SharedClient<reqwest::blocking::Client>>> = Lazy::new(|| {
+    let client = ClientConfig::default()
+        .build()
+        .expect("failed to build the HTTP client");
+    RwLock::new(client)
+});
+
+/// The client shared by every async request, so that connections are
+/// reused.
+#[cfg(feature = "http-async")]
+static ASYNC_CLIENT: Lazy<RwLock<SharedClient<reqwest::Client>>> = Lazy::new(|| {
+    let client = ClientConfig::default()
+        .build_async()
+        .expect("failed to build the HTTP client");
+    RwLock::new(client)
+});
+
+/// Whether package downloads are decoded; see
+/// [`ClientConfig::with_content_decoding`].
+#[cfg(feature = "http-blocking")]
+static CONTENT_DECODING: AtomicBool = AtomicBool::new(true);
+
+/// The rate limit and connection limit applied by [`send`] and
+/// [`send_async`].
+static LIMITER: Lazy<Mutex<Limiter>> = Lazy::new(Mutex::default);
+
+/// Signalled when a connection counted by [`LIMITER`] closes, for the
+/// blocking requests waiting for one.
+#[cfg(not(target_arch = "wasm32"))]
+static CONNECTION_CLOSED: Lazy<Condvar> = Lazy::new(Condvar::new);
+
+/// The transport set with [`set_transport`], if any.
+#[cfg(feature = "http-blocking")]
+static TRANSPORT: Lazy<RwLock<Option<Arc<dyn Transport>>>> = Lazy::new(RwLock::default);
+
+/// Send every request through `transport`, or over the network again if
+/// `None`, for the rest of the process.
+#[cfg(feature = "http-blocking")]
+pub fn set_transport(transport: Option<Arc<dyn Transport>>) {
+    *TRANSPORT
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = transport;
+}
+
+/// The transport set with [`set_transport`], if any.
+#[cfg(feature = "http-blocking")]
+fn transport() -> Option<Arc<dyn Transport>> {
+    TRANSPORT
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Set how often the clients may send requests to each host, for the rest
+/// of the process.
+pub fn set_rate_limit(limit: RateLimit) {
+    let mut limiter = LIMITER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    limiter.limit = limit;
+    #[cfg(not(target_arch = "wasm32"))]
+    limiter.next.clear();
+}
+
+/// Replace the shared clients with ones using `config`, for the rest of the
+/// process. Connections of the old clients aren't reused.
+pub fn set_client_config(config: &ClientConfig) -> Result<(), PkgError> {
+    #[cfg(feature = "http-blocking")]
+    let client = config.build()?;
+    #[cfg(feature = "http-async")]
+    let async_client = config.build_async()?;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        LIMITER
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .max_connections = config.max_connections_per_host;
+        CONNECTION_CLOSED.notify_all();
+    }
+    #[cfg(feature = "http-blocking")]
+    {
+        CONTENT_DECODING.store(config.content_decoding, Ordering::Relaxed);
+        *CLIENT
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = client;
+    }
+    #[cfg(feature = "http-async")]
+    {
+        *ASYNC_CLIENT
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = async_client;
+    }
+    Ok(())
+}
+
+/// The shared blocking client. Requests built with it should be sent with
+/// [`send`] so that the rate limit applies.
+#[cfg(feature = "http-blocking")]
+pub(crate) fn client() -> reqwest::blocking::Client {
+    shared_client().client
+}
+
+/// The shared blocking clients.
+#[cfg(feature = "http-blocking")]
+fn shared_client() -> SharedClient<reqwest::blocking::Client> {
+    CLIENT
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// The shared async client. Requests built with it should be sent with
+/// [`send_async`] so that the rate limit applies.
+#[cfg(feature = "http-async")]
+pub(crate) fn async_client() -> reqwest::Client {
+    shared_async_client().client
+}
+
+/// The shared async clients.
+#[cfg(feature = "http-async")]
+fn shared_async_client() -> SharedClient<reqwest::Client> {
+    ASYNC_CLIENT
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Send a request over the network, retrying with the fallback client if
+/// there is one and the first can't connect.
+#[cfg(feature = "http-blocking")]
+fn execute(request: reqwest::blocking::Request) -> Result<reqwest::blocking::Response, PkgError> {
+    let shared = shared_client();
+    let (fallback, retry) = match shared.fallback {
+        Some(fallback) => (fallback, request.try_clone()),
+        None => return Ok(shared.client.execute(request)?),
+    };
+    match (shared.client.execute(request), retry) {
+        (Err(err), Some(retry)) if err.is_connect() => {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(error = %err, "retrying with any address family");
+            Ok(fallback.execute(retry)?)
+        }
+        (result, _) => Ok(result?),
+    }
+}
+
+/// Send an async request over the network, retrying with the fallback
+/// client if there is one and the first can't connect.
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+async fn execute_async(request: reqwest::Request) -> Result<reqwest::Response, PkgError> {
+    let shared = shared_async_client();
+    let (fallback, retry) = match shared.fallback {
+        Some(fallback) => (fallback, request.try_clone()),
+        None => return Ok(shared.client.execute(request).await?),
+    };
+    match (shared.client.execute(request).await, retry) {
+        (Err(err), Some(retry)) if err.is_connect() => {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(error = %err, "retrying with any address family");
+            Ok(fallback.execute(retry).await?)
+        }
+        (result, _) => Ok(result?),
+    }
+}
+
+/// The most times a throttled request is sent again.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_THROTTLED_RETRIES: u32 = 10;
+
+/// How long a request has waited for servers throttling it; see
+/// [`ClientConfig::with_max_retry_wait`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+struct Throttle {
+    max_wait: Duration,
+    waited: Duration,
+    retries: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Throttle {
+    /// Start waiting up to `max_wait` in total.
+    fn new(max_wait: Duration) -> Self {
+        Self {
+            max_wait,
+            waited: Duration::from_secs(0),
+            retries: 0,
+        }
+    }
+
+    /// How long to wait before sending the request again, given the delay
+    /// its response asked for, if it was throttled, and whether the request
+    /// can be sent again. `None` means the response is final.
+    fn next_wait(
+        &mut self,
+        delay: Option<Duration>,
+        can_retry: bool,
+    ) -> Result<Option<Duration>, PkgError> {
+        match delay {
+            None => Ok(None),
+            Some(delay)
+                if can_retry
+                    && self.retries < MAX_THROTTLED_RETRIES
+                    && self.waited + delay <= self.max_wait =>
+            {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(?delay, "throttled, waiting to retry");
+                self.waited += delay;
+                self.retries += 1;
+                Ok(Some(delay))
+            }
+            Some(delay) => Err(PkgError::Throttled(delay)),
+        }
+    }
+}
+
+/// Book the next request to the host of `url` under the rate limit,
+/// returning how long to wait before sending it.
+#[cfg(not(target_arch = "wasm32"))]
+fn rate_limit_wait(url: &reqwest::Url) -> Duration {
+    let wait = match url.host_str() {
+        Some(host) => LIMITER
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .reserve(host, Instant::now()),
+        None => Duration::from_secs(0),
+    };
+    #[cfg(feature = "tracing")]
+    if wait > Duration::from_secs(0) {
+        tracing::debug!(?wait, "waiting for rate limit");
+    }
+    wait
+}
+
+/// Wait until a connection to the host of `url` is allowed under
+/// [`ClientConfig::with_max_connections_per_host`].
+#[cfg(feature = "http-blocking")]
+fn open_connection(url: &reqwest::Url) -> Connection {
+    let host = match url.host_str() {
+        Some(host) => host,
+        None => return Connection(None),
+    };
+    let mut limiter = LIMITER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if limiter.max_connections.is_none() {
+        return Connection(None);
+    }
+    while !limiter.try_open(host) {
+        limiter = CONNECTION_CLOSED
+            .wait(limiter)
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+    Connection(Some(host.to_owned()))
+}
+
+/// Send a request built with [`client`] through the transport, first
+/// waiting for its turn under the rate limit, and sending it again if the
+/// server throttles it; see [`ClientConfig::with_max_retry_wait`].
+#[cfg(feature = "http-blocking")]
+pub(crate) fn send(
+    request: reqwest::blocking::RequestBuilder,
+) -> Result<reqwest::blocking::Response, PkgError> {
+    let mut request = request.build()?;
+    #[cfg(feature = "tracing")]
+    let _span =
+        tracing::debug_span!("request", method = %request.method(), url = %request.url()).entered();
+
+    let mut throttle = Throttle::new(shared_client().config.max_retry_wait);
+    loop {
+        let retry = request.try_clone();
+        let response = send_once(request)?;
+        let delay = retry_after(response.status(), response.headers(), SystemTime::now());
+        match (throttle.next_wait(delay, retry.is_some())?, retry) {
+            (Some(delay), Some(retry)) => {
+                std::thread::sleep(delay);
+                request = retry;
+            }
+            _ => return Ok(response),
+        }
+    }
+}
+
+/// Send a request built with [`async_client`] as [`send`] does, waiting
+/// without blocking.
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+pub(crate) async fn send_async(
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, PkgError> {
+    let mut request = request.build()?;
+    let mut throttle = Throttle::new(shared_async_client().config.max_retry_wait);
+    loop {
+        let retry = request.try_clone();
+        let response = send_once_async(request).await?;
+        let delay = retry_after(response.status(), response.headers(), SystemTime::now());
+        let wait = throttle.next_wait(delay, retry.is_some())?;
+        match (wait, retry) {
+            (Some(delay), Some(retry)) => {
+                tokio::time::sleep(delay).await;
+                request = retry;
+            }
+            _ => return Ok(response),
+        }
+    }
+}
+
+/// Send a request built with [`async_client`] from the browser, which has
+/// no clock to apply the rate limit or wait for throttling with.
+#[cfg(all(feature = "http-async", target_arch = "wasm32"))]
+pub(crate) async fn send_async(
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, PkgError> {
+    Ok(request.send().await?)
+}
+
+/// How long a response throttling a request asks to wait before sending it
+/// again: the `Retry-After` of a 429 or 503 response, in seconds or as a
+/// date.
+#[cfg(not(target_arch = "wasm32"))]
+fn retry_after(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    now: SystemTime,
+) -> Option<Duration> {
+    if status != reqwest::StatusCode::TOO_MANY_REQUESTS
+        && status != reqwest::StatusCode::SERVICE_UNAVAILABLE
+    {
+        return None;
+    }
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    match value.parse() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(date.duration_since(now).unwrap_or_default())
+        }
+    }
+}
+
+/// Send a request through the transport once, after waiting for its turn
+/// under the connection and rate limits. The connection is counted until
+/// the response is dropped.
+#[cfg(feature = "http-blocking")]
+fn send_once(request: reqwest::blocking::Request) -> Result<reqwest::blocking::Response, PkgError> {
+    let connection = open_connection(request.url());
+    let wait = rate_limit_wait(request.url());
+    if wait > Duration::from_secs(0) {
+        std::thread::sleep(wait);
+    }
+
+    let result = match transport() {
+        Some(transport) => transport.send(request),
+        None => execute(request),
+    }
+    .map(|mut response| {
+        let _ = response.extensions_mut().insert(connection);
+        response
+    });
+
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(response) => tracing::debug!(
+            status = response.status().as_u16(),
+            content_length = ?response.content_length(),
+            "received response"
+        ),
+        Err(err) => tracing::warn!(error = %err, "request failed"),
+    }
+    result
+}
+
+/// Send an async request through the transport once, after waiting for its
+/// turn under the connection and rate limits. The connection is counted
+/// until the response is dropped.
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+async fn send_once_async(request: reqwest::Request) -> Result<reqwest::Response, PkgError> {
+    let connection = OpenConnection(request.url().host_str().map(str::to_owned)).await;
+    let mut response = send_once_through_async(request).await?;
+    let _ = response.extensions_mut().insert(connection);
+    Ok(response)
+}
+
+/// Send an async request through the transport once, after waiting for its
+/// turn under the rate limit.
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+async fn send_once_through_async(request: reqwest::Request) -> Result<reqwest::Response, PkgError> {
+    let wait = rate_limit_wait(request.url());
+    if wait > Duration::from_secs(0) {
+        tokio::time::sleep(wait).await;
+    }
+
+    // The transport is blocking, so it runs where blocking is allowed. Not
+    // every error can be passed back between threads, so they come back as
+    // I/O errors.
+    #[cfg(feature = "http-blocking")]
+    if let Some(transport) = transport() {
+        let send = tokio::task::spawn_blocking(move || {
+            send_through(transport.as_ref(), request).map_err(|err| match err {
+                PkgError::IoError(err) => err,
+                PkgError::HTTPError(err) => io::Error::other(err),
+                err => io::Error::other(err.to_string()),
+            })
+        });
+        let result = send.await.map_err(io::Error::other)?;
+        return Ok(result?);
+    }
+    execute_async(request).await
+}
+
+/// Send an async request through a [`Transport`], as a blocking request,
+/// reading the whole body of the response. This blocks, so must not be
+/// called from an async task.
+#[cfg(all(
+    feature = "http-async",
+    feature = "http-blocking",
+    not(target_arch = "wasm32")
+))]
+fn send_through(
+    transport: &dyn Transport,
+    request: reqwest::Request,
+) -> Result<reqwest::Response, PkgError> {
+    let mut blocking =
+        reqwest::blocking::Request::new(request.method().clone(), request.url().clone());
+    *blocking.headers_mut() = request.headers().clone();
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        *blocking.body_mut() = Some(body.to_vec().into());
+    }
+
+    let response = transport.send(blocking)?;
+    let mut builder = http_crate::Response::builder()
+        .status(response.status())
+        .version(response.version());
+    builder = reqwest::ResponseBuilderExt::url(builder, response.url().clone());
+    for (name, value) in response.headers() {
+        builder = builder.header(name, value);
+    }
+    let response = builder
+        .body(response.bytes()?)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(response.into())
+}
+
+/// Send a blocking GET request for `url`, treating non-success statuses as
+/// errors.
+#[cfg(feature = "http-blocking")]
+pub(crate) fn get(url: &str) -> Result<reqwest::blocking::Response, PkgError> {
+    get_with_auth(url, None)
+}
+
+/// Send a blocking GET request for a package file, treating non-success
+/// statuses as errors, and start reading its body as the file itself; see
+/// [`Transfer::from_response`].
+#[cfg(feature = "http-blocking")]
+pub(crate) fn get_package(url: &str) -> Result<Transfer<Box<dyn Read>>, PkgError> {
+    get(url).and_then(Transfer::from_response)
+}
+
+/// Send a blocking GET request for a package file like [`get_package`],
+/// waiting up to `timeout` for the response and each read of its body in
+/// place of [`ClientConfig::with_read_timeout`], if given.
+#[cfg(feature = "http-blocking")]
+pub(crate) fn get_package_with_timeout(
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<Transfer<Box<dyn Read>>, PkgError> {
+    let response = send(with_timeout(client().get(url), timeout))?.error_for_status()?;
+    Transfer::from_response(response)
+}
+
+/// Set the timeout of a blocking request, if given, in place of the read
+/// timeout of the client.
+#[cfg(feature = "http-blocking")]
+pub(crate) fn with_timeout(
+    request: reqwest::blocking::RequestBuilder,
+    timeout: Option<Duration>,
+) -> reqwest::blocking::RequestBuilder {
+    match timeout {
+        Some(timeout) => request.timeout(timeout),
+        None => request,
+    }
+}
+
+/// Set the timeout of an async request, if given, in place of the read
+/// timeout of the client. The timeout covers the whole request, as the
+/// async client has no timeout for each read.
+#[cfg(all(feature = "http-async", not(target_arch = "wasm32")))]
+pub(crate) fn with_async_timeout(
+    request: reqwest::RequestBuilder,
+    timeout: Option<Duration>,
+) -> reqwest::RequestBuilder {
+    match timeout {
+        Some(timeout) => request.timeout(timeout),
+        None => request,
+    }
+}
+
+/// Send a blocking GET request for `url` with optional credentials,
+/// treating non-success statuses as errors.
+#[cfg(feature = "http-blocking")]
+pub(crate) fn get_with_auth(
+    url: &str,
+    auth: Option<&Auth>,
+) -> Result<reqwest::blocking::Response, PkgError> {
+    let request = with_auth(client().get(url), auth);
+
+    // Send an HTTP request and make sure the server actually found the file.
+    let response = send(request)?.error_for_status()?;
+    Ok(response)
+}
+
+/// Add credentials, if any, to a blocking request.
+#[cfg(feature = "http-blocking")]
+pub(crate) fn with_auth(
+    request: reqwest::blocking::RequestBuilder,
+    auth: Option<&Auth>,
+) -> reqwest::blocking::RequestBuilder {
+    match auth {
+        Some(Auth::Bearer(token)) => request.bearer_auth(token),
+        Some(Auth::Basic { username, password }) => request.basic_auth(username, password.as_ref()),
+        Some(Auth::Header { name, value }) => request.header(name.as_str(), value.as_str()),
+        None => request,
+    }
+}
+
+/// Send a blocking POST request with a JSON body to `url`, treating
+/// non-success statuses as errors.
+#[cfg(all(feature = "http-blocking", feature = "osv"))]
+pub(crate) fn post_json(
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<reqwest::blocking::Response, PkgError> {
+    let request = client()
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string());
+    let response = send(request)?.error_for_status()?;
+    Ok(response)
+}
+
+/// How much of a download was transferred before it was stopped.
+#[cfg(feature = "http-blocking")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferStats {
+    /// The bytes of the response body that were read.
+    pub bytes_transferred: u64,
+
+    /// The length of the whole body, if the server sent a `Content-Length`
+    /// and the body wasn't decoded from a `Content-Encoding`.
+    pub content_length: Option<u64>,
+
+    /// The URL the body came from, after following any redirects.
+    pub final_url: Option<String>,
+}
+
+/// A download being read, counting the bytes of the body transferred.
+///
+/// Parsers only read as far as they need to, so once the metadata of a
+/// package is parsed, [`Transfer::finish`] stops the download instead of
+/// reading the rest of the body.
+#[cfg(feature = "http-blocking")]
+#[derive(Debug)]
+pub(crate) struct Transfer<R> {
+    reader: R,
+    content_length: Option<u64>,
+    bytes_transferred: u64,
+    final_url: Option<String>,
+}
+
+#[cfg(feature = "http-blocking")]
+impl Transfer<Box<dyn Read>> {
+    /// Start counting the body of a response, decoded from any
+    /// `Content-Encoding` unless that's turned off; see
+    /// [`ClientConfig::with_content_decoding`].
+    pub(crate) fn from_response(response: reqwest::blocking::Response) -> Result<Self, PkgError> {
+        Self::from_response_decoding(response, CONTENT_DECODING.load(Ordering::Relaxed))
+    }
+
+    /// Start counting the body of a response, decoding it if `decode`.
+    fn from_response_decoding(
+        response: reqwest::blocking::Response,
+        decode: bool,
+    ) -> Result<Self, PkgError> {
+        let final_url = response.url().to_string();
+        let (body, content_length) = decoded_body(response, decode)?;
+        let mut transfer = Self::new(body, content_length);
+        transfer.final_url = Some(final_url);
+        Ok(transfer)
+    }
+}
+
+/// Get the body of a response, decoded from any gzip or zstd
+/// `Content-Encoding` if `decode`, with its length if that's known.
+#[cfg(feature = "http-blocking")]
+fn decoded_body(
+    response: reqwest::blocking::Response,
+    decode: bool,
+) -> Result<(Box<dyn Read>, Option<u64>), PkgError> {
+    let content_length = response.content_length();
+    let encoding = match response
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("gzip") | Some("x-gzip") if decode => Compression::Gzip,
+        Some("zstd") if decode => Compression::Zstd,
+        _ => return Ok((Box::new(response), content_length)),
+    };
+
+    // Check the body really is encoded, putting back what was read.
+    let mut reader = response.take(16);
+    let mut magic = Vec::new();
+    let _ = reader.read_to_end(&mut magic)?;
+    let encoded = Compression::from_magic(&magic) == encoding;
+    let body = io::Cursor::new(magic).chain(reader.into_inner());
+    if !encoded {
+        return Ok((Box::new(body), content_length));
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?encoding, "decoding content encoding");
+    Ok((encoding.decoder(body)?, None))
+}
+
+#[cfg(feature = "http-blocking")]
+impl<R> Transfer<R> {
+    /// Start counting the bytes read from `reader`.
+    pub(crate) fn new(reader: R, content_length: Option<u64>) -> Self {
+        Self {
+            reader,
+            content_length,
+            bytes_transferred: 0,
+            final_url: None,
+        }
+    }
+
+    /// The bytes of the body read so far.
+    pub(crate) fn bytes_transferred(&self) -> u64 {
+        self.bytes_transferred
+    }
+
+    /// Stop the download, returning how much of it was transferred.
+    ///
+    /// A response dropped before the end of its body closes its connection
+    /// rather than reading the remainder, so nothing more is downloaded.
+    pub(crate) fn finish(self) -> TransferStats {
+        let stats = TransferStats {
+            bytes_transferred: self.bytes_transferred,
+            content_length: self.content_length,
+            final_url: self.final_url,
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            bytes_transferred = stats.bytes_transferred,
+            content_length = ?stats.content_length,
+            final_url = ?stats.final_url,
+            "stopped download"
+        );
+        drop(self.reader);
+        stats
+    }
+}
+
+#[cfg(feature = "http-blocking")]
+impl<R: Read> Read for Transfer<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.bytes_transferred += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(all(test, feature = "http-blocking"))]
+mod tests {
+    use super::*;
+
+    /// A self-signed CA certificate.
+    const TEST_CA: &str = "\
+-----BEGIN CERTIFICATE-----
+MIIBizCCATGgAwIBAgIUHvKWs8qdhb6h64IUxdfkdjWyqdIwCgYIKoZIzj0EAwIw
+GjEYMBYGA1UEAwwPRXhhbXBsZSBUZXN0IENBMCAXDTI2MTAxNDE5MTUxOFoYDzIx
+MjYwOTIwMTkxNTE4WjAaMRgwFgYDVQQDDA9FeGFtcGxlIFRlc3QgQ0EwWTATBgcq
+hkjOPQIBBggqhkjOPQMBBwNCAATckJLeXP3l8YxJyRPTg8MAd/UJ3FAJhQoQFmiX
+vMSqVVQNzv+oQuRDJt8ld7XJNgIisNIDzgHdXHqsiWuGpKMGo1MwUTAdBgNVHQ4E
+FgQUFXbyA5z3tzRwPZu+knH138+wYHswHwYDVR0jBBgwFoAUFXbyA5z3tzRwPZu+
+knH138+wYHswDwYDVR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiEA98i5
+2q1/ph+aQmYJDXyPE3RhWSiGaGtinrOxLZ6nMswCIEE/mKSU4WnEw7J3vrPZauJK
+az0++sAB2y+n47Qxw1l2
+-----END CERTIFICATE-----";
+
+    #[test]
+    fn test_rate_limit() {
+        let limit = RateLimit::per_host(2.0).with_host("Mirror.example.com", 0.5);
+        assert_eq!(
+            limit.interval("deb.debian.org"),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            limit.interval("mirror.example.com"),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(RateLimit::unlimited().interval("deb.debian.org"), None);
+
+        // Requests are booked one interval apart, per host.
+        let mut limiter = Limiter {
+            limit,
+            ..Limiter::default()
+        };
+        let now = Instant::now();
+        assert_eq!(
+            limiter.reserve("a.example.com", now),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            limiter.reserve("a.example.com", now),
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            limiter.reserve("a.example.com", now),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            limiter.reserve("b.example.com", now),
+            Duration::from_secs(0)
+        );
+        let later = now + Duration::from_secs(10);
+        assert_eq!(
+            limiter.reserve("a.example.com", later),
+            Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn test_max_connections() {
+        let mut limiter = Limiter::default();
+        assert!((0..10).all(|_| limiter.try_open("a.example.com")));
+
+        // Connections are counted per host, up to the limit.
+        let mut limiter = Limiter {
+            max_connections: Some(2),
+            ..Limiter::default()
+        };
+        assert!(limiter.try_open("a.example.com"));
+        assert!(limiter.try_open("a.example.com"));
+        assert!(!limiter.try_open("a.example.com"));
+        assert!(limiter.try_open("b.example.com"));
+        limiter.close("a.example.com");
+        assert!(limiter.try_open("a.example.com"));
+        assert!(!limiter.try_open("a.example.com"));
+
+        assert_eq!(
+            ClientConfig::new().with_max_connections_per_host(Some(0)),
+            ClientConfig::new().with_max_connections_per_host(Some(1))
+        );
+    }
+
+    #[test]
+    fn test_client_config() {
+        let config = ClientConfig::new()
+            .with_http2_prior_knowledge(true)
+            .with_pool_idle_timeout(None)
+            .with_pool_max_idle_per_host(8)
+            .with_max_connections_per_host(Some(4))
+            .with_tcp_keepalive(Some(Duration::from_secs(30)))
+            .with_max_redirects(2)
+            .with_cross_host_redirects(false)
+            .with_address_family(AddressFamily::PreferV4)
+            .with_connect_timeout(Some(Duration::from_secs(5)))
+            .with_read_timeout(None);
+        assert_ne!(config, ClientConfig::default());
+        assert!(config.build().unwrap().fallback.is_some());
+        for address_family in [AddressFamily::V4Only, AddressFamily::V6Only] {
+            let config = ClientConfig::new().with_address_family(address_family);
+            assert!(config.build().unwrap().fallback.is_none());
+        }
+
+        // Every certificate in a bundle is trusted.
+        let bundle = format!("{}\n{}\n", TEST_CA, TEST_CA);
+        assert_eq!(pem_certificates(bundle.as_bytes()).unwrap().len(), 2);
+        let config = ClientConfig::new().with_root_certificates_pem(bundle);
+        assert!(config.build().is_ok());
+        let config = ClientConfig::new().with_root_certificates_pem("not a certificate");
+        assert!(config.build().is_err());
+        assert!(ClientConfig::new()
+            .with_root_certificates_file("/nonexistent/ca.pem")
+            .is_err());
+    }
+
+    #[cfg(all(feature = "testing", feature = "debian", feature = "checksum"))]
+    #[test]
+    fn test_content_encoding() {
+        use std::io::Write as _;
+
+        use sha2::{Digest as _, Sha256};
+
+        use crate::checksum::Algorithm;
+        use crate::testing::{DebBuilder, MockResponse, MockTransport};
+
+        let deb = DebBuilder::new("hello", "1.0", "all").build();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&deb).unwrap();
+        let gzipped = encoder.finish().unwrap();
+        let sha256 = format!("{:x}", Sha256::digest(&deb));
+
+        let encoded_url = "https://example.com/encoded.deb";
+        let _mock = MockTransport::new()
+            .with_response(
+                encoded_url,
+                MockResponse::new(gzipped.clone()).with_header("Content-Encoding", "gzip"),
+            )
+            // Marked as encoded, but sent as is.
+            .with_response(
+                "https://example.com/marked.deb",
+                MockResponse::new(deb).with_header("Content-Encoding", "gzip"),
+            )
+            .install();
+
+        // Packages are parsed and hashed as the files themselves.
+        for url in [encoded_url, "https://example.com/marked.deb"] {
+            let (package, digests) =
+                crate::from_url_with_digests(url, &[Algorithm::Sha256]).unwrap();
+            assert_eq!(package.package_name().unwrap(), "hello");
+            assert_eq!(digests.sha256(), Some(sha256.as_str()));
+        }
+
+        // Without decoding, the body is read as sent.
+        let response = get(encoded_url).unwrap();
+        let mut body = Vec::new();
+        let _ = Transfer::from_response_decoding(response, false)
+            .unwrap()
+            .read_to_end(&mut body)
+            .unwrap();
+        assert_eq!(body, gzipped);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_throttling() {
+        use crate::testing::{MockResponse, MockTransport};
+
+        let url = "https://example.com/throttled";
+        let mock = MockTransport::new()
+            .with_response(
+                url,
+                MockResponse::new("slow down")
+                    .with_status(429)
+                    .with_header("Retry-After", "0"),
+            )
+            .with_body(url, "hello")
+            .with_response(
+                "https://example.com/closed",
+                MockResponse::new("come back later")
+                    .with_status(503)
+                    .with_header("Retry-After", "3600"),
+            )
+            .install();
+
+        // Asked to wait no time, the request is sent again.
+        assert_eq!(get(url).unwrap().text().unwrap(), "hello");
+        assert_eq!(mock.requests(), vec![url, url]);
+
+        // Asked to wait longer than allowed, the request fails at once.
+        let err = get("https://example.com/closed").unwrap_err();
+        assert!(matches!(err, PkgError::Throttled(_)));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3600)));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_retry_after_date() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        let status = reqwest::StatusCode::SERVICE_UNAVAILABLE;
+        let headers = |value: &str| {
+            let mut headers = reqwest::header::HeaderMap::new();
+            let _ = headers.insert(reqwest::header::RETRY_AFTER, value.parse().unwrap());
+            headers
+        };
+        assert_eq!(
+            retry_after(status, &headers("Sun, 06 Nov 1994 08:49:47 GMT"), now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            retry_after(status, &headers("Sun, 06 Nov 1994 08:49:27 GMT"), now),
+            Some(Duration::from_secs(0))
+        );
+        assert_eq!(retry_after(status, &headers("soon"), now), None);
+    }
+
+    #[test]
+    fn test_with_timeout() {
+        let url = "https://example.com/hello.rpm";
+        let second = Duration::from_secs(1);
+        let request = with_timeout(client().get(url), Some(second))
+            .build()
+            .unwrap();
+        assert_eq!(request.timeout(), Some(&second));
+        let request = with_timeout(client().get(url), None).build().unwrap();
+        assert_eq!(request.timeout(), None);
+
+        #[cfg(feature = "http-async")]
+        {
+            let request = with_async_timeout(async_client().get(url), Some(second))
+                .build()
+                .unwrap();
+            assert_eq!(request.timeout(), Some(&second));
+        }
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_transfer() {
+        // Only the start of a package is read, however large it is.
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+        let body = rpm.as_slice().chain(io::repeat(0).take(u64::MAX));
+        let mut transfer = Transfer::new(body, Some(u64::MAX));
+        let package = crate::from_read(&mut transfer).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+
+        let stats = transfer.finish();
+        assert!(stats.bytes_transferred <= rpm.len() as u64 + 1024);
+        assert_eq!(stats.content_length, Some(u64::MAX));
+    }
+}
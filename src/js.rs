@@ -0,0 +1,78 @@
+//! JavaScript bindings for WebAssembly.
+//!
+//! Build the crate as a `cdylib` for wasm32 and generate the JavaScript glue
+//! with `wasm-bindgen`:
+//!
+//! ```text
+//! cargo rustc --lib --crate-type cdylib --release --target wasm32-unknown-unknown \
+//!     --no-default-features --features js,debian,rpm
+//! wasm-bindgen --target web --out-dir pkg \
+//!     target/wasm32-unknown-unknown/release/remote_package.wasm
+//! ```
+//!
+//! Packages are fetched with the browser's `fetch` API as described in
+//! [`fetch`](crate::fetch), and their metadata is returned as a plain object
+//! in the shape documented in [`metadata`](crate::metadata):
+//!
+//! ```text
+//! import init, { inspectPackage } from "./pkg/remote_package.js";
+//!
+//! await init();
+//! const metadata = await inspectPackage("https://example.com/hello_1.0_amd64.deb");
+//! console.log(metadata.name, metadata.version);
+//! ```
+//!
+//! Failures reject with an `Error` carrying the message of the
+//! [`PkgError`].
+use wasm_bindgen::prelude::*;
+
+use crate::metadata::PackageMetadata;
+use crate::PkgError;
+
+/// Fetch the package at `url` and resolve to its metadata, stopping the
+/// download once the metadata has been parsed.
+#[wasm_bindgen(js_name = inspectPackage)]
+pub async fn inspect_package(url: String) -> Result<JsValue, JsValue> {
+    let metadata = crate::fetch::from_url_async(&url)
+        .await
+        .and_then(|package| package.to_metadata())
+        .map_err(to_js_error)?;
+    to_js_value(&metadata)
+}
+
+/// Get the metadata of a package from its bytes, such as a file the user
+/// chose.
+#[wasm_bindgen(js_name = inspectBytes)]
+pub fn inspect_bytes(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    let metadata = metadata_from_bytes(bytes).map_err(to_js_error)?;
+    to_js_value(&metadata)
+}
+
+/// Parse the metadata of a package held in memory.
+fn metadata_from_bytes(bytes: &[u8]) -> Result<PackageMetadata, PkgError> {
+    crate::from_read(bytes)?.to_metadata()
+}
+
+/// Convert metadata to a plain JavaScript object, through its JSON form.
+fn to_js_value(metadata: &PackageMetadata) -> Result<JsValue, JsValue> {
+    js_sys::JSON::parse(&metadata.to_json().map_err(to_js_error)?)
+}
+
+/// Convert an error to a JavaScript `Error`.
+fn to_js_error(err: PkgError) -> JsValue {
+    js_sys::Error::new(&err.to_string()).into()
+}
+
+#[cfg(all(test, feature = "rpm"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_from_bytes() {
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+        let metadata = metadata_from_bytes(&rpm).unwrap();
+        assert_eq!(metadata.name, "hello");
+        assert_eq!(metadata.package_type, "rpm");
+        assert!(metadata_from_bytes(b"not a package").is_err());
+    }
+}
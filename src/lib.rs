@@ -10,6 +10,7 @@
 )]
 
 /// Types of remote package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum RemotePackageType {
     #[cfg(feature = "debian")]
     /// Debian package
@@ -20,6 +21,45 @@ pub enum RemotePackageType {
     Rpm,
 }
 
+impl RemotePackageType {
+    /// The canonical file extension of packages of this type, without the
+    /// dot, e.g. `deb`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "debian")]
+            RemotePackageType::Deb => "deb",
+            #[cfg(feature = "rpm")]
+            RemotePackageType::Rpm => "rpm",
+        }
+    }
+
+    /// The MIME type of packages of this type, for `Content-Type` headers.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            #[cfg(feature = "debian")]
+            RemotePackageType::Deb => "application/vnd.debian.binary-package",
+            #[cfg(feature = "rpm")]
+            RemotePackageType::Rpm => "application/x-rpm",
+        }
+    }
+
+    /// Get the package type of a file extension, with or without the dot,
+    /// ignoring case. Debian installer packages (`udeb`) are Debian packages.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension
+            .trim_start_matches('.')
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            #[cfg(feature = "debian")]
+            "deb" | "udeb" => Some(RemotePackageType::Deb),
+            #[cfg(feature = "rpm")]
+            "rpm" => Some(RemotePackageType::Rpm),
+            _ => None,
+        }
+    }
+}
+
 /// Error type for this crate.
 #[derive(thiserror::Error, Debug)]
 pub enum PkgError {
@@ -34,7 +74,7 @@ pub enum PkgError {
     RpmError(#[from] ::fez::RPMError),
 
     /// An error from the underlying HTTP client library
-    #[cfg(feature = "http")]
+    #[cfg(any(feature = "http-blocking", feature = "http-async"))]
     #[error("HTTP Error")]
     HTTPError(#[from] reqwest::Error),
 
@@ -50,6 +90,618 @@ pub enum PkgError {
     /// Package type can't be queried.
     #[error("Package type cannot be queried (inferred: {0})")]
     UnknownPackageType(String),
+
+    /// A server throttling requests asked to wait longer before retrying than
+    /// [`http::ClientConfig::with_max_retry_wait`] allows.
+    #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+    #[error("Throttled by the server, retry after {0:?}")]
+    Throttled(std::time::Duration),
+
+    /// An I/O error while reading data.
+    #[error("I/O Error")]
+    IoError(#[from] std::io::Error),
+
+    /// An error from the underlying XML library
+    #[cfg(feature = "quick-xml")]
+    #[error("XML Error")]
+    XmlError(#[from] quick_xml::Error),
+
+    /// An error from the underlying JSON library
+    #[cfg(feature = "serde_json")]
+    #[error("JSON Error")]
+    JsonError(#[from] serde_json::Error),
+
+    /// A version range could not be parsed.
+    #[error("Invalid version range: {0}")]
+    InvalidVersionRange(String),
+
+    /// A metadata file was not listed by the repository.
+    #[error("Repository metadata not found: {0}")]
+    RepoDataNotFound(String),
+
+    /// Repository metadata could not be understood.
+    #[error("Invalid repository metadata: {0}")]
+    InvalidRepoData(String),
+
+    /// A repository specification such as `ppa:owner/name` was malformed.
+    #[error("Invalid repository specification: {0}")]
+    InvalidRepoSpec(String),
+
+    /// A package file is malformed.
+    #[error("Invalid package: {0}")]
+    InvalidPackage(String),
+
+    /// A search pattern could not be parsed.
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+
+    /// An S3 location, or a request to presign it, was malformed.
+    #[cfg(feature = "s3")]
+    #[error("Invalid S3 location: {0}")]
+    InvalidS3Location(String),
+
+    /// A requested package is not in the repository.
+    #[error("Package not found: {0}")]
+    PackageNotFound(String),
+
+    /// No package in the repository satisfies a dependency.
+    #[error("Unsatisfied dependency of {package}: {dependency}")]
+    UnsatisfiedDependency {
+        /// The package with the dependency.
+        package: String,
+
+        /// The dependency, including any alternatives.
+        dependency: String,
+    },
+
+    /// Downloaded contents did not match the expected hash.
+    #[error("{algorithm} mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        /// The hash algorithm, e.g. `sha256`.
+        algorithm: String,
+
+        /// The expected value.
+        expected: String,
+
+        /// The value actually found.
+        actual: String,
+    },
+
+    /// Downloaded contents did not have the expected size.
+    #[error("size mismatch: expected {expected} bytes, got {actual}")]
+    SizeMismatch {
+        /// The expected size in bytes.
+        expected: u64,
+
+        /// The number of bytes actually found.
+        actual: u64,
+    },
+
+    /// An OpenPGP key or signature could not be parsed.
+    #[cfg(feature = "pgp")]
+    #[error("PGP Error")]
+    PgpError(#[from] pgp::errors::Error),
+
+    /// A signature could not be verified.
+    #[cfg(any(feature = "verify", feature = "cosign"))]
+    #[error("Signature verification failed: {0}")]
+    SignatureError(String),
+
+    /// A package URL couldn't be mapped to an OSV ecosystem.
+    #[cfg(feature = "osv")]
+    #[error("No OSV ecosystem for package: {0}")]
+    UnknownEcosystem(String),
+
+    /// More metadata was read than [`limits::Limits`] allow.
+    #[error("Metadata size limit of {0} bytes exceeded")]
+    SizeLimitExceeded(u64),
+
+    /// An archive or header had more entries than [`limits::Limits`] allow.
+    #[error("Entry limit of {0} exceeded")]
+    EntryLimitExceeded(u64),
+
+    /// Parsing took longer than [`limits::Limits`] allow.
+    #[error("Time limit of {0:?} exceeded")]
+    TimeLimitExceeded(std::time::Duration),
+
+    /// Decompressing metadata needed more memory than [`limits::Limits`]
+    /// allow.
+    #[error("Decompression memory limit of {0} bytes exceeded")]
+    MemoryLimitExceeded(u64),
+
+    /// A file was not found in the payload of a package.
+    #[cfg(any(feature = "debian", feature = "rpm"))]
+    #[error("File not found in package: {0}")]
+    FileNotFound(String),
+
+    /// A package entry would be written outside of the extraction
+    /// directory.
+    #[cfg(any(feature = "debian", feature = "rpm"))]
+    #[error("Unsafe path in package: {0}")]
+    UnsafePath(String),
+
+    /// An ELF file could not be parsed.
+    #[cfg(feature = "elf")]
+    #[error("Invalid ELF file: {0}")]
+    InvalidElf(String),
+
+    /// A query run on another thread failed, with an error that can't be
+    /// passed between threads. Only its message is kept.
+    #[cfg(feature = "batch")]
+    #[error("Query failed: {0}")]
+    QueryFailed(String),
+
+    /// An error fetching or parsing the package at a URL, with the context
+    /// it happened in.
+    #[error("Failed to {phase} {url}: {source}")]
+    Context {
+        /// The URL of the package.
+        url: String,
+
+        /// What was being done when the error happened.
+        phase: Phase,
+
+        /// How many bytes of the response body had been read, if known.
+        offset: Option<u64>,
+
+        /// The error itself.
+        #[source]
+        source: Box<PkgError>,
+    },
+}
+
+/// What was being done with a package when an error happened; see
+/// [`PkgError::phase`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Sending the request or receiving the response.
+    Fetch,
+
+    /// Detecting the type of package from its first bytes.
+    Infer,
+
+    /// Parsing the metadata of the package.
+    Parse,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::Fetch => "fetch",
+            Phase::Infer => "detect the type of",
+            Phase::Parse => "parse",
+        })
+    }
+}
+
+/// A broad classification of a [`PkgError`], for deciding what to do about
+/// it; see [`PkgError::kind`].
+///
+/// More kinds may be added, so matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The server couldn't be reached, or answered with an error.
+    Network,
+
+    /// The package, file or repository metadata asked for doesn't exist.
+    NotFound,
+
+    /// The server needs credentials, or refused the ones given.
+    AuthRequired,
+
+    /// A package or metadata file is malformed.
+    Parse,
+
+    /// The type of package isn't supported, or its feature is disabled.
+    Unsupported,
+
+    /// A [`limits::Limits`] bound was exceeded.
+    LimitExceeded,
+
+    /// Contents didn't match their expected size or hash.
+    ChecksumMismatch,
+
+    /// A signature couldn't be parsed or verified.
+    Signature,
+
+    /// An argument, such as a version range or pattern, was malformed.
+    InvalidInput,
+
+    /// An I/O error other than a network failure.
+    Io,
+
+    /// Any other error.
+    Other,
+}
+
+impl PkgError {
+    /// The broad class of the error, so that callers can branch on it
+    /// without matching every variant or the message.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            PkgError::Context { source, .. } => source.kind(),
+            #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+            PkgError::HTTPError(err) => http_error_kind(err),
+            #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+            PkgError::Throttled(_) => ErrorKind::Network,
+            PkgError::IoError(err) => {
+                #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+                if let Some(err) = err.get_ref().and_then(|err| err.downcast_ref()) {
+                    return http_error_kind(err);
+                }
+                match err.kind() {
+                    std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+                    std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
+                        ErrorKind::Parse
+                    }
+                    _ => ErrorKind::Io,
+                }
+            }
+            #[cfg(feature = "debian")]
+            PkgError::DebPkgError(_) | PkgError::DebianControlFieldNotFound(_) => ErrorKind::Parse,
+            #[cfg(feature = "rpm")]
+            PkgError::RpmError(_) => ErrorKind::Parse,
+            #[cfg(feature = "quick-xml")]
+            PkgError::XmlError(_) => ErrorKind::Parse,
+            #[cfg(feature = "serde_json")]
+            PkgError::JsonError(_) => ErrorKind::Parse,
+            #[cfg(feature = "elf")]
+            PkgError::InvalidElf(_) => ErrorKind::Parse,
+            #[cfg(any(feature = "debian", feature = "rpm"))]
+            PkgError::FileNotFound(_) => ErrorKind::NotFound,
+            #[cfg(any(feature = "debian", feature = "rpm"))]
+            PkgError::UnsafePath(_) => ErrorKind::Parse,
+            #[cfg(feature = "pgp")]
+            PkgError::PgpError(_) => ErrorKind::Signature,
+            #[cfg(any(feature = "verify", feature = "cosign"))]
+            PkgError::SignatureError(_) => ErrorKind::Signature,
+            #[cfg(feature = "osv")]
+            PkgError::UnknownEcosystem(_) => ErrorKind::Unsupported,
+            #[cfg(feature = "batch")]
+            PkgError::QueryFailed(_) => ErrorKind::Other,
+            PkgError::InvalidRepoData(_) | PkgError::InvalidPackage(_) => ErrorKind::Parse,
+            PkgError::InferError | PkgError::UnknownPackageType(_) => ErrorKind::Unsupported,
+            PkgError::InvalidVersionRange(_)
+            | PkgError::InvalidRepoSpec(_)
+            | PkgError::InvalidPattern(_) => ErrorKind::InvalidInput,
+            #[cfg(feature = "s3")]
+            PkgError::InvalidS3Location(_) => ErrorKind::InvalidInput,
+            PkgError::RepoDataNotFound(_)
+            | PkgError::PackageNotFound(_)
+            | PkgError::UnsatisfiedDependency { .. } => ErrorKind::NotFound,
+            PkgError::ChecksumMismatch { .. } | PkgError::SizeMismatch { .. } => {
+                ErrorKind::ChecksumMismatch
+            }
+            PkgError::SizeLimitExceeded(_)
+            | PkgError::EntryLimitExceeded(_)
+            | PkgError::TimeLimitExceeded(_)
+            | PkgError::MemoryLimitExceeded(_) => ErrorKind::LimitExceeded,
+        }
+    }
+
+    /// The URL of the package the error happened for, if known.
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            PkgError::Context { url, .. } => Some(url),
+            _ => None,
+        }
+    }
+
+    /// What was being done when the error happened, if known.
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            PkgError::Context { phase, .. } => Some(*phase),
+            _ => None,
+        }
+    }
+
+    /// How many bytes of the response body had been read when the error
+    /// happened, if known.
+    pub fn offset(&self) -> Option<u64> {
+        match self {
+            PkgError::Context { offset, .. } => *offset,
+            _ => None,
+        }
+    }
+
+    /// Whether trying again might succeed: the error is a timeout, a
+    /// connection failure, or an HTTP status of 408, 429 or 5xx.
+    ///
+    /// Errors in the package itself, such as a malformed header, are never
+    /// retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PkgError::Context { source, .. } => source.is_retryable(),
+            #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+            PkgError::HTTPError(err) => is_retryable_http(err),
+            #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+            PkgError::Throttled(_) => true,
+            PkgError::IoError(err) => {
+                #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+                if let Some(err) = err.get_ref().and_then(|err| err.downcast_ref()) {
+                    return is_retryable_http(err);
+                }
+                matches!(
+                    err.kind(),
+                    std::io::ErrorKind::TimedOut
+                        | std::io::ErrorKind::Interrupted
+                        | std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                        | std::io::ErrorKind::BrokenPipe
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// How long a throttling server asked to wait before retrying, if it
+    /// did.
+    #[cfg(feature = "http-blocking")]
+    pub fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            PkgError::Context { source, .. } => source.retry_after(),
+            PkgError::Throttled(retry_after) => Some(*retry_after),
+            _ => None,
+        }
+    }
+
+    /// Attach the URL of the package, and how much of it had been read, to
+    /// an error fetching or parsing it. The phase is worked out from the
+    /// error.
+    #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+    pub(crate) fn in_context(self, url: &str, offset: Option<u64>) -> Self {
+        let phase = match &self {
+            PkgError::Context { .. } => return self,
+            PkgError::InferError | PkgError::UnknownPackageType(_) => Phase::Infer,
+            #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+            PkgError::HTTPError(_) => Phase::Fetch,
+            #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+            PkgError::Throttled(_) => Phase::Fetch,
+            #[cfg(any(feature = "http-blocking", feature = "http-async"))]
+            PkgError::IoError(err)
+                if err.get_ref().is_some_and(|err| err.is::<reqwest::Error>()) =>
+            {
+                Phase::Fetch
+            }
+            _ => Phase::Parse,
+        };
+        PkgError::Context {
+            url: url.to_owned(),
+            phase,
+            offset,
+            source: Box::new(self),
+        }
+    }
+}
+
+/// The kind of a request that failed with `err`.
+#[cfg(any(feature = "http-blocking", feature = "http-async"))]
+fn http_error_kind(err: &reqwest::Error) -> ErrorKind {
+    match err.status() {
+        Some(reqwest::StatusCode::NOT_FOUND) | Some(reqwest::StatusCode::GONE) => {
+            ErrorKind::NotFound
+        }
+        Some(reqwest::StatusCode::UNAUTHORIZED)
+        | Some(reqwest::StatusCode::FORBIDDEN)
+        | Some(reqwest::StatusCode::PROXY_AUTHENTICATION_REQUIRED) => ErrorKind::AuthRequired,
+        _ if err.is_decode() => ErrorKind::Parse,
+        _ if err.is_builder() => ErrorKind::InvalidInput,
+        _ => ErrorKind::Network,
+    }
+}
+
+/// Whether a request that failed with `err` might succeed if sent again.
+#[cfg(any(feature = "http-blocking", feature = "http-async"))]
+fn is_retryable_http(err: &reqwest::Error) -> bool {
+    match err.status() {
+        Some(status) => {
+            status == reqwest::StatusCode::REQUEST_TIMEOUT
+                || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || status.is_server_error()
+        }
+        None => err.is_timeout() || err.is_request() || err.is_body(),
+    }
+}
+
+/// A script run when a package is installed or removed, e.g. a Debian
+/// `postinst` or an RPM `%post` scriptlet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageScript {
+    /// The name of the script in the package format's terms, e.g.
+    /// `postinst` or `%post`.
+    pub name: String,
+
+    /// The interpreter the script is run with, e.g. `/bin/sh -e`, if known.
+    pub interpreter: Option<String>,
+
+    /// The script itself. This can be empty for RPM scriptlets that just
+    /// run a program, such as `%post -p /sbin/ldconfig`.
+    pub contents: String,
+}
+
+/// The kind of a [`PackageRelation`], from hard requirements to the weak
+/// dependencies that package managers may or may not install.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RelationKind {
+    /// A hard requirement: Debian `Pre-Depends` and `Depends`, or RPM
+    /// `Requires`.
+    Depends,
+
+    /// A weak dependency installed by default.
+    Recommends,
+
+    /// A weak dependency not installed by default.
+    Suggests,
+
+    /// A reverse weak dependency: the package is installed by default
+    /// along with the named one. RPM only.
+    Supplements,
+
+    /// A reverse dependency that isn't installed by default: the package
+    /// adds to the named one.
+    Enhances,
+}
+
+impl RelationKind {
+    /// Whether the relation is a weak dependency rather than a hard
+    /// requirement.
+    pub fn is_weak(self) -> bool {
+        self != RelationKind::Depends
+    }
+}
+
+/// A relation to another package, as written in the package metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageRelation {
+    /// The kind of relation.
+    pub kind: RelationKind,
+
+    /// The relation with any version constraint, e.g.
+    /// `libc6 (>= 2.34) | libc6-udeb` or `glibc >= 2.34`. For RPM this may
+    /// be a rich dependency such as `(foo if bar)`.
+    pub relation: String,
+}
+
+/// The distribution a package was built for, as far as its metadata says.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Distribution {
+    /// The name of the distribution, e.g. `Fedora Project`. Only RPM
+    /// packages record this, in the `DISTRIBUTION` header.
+    pub name: Option<String>,
+
+    /// The part of the version naming the distribution release: an RPM dist
+    /// tag such as `.el9` or `.fc40`, or the suffix of a Debian revision
+    /// such as `ubuntu22.04` or `deb12u1`.
+    pub tag: Option<String>,
+}
+
+/// The identity of a package: its type, name, epoch, version, release and
+/// architecture. Packages with the same identity are the same build, so it
+/// can be used to deduplicate packages in a `HashSet`.
+///
+/// The ordering compares the fields in turn, and versions as text, so it is
+/// deterministic but is not version ordering; see [`version`] for that.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PackageId {
+    /// The package type.
+    pub package_type: RemotePackageType,
+
+    /// The package name.
+    pub name: String,
+
+    /// The package epoch, if it has one.
+    pub epoch: Option<u32>,
+
+    /// The version, without the epoch or release.
+    pub version: String,
+
+    /// The release, or Debian revision.
+    pub release: Option<String>,
+
+    /// The package architecture.
+    pub arch: String,
+}
+
+impl PackageId {
+    /// Get the identity of a package.
+    #[cfg_attr(
+        not(any(feature = "debian", feature = "rpm")),
+        allow(unreachable_code, unused_assignments)
+    )]
+    pub fn from_package<P: RemotePackage + ?Sized>(package: &P) -> Result<Self, PkgError> {
+        let epoch = package.package_epoch();
+        let release = package.package_iteration();
+
+        // Debian versions include the epoch and revision.
+        let mut version = package.package_version()?;
+        if let Some((prefix, rest)) = version.split_once(':') {
+            if epoch.map(|epoch| epoch.to_string()).as_deref() == Some(prefix) {
+                version = rest;
+            }
+        }
+        if let Some(release) = release {
+            version = version
+                .strip_suffix(release)
+                .and_then(|version| version.strip_suffix('-'))
+                .unwrap_or(version);
+        }
+
+        Ok(PackageId {
+            package_type: package.package_type(),
+            name: package.package_name()?.to_owned(),
+            epoch,
+            version: version.to_owned(),
+            release: release.map(str::to_owned),
+            arch: package.package_arch()?.to_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for PackageId {
+    /// Formats the identity as `name-[epoch:]version[-release].arch`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-", self.name)?;
+        if let Some(epoch) = self.epoch {
+            write!(f, "{}:", epoch)?;
+        }
+        write!(f, "{}", self.version)?;
+        if let Some(release) = &self.release {
+            write!(f, "-{}", release)?;
+        }
+        write!(f, ".{}", self.arch)
+    }
+}
+
+/// Get the languages to try for a locale, most specific first, as gettext
+/// does: `de_AT.UTF-8@euro` gives `de_AT@euro`, `de_AT`, `de@euro` and `de`.
+/// The `C` and `POSIX` locales give none, meaning untranslated text.
+pub fn language_fallbacks(lang: &str) -> Vec<String> {
+    let lang = lang.trim().replace('-', "_");
+    let (lang, modifier) = match lang.split_once('@') {
+        Some((lang, modifier)) => (lang, Some(modifier)),
+        None => (lang.as_str(), None),
+    };
+    let lang = lang.split_once('.').map_or(lang, |(lang, _codeset)| lang);
+    if lang.is_empty() || lang == "C" || lang == "POSIX" {
+        return Vec::new();
+    }
+
+    let mut languages: Vec<&str> = vec![lang];
+    if let Some((language, _territory)) = lang.split_once('_') {
+        languages.push(language);
+    }
+    let mut fallbacks = Vec::new();
+    for language in languages {
+        if let Some(modifier) = modifier {
+            fallbacks.push(format!("{}@{}", language, modifier));
+        }
+        fallbacks.push(language.to_owned());
+    }
+    fallbacks
+}
+
+/// A trigger a package declares, which runs a script when something else on
+/// the system changes, e.g. a dpkg `interest` trigger or an RPM
+/// `%triggerin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackageTrigger {
+    /// The type of trigger in the package format's terms, e.g.
+    /// `interest-noawait` or `%triggerin`.
+    pub kind: String,
+
+    /// What the trigger is on: a dpkg trigger name or path, or for RPM a
+    /// package name with any version condition, e.g. `glibc >= 2.17`.
+    pub condition: String,
+
+    /// The script run when the trigger fires. Debian packages don't have
+    /// one, as dpkg runs `postinst triggered` instead.
+    pub script: Option<PackageScript>,
 }
 
 /// Trait representing a remote package.
@@ -69,10 +721,144 @@ pub trait RemotePackage {
     /// different places.
     fn package_iteration(&self) -> Option<&str>;
 
+    /// Get the package epoch, if it has one.
+    fn package_epoch(&self) -> Option<u32> {
+        None
+    }
+
     /// Get the package architecture.
     fn package_arch(&self) -> Result<&str, PkgError>;
+
+    /// Get the declared license of the package, if the package format
+    /// records one.
+    fn package_license(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get the description of the package, if the package format records
+    /// one.
+    fn package_description(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get the description of the package in the language `lang`, e.g.
+    /// `de_DE.UTF-8`, trying each of [`language_fallbacks`] in turn and then
+    /// the untranslated description. Formats without translations give the
+    /// [`package_description`](Self::package_description).
+    fn description_localized(&self, _lang: &str) -> Option<&str> {
+        self.package_description()
+    }
+
+    /// Get the vendor or origin of the package, if it records one, so that
+    /// packages can be required to come from approved vendors.
+    fn package_vendor(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get the paths of the files installed by the package, if they are known
+    /// from its metadata.
+    fn package_files(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Get the paths of the files the package treats as configuration, which
+    /// the package manager keeps local changes to on upgrade.
+    fn package_config_files(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Get the names of the packages, or for RPM the capabilities, that the
+    /// package declares it depends on, without their version constraints.
+    fn package_dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Get the relations the package declares, hard and weak, with their
+    /// version constraints.
+    fn package_relations(&self) -> Vec<PackageRelation> {
+        Vec::new()
+    }
+
+    /// Get the scripts run when the package is installed or removed, so that
+    /// they can be reviewed before the package is allowed.
+    fn package_scripts(&self) -> Vec<PackageScript> {
+        Vec::new()
+    }
+
+    /// Get the triggers the package declares or activates.
+    fn package_triggers(&self) -> Vec<PackageTrigger> {
+        Vec::new()
+    }
+
+    /// Get the distribution the package was built for, so that it can be
+    /// placed in the matching repository.
+    fn package_distribution(&self) -> Distribution {
+        Distribution::default()
+    }
+
+    /// Take a snapshot of the package metadata; see
+    /// [`metadata::PackageMetadata`].
+    #[cfg(feature = "metadata")]
+    fn to_metadata(&self) -> Result<metadata::PackageMetadata, PkgError> {
+        metadata::PackageMetadata::from_package(self)
+    }
+
+    /// Serialize the package metadata as JSON, in the shape documented in
+    /// [`metadata`].
+    #[cfg(feature = "metadata")]
+    fn to_json(&self) -> Result<String, PkgError> {
+        self.to_metadata()?.to_json()
+    }
+
+    /// Render the package metadata as a Debian control paragraph; see
+    /// [`metadata::PackageMetadata::to_control_text`].
+    #[cfg(feature = "metadata")]
+    fn to_control_text(&self) -> Result<String, PkgError> {
+        Ok(self.to_metadata()?.to_control_text())
+    }
+
+    /// Render the package metadata as an RPM spec preamble; see
+    /// [`metadata::PackageMetadata::to_spec_preamble`].
+    #[cfg(feature = "metadata")]
+    fn to_spec_preamble(&self) -> Result<String, PkgError> {
+        Ok(self.to_metadata()?.to_spec_preamble())
+    }
+
+    /// Get the arguments to rebuild the package with fpm; see
+    /// [`fpm::FpmArgs`].
+    #[cfg(feature = "fpm")]
+    fn to_fpm_args(&self) -> Result<fpm::FpmArgs, PkgError> {
+        fpm::FpmArgs::from_package(self)
+    }
+
+    /// Describe the package as an SPDX document; see [`sbom::spdx_document`].
+    #[cfg(feature = "sbom")]
+    fn to_spdx(&self) -> Result<sbom::SpdxDocument, PkgError> {
+        sbom::spdx_document(self, None)
+    }
+
+    /// Make a best-effort CPE 2.3 name for the package; see
+    /// [`sbom::Cpe::from_package`].
+    #[cfg(feature = "sbom")]
+    fn to_cpe(&self) -> Result<sbom::Cpe, PkgError> {
+        sbom::Cpe::from_package(self)
+    }
+
+    /// Describe the package as a CycloneDX BOM; see [`sbom::cyclonedx_bom`].
+    #[cfg(feature = "sbom")]
+    fn to_cyclonedx(&self) -> Result<sbom::CycloneDxBom, PkgError> {
+        sbom::cyclonedx_bom(self, None)
+    }
 }
 
+// Include architecture compatibility helpers
+#[cfg(feature = "arch")]
+pub mod arch;
+
+// Include package name normalization
+#[cfg(feature = "naming")]
+pub mod naming;
+
 // Include Debian package support
 #[cfg(feature = "debian")]
 pub mod debian;
@@ -81,48 +867,471 @@ pub mod debian;
 #[cfg(feature = "rpm")]
 pub mod rpm;
 
+// Include parsing of RPM rich dependencies
+#[cfg(feature = "rich-deps")]
+pub mod rich_dep;
+
+// Include Yum/DNF repository support
+#[cfg(feature = "repo-rpm")]
+pub mod repo_rpm;
+
+// Include apt repository support
+#[cfg(feature = "repo-deb")]
+pub mod repo_deb;
+
+// Include Alpine repository support
+#[cfg(feature = "repo-apk")]
+pub mod repo_apk;
+
+// Include Arch Linux repository support
+#[cfg(feature = "repo-arch")]
+pub mod repo_arch;
+
+// Include PyPI lookup support
+#[cfg(feature = "pypi")]
+pub mod pypi;
+
+// Include Cargo registry index support
+#[cfg(feature = "crates-index")]
+pub mod crates_index;
+
+// Include npm registry support
+#[cfg(feature = "npm-registry")]
+pub mod npm_registry;
+
+// Include NuGet feed support
+#[cfg(feature = "nuget")]
+pub mod nuget;
+
+// Include Debian and Ubuntu changelog service support
+#[cfg(feature = "changelog")]
+pub mod changelog;
+
+// Include snapshot.debian.org support
+#[cfg(feature = "debian-snapshot")]
+pub mod debian_snapshot;
+
+// Include Fedora COPR support
+#[cfg(feature = "copr")]
+pub mod copr;
+
+// Include Amazon S3 support
+#[cfg(feature = "s3")]
+pub mod s3;
+
+// Include Metalink support
+#[cfg(feature = "metalink")]
+pub mod metalink;
+
+// Include checksum verification support
+#[cfg(feature = "checksum")]
+pub mod checksum;
+#[cfg(feature = "checksum")]
+pub use crate::checksum::{Algorithm, Digest, Digests};
+
+// Include support for reading package payloads
+#[cfg(any(feature = "debian", feature = "rpm"))]
+pub mod payload;
+
+// Include detection of services installed by packages
+#[cfg(any(feature = "debian", feature = "rpm"))]
+pub mod services;
+
+// Include extraction of desktop entries and AppStream metainfo
+#[cfg(all(feature = "appstream", any(feature = "debian", feature = "rpm")))]
+pub mod apps;
+
+// Include ELF dependency analysis
+#[cfg(feature = "elf")]
+pub mod elf;
+
+// Include metadata export support
+#[cfg(feature = "metadata")]
+pub mod metadata;
+
+// Include fpm argument generation
+#[cfg(feature = "fpm")]
+pub mod fpm;
+
+// Include comparison of two packages
+#[cfg(feature = "diff")]
+pub mod diff;
+
+// Include tabular reports on packages
+#[cfg(feature = "report")]
+pub mod report;
+
+// Include querying of many packages at once
+#[cfg(any(feature = "batch", feature = "async"))]
+pub mod batch;
+
+// Include the on-disk metadata cache
+#[cfg(feature = "cache")]
+pub mod cache;
+
+// Include fetching of packages with an async HTTP client
+#[cfg(feature = "http-async")]
+pub mod fetch;
+
+// Include JavaScript bindings for WebAssembly
+#[cfg(feature = "js")]
+pub mod js;
+
+// Include SBOM generation support
+#[cfg(feature = "sbom")]
+pub mod sbom;
+
+// Include OSV vulnerability lookup support
+#[cfg(feature = "osv")]
+pub mod osv;
+
+// Include the deb822 control file parser
+#[cfg(feature = "deb822")]
+pub mod deb822;
+
+// Include Debian .buildinfo support
+#[cfg(feature = "buildinfo")]
+pub mod buildinfo;
+
+// Include signature verification support
+#[cfg(feature = "verify")]
+pub mod verify;
+
+// Include fetching of packages from OCI registries
+#[cfg(feature = "oci")]
+pub mod oci;
+
+// Include cosign signature verification for OCI registries
+#[cfg(feature = "cosign")]
+pub mod cosign;
+
+// Include test helpers for mocking requests and building packages
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub mod version;
+
+pub mod limits;
+
+#[cfg(any(
+    feature = "debian",
+    feature = "rpm",
+    feature = "repo-rpm",
+    feature = "repo-deb",
+    feature = "repo-arch",
+    feature = "http-blocking"
+))]
+mod compression;
+
+#[cfg(feature = "rpm")]
+mod rpm_header;
+
+#[cfg(any(feature = "http-blocking", feature = "http-async"))]
+pub mod http;
+
+#[cfg(any(
+    feature = "repo-rpm",
+    feature = "metalink",
+    all(feature = "appstream", any(feature = "debian", feature = "rpm"))
+))]
+mod xml;
+
+#[cfg(feature = "tracing")]
+mod trace;
+
+#[cfg(any(
+    feature = "repo-rpm",
+    feature = "repo-deb",
+    feature = "repo-apk",
+    feature = "repo-arch",
+    feature = "crates-index"
+))]
+pub mod repo;
+
 /// Create a RemotePackage from a URL.
 ///
+/// If the URL points at a metalink rather than a package, the package is
+/// downloaded from the best mirror that serves it with the expected hash.
+/// Otherwise the download is stopped as soon as the metadata is parsed.
+///
 /// Uses a blocking tokio client to download the remote package - if
 /// using this in an async environment, surround this with tokio::spawn_blocking.
-#[cfg(feature = "http")]
+#[cfg(feature = "http-blocking")]
 pub fn from_url(url: &str) -> Result<Box<dyn RemotePackage>, PkgError> {
-    use std::io::Read;
+    from_url_with_stats(url).map(|(package, _stats)| package)
+}
 
-    let client = reqwest::blocking::Client::new();
+/// Create a RemotePackage from a URL like [`from_url`], also returning how
+/// many bytes were transferred before the download was stopped, and the URL
+/// reached after any redirects.
+///
+/// For a metalink, the bytes of both the metalink and the package it names
+/// are counted, and the final URL is that of the metalink.
+///
+/// Uses a blocking tokio client to download the remote package - if
+/// using this in an async environment, surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn from_url_with_stats(
+    url: &str,
+) -> Result<(Box<dyn RemotePackage>, http::TransferStats), PkgError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("from_url", url).entered();
 
-    // Send an HTTP request for the package and get the Response.
-    let response = client.get(url).send()?;
+    let result = fetch_with_stats(url, None);
 
-    // Read the first 1024 bytes for infer.
-    let mut reader = response.take(1024);
-    let mut infer_buf = vec![];
-    let _ = reader
-        .read_to_end(&mut infer_buf)
-        .map_err(|_| PkgError::InferError)?;
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        tracing::warn!(error = %err, "failed to fetch package");
+    }
+    result
+}
+
+/// Create a RemotePackage from a URL like [`from_url`], waiting up to
+/// `timeout` for the response and for each read of its body in place of
+/// [`ClientConfig::with_read_timeout`](http::ClientConfig::with_read_timeout),
+/// e.g. to allow longer for a slow mirror. The connect timeout is that of
+/// the client.
+///
+/// Uses a blocking tokio client to download the remote package - if
+/// using this in an async environment, surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn from_url_with_timeout(
+    url: &str,
+    timeout: std::time::Duration,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("from_url", url).entered();
+
+    let result = fetch_with_stats(url, Some(timeout)).map(|(package, _stats)| package);
+
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        tracing::warn!(error = %err, "failed to fetch package");
+    }
+    result
+}
+
+/// Fetch and parse a package for [`from_url_with_stats`], with the read
+/// timeout of the client unless `timeout` is given.
+#[cfg(feature = "http-blocking")]
+fn fetch_with_stats(
+    url: &str,
+    timeout: Option<std::time::Duration>,
+) -> Result<(Box<dyn RemotePackage>, http::TransferStats), PkgError> {
+    // Send an HTTP request for the package and start reading its body.
+    let mut transfer =
+        http::get_package_with_timeout(url, timeout).map_err(|err| err.in_context(url, None))?;
+
+    // Metalinks are small, so they can be parsed entirely before fetching the
+    // package they describe. The package is then parsed as it streams in from
+    // a mirror, and checked against the metalink once it has all been read.
+    #[cfg(feature = "metalink")]
+    {
+        let (header, rsp) = peek(&mut transfer).map_err(|err| err.in_context(url, None))?;
+        if metalink::is_metalink(&header) {
+            let (package, size) = metalink::Metalink::parse(std::io::BufReader::new(rsp))
+                .and_then(|doc| {
+                    let file = doc.files.first().ok_or_else(|| {
+                        PkgError::InvalidRepoData("metalink lists no files".to_string())
+                    })?;
+                    file.download_with_timeout(timeout, |reader| from_read(reader))
+                })
+                .map_err(|err| err.in_context(url, None))?;
+            let mut stats = transfer.finish();
+            stats.bytes_transferred += size;
+            stats.content_length = None;
+            return Ok((package, stats));
+        }
+        let package = from_read(rsp)
+            .map_err(|err| err.in_context(url, Some(transfer.bytes_transferred())))?;
+        Ok((package, transfer.finish()))
+    }
+
+    #[cfg(not(feature = "metalink"))]
+    {
+        let package = from_read(&mut transfer)
+            .map_err(|err| err.in_context(url, Some(transfer.bytes_transferred())))?;
+        Ok((package, transfer.finish()))
+    }
+}
+
+/// Create a RemotePackage of a known type from a URL, skipping the
+/// detection of the package type from its first bytes.
+///
+/// The download is stopped as soon as the metadata is parsed. Metalinks
+/// aren't followed.
+///
+/// Uses a blocking tokio client to download the remote package - if
+/// using this in an async environment, surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn from_url_with_type(
+    url: &str,
+    package_type: RemotePackageType,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("from_url", url).entered();
+
+    let mut transfer = http::get_package(url).map_err(|err| err.in_context(url, None))?;
+    let result = from_read_with_type(&mut transfer, package_type)
+        .map_err(|err| err.in_context(url, Some(transfer.bytes_transferred())));
+
+    #[cfg(feature = "tracing")]
+    if let Err(err) = &result {
+        tracing::warn!(error = %err, "failed to fetch package");
+    }
+    result
+}
+
+/// Create a RemotePackage from a URL, checking the whole download against an
+/// expected checksum such as one recorded in a lockfile.
+///
+/// The package is read to the end so that it can be hashed, and fails with
+/// [`PkgError::ChecksumMismatch`] if the checksum differs.
+///
+/// Uses a blocking tokio client to download the remote package - if
+/// using this in an async environment, surround this with tokio::spawn_blocking.
+#[cfg(all(feature = "http-blocking", feature = "checksum"))]
+pub fn from_url_verified(url: &str, digest: Digest) -> Result<Box<dyn RemotePackage>, PkgError> {
+    http::get_package(url)
+        .and_then(|body| from_read_verified(body, digest))
+        .map_err(|err| err.in_context(url, None))
+}
+
+/// Create a RemotePackage from something that impls Read, checking the whole
+/// stream against an expected checksum; see [`from_url_verified`].
+#[cfg(feature = "checksum")]
+pub fn from_read_verified<R: std::io::Read>(
+    reader: R,
+    digest: Digest,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    let mut reader = digest.reader(reader);
+    let package = from_read(&mut reader)?;
+    reader.finish()?;
+    Ok(package)
+}
+
+/// Create a RemotePackage from a URL, also computing checksums of the whole
+/// download with each of `algorithms` in the same pass.
+///
+/// Uses a blocking tokio client to download the remote package - if
+/// using this in an async environment, surround this with tokio::spawn_blocking.
+#[cfg(all(feature = "http-blocking", feature = "checksum"))]
+pub fn from_url_with_digests(
+    url: &str,
+    algorithms: &[Algorithm],
+) -> Result<(Box<dyn RemotePackage>, Digests), PkgError> {
+    http::get_package(url)
+        .and_then(|body| from_read_with_digests(body, algorithms))
+        .map_err(|err| err.in_context(url, None))
+}
+
+/// Create a RemotePackage from something that impls Read, also computing
+/// checksums of the whole stream; see [`from_url_with_digests`].
+#[cfg(feature = "checksum")]
+pub fn from_read_with_digests<R: std::io::Read>(
+    reader: R,
+    algorithms: &[Algorithm],
+) -> Result<(Box<dyn RemotePackage>, Digests), PkgError> {
+    let mut reader = checksum::DigestsReader::new(reader, algorithms);
+    let package = from_read(&mut reader)?;
+    let digests = reader.finish()?;
+    Ok((package, digests))
+}
+
+/// Create a RemotePackage from something that impls Read, detecting the
+/// package type from its contents.
+///
+/// The default [`limits::Limits`] are applied while parsing.
+pub fn from_read<R: std::io::Read>(reader: R) -> Result<Box<dyn RemotePackage>, PkgError> {
+    from_read_with_limits(reader, &limits::Limits::default())
+}
+
+/// Create a RemotePackage of a known type from something that impls Read,
+/// skipping the detection of the package type.
+///
+/// The default [`limits::Limits`] are applied while parsing.
+pub fn from_read_with_type<R: std::io::Read>(
+    reader: R,
+    package_type: RemotePackageType,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    // Silence unused warnings when the package features are disabled.
+    #[cfg(not(any(feature = "debian", feature = "rpm")))]
+    let _ = reader;
+
+    match package_type {
+        #[cfg(feature = "debian")]
+        RemotePackageType::Deb => Ok(Box::new(debian::DebianRemotePackage::new_from_read(
+            reader,
+        )?)),
+        #[cfg(feature = "rpm")]
+        RemotePackageType::Rpm => Ok(Box::new(rpm::RpmRemotePackage::new_from_read(reader)?)),
+    }
+}
+
+/// Create a RemotePackage from something that impls Read, applying the given
+/// resource limits while parsing it.
+pub fn from_read_with_limits<R: std::io::Read>(
+    reader: R,
+    limits: &limits::Limits,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("parse").entered();
+    #[cfg(feature = "tracing")]
+    let stopwatch = trace::Stopwatch::start();
+
+    let result = parse_with_limits(reader, limits);
+
+    #[cfg(feature = "tracing")]
+    match &result {
+        Ok(_) => tracing::debug!(elapsed = ?stopwatch.elapsed(), "parsed package"),
+        Err(err) => {
+            tracing::debug!(elapsed = ?stopwatch.elapsed(), error = %err, "failed to parse package")
+        }
+    }
+    result
+}
+
+/// Detect the type of a package and parse it, for [`from_read_with_limits`].
+fn parse_with_limits<R: std::io::Read>(
+    reader: R,
+    limits: &limits::Limits,
+) -> Result<Box<dyn RemotePackage>, PkgError> {
+    let (infer_buf, rsp) = peek(reader)?;
 
     // Infer uses magic to detect file types from starting bytes.
     let ext = infer::get(&infer_buf).map(|t| t.extension());
     let is_deb = infer::archive::is_deb(&infer_buf);
     let is_rpm = infer::archive::is_rpm(&infer_buf);
 
-    // Using a cursor and chain allows us to reconstruct the original response.
-    let rsp = std::io::Cursor::new(infer_buf).chain(reader.into_inner());
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        package_type = if is_deb {
+            "deb"
+        } else if is_rpm {
+            "rpm"
+        } else {
+            ext.unwrap_or("unknown")
+        },
+        "inferred package type"
+    );
 
     // If the feature is enabled and the package is Debian, make a Debian remote package.
     #[cfg(feature = "debian")]
     if is_deb {
-        let pkg = debian::DebianRemotePackage::new_from_read(rsp)?;
+        let pkg = debian::DebianRemotePackage::new_from_read_with_limits(rsp, limits)?;
         return Ok(Box::new(pkg));
     }
 
     // If the feature is enabled and the package is RPM, make an RPM remote package.
     #[cfg(feature = "rpm")]
     if is_rpm {
-        let pkg = rpm::RpmRemotePackage::new_from_read(rsp)?;
+        let pkg = rpm::RpmRemotePackage::new_from_read_with_limits(rsp, limits)?;
         return Ok(Box::new(pkg));
     }
 
+    // Silence unused warnings when the package features are disabled.
+    let _ = (is_deb, is_rpm, rsp, limits);
+
     // The package type was unknown or the necessary feature was disabled.
     // Return an error in either case.
     Err(PkgError::UnknownPackageType(
@@ -130,12 +1339,35 @@ pub fn from_url(url: &str) -> Result<Box<dyn RemotePackage>, PkgError> {
     ))
 }
 
+/// Read the first 1024 bytes of a stream, returning them along with a reader
+/// that yields the whole stream.
+fn peek<R: std::io::Read>(reader: R) -> Result<(Vec<u8>, impl std::io::Read), PkgError> {
+    use std::io::Read;
+
+    // Read the first 1024 bytes for infer.
+    let mut reader = reader.take(1024);
+    let mut infer_buf = vec![];
+    let _ = reader.read_to_end(&mut infer_buf)?;
+
+    // Using a cursor and chain allows us to reconstruct the original response.
+    let rsp = std::io::Cursor::new(infer_buf.clone()).chain(reader.into_inner());
+    Ok((infer_buf, rsp))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[cfg(feature = "http")]
-    fn test_from_url(url: &str, package_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// Check that `package`, served from `url`, is parsed as `package_name`.
+    #[cfg(all(feature = "testing", any(feature = "debian", feature = "rpm")))]
+    fn test_from_url(
+        url: &str,
+        package: Vec<u8>,
+        package_name: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let _mock = testing::MockTransport::new()
+            .with_body(url, package)
+            .install();
         let package = from_url(url).expect("Failed to download package");
 
         println!(
@@ -149,23 +1381,253 @@ mod tests {
         Ok(())
     }
 
-    #[cfg(all(feature = "http", feature = "rpm"))]
+    #[cfg(all(feature = "testing", feature = "rpm"))]
     #[test]
     fn test_from_url_rpm() -> Result<(), Box<dyn std::error::Error>> {
-        // Kibana is huge but should finish very quickly if just getting metadata.
         test_from_url(
             "https://artifacts.elastic.co/downloads/kibana/kibana-8.2.1-x86_64.rpm",
+            testing::RpmBuilder::new("kibana", "8.2.1", "1", "x86_64").build(),
             "kibana",
         )
     }
 
-    #[cfg(all(feature = "http", feature = "debian"))]
+    #[cfg(all(feature = "checksum", feature = "rpm"))]
+    #[test]
+    fn test_from_read_verified() {
+        use sha2::{Digest as _, Sha256};
+
+        let mut rpm = rpm_header::tests::build_rpm(&[]);
+        rpm.extend_from_slice(b"payload");
+        let sha256 = format!("{:x}", Sha256::digest(&rpm));
+
+        let package = from_read_verified(rpm.as_slice(), Digest::Sha256(sha256.clone())).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+
+        let (_, digests) = from_read_with_digests(rpm.as_slice(), &[Algorithm::Sha256]).unwrap();
+        assert_eq!(digests.sha256(), Some(sha256.as_str()));
+        assert_eq!(digests.sha1(), None);
+
+        // The payload isn't needed for the metadata but is still checked.
+        let mut tampered = rpm.clone();
+        *tampered.last_mut().unwrap() = b'!';
+        assert!(matches!(
+            from_read_verified(tampered.as_slice(), Digest::Sha256(sha256)),
+            Err(PkgError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[cfg(all(feature = "testing", feature = "debian"))]
     #[test]
     fn test_from_url_deb() -> Result<(), Box<dyn std::error::Error>> {
-        // Test a small Debian package.
         test_from_url(
             "http://cz.archive.ubuntu.com/ubuntu/pool/universe/d/debian-faq/debian-faq_10.1_all.deb",
+            testing::DebBuilder::new("debian-faq", "10.1", "all").build(),
             "debian-faq",
         )
     }
+
+    #[cfg(all(feature = "debian", feature = "rpm"))]
+    #[test]
+    fn test_package_type_extension() {
+        for package_type in [RemotePackageType::Deb, RemotePackageType::Rpm] {
+            assert_eq!(
+                RemotePackageType::from_extension(package_type.extension()),
+                Some(package_type)
+            );
+        }
+        assert_eq!(
+            RemotePackageType::from_extension(".UDEB"),
+            Some(RemotePackageType::Deb)
+        );
+        assert_eq!(RemotePackageType::from_extension("tar.gz"), None);
+        assert_eq!(RemotePackageType::Rpm.mime_type(), "application/x-rpm");
+    }
+
+    #[test]
+    fn test_language_fallbacks() {
+        assert_eq!(
+            language_fallbacks("de_AT.UTF-8@euro"),
+            vec!["de_AT@euro", "de_AT", "de@euro", "de"]
+        );
+        assert_eq!(language_fallbacks("pt-BR"), vec!["pt_BR", "pt"]);
+        assert_eq!(language_fallbacks("fr"), vec!["fr"]);
+        assert!(language_fallbacks("C.UTF-8").is_empty());
+        assert!(language_fallbacks("").is_empty());
+    }
+
+    #[cfg(all(feature = "testing", feature = "debian", feature = "rpm"))]
+    #[test]
+    fn test_package_id() {
+        use std::collections::HashSet;
+
+        let deb = testing::DebBuilder::new("hello", "1:2.10-3ubuntu1", "amd64").build();
+        let deb = debian::DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+        let deb_id = PackageId::from_package(&deb).unwrap();
+        assert_eq!(deb_id.epoch, Some(1));
+        assert_eq!(deb_id.version, "2.10");
+        assert_eq!(deb_id.release.as_deref(), Some("3ubuntu1"));
+        assert_eq!(deb_id.to_string(), "hello-1:2.10-3ubuntu1.amd64");
+
+        let rpm = testing::RpmBuilder::new("hello", "2.10", "3.fc40", "x86_64").build();
+        let rpm: Box<dyn RemotePackage> =
+            Box::new(rpm::RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap());
+        let rpm_id = PackageId::from_package(rpm.as_ref()).unwrap();
+        assert_eq!(rpm_id.to_string(), "hello-2.10-3.fc40.x86_64");
+
+        let ids: HashSet<PackageId> = vec![deb_id.clone(), rpm_id.clone(), deb_id.clone()]
+            .into_iter()
+            .collect();
+        assert_eq!(ids.len(), 2);
+        let mut sorted: Vec<PackageId> = ids.into_iter().collect();
+        sorted.sort();
+        assert_eq!(sorted, vec![deb_id, rpm_id]);
+    }
+
+    #[cfg(all(feature = "testing", feature = "rpm"))]
+    #[test]
+    fn test_final_url() {
+        let rpm = testing::RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+        let mirror = "https://mirror.example.com/pool/hello-1.0-1.noarch.rpm";
+        let mock = testing::MockTransport::new()
+            .with_response(
+                "https://redirector.example.com/hello.rpm",
+                testing::MockResponse::redirect(mirror),
+            )
+            .with_body(mirror, rpm)
+            .install();
+
+        let (package, stats) =
+            from_url_with_stats("https://redirector.example.com/hello.rpm").unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(stats.final_url.as_deref(), Some(mirror));
+        assert_eq!(
+            mock.requests(),
+            vec!["https://redirector.example.com/hello.rpm", mirror]
+        );
+    }
+
+    #[cfg(all(feature = "testing", feature = "rpm"))]
+    #[test]
+    fn test_error_context() {
+        let rpm = testing::RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+        let _mock = testing::MockTransport::new()
+            .with_response(
+                "https://example.com/busy.rpm",
+                testing::MockResponse::new("").with_status(503),
+            )
+            .with_body("https://example.com/hello.txt", "hello")
+            .with_body("https://example.com/truncated.rpm", &rpm[..200])
+            .install();
+
+        let err = from_url("https://example.com/busy.rpm").err().unwrap();
+        assert_eq!(err.url(), Some("https://example.com/busy.rpm"));
+        assert_eq!(err.phase(), Some(Phase::Fetch));
+        assert!(err.is_retryable());
+
+        let err = from_url("https://example.com/missing.rpm").err().unwrap();
+        assert_eq!(err.phase(), Some(Phase::Fetch));
+        assert!(!err.is_retryable());
+
+        let err = from_url("https://example.com/hello.txt").err().unwrap();
+        assert_eq!(err.phase(), Some(Phase::Infer));
+        assert!(err
+            .to_string()
+            .starts_with("Failed to detect the type of https://example.com/hello.txt: "));
+
+        let err = from_url("https://example.com/truncated.rpm").err().unwrap();
+        assert_eq!(err.phase(), Some(Phase::Parse));
+        assert_eq!(err.offset(), Some(200));
+        assert!(!err.is_retryable());
+    }
+
+    #[cfg(all(feature = "testing", feature = "rpm"))]
+    #[test]
+    fn test_error_kind() {
+        let _mock = testing::MockTransport::new()
+            .with_response(
+                "https://example.com/private.rpm",
+                testing::MockResponse::new("").with_status(401),
+            )
+            .with_response(
+                "https://example.com/busy.rpm",
+                testing::MockResponse::new("").with_status(503),
+            )
+            .with_body("https://example.com/hello.txt", "hello")
+            .install();
+
+        let kind = |url| from_url(url).err().unwrap().kind();
+        assert_eq!(kind("https://example.com/missing.rpm"), ErrorKind::NotFound);
+        assert_eq!(
+            kind("https://example.com/private.rpm"),
+            ErrorKind::AuthRequired
+        );
+        assert_eq!(kind("https://example.com/busy.rpm"), ErrorKind::Network);
+        assert_eq!(
+            kind("https://example.com/hello.txt"),
+            ErrorKind::Unsupported
+        );
+        assert_eq!(kind("not a url"), ErrorKind::InvalidInput);
+
+        assert_eq!(
+            PkgError::SizeLimitExceeded(1).kind(),
+            ErrorKind::LimitExceeded
+        );
+        assert_eq!(
+            PkgError::InvalidPackage("bad".to_string()).kind(),
+            ErrorKind::Parse
+        );
+    }
+
+    #[cfg(all(feature = "testing", feature = "debian", feature = "rpm"))]
+    #[test]
+    fn test_from_url_with_type() {
+        let url = "https://example.com/hello.deb";
+        let rpm = testing::RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+        let _mock = testing::MockTransport::new().with_body(url, rpm).install();
+
+        let package = from_url_with_type(url, RemotePackageType::Rpm).unwrap();
+        assert_eq!(package.package_type(), RemotePackageType::Rpm);
+        assert_eq!(package.package_name().unwrap(), "hello");
+
+        let err = from_url_with_type(url, RemotePackageType::Deb)
+            .err()
+            .unwrap();
+        assert_eq!(err.phase(), Some(Phase::Parse));
+    }
+
+    #[cfg(all(feature = "testing", feature = "debian", feature = "rpm"))]
+    #[test]
+    fn test_from_url_with_timeout() {
+        let rpm_url = "https://example.com/hello.rpm";
+        let deb_url = "https://example.com/hello.deb";
+        let _mock = testing::MockTransport::new()
+            .with_body(
+                rpm_url,
+                testing::RpmBuilder::new("hello", "1.0", "1", "noarch").build(),
+            )
+            .with_body(
+                deb_url,
+                testing::DebBuilder::new("hello", "1.0", "all").build(),
+            )
+            .install();
+
+        let timeout = std::time::Duration::from_secs(5);
+        let package = from_url_with_timeout(rpm_url, timeout).unwrap();
+        assert_eq!(package.package_type(), RemotePackageType::Rpm);
+        let package = rpm::RpmRemotePackage::new_from_url_with_timeout(rpm_url, timeout).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        let package =
+            debian::DebianRemotePackage::new_from_url_with_timeout(deb_url, timeout).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        let io = |kind| PkgError::IoError(std::io::Error::from(kind));
+        assert!(io(std::io::ErrorKind::TimedOut).is_retryable());
+        assert!(io(std::io::ErrorKind::ConnectionReset).is_retryable());
+        assert!(!io(std::io::ErrorKind::InvalidData).is_retryable());
+        assert!(!PkgError::SizeLimitExceeded(1).is_retryable());
+        assert!(!PkgError::InvalidPackage("bad".to_string()).is_retryable());
+    }
 }
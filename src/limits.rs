@@ -0,0 +1,266 @@
+//! Limits on the resources used when parsing untrusted packages.
+//!
+//! A small package can decompress to a huge control archive or declare
+//! millions of header entries. [`Limits`] bounds how much metadata is read,
+//! how many archive or header entries are accepted and how long parsing may
+//! take, failing with a dedicated [`PkgError`](crate::PkgError) variant when
+//! one is exceeded.
+//!
+//! Parsing is done as a stream, so the memory used doesn't grow with the
+//! size of the package. Only the metadata is kept, which is bounded by
+//! [`Limits::max_metadata_size`]; the payload is skipped or read in fixed
+//! size chunks and never buffered. The only other large allocation is the
+//! window of an xz or zstd decoder, which the stream itself picks and
+//! [`Limits::max_decompression_memory`] bounds. The defaults cap those two at
+//! 64 MiB of metadata and a 256 MiB decoder window; real packages use far
+//! less.
+//!
+//! Time is a separate matter: [`Limits::max_duration`] is `None` by default,
+//! so a slow or endless stream keeps a parse going until it ends or the
+//! metadata size limit is reached. Set it when parsing from an untrusted
+//! source that may stall.
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use std::cell::Cell;
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use std::io::{self, Read};
+use std::time::Duration;
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use std::time::Instant;
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use crate::PkgError;
+
+/// Resource limits applied while parsing a package.
+///
+/// A limit of `None` is not enforced. The defaults are generous enough for
+/// any real package while still stopping decompression bombs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The most bytes of decompressed metadata to read, i.e. the control
+    /// archive of a Debian package or the headers of an RPM.
+    pub max_metadata_size: Option<u64>,
+
+    /// The most entries to accept in a control archive or RPM header.
+    pub max_entries: Option<u64>,
+
+    /// The longest that parsing the metadata may take.
+    pub max_duration: Option<Duration>,
+
+    /// The most memory an xz or zstd decoder may allocate for its window
+    /// while decompressing metadata.
+    pub max_decompression_memory: Option<u64>,
+}
+
+impl Limits {
+    /// Limits that are never exceeded.
+    pub fn unlimited() -> Self {
+        Self {
+            max_metadata_size: None,
+            max_entries: None,
+            max_duration: None,
+            max_decompression_memory: None,
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_metadata_size: Some(64 * 1024 * 1024),
+            max_entries: Some(100_000),
+            max_duration: None,
+            max_decompression_memory: Some(256 * 1024 * 1024),
+        }
+    }
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+/// Which limit was exceeded.
+#[derive(Debug, Clone, Copy)]
+enum Exceeded {
+    Size(u64),
+    Entries(u64),
+    Time(Duration),
+    Memory(u64),
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+impl From<Exceeded> for PkgError {
+    fn from(exceeded: Exceeded) -> Self {
+        match exceeded {
+            Exceeded::Size(limit) => PkgError::SizeLimitExceeded(limit),
+            Exceeded::Entries(limit) => PkgError::EntryLimitExceeded(limit),
+            Exceeded::Time(limit) => PkgError::TimeLimitExceeded(limit),
+            Exceeded::Memory(limit) => PkgError::MemoryLimitExceeded(limit),
+        }
+    }
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+/// Tracks the resources used by one parse against a set of limits.
+///
+/// The parsers underneath only see I/O errors from a [`GuardedReader`], so
+/// the guard remembers which limit tripped and [`Guard::finish`] turns the
+/// result back into the matching error.
+#[derive(Debug)]
+pub(crate) struct Guard<'a> {
+    limits: &'a Limits,
+    start: Option<Instant>,
+    bytes: Cell<u64>,
+    entries: Cell<u64>,
+    exceeded: Cell<Option<Exceeded>>,
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+impl<'a> Guard<'a> {
+    /// Start tracking a parse.
+    pub(crate) fn new(limits: &'a Limits) -> Self {
+        Self {
+            limits,
+            // The clock is only read when there's a time limit, as it isn't
+            // available on every platform, e.g. wasm32 in a browser.
+            start: limits.max_duration.map(|_| Instant::now()),
+            bytes: Cell::new(0),
+            entries: Cell::new(0),
+            exceeded: Cell::new(None),
+        }
+    }
+
+    /// Wrap a reader of metadata so that its bytes count against the size
+    /// limit, and the time limit is checked on every read.
+    pub(crate) fn reader<R: Read>(&self, inner: R) -> GuardedReader<'_, 'a, R> {
+        GuardedReader { inner, guard: self }
+    }
+
+    /// Count entries against the entry limit.
+    pub(crate) fn add_entries(&self, count: u64) -> Result<(), PkgError> {
+        let entries = self.entries.get().saturating_add(count);
+        self.entries.set(entries);
+        match self.limits.max_entries {
+            Some(limit) if entries > limit => self.trip(Exceeded::Entries(limit)),
+            _ => self.check_time(),
+        }
+    }
+
+    /// Check the time limit.
+    pub(crate) fn check_time(&self) -> Result<(), PkgError> {
+        match (self.limits.max_duration, self.start) {
+            (Some(limit), Some(start)) if start.elapsed() > limit => {
+                self.trip(Exceeded::Time(limit))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace the result of a parse with the error for the limit that was
+    /// exceeded, if any.
+    pub(crate) fn finish<T>(&self, result: Result<T, PkgError>) -> Result<T, PkgError> {
+        match self.exceeded.get() {
+            Some(exceeded) => Err(exceeded.into()),
+            None => result,
+        }
+    }
+
+    /// The memory limit to give decoders of metadata.
+    #[cfg(feature = "debian")]
+    pub(crate) fn max_decompression_memory(&self) -> Option<u64> {
+        self.limits.max_decompression_memory
+    }
+
+    /// Record that a limit was exceeded.
+    fn trip(&self, exceeded: Exceeded) -> Result<(), PkgError> {
+        self.exceeded.set(Some(exceeded));
+        Err(exceeded.into())
+    }
+
+    /// Trip the memory limit if a decoder failed because of it.
+    fn check_memory(&self, err: io::Error) -> io::Error {
+        #[cfg(feature = "compression")]
+        if let Some(limit) = self.limits.max_decompression_memory {
+            if crate::compression::is_memory_limit_error(&err) {
+                self.exceeded.set(Some(Exceeded::Memory(limit)));
+            }
+        }
+        err
+    }
+
+    /// Count bytes read against the size limit.
+    fn add_bytes(&self, count: u64) -> Result<(), PkgError> {
+        let bytes = self.bytes.get().saturating_add(count);
+        self.bytes.set(bytes);
+        match self.limits.max_metadata_size {
+            Some(limit) if bytes > limit => self.trip(Exceeded::Size(limit)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+/// A reader whose bytes count against a [`Guard`].
+#[derive(Debug)]
+pub(crate) struct GuardedReader<'g, 'a, R> {
+    inner: R,
+    guard: &'g Guard<'a>,
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+impl<R: Read> Read for GuardedReader<'_, '_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.guard.check_time().map_err(to_io_error)?;
+        let n = self
+            .inner
+            .read(buf)
+            .map_err(|err| self.guard.check_memory(err))?;
+        self.guard.add_bytes(n as u64).map_err(to_io_error)?;
+        Ok(n)
+    }
+}
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+/// Pass a limit error through a parser that only understands I/O errors.
+fn to_io_error(err: PkgError) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+#[cfg(all(test, any(feature = "debian", feature = "rpm")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard() {
+        let limits = Limits {
+            max_metadata_size: Some(4),
+            max_entries: Some(2),
+            max_duration: None,
+            max_decompression_memory: None,
+        };
+
+        let guard = Guard::new(&limits);
+        let mut data = vec![];
+        assert!(guard.reader(&b"abcd"[..]).read_to_end(&mut data).is_ok());
+        assert!(guard.add_entries(2).is_ok());
+        assert_eq!(guard.finish(Ok(1)).unwrap(), 1);
+
+        let guard = Guard::new(&limits);
+        assert!(guard.reader(&b"abcde"[..]).read_to_end(&mut data).is_err());
+        let result: Result<(), _> = guard.finish(Err(PkgError::InferError));
+        assert!(matches!(result, Err(PkgError::SizeLimitExceeded(4))));
+
+        let guard = Guard::new(&limits);
+        assert!(matches!(
+            guard.add_entries(3),
+            Err(PkgError::EntryLimitExceeded(2))
+        ));
+
+        let limits = Limits {
+            max_duration: Some(Duration::from_secs(0)),
+            ..Limits::unlimited()
+        };
+        let guard = Guard::new(&limits);
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(matches!(
+            guard.check_time(),
+            Err(PkgError::TimeLimitExceeded(_))
+        ));
+    }
+}
@@ -0,0 +1,505 @@
+//! A snapshot of the metadata of a package, for export to other tools.
+//!
+//! The JSON shape is stable: fields are only ever added. Fields a package
+//! format doesn't record are `null` or empty lists, rather than left out.
+//! The same fields can be written as YAML or TOML, with the keys of each
+//! mapping in alphabetical order; TOML has no null, so missing values are
+//! left out there.
+//!
+//! It can also be rendered in the native formats: a Debian control
+//! paragraph or the preamble of an RPM spec file.
+//!
+//! ```json
+//! {
+//!   "type": "deb",
+//!   "name": "hello",
+//!   "version": "2.10-3",
+//!   "iteration": "3",
+//!   "arch": "amd64",
+//!   "license": null,
+//!   "dependencies": ["libc6"],
+//!   "files": [],
+//!   "config_files": ["/etc/hello.conf"],
+//!   "scripts": [{"name": "postinst", "interpreter": "/bin/sh", "contents": "..."}],
+//!   "triggers": [{"kind": "activate", "condition": "ldconfig", "script": null}]
+//! }
+//! ```
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use crate::RemotePackageType;
+use crate::{PackageScript, PackageTrigger, PkgError, RemotePackage};
+
+/// The metadata of a package, as returned by the [`RemotePackage`] methods.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageMetadata {
+    /// The package type, `deb` or `rpm`.
+    #[serde(rename = "type")]
+    pub package_type: String,
+
+    /// The package name.
+    pub name: String,
+
+    /// The package version, as the package records it.
+    pub version: String,
+
+    /// The package iteration, if the package has one.
+    pub iteration: Option<String>,
+
+    /// The package architecture.
+    pub arch: String,
+
+    /// The declared license, if the package format records one.
+    pub license: Option<String>,
+
+    /// The names of the declared dependencies.
+    pub dependencies: Vec<String>,
+
+    /// The paths of the files installed, if known from the metadata.
+    pub files: Vec<String>,
+
+    /// The paths of the configuration files.
+    pub config_files: Vec<String>,
+
+    /// The install and removal scripts.
+    pub scripts: Vec<PackageScript>,
+
+    /// The declared triggers.
+    pub triggers: Vec<PackageTrigger>,
+}
+
+impl PackageMetadata {
+    /// Take a snapshot of the metadata of a package.
+    pub fn from_package<P: RemotePackage + ?Sized>(package: &P) -> Result<Self, PkgError> {
+        Ok(Self {
+            package_type: package_type(package).to_owned(),
+            name: package.package_name()?.to_owned(),
+            version: package.package_version()?.to_owned(),
+            iteration: package.package_iteration().map(str::to_owned),
+            arch: package.package_arch()?.to_owned(),
+            license: package.package_license().map(str::to_owned),
+            dependencies: package.package_dependencies(),
+            files: package.package_files(),
+            config_files: package.package_config_files(),
+            scripts: package.package_scripts(),
+            triggers: package.package_triggers(),
+        })
+    }
+
+    /// Parse metadata from JSON in the shape written by
+    /// [`PackageMetadata::to_json`].
+    pub fn from_json(json: &str) -> Result<Self, PkgError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Serialize the metadata as compact JSON.
+    pub fn to_json(&self) -> Result<String, PkgError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Serialize the metadata as indented JSON.
+    pub fn to_json_pretty(&self) -> Result<String, PkgError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Serialize the metadata as a YAML document, in block style with every
+    /// string quoted.
+    pub fn to_yaml(&self) -> Result<String, PkgError> {
+        let mut out = String::new();
+        match serde_json::to_value(self)? {
+            Value::Object(map) => write_yaml_map(&mut out, &map, 0),
+            _ => unreachable!("metadata serializes to an object"),
+        }
+        Ok(out)
+    }
+
+    /// Serialize the metadata as a TOML document. Scripts and triggers are
+    /// arrays of tables.
+    pub fn to_toml(&self) -> Result<String, PkgError> {
+        let mut out = String::new();
+        match serde_json::to_value(self)? {
+            Value::Object(map) => write_toml_table(&mut out, "", &map),
+            _ => unreachable!("metadata serializes to an object"),
+        }
+        Ok(out)
+    }
+}
+
+/// The Debian names of RPM architectures; the others are the same in both.
+const ARCH_NAMES: [(&str, &str); 5] = [
+    ("all", "noarch"),
+    ("amd64", "x86_64"),
+    ("arm64", "aarch64"),
+    ("i386", "i686"),
+    ("ppc64el", "ppc64le"),
+];
+
+impl PackageMetadata {
+    /// The version without the iteration, if the version includes it, as
+    /// Debian versions do.
+    fn upstream_version(&self) -> &str {
+        self.iteration
+            .as_deref()
+            .and_then(|iteration| self.version.strip_suffix(iteration))
+            .and_then(|version| version.strip_suffix('-'))
+            .unwrap_or(&self.version)
+    }
+
+    /// The version including the iteration.
+    fn full_version(&self) -> String {
+        match &self.iteration {
+            Some(iteration) if self.upstream_version() == self.version => {
+                format!("{}-{}", self.version, iteration)
+            }
+            _ => self.version.clone(),
+        }
+    }
+
+    /// Render the metadata as a Debian control paragraph, as in the
+    /// `control` file of a `.deb`.
+    ///
+    /// Dependencies are written by name only, as the version constraints
+    /// aren't kept, and RPM architectures are given their Debian names.
+    pub fn to_control_text(&self) -> String {
+        let arch = ARCH_NAMES
+            .iter()
+            .find(|(_, rpm)| *rpm == self.arch)
+            .map_or(self.arch.as_str(), |(deb, _)| deb);
+
+        let mut text = format!(
+            "Package: {}\nVersion: {}\nArchitecture: {}\n",
+            self.name,
+            self.full_version(),
+            arch
+        );
+        if !self.dependencies.is_empty() {
+            text.push_str(&format!("Depends: {}\n", self.dependencies.join(", ")));
+        }
+        text
+    }
+
+    /// Render the metadata as the preamble of an RPM spec file.
+    ///
+    /// Debian versions are split into `Version` and `Release`, and Debian
+    /// architectures are given their RPM names.
+    pub fn to_spec_preamble(&self) -> String {
+        let arch = ARCH_NAMES
+            .iter()
+            .find(|(deb, _)| *deb == self.arch)
+            .map_or(self.arch.as_str(), |(_, rpm)| rpm);
+
+        let mut tags = vec![
+            ("Name", self.name.as_str()),
+            ("Version", self.upstream_version()),
+        ];
+        if let Some(iteration) = &self.iteration {
+            tags.push(("Release", iteration));
+        }
+        if let Some(license) = &self.license {
+            tags.push(("License", license));
+        }
+        tags.push(("BuildArch", arch));
+        for dependency in &self.dependencies {
+            tags.push(("Requires", dependency));
+        }
+
+        tags.iter()
+            .map(|(tag, value)| format!("{:<16}{}\n", format!("{}:", tag), value))
+            .collect()
+    }
+}
+
+/// Whether a key can be written without quotes in YAML and TOML.
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Quote a string. JSON's escapes are valid in both YAML double quoted
+/// scalars and TOML basic strings.
+fn quote(value: &str) -> String {
+    Value::from(value).to_string()
+}
+
+/// A key, quoted if needed.
+fn key(key: &str) -> String {
+    if is_bare_key(key) {
+        key.to_owned()
+    } else {
+        quote(key)
+    }
+}
+
+/// Write the entries of a mapping in YAML block style, indented by `indent`
+/// spaces.
+fn write_yaml_map(out: &mut String, map: &Map<String, Value>, indent: usize) {
+    let pad = " ".repeat(indent);
+    for (name, value) in map {
+        out.push_str(&format!("{}{}:", pad, key(name)));
+        match value {
+            Value::Array(items) if !items.is_empty() => {
+                out.push('\n');
+                for item in items {
+                    match item {
+                        // The first entry goes on the same line as the dash.
+                        Value::Object(item) if !item.is_empty() => {
+                            let mut entry = String::new();
+                            write_yaml_map(&mut entry, item, indent + 2);
+                            out.push_str(&format!("{}- {}", pad, &entry[indent + 2..]));
+                        }
+                        _ => out.push_str(&format!("{}- {}\n", pad, yaml_scalar(item))),
+                    }
+                }
+            }
+            Value::Object(inner) if !inner.is_empty() => {
+                out.push('\n');
+                write_yaml_map(out, inner, indent + 2);
+            }
+            _ => out.push_str(&format!(" {}\n", yaml_scalar(value))),
+        }
+    }
+}
+
+/// Write a value that fits on one line. JSON is valid YAML flow style, so
+/// empty and nested collections are written as JSON.
+fn yaml_scalar(value: &Value) -> String {
+    value.to_string()
+}
+
+/// Write a TOML table: its plain values first, then its sub-tables and
+/// arrays of tables. `path` is the dotted name of the table, or empty for
+/// the root.
+fn write_toml_table(out: &mut String, path: &str, map: &Map<String, Value>) {
+    let is_table_array = |value: &Value| match value {
+        Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_object),
+        _ => false,
+    };
+
+    for (name, value) in map {
+        if value.is_object() || is_table_array(value) {
+            continue;
+        }
+        if let Some(value) = toml_value(value) {
+            out.push_str(&format!("{} = {}\n", key(name), value));
+        }
+    }
+
+    for (name, value) in map {
+        let child = if path.is_empty() {
+            key(name)
+        } else {
+            format!("{}.{}", path, key(name))
+        };
+        match value {
+            Value::Object(inner) => {
+                out.push_str(&format!("\n[{}]\n", child));
+                write_toml_table(out, &child, inner);
+            }
+            Value::Array(items) if is_table_array(value) => {
+                for item in items.iter().filter_map(Value::as_object) {
+                    out.push_str(&format!("\n[[{}]]\n", child));
+                    write_toml_table(out, &child, item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Write a value inline, or `None` for null, which TOML can't represent.
+fn toml_value(value: &Value) -> Option<String> {
+    Some(match value {
+        Value::Null => return None,
+        Value::String(s) => quote(s),
+        Value::Array(items) => format!(
+            "[{}]",
+            items
+                .iter()
+                .filter_map(toml_value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Object(map) => format!(
+            "{{ {} }}",
+            map.iter()
+                .filter_map(|(name, value)| Some(format!("{} = {}", key(name), toml_value(value)?)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Value::Bool(_) | Value::Number(_) => value.to_string(),
+    })
+}
+
+/// The name of the type of a package.
+#[cfg_attr(not(any(feature = "debian", feature = "rpm")), allow(unreachable_code))]
+fn package_type<P: RemotePackage + ?Sized>(package: &P) -> &'static str {
+    match package.package_type() {
+        #[cfg(feature = "debian")]
+        RemotePackageType::Deb => "deb",
+        #[cfg(feature = "rpm")]
+        RemotePackageType::Rpm => "rpm",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_to_json() {
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+        let package = crate::rpm::RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+
+        let metadata = PackageMetadata::from_package(&package).unwrap();
+        assert_eq!(metadata.name, "hello");
+        assert_eq!(metadata.iteration.as_deref(), Some("1"));
+        let json: Value = serde_json::from_str(&package.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "rpm",
+                "name": "hello",
+                "version": "1.0",
+                "iteration": "1",
+                "arch": "noarch",
+                "license": "MIT",
+                "dependencies": metadata.dependencies,
+                "files": [],
+                "config_files": [],
+                "scripts": [],
+                "triggers": [],
+            })
+        );
+        assert!(metadata.to_json_pretty().unwrap().contains("\n  \"name\""));
+        assert_eq!(
+            PackageMetadata::from_json(&metadata.to_json().unwrap()).unwrap(),
+            metadata
+        );
+    }
+
+    /// Metadata with every kind of field.
+    fn metadata() -> PackageMetadata {
+        PackageMetadata {
+            package_type: "deb".to_string(),
+            name: "hello".to_string(),
+            version: "2.10-3".to_string(),
+            iteration: Some("3".to_string()),
+            arch: "amd64".to_string(),
+            license: None,
+            dependencies: vec!["libc6".to_string()],
+            files: vec![],
+            config_files: vec![],
+            scripts: vec![PackageScript {
+                name: "postinst".to_string(),
+                interpreter: Some("/bin/sh".to_string()),
+                contents: "#!/bin/sh\necho \"hi\"\n".to_string(),
+            }],
+            triggers: vec![PackageTrigger {
+                kind: "%triggerin".to_string(),
+                condition: "glibc".to_string(),
+                script: Some(PackageScript {
+                    name: "%triggerin".to_string(),
+                    interpreter: None,
+                    contents: "ldconfig".to_string(),
+                }),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        assert_eq!(
+            metadata().to_yaml().unwrap(),
+            r##"arch: "amd64"
+config_files: []
+dependencies:
+- "libc6"
+files: []
+iteration: "3"
+license: null
+name: "hello"
+scripts:
+- contents: "#!/bin/sh\necho \"hi\"\n"
+  interpreter: "/bin/sh"
+  name: "postinst"
+triggers:
+- condition: "glibc"
+  kind: "%triggerin"
+  script:
+    contents: "ldconfig"
+    interpreter: null
+    name: "%triggerin"
+type: "deb"
+version: "2.10-3"
+"##
+        );
+    }
+
+    #[test]
+    fn test_to_toml() {
+        assert_eq!(
+            metadata().to_toml().unwrap(),
+            r##"arch = "amd64"
+config_files = []
+dependencies = ["libc6"]
+files = []
+iteration = "3"
+name = "hello"
+type = "deb"
+version = "2.10-3"
+
+[[scripts]]
+contents = "#!/bin/sh\necho \"hi\"\n"
+interpreter = "/bin/sh"
+name = "postinst"
+
+[[triggers]]
+condition = "glibc"
+kind = "%triggerin"
+
+[triggers.script]
+contents = "ldconfig"
+name = "%triggerin"
+"##
+        );
+        assert_eq!(key("a.b"), "\"a.b\"");
+    }
+
+    #[test]
+    fn test_to_control_text() {
+        assert_eq!(
+            metadata().to_control_text(),
+            "Package: hello\nVersion: 2.10-3\nArchitecture: amd64\nDepends: libc6\n"
+        );
+
+        let mut metadata = metadata();
+        metadata.version = "1.0".to_string();
+        metadata.iteration = Some("1".to_string());
+        metadata.arch = "noarch".to_string();
+        metadata.dependencies.clear();
+        assert_eq!(
+            metadata.to_control_text(),
+            "Package: hello\nVersion: 1.0-1\nArchitecture: all\n"
+        );
+    }
+
+    #[test]
+    fn test_to_spec_preamble() {
+        let mut metadata = metadata();
+        metadata.license = Some("GPL-3.0-or-later".to_string());
+        assert_eq!(
+            metadata.to_spec_preamble(),
+            "Name:           hello
+Version:        2.10
+Release:        3
+License:        GPL-3.0-or-later
+BuildArch:      x86_64
+Requires:       libc6
+"
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! Support for Metalink files (`.metalink` and `.meta4`).
+//!
+//! A metalink describes a file by its size and hashes along with a list of
+//! mirrors it can be downloaded from. Both Metalink 3.0 and Metalink 4
+//! (RFC 5854) documents are understood.
+use std::io::{self, BufRead, Read};
+#[cfg(feature = "http-blocking")]
+use std::time::Duration;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::xml::attribute;
+use crate::PkgError;
+
+/// A hash of a file listed in a metalink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetalinkHash {
+    /// The hash algorithm, normalized to e.g. `sha256` or `md5`.
+    pub algorithm: String,
+
+    /// The hex-encoded hash value.
+    pub value: String,
+}
+
+/// A mirror a file can be downloaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetalinkUrl {
+    /// The download URL.
+    pub url: String,
+
+    /// The location of the mirror, usually an ISO 3166 country code.
+    pub location: Option<String>,
+}
+
+/// A file described by a metalink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetalinkFile {
+    /// The file name.
+    pub name: String,
+
+    /// The file size in bytes, if declared.
+    pub size: Option<u64>,
+
+    /// Whole-file hashes of the file.
+    pub hashes: Vec<MetalinkHash>,
+
+    /// The mirrors for the file, best first.
+    pub urls: Vec<MetalinkUrl>,
+}
+
+impl MetalinkFile {
+    /// Get the declared hash for an algorithm, e.g. `sha256`.
+    pub fn hash(&self, algorithm: &str) -> Option<&str> {
+        self.hashes
+            .iter()
+            .find(|h| h.algorithm == algorithm)
+            .map(|h| h.value.as_str())
+    }
+
+    /// Check downloaded contents against the declared size and the strongest
+    /// declared SHA-2 hash; see [`MetalinkFile::reader`].
+    pub fn verify(&self, data: &[u8]) -> Result<(), PkgError> {
+        self.reader(data)?.finish()
+    }
+
+    /// Wrap a download of the file so that it is checked against the
+    /// declared size and the strongest declared SHA-2 hash as it is read;
+    /// call [`MetalinkReader::finish`] to check it.
+    ///
+    /// No more than one byte past the declared size is read. Files that
+    /// declare neither a size nor a SHA-2 hash can't be checked, so are
+    /// rejected.
+    pub fn reader<R: Read>(&self, reader: R) -> Result<MetalinkReader<R>, PkgError> {
+        let hash = if let Some(expected) = self.hash("sha512") {
+            Some((Sha2::Sha512(Sha512::new()), expected))
+        } else if let Some(expected) = self.hash("sha384") {
+            Some((Sha2::Sha384(Sha384::new()), expected))
+        } else {
+            self.hash("sha256")
+                .map(|expected| (Sha2::Sha256(Sha256::new()), expected))
+        };
+        if hash.is_none() && self.size.is_none() {
+            return Err(PkgError::InvalidRepoData(format!(
+                "{} has no size or SHA-2 hash to check it against",
+                self.name
+            )));
+        }
+
+        Ok(MetalinkReader {
+            inner: reader.take(self.size.map_or(u64::MAX, |size| size.saturating_add(1))),
+            size: self.size,
+            hash: hash.map(|(hasher, expected)| (hasher, expected.to_owned())),
+            bytes_read: 0,
+        })
+    }
+
+    /// Download the file from the first mirror that serves it intact,
+    /// verifying each download with [`MetalinkFile::verify`].
+    ///
+    /// The whole file is held in memory so it can be verified before use;
+    /// see [`MetalinkFile::download_with`] to stream it instead.
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn download(&self) -> Result<Vec<u8>, PkgError> {
+        self.download_with(|reader| {
+            let mut data = Vec::new();
+            let _ = reader.read_to_end(&mut data)?;
+            Ok(data)
+        })
+        .map(|(data, _size)| data)
+    }
+
+    /// Download the file from the first mirror that serves it intact,
+    /// passing each download to `read` as it streams in.
+    ///
+    /// `read` may stop early; the rest of the download is then read and
+    /// checked as for [`MetalinkReader::finish`], and if it doesn't match,
+    /// the next mirror is tried. Returns what `read` returned for the
+    /// intact download, along with its size in bytes.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn download_with<T, F>(&self, read: F) -> Result<(T, u64), PkgError>
+    where
+        F: FnMut(&mut MetalinkReader<Box<dyn Read>>) -> Result<T, PkgError>,
+    {
+        self.download_with_timeout(None, read)
+    }
+
+    /// Download the file as [`MetalinkFile::download_with`] does, waiting up
+    /// to `timeout` for each mirror in place of the read timeout of the
+    /// client, if given.
+    #[cfg(feature = "http-blocking")]
+    pub(crate) fn download_with_timeout<T, F>(
+        &self,
+        timeout: Option<Duration>,
+        mut read: F,
+    ) -> Result<(T, u64), PkgError>
+    where
+        F: FnMut(&mut MetalinkReader<Box<dyn Read>>) -> Result<T, PkgError>,
+    {
+        let mut last_error = None;
+        for mirror in &self.urls {
+            let result =
+                crate::http::get_package_with_timeout(&mirror.url, timeout).and_then(|response| {
+                    let response: Box<dyn Read> = Box::new(response);
+                    let mut reader = self.reader(response)?;
+                    let value = read(&mut reader)?;
+                    reader.finish()?;
+                    Ok((value, reader.bytes_read()))
+                });
+
+            // A broken or out of date mirror shouldn't stop the download.
+            match result {
+                Ok(result) => return Ok(result),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            PkgError::InvalidRepoData(format!("no mirrors listed for {}", self.name))
+        }))
+    }
+}
+
+/// A reader that checks a download of a [`MetalinkFile`] as it is read.
+#[derive(Debug)]
+pub struct MetalinkReader<R: Read> {
+    inner: io::Take<R>,
+    size: Option<u64>,
+    hash: Option<(Sha2, String)>,
+    bytes_read: u64,
+}
+
+impl<R: Read> MetalinkReader<R> {
+    /// The number of bytes read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Read the rest of the download and check it, failing with
+    /// [`PkgError::SizeMismatch`] or [`PkgError::ChecksumMismatch`] if it
+    /// differs from the metalink.
+    ///
+    /// Reading stops one byte past the declared size, so a longer download
+    /// is reported as exactly one byte too long.
+    pub fn finish(&mut self) -> Result<(), PkgError> {
+        let _ = io::copy(self, &mut io::sink())?;
+
+        if let Some(size) = self.size {
+            if size != self.bytes_read {
+                return Err(PkgError::SizeMismatch {
+                    expected: size,
+                    actual: self.bytes_read,
+                });
+            }
+        }
+        if let Some((hasher, expected)) = &self.hash {
+            let actual = hasher.hex();
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(PkgError::ChecksumMismatch {
+                    algorithm: hasher.name().to_string(),
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for MetalinkReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some((hasher, _)) = &mut self.hash {
+            hasher.update(&buf[..n]);
+        }
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+/// A running SHA-2 hash.
+#[derive(Debug, Clone)]
+enum Sha2 {
+    Sha256(Sha256),
+    Sha384(Sha384),
+    Sha512(Sha512),
+}
+
+impl Sha2 {
+    fn name(&self) -> &'static str {
+        match self {
+            Sha2::Sha256(_) => "sha256",
+            Sha2::Sha384(_) => "sha384",
+            Sha2::Sha512(_) => "sha512",
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Sha2::Sha256(hasher) => hasher.update(data),
+            Sha2::Sha384(hasher) => hasher.update(data),
+            Sha2::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    /// The hex hash of everything so far.
+    fn hex(&self) -> String {
+        match self {
+            Sha2::Sha256(hasher) => format!("{:x}", hasher.clone().finalize()),
+            Sha2::Sha384(hasher) => format!("{:x}", hasher.clone().finalize()),
+            Sha2::Sha512(hasher) => format!("{:x}", hasher.clone().finalize()),
+        }
+    }
+}
+
+/// A parsed metalink document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Metalink {
+    /// The files described by the metalink.
+    pub files: Vec<MetalinkFile>,
+}
+
+impl Metalink {
+    /// Parse a Metalink 3.0 or Metalink 4 document.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Self, PkgError> {
+        let mut reader = Reader::from_reader(reader);
+        let _ = reader.trim_text(true);
+
+        let mut files = Vec::new();
+        let mut current: Option<MetalinkFile> = None;
+        // Mirrors alongside their rank; lower ranks are preferred.
+        let mut ranked_urls: Vec<(u32, MetalinkUrl)> = Vec::new();
+        let mut hash_type: Option<String> = None;
+        let mut url_attrs: Option<(Option<String>, u32)> = None;
+        let mut text_field: Option<&[u8]> = None;
+        // Element names below the current `<file>`.
+        let mut path: Vec<Vec<u8>> = Vec::new();
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(e) => {
+                    let name = e.local_name().as_ref().to_vec();
+                    if name == b"file" {
+                        current = Some(MetalinkFile {
+                            name: attribute(&e, b"name")?.unwrap_or_default(),
+                            size: None,
+                            hashes: Vec::new(),
+                            urls: Vec::new(),
+                        });
+                        path.clear();
+                        buf.clear();
+                        continue;
+                    }
+                    path.push(name);
+
+                    // Piece hashes and alternates are not whole-file hashes,
+                    // so only accept elements in these places.
+                    let parents: Vec<&[u8]> = path.iter().map(Vec::as_slice).collect();
+                    match parents.as_slice() {
+                        [b"size"] => text_field = Some(b"size"),
+                        [b"hash"] | [b"verification", b"hash"] => {
+                            hash_type = attribute(&e, b"type")?;
+                            text_field = Some(b"hash");
+                        }
+                        [b"url"] | [b"resources", b"url"] => {
+                            url_attrs = Some((attribute(&e, b"location")?, url_rank(&e)?));
+                            text_field = Some(b"url");
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Text(t) => {
+                    let text = t.unescape()?.into_owned();
+                    match (text_field, current.as_mut()) {
+                        (Some(b"size"), Some(file)) => {
+                            file.size = Some(text.trim().parse().map_err(|_| {
+                                PkgError::InvalidRepoData(format!("invalid size: {}", text))
+                            })?);
+                        }
+                        (Some(b"hash"), Some(file)) => {
+                            if let Some(algorithm) = hash_type.take() {
+                                file.hashes.push(MetalinkHash {
+                                    algorithm: normalize_algorithm(&algorithm),
+                                    value: text.trim().to_owned(),
+                                });
+                            }
+                        }
+                        (Some(b"url"), Some(_)) => {
+                            if let Some((location, rank)) = url_attrs.take() {
+                                let url = text.trim().to_owned();
+                                ranked_urls.push((rank, MetalinkUrl { url, location }));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::End(e) => {
+                    text_field = None;
+                    let _ = path.pop();
+                    if e.local_name().as_ref() == b"file" {
+                        if let Some(mut file) = current.take() {
+                            // A stable sort keeps document order among equals.
+                            ranked_urls.sort_by_key(|(rank, _)| *rank);
+                            file.urls = ranked_urls.drain(..).map(|(_, url)| url).collect();
+                            files.push(file);
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(Self { files })
+    }
+}
+
+/// Check whether the start of a document looks like a metalink.
+pub fn is_metalink(buf: &[u8]) -> bool {
+    String::from_utf8_lossy(buf).contains("<metalink")
+}
+
+/// Rank a mirror so that lower values are preferred. Metalink 4 uses
+/// `priority` (1 is best); Metalink 3.0 uses `preference` (100 is best).
+fn url_rank(element: &BytesStart<'_>) -> Result<u32, PkgError> {
+    let number = |value: String| {
+        value
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| PkgError::InvalidRepoData(format!("invalid mirror rank: {}", value)))
+    };
+
+    if let Some(priority) = attribute(element, b"priority")? {
+        return number(priority);
+    }
+    match attribute(element, b"preference")? {
+        Some(preference) => Ok(100u32.saturating_sub(number(preference)?)),
+        None => Ok(u32::MAX),
+    }
+}
+
+/// Normalize hash names across versions, e.g. `sha-256` to `sha256`.
+fn normalize_algorithm(algorithm: &str) -> String {
+    algorithm.to_lowercase().replace('-', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const META4: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metalink xmlns="urn:ietf:params:xml:ns:metalink">
+  <file name="hello.deb">
+    <size>5</size>
+    <hash type="sha-256">2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824</hash>
+    <pieces length="262144" type="sha-1">
+      <hash>aaaa</hash>
+    </pieces>
+    <url location="de" priority="2">https://de.example.org/hello.deb</url>
+    <url location="us" priority="1">https://us.example.org/hello.deb</url>
+  </file>
+</metalink>"#;
+
+    const METALINK3: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<metalink version="3.0" xmlns="http://www.metalinker.org/" xmlns:mm0="http://fedorahosted.org/mirrormanager">
+  <files>
+    <file name="repomd.xml">
+      <mm0:alternates>
+        <mm0:alternate>
+          <verification><hash type="sha256">old</hash></verification>
+        </mm0:alternate>
+      </mm0:alternates>
+      <size>5</size>
+      <verification>
+        <hash type="md5">5d41402abc4b2a76b9719d911017c592</hash>
+        <hash type="sha256">2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824</hash>
+      </verification>
+      <resources maxconnections="1">
+        <url protocol="https" type="https" location="US" preference="99">https://mirror1.example.org/repodata/repomd.xml</url>
+        <url protocol="https" type="https" location="GB" preference="100">https://mirror2.example.org/repodata/repomd.xml</url>
+      </resources>
+    </file>
+  </files>
+</metalink>"#;
+
+    #[test]
+    fn test_meta4() {
+        assert!(is_metalink(META4.as_bytes()));
+        let metalink = Metalink::parse(META4.as_bytes()).unwrap();
+        let file = &metalink.files[0];
+        assert_eq!(file.name, "hello.deb");
+        assert_eq!(file.size, Some(5));
+        assert_eq!(file.hashes.len(), 1);
+        assert_eq!(file.urls[0].url, "https://us.example.org/hello.deb");
+        assert_eq!(file.urls[1].location.as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn test_metalink3() {
+        let metalink = Metalink::parse(METALINK3.as_bytes()).unwrap();
+        let file = &metalink.files[0];
+        assert_eq!(file.hashes.len(), 2);
+        assert_eq!(file.hash("md5"), Some("5d41402abc4b2a76b9719d911017c592"));
+        assert_eq!(
+            file.urls[0].url,
+            "https://mirror2.example.org/repodata/repomd.xml"
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let metalink = Metalink::parse(META4.as_bytes()).unwrap();
+        let file = &metalink.files[0];
+        file.verify(b"hello").unwrap();
+        assert!(matches!(
+            file.verify(b"hellO"),
+            Err(PkgError::ChecksumMismatch { algorithm, .. }) if algorithm == "sha256"
+        ));
+        assert!(matches!(
+            file.verify(b"hello!"),
+            Err(PkgError::SizeMismatch {
+                expected: 5,
+                actual: 6
+            })
+        ));
+
+        // Only a byte past the declared size is read.
+        let mut reader = file.reader(&[0; 1000][..]).unwrap();
+        assert!(reader.finish().is_err());
+        assert_eq!(reader.bytes_read(), 6);
+
+        // A file with nothing to check against can't be verified.
+        let unchecked = MetalinkFile {
+            size: None,
+            hashes: vec![MetalinkHash {
+                algorithm: "md5".to_string(),
+                value: "5d41402abc4b2a76b9719d911017c592".to_string(),
+            }],
+            ..file.clone()
+        };
+        assert!(matches!(
+            unchecked.verify(b"hello"),
+            Err(PkgError::InvalidRepoData(_))
+        ));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_download_with() {
+        use crate::testing::MockTransport;
+
+        let _mock = MockTransport::new()
+            .with_body("https://us.example.org/hello.deb", "hellO")
+            .with_body("https://de.example.org/hello.deb", "hello")
+            .install();
+        let metalink = Metalink::parse(META4.as_bytes()).unwrap();
+
+        // The first mirror is out of date, though its first byte matches.
+        let (first, size) = metalink.files[0]
+            .download_with(|reader| {
+                let mut byte = [0; 1];
+                reader.read_exact(&mut byte)?;
+                Ok(byte[0])
+            })
+            .unwrap();
+        assert_eq!((first, size), (b'h', 5));
+        assert_eq!(metalink.files[0].download().unwrap(), b"hello");
+    }
+}
@@ -0,0 +1,174 @@
+//! Normalization of package names by the rules of each ecosystem, so that
+//! names typed by users match the names in indexes.
+//!
+//! ```
+//! use remote_package::naming::{self, Ecosystem};
+//!
+//! assert_eq!(naming::pypi("Zope.Interface"), "zope-interface");
+//! assert_eq!(Ecosystem::Npm.normalize(" @Types/Node "), "@types/node");
+//! assert!(Ecosystem::Crates.same_name("serde_json", "Serde-JSON"));
+//! ```
+
+/// A package ecosystem with its own naming rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ecosystem {
+    /// Debian packages and apt repositories.
+    Debian,
+
+    /// RPM packages and Yum/DNF repositories. Names are case sensitive.
+    Rpm,
+
+    /// Alpine packages. Names are case sensitive.
+    Alpine,
+
+    /// Arch Linux packages.
+    Arch,
+
+    /// Python packages on PyPI.
+    PyPi,
+
+    /// npm packages, which may be scoped.
+    Npm,
+
+    /// Rust crates on crates.io.
+    Crates,
+
+    /// NuGet packages.
+    NuGet,
+}
+
+impl Ecosystem {
+    /// Normalize a name by the rules of the ecosystem.
+    pub fn normalize(self, name: &str) -> String {
+        match self {
+            Ecosystem::Debian => debian(name),
+            Ecosystem::Rpm | Ecosystem::Alpine => name.trim().to_owned(),
+            Ecosystem::Arch | Ecosystem::NuGet => name.trim().to_lowercase(),
+            Ecosystem::PyPi => pypi(name),
+            Ecosystem::Npm => npm(name),
+            Ecosystem::Crates => crate_name(name),
+        }
+    }
+
+    /// Whether two names refer to the same package in the ecosystem.
+    pub fn same_name(self, a: &str, b: &str) -> bool {
+        self.normalize(a) == self.normalize(b)
+    }
+}
+
+/// Normalize a Python package name as PEP 503 describes: lower case, with
+/// each run of `-`, `_` and `.` replaced by a single `-`.
+pub fn pypi(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut separator = false;
+    for c in name.trim().chars() {
+        if c == '-' || c == '_' || c == '.' {
+            separator = true;
+            continue;
+        }
+        if separator && !normalized.is_empty() {
+            normalized.push('-');
+        }
+        separator = false;
+        normalized.extend(c.to_lowercase());
+    }
+    normalized
+}
+
+/// Normalize a Debian package name. Names may only contain lower case
+/// letters, digits and `+`, `-` and `.`, so upper case letters are lowered
+/// and `_` (as in Python module names) becomes `-`.
+pub fn debian(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| match c {
+            '_' => '-',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Whether a name is a valid Debian package name: at least two characters
+/// from lower case letters, digits, `+`, `-` and `.`, starting with a
+/// letter or digit.
+pub fn is_valid_debian(name: &str) -> bool {
+    name.len() >= 2
+        && name.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "+-.".contains(c))
+}
+
+/// Normalize an npm package name to lower case, with the scope of a scoped
+/// name given its `@`, e.g. `@types/node`.
+pub fn npm(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match name.split_once('/') {
+        Some((scope, _)) if !scope.starts_with('@') => format!("@{}", name),
+        _ => name,
+    }
+}
+
+/// Split an npm package name into its scope, without the `@`, and the name
+/// within the scope.
+pub fn npm_scope(name: &str) -> (Option<&str>, &str) {
+    match name.strip_prefix('@').and_then(|rest| rest.split_once('/')) {
+        Some((scope, name)) => (Some(scope), name),
+        None => (None, name),
+    }
+}
+
+/// Normalize a crate name as crates.io compares them: case insensitively,
+/// with `-` and `_` the same. This is for comparisons; index paths use the
+/// published name.
+pub fn crate_name(name: &str) -> String {
+    name.trim().to_lowercase().replace('_', "-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pypi() {
+        assert_eq!(pypi("Django"), "django");
+        assert_eq!(pypi("zope.interface"), "zope-interface");
+        assert_eq!(pypi("Foo__Bar-.baz"), "foo-bar-baz");
+        assert_eq!(pypi("_private"), "private");
+        assert!(Ecosystem::PyPi.same_name("typing_extensions", "Typing-Extensions"));
+    }
+
+    #[test]
+    fn test_debian() {
+        assert_eq!(debian(" Python3_Foo "), "python3-foo");
+        assert!(is_valid_debian("libstdc++6"));
+        assert!(is_valid_debian("g++"));
+        assert!(!is_valid_debian("a"));
+        assert!(!is_valid_debian("-foo"));
+        assert!(!is_valid_debian("Foo"));
+        assert!(is_valid_debian(&debian("Foo_Bar")));
+    }
+
+    #[test]
+    fn test_npm() {
+        assert_eq!(npm("@Babel/Core"), "@babel/core");
+        assert_eq!(npm("babel/core"), "@babel/core");
+        assert_eq!(npm("Express"), "express");
+        assert_eq!(npm_scope("@babel/core"), (Some("babel"), "core"));
+        assert_eq!(npm_scope("express"), (None, "express"));
+    }
+
+    #[test]
+    fn test_other_ecosystems() {
+        assert_eq!(crate_name("Serde_JSON"), "serde-json");
+        assert_eq!(
+            Ecosystem::NuGet.normalize("Newtonsoft.Json"),
+            "newtonsoft.json"
+        );
+        assert_eq!(
+            Ecosystem::Rpm.normalize(" NetworkManager "),
+            "NetworkManager"
+        );
+        assert!(!Ecosystem::Rpm.same_name("networkmanager", "NetworkManager"));
+    }
+}
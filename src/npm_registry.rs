@@ -0,0 +1,220 @@
+//! Support for looking up packages in an npm registry.
+//!
+//! The registry serves a "packument" per package listing every published
+//! version, its tarball URL and integrity hash, along with dist-tags such as
+//! `latest`.
+use std::collections::BTreeMap;
+
+use semver::{Version, VersionReq};
+use serde::Deserialize;
+
+use crate::PkgError;
+
+#[cfg(feature = "http-blocking")]
+use crate::http::Auth;
+
+/// The public npm registry.
+pub const NPM_REGISTRY: &str = "https://registry.npmjs.org";
+
+/// Distribution information for a published version.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct NpmDist {
+    /// The tarball download URL.
+    pub tarball: String,
+
+    /// The Subresource Integrity string, e.g. `sha512-...`.
+    #[serde(default)]
+    pub integrity: Option<String>,
+
+    /// The legacy hex SHA1 of the tarball.
+    #[serde(default)]
+    pub shasum: Option<String>,
+}
+
+/// A published version of a package.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct NpmVersion {
+    /// The package name.
+    pub name: String,
+
+    /// The version.
+    pub version: String,
+
+    /// Where to download the version from.
+    pub dist: NpmDist,
+
+    /// The deprecation message, if the version is deprecated.
+    #[serde(default)]
+    pub deprecated: Option<String>,
+}
+
+/// A package document from an npm registry.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NpmPackument {
+    /// The package name.
+    pub name: String,
+
+    /// Tags pointing at versions, e.g. `latest`.
+    #[serde(rename = "dist-tags", default)]
+    pub dist_tags: BTreeMap<String, String>,
+
+    /// Every published version, keyed by version.
+    #[serde(default)]
+    pub versions: BTreeMap<String, NpmVersion>,
+}
+
+impl NpmPackument {
+    /// Parse a package document.
+    pub fn parse(json: &str) -> Result<Self, PkgError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Fetch a package document from a registry such as [`NPM_REGISTRY`],
+    /// optionally authenticating against a private registry.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn fetch(registry: &str, name: &str, auth: Option<&Auth>) -> Result<Self, PkgError> {
+        let url = package_url(registry, name);
+        let response = crate::http::get_with_auth(&url, auth)?;
+        Self::parse(&response.text()?)
+    }
+
+    /// Resolve a dist-tag (e.g. `latest`) or semver range (e.g. `^1.2.0`,
+    /// `>=1.0.0 <2.0.0 || 3.x`) to the best matching version.
+    ///
+    /// Dist-tags take priority over ranges. For a range, the highest
+    /// matching version is chosen. Returns `None` if nothing matches.
+    pub fn resolve(&self, spec: &str) -> Result<Option<&NpmVersion>, PkgError> {
+        if let Some(version) = self.dist_tags.get(spec) {
+            return Ok(self.versions.get(version));
+        }
+
+        let reqs = parse_range(spec)?;
+        let best = self
+            .versions
+            .values()
+            .filter_map(|v| Version::parse(&v.version).ok().map(|parsed| (parsed, v)))
+            .filter(|(parsed, _)| reqs.iter().any(|req| req.matches(parsed)))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, v)| v);
+        Ok(best)
+    }
+}
+
+/// Get the URL of a package document, escaping the `/` of scoped names.
+pub fn package_url(registry: &str, name: &str) -> String {
+    format!(
+        "{}/{}",
+        registry.trim_end_matches('/'),
+        name.replace('/', "%2f")
+    )
+}
+
+/// Convert an npm range into semver requirements, one per `||` alternative.
+fn parse_range(range: &str) -> Result<Vec<VersionReq>, PkgError> {
+    range
+        .split("||")
+        .map(|alternative| {
+            let alternative = alternative.trim();
+            let comparators = match alternative.split_once(" - ") {
+                // Hyphen ranges are inclusive at both ends.
+                Some((low, high)) => {
+                    vec![format!(">={}", low.trim()), format!("<={}", high.trim())]
+                }
+                None => split_comparators(alternative),
+            };
+
+            let req = if comparators.is_empty() {
+                "*".to_string()
+            } else {
+                comparators.join(", ")
+            };
+            VersionReq::parse(&req)
+                .map_err(|e| PkgError::InvalidVersionRange(format!("{}: {}", range, e)))
+        })
+        .collect()
+}
+
+/// Split the space separated comparators of an npm range, converting bare
+/// versions to exact matches (semver treats them as caret requirements).
+fn split_comparators(range: &str) -> Vec<String> {
+    let mut comparators = Vec::new();
+    let mut pending_op = String::new();
+
+    for token in range.split_whitespace() {
+        // Allow a space between an operator and its version, e.g. ">= 1.0".
+        if token.chars().all(|c| "<>=~^".contains(c)) {
+            pending_op.push_str(token);
+            continue;
+        }
+        let token = std::mem::take(&mut pending_op) + token.trim_start_matches('v');
+
+        let is_bare = token.starts_with(|c: char| c.is_ascii_digit());
+        let is_wildcard = token.contains(['x', 'X', '*']);
+        if is_bare && !is_wildcard {
+            comparators.push(format!("={}", token));
+        } else {
+            comparators.push(token);
+        }
+    }
+    comparators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKUMENT: &str = r#"{
+        "name": "@scope/example",
+        "dist-tags": {"latest": "1.2.0", "next": "2.0.0-beta.1"},
+        "versions": {
+            "1.0.0": {"name": "@scope/example", "version": "1.0.0",
+                      "dist": {"tarball": "https://registry.example/example-1.0.0.tgz", "shasum": "aaaa"}},
+            "1.2.0": {"name": "@scope/example", "version": "1.2.0",
+                      "dist": {"tarball": "https://registry.example/example-1.2.0.tgz", "integrity": "sha512-bbbb"}},
+            "1.3.0": {"name": "@scope/example", "version": "1.3.0",
+                      "dist": {"tarball": "https://registry.example/example-1.3.0.tgz"}},
+            "2.0.0-beta.1": {"name": "@scope/example", "version": "2.0.0-beta.1",
+                      "dist": {"tarball": "https://registry.example/example-2.0.0-beta.1.tgz"}},
+            "3.1.0": {"name": "@scope/example", "version": "3.1.0",
+                      "dist": {"tarball": "https://registry.example/example-3.1.0.tgz"}}
+        }
+    }"#;
+
+    fn resolve(spec: &str) -> Option<String> {
+        let packument = NpmPackument::parse(PACKUMENT).unwrap();
+        packument.resolve(spec).unwrap().map(|v| v.version.clone())
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("latest").as_deref(), Some("1.2.0"));
+        assert_eq!(resolve("next").as_deref(), Some("2.0.0-beta.1"));
+        assert_eq!(resolve("^1.0.0").as_deref(), Some("1.3.0"));
+        assert_eq!(resolve("~1.2.0").as_deref(), Some("1.2.0"));
+        assert_eq!(resolve("1.0.0").as_deref(), Some("1.0.0"));
+        assert_eq!(resolve("v1.0.0").as_deref(), Some("1.0.0"));
+        assert_eq!(resolve(">= 1.0.0 <1.3.0").as_deref(), Some("1.2.0"));
+        assert_eq!(resolve("1.0.0 - 1.2.0").as_deref(), Some("1.2.0"));
+        assert_eq!(resolve("^1.0.0 || 3.x").as_deref(), Some("3.1.0"));
+        assert_eq!(resolve("*").as_deref(), Some("3.1.0"));
+        assert_eq!(resolve("^4.0.0"), None);
+        assert!(NpmPackument::parse(PACKUMENT)
+            .unwrap()
+            .resolve("not a range")
+            .is_err());
+    }
+
+    #[test]
+    fn test_integrity() {
+        let packument = NpmPackument::parse(PACKUMENT).unwrap();
+        let latest = packument.resolve("latest").unwrap().unwrap();
+        assert_eq!(latest.dist.integrity.as_deref(), Some("sha512-bbbb"));
+        assert_eq!(
+            package_url("https://registry.npmjs.org/", &packument.name),
+            "https://registry.npmjs.org/@scope%2fexample"
+        );
+    }
+}
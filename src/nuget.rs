@@ -0,0 +1,190 @@
+//! Support for looking up packages in NuGet v3 feeds.
+//!
+//! A v3 feed is described by a service index (`index.json`) listing the
+//! resources the feed provides. Package contents are served by the
+//! `PackageBaseAddress` resource, also known as the flat container.
+use serde::Deserialize;
+
+use crate::PkgError;
+
+#[cfg(feature = "http-blocking")]
+use crate::http::Auth;
+
+/// The service index for nuget.org.
+pub const NUGET_ORG: &str = "https://api.nuget.org/v3/index.json";
+
+/// The resource type of the flat container in a service index.
+const PACKAGE_BASE_ADDRESS: &str = "PackageBaseAddress/3.0.0";
+
+/// A resource listed in a service index.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct NugetResource {
+    /// The resource URL.
+    #[serde(rename = "@id")]
+    pub id: String,
+
+    /// The resource type, e.g. `PackageBaseAddress/3.0.0`.
+    #[serde(rename = "@type")]
+    pub resource_type: String,
+}
+
+/// A NuGet v3 service index.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ServiceIndex {
+    /// The resources provided by the feed.
+    pub resources: Vec<NugetResource>,
+}
+
+impl ServiceIndex {
+    /// Parse a service index.
+    pub fn parse(json: &str) -> Result<Self, PkgError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Get the package base address (flat container) URL.
+    pub fn package_base_address(&self) -> Result<&str, PkgError> {
+        self.resources
+            .iter()
+            .find(|r| r.resource_type == PACKAGE_BASE_ADDRESS)
+            .map(|r| r.id.trim_end_matches('/'))
+            .ok_or_else(|| PkgError::RepoDataNotFound(PACKAGE_BASE_ADDRESS.to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionList {
+    versions: Vec<String>,
+}
+
+/// Parse the version list returned by the flat container.
+pub fn parse_versions(json: &str) -> Result<Vec<String>, PkgError> {
+    let list: VersionList = serde_json::from_str(json)?;
+    Ok(list.versions)
+}
+
+/// Normalize a NuGet version as the flat container expects: lower case,
+/// with build metadata removed and a zero fourth component dropped.
+pub fn normalize_version(version: &str) -> String {
+    let version = version.split('+').next().unwrap_or_default();
+    let (release, prerelease) = match version.split_once('-') {
+        Some((release, prerelease)) => (release, Some(prerelease)),
+        None => (version, None),
+    };
+
+    let mut parts: Vec<String> = release
+        .split('.')
+        .map(|part| part.trim_start_matches('0'))
+        .map(|part| if part.is_empty() { "0" } else { part }.to_owned())
+        .collect();
+    while parts.len() < 3 {
+        parts.push("0".to_string());
+    }
+    if parts.len() == 4 && parts[3] == "0" {
+        let _ = parts.pop();
+    }
+
+    let mut normalized = parts.join(".");
+    if let Some(prerelease) = prerelease {
+        normalized.push('-');
+        normalized.push_str(prerelease);
+    }
+    normalized.to_lowercase()
+}
+
+/// Get the `.nupkg` download URL for a package in a flat container.
+pub fn nupkg_url(base_address: &str, id: &str, version: &str) -> String {
+    let id = id.to_lowercase();
+    let version = normalize_version(version);
+    format!(
+        "{}/{}/{}/{}.{}.nupkg",
+        base_address.trim_end_matches('/'),
+        id,
+        version,
+        id,
+        version
+    )
+}
+
+/// A remote NuGet v3 feed.
+#[cfg(feature = "http-blocking")]
+#[derive(Debug, Clone)]
+pub struct NugetFeed {
+    base_address: String,
+    auth: Option<Auth>,
+}
+
+#[cfg(feature = "http-blocking")]
+impl NugetFeed {
+    /// Connect to a feed by fetching its service index, e.g. [`NUGET_ORG`].
+    /// Credentials are used for every request, so private feeds work too.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn connect(service_index_url: &str, auth: Option<Auth>) -> Result<Self, PkgError> {
+        let response = crate::http::get_with_auth(service_index_url, auth.as_ref())?;
+        let index = ServiceIndex::parse(&response.text()?)?;
+        Ok(Self {
+            base_address: index.package_base_address()?.to_owned(),
+            auth,
+        })
+    }
+
+    /// Get the package base address of the feed.
+    pub fn base_address(&self) -> &str {
+        &self.base_address
+    }
+
+    /// List the published versions of a package.
+    pub fn versions(&self, id: &str) -> Result<Vec<String>, PkgError> {
+        let url = format!("{}/{}/index.json", self.base_address, id.to_lowercase());
+        let response = crate::http::get_with_auth(&url, self.auth.as_ref())?;
+        parse_versions(&response.text()?)
+    }
+
+    /// Get the `.nupkg` download URL for a package version.
+    pub fn nupkg_url(&self, id: &str, version: &str) -> String {
+        nupkg_url(&self.base_address, id, version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_index() {
+        let index = ServiceIndex::parse(
+            r#"{"version": "3.0.0", "resources": [
+                {"@id": "https://azuresearch-usnc.nuget.org/query", "@type": "SearchQueryService"},
+                {"@id": "https://api.nuget.org/v3-flatcontainer/", "@type": "PackageBaseAddress/3.0.0"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            index.package_base_address().unwrap(),
+            "https://api.nuget.org/v3-flatcontainer"
+        );
+
+        let empty = ServiceIndex::parse(r#"{"resources": []}"#).unwrap();
+        assert!(empty.package_base_address().is_err());
+    }
+
+    #[test]
+    fn test_versions() {
+        let versions = parse_versions(r#"{"versions": ["12.0.1", "13.0.3"]}"#).unwrap();
+        assert_eq!(versions, vec!["12.0.1", "13.0.3"]);
+    }
+
+    #[test]
+    fn test_nupkg_url() {
+        assert_eq!(normalize_version("1.0"), "1.0.0");
+        assert_eq!(normalize_version("01.2.3.0"), "1.2.3");
+        assert_eq!(normalize_version("1.2.3.4"), "1.2.3.4");
+        assert_eq!(normalize_version("1.0.0-Beta+build.5"), "1.0.0-beta");
+
+        assert_eq!(
+            nupkg_url("https://api.nuget.org/v3-flatcontainer/", "Newtonsoft.Json", "13.0.3"),
+            "https://api.nuget.org/v3-flatcontainer/newtonsoft.json/13.0.3/newtonsoft.json.13.0.3.nupkg"
+        );
+    }
+}
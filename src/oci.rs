@@ -0,0 +1,565 @@
+//! Support for fetching packages stored as artifacts in OCI registries.
+//!
+//! Registries that implement the OCI distribution spec, such as ghcr.io,
+//! Harbor or Zot, can hold any file as a layer of an image manifest, as
+//! pushed by e.g. `oras push ghcr.io/org/hello:1.0 hello-1.0-1.noarch.rpm`.
+//! [`OciRepository`] fetches a manifest by tag or digest, picks the layer
+//! holding the package and parses it as it streams in, checking the whole
+//! layer against its digest.
+//!
+//! Registries that want a token even for anonymous pulls, such as ghcr.io
+//! and Docker Hub, are asked for one when they answer with a `Bearer`
+//! challenge. With the `cosign` feature, the manifest's cosign signature can
+//! be checked before the package is fetched; see the `cosign` module.
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+
+use crate::checksum::Digest;
+use crate::http::Auth;
+use crate::{PkgError, RemotePackage};
+
+/// The media type of an OCI image manifest.
+pub const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// The media type of a Docker image manifest, which OCI registries also
+/// serve.
+pub const DOCKER_MANIFEST: &str = "application/vnd.docker.distribution.manifest.v2+json";
+
+/// The annotation naming the file a layer holds.
+const TITLE_ANNOTATION: &str = "org.opencontainers.image.title";
+
+/// The largest manifest read. Registries don't accept larger ones.
+const MAX_MANIFEST_SIZE: u64 = 4 * 1024 * 1024;
+
+/// A reference to a manifest in a registry, e.g. `ghcr.io/org/hello:1.0` or
+/// `ghcr.io/org/hello@sha256:…`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OciReference {
+    /// The registry host, with its port if any, e.g. `ghcr.io`.
+    pub registry: String,
+
+    /// The repository in the registry, e.g. `org/hello`.
+    pub repository: String,
+
+    /// The tag or digest of the manifest.
+    pub reference: String,
+}
+
+impl OciReference {
+    /// Parse a reference, with or without an `oci://` prefix. The registry
+    /// must be given; there's no default registry. A reference without a
+    /// tag or digest is for `latest`.
+    pub fn parse(reference: &str) -> Result<Self, PkgError> {
+        let invalid = || PkgError::InvalidRepoSpec(reference.to_string());
+        let rest = reference.strip_prefix("oci://").unwrap_or(reference);
+        let (registry, path) = rest.split_once('/').ok_or_else(invalid)?;
+        if registry.is_empty() || !(registry.contains('.') || registry.contains(':')) {
+            return Err(invalid());
+        }
+
+        // A digest follows an @, and a tag the last colon after the last
+        // slash, as the registry's port also follows a colon.
+        let (repository, reference) = match path.split_once('@') {
+            Some((repository, digest)) => (repository, digest),
+            None => match path
+                .rfind(':')
+                .filter(|&colon| !path[colon..].contains('/'))
+            {
+                Some(colon) => (&path[..colon], &path[colon + 1..]),
+                None => (path, "latest"),
+            },
+        };
+        if repository.is_empty() || reference.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            registry: registry.to_owned(),
+            repository: repository.to_owned(),
+            reference: reference.to_owned(),
+        })
+    }
+}
+
+impl std::fmt::Display for OciReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let separator = if self.reference.contains(':') {
+            '@'
+        } else {
+            ':'
+        };
+        write!(
+            f,
+            "{}/{}{}{}",
+            self.registry, self.repository, separator, self.reference
+        )
+    }
+}
+
+/// A blob listed in a manifest, such as a layer.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OciDescriptor {
+    /// The media type of the blob.
+    #[serde(rename = "mediaType")]
+    pub media_type: String,
+
+    /// The digest of the blob, e.g. `sha256:…`.
+    pub digest: String,
+
+    /// The size of the blob in bytes.
+    pub size: u64,
+
+    /// The annotations of the blob.
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+impl OciDescriptor {
+    /// The name of the file the blob holds, if it was pushed with one.
+    pub fn title(&self) -> Option<&str> {
+        self.annotations.get(TITLE_ANNOTATION).map(String::as_str)
+    }
+
+    /// The expected checksum of the blob.
+    pub fn checksum(&self) -> Result<Digest, PkgError> {
+        parse_digest(&self.digest)
+    }
+}
+
+/// An image manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OciManifest {
+    /// The digest of the manifest itself, e.g. `sha256:…`.
+    #[serde(skip)]
+    pub digest: String,
+
+    /// The media type of the manifest, if it says.
+    #[serde(rename = "mediaType", default)]
+    pub media_type: Option<String>,
+
+    /// The layers, in order.
+    pub layers: Vec<OciDescriptor>,
+
+    /// The annotations of the manifest.
+    #[serde(default)]
+    pub annotations: HashMap<String, String>,
+}
+
+impl OciManifest {
+    /// Parse a manifest, computing its digest from its bytes.
+    pub fn parse(data: &[u8]) -> Result<Self, PkgError> {
+        let mut manifest: Self = serde_json::from_slice(data)?;
+        manifest.digest = format!("sha256:{:x}", Sha256::digest(data));
+        Ok(manifest)
+    }
+
+    /// The layer holding the package: the only layer, or else the one
+    /// whose title has a package file extension.
+    pub fn package_layer(&self) -> Result<&OciDescriptor, PkgError> {
+        if let [layer] = self.layers.as_slice() {
+            return Ok(layer);
+        }
+        let mut packages = self.layers.iter().filter(|layer| {
+            layer
+                .title()
+                .and_then(|title| title.rsplit_once('.'))
+                .and_then(|(_, extension)| crate::RemotePackageType::from_extension(extension))
+                .is_some()
+        });
+        match (packages.next(), packages.next()) {
+            (Some(layer), None) => Ok(layer),
+            (None, _) => Err(PkgError::PackageNotFound(format!(
+                "no package layer in manifest {}",
+                self.digest
+            ))),
+            (Some(_), Some(_)) => Err(PkgError::InvalidRepoData(format!(
+                "more than one package layer in manifest {}",
+                self.digest
+            ))),
+        }
+    }
+}
+
+/// Turn an OCI digest such as `sha256:…` into a checksum.
+fn parse_digest(digest: &str) -> Result<Digest, PkgError> {
+    match digest.split_once(':') {
+        Some(("sha256", hex)) if hex.len() == 64 => Ok(Digest::Sha256(hex.to_owned())),
+        Some(("sha512", hex)) if hex.len() == 128 => Ok(Digest::Sha512(hex.to_owned())),
+        _ => Err(PkgError::InvalidRepoData(format!(
+            "unsupported digest {}",
+            digest
+        ))),
+    }
+}
+
+/// A token from a registry's token service.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    access_token: Option<String>,
+}
+
+/// A repository in an OCI registry.
+#[derive(Debug)]
+pub struct OciRepository {
+    registry: String,
+    repository: String,
+    auth: Option<Auth>,
+
+    /// The token from the registry's token service, once one was needed.
+    token: Mutex<Option<Auth>>,
+}
+
+impl OciRepository {
+    /// A repository, e.g. `org/hello` in `ghcr.io`. Credentials are sent
+    /// with every request, or to the registry's token service if it asks
+    /// for a token.
+    pub fn new(registry: &str, repository: &str, auth: Option<Auth>) -> Self {
+        Self {
+            registry: registry.trim_end_matches('/').to_owned(),
+            repository: repository.trim_matches('/').to_owned(),
+            auth,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// The repository a reference points into.
+    pub fn from_reference(reference: &OciReference, auth: Option<Auth>) -> Self {
+        Self::new(&reference.registry, &reference.repository, auth)
+    }
+
+    /// Fetch a manifest by tag or digest. A manifest fetched by digest is
+    /// checked against it.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn manifest(&self, reference: &str) -> Result<OciManifest, PkgError> {
+        let url = self.url("manifests", reference);
+        let accept = format!("{}, {}", OCI_MANIFEST, DOCKER_MANIFEST);
+        let response = self.get(&url, Some(&accept))?;
+
+        let mut data = Vec::new();
+        let _ = response
+            .take(MAX_MANIFEST_SIZE + 1)
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > MAX_MANIFEST_SIZE {
+            return Err(PkgError::SizeLimitExceeded(MAX_MANIFEST_SIZE));
+        }
+        if reference.contains(':') {
+            parse_digest(reference)?.check(&data)?;
+        }
+        OciManifest::parse(&data)
+    }
+
+    /// Fetch a blob, such as a layer of a manifest, as it streams in. The
+    /// caller checks it against [`OciDescriptor::checksum`].
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn blob(&self, descriptor: &OciDescriptor) -> Result<impl Read, PkgError> {
+        let url = self.url("blobs", &descriptor.digest);
+        Ok(self.get(&url, None)?.take(descriptor.size + 1))
+    }
+
+    /// Fetch and parse the package in a manifest, by tag or digest; see
+    /// [`OciManifest::package_layer`]. The whole layer is read, so that it
+    /// can be checked against its size and digest.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    pub fn package(&self, reference: &str) -> Result<Box<dyn RemotePackage>, PkgError> {
+        let manifest = self.manifest(reference)?;
+        self.manifest_package(&manifest)
+    }
+
+    /// Fetch and parse the package in a manifest that was already fetched.
+    pub(crate) fn manifest_package(
+        &self,
+        manifest: &OciManifest,
+    ) -> Result<Box<dyn RemotePackage>, PkgError> {
+        let layer = manifest.package_layer()?;
+        let checksum = layer.checksum()?;
+        crate::checksum::parse_checked(
+            self.blob(layer)?,
+            Some(layer.size),
+            Some((checksum.algorithm(), checksum.expected())),
+            |reader| crate::from_read(reader),
+        )
+    }
+
+    /// The URL of a manifest or blob in the repository.
+    fn url(&self, kind: &str, reference: &str) -> String {
+        format!(
+            "https://{}/v2/{}/{}/{}",
+            self.registry, self.repository, kind, reference
+        )
+    }
+
+    /// Send a GET request, asking the token service for a token first if
+    /// the registry answers with a `Bearer` challenge.
+    fn get(
+        &self,
+        url: &str,
+        accept: Option<&str>,
+    ) -> Result<reqwest::blocking::Response, PkgError> {
+        let token = self
+            .token
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        let response = self.send(url, accept, token.as_ref().or(self.auth.as_ref()))?;
+        if response.status() != reqwest::StatusCode::UNAUTHORIZED || token.is_some() {
+            return Ok(response.error_for_status()?);
+        }
+        let challenge = match response
+            .headers()
+            .get(reqwest::header::WWW_AUTHENTICATE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_bearer_challenge)
+        {
+            Some(challenge) => challenge,
+            None => return Ok(response.error_for_status()?),
+        };
+
+        let token = Auth::Bearer(self.fetch_token(&challenge)?);
+        *self
+            .token
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(token.clone());
+        Ok(self.send(url, accept, Some(&token))?.error_for_status()?)
+    }
+
+    /// Send a GET request with the given credentials.
+    fn send(
+        &self,
+        url: &str,
+        accept: Option<&str>,
+        auth: Option<&Auth>,
+    ) -> Result<reqwest::blocking::Response, PkgError> {
+        let mut request = crate::http::with_auth(crate::http::client().get(url), auth);
+        if let Some(accept) = accept {
+            request = request.header(reqwest::header::ACCEPT, accept);
+        }
+        crate::http::send(request)
+    }
+
+    /// Ask the token service named by a challenge for a token to pull from
+    /// the repository, with the credentials if there are any.
+    fn fetch_token(&self, challenge: &HashMap<String, String>) -> Result<String, PkgError> {
+        let realm = challenge.get("realm").ok_or_else(|| {
+            PkgError::InvalidRepoData(format!("no token realm for {}", self.registry))
+        })?;
+        let mut url = reqwest::Url::parse(realm)
+            .map_err(|e| PkgError::InvalidRepoData(format!("bad token realm {}: {}", realm, e)))?;
+        let scope = format!("repository:{}:pull", self.repository);
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(service) = challenge.get("service") {
+                let _ = query.append_pair("service", service);
+            }
+            let _ = query.append_pair(
+                "scope",
+                challenge.get("scope").map(String::as_str).unwrap_or(&scope),
+            );
+        }
+
+        // Only a user name and password are exchanged for a token; other
+        // credentials are for the registry itself.
+        let auth = match &self.auth {
+            Some(auth @ Auth::Basic { .. }) => Some(auth),
+            _ => None,
+        };
+        let response = crate::http::get_with_auth(url.as_str(), auth)?;
+        let token: TokenResponse = serde_json::from_reader(response)?;
+        token
+            .token
+            .or(token.access_token)
+            .ok_or_else(|| PkgError::InvalidRepoData(format!("no token from {}", realm)))
+    }
+}
+
+/// Parse the parameters of a `WWW-Authenticate: Bearer` challenge, such as
+/// `Bearer realm="https://ghcr.io/token",service="ghcr.io"`.
+fn parse_bearer_challenge(header: &str) -> Option<HashMap<String, String>> {
+    let (scheme, mut rest) = header.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+
+    let mut params = HashMap::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        let (name, value) = match rest.split_once('=') {
+            Some(param) => param,
+            None => return Some(params),
+        };
+        // Values may be quoted, and quoted values may hold commas.
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => {
+                let end = value.find(',').unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        let _ = params.insert(name.trim().to_ascii_lowercase(), value.to_owned());
+        rest = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference() {
+        let reference = OciReference::parse("ghcr.io/org/hello:1.0").unwrap();
+        assert_eq!(reference.registry, "ghcr.io");
+        assert_eq!(reference.repository, "org/hello");
+        assert_eq!(reference.reference, "1.0");
+        assert_eq!(reference.to_string(), "ghcr.io/org/hello:1.0");
+
+        let digest = format!("sha256:{}", "a".repeat(64));
+        let reference =
+            OciReference::parse(&format!("oci://localhost:5000/hello@{}", digest)).unwrap();
+        assert_eq!(reference.registry, "localhost:5000");
+        assert_eq!(reference.repository, "hello");
+        assert_eq!(reference.reference, digest);
+        assert_eq!(
+            reference.to_string(),
+            format!("localhost:5000/hello@{}", digest)
+        );
+
+        let reference = OciReference::parse("localhost:5000/org/hello").unwrap();
+        assert_eq!(reference.repository, "org/hello");
+        assert_eq!(reference.reference, "latest");
+
+        assert!(OciReference::parse("hello:1.0").is_err());
+        assert!(OciReference::parse("org/hello:1.0").is_err());
+        assert!(OciReference::parse("ghcr.io/").is_err());
+    }
+
+    #[test]
+    fn test_manifest() {
+        let layer = |title: &str| {
+            format!(
+                r#"{{"mediaType": "application/octet-stream", "digest": "sha256:{}", "size": 3,
+                    "annotations": {{"org.opencontainers.image.title": "{}"}}}}"#,
+                "a".repeat(64),
+                title
+            )
+        };
+        let manifest = |layers: &[String]| {
+            let json = format!(
+                r#"{{"schemaVersion": 2, "mediaType": "{}", "layers": [{}]}}"#,
+                OCI_MANIFEST,
+                layers.join(",")
+            );
+            OciManifest::parse(json.as_bytes()).unwrap()
+        };
+
+        let single = manifest(&[layer("README")]);
+        assert!(single.digest.starts_with("sha256:"));
+        assert_eq!(single.media_type.as_deref(), Some(OCI_MANIFEST));
+        assert_eq!(single.package_layer().unwrap().title(), Some("README"));
+
+        #[cfg(feature = "rpm")]
+        {
+            let layers = manifest(&[layer("README"), layer("hello-1.0-1.noarch.rpm")]);
+            assert_eq!(
+                layers.package_layer().unwrap().title(),
+                Some("hello-1.0-1.noarch.rpm")
+            );
+        }
+        assert!(manifest(&[layer("README"), layer("LICENSE")])
+            .package_layer()
+            .is_err());
+        assert!(manifest(&[]).package_layer().is_err());
+    }
+
+    #[test]
+    fn test_parse_digest() {
+        let hex = "a".repeat(64);
+        assert_eq!(
+            parse_digest(&format!("sha256:{}", hex)).unwrap(),
+            Digest::Sha256(hex)
+        );
+        assert!(parse_digest("sha256:abc").is_err());
+        assert!(parse_digest(&format!("md5:{}", "a".repeat(32))).is_err());
+    }
+
+    #[test]
+    fn test_bearer_challenge() {
+        let challenge = parse_bearer_challenge(
+            r#"Bearer realm="https://ghcr.io/token",service="ghcr.io",scope="repository:org/hello:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(challenge["realm"], "https://ghcr.io/token");
+        assert_eq!(challenge["service"], "ghcr.io");
+        assert_eq!(challenge["scope"], "repository:org/hello:pull,push");
+
+        assert!(parse_bearer_challenge(r#"Basic realm="registry""#).is_none());
+    }
+
+    #[cfg(all(feature = "testing", feature = "rpm"))]
+    #[test]
+    fn test_package() {
+        let rpm = crate::testing::RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+        let blob = format!("sha256:{:x}", Sha256::digest(&rpm));
+        let manifest = format!(
+            r#"{{"schemaVersion": 2, "layers": [{{"mediaType": "application/x-rpm",
+                "digest": "{}", "size": {}}}]}}"#,
+            blob,
+            rpm.len()
+        );
+        let base = "https://registry.example.com/v2/org/hello";
+        let challenge =
+            r#"Bearer realm="https://auth.example.com/token",service="registry.example.com""#;
+        let mock = crate::testing::MockTransport::new()
+            .with_response(
+                &format!("{}/manifests/1.0", base),
+                crate::testing::MockResponse::new(Vec::new())
+                    .with_status(401)
+                    .with_header("WWW-Authenticate", challenge),
+            )
+            .with_body(&format!("{}/manifests/1.0", base), manifest.clone())
+            .with_body(
+                "https://auth.example.com/token?service=registry.example.com&scope=repository%3Aorg%2Fhello%3Apull",
+                r#"{"token": "secret"}"#,
+            )
+            .with_body(&format!("{}/blobs/{}", base, blob), rpm.clone())
+            .install();
+
+        let repository = OciRepository::new("registry.example.com", "org/hello", None);
+        let package = repository.package("1.0").unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(mock.requests().len(), 4);
+
+        // A manifest fetched by digest must match it.
+        let digest = format!("sha256:{:x}", Sha256::digest(manifest.as_bytes()));
+        drop(mock);
+        let _mock = crate::testing::MockTransport::new()
+            .with_body(&format!("{}/manifests/{}", base, digest), manifest.clone())
+            .with_body(&format!("{}/manifests/{}", base, blob), manifest)
+            .with_body(&format!("{}/blobs/{}", base, blob), &rpm[..rpm.len() - 1])
+            .install();
+        assert_eq!(repository.manifest(&digest).unwrap().digest, digest);
+        assert!(matches!(
+            repository.manifest(&blob),
+            Err(PkgError::ChecksumMismatch { .. })
+        ));
+
+        // So must the layer.
+        assert!(matches!(
+            repository.package(&digest),
+            Err(PkgError::SizeMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,308 @@
+//! Support for looking up known vulnerabilities in the
+//! [OSV](https://osv.dev) database.
+//!
+//! OSV identifies packages by ecosystem, name and version. These are derived
+//! from the package's purl, so types other than remote packages, such as
+//! PyPI or crates.io releases, can also be queried with [`OsvPackage::from_purl`].
+use serde::Deserialize;
+
+use crate::{PkgError, RemotePackage};
+
+/// The public OSV API.
+pub const OSV_API: &str = "https://api.osv.dev/v1";
+
+/// A package version to look up in OSV.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsvPackage {
+    /// The OSV ecosystem, e.g. `Debian:12` or `PyPI`.
+    pub ecosystem: String,
+
+    /// The package name within the ecosystem.
+    pub name: String,
+
+    /// The package version, in the ecosystem's format.
+    pub version: String,
+}
+
+/// A vulnerability affecting a package, as listed by OSV.
+///
+/// Only the summary fields are kept; fetch `/vulns/<id>` for the full
+/// record.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct OsvVulnerability {
+    /// The OSV identifier, e.g. `DSA-5000-1` or `GHSA-xxxx-xxxx-xxxx`.
+    pub id: String,
+
+    /// A one line summary, if given.
+    #[serde(default)]
+    pub summary: Option<String>,
+
+    /// Other identifiers of the same vulnerability, e.g. CVE IDs.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    /// When the record was last modified.
+    #[serde(default)]
+    pub modified: Option<String>,
+}
+
+/// A page of results from the query API.
+#[cfg(any(feature = "http-blocking", test))]
+#[derive(Debug, Deserialize)]
+struct QueryResponse {
+    #[serde(default)]
+    vulns: Vec<OsvVulnerability>,
+
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+impl OsvPackage {
+    /// Describe a remote package for OSV.
+    ///
+    /// The distribution a package was built for isn't recorded in it, so
+    /// the ecosystem, e.g. `Debian:12`, `Ubuntu:22.04` or `AlmaLinux:9`, must
+    /// be given unless the package is a Debian package, which defaults to
+    /// `Debian`.
+    pub fn from_package<P: RemotePackage + ?Sized>(
+        package: &P,
+        ecosystem: Option<&str>,
+    ) -> Result<Self, PkgError> {
+        let purl = crate::sbom::purl(package)?;
+        let parsed = parse_purl(&purl).ok_or_else(|| PkgError::UnknownEcosystem(purl.clone()))?;
+        let ecosystem = ecosystem
+            .or_else(|| self::ecosystem(&parsed.kind, parsed.namespace.as_deref()))
+            .ok_or_else(|| PkgError::UnknownEcosystem(purl.clone()))?;
+
+        Ok(Self {
+            ecosystem: ecosystem.to_owned(),
+            name: parsed.name,
+            version: parsed.version.unwrap_or_default(),
+        })
+    }
+
+    /// Describe the package identified by a versioned purl, e.g.
+    /// `pkg:deb/debian/curl@7.88.1-10` or `pkg:pypi/requests@2.31.0`.
+    ///
+    /// Fails with [`PkgError::UnknownEcosystem`] if the purl has no version
+    /// or doesn't map to an OSV ecosystem.
+    pub fn from_purl(purl: &str) -> Result<Self, PkgError> {
+        let unknown = || PkgError::UnknownEcosystem(purl.to_owned());
+        let parsed = parse_purl(purl).ok_or_else(unknown)?;
+        let version = parsed.version.clone().ok_or_else(unknown)?;
+        let ecosystem = ecosystem(&parsed.kind, parsed.namespace.as_deref()).ok_or_else(unknown)?;
+
+        // npm scopes and Maven groups are part of the name in OSV.
+        let name = match (parsed.kind.as_str(), &parsed.namespace) {
+            ("npm", Some(scope)) => format!("{}/{}", scope, parsed.name),
+            ("maven", Some(group)) => format!("{}:{}", group, parsed.name),
+            _ => parsed.name,
+        };
+
+        Ok(Self {
+            ecosystem: ecosystem.to_owned(),
+            name,
+            version,
+        })
+    }
+
+    /// The body of a query for this package.
+    #[cfg(any(feature = "http-blocking", test))]
+    fn query(&self, page_token: Option<&str>) -> serde_json::Value {
+        let mut query = serde_json::json!({
+            "version": self.version,
+            "package": {
+                "name": self.name,
+                "ecosystem": self.ecosystem,
+            },
+        });
+        if let Some(token) = page_token {
+            query["page_token"] = serde_json::Value::from(token);
+        }
+        query
+    }
+
+    /// Get the vulnerabilities affecting this package version from an OSV
+    /// API such as [`OSV_API`].
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn vulnerabilities(&self, api_url: &str) -> Result<Vec<OsvVulnerability>, PkgError> {
+        let url = format!("{}/query", api_url.trim_end_matches('/'));
+        let mut vulns = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let response = crate::http::post_json(&url, &self.query(page_token.as_deref()))?;
+            let page: QueryResponse = serde_json::from_str(&response.text()?)?;
+            vulns.extend(page.vulns);
+
+            match page.next_page_token {
+                Some(token) if !token.is_empty() => page_token = Some(token),
+                _ => return Ok(vulns),
+            }
+        }
+    }
+
+    /// Get the IDs of the vulnerabilities affecting this package version from
+    /// the public OSV API; see [`OsvPackage::vulnerabilities`].
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn vulnerability_ids(&self) -> Result<Vec<String>, PkgError> {
+        Ok(self
+            .vulnerabilities(OSV_API)?
+            .into_iter()
+            .map(|vuln| vuln.id)
+            .collect())
+    }
+}
+
+/// The parts of a purl needed to identify a package.
+#[derive(Debug, PartialEq, Eq)]
+struct Purl {
+    kind: String,
+    namespace: Option<String>,
+    name: String,
+    version: Option<String>,
+}
+
+/// Parse a purl, ignoring any qualifiers and subpath.
+fn parse_purl(purl: &str) -> Option<Purl> {
+    let rest = purl.strip_prefix("pkg:")?;
+    let rest = rest.split(['?', '#']).next()?;
+    let (path, version) = match rest.rsplit_once('@') {
+        Some((path, version)) => (path, Some(percent_decode(version))),
+        None => (rest, None),
+    };
+
+    let (kind, path) = path.trim_matches('/').split_once('/')?;
+    let (namespace, name) = match path.rsplit_once('/') {
+        Some((namespace, name)) => (Some(percent_decode(namespace)), name),
+        None => (None, path),
+    };
+
+    Some(Purl {
+        kind: kind.to_ascii_lowercase(),
+        namespace,
+        name: percent_decode(name),
+        version,
+    })
+}
+
+/// The OSV ecosystem of a purl type and namespace. Debian packages without
+/// a namespace are assumed to be from Debian itself.
+fn ecosystem(kind: &str, namespace: Option<&str>) -> Option<&'static str> {
+    let namespace = namespace.map(str::to_ascii_lowercase);
+    Some(match (kind, namespace.as_deref()) {
+        ("deb", Some("debian")) | ("deb", None) => "Debian",
+        ("deb", Some("ubuntu")) => "Ubuntu",
+        ("rpm", Some("almalinux")) => "AlmaLinux",
+        ("rpm", Some("rocky-linux")) | ("rpm", Some("rocky")) => "Rocky Linux",
+        ("rpm", Some("redhat")) => "Red Hat",
+        ("rpm", Some("opensuse")) => "openSUSE",
+        ("rpm", Some("suse")) => "SUSE",
+        ("rpm", Some("mageia")) => "Mageia",
+        ("apk", Some("alpine")) => "Alpine",
+        ("pypi", _) => "PyPI",
+        ("npm", _) => "npm",
+        ("cargo", _) => "crates.io",
+        ("nuget", _) => "NuGet",
+        ("gem", _) => "RubyGems",
+        ("golang", _) => "Go",
+        ("maven", _) => "Maven",
+        _ => return None,
+    })
+}
+
+/// Decode `%XX` escapes in a purl component.
+fn percent_decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_purl() {
+        let osv =
+            OsvPackage::from_purl("pkg:deb/debian/curl@7.88.1-10%2Bdeb12u5?arch=amd64").unwrap();
+        assert_eq!(
+            osv,
+            OsvPackage {
+                ecosystem: "Debian".to_string(),
+                name: "curl".to_string(),
+                version: "7.88.1-10+deb12u5".to_string(),
+            }
+        );
+
+        let osv = OsvPackage::from_purl("pkg:npm/%40babel/core@7.0.0").unwrap();
+        assert_eq!(osv.ecosystem, "npm");
+        assert_eq!(osv.name, "@babel/core");
+
+        assert!(matches!(
+            OsvPackage::from_purl("pkg:rpm/curl@8.0-1"),
+            Err(PkgError::UnknownEcosystem(_))
+        ));
+        assert!(OsvPackage::from_purl("pkg:pypi/requests").is_err());
+        assert!(OsvPackage::from_purl("not a purl").is_err());
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_from_package() {
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+        let package = crate::rpm::RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+
+        assert!(OsvPackage::from_package(&package, None).is_err());
+        let osv = OsvPackage::from_package(&package, Some("AlmaLinux:9")).unwrap();
+        assert_eq!(osv.ecosystem, "AlmaLinux:9");
+        assert_eq!(osv.name, "hello");
+        assert_eq!(osv.version, "1.0-1");
+    }
+
+    #[test]
+    fn test_query() {
+        let osv = OsvPackage::from_purl("pkg:cargo/time@0.1.0").unwrap();
+        assert_eq!(
+            osv.query(Some("abc")),
+            serde_json::json!({
+                "version": "0.1.0",
+                "package": {"name": "time", "ecosystem": "crates.io"},
+                "page_token": "abc",
+            })
+        );
+
+        let page: QueryResponse = serde_json::from_str(
+            r#"{"vulns": [{"id": "RUSTSEC-2020-0071", "aliases": ["CVE-2020-26235"],
+                 "modified": "2023-06-13T13:10:24Z", "affected": []}]}"#,
+        )
+        .unwrap();
+        assert_eq!(page.vulns[0].id, "RUSTSEC-2020-0071");
+        assert_eq!(page.vulns[0].aliases, vec!["CVE-2020-26235"]);
+        assert!(page.next_page_token.is_none());
+    }
+}
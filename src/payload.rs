@@ -0,0 +1,1720 @@
+//! Support for reading the files inside a package.
+//!
+//! The payload of a package, the `data.tar.*` of a Debian package or the
+//! cpio archive of an RPM, comes after its metadata, so these functions read
+//! the package as a stream, entry by entry. They stop reading as soon as they
+//! have what they need.
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub use crate::compression::Compression;
+use crate::PkgError;
+
+/// A license file found in a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LicenseFile {
+    /// The installed path, e.g. `/usr/share/doc/hello/copyright`.
+    pub path: String,
+
+    /// The contents of the file, decoded as UTF-8 with any invalid
+    /// sequences replaced.
+    pub contents: String,
+
+    /// Whether the contents were cut short by the size limit.
+    pub truncated: bool,
+}
+
+/// Whether a file name looks like a license file: `LICENSE*`, `LICENCE*`,
+/// `COPYING*` or Debian's `copyright`.
+pub fn is_license_file(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let upper = name.to_ascii_uppercase();
+    ["LICENSE", "LICENCE", "COPYING"]
+        .iter()
+        .any(|prefix| upper.starts_with(prefix))
+        || name == "copyright"
+}
+
+/// Read every license file in a package, keeping at most `max_size` bytes
+/// of each.
+///
+/// This reads the whole package, as license files can be anywhere in the
+/// payload.
+pub fn scan_licenses<R: Read>(reader: R, max_size: u64) -> Result<Vec<LicenseFile>, PkgError> {
+    let mut licenses = Vec::new();
+    walk(reader, |path, entry| {
+        if is_license_file(path) {
+            let mut contents = Vec::new();
+            let _ = entry.take(max_size).read_to_end(&mut contents)?;
+            // Check for anything past the limit.
+            let truncated = entry.read(&mut [0])? != 0;
+            licenses.push(LicenseFile {
+                path: path.to_owned(),
+                contents: String::from_utf8_lossy(&contents).into_owned(),
+                truncated,
+            });
+        }
+        Ok(true)
+    })?;
+    Ok(licenses)
+}
+
+/// Read every license file in a package from a URL; see [`scan_licenses`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn scan_licenses_from_url(url: &str, max_size: u64) -> Result<Vec<LicenseFile>, PkgError> {
+    scan_licenses(crate::http::get_package(url)?, max_size)
+}
+
+/// Read a single file out of a package, by its installed path, e.g.
+/// `/usr/share/doc/hello/changelog.gz`. The leading `/` or `./` may be left
+/// out.
+///
+/// Reading stops as soon as the file has been read, so files near the start
+/// of the payload are quick to get. Fails with [`PkgError::FileNotFound`] if
+/// the package has no such file.
+pub fn extract_file<R: Read>(reader: R, path: &str) -> Result<Vec<u8>, PkgError> {
+    let mut contents = Vec::new();
+    let _ = extract_file_to(reader, path, &mut contents)?;
+    Ok(contents)
+}
+
+/// Write a single file out of a package; see [`extract_file`]. Returns the
+/// number of bytes written.
+pub fn extract_file_to<R: Read, W: Write>(
+    reader: R,
+    path: &str,
+    mut writer: W,
+) -> Result<u64, PkgError> {
+    let wanted = installed_path(path);
+    let mut written = None;
+    walk(reader, |path, entry| {
+        if path != wanted {
+            return Ok(true);
+        }
+        written = Some(io::copy(entry, &mut writer)?);
+        Ok(false)
+    })?;
+    written.ok_or(PkgError::FileNotFound(wanted))
+}
+
+/// Read a single file out of a package from a URL; see [`extract_file`].
+/// The download stops once the file has been read.
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn extract_file_from_url(url: &str, path: &str) -> Result<Vec<u8>, PkgError> {
+    extract_file(crate::http::get_package(url)?, path)
+}
+
+/// What to do with symbolic links when extracting a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Create links whose target stays inside the extraction directory,
+    /// treating absolute targets as relative to it, and fail with
+    /// [`PkgError::UnsafePath`] on any others.
+    Contained,
+
+    /// Create every link as it is, even if it points outside the extraction
+    /// directory. Nothing is ever written through a link either way.
+    Any,
+
+    /// Don't create links.
+    Skip,
+}
+
+/// Options for [`extract_to`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractOptions {
+    /// Whether to apply the permission bits of each entry. Setuid, setgid
+    /// and sticky bits are never applied.
+    pub permissions: bool,
+
+    /// What to do with symbolic links.
+    pub symlinks: SymlinkPolicy,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            permissions: true,
+            symlinks: SymlinkPolicy::Contained,
+        }
+    }
+}
+
+/// Unpack the payload of a package into a directory, like `dpkg-deb -x` or
+/// `rpm2cpio | cpio -id`. Returns the number of entries written.
+///
+/// Paths are made relative to `dir`, and entries with `..` components, or
+/// that would be written through a symbolic link, are rejected with
+/// [`PkgError::UnsafePath`]. Existing files are replaced. Device nodes and
+/// other special files are skipped, as are symbolic links on platforms
+/// without them.
+pub fn extract_to<R: Read, P: AsRef<Path>>(
+    reader: R,
+    dir: P,
+    options: &ExtractOptions,
+) -> Result<u64, PkgError> {
+    let root = dir.as_ref();
+    fs::create_dir_all(root)?;
+
+    let mut written = 0;
+    let mut entries = Entries::new(reader)?;
+    while let Some(mut entry) = entries.next_entry()? {
+        let components = path_components(entry.path())?;
+        let target = root.join(components.iter().collect::<PathBuf>());
+        if let Some(parent) = target.parent() {
+            check_no_symlinks(root, parent)?;
+            fs::create_dir_all(parent)?;
+        }
+
+        match entry.kind().clone() {
+            EntryKind::Directory => {
+                if components.is_empty() {
+                    continue;
+                }
+                // An existing link would be followed when creating the
+                // directory and setting its permissions.
+                check_no_symlinks(root, &target)?;
+                fs::create_dir_all(&target)?;
+            }
+            EntryKind::File => {
+                remove_existing(&target)?;
+                let _ = io::copy(&mut entry, &mut fs::File::create(&target)?)?;
+            }
+            EntryKind::HardLink(link) => {
+                let source = root.join(path_components(&link)?.iter().collect::<PathBuf>());
+                check_no_symlinks(root, &source)?;
+                remove_existing(&target)?;
+                fs::hard_link(source, &target)?;
+            }
+            EntryKind::Symlink(link) => {
+                let parent = &components[..components.len().saturating_sub(1)];
+                if options.symlinks == SymlinkPolicy::Skip || !cfg!(unix) || components.is_empty() {
+                    continue;
+                }
+                if options.symlinks == SymlinkPolicy::Contained && !link_is_contained(parent, &link)
+                {
+                    return Err(PkgError::UnsafePath(format!(
+                        "{} -> {}",
+                        entry.path(),
+                        link
+                    )));
+                }
+                remove_existing(&target)?;
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(&link, &target)?;
+                written += 1;
+                continue;
+            }
+            EntryKind::Other => continue,
+        }
+
+        #[cfg(unix)]
+        if options.permissions {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&target, fs::Permissions::from_mode(entry.mode() & 0o777))?;
+        }
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Unpack the payload of a package from a URL into a directory; see
+/// [`extract_to`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn extract_to_from_url<P: AsRef<Path>>(
+    url: &str,
+    dir: P,
+    options: &ExtractOptions,
+) -> Result<u64, PkgError> {
+    extract_to(crate::http::get_package(url)?, dir, options)
+}
+
+/// Split an installed path into its components, rejecting `..`.
+fn path_components(path: &str) -> Result<Vec<&str>, PkgError> {
+    let components: Vec<&str> = path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    if components.contains(&"..") {
+        return Err(PkgError::UnsafePath(path.to_owned()));
+    }
+    Ok(components)
+}
+
+/// Check that no directory between `root` and `path` is a symbolic link,
+/// so that nothing is written outside of `root` through one.
+fn check_no_symlinks(root: &Path, path: &Path) -> Result<(), PkgError> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let mut current = root.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        match fs::symlink_metadata(&current) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(PkgError::UnsafePath(relative.display().to_string()));
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Remove a file or symbolic link about to be replaced, without following a
+/// link.
+fn remove_existing(path: &Path) -> Result<(), PkgError> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => Ok(fs::remove_file(path)?),
+        _ => Ok(()),
+    }
+}
+
+/// Whether a symbolic link in the directory `parent` with target `link`
+/// resolves inside the extraction directory. Absolute targets are relative to
+/// the extraction directory.
+fn link_is_contained(parent: &[&str], link: &str) -> bool {
+    let mut depth = if link.starts_with('/') {
+        0
+    } else {
+        parent.len()
+    };
+    for component in link.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if depth == 0 => return false,
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+    }
+    true
+}
+
+/// The type of an entry in a package payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A regular file.
+    File,
+
+    /// A directory.
+    Directory,
+
+    /// A symbolic link to the given target.
+    Symlink(String),
+
+    /// A hard link to the given installed path.
+    HardLink(String),
+
+    /// Anything else, such as a device node or FIFO.
+    Other,
+}
+
+/// An entry in a package payload, which can be read to get its contents.
+///
+/// Anything that isn't read is skipped when the next entry is asked for.
+pub struct Entry<'e> {
+    path: String,
+    header: TarHeader,
+    reader: &'e mut dyn Read,
+    remaining: &'e mut u64,
+}
+
+impl<'e> Entry<'e> {
+    /// The installed path, e.g. `/usr/bin/hello`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The size of the contents in bytes. This is zero for anything but
+    /// regular files.
+    pub fn size(&self) -> u64 {
+        self.header.size
+    }
+
+    /// The permission bits, e.g. `0o755`.
+    pub fn mode(&self) -> u32 {
+        self.header.mode & 0o7777
+    }
+
+    /// The type of the entry.
+    pub fn kind(&self) -> &EntryKind {
+        &self.header.kind
+    }
+
+    /// The numeric id of the owning user.
+    pub fn uid(&self) -> u32 {
+        self.header.uid
+    }
+
+    /// The numeric id of the owning group.
+    pub fn gid(&self) -> u32 {
+        self.header.gid
+    }
+
+    /// The name of the owning user, if the archive records it. RPM payloads
+    /// don't; see [`RpmRemotePackage::files`](crate::rpm::RpmRemotePackage::files)
+    /// instead.
+    pub fn user(&self) -> Option<&str> {
+        self.header.user.as_deref()
+    }
+
+    /// The name of the owning group, if the archive records it.
+    pub fn group(&self) -> Option<&str> {
+        self.header.group.as_deref()
+    }
+
+    /// The modification time, in seconds since the Unix epoch.
+    pub fn mtime(&self) -> u64 {
+        self.header.mtime
+    }
+}
+
+impl<'e> Read for Entry<'e> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let max = buf.len().min(*self.remaining as usize);
+        if max == 0 {
+            return Ok(0);
+        }
+        let read = self.reader.read(&mut buf[..max])?;
+        *self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// The archive format of a payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Tar,
+    Cpio,
+}
+
+/// A streaming iterator over the entries in a package payload.
+///
+/// Each [`Entry`] borrows the underlying reader, so it must be dropped
+/// before the next one is asked for:
+///
+/// ```no_run
+/// # fn main() -> Result<(), remote_package::PkgError> {
+/// # let reader = std::io::empty();
+/// let mut entries = remote_package::payload::entries(reader)?;
+/// while let Some(entry) = entries.next_entry()? {
+///     println!("{} {:o} {}", entry.path(), entry.mode(), entry.size());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Entries<'a> {
+    format: Format,
+    reader: Box<dyn Read + 'a>,
+
+    /// The bytes of the current entry that haven't been read.
+    remaining: u64,
+
+    /// The padding after the current entry.
+    padding: u64,
+
+    done: bool,
+}
+
+/// Iterate over the entries in the payload of a package; see [`Entries`].
+///
+/// Only the package metadata is read up front, and the payload is read as
+/// the entries are.
+pub fn entries<'a, R: Read + 'a>(reader: R) -> Result<Entries<'a>, PkgError> {
+    Entries::new(reader)
+}
+
+impl<'a> Entries<'a> {
+    /// Read up to the start of the payload of a package.
+    pub fn new<R: Read + 'a>(reader: R) -> Result<Self, PkgError> {
+        let (infer_buf, reader) = crate::peek(reader)?;
+
+        #[cfg(feature = "debian")]
+        if infer::archive::is_deb(&infer_buf) {
+            return Ok(Self::with_format(Format::Tar, deb_data(reader)?));
+        }
+
+        #[cfg(feature = "rpm")]
+        if infer::archive::is_rpm(&infer_buf) {
+            let mut reader = reader;
+            let _ = crate::rpm_header::RpmHeaders::read(
+                &mut reader,
+                &crate::limits::Limits::default(),
+            )?;
+            let payload = Compression::detect_decoder(reader)?;
+            return Ok(Self::with_format(Format::Cpio, payload));
+        }
+
+        let _ = reader;
+        Err(PkgError::UnknownPackageType(
+            infer::get(&infer_buf)
+                .map(|t| t.extension())
+                .unwrap_or("unknown")
+                .to_owned(),
+        ))
+    }
+
+    fn with_format(format: Format, reader: Box<dyn Read + 'a>) -> Self {
+        Self {
+            format,
+            reader,
+            remaining: 0,
+            padding: 0,
+            done: false,
+        }
+    }
+
+    /// Get the next entry, or `None` at the end of the payload.
+    pub fn next_entry(&mut self) -> Result<Option<Entry<'_>>, PkgError> {
+        // Skip whatever of the last entry wasn't read.
+        skip(&mut self.reader, self.remaining + self.padding)?;
+        self.remaining = 0;
+        self.padding = 0;
+        if self.done {
+            return Ok(None);
+        }
+
+        let header = match self.format {
+            Format::Tar => TarHeader::read(&mut self.reader)?,
+            Format::Cpio => CpioHeader::read(&mut self.reader)?.map(CpioHeader::into_tar),
+        };
+        let mut header = match header {
+            Some(header) => header,
+            None => {
+                self.done = true;
+                return Ok(None);
+            }
+        };
+
+        // cpio stores the target of a symlink as its contents.
+        if self.format == Format::Cpio && header.kind == EntryKind::Symlink(String::new()) {
+            let mut target = Vec::new();
+            let _ = (&mut self.reader)
+                .take(header.size)
+                .read_to_end(&mut target)?;
+            skip(&mut self.reader, header.size - target.len() as u64)?;
+            header.kind = EntryKind::Symlink(String::from_utf8_lossy(&target).into_owned());
+            self.padding = padding(header.size, self.format);
+            header.size = 0;
+        } else {
+            self.remaining = header.size;
+            self.padding = padding(header.size, self.format);
+        }
+
+        Ok(Some(Entry {
+            path: installed_path(&header.name),
+            header,
+            reader: &mut self.reader,
+            remaining: &mut self.remaining,
+        }))
+    }
+}
+
+/// The metadata of a file installed by a package.
+///
+/// What's known depends on where it came from: RPM headers record owners by
+/// name only, while the payload of a package records numeric ids too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEntry {
+    /// The installed path, e.g. `/usr/bin/hello`.
+    pub path: String,
+
+    /// The full mode, including the file type bits, e.g. `0o100755`.
+    pub mode: u32,
+
+    /// The name of the owning user, if known.
+    pub user: Option<String>,
+
+    /// The name of the owning group, if known.
+    pub group: Option<String>,
+
+    /// The numeric id of the owning user, if known.
+    pub uid: Option<u32>,
+
+    /// The numeric id of the owning group, if known.
+    pub gid: Option<u32>,
+
+    /// The size in bytes. This is zero for anything but regular files.
+    pub size: u64,
+
+    /// The modification time, in seconds since the Unix epoch.
+    pub mtime: u64,
+
+    /// The hex digest of the contents of a regular file and its algorithm,
+    /// e.g. `sha256`, if known.
+    pub digest: Option<(String, String)>,
+
+    /// The target of a symbolic link, as stored, e.g. `../lib/hello.so.1`.
+    pub link_target: Option<String>,
+
+    /// The installed paths of the other files that are hard links to the
+    /// same contents, in package order.
+    pub hardlinks: Vec<String>,
+}
+
+/// Get the metadata of every entry in the payload of a package, reading it
+/// as a stream.
+///
+/// The SHA-256 of each regular file is computed when the `checksum` feature
+/// is enabled. For RPM packages, the header has most of this without reading
+/// the payload; see [`RpmRemotePackage::files`](crate::rpm::RpmRemotePackage::files).
+pub fn files<R: Read>(reader: R) -> Result<Vec<FileEntry>, PkgError> {
+    let mut files: Vec<FileEntry> = Vec::new();
+    // Hard linked entries, by what they link to: the target's path for tar,
+    // or the inode for cpio.
+    let mut links: HashMap<String, Vec<usize>> = HashMap::new();
+    // The index of each path, for finding the targets of tar hard links.
+    let mut indexes: HashMap<String, usize> = HashMap::new();
+    let mut entries = Entries::new(reader)?;
+    while let Some(entry) = entries.next_entry()? {
+        let link = match (entry.kind(), entry.header.inode) {
+            (EntryKind::HardLink(target), _) => Some((target.clone(), Some(target))),
+            (_, Some(inode)) => Some((format!("inode {}", inode), None)),
+            _ => None,
+        };
+        if let Some((key, target)) = link {
+            links
+                .entry(key)
+                .or_insert_with(|| {
+                    target
+                        .and_then(|target| indexes.get(target).copied())
+                        .into_iter()
+                        .collect()
+                })
+                .push(files.len());
+        }
+
+        let file_type = match entry.kind() {
+            EntryKind::File | EntryKind::HardLink(_) => S_IFREG,
+            EntryKind::Directory => S_IFDIR,
+            EntryKind::Symlink(_) => S_IFLNK,
+            EntryKind::Other => entry.header.mode & S_IFMT,
+        };
+        #[cfg_attr(not(feature = "checksum"), allow(unused_mut))]
+        let mut file = FileEntry {
+            path: entry.path().to_owned(),
+            mode: file_type | entry.mode(),
+            user: entry.user().map(str::to_owned),
+            group: entry.group().map(str::to_owned),
+            uid: Some(entry.uid()),
+            gid: Some(entry.gid()),
+            size: entry.size(),
+            mtime: entry.mtime(),
+            digest: None,
+            link_target: match entry.kind() {
+                EntryKind::Symlink(target) => Some(target.clone()),
+                _ => None,
+            },
+            hardlinks: Vec::new(),
+        };
+
+        #[cfg(feature = "checksum")]
+        if *entry.kind() == EntryKind::File {
+            use crate::checksum::{Algorithm, DigestsReader};
+
+            let mut hasher = DigestsReader::new(entry, &[Algorithm::Sha256]);
+            let _ = io::copy(&mut hasher, &mut io::sink())?;
+            file.digest = hasher
+                .finish()?
+                .sha256()
+                .map(|digest| (Algorithm::Sha256.name().to_owned(), digest.to_owned()));
+        }
+        let _ = indexes.insert(file.path.clone(), files.len());
+        files.push(file);
+    }
+
+    link_files(&mut files, links.into_values());
+    Ok(files)
+}
+
+/// Record the hard links between files, given groups of indexes into
+/// `files` that share contents.
+///
+/// Archives only store the contents once, so the other links in a group get
+/// the size and digest of the largest.
+pub(crate) fn link_files<I>(files: &mut [FileEntry], groups: I)
+where
+    I: IntoIterator<Item = Vec<usize>>,
+{
+    for group in groups {
+        if group.len() < 2 {
+            continue;
+        }
+        let paths: Vec<String> = group.iter().map(|&i| files[i].path.clone()).collect();
+        let contents = group.iter().copied().max_by_key(|&i| files[i].size);
+        let (size, digest) = contents
+            .map(|i| (files[i].size, files[i].digest.clone()))
+            .unwrap_or_default();
+
+        for &i in &group {
+            let file = &mut files[i];
+            file.hardlinks = paths
+                .iter()
+                .filter(|path| **path != file.path)
+                .cloned()
+                .collect();
+            file.size = size;
+            if digest.is_some() {
+                file.digest = digest.clone();
+            }
+        }
+    }
+}
+
+/// Get the metadata of every entry in the payload of a package from a URL;
+/// see [`files`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn files_from_url(url: &str) -> Result<Vec<FileEntry>, PkgError> {
+    files(crate::http::get_package(url)?)
+}
+
+/// Call `visit` with the installed path and contents of each regular file in
+/// the payload of a package, until it returns false.
+pub(crate) fn walk<R, F>(reader: R, mut visit: F) -> Result<(), PkgError>
+where
+    R: Read,
+    F: FnMut(&str, &mut dyn Read) -> Result<bool, PkgError>,
+{
+    let mut entries = Entries::new(reader)?;
+    while let Some(mut entry) = entries.next_entry()? {
+        if *entry.kind() == EntryKind::File {
+            let path = entry.path.clone();
+            if !visit(&path, &mut entry)? {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find the data archive in a Debian package, returning a reader over the
+/// decompressed tar.
+#[cfg(feature = "debian")]
+fn deb_data<'a, R: Read + 'a>(mut reader: R) -> Result<Box<dyn Read + 'a>, PkgError> {
+    read_ar_magic(&mut reader)?;
+    let size = find_ar_member(&mut reader, "data.tar")?;
+    Compression::detect_decoder(reader.take(size))
+}
+
+/// Check the magic at the start of an ar archive, such as a Debian package.
+#[cfg(feature = "debian")]
+pub(crate) fn read_ar_magic<R: Read>(reader: &mut R) -> Result<(), PkgError> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"!<arch>\n" {
+        return Err(PkgError::InvalidPackage("not an ar archive".to_string()));
+    }
+    Ok(())
+}
+
+/// Skip to the next ar member whose name starts with `prefix`, returning its
+/// size.
+#[cfg(feature = "debian")]
+pub(crate) fn find_ar_member<R: Read>(reader: &mut R, prefix: &str) -> Result<u64, PkgError> {
+    loop {
+        let mut header = [0; 60];
+        reader.read_exact(&mut header)?;
+        let name = String::from_utf8_lossy(&header[..16]);
+        let name = name.trim_end().trim_end_matches('/').to_owned();
+        let size: u64 = std::str::from_utf8(&header[48..58])
+            .ok()
+            .and_then(|size| size.trim().parse().ok())
+            .ok_or_else(|| PkgError::InvalidPackage("bad ar member size".to_string()))?;
+
+        if name.starts_with(prefix) {
+            return Ok(size);
+        }
+        // Members are padded to an even size.
+        skip(reader, size + size % 2)?;
+    }
+}
+
+/// A file in a Debian package whose contents don't match its `md5sums`
+/// entry.
+#[cfg(all(feature = "debian", feature = "checksum"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Md5sumMismatch {
+    /// The installed path, e.g. `/usr/bin/hello`.
+    pub path: String,
+
+    /// The MD5 listed in `md5sums`.
+    pub expected: String,
+
+    /// The MD5 of the file in the payload, or `None` if the file is missing.
+    pub actual: Option<String>,
+}
+
+/// Check the files in the payload of a Debian package against the package's
+/// `md5sums`, reading it as a stream. Returns the files that don't match.
+///
+/// Files that aren't listed, such as conffiles, aren't checked. Fails with
+/// [`PkgError::FileNotFound`] if the package has no `md5sums`.
+#[cfg(all(feature = "debian", feature = "checksum"))]
+pub fn verify_md5sums<R: Read>(mut reader: R) -> Result<Vec<Md5sumMismatch>, PkgError> {
+    use crate::checksum::{Algorithm, DigestsReader};
+
+    // The control archive comes first, and holds the md5sums.
+    read_ar_magic(&mut reader)?;
+    let size = find_ar_member(&mut reader, "control.tar")?;
+    let md5sums = {
+        let mut control = Compression::detect_decoder((&mut reader).take(size))?;
+        let md5sums = read_md5sums(&mut control)?;
+        let _ = io::copy(&mut control, &mut io::sink())?;
+        md5sums
+    };
+    skip(&mut reader, size % 2)?;
+    let mut expected = md5sums.ok_or_else(|| PkgError::FileNotFound("md5sums".to_string()))?;
+
+    let size = find_ar_member(&mut reader, "data.tar")?;
+    let data = Compression::detect_decoder(reader.take(size))?;
+    let mut entries = Entries::with_format(Format::Tar, data);
+    let mut mismatches = Vec::new();
+    while let Some(entry) = entries.next_entry()? {
+        if *entry.kind() != EntryKind::File {
+            continue;
+        }
+        let index = match expected.iter().position(|(path, _)| path == entry.path()) {
+            Some(index) => index,
+            None => continue,
+        };
+        let (path, md5) = expected.swap_remove(index);
+
+        let mut hasher = DigestsReader::new(entry, &[Algorithm::Md5]);
+        let _ = io::copy(&mut hasher, &mut io::sink())?;
+        let digests = hasher.finish()?;
+        let actual = digests.md5().unwrap_or_default();
+        if !actual.eq_ignore_ascii_case(&md5) {
+            mismatches.push(Md5sumMismatch {
+                path,
+                expected: md5,
+                actual: Some(actual.to_owned()),
+            });
+        }
+    }
+
+    // Anything left was listed but not shipped.
+    mismatches.extend(expected.into_iter().map(|(path, md5)| Md5sumMismatch {
+        path,
+        expected: md5,
+        actual: None,
+    }));
+    Ok(mismatches)
+}
+
+/// Find and parse `md5sums` in a control archive.
+#[cfg(all(feature = "debian", feature = "checksum"))]
+fn read_md5sums<R: Read>(control: &mut R) -> Result<Option<Vec<(String, String)>>, PkgError> {
+    let limits = crate::limits::Limits::default();
+    let guard = crate::limits::Guard::new(&limits);
+    let result = (|| {
+        let mut control = guard.reader(control);
+        while let Some(header) = TarHeader::read(&mut control)? {
+            guard.add_entries(1)?;
+            let mut contents = Vec::new();
+            let _ = (&mut control)
+                .take(header.size)
+                .read_to_end(&mut contents)?;
+            skip(&mut control, padding(header.size, Format::Tar))?;
+            if installed_path(&header.name) == "/md5sums" {
+                let text = String::from_utf8_lossy(&contents);
+                return Ok(Some(crate::debian::parse_md5sums(&text)));
+            }
+        }
+        Ok(None)
+    })();
+    guard.finish(result)
+}
+
+/// The size of a block in a tar archive.
+const TAR_BLOCK: u64 = 512;
+
+/// The fields of an archive header needed to read the entry.
+#[derive(Debug)]
+struct TarHeader {
+    name: String,
+    mode: u32,
+    size: u64,
+    kind: EntryKind,
+    uid: u32,
+    gid: u32,
+    mtime: u64,
+    user: Option<String>,
+    group: Option<String>,
+
+    /// The inode of a hard linked cpio entry. Tar archives name the target
+    /// of a hard link instead.
+    inode: Option<u32>,
+}
+
+impl TarHeader {
+    /// Read the next header, following GNU long name and pax extended
+    /// headers, and returning `None` at the end of the archive.
+    fn read<R: Read>(reader: &mut R) -> Result<Option<Self>, PkgError> {
+        let invalid = || PkgError::InvalidPackage("bad tar header".to_string());
+        let mut long_name = None;
+        let mut long_link = None;
+        let mut pax_user = None;
+        let mut pax_group = None;
+
+        loop {
+            let mut block = [0; TAR_BLOCK as usize];
+            match reader.read_exact(&mut block) {
+                Ok(()) => {}
+                // Some archives stop without the end of archive blocks.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+            if block.iter().all(|&b| b == 0) {
+                return Ok(None);
+            }
+
+            let mode = tar_number(&block[100..108]).ok_or_else(invalid)? as u32;
+            let size = tar_number(&block[124..136]).ok_or_else(invalid)?;
+            let type_flag = block[156];
+
+            // Extension headers describe the entry that follows.
+            if let b'L' | b'K' | b'x' | b'g' = type_flag {
+                let mut data = Vec::new();
+                let _ = reader.take(size).read_to_end(&mut data)?;
+                if data.len() as u64 != size {
+                    return Err(invalid());
+                }
+                skip(reader, padding(size, Format::Tar))?;
+
+                let text = || {
+                    String::from_utf8_lossy(data.split(|&b| b == 0).next().unwrap_or_default())
+                        .into_owned()
+                };
+                match type_flag {
+                    b'L' => long_name = Some(text()),
+                    b'K' => long_link = Some(text()),
+                    b'x' => {
+                        for (key, value) in pax_records(&data) {
+                            match key {
+                                "path" => long_name = Some(value.to_owned()),
+                                "linkpath" => long_link = Some(value.to_owned()),
+                                "uname" => pax_user = Some(value.to_owned()),
+                                "gname" => pax_group = Some(value.to_owned()),
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            let name = long_name.take().unwrap_or_else(|| {
+                let name = tar_string(&block[..100]);
+                // POSIX ustar headers can split long names.
+                if &block[257..263] == b"ustar\0" && block[345] != 0 {
+                    format!("{}/{}", tar_string(&block[345..500]), name)
+                } else {
+                    name
+                }
+            });
+            let link = || {
+                long_link
+                    .clone()
+                    .unwrap_or_else(|| tar_string(&block[157..257]))
+            };
+            let kind = match type_flag {
+                b'0' | 0 | b'7' => EntryKind::File,
+                b'1' => EntryKind::HardLink(installed_path(&link())),
+                b'2' => EntryKind::Symlink(link()),
+                b'5' => EntryKind::Directory,
+                _ => EntryKind::Other,
+            };
+            // Links, directories, devices and FIFOs have no data whatever
+            // their size says, but other types, including unknown ones, do.
+            let size = match type_flag {
+                b'1'..=b'6' => 0,
+                _ => size,
+            };
+
+            // Both ustar and GNU headers have owner names.
+            let owner = |pax: Option<String>, field: &[u8]| {
+                pax.or_else(|| Some(tar_string(field)))
+                    .filter(|name| block[257..262] == *b"ustar" && !name.is_empty())
+            };
+            return Ok(Some(Self {
+                name,
+                mode,
+                size,
+                kind,
+                uid: tar_number(&block[108..116]).ok_or_else(invalid)? as u32,
+                gid: tar_number(&block[116..124]).ok_or_else(invalid)? as u32,
+                mtime: tar_number(&block[136..148]).ok_or_else(invalid)?,
+                user: owner(pax_user, &block[265..297]),
+                group: owner(pax_group, &block[297..329]),
+                inode: None,
+            }));
+        }
+    }
+}
+
+/// Read a NUL terminated string field of a tar header.
+fn tar_string(field: &[u8]) -> String {
+    String::from_utf8_lossy(field.split(|&b| b == 0).next().unwrap_or_default()).into_owned()
+}
+
+/// Read a numeric field of a tar header: octal, or base-256 for large
+/// values.
+fn tar_number(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        return Some(
+            field[1..]
+                .iter()
+                .fold(u64::from(field[0] & 0x7f), |n, &b| (n << 8) | u64::from(b)),
+        );
+    }
+    let digits = tar_string(field);
+    let digits = digits.trim();
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+/// Parse the `<length> <key>=<value>\n` records of a pax extended header.
+fn pax_records(data: &[u8]) -> Vec<(&str, &str)> {
+    let mut records = Vec::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let len = match std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|len| len.parse::<usize>().ok())
+        {
+            Some(len) if len > space && len <= rest.len() => len,
+            _ => break,
+        };
+        let record = &rest[space + 1..len];
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Ok(record) = std::str::from_utf8(record) {
+            if let Some((key, value)) = record.split_once('=') {
+                records.push((key, value));
+            }
+        }
+        rest = &rest[len..];
+    }
+    records
+}
+
+/// The file type bits of a mode, and the types of regular files,
+/// directories and symbolic links.
+pub(crate) const S_IFMT: u32 = 0o170000;
+pub(crate) const S_IFREG: u32 = 0o100000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+
+/// The magic at the start of a "new ASCII" cpio header, as used by rpm.
+const CPIO_MAGIC: &[u8] = b"070701";
+
+/// The name of the entry that ends a cpio archive.
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+/// The longest name of a cpio entry, including its trailing NUL, which is
+/// Linux's `PATH_MAX`. The name is read before anything is known about the
+/// entry, so this stops a bad header from allocating gigabytes.
+const CPIO_MAX_NAME: usize = 4096;
+
+/// The fields of a cpio header needed to read the entry.
+#[derive(Debug)]
+struct CpioHeader {
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    mtime: u32,
+    file_size: u64,
+    name: String,
+
+    /// The inode of the entry, if it has hard links.
+    inode: Option<u32>,
+}
+
+impl CpioHeader {
+    /// Read the next header and name, returning `None` at the trailer.
+    fn read<R: Read>(reader: &mut R) -> Result<Option<Self>, PkgError> {
+        let mut header = [0; 110];
+        reader.read_exact(&mut header)?;
+        if &header[..6] != CPIO_MAGIC {
+            return Err(PkgError::InvalidPackage(
+                "unsupported cpio payload format".to_string(),
+            ));
+        }
+
+        // Thirteen 8-digit hex fields follow the magic.
+        let field = |i: usize| -> Result<u32, PkgError> {
+            std::str::from_utf8(&header[6 + i * 8..14 + i * 8])
+                .ok()
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| PkgError::InvalidPackage("bad cpio header".to_string()))
+        };
+        let mode = field(1)?;
+        let file_size = u64::from(field(6)?);
+        let name_size = field(11)? as usize;
+        if name_size > CPIO_MAX_NAME {
+            return Err(PkgError::InvalidPackage(
+                "cpio entry name too long".to_string(),
+            ));
+        }
+
+        // The name includes a trailing NUL, and is padded so that the data
+        // starts on a multiple of four bytes.
+        let mut name = vec![0; name_size];
+        reader.read_exact(&mut name)?;
+        skip(reader, padding((110 + name_size) as u64, Format::Cpio))?;
+        let name = String::from_utf8_lossy(name.split(|&b| b == 0).next().unwrap_or_default())
+            .into_owned();
+
+        if name == CPIO_TRAILER {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            mode,
+            uid: field(2)?,
+            gid: field(3)?,
+            mtime: field(5)?,
+            file_size,
+            name,
+            inode: Some(field(0)?).filter(|_| field(4).is_ok_and(|nlink| nlink > 1)),
+        }))
+    }
+
+    /// Describe the entry in the same way as a tar entry. Symlink targets
+    /// are the contents of the entry, so are left to be read.
+    fn into_tar(self) -> TarHeader {
+        let kind = match self.mode & S_IFMT {
+            S_IFREG => EntryKind::File,
+            S_IFDIR => EntryKind::Directory,
+            S_IFLNK => EntryKind::Symlink(String::new()),
+            _ => EntryKind::Other,
+        };
+        TarHeader {
+            name: self.name,
+            mode: self.mode,
+            size: self.file_size,
+            kind,
+            uid: self.uid,
+            gid: self.gid,
+            mtime: u64::from(self.mtime),
+            user: None,
+            group: None,
+            inode: self.inode,
+        }
+    }
+}
+
+/// The padding after `len` bytes of an entry, to reach a multiple of four
+/// bytes for cpio or a whole block for tar.
+fn padding(len: u64, format: Format) -> u64 {
+    let align = match format {
+        Format::Tar => TAR_BLOCK,
+        Format::Cpio => 4,
+    };
+    (align - len % align) % align
+}
+
+/// Discard `len` bytes from a reader.
+pub(crate) fn skip<R: Read>(reader: &mut R, len: u64) -> Result<(), PkgError> {
+    if io::copy(&mut reader.take(len), &mut io::sink())? != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+/// Turn an archive path such as `./usr/bin/hello` into an installed path
+/// such as `/usr/bin/hello`.
+fn installed_path(path: &str) -> String {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    format!("/{}", path.trim_start_matches('/'))
+}
+
+/// Build a cpio archive of regular files, as found in an RPM payload.
+#[cfg(all(feature = "rpm", any(test, feature = "testing")))]
+pub(crate) fn build_cpio(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut cpio = Vec::new();
+    let trailer: (&str, &[u8]) = (CPIO_TRAILER, b"");
+    for (i, (name, data)) in files.iter().chain(std::iter::once(&trailer)).enumerate() {
+        let mode = if *name == CPIO_TRAILER { 0 } else { 0o100644 };
+        let fields = [
+            i as u32 + 1,
+            mode,
+            0,
+            0,
+            1,
+            0,
+            data.len() as u32,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u32 + 1,
+            0,
+        ];
+        cpio.extend_from_slice(CPIO_MAGIC);
+        for value in &fields {
+            cpio.extend_from_slice(format!("{:08x}", value).as_bytes());
+        }
+        cpio.extend_from_slice(name.as_bytes());
+        cpio.push(0);
+        cpio.resize(
+            cpio.len() + padding(110 + name.len() as u64 + 1, Format::Cpio) as usize,
+            0,
+        );
+        cpio.extend_from_slice(data);
+        cpio.resize(
+            cpio.len() + padding(data.len() as u64, Format::Cpio) as usize,
+            0,
+        );
+    }
+    cpio
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Build a package with a gzipped cpio payload of `files`.
+    #[cfg(feature = "rpm")]
+    pub(crate) fn build_rpm_with_payload(files: &[(&str, &[u8])]) -> Vec<u8> {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&build_cpio(files)).unwrap();
+        let mut rpm = crate::rpm_header::tests::build_rpm(&[]);
+        rpm.extend(encoder.finish().unwrap());
+        rpm
+    }
+
+    #[test]
+    fn test_is_license_file() {
+        assert!(is_license_file("/usr/share/doc/hello/copyright"));
+        assert!(is_license_file("/usr/share/licenses/hello/COPYING.LIB"));
+        assert!(is_license_file("/opt/app/License.txt"));
+        assert!(!is_license_file("/usr/share/doc/hello/Copyright"));
+        assert!(!is_license_file("/usr/bin/hello"));
+    }
+
+    #[cfg(feature = "debian")]
+    #[test]
+    fn test_scan_licenses_deb() {
+        let deb = crate::debian::tests::build_deb_with_data(
+            &[],
+            &[
+                ("./usr/bin/hello", b"binary"),
+                ("./usr/share/doc/hello/copyright", b"GPL-3+ text"),
+            ],
+        );
+
+        let licenses = scan_licenses(deb.as_slice(), 3).unwrap();
+        assert_eq!(
+            licenses,
+            vec![LicenseFile {
+                path: "/usr/share/doc/hello/copyright".to_string(),
+                contents: "GPL".to_string(),
+                truncated: true,
+            }]
+        );
+    }
+
+    #[cfg(feature = "debian")]
+    #[test]
+    fn test_entries_deb() {
+        let long_name = format!("./usr/share/doc/hello/{}.txt", "x".repeat(120));
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "./usr/bin/", io::empty())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o755);
+        header.set_size(6);
+        builder
+            .append_data(&mut header, "./usr/bin/hello", &b"binary"[..])
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_mode(0o777);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "./usr/bin/hi", "hello")
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o644);
+        header.set_size(4);
+        builder
+            .append_data(&mut header, &long_name, &b"text"[..])
+            .unwrap();
+        let deb =
+            crate::debian::tests::build_deb_with_data_tar(&[], &builder.into_inner().unwrap());
+
+        let mut entries = entries(deb.as_slice()).unwrap();
+        let mut seen = Vec::new();
+        while let Some(mut entry) = entries.next_entry().unwrap() {
+            // Only read some of the contents, to check the rest is skipped.
+            let mut start = [0; 2];
+            let read = entry.read(&mut start).unwrap();
+            seen.push((
+                entry.path().to_owned(),
+                entry.size(),
+                entry.mode(),
+                entry.kind().clone(),
+                start[..read].to_vec(),
+            ));
+        }
+        assert!(entries.next_entry().unwrap().is_none());
+
+        assert_eq!(
+            seen,
+            vec![
+                (
+                    "/usr/bin/".to_string(),
+                    0,
+                    0o755,
+                    EntryKind::Directory,
+                    vec![]
+                ),
+                (
+                    "/usr/bin/hello".to_string(),
+                    6,
+                    0o755,
+                    EntryKind::File,
+                    b"bi".to_vec()
+                ),
+                (
+                    "/usr/bin/hi".to_string(),
+                    0,
+                    0o777,
+                    EntryKind::Symlink("hello".to_string()),
+                    vec![]
+                ),
+                (
+                    installed_path(&long_name),
+                    4,
+                    0o644,
+                    EntryKind::File,
+                    b"te".to_vec()
+                ),
+            ]
+        );
+    }
+
+    /// Compress `data` in each of the formats packages use.
+    fn compress_all(data: &[u8]) -> Vec<(Compression, Vec<u8>)> {
+        use std::io::Write;
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(data).unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(data).unwrap();
+        let options = xz2::stream::LzmaOptions::new_preset(6).unwrap();
+        let stream = xz2::stream::Stream::new_lzma_encoder(&options).unwrap();
+        let mut lzma = xz2::write::XzEncoder::new_stream(Vec::new(), stream);
+        lzma.write_all(data).unwrap();
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(data).unwrap();
+
+        vec![
+            (Compression::None, data.to_vec()),
+            (Compression::Gzip, gzip.finish().unwrap()),
+            (Compression::Xz, xz.finish().unwrap()),
+            (Compression::Lzma, lzma.finish().unwrap()),
+            (Compression::Zstd, zstd::encode_all(data, 19).unwrap()),
+            (Compression::Bzip2, bzip2.finish().unwrap()),
+        ]
+    }
+
+    #[cfg(feature = "debian")]
+    #[test]
+    fn test_entries_deb_compression() {
+        let data_tar = crate::debian::build_tar(vec![&("./usr/bin/hello", &b"binary"[..])]);
+        for (compression, data) in compress_all(&data_tar) {
+            let deb = crate::debian::tests::build_deb_with_data_tar(&[], &data);
+            assert_eq!(
+                extract_file(deb.as_slice(), "/usr/bin/hello").unwrap(),
+                b"binary",
+                "{:?}",
+                compression
+            );
+            let package =
+                crate::debian::DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+            assert_eq!(package.payload_compression(), Some(compression));
+        }
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_entries_rpm_compression() {
+        let cpio = build_cpio(&[("./usr/bin/hello", b"binary")]);
+        for (compression, payload) in compress_all(&cpio) {
+            let mut rpm = crate::rpm_header::tests::build_rpm(&[]);
+            rpm.extend(payload);
+            assert_eq!(
+                extract_file(rpm.as_slice(), "/usr/bin/hello").unwrap(),
+                b"binary",
+                "{:?}",
+                compression
+            );
+        }
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_entries_rpm() {
+        let rpm =
+            build_rpm_with_payload(&[("./usr/bin/hello", b"binary"), ("./etc/hello.conf", b"")]);
+
+        let mut entries = entries(rpm.as_slice()).unwrap();
+        let entry = entries.next_entry().unwrap().unwrap();
+        assert_eq!(entry.path(), "/usr/bin/hello");
+        assert_eq!(entry.size(), 6);
+        assert_eq!(entry.mode(), 0o644);
+        assert_eq!(entry.kind(), &EntryKind::File);
+
+        let mut entry = entries.next_entry().unwrap().unwrap();
+        assert_eq!(entry.path(), "/etc/hello.conf");
+        assert_eq!(entry.read(&mut [0; 4]).unwrap(), 0);
+        assert!(entries.next_entry().unwrap().is_none());
+
+        // The name size is checked before the name is read.
+        let mut cpio = build_cpio(&[("./usr/bin/hello", b"binary")]);
+        cpio[94..102].copy_from_slice(b"FFFFFFFF");
+        assert!(matches!(
+            CpioHeader::read(&mut cpio.as_slice()),
+            Err(PkgError::InvalidPackage(_))
+        ));
+
+        assert!(matches!(
+            super::entries(&b"not a package"[..]),
+            Err(PkgError::UnknownPackageType(_))
+        ));
+    }
+
+    #[cfg(feature = "debian")]
+    #[test]
+    fn test_files() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder
+            .append_data(&mut header, "./usr/bin/", io::empty())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(0o4755);
+        header.set_size(5);
+        header.set_uid(0);
+        header.set_gid(50);
+        header.set_mtime(1_700_000_000);
+        header.set_username("root").unwrap();
+        header.set_groupname("staff").unwrap();
+        builder
+            .append_data(&mut header, "./usr/bin/hello", &b"hello"[..])
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_mode(0o755);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "./usr/bin/hello2", "./usr/bin/hello")
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_mode(0o777);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "./usr/bin/hi", "hello")
+            .unwrap();
+        let deb =
+            crate::debian::tests::build_deb_with_data_tar(&[], &builder.into_inner().unwrap());
+
+        let files = files(deb.as_slice()).unwrap();
+        assert_eq!(files.len(), 4);
+        assert_eq!(files[0].path, "/usr/bin/");
+        assert_eq!(files[0].mode, 0o040755);
+        assert_eq!(files[0].digest, None);
+        assert_eq!(files[0].user, None);
+        assert_eq!(
+            files[1],
+            FileEntry {
+                path: "/usr/bin/hello".to_string(),
+                mode: 0o104755,
+                user: Some("root".to_string()),
+                group: Some("staff".to_string()),
+                uid: Some(0),
+                gid: Some(50),
+                size: 5,
+                mtime: 1_700_000_000,
+                #[cfg(feature = "checksum")]
+                digest: Some((
+                    "sha256".to_string(),
+                    "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_string()
+                )),
+                #[cfg(not(feature = "checksum"))]
+                digest: None,
+                link_target: None,
+                hardlinks: vec!["/usr/bin/hello2".to_string()],
+            }
+        );
+
+        // The hard link shares the contents of its target.
+        assert_eq!(files[2].path, "/usr/bin/hello2");
+        assert_eq!(files[2].mode, 0o100755);
+        assert_eq!(files[2].size, 5);
+        assert_eq!(files[2].digest, files[1].digest);
+        assert_eq!(files[2].hardlinks, vec!["/usr/bin/hello"]);
+        assert_eq!(files[3].mode, 0o120777);
+        assert_eq!(files[3].link_target.as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn test_tar_fields() {
+        assert_eq!(tar_number(b"0000644\0"), Some(0o644));
+        assert_eq!(tar_number(b"\0\0\0\0"), Some(0));
+        assert_eq!(tar_number(&[0x80, 0, 0, 1, 0]), Some(256));
+        assert_eq!(tar_number(b"99"), None);
+        assert_eq!(
+            pax_records(b"28 path=usr/share/doc/hello\n18 linkpath=hello\n"),
+            vec![("path", "usr/share/doc/hello"), ("linkpath", "hello")]
+        );
+    }
+
+    /// Build the data archive of a package from directories, files and
+    /// symlinks, where a symlink's contents is its target.
+    #[cfg(feature = "debian")]
+    fn build_data_tar(entries: &[(tar::EntryType, &str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (kind, path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(*kind);
+            header.set_mode(if kind.is_dir() { 0o755 } else { 0o640 });
+            if kind.is_symlink() {
+                header.set_size(0);
+                let target = std::str::from_utf8(data).unwrap();
+                builder.append_link(&mut header, path, target).unwrap();
+            } else {
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, path, *data).unwrap();
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    #[cfg(feature = "debian")]
+    #[test]
+    fn test_entries_tar_types() {
+        use tar::EntryType;
+
+        // The data of contiguous files and of unknown types is skipped.
+        let data = build_data_tar(&[
+            (EntryType::Continuous, "./usr/bin/contiguous", b"contiguous"),
+            (EntryType::new(b'Q'), "./usr/bin/unknown", b"unknown"),
+            (EntryType::Regular, "./usr/bin/hello", b"binary"),
+        ]);
+        let deb = crate::debian::tests::build_deb_with_data_tar(&[], &data);
+
+        let mut entries = entries(deb.as_slice()).unwrap();
+        let entry = entries.next_entry().unwrap().unwrap();
+        assert_eq!(entry.kind(), &EntryKind::File);
+        assert_eq!(entry.size(), 10);
+        let entry = entries.next_entry().unwrap().unwrap();
+        assert_eq!(entry.kind(), &EntryKind::Other);
+        assert_eq!(entry.size(), 7);
+        let mut entry = entries.next_entry().unwrap().unwrap();
+        assert_eq!(entry.path(), "/usr/bin/hello");
+        let mut contents = Vec::new();
+        let _ = entry.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"binary");
+        assert!(entries.next_entry().unwrap().is_none());
+    }
+
+    #[cfg(all(feature = "debian", feature = "checksum"))]
+    #[test]
+    fn test_verify_md5sums() {
+        let md5sums = b"9d7183f16acce70658f686ae7f1a4d20  usr/bin/hello
+00000000000000000000000000000000  usr/share/doc/hello/copyright
+a75a069601a66b8d7655437cb132a350  usr/share/doc/hello/missing
+";
+        let deb = crate::debian::tests::build_deb_with_data(
+            &[("./md5sums", md5sums)],
+            &[
+                ("./usr/bin/hello", b"binary"),
+                ("./usr/share/doc/hello/copyright", b"GPL"),
+                ("./etc/hello.conf", b"unlisted"),
+            ],
+        );
+
+        assert_eq!(
+            verify_md5sums(deb.as_slice()).unwrap(),
+            vec![
+                Md5sumMismatch {
+                    path: "/usr/share/doc/hello/copyright".to_string(),
+                    expected: "00000000000000000000000000000000".to_string(),
+                    actual: Some("a75a069601a66b8d7655437cb132a350".to_string()),
+                },
+                Md5sumMismatch {
+                    path: "/usr/share/doc/hello/missing".to_string(),
+                    expected: "a75a069601a66b8d7655437cb132a350".to_string(),
+                    actual: None,
+                },
+            ]
+        );
+
+        let deb = crate::debian::tests::build_deb(&[]);
+        assert!(matches!(
+            verify_md5sums(deb.as_slice()),
+            Err(PkgError::FileNotFound(_))
+        ));
+    }
+
+    #[cfg(all(feature = "debian", unix))]
+    #[test]
+    fn test_extract_to() {
+        use std::os::unix::fs::PermissionsExt;
+        use tar::EntryType;
+
+        let dir =
+            std::env::temp_dir().join(format!("remote-package-extract-{}", std::process::id()));
+        let data = build_data_tar(&[
+            (EntryType::Directory, "./usr/bin/", b""),
+            (EntryType::Regular, "./usr/bin/hello", b"binary"),
+            (EntryType::Symlink, "./usr/bin/hi", b"hello"),
+            (EntryType::Symlink, "./usr/bin/sh", b"/bin/bash"),
+        ]);
+        let deb = crate::debian::tests::build_deb_with_data_tar(&[], &data);
+
+        let written = extract_to(deb.as_slice(), &dir, &ExtractOptions::default()).unwrap();
+        assert_eq!(written, 4);
+        assert_eq!(fs::read(dir.join("usr/bin/hi")).unwrap(), b"binary");
+        let mode = fs::metadata(dir.join("usr/bin/hello"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(
+            fs::read_link(dir.join("usr/bin/sh")).unwrap(),
+            Path::new("/bin/bash")
+        );
+
+        // Extracting again replaces what is there.
+        let options = ExtractOptions {
+            symlinks: SymlinkPolicy::Skip,
+            ..ExtractOptions::default()
+        };
+        assert_eq!(extract_to(deb.as_slice(), &dir, &options).unwrap(), 2);
+
+        // Links out of the directory, and writing through links, are
+        // rejected.
+        let data = build_data_tar(&[(EntryType::Symlink, "./escape", b"../../etc")]);
+        let deb = crate::debian::tests::build_deb_with_data_tar(&[], &data);
+        assert!(matches!(
+            extract_to(deb.as_slice(), &dir, &ExtractOptions::default()),
+            Err(PkgError::UnsafePath(_))
+        ));
+        let data = build_data_tar(&[
+            (EntryType::Symlink, "./etc", b"/tmp"),
+            (EntryType::Regular, "./etc/passwd", b"root"),
+        ]);
+        let deb = crate::debian::tests::build_deb_with_data_tar(&[], &data);
+        assert!(matches!(
+            extract_to(deb.as_slice(), &dir, &ExtractOptions::default()),
+            Err(PkgError::UnsafePath(_))
+        ));
+
+        // Even when any link is allowed, a directory entry isn't created
+        // through an existing link, nor are its permissions set.
+        let outside = dir.with_extension("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::set_permissions(&outside, fs::Permissions::from_mode(0o700)).unwrap();
+        let link = outside.to_str().unwrap().as_bytes();
+        let data = build_data_tar(&[
+            (EntryType::Symlink, "./outside", link),
+            (EntryType::Directory, "./outside/", b""),
+        ]);
+        let deb = crate::debian::tests::build_deb_with_data_tar(&[], &data);
+        let options = ExtractOptions {
+            symlinks: SymlinkPolicy::Any,
+            ..ExtractOptions::default()
+        };
+        assert!(matches!(
+            extract_to(deb.as_slice(), &dir, &options),
+            Err(PkgError::UnsafePath(_))
+        ));
+        let mode = fs::metadata(&outside).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        fs::remove_dir_all(&outside).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unsafe_paths() {
+        assert_eq!(path_components("./usr//bin/").unwrap(), vec!["usr", "bin"]);
+        assert!(path_components("/usr/../../etc").is_err());
+
+        assert!(link_is_contained(&["usr", "bin"], "../lib/hello"));
+        assert!(link_is_contained(&["usr", "bin"], "/etc/hello"));
+        assert!(!link_is_contained(&["usr", "bin"], "../../../etc"));
+        assert!(!link_is_contained(&[], "/../etc"));
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_extract_file() {
+        let rpm = build_rpm_with_payload(&[
+            ("./usr/bin/hello", b"binary"),
+            ("./etc/hello.conf", b"greeting=hi\n"),
+        ]);
+
+        assert_eq!(
+            extract_file(rpm.as_slice(), "/usr/bin/hello").unwrap(),
+            b"binary"
+        );
+        assert_eq!(
+            extract_file(rpm.as_slice(), "etc/hello.conf").unwrap(),
+            b"greeting=hi\n"
+        );
+
+        let mut contents = Vec::new();
+        let written = extract_file_to(rpm.as_slice(), "./etc/hello.conf", &mut contents).unwrap();
+        assert_eq!(written, 12);
+        assert_eq!(contents, b"greeting=hi\n");
+
+        assert!(matches!(
+            extract_file(rpm.as_slice(), "/usr/bin/goodbye"),
+            Err(PkgError::FileNotFound(path)) if path == "/usr/bin/goodbye"
+        ));
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_scan_licenses_rpm() {
+        let rpm = build_rpm_with_payload(&[
+            ("./usr/share/licenses/hello/LICENSE", b"MIT"),
+            ("./usr/bin/hello", b"binary"),
+        ]);
+
+        let licenses = scan_licenses(rpm.as_slice(), 1024).unwrap();
+        assert_eq!(licenses.len(), 1);
+        assert_eq!(licenses[0].path, "/usr/share/licenses/hello/LICENSE");
+        assert_eq!(licenses[0].contents, "MIT");
+        assert!(!licenses[0].truncated);
+    }
+}
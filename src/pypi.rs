@@ -0,0 +1,227 @@
+//! Support for looking up distributions on PyPI.
+//!
+//! Uses the PyPI JSON API (`/pypi/<project>/json`), which lists every
+//! release of a project along with its files and their hashes.
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::PkgError;
+
+/// The default PyPI JSON API endpoint.
+pub const PYPI_URL: &str = "https://pypi.org/pypi";
+
+/// A file (wheel or sdist) belonging to a release.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PypiFile {
+    /// The file name, e.g. `requests-2.31.0-py3-none-any.whl`.
+    pub filename: String,
+
+    /// The download URL.
+    pub url: String,
+
+    /// The distribution type, `bdist_wheel` or `sdist`.
+    pub packagetype: String,
+
+    /// The file size in bytes.
+    #[serde(default)]
+    pub size: Option<u64>,
+
+    /// Hashes of the file, keyed by algorithm (e.g. `sha256`).
+    #[serde(default)]
+    pub digests: BTreeMap<String, String>,
+
+    /// The `Requires-Python` constraint for the file, if any.
+    #[serde(default)]
+    pub requires_python: Option<String>,
+
+    /// Whether the file has been yanked.
+    #[serde(default)]
+    pub yanked: bool,
+}
+
+impl PypiFile {
+    /// Get the SHA256 hash of the file.
+    pub fn sha256(&self) -> Option<&str> {
+        self.digests.get("sha256").map(String::as_str)
+    }
+
+    /// Whether this file is a wheel.
+    pub fn is_wheel(&self) -> bool {
+        self.packagetype == "bdist_wheel" || self.filename.ends_with(".whl")
+    }
+
+    /// Get the `python-abi-platform` tags supported by a wheel, expanding
+    /// compressed tag sets such as `py2.py3-none-any`. Returns an empty list
+    /// for non-wheel files.
+    pub fn wheel_tags(&self) -> Vec<String> {
+        let stem = match self.filename.strip_suffix(".whl") {
+            Some(stem) if self.is_wheel() => stem,
+            _ => return Vec::new(),
+        };
+
+        // The last three dash-separated components are always the tags.
+        let parts: Vec<&str> = stem.rsplitn(4, '-').collect();
+        if parts.len() < 4 {
+            return Vec::new();
+        }
+        let (platforms, abis, pythons) = (parts[0], parts[1], parts[2]);
+
+        let mut tags = Vec::new();
+        for python in pythons.split('.') {
+            for abi in abis.split('.') {
+                for platform in platforms.split('.') {
+                    tags.push(format!("{}-{}-{}", python, abi, platform));
+                }
+            }
+        }
+        tags
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PypiInfo {
+    name: String,
+    version: String,
+}
+
+/// A project's metadata as returned by the PyPI JSON API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PypiProject {
+    info: PypiInfo,
+
+    #[serde(default)]
+    releases: BTreeMap<String, Vec<PypiFile>>,
+}
+
+impl PypiProject {
+    /// Parse a response from the PyPI JSON API.
+    pub fn parse(json: &str) -> Result<Self, PkgError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Fetch a project from a PyPI-compatible JSON API, such as
+    /// [`PYPI_URL`]. The project name is normalized as PEP 503 describes,
+    /// so `Zope.Interface` finds `zope-interface`.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn fetch(index_url: &str, project: &str) -> Result<Self, PkgError> {
+        let url = format!(
+            "{}/{}/json",
+            index_url.trim_end_matches('/'),
+            crate::naming::pypi(project)
+        );
+        let response = crate::http::get(&url)?;
+        Self::parse(&response.text()?)
+    }
+
+    /// Get the project name.
+    pub fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    /// Get the latest version of the project.
+    pub fn latest_version(&self) -> &str {
+        &self.info.version
+    }
+
+    /// Get the versions of the project that have been released.
+    pub fn versions(&self) -> impl Iterator<Item = &str> {
+        self.releases.keys().map(String::as_str)
+    }
+
+    /// Get the files for a version of the project.
+    pub fn files(&self, version: &str) -> Option<&[PypiFile]> {
+        self.releases.get(version).map(Vec::as_slice)
+    }
+
+    /// Select the best file for a version (or the latest version if `None`).
+    ///
+    /// `tags` is a list of acceptable wheel tags, such as `py3-none-any` or
+    /// `cp311-cp311-manylinux_2_17_x86_64`, in order of preference. The wheel
+    /// matching the earliest tag is chosen; if no wheel matches, the sdist is
+    /// returned instead. Yanked files are never selected.
+    pub fn select_file(&self, version: Option<&str>, tags: &[&str]) -> Option<&PypiFile> {
+        let version = version.unwrap_or_else(|| self.latest_version());
+        let files = self.files(version)?;
+        let candidates = files.iter().filter(|f| !f.yanked);
+
+        let wheel = candidates
+            .clone()
+            .filter_map(|file| {
+                let supported = file.wheel_tags();
+                tags.iter()
+                    .position(|tag| supported.iter().any(|s| s == tag))
+                    .map(|priority| (priority, file))
+            })
+            .min_by_key(|(priority, _)| *priority)
+            .map(|(_, file)| file);
+
+        wheel.or_else(|| candidates.clone().find(|f| f.packagetype == "sdist"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = r#"{
+        "info": {"name": "example", "version": "2.0"},
+        "releases": {
+            "1.0": [
+                {"filename": "example-1.0.tar.gz", "url": "https://files.example/example-1.0.tar.gz",
+                 "packagetype": "sdist", "size": 100, "digests": {"sha256": "aaaa"}}
+            ],
+            "2.0": [
+                {"filename": "example-2.0-cp311-cp311-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+                 "url": "https://files.example/example-2.0-cp311.whl",
+                 "packagetype": "bdist_wheel", "digests": {"sha256": "bbbb"}},
+                {"filename": "example-2.0-py2.py3-none-any.whl",
+                 "url": "https://files.example/example-2.0-py3.whl",
+                 "packagetype": "bdist_wheel", "digests": {"sha256": "cccc"}},
+                {"filename": "example-2.0-cp312-cp312-win_amd64.whl",
+                 "url": "https://files.example/example-2.0-win.whl",
+                 "packagetype": "bdist_wheel", "digests": {"sha256": "dddd"}, "yanked": true},
+                {"filename": "example-2.0.tar.gz", "url": "https://files.example/example-2.0.tar.gz",
+                 "packagetype": "sdist", "digests": {"sha256": "eeee"}}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_wheel_tags() {
+        let project = PypiProject::parse(PROJECT).unwrap();
+        let files = project.files("2.0").unwrap();
+        assert_eq!(files[1].wheel_tags(), vec!["py2-none-any", "py3-none-any"]);
+        assert_eq!(files[0].wheel_tags().len(), 2);
+        assert!(files[3].wheel_tags().is_empty());
+    }
+
+    #[test]
+    fn test_select_file() {
+        let project = PypiProject::parse(PROJECT).unwrap();
+        assert_eq!(project.name(), "example");
+        assert_eq!(project.versions().collect::<Vec<_>>(), vec!["1.0", "2.0"]);
+
+        let native = project
+            .select_file(None, &["cp311-cp311-manylinux_2_17_x86_64", "py3-none-any"])
+            .unwrap();
+        assert_eq!(native.sha256(), Some("bbbb"));
+
+        let pure = project.select_file(None, &["py3-none-any"]).unwrap();
+        assert_eq!(pure.url, "https://files.example/example-2.0-py3.whl");
+
+        // Yanked wheels are skipped, falling back to the sdist.
+        let sdist = project
+            .select_file(Some("2.0"), &["cp312-cp312-win_amd64"])
+            .unwrap();
+        assert_eq!(sdist.filename, "example-2.0.tar.gz");
+
+        let old = project.select_file(Some("1.0"), &[]).unwrap();
+        assert_eq!(old.size, Some(100));
+
+        assert!(project.select_file(Some("3.0"), &[]).is_none());
+    }
+}
@@ -0,0 +1,767 @@
+//! Functionality shared between the repository index modules.
+//!
+//! Repositories whose packages can be compared by version implement
+//! [`Repo`], which allows them to be compared with [`repo_diff`]. Package
+//! relationships are described with [`Dependency`], allowing queries such as
+//! [`reverse_dependencies`] and [`DependencyResolver`] across any supported
+//! index.
+#[cfg(all(
+    feature = "http-blocking",
+    any(feature = "repo-rpm", all(feature = "repo-deb", feature = "verify"))
+))]
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+#[cfg(all(
+    feature = "http-blocking",
+    any(feature = "repo-rpm", all(feature = "repo-deb", feature = "verify"))
+))]
+use std::io::{self, Read};
+#[cfg(all(
+    feature = "http-blocking",
+    any(feature = "repo-rpm", all(feature = "repo-deb", feature = "verify"))
+))]
+use std::rc::Rc;
+
+pub use crate::version::{VersionConstraint, VersionOp};
+use crate::PkgError;
+
+/// A dependency on (or a provide of) a package name, with an optional
+/// version constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    /// The package, virtual package or capability name.
+    pub name: String,
+
+    /// The version constraint, if any.
+    pub constraint: Option<VersionConstraint>,
+}
+
+impl Dependency {
+    /// Whether a package satisfies the dependency, either by its own name
+    /// and version, by one of its provides, or (for paths) by shipping the
+    /// file.
+    ///
+    /// An unversioned provide only satisfies an unversioned dependency.
+    pub fn is_satisfied_by<P: RepoPackage>(&self, package: &P) -> bool {
+        let matches = |version: &str| match &self.constraint {
+            Some(constraint) => constraint.matches(version, P::compare_versions),
+            None => true,
+        };
+
+        if package.name() == self.name && matches(&package.full_version()) {
+            return true;
+        }
+        if self.name.starts_with('/') && package.has_file(&self.name) {
+            return true;
+        }
+        package.provides().iter().any(|provide| {
+            provide.name == self.name
+                && match (&self.constraint, &provide.constraint) {
+                    (None, _) => true,
+                    (Some(_), Some(provided)) if provided.op == VersionOp::Eq => {
+                        matches(&provided.version)
+                    }
+                    _ => false,
+                }
+        })
+    }
+}
+
+impl fmt::Display for Dependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.constraint {
+            Some(constraint) => write!(f, "{} ({})", self.name, constraint),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// A package listed in a repository index.
+pub trait RepoPackage {
+    /// Get the package name.
+    fn name(&self) -> &str;
+
+    /// Get the full version of the package, including any epoch and
+    /// release, in the form expected by [`RepoPackage::compare_versions`].
+    fn full_version(&self) -> String;
+
+    /// Get the package architecture, if declared.
+    fn arch(&self) -> Option<&str>;
+
+    /// Compare two full versions using the repository's version ordering.
+    fn compare_versions(a: &str, b: &str) -> Ordering;
+
+    /// Get the hard dependencies of the package. Each entry is a list of
+    /// alternatives, any one of which satisfies it.
+    fn depends(&self) -> Vec<Vec<Dependency>> {
+        Vec::new()
+    }
+
+    /// Get the recommended (weak) dependencies of the package, as lists of
+    /// alternatives.
+    fn recommends(&self) -> Vec<Vec<Dependency>> {
+        Vec::new()
+    }
+
+    /// Get the virtual packages or capabilities the package provides.
+    fn provides(&self) -> Vec<Dependency> {
+        Vec::new()
+    }
+
+    /// Get the size of the package file in bytes, if listed.
+    fn download_size(&self) -> Option<u64> {
+        None
+    }
+
+    /// Get the installed size of the package in bytes, if listed.
+    fn installed_size(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether the index lists the package as shipping the file at `path`,
+    /// for indexes where files can satisfy dependencies.
+    fn has_file(&self, _path: &str) -> bool {
+        false
+    }
+}
+
+/// A repository whose packages can be listed.
+pub trait Repo {
+    /// The type of package listed in the repository index.
+    type Package: RepoPackage;
+
+    /// Fetch every package listed in the repository index.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    fn list_packages(&self) -> Result<Vec<Self::Package>, PkgError>;
+
+    /// Fetch the repository index and find the packages whose names match
+    /// `pattern`, optionally restricted to an architecture; see
+    /// [`search_packages`].
+    #[cfg(feature = "http-blocking")]
+    fn search(
+        &self,
+        pattern: &NamePattern,
+        arch_filter: Option<&str>,
+    ) -> Result<Vec<Self::Package>, PkgError> {
+        Ok(search_packages(self.list_packages()?, pattern, arch_filter))
+    }
+}
+
+/// A pattern for matching package names.
+#[derive(Debug, Clone)]
+pub enum NamePattern {
+    /// Match the name exactly.
+    Exact(String),
+
+    /// Match a shell-style glob, where `*` matches any run of characters,
+    /// `?` any single character and `[...]` a character class.
+    Glob(String),
+
+    /// Match a regular expression anywhere in the name, as `apt-cache
+    /// search` does. Use `^...$` to match the whole name.
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl NamePattern {
+    /// Create a glob pattern, e.g. `python3-*`.
+    pub fn glob(pattern: &str) -> Self {
+        NamePattern::Glob(pattern.to_owned())
+    }
+
+    /// Create a regular expression pattern, e.g. `^lib.*-dev$`.
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self, PkgError> {
+        regex::Regex::new(pattern)
+            .map(NamePattern::Regex)
+            .map_err(|e| PkgError::InvalidPattern(e.to_string()))
+    }
+
+    /// Whether a package name matches the pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Exact(exact) => exact == name,
+            NamePattern::Glob(glob) => {
+                let pattern: Vec<char> = glob.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                glob_match(&pattern, &name)
+            }
+            #[cfg(feature = "regex")]
+            NamePattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Find the packages whose names match `pattern`.
+///
+/// If `arch_filter` is given, only packages for that architecture or
+/// architecture-independent (`all`, `noarch` or `any`) packages are kept.
+pub fn search_packages<P, I>(
+    packages: I,
+    pattern: &NamePattern,
+    arch_filter: Option<&str>,
+) -> Vec<P>
+where
+    P: RepoPackage,
+    I: IntoIterator<Item = P>,
+{
+    packages
+        .into_iter()
+        .filter(|p| pattern.matches(p.name()))
+        .filter(|p| match (arch_filter, p.arch()) {
+            (Some(arch), Some(package_arch)) => {
+                package_arch == arch || ["all", "noarch", "any"].contains(&package_arch)
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// Match a glob pattern against a name.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some(('[', rest)) => {
+            let end = match rest.iter().skip(1).position(|c| *c == ']') {
+                Some(end) => end + 1,
+                // An unterminated class is matched literally.
+                None => return name.first() == Some(&'[') && glob_match(rest, &name[1..]),
+            };
+            let (class, rest) = (&rest[..end], &rest[end + 1..]);
+            let (negated, class) = match class.split_first() {
+                Some(('!', class)) | Some(('^', class)) => (true, class),
+                _ => (false, class),
+            };
+            let c = match name.first() {
+                Some(c) => *c,
+                None => return false,
+            };
+
+            let mut matched = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    matched |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    matched |= class[i] == c;
+                    i += 1;
+                }
+            }
+            matched != negated && glob_match(rest, &name[1..])
+        }
+        Some((literal, rest)) => name.first() == Some(literal) && glob_match(rest, &name[1..]),
+    }
+}
+
+/// Parse an inline dependency such as `glibc>=2.38` or `so:libc.so.1`, as
+/// used by apk and pacman.
+#[cfg(any(feature = "repo-apk", feature = "repo-arch"))]
+pub(crate) fn parse_inline_dependency(dependency: &str) -> Dependency {
+    let ops = [
+        ("<=", VersionOp::Le),
+        (">=", VersionOp::Ge),
+        ("=", VersionOp::Eq),
+        ("<", VersionOp::Lt),
+        (">", VersionOp::Gt),
+    ];
+    let split = dependency.find(['<', '>', '=']);
+
+    match split {
+        Some(split) => {
+            let (name, constraint) = dependency.split_at(split);
+            let constraint = ops.iter().find_map(|(token, op)| {
+                constraint
+                    .strip_prefix(token)
+                    .map(|version| VersionConstraint {
+                        op: *op,
+                        version: version.to_owned(),
+                    })
+            });
+            Dependency {
+                name: name.to_owned(),
+                constraint,
+            }
+        }
+        None => Dependency {
+            name: dependency.to_owned(),
+            constraint: None,
+        },
+    }
+}
+
+/// A package whose version differs between two repositories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionChange<P> {
+    /// The package in the first repository.
+    pub from: P,
+
+    /// The package in the second repository.
+    pub to: P,
+}
+
+/// The differences between two repositories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoDiff<P> {
+    /// Packages only in the second repository.
+    pub added: Vec<P>,
+
+    /// Packages only in the first repository.
+    pub removed: Vec<P>,
+
+    /// Packages with a newer version in the second repository.
+    pub upgraded: Vec<VersionChange<P>>,
+
+    /// Packages with an older version in the second repository.
+    pub downgraded: Vec<VersionChange<P>>,
+}
+
+impl<P> RepoDiff<P> {
+    /// Whether the repositories contain the same packages.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.upgraded.is_empty()
+            && self.downgraded.is_empty()
+    }
+}
+
+/// Compare two package listings.
+///
+/// Packages are matched by name and architecture. Where a listing has
+/// several versions of a package only the newest is compared, so the result
+/// describes what a client would install from each. Results are sorted by
+/// name and architecture.
+pub fn diff_packages<P, A, B>(a: A, b: B) -> RepoDiff<P>
+where
+    P: RepoPackage,
+    A: IntoIterator<Item = P>,
+    B: IntoIterator<Item = P>,
+{
+    let mut a = newest_by_key(a);
+    let b = newest_by_key(b);
+
+    let mut diff = RepoDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+        upgraded: Vec::new(),
+        downgraded: Vec::new(),
+    };
+
+    for (key, to) in b {
+        let from = match a.remove(&key) {
+            Some(from) => from,
+            None => {
+                diff.added.push(to);
+                continue;
+            }
+        };
+        match P::compare_versions(&from.full_version(), &to.full_version()) {
+            Ordering::Less => diff.upgraded.push(VersionChange { from, to }),
+            Ordering::Greater => diff.downgraded.push(VersionChange { from, to }),
+            Ordering::Equal => {}
+        }
+    }
+    diff.removed = a.into_values().collect();
+    diff
+}
+
+/// Fetch the indexes of two repositories and compare them, e.g. a mirror
+/// against its upstream, or two snapshots of the same repository. See
+/// [`diff_packages`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn repo_diff<R: Repo>(repo_a: &R, repo_b: &R) -> Result<RepoDiff<R::Package>, PkgError> {
+    Ok(diff_packages(
+        repo_a.list_packages()?,
+        repo_b.list_packages()?,
+    ))
+}
+
+/// Find the packages that depend on, or recommend, `name`.
+///
+/// `name` may be a package name or a provide (virtual package or
+/// capability). Any alternative mentioning the name counts, whatever its
+/// version constraint.
+pub fn reverse_dependencies<'a, P, I>(packages: I, name: &str) -> Vec<&'a P>
+where
+    P: RepoPackage + 'a,
+    I: IntoIterator<Item = &'a P>,
+{
+    packages
+        .into_iter()
+        .filter(|package| {
+            package
+                .depends()
+                .into_iter()
+                .chain(package.recommends())
+                .flatten()
+                .any(|dep| dep.name == name)
+        })
+        .collect()
+}
+
+/// The total size of a set of resolved packages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClosureSize {
+    /// The number of packages in the set.
+    pub packages: usize,
+
+    /// The total size of the package files, in bytes.
+    pub download_size: u64,
+
+    /// The total installed size of the packages, in bytes.
+    pub installed_size: u64,
+
+    /// The number of packages whose download or installed size isn't listed
+    /// in the index, and so isn't included in the totals.
+    pub unknown_sizes: usize,
+}
+
+impl ClosureSize {
+    /// Sum the sizes of a set of packages.
+    pub fn of<'a, P, I>(packages: I) -> Self
+    where
+        P: RepoPackage + 'a,
+        I: IntoIterator<Item = &'a P>,
+    {
+        let mut size = ClosureSize::default();
+        for package in packages {
+            size.packages += 1;
+            match (package.download_size(), package.installed_size()) {
+                (Some(download), Some(installed)) => {
+                    size.download_size += download;
+                    size.installed_size += installed;
+                }
+                (download, installed) => {
+                    size.download_size += download.unwrap_or_default();
+                    size.installed_size += installed.unwrap_or_default();
+                    size.unknown_sizes += 1;
+                }
+            }
+        }
+        size
+    }
+}
+
+/// Resolves the transitive dependencies of packages within an index.
+///
+/// Packages are chosen greedily: for each dependency not already satisfied
+/// by a chosen package, the first alternative that can be satisfied is used,
+/// preferring a package with that exact name, then the newest version.
+/// Conflicts are not considered.
+#[derive(Debug, Clone)]
+pub struct DependencyResolver<'a, P> {
+    packages: Vec<&'a P>,
+    arch: Option<String>,
+    recommends: bool,
+}
+
+impl<'a, P: RepoPackage> DependencyResolver<'a, P> {
+    /// Create a resolver over the packages of an index.
+    pub fn new<I: IntoIterator<Item = &'a P>>(packages: I) -> Self {
+        Self {
+            packages: packages.into_iter().collect(),
+            arch: None,
+            recommends: false,
+        }
+    }
+
+    /// Only choose packages for `arch`, or architecture-independent
+    /// (`all`/`noarch`) packages. This avoids picking multilib packages
+    /// from yum repositories.
+    pub fn arch(mut self, arch: &str) -> Self {
+        self.arch = Some(arch.to_owned());
+        self
+    }
+
+    /// Also follow recommended dependencies. Recommendations that can't be
+    /// satisfied are skipped rather than treated as errors.
+    pub fn recommends(mut self, recommends: bool) -> Self {
+        self.recommends = recommends;
+        self
+    }
+
+    /// Resolve the named packages and everything they depend on, returning
+    /// the chosen packages with the requested packages first.
+    pub fn resolve(&self, names: &[&str]) -> Result<Vec<&'a P>, PkgError> {
+        let mut chosen: Vec<&'a P> = Vec::new();
+        let mut queue = VecDeque::new();
+
+        for name in names {
+            let dependency = Dependency {
+                name: (*name).to_owned(),
+                constraint: None,
+            };
+            if chosen.iter().any(|c| dependency.is_satisfied_by(*c)) {
+                continue;
+            }
+            let package = self
+                .best_candidate(&dependency, &chosen)
+                .ok_or_else(|| PkgError::PackageNotFound((*name).to_owned()))?;
+            chosen.push(package);
+            queue.push_back(package);
+        }
+
+        while let Some(package) = queue.pop_front() {
+            let mut groups: Vec<(Vec<Dependency>, bool)> = package
+                .depends()
+                .into_iter()
+                .map(|group| (group, true))
+                .collect();
+            if self.recommends {
+                groups.extend(package.recommends().into_iter().map(|group| (group, false)));
+            }
+
+            for (group, required) in groups {
+                let satisfied = chosen
+                    .iter()
+                    .any(|c| group.iter().any(|dep| dep.is_satisfied_by(*c)));
+                if satisfied {
+                    continue;
+                }
+
+                let candidate = group
+                    .iter()
+                    .find_map(|dep| self.best_candidate(dep, &chosen));
+                match candidate {
+                    Some(candidate) => {
+                        chosen.push(candidate);
+                        queue.push_back(candidate);
+                    }
+                    None if required => {
+                        let alternatives: Vec<String> =
+                            group.iter().map(ToString::to_string).collect();
+                        return Err(PkgError::UnsatisfiedDependency {
+                            package: package.name().to_owned(),
+                            dependency: alternatives.join(" | "),
+                        });
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        Ok(chosen)
+    }
+
+    /// Resolve the named packages and sum the download and installed sizes of
+    /// everything that would be installed.
+    pub fn closure_size(&self, names: &[&str]) -> Result<ClosureSize, PkgError> {
+        Ok(ClosureSize::of(self.resolve(names)?))
+    }
+
+    /// Find the best package to satisfy a dependency. Packages sharing a name
+    /// and architecture with an already chosen package are skipped, as only
+    /// one version of each can be installed.
+    fn best_candidate(&self, dependency: &Dependency, chosen: &[&'a P]) -> Option<&'a P> {
+        self.packages
+            .iter()
+            .copied()
+            .filter(|p| self.arch_allowed(*p))
+            .filter(|p| {
+                !chosen
+                    .iter()
+                    .any(|c| c.name() == p.name() && c.arch() == p.arch())
+            })
+            .filter(|p| dependency.is_satisfied_by(*p))
+            .max_by(|a, b| {
+                let exact = |p: &P| p.name() == dependency.name;
+                exact(a)
+                    .cmp(&exact(b))
+                    .then_with(|| P::compare_versions(&a.full_version(), &b.full_version()))
+                    // Prefer the first name alphabetically for determinism.
+                    .then_with(|| b.name().cmp(a.name()))
+            })
+    }
+
+    fn arch_allowed(&self, package: &P) -> bool {
+        match (&self.arch, package.arch()) {
+            (Some(arch), Some(package_arch)) => {
+                package_arch == arch || package_arch == "all" || package_arch == "noarch"
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Index packages by name and architecture, keeping the newest version.
+fn newest_by_key<P, I>(packages: I) -> BTreeMap<(String, String), P>
+where
+    P: RepoPackage,
+    I: IntoIterator<Item = P>,
+{
+    let mut newest: BTreeMap<(String, String), P> = BTreeMap::new();
+    for package in packages {
+        let key = (
+            package.name().to_owned(),
+            package.arch().unwrap_or_default().to_owned(),
+        );
+        match newest.get(&key) {
+            Some(existing)
+                if P::compare_versions(&existing.full_version(), &package.full_version())
+                    != Ordering::Less => {}
+            _ => {
+                let _ = newest.insert(key, package);
+            }
+        }
+    }
+    newest
+}
+
+/// A check run once an index has been read to the end, such as comparing
+/// it against the checksum the repository lists for it.
+#[cfg(any(feature = "repo-rpm", feature = "repo-deb"))]
+pub(crate) type Check = Box<dyn FnOnce() -> Result<(), PkgError>>;
+
+/// A reader shared between the decoder of an index and the [`Check`] that
+/// finishes reading it, as the decoder may stop at the end of the
+/// compressed stream.
+#[cfg(all(
+    feature = "http-blocking",
+    any(feature = "repo-rpm", all(feature = "repo-deb", feature = "verify"))
+))]
+pub(crate) struct SharedReader<R: Read>(pub(crate) Rc<RefCell<R>>);
+
+#[cfg(all(
+    feature = "http-blocking",
+    any(feature = "repo-rpm", all(feature = "repo-deb", feature = "verify"))
+))]
+impl<R: Read> Read for SharedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.borrow_mut().read(buf)
+    }
+}
+
+/// Join a repository base URL and a path relative to it.
+pub(crate) fn join_url(base_url: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct TestPackage(&'static str, &'static str);
+
+    impl RepoPackage for TestPackage {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn full_version(&self) -> String {
+            self.1.to_owned()
+        }
+
+        fn arch(&self) -> Option<&str> {
+            None
+        }
+
+        fn compare_versions(a: &str, b: &str) -> Ordering {
+            crate::version::compare_debian(a, b)
+        }
+    }
+
+    #[test]
+    fn test_diff_packages() {
+        let a = vec![
+            TestPackage("curl", "7.81.0-1"),
+            TestPackage("curl", "7.81.0-2"),
+            TestPackage("wget", "1.21-1"),
+            TestPackage("zlib", "1:1.2.13-1"),
+            TestPackage("bash", "5.1-6"),
+        ];
+        let b = vec![
+            TestPackage("curl", "7.88.1-10"),
+            TestPackage("zlib", "1:1.2.11-1"),
+            TestPackage("bash", "5.1-6"),
+            TestPackage("jq", "1.6-2"),
+        ];
+
+        let diff = diff_packages(a, b);
+        assert_eq!(diff.added, vec![TestPackage("jq", "1.6-2")]);
+        assert_eq!(diff.removed, vec![TestPackage("wget", "1.21-1")]);
+        assert_eq!(
+            diff.upgraded,
+            vec![VersionChange {
+                from: TestPackage("curl", "7.81.0-2"),
+                to: TestPackage("curl", "7.88.1-10"),
+            }]
+        );
+        assert_eq!(diff.downgraded[0].to, TestPackage("zlib", "1:1.2.11-1"));
+
+        assert!(diff_packages(diff.added.clone(), diff.added).is_empty());
+    }
+
+    #[test]
+    fn test_search_packages() {
+        let packages = vec![
+            TestPackage("python3-requests", "2.28.1-1"),
+            TestPackage("python3-urllib3", "1.26.12-1"),
+            TestPackage("python3", "3.11.2-1"),
+            TestPackage("libssl-dev", "3.0.11-1"),
+        ];
+        let names = |pattern: &NamePattern| {
+            search_packages(packages.clone(), pattern, Some("amd64"))
+                .into_iter()
+                .map(|p| p.0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(&NamePattern::glob("python3-*")),
+            vec!["python3-requests", "python3-urllib3"]
+        );
+        assert_eq!(names(&NamePattern::glob("python?")), vec!["python3"]);
+        assert_eq!(
+            names(&NamePattern::glob("lib[a-s]sl-*")),
+            vec!["libssl-dev"]
+        );
+        assert!(names(&NamePattern::glob("lib[!s]sl-*")).is_empty());
+        assert_eq!(
+            names(&NamePattern::Exact("python3".to_string())),
+            vec!["python3"]
+        );
+
+        #[cfg(feature = "regex")]
+        {
+            assert_eq!(
+                names(&NamePattern::regex("-dev$").unwrap()),
+                vec!["libssl-dev"]
+            );
+            assert!(NamePattern::regex("(").is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(any(feature = "repo-apk", feature = "repo-arch"))]
+    fn test_parse_inline_dependency() {
+        let dep = parse_inline_dependency("glibc>=2.38");
+        assert_eq!(dep.name, "glibc");
+        assert_eq!(
+            dep.constraint,
+            Some(VersionConstraint {
+                op: VersionOp::Ge,
+                version: "2.38".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_inline_dependency("so:libc.musl-x86_64.so.1").constraint,
+            None
+        );
+    }
+}
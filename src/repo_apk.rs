@@ -0,0 +1,332 @@
+//! Support for Alpine (apk) repository indexes.
+//!
+//! An Alpine repository publishes `APKINDEX.tar.gz` per branch, repository
+//! and architecture. The archive contains a plain text `APKINDEX` file where
+//! each package is a block of `X:value` lines, separated by blank lines.
+use std::io::{BufRead, BufReader, Read};
+
+use crate::repo::{join_url, parse_inline_dependency, Dependency, Repo, RepoPackage};
+use crate::version::compare_apk;
+use crate::PkgError;
+
+/// A package as described by an `APKINDEX`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ApkPackage {
+    /// Package name (`P:`).
+    pub name: String,
+
+    /// Package version, including the `-rN` package release (`V:`).
+    pub version: String,
+
+    /// Package architecture (`A:`).
+    pub arch: Option<String>,
+
+    /// Package checksum (`C:`), usually `Q1` followed by a base64 SHA1.
+    pub checksum: Option<String>,
+
+    /// Size of the package file in bytes (`S:`).
+    pub size: Option<u64>,
+
+    /// Installed size of the package in bytes (`I:`).
+    pub installed_size: Option<u64>,
+
+    /// One line description (`T:`).
+    pub description: Option<String>,
+
+    /// Project URL (`U:`).
+    pub url: Option<String>,
+
+    /// License (`L:`).
+    pub license: Option<String>,
+
+    /// The origin (source) package name (`o:`).
+    pub origin: Option<String>,
+
+    /// Dependencies (`D:`).
+    pub depends: Vec<String>,
+
+    /// Provided names (`p:`).
+    pub provides: Vec<String>,
+}
+
+impl ApkPackage {
+    /// Get the file name of the package, `<name>-<version>.apk`.
+    pub fn filename(&self) -> String {
+        format!("{}-{}.apk", self.name, self.version)
+    }
+}
+
+/// Parse the text of an `APKINDEX` file.
+pub fn parse_apkindex<R: BufRead>(reader: R) -> Result<Vec<ApkPackage>, PkgError> {
+    let mut packages = Vec::new();
+    let mut current = ApkPackage::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            finish_package(&mut packages, &mut current)?;
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| PkgError::InvalidRepoData(format!("invalid APKINDEX line: {}", line)))?;
+        let value = value.to_owned();
+
+        match key {
+            "P" => current.name = value,
+            "V" => current.version = value,
+            "A" => current.arch = Some(value),
+            "C" => current.checksum = Some(value),
+            "S" => current.size = value.parse().ok(),
+            "I" => current.installed_size = value.parse().ok(),
+            "T" => current.description = Some(value),
+            "U" => current.url = Some(value),
+            "L" => current.license = Some(value),
+            "o" => current.origin = Some(value),
+            "D" => current.depends = value.split_whitespace().map(str::to_owned).collect(),
+            "p" => current.provides = value.split_whitespace().map(str::to_owned).collect(),
+            // Other fields (maintainer, build time, commit...) are ignored.
+            _ => {}
+        }
+    }
+    finish_package(&mut packages, &mut current)?;
+
+    Ok(packages)
+}
+
+fn finish_package(
+    packages: &mut Vec<ApkPackage>,
+    current: &mut ApkPackage,
+) -> Result<(), PkgError> {
+    if *current == ApkPackage::default() {
+        return Ok(());
+    }
+
+    let package = std::mem::take(current);
+    if package.name.is_empty() || package.version.is_empty() {
+        return Err(PkgError::InvalidRepoData(
+            "APKINDEX entry is missing P or V".to_string(),
+        ));
+    }
+    packages.push(package);
+    Ok(())
+}
+
+/// Read the packages from an `APKINDEX.tar.gz` archive.
+///
+/// The archive is usually a signature archive concatenated with the index
+/// archive, so all gzip members are decompressed.
+pub fn read_apkindex_archive<R: Read>(reader: R) -> Result<Vec<ApkPackage>, PkgError> {
+    let decoder = flate2::read::MultiGzDecoder::new(reader);
+    let mut archive = tar::Archive::new(decoder);
+
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.path()?.as_os_str() == "APKINDEX" {
+            return parse_apkindex(BufReader::new(entry));
+        }
+    }
+
+    Err(PkgError::RepoDataNotFound("APKINDEX".to_string()))
+}
+
+/// A package resolved from an Alpine repository, ready to be fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedApkPackage {
+    /// The index entry for the package.
+    pub package: ApkPackage,
+
+    /// The full download URL of the package.
+    pub url: String,
+}
+
+/// A remote Alpine repository on a given mirror, branch and architecture.
+#[derive(Debug, Clone)]
+pub struct AlpineRepo {
+    base_url: String,
+}
+
+impl AlpineRepo {
+    /// Create a handle on an Alpine repository.
+    ///
+    /// For example, `AlpineRepo::new("https://dl-cdn.alpinelinux.org/alpine",
+    /// "v3.19", "main", "x86_64")`.
+    pub fn new(mirror: &str, branch: &str, repository: &str, arch: &str) -> Self {
+        Self {
+            base_url: format!(
+                "{}/{}/{}/{}",
+                mirror.trim_end_matches('/'),
+                branch,
+                repository,
+                arch
+            ),
+        }
+    }
+
+    /// Get the URL of the directory holding the index and packages.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the full download URL for a package in this repository.
+    pub fn package_url(&self, package: &ApkPackage) -> String {
+        join_url(&self.base_url, &package.filename())
+    }
+
+    /// Fetch and parse the repository's `APKINDEX.tar.gz`.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn packages(&self) -> Result<Vec<ApkPackage>, PkgError> {
+        let response = crate::http::get(&join_url(&self.base_url, "APKINDEX.tar.gz"))?;
+        read_apkindex_archive(response)
+    }
+
+    /// Look up a package by name and resolve its download URL.
+    #[cfg(feature = "http-blocking")]
+    pub fn resolve(&self, name: &str) -> Result<Option<ResolvedApkPackage>, PkgError> {
+        Ok(self.select(name, self.packages()?))
+    }
+
+    #[cfg(any(feature = "http-blocking", test))]
+    fn select(&self, name: &str, packages: Vec<ApkPackage>) -> Option<ResolvedApkPackage> {
+        packages
+            .into_iter()
+            .find(|p| p.name == name)
+            .map(|package| ResolvedApkPackage {
+                url: self.package_url(&package),
+                package,
+            })
+    }
+}
+
+impl RepoPackage for ApkPackage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn full_version(&self) -> String {
+        self.version.clone()
+    }
+
+    fn arch(&self) -> Option<&str> {
+        self.arch.as_deref()
+    }
+
+    fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+        compare_apk(a, b)
+    }
+
+    /// Conflicts (`!name`) are left out.
+    fn depends(&self) -> Vec<Vec<Dependency>> {
+        self.depends
+            .iter()
+            .filter(|dep| !dep.starts_with('!'))
+            .map(|dep| vec![parse_inline_dependency(dep)])
+            .collect()
+    }
+
+    fn provides(&self) -> Vec<Dependency> {
+        self.provides
+            .iter()
+            .map(|provide| parse_inline_dependency(provide))
+            .collect()
+    }
+
+    fn download_size(&self) -> Option<u64> {
+        self.size
+    }
+
+    fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+}
+
+impl Repo for AlpineRepo {
+    type Package = ApkPackage;
+
+    #[cfg(feature = "http-blocking")]
+    fn list_packages(&self) -> Result<Vec<ApkPackage>, PkgError> {
+        self.packages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APKINDEX: &str = "C:Q1m1YF9YJZ5bLtW3Wxsc4OU2E2Jz8=
+P:musl
+V:1.2.4_git20230717-r4
+A:x86_64
+S:407000
+I:636000
+T:the musl c library (libc) implementation
+U:https://musl.libc.org/
+L:MIT
+o:musl
+p:so:libc.musl-x86_64.so.1=1
+
+C:Q1AbCdEf0123456789abcdefABCDEF0=
+P:curl
+V:8.5.0-r0
+A:x86_64
+S:200000
+T:URL retrieval utility and library
+L:curl
+D:ca-certificates so:libc.musl-x86_64.so.1 so:libcurl.so.4
+";
+
+    fn archive() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [("DESCRIPTION", "v3.19.0\n"), ("APKINDEX", APKINDEX)] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_bytes())
+                .unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &tar).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parse_apkindex() {
+        let packages = parse_apkindex(APKINDEX.as_bytes()).unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "musl");
+        assert_eq!(packages[0].provides, vec!["so:libc.musl-x86_64.so.1=1"]);
+        assert_eq!(packages[1].version, "8.5.0-r0");
+        assert_eq!(packages[1].depends.len(), 3);
+        assert_eq!(packages[1].size, Some(200000));
+    }
+
+    #[test]
+    fn test_archive() {
+        let packages = read_apkindex_archive(&archive()[..]).unwrap();
+        let repo = AlpineRepo::new(
+            "https://dl-cdn.alpinelinux.org/alpine/",
+            "v3.19",
+            "main",
+            "x86_64",
+        );
+
+        let curl = repo.select("curl", packages).unwrap();
+        assert_eq!(
+            curl.url,
+            "https://dl-cdn.alpinelinux.org/alpine/v3.19/main/x86_64/curl-8.5.0-r0.apk"
+        );
+    }
+
+    #[test]
+    fn test_invalid_entry() {
+        assert!(parse_apkindex("P:nameonly\n".as_bytes()).is_err());
+    }
+}
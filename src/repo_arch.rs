@@ -0,0 +1,319 @@
+//! Support for Arch Linux (pacman) repository databases.
+//!
+//! A pacman repository publishes `<repo>.db`, a compressed tar archive with
+//! a directory per package. Each directory holds a `desc` file made up of
+//! `%SECTION%` headers, each followed by one or more value lines.
+use std::io::{BufRead, BufReader, Read};
+
+use crate::compression::Compression;
+use crate::repo::{join_url, parse_inline_dependency, Dependency, Repo, RepoPackage};
+use crate::version::compare_rpm;
+use crate::PkgError;
+
+/// A package as described by a pacman repository database.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PacmanPackage {
+    /// Package file name (`%FILENAME%`).
+    pub filename: String,
+
+    /// Package name (`%NAME%`).
+    pub name: String,
+
+    /// Package version including the pkgrel (`%VERSION%`).
+    pub version: String,
+
+    /// Package description (`%DESC%`).
+    pub description: Option<String>,
+
+    /// Package architecture (`%ARCH%`).
+    pub arch: Option<String>,
+
+    /// Size of the package file in bytes (`%CSIZE%`).
+    pub compressed_size: Option<u64>,
+
+    /// Installed size of the package in bytes (`%ISIZE%`).
+    pub installed_size: Option<u64>,
+
+    /// SHA256 checksum of the package file (`%SHA256SUM%`).
+    pub sha256: Option<String>,
+
+    /// Project URL (`%URL%`).
+    pub url: Option<String>,
+
+    /// Licenses (`%LICENSE%`).
+    pub licenses: Vec<String>,
+
+    /// Dependencies (`%DEPENDS%`).
+    pub depends: Vec<String>,
+
+    /// Provided names (`%PROVIDES%`).
+    pub provides: Vec<String>,
+}
+
+/// Parse the contents of a package's `desc` file.
+pub fn parse_desc<R: BufRead>(reader: R) -> Result<PacmanPackage, PkgError> {
+    let mut package = PacmanPackage::default();
+    let mut section: Option<String> = None;
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            // Sections are separated by blank lines.
+            section = None;
+            continue;
+        }
+
+        if section.is_none() && line.starts_with('%') && line.ends_with('%') && line.len() > 1 {
+            section = Some(line[1..line.len() - 1].to_owned());
+            continue;
+        }
+
+        let value = line;
+        match section.as_deref() {
+            Some("FILENAME") => package.filename = value,
+            Some("NAME") => package.name = value,
+            Some("VERSION") => package.version = value,
+            Some("DESC") => package.description = Some(value),
+            Some("ARCH") => package.arch = Some(value),
+            Some("CSIZE") => package.compressed_size = value.parse().ok(),
+            Some("ISIZE") => package.installed_size = value.parse().ok(),
+            Some("SHA256SUM") => package.sha256 = Some(value),
+            Some("URL") => package.url = Some(value),
+            Some("LICENSE") => package.licenses.push(value),
+            Some("DEPENDS") => package.depends.push(value),
+            Some("PROVIDES") => package.provides.push(value),
+            Some(_) => {}
+            None => {
+                return Err(PkgError::InvalidRepoData(format!(
+                    "value outside of a section in desc: {}",
+                    value
+                )))
+            }
+        }
+    }
+
+    if package.name.is_empty() || package.version.is_empty() || package.filename.is_empty() {
+        return Err(PkgError::InvalidRepoData(
+            "desc is missing NAME, VERSION or FILENAME".to_string(),
+        ));
+    }
+
+    Ok(package)
+}
+
+/// Read the packages from a (possibly compressed) pacman repository
+/// database.
+pub fn read_database<R: Read>(reader: R) -> Result<Vec<PacmanPackage>, PkgError> {
+    let decoder = Compression::detect_decoder(reader)?;
+    let mut archive = tar::Archive::new(decoder);
+
+    let mut packages = Vec::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let is_desc = entry.path()?.file_name().is_some_and(|name| name == "desc");
+        if is_desc {
+            packages.push(parse_desc(BufReader::new(entry))?);
+        }
+    }
+
+    Ok(packages)
+}
+
+/// A package resolved from a pacman repository, ready to be fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedPacmanPackage {
+    /// The database entry for the package.
+    pub package: PacmanPackage,
+
+    /// The full download URL of the package.
+    pub url: String,
+}
+
+/// A remote pacman repository on a given mirror and architecture.
+#[derive(Debug, Clone)]
+pub struct PacmanRepo {
+    base_url: String,
+    #[cfg_attr(not(feature = "http-blocking"), allow(dead_code))]
+    repo: String,
+}
+
+impl PacmanRepo {
+    /// Create a handle on a pacman repository using the standard
+    /// `$repo/os/$arch` mirror layout.
+    ///
+    /// For example, `PacmanRepo::new("https://geo.mirror.pkgbuild.com",
+    /// "core", "x86_64")`.
+    pub fn new(mirror: &str, repo: &str, arch: &str) -> Self {
+        Self {
+            base_url: format!("{}/{}/os/{}", mirror.trim_end_matches('/'), repo, arch),
+            repo: repo.to_owned(),
+        }
+    }
+
+    /// Get the URL of the directory holding the database and packages.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the full download URL for a package in this repository.
+    pub fn package_url(&self, package: &PacmanPackage) -> String {
+        join_url(&self.base_url, &package.filename)
+    }
+
+    /// Fetch and parse the repository database.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn packages(&self) -> Result<Vec<PacmanPackage>, PkgError> {
+        let db = format!("{}.db", self.repo);
+        let response = crate::http::get(&join_url(&self.base_url, &db))?;
+        read_database(response)
+    }
+
+    /// Look up a package by name and resolve its download URL.
+    #[cfg(feature = "http-blocking")]
+    pub fn resolve(&self, name: &str) -> Result<Option<ResolvedPacmanPackage>, PkgError> {
+        Ok(self.select(name, self.packages()?))
+    }
+
+    #[cfg(any(feature = "http-blocking", test))]
+    fn select(&self, name: &str, packages: Vec<PacmanPackage>) -> Option<ResolvedPacmanPackage> {
+        packages
+            .into_iter()
+            .find(|p| p.name == name)
+            .map(|package| ResolvedPacmanPackage {
+                url: self.package_url(&package),
+                package,
+            })
+    }
+}
+
+/// Pacman's `vercmp` orders `epoch:version-pkgrel` versions the same way as
+/// rpm.
+impl RepoPackage for PacmanPackage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn full_version(&self) -> String {
+        self.version.clone()
+    }
+
+    fn arch(&self) -> Option<&str> {
+        self.arch.as_deref()
+    }
+
+    fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+        compare_rpm(a, b)
+    }
+
+    fn depends(&self) -> Vec<Vec<Dependency>> {
+        self.depends
+            .iter()
+            .map(|dep| vec![parse_inline_dependency(dep)])
+            .collect()
+    }
+
+    fn provides(&self) -> Vec<Dependency> {
+        self.provides
+            .iter()
+            .map(|provide| parse_inline_dependency(provide))
+            .collect()
+    }
+
+    fn download_size(&self) -> Option<u64> {
+        self.compressed_size
+    }
+
+    fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+}
+
+impl Repo for PacmanRepo {
+    type Package = PacmanPackage;
+
+    #[cfg(feature = "http-blocking")]
+    fn list_packages(&self) -> Result<Vec<PacmanPackage>, PkgError> {
+        self.packages()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESC: &str = "%FILENAME%
+zlib-1:1.3.1-1-x86_64.pkg.tar.zst
+
+%NAME%
+zlib
+
+%VERSION%
+1:1.3.1-1
+
+%DESC%
+Compression library implementing the deflate compression method found in gzip and PKZIP
+
+%CSIZE%
+92000
+
+%SHA256SUM%
+abcd
+
+%ARCH%
+x86_64
+
+%LICENSE%
+Zlib
+
+%DEPENDS%
+glibc
+
+%PROVIDES%
+libz.so=1-64
+";
+
+    fn database() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, data) in [("zlib-1:1.3.1-1/desc", DESC)] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, data.as_bytes())
+                .unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut encoder, &tar).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_parse_desc() {
+        let package = parse_desc(DESC.as_bytes()).unwrap();
+        assert_eq!(package.name, "zlib");
+        assert_eq!(package.version, "1:1.3.1-1");
+        assert_eq!(package.compressed_size, Some(92000));
+        assert_eq!(package.licenses, vec!["Zlib"]);
+        assert_eq!(package.provides, vec!["libz.so=1-64"]);
+
+        assert!(parse_desc("%NAME%\nzlib\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_database() {
+        let packages = read_database(&database()[..]).unwrap();
+        let repo = PacmanRepo::new("https://geo.mirror.pkgbuild.com/", "core", "x86_64");
+
+        let zlib = repo.select("zlib", packages).unwrap();
+        assert_eq!(
+            zlib.url,
+            "https://geo.mirror.pkgbuild.com/core/os/x86_64/zlib-1:1.3.1-1-x86_64.pkg.tar.zst"
+        );
+    }
+}
@@ -0,0 +1,1615 @@
+//! Support for apt (Debian/Ubuntu) repository indexes.
+//!
+//! An apt repository publishes a `Packages` index per distribution,
+//! component and architecture, under
+//! `dists/<dist>/<component>/binary-<arch>/`. Each paragraph of the index
+//! describes one package and where to download it from the pool.
+//!
+//! Repositories may also publish `Contents-<arch>` indexes, mapping every
+//! file shipped in the repository to the packages that contain it, and
+//! `i18n/Translation-<lang>` files with the descriptions of the packages in
+//! other languages.
+//!
+//! The `Release` file of each distribution lists the checksums of its
+//! indexes. Given a keyring, [`AptRepo`] checks the signature of the
+//! `Release` file and the checksum of every index it fetches.
+#[cfg(all(feature = "verify", feature = "http-blocking"))]
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+#[cfg(all(feature = "verify", feature = "http-blocking"))]
+use std::rc::Rc;
+#[cfg(all(feature = "verify", feature = "http-blocking"))]
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+#[cfg(feature = "verify")]
+use sha2::{Digest, Sha256};
+
+use crate::arch::MultiArch;
+#[cfg(all(feature = "debian", feature = "checksum"))]
+use crate::checksum::{parse_checked, Algorithm};
+use crate::compression::Compression;
+use crate::deb822::{Deb822Reader, Paragraph};
+#[cfg(all(feature = "verify", feature = "http-blocking"))]
+use crate::repo::SharedReader;
+use crate::repo::{join_url, Check, Dependency, Repo, RepoPackage, VersionConstraint, VersionOp};
+#[cfg(feature = "verify")]
+use crate::verify::{Keyring, TrustPolicy};
+use crate::version::compare_debian;
+use crate::PkgError;
+
+/// A package as described by an apt `Packages` index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AptPackage {
+    paragraph: Paragraph,
+}
+
+impl AptPackage {
+    fn from_paragraph(paragraph: Paragraph) -> Result<Self, PkgError> {
+        // Reject entries which can't be used to fetch anything.
+        for field in &["Package", "Version", "Filename"] {
+            if paragraph.get(field).is_none() {
+                return Err(PkgError::InvalidRepoData(format!(
+                    "Packages entry is missing {}",
+                    field
+                )));
+            }
+        }
+        Ok(Self { paragraph })
+    }
+
+    /// Get the package name.
+    pub fn name(&self) -> &str {
+        self.paragraph.get("Package").unwrap_or_default()
+    }
+
+    /// Get the package version.
+    pub fn version(&self) -> &str {
+        self.paragraph.get("Version").unwrap_or_default()
+    }
+
+    /// Get the package architecture.
+    pub fn architecture(&self) -> Option<&str> {
+        self.paragraph.get("Architecture")
+    }
+
+    /// Get the `Multi-Arch` field; see [`arch`](crate::arch).
+    pub fn multi_arch(&self) -> Result<MultiArch, PkgError> {
+        MultiArch::from_field(self.paragraph.get("Multi-Arch"))
+    }
+
+    /// Get the location of the package relative to the repository base URL.
+    pub fn filename(&self) -> &str {
+        self.paragraph.get("Filename").unwrap_or_default()
+    }
+
+    /// Get the size of the package file in bytes.
+    pub fn size(&self) -> Option<u64> {
+        self.paragraph.get("Size").and_then(|s| s.parse().ok())
+    }
+
+    /// Get the SHA256 checksum of the package file.
+    pub fn sha256(&self) -> Option<&str> {
+        self.paragraph.get("SHA256")
+    }
+
+    /// Get the value of any field in the index entry. The field name is
+    /// case insensitive.
+    pub fn get(&self, field_name: &str) -> Option<&str> {
+        self.paragraph.get(field_name)
+    }
+
+    /// Get the untranslated description. Many indexes only carry the first
+    /// line, leaving the rest to `Translation-en`.
+    pub fn description(&self) -> Option<&str> {
+        self.paragraph.get("Description")
+    }
+
+    /// Get the description from `translations`, such as those fetched with
+    /// [`AptRepo::translations`], or else the untranslated description.
+    pub fn description_localized<'a>(&'a self, translations: &'a Translations) -> Option<&'a str> {
+        translations.get(self).or_else(|| self.description())
+    }
+}
+
+/// The package descriptions of a `Translation-<lang>` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Translations {
+    lang: Option<String>,
+
+    /// The descriptions of each package, with their `Description-md5`.
+    descriptions: HashMap<String, Vec<(String, String)>>,
+}
+
+impl Translations {
+    /// Read an uncompressed `Translation-<lang>` file for `lang`, e.g. `de`.
+    pub fn read<R: BufRead>(lang: &str, reader: R) -> Result<Self, PkgError> {
+        let field = format!("Description-{}", lang);
+        let mut descriptions: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for paragraph in Deb822Reader::new(reader) {
+            let paragraph = paragraph?;
+            let (name, description) = match (paragraph.get("Package"), paragraph.get(&field)) {
+                (Some(name), Some(description)) => (name, description),
+                _ => continue,
+            };
+            let md5 = paragraph.get("Description-md5").unwrap_or_default();
+            descriptions
+                .entry(name.to_owned())
+                .or_default()
+                .push((md5.to_owned(), description.to_owned()));
+        }
+        Ok(Self {
+            lang: Some(lang.to_owned()),
+            descriptions,
+        })
+    }
+
+    /// The language of the descriptions, or `None` if there are none.
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+
+    /// Get the translated description of a package. When the index gives
+    /// the `Description-md5` of the description, only a translation of that
+    /// description matches, so translations of older versions are skipped.
+    pub fn get(&self, package: &AptPackage) -> Option<&str> {
+        let descriptions = self.descriptions.get(package.name())?;
+        let found = match package.get("Description-md5") {
+            Some(md5) => descriptions.iter().find(|(known, _)| known == md5),
+            None => descriptions.first(),
+        };
+        found.map(|(_, description)| description.as_str())
+    }
+}
+
+/// A streaming reader over the packages in a `Packages` index.
+pub struct PackagesReader<R: BufRead> {
+    reader: Deb822Reader<R>,
+    check: Option<Check>,
+}
+
+impl<R: BufRead> PackagesReader<R> {
+    /// Create a reader over uncompressed `Packages` data.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Deb822Reader::new(reader),
+            check: None,
+        }
+    }
+
+    /// Run `check`, if any, once the end of the index is reached, yielding
+    /// its error as the last item.
+    #[cfg(feature = "http-blocking")]
+    fn with_check(mut self, check: Option<Check>) -> Self {
+        self.check = check;
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for PackagesReader<R> {
+    type Item = Result<AptPackage, PkgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.next() {
+            Some(paragraph) => Some(paragraph.and_then(AptPackage::from_paragraph)),
+            None => self.check.take().and_then(|check| check().err()).map(Err),
+        }
+    }
+}
+
+/// Wrap a (possibly compressed) `Packages` stream in a `PackagesReader`,
+/// using `path` to work out the compression.
+pub fn open_packages<'a, R: Read + 'a>(
+    path: &str,
+    reader: R,
+) -> Result<PackagesReader<BufReader<Box<dyn Read + 'a>>>, PkgError> {
+    let decoder = Compression::from_path(path).decoder(reader)?;
+    Ok(PackagesReader::new(BufReader::new(decoder)))
+}
+
+/// A line of a `Contents` index: a file and the packages shipping it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentsEntry {
+    /// The path of the file, without a leading `/`.
+    pub path: String,
+
+    /// The packages shipping the file, qualified by section, e.g.
+    /// `admin/curl` or `universe/web/wget`.
+    pub locations: Vec<String>,
+}
+
+impl ContentsEntry {
+    fn parse(line: &str) -> Result<Self, PkgError> {
+        // Paths may contain spaces, so the location list is the last column.
+        let (path, locations) = line
+            .trim_end()
+            .rsplit_once(|c: char| c.is_whitespace())
+            .ok_or_else(|| PkgError::InvalidRepoData(format!("invalid Contents line: {}", line)))?;
+
+        Ok(Self {
+            path: path.trim_end().trim_start_matches('/').to_owned(),
+            locations: locations.split(',').map(str::to_owned).collect(),
+        })
+    }
+
+    /// Get the names of the packages shipping the file.
+    pub fn packages(&self) -> impl Iterator<Item = &str> {
+        self.locations
+            .iter()
+            .map(|location| location.rsplit('/').next().unwrap_or_default())
+    }
+}
+
+/// The number of lines to look through for the header of older `Contents`
+/// indexes.
+const CONTENTS_HEADER_LINES: usize = 64;
+
+/// A streaming reader over the entries in a `Contents` index.
+pub struct ContentsReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    pending: VecDeque<String>,
+    started: bool,
+    check: Option<Check>,
+}
+
+impl<R: BufRead> ContentsReader<R> {
+    /// Create a reader over uncompressed `Contents` data.
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            pending: VecDeque::new(),
+            started: false,
+            check: None,
+        }
+    }
+
+    /// Run `check`, if any, once the end of the index is reached, yielding
+    /// its error as the last item.
+    #[cfg(feature = "http-blocking")]
+    fn with_check(mut self, check: Option<Check>) -> Self {
+        self.check = check;
+        self
+    }
+
+    /// Skip the free-form header of older indexes, which ends with a
+    /// `FILE  LOCATION` line. Newer indexes have no header, in which case
+    /// the lines read ahead are kept.
+    fn skip_header(&mut self) -> Result<(), PkgError> {
+        while self.pending.len() < CONTENTS_HEADER_LINES {
+            let line = match self.lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            let mut columns = line.split_whitespace();
+            if columns.next() == Some("FILE") && columns.next() == Some("LOCATION") {
+                self.pending.clear();
+                return Ok(());
+            }
+            self.pending.push_back(line);
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Iterator for ContentsReader<R> {
+    type Item = Result<ContentsEntry, PkgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if let Err(e) = self.skip_header() {
+                return Some(Err(e));
+            }
+        }
+
+        loop {
+            let line = match self.pending.pop_front() {
+                Some(line) => line,
+                None => match self.lines.next() {
+                    Some(Ok(line)) => line,
+                    Some(Err(e)) => return Some(Err(e.into())),
+                    None => return self.check.take().and_then(|check| check().err()).map(Err),
+                },
+            };
+            if !line.trim().is_empty() {
+                return Some(ContentsEntry::parse(&line));
+            }
+        }
+    }
+}
+
+/// Wrap a (possibly compressed) `Contents` stream in a `ContentsReader`,
+/// using `path` to work out the compression.
+pub fn open_contents<'a, R: Read + 'a>(
+    path: &str,
+    reader: R,
+) -> Result<ContentsReader<BufReader<Box<dyn Read + 'a>>>, PkgError> {
+    let decoder = Compression::from_path(path).decoder(reader)?;
+    Ok(ContentsReader::new(BufReader::new(decoder)))
+}
+
+/// The host serving Launchpad PPAs.
+pub const LAUNCHPAD_PPA_URL: &str = "https://ppa.launchpadcontent.net";
+
+/// An index listed in a `Release` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReleaseFile {
+    /// The path of the index, relative to the distribution directory, e.g.
+    /// `main/binary-amd64/Packages.gz`.
+    pub path: String,
+
+    /// The size of the index in bytes.
+    pub size: u64,
+
+    /// The SHA256 checksum of the index.
+    pub sha256: String,
+}
+
+impl ReleaseFile {
+    /// Check the size and SHA256 checksum of a download of the index.
+    #[cfg(feature = "verify")]
+    fn check(&self, size: u64, sha256: &str) -> Result<(), PkgError> {
+        if self.size != size {
+            return Err(PkgError::SizeMismatch {
+                expected: self.size,
+                actual: size,
+            });
+        }
+        if !sha256.eq_ignore_ascii_case(&self.sha256) {
+            return Err(PkgError::ChecksumMismatch {
+                algorithm: "sha256".to_string(),
+                expected: self.sha256.clone(),
+                actual: sha256.to_owned(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The `Release` file of an apt distribution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AptRelease {
+    paragraph: Paragraph,
+    files: Vec<ReleaseFile>,
+}
+
+impl AptRelease {
+    /// Parse an (unsigned) `Release` file.
+    pub fn parse(text: &str) -> Result<Self, PkgError> {
+        let paragraph = Deb822Reader::new(text.as_bytes())
+            .next()
+            .ok_or_else(|| PkgError::InvalidRepoData("empty Release file".to_string()))??;
+
+        let invalid =
+            |line: &str| PkgError::InvalidRepoData(format!("invalid Release entry: {}", line));
+        let mut files = Vec::new();
+        for line in paragraph.get("SHA256").unwrap_or_default().lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                [] => continue,
+                [sha256, size, path] => files.push(ReleaseFile {
+                    path: (*path).to_owned(),
+                    size: size.parse().map_err(|_| invalid(line))?,
+                    sha256: (*sha256).to_owned(),
+                }),
+                _ => return Err(invalid(line)),
+            }
+        }
+
+        Ok(Self { paragraph, files })
+    }
+
+    /// Get the distribution codename, e.g. `jammy`.
+    pub fn codename(&self) -> Option<&str> {
+        self.paragraph.get("Codename")
+    }
+
+    /// Get the distribution suite, e.g. `stable`.
+    pub fn suite(&self) -> Option<&str> {
+        self.paragraph.get("Suite")
+    }
+
+    /// Get the indexes listed with SHA256 checksums.
+    pub fn files(&self) -> &[ReleaseFile] {
+        &self.files
+    }
+
+    /// Get the entry for an index, by its path relative to the distribution
+    /// directory.
+    pub fn file(&self, path: &str) -> Option<&ReleaseFile> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    /// Get the value of any field in the file. The field name is case
+    /// insensitive.
+    pub fn get(&self, field_name: &str) -> Option<&str> {
+        self.paragraph.get(field_name)
+    }
+
+    /// Check the contents of an index against its size and checksum. Indexes
+    /// that aren't listed are rejected, as they can't be trusted.
+    #[cfg(feature = "verify")]
+    pub fn check(&self, path: &str, data: &[u8]) -> Result<(), PkgError> {
+        let file = self
+            .file(path)
+            .ok_or_else(|| PkgError::RepoDataNotFound(format!("{} is not in Release", path)))?;
+        file.check(data.len() as u64, &format!("{:x}", Sha256::digest(data)))
+    }
+
+    /// Get the time after which the file is out of date, from its
+    /// `Valid-Until` field.
+    pub fn valid_until(&self) -> Result<Option<SystemTime>, PkgError> {
+        let value = match self.get("Valid-Until") {
+            Some(value) => value.trim(),
+            None => return Ok(None),
+        };
+        // Release files give times in UTC, which HTTP dates call GMT.
+        let date = match value.strip_suffix("UTC") {
+            Some(date) => format!("{}GMT", date),
+            None => value.to_owned(),
+        };
+        httpdate::parse_http_date(&date)
+            .map(Some)
+            .map_err(|_| PkgError::InvalidRepoData(format!("invalid Valid-Until: {}", value)))
+    }
+
+    /// Check that the file is for the distribution `dist`, by its `Suite`
+    /// or `Codename`, and that it was still valid at `now`. This stops a
+    /// mirror from serving an old or different distribution's signed
+    /// `Release` file in place of the current one.
+    pub fn check_current(&self, dist: &str, now: SystemTime) -> Result<(), PkgError> {
+        if (self.suite().is_some() || self.codename().is_some())
+            && self.suite() != Some(dist)
+            && self.codename() != Some(dist)
+        {
+            return Err(PkgError::InvalidRepoData(format!(
+                "Release is for {}, not {}",
+                self.codename().or(self.suite()).unwrap_or_default(),
+                dist
+            )));
+        }
+        if let Some(valid_until) = self.valid_until()? {
+            if now > valid_until {
+                return Err(PkgError::InvalidRepoData(format!(
+                    "Release expired at {}",
+                    self.get("Valid-Until").unwrap_or_default()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Wrap a download of an index so that it is hashed as it is read. The
+/// returned check reads the rest of the download and compares it against
+/// the index's `Release` entry.
+///
+/// No more than one byte past the listed size is read.
+#[cfg(all(feature = "verify", feature = "http-blocking"))]
+fn open_checked<R: Read + 'static>(file: ReleaseFile, reader: R) -> (Box<dyn Read>, Check) {
+    let download = Rc::new(RefCell::new(IndexDownload {
+        inner: reader.take(file.size.saturating_add(1)),
+        hasher: Sha256::new(),
+        bytes_read: 0,
+    }));
+    let reader: Box<dyn Read> = Box::new(SharedReader(Rc::clone(&download)));
+    let check = move || {
+        let mut download = download.borrow_mut();
+        let _ = std::io::copy(&mut *download, &mut std::io::sink())?;
+        file.check(
+            download.bytes_read,
+            &format!("{:x}", download.hasher.clone().finalize()),
+        )
+    };
+    (reader, Box::new(check))
+}
+
+/// A download of an index, hashed as it is read.
+#[cfg(all(feature = "verify", feature = "http-blocking"))]
+struct IndexDownload<R: Read> {
+    inner: std::io::Take<R>,
+    hasher: Sha256,
+    bytes_read: u64,
+}
+
+#[cfg(all(feature = "verify", feature = "http-blocking"))]
+impl<R: Read> Read for IndexDownload<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+/// A package resolved from an apt repository, ready to be fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedAptPackage {
+    /// The index entry for the package.
+    pub package: AptPackage,
+
+    /// The full download URL of the package.
+    pub url: String,
+}
+
+#[cfg(all(feature = "debian", feature = "checksum"))]
+impl ResolvedAptPackage {
+    /// Download the package and parse it, checking it against the size and
+    /// SHA256 checksum in its index entry; see
+    /// [`ResolvedAptPackage::read_checked`].
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn fetch(&self) -> Result<crate::debian::DebianRemotePackage, PkgError> {
+        self.read_checked(crate::http::get_package(&self.url)?)
+    }
+
+    /// Parse a package read from `reader`, checking it against the size and
+    /// SHA256 checksum in its index entry.
+    ///
+    /// The whole package is read, and fails with [`PkgError::SizeMismatch`]
+    /// or [`PkgError::ChecksumMismatch`] if it differs from the index. Together
+    /// with [`AptRepo::with_keyring`] this gives a trusted path from the
+    /// signed `Release` file to the package contents.
+    pub fn read_checked<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<crate::debian::DebianRemotePackage, PkgError> {
+        let checksum = self
+            .package
+            .sha256()
+            .map(|sha256| (Algorithm::Sha256, sha256));
+        parse_checked(reader, self.package.size(), checksum, |reader| {
+            crate::debian::DebianRemotePackage::new_from_read(reader)
+        })
+    }
+}
+
+/// A remote apt repository, restricted to one distribution, a set of
+/// components and one architecture.
+#[derive(Debug, Clone)]
+pub struct AptRepo {
+    base_url: String,
+    dist: String,
+    #[cfg_attr(not(feature = "http-blocking"), allow(dead_code))]
+    components: Vec<String>,
+    arch: String,
+
+    #[cfg(feature = "verify")]
+    keyring: Option<Keyring>,
+
+    #[cfg(feature = "verify")]
+    policy: TrustPolicy,
+
+    /// The `Release` file once it has been fetched and verified.
+    #[cfg(all(feature = "verify", feature = "http-blocking"))]
+    verified_release: OnceLock<AptRelease>,
+}
+
+impl AptRepo {
+    /// Create a handle on an apt repository.
+    ///
+    /// For example, `AptRepo::new("http://archive.ubuntu.com/ubuntu", "jammy",
+    /// &["main", "universe"], "amd64")`.
+    pub fn new<S: AsRef<str>>(base_url: &str, dist: &str, components: &[S], arch: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            dist: dist.to_owned(),
+            components: components.iter().map(|c| c.as_ref().to_owned()).collect(),
+            arch: arch.to_owned(),
+            #[cfg(feature = "verify")]
+            keyring: None,
+            #[cfg(feature = "verify")]
+            policy: TrustPolicy::default(),
+            #[cfg(all(feature = "verify", feature = "http-blocking"))]
+            verified_release: OnceLock::new(),
+        }
+    }
+
+    /// Only trust indexes listed in a `Release` file signed by a key in
+    /// `keyring`, e.g. one loaded from `/usr/share/keyrings`.
+    ///
+    /// The `Release` file is then fetched and verified once, and every
+    /// index is checked against its `Release` size and checksum as it is
+    /// read, with a mismatch reported as the last item read from it. A
+    /// package can in turn be checked against the `SHA256` field of its
+    /// index entry.
+    #[cfg(feature = "verify")]
+    pub fn with_keyring(mut self, keyring: Keyring) -> Self {
+        self.keyring = Some(keyring);
+        #[cfg(feature = "http-blocking")]
+        {
+            self.verified_release = OnceLock::new();
+        }
+        self
+    }
+
+    /// Set which `Release` signatures to accept when the repository has a
+    /// keyring. By default one signature by a key in the keyring is needed.
+    #[cfg(feature = "verify")]
+    pub fn with_trust_policy(mut self, policy: TrustPolicy) -> Self {
+        self.policy = policy;
+        #[cfg(feature = "http-blocking")]
+        {
+            self.verified_release = OnceLock::new();
+        }
+        self
+    }
+
+    /// Create a handle on a Launchpad PPA from a `ppa:owner/name`
+    /// specification, as accepted by `add-apt-repository`.
+    ///
+    /// `series` is the Ubuntu release codename, e.g. `jammy`. PPAs only have
+    /// a `main` component.
+    pub fn ppa(spec: &str, series: &str, arch: &str) -> Result<Self, PkgError> {
+        let invalid = || PkgError::InvalidRepoSpec(spec.to_owned());
+
+        let (owner, name) = spec
+            .strip_prefix("ppa:")
+            .and_then(|ppa| ppa.split_once('/'))
+            .ok_or_else(invalid)?;
+        if owner.is_empty() || name.is_empty() || name.contains('/') {
+            return Err(invalid());
+        }
+
+        let base_url = format!("{}/{}/{}/ubuntu", LAUNCHPAD_PPA_URL, owner, name);
+        Ok(Self::new(&base_url, series, &["main"], arch))
+    }
+
+    /// Get the repository base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the full download URL for a package in this repository.
+    pub fn package_url(&self, package: &AptPackage) -> String {
+        join_url(&self.base_url, package.filename())
+    }
+
+    /// Get the path of the `Packages` index for a component, relative to the
+    /// repository base URL.
+    pub fn packages_path(&self, component: &str) -> String {
+        format!(
+            "dists/{}/{}/binary-{}/Packages.gz",
+            self.dist, component, self.arch
+        )
+    }
+
+    /// Get the path of the `Contents` index for a component, relative to the
+    /// repository base URL.
+    pub fn contents_path(&self, component: &str) -> String {
+        format!(
+            "dists/{}/{}/Contents-{}.gz",
+            self.dist, component, self.arch
+        )
+    }
+
+    /// Get the path of the `Translation` file for a component and language,
+    /// e.g. `de`, relative to the repository base URL.
+    pub fn translation_path(&self, component: &str, lang: &str) -> String {
+        format!(
+            "dists/{}/{}/i18n/Translation-{}.bz2",
+            self.dist, component, lang
+        )
+    }
+
+    /// Fetch the `Release` file of the distribution.
+    ///
+    /// If the repository has a keyring, the signed `InRelease` file is
+    /// preferred, falling back to `Release` and its detached `Release.gpg`
+    /// signature if there is no `InRelease`. Signatures that don't meet the
+    /// repository's [`TrustPolicy`] are an error.
+    ///
+    /// A `Release` file for another distribution, or one past its
+    /// `Valid-Until` time, is an error; see [`AptRelease::check_current`].
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn release(&self) -> Result<AptRelease, PkgError> {
+        let release = self.fetch_release()?;
+        release.check_current(&self.dist, SystemTime::now())?;
+        Ok(release)
+    }
+
+    /// Fetch and parse the `Release` file for [`AptRepo::release`].
+    #[cfg(feature = "http-blocking")]
+    fn fetch_release(&self) -> Result<AptRelease, PkgError> {
+        let dist_url = join_url(&self.base_url, &format!("dists/{}", self.dist));
+
+        #[cfg(feature = "verify")]
+        if let Some(keyring) = &self.keyring {
+            match crate::http::get(&join_url(&dist_url, "InRelease")) {
+                Ok(response) => {
+                    let (text, statuses) = keyring.verify_cleartext_all(&response.text()?)?;
+                    self.policy.check(&statuses)?;
+                    return AptRelease::parse(&text);
+                }
+                Err(err) if err.kind() == crate::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+
+            let text = crate::http::get(&join_url(&dist_url, "Release"))?.text()?;
+            let signature = crate::http::get(&join_url(&dist_url, "Release.gpg"))?.text()?;
+            self.policy
+                .check(&keyring.verify_armored_all(&signature, text.as_bytes())?)?;
+            return AptRelease::parse(&text);
+        }
+
+        AptRelease::parse(&crate::http::get(&join_url(&dist_url, "Release"))?.text()?)
+    }
+
+    /// Get the verified `Release` file, fetching it the first time.
+    #[cfg(all(feature = "verify", feature = "http-blocking"))]
+    fn verified_release(&self) -> Result<&AptRelease, PkgError> {
+        if let Some(release) = self.verified_release.get() {
+            return Ok(release);
+        }
+        let release = self.release()?;
+        Ok(self.verified_release.get_or_init(|| release))
+    }
+
+    /// Fetch an index, along with a check of it against the signed `Release`
+    /// file to run once it has been read if the repository has a keyring.
+    #[cfg(feature = "http-blocking")]
+    fn fetch_index(&self, path: &str) -> Result<(Box<dyn Read>, Option<Check>), PkgError> {
+        #[cfg(feature = "verify")]
+        if self.keyring.is_some() {
+            // Release lists indexes relative to the distribution directory.
+            let prefix = format!("dists/{}/", self.dist);
+            let listed = path.strip_prefix(&prefix).unwrap_or(path);
+            let file = self
+                .verified_release()?
+                .file(listed)
+                .cloned()
+                .ok_or_else(|| {
+                    PkgError::RepoDataNotFound(format!("{} is not in Release", listed))
+                })?;
+
+            let response = crate::http::get(&join_url(&self.base_url, path))?;
+            let (reader, check) = open_checked(file, response);
+            return Ok((reader, Some(check)));
+        }
+
+        let response = crate::http::get(&join_url(&self.base_url, path))?;
+        Ok((Box::new(response), None))
+    }
+
+    /// Fetch the `Contents` index for a component and return a streaming
+    /// reader over its entries.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn contents(
+        &self,
+        component: &str,
+    ) -> Result<ContentsReader<BufReader<Box<dyn Read>>>, PkgError> {
+        let path = self.contents_path(component);
+        let (reader, check) = self.fetch_index(&path)?;
+        Ok(open_contents(&path, reader)?.with_check(check))
+    }
+
+    /// Find the packages shipping a file, e.g. `/usr/bin/curl`, across all
+    /// configured components. Returns an empty list if no package ships it.
+    #[cfg(feature = "http-blocking")]
+    pub fn find_file(&self, path: &str) -> Result<Vec<String>, PkgError> {
+        let path = path.trim_start_matches('/');
+        let mut packages = Vec::new();
+        for component in &self.components {
+            for entry in self.contents(component)? {
+                let entry = entry?;
+                if entry.path == path {
+                    packages.extend(entry.packages().map(str::to_owned));
+                }
+            }
+        }
+        Ok(packages)
+    }
+
+    /// Fetch the package descriptions of a component in the language
+    /// `lang`, e.g. `pt_BR.UTF-8`, from the first language of
+    /// [`language_fallbacks`](crate::language_fallbacks) the repository has
+    /// a `Translation` file for, or else from `Translation-en`, which holds
+    /// the full untranslated descriptions. If there is none of these, there
+    /// are no translations.
+    #[cfg(feature = "http-blocking")]
+    pub fn translations(&self, component: &str, lang: &str) -> Result<Translations, PkgError> {
+        let mut languages = crate::language_fallbacks(lang);
+        languages.push("en".to_owned());
+        for language in languages {
+            let path = self.translation_path(component, &language);
+            let (reader, check) = match self.fetch_index(&path) {
+                Ok(index) => index,
+                Err(err) if err.kind() == crate::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            let decoder = Compression::from_path(&path).decoder(reader)?;
+            let translations = Translations::read(&language, BufReader::new(decoder))?;
+            if let Some(check) = check {
+                check()?;
+            }
+            return Ok(translations);
+        }
+        Ok(Translations::default())
+    }
+
+    /// Fetch the `Packages` index for a component and return a streaming
+    /// reader over the packages it lists.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn packages(
+        &self,
+        component: &str,
+    ) -> Result<PackagesReader<BufReader<Box<dyn Read>>>, PkgError> {
+        let path = self.packages_path(component);
+        let (reader, check) = self.fetch_index(&path)?;
+        Ok(open_packages(&path, reader)?.with_check(check))
+    }
+
+    /// Find the newest version of the named package across all configured
+    /// components, using dpkg version ordering. The name is normalized with
+    /// [`naming::debian`](crate::naming::debian), so `Python3_Foo` finds
+    /// `python3-foo`.
+    ///
+    /// Packages built for this repository's architecture or for `all` are
+    /// considered. Returns `None` if no component contains the package.
+    #[cfg(feature = "http-blocking")]
+    pub fn latest(&self, name: &str) -> Result<Option<ResolvedAptPackage>, PkgError> {
+        let name = crate::naming::debian(name);
+        let mut candidates = Vec::new();
+        for component in &self.components {
+            for package in self.packages(component)? {
+                // Only keep matching entries, as indexes can be very large.
+                let package = package?;
+                if package.name() == name {
+                    candidates.push(package);
+                }
+            }
+        }
+        Ok(self.select_latest(&name, candidates))
+    }
+
+    /// Pick the newest version of the named package from a set of index
+    /// entries.
+    #[cfg(any(feature = "http-blocking", test))]
+    fn select_latest<I>(&self, name: &str, packages: I) -> Option<ResolvedAptPackage>
+    where
+        I: IntoIterator<Item = AptPackage>,
+    {
+        packages
+            .into_iter()
+            .filter(|p| p.name() == name)
+            .filter(|p| match p.architecture() {
+                Some(arch) => arch == self.arch || arch == "all",
+                None => true,
+            })
+            .max_by(|a, b| compare_debian(a.version(), b.version()))
+            .map(|package| ResolvedAptPackage {
+                url: self.package_url(&package),
+                package,
+            })
+    }
+}
+
+/// Parse a relationship field such as `Depends`, e.g.
+/// `libc6 (>= 2.34), libssl3 | libssl1.1`, into lists of alternatives.
+///
+/// Architecture qualifiers (`:any`) and build restrictions are dropped.
+fn parse_relations(field: &str) -> Vec<Vec<Dependency>> {
+    field
+        .split(',')
+        .map(|group| {
+            group
+                .split('|')
+                .filter_map(parse_relation)
+                .collect::<Vec<_>>()
+        })
+        .filter(|group| !group.is_empty())
+        .collect()
+}
+
+fn parse_relation(relation: &str) -> Option<Dependency> {
+    let relation = relation.trim();
+    let name_end = relation
+        .find(|c: char| c.is_whitespace() || "([<".contains(c))
+        .unwrap_or(relation.len());
+    let name = relation[..name_end].split(':').next().unwrap_or_default();
+    if name.is_empty() {
+        return None;
+    }
+
+    let constraint = relation[name_end..]
+        .trim_start()
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .and_then(|(constraint, _)| {
+            let constraint = constraint.trim();
+            // Check the two character operators first.
+            let ops = [
+                ("<<", VersionOp::Lt),
+                ("<=", VersionOp::Le),
+                (">=", VersionOp::Ge),
+                (">>", VersionOp::Gt),
+                ("=", VersionOp::Eq),
+                // Obsolete forms, which are inclusive.
+                ("<", VersionOp::Le),
+                (">", VersionOp::Ge),
+            ];
+            ops.iter().find_map(|(token, op)| {
+                constraint
+                    .strip_prefix(token)
+                    .map(|version| VersionConstraint {
+                        op: *op,
+                        version: version.trim().to_owned(),
+                    })
+            })
+        });
+
+    Some(Dependency {
+        name: name.to_owned(),
+        constraint,
+    })
+}
+
+impl RepoPackage for AptPackage {
+    fn name(&self) -> &str {
+        AptPackage::name(self)
+    }
+
+    fn full_version(&self) -> String {
+        self.version().to_owned()
+    }
+
+    fn arch(&self) -> Option<&str> {
+        self.architecture()
+    }
+
+    fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+        compare_debian(a, b)
+    }
+
+    /// Combines `Pre-Depends` and `Depends`.
+    fn depends(&self) -> Vec<Vec<Dependency>> {
+        ["Pre-Depends", "Depends"]
+            .iter()
+            .filter_map(|field| self.get(field))
+            .flat_map(parse_relations)
+            .collect()
+    }
+
+    fn recommends(&self) -> Vec<Vec<Dependency>> {
+        self.get("Recommends")
+            .map(parse_relations)
+            .unwrap_or_default()
+    }
+
+    fn download_size(&self) -> Option<u64> {
+        self.size()
+    }
+
+    /// `Installed-Size` is given in kibibytes.
+    fn installed_size(&self) -> Option<u64> {
+        self.get("Installed-Size")
+            .and_then(|size| size.parse::<u64>().ok())
+            .map(|kib| kib * 1024)
+    }
+
+    fn provides(&self) -> Vec<Dependency> {
+        self.get("Provides")
+            .map(parse_relations)
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
+
+impl Repo for AptRepo {
+    type Package = AptPackage;
+
+    /// Lists the packages of every configured component.
+    #[cfg(feature = "http-blocking")]
+    fn list_packages(&self) -> Result<Vec<AptPackage>, PkgError> {
+        let mut packages = Vec::new();
+        for component in &self.components {
+            for package in self.packages(component)? {
+                packages.push(package?);
+            }
+        }
+        Ok(packages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKAGES: &str = "Package: curl
+Architecture: amd64
+Version: 7.81.0-1ubuntu1.4
+Filename: pool/main/c/curl/curl_7.81.0-1ubuntu1.4_amd64.deb
+Size: 194000
+SHA256: 1111
+
+Package: curl
+Architecture: amd64
+Version: 7.81.0-1ubuntu1.15
+Filename: pool/main/c/curl/curl_7.81.0-1ubuntu1.15_amd64.deb
+Size: 194500
+SHA256: 2222
+Description: command line tool for transferring data with URL syntax
+ This is a long description.
+
+Package: curl
+Architecture: arm64
+Version: 8.0.0-1
+Filename: pool/main/c/curl/curl_8.0.0-1_arm64.deb
+
+Package: tzdata
+Architecture: all
+Multi-Arch: foreign
+Version: 2024a-0ubuntu0.22.04
+Filename: pool/main/t/tzdata/tzdata_2024a-0ubuntu0.22.04_all.deb
+";
+
+    fn packages() -> Vec<AptPackage> {
+        PackagesReader::new(PACKAGES.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_packages() {
+        let packages = packages();
+        assert_eq!(packages.len(), 4);
+        assert_eq!(packages[1].name(), "curl");
+        assert_eq!(packages[1].size(), Some(194500));
+        assert_eq!(packages[1].sha256(), Some("2222"));
+        assert_eq!(
+            packages[1].get("description"),
+            Some("command line tool for transferring data with URL syntax\nThis is a long description.")
+        );
+        assert_eq!(packages[1].multi_arch().unwrap(), MultiArch::No);
+        assert_eq!(packages[3].multi_arch().unwrap(), MultiArch::Foreign);
+    }
+
+    #[test]
+    fn test_translations() {
+        let translation = "Package: curl
+Description-md5: 0d6e8d4a7b7e6a5b3d6ff2f1d8a0d3c2
+Description-de: Werkzeug zum Übertragen von Daten mit URL-Syntax
+ Dies ist eine lange Beschreibung.
+
+Package: curl
+Description-md5: 5f1c3b0b2f1d7c3e8c0a5d0c6c2f0e5a
+Description-de: Eine ältere Beschreibung
+
+Package: tzdata
+Description-md5: 1a2b
+Description-de: Zeitzonen-Daten
+";
+        let translations = Translations::read("de", translation.as_bytes()).unwrap();
+        assert_eq!(translations.lang(), Some("de"));
+
+        let packages = PackagesReader::new(
+            "Package: curl
+Version: 7.81.0-1
+Filename: pool/main/c/curl/curl_7.81.0-1_amd64.deb
+Description: command line tool for transferring data with URL syntax
+Description-md5: 5f1c3b0b2f1d7c3e8c0a5d0c6c2f0e5a
+
+Package: tzdata
+Version: 2024a-1
+Filename: pool/main/t/tzdata/tzdata_2024a-1_all.deb
+
+Package: wget
+Version: 1.21-1
+Filename: pool/main/w/wget/wget_1.21-1_amd64.deb
+Description: retrieves files from the web
+"
+            .as_bytes(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        // The translation of the same description is picked.
+        assert_eq!(
+            packages[0].description_localized(&translations),
+            Some("Eine ältere Beschreibung")
+        );
+        assert_eq!(
+            packages[1].description_localized(&translations),
+            Some("Zeitzonen-Daten")
+        );
+        assert_eq!(
+            packages[2].description_localized(&translations),
+            Some("retrieves files from the web")
+        );
+        assert_eq!(
+            packages[2].description_localized(&Translations::default()),
+            packages[2].description()
+        );
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_fetch_translations() {
+        use std::io::Write as _;
+
+        use crate::testing::MockTransport;
+
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder
+            .write_all(b"Package: curl\nDescription-md5: 1a2b\nDescription-pt: Ferramenta\n")
+            .unwrap();
+        let repo = AptRepo::new("https://deb.example.com", "stable", &["main"], "amd64");
+        let mock = MockTransport::new()
+            .with_body(
+                "https://deb.example.com/dists/stable/main/i18n/Translation-pt.bz2",
+                encoder.finish().unwrap(),
+            )
+            .install();
+
+        // There is no Translation-pt_BR, so Translation-pt is used.
+        let translations = repo.translations("main", "pt_BR.UTF-8").unwrap();
+        assert_eq!(translations.lang(), Some("pt"));
+        assert_eq!(mock.requests().len(), 2);
+
+        // With no translations at all, there are none.
+        assert_eq!(
+            repo.translations("contrib", "fr").unwrap(),
+            Translations::default()
+        );
+    }
+
+    #[test]
+    fn test_missing_filename() {
+        let mut reader = PackagesReader::new("Package: a\nVersion: 1\n".as_bytes());
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_select_latest() {
+        let repo = AptRepo::new(
+            "http://archive.ubuntu.com/ubuntu/",
+            "jammy",
+            &["main"],
+            "amd64",
+        );
+        assert_eq!(
+            repo.packages_path("main"),
+            "dists/jammy/main/binary-amd64/Packages.gz"
+        );
+
+        let curl = repo.select_latest("curl", packages()).unwrap();
+        assert_eq!(curl.package.version(), "7.81.0-1ubuntu1.15");
+        assert_eq!(
+            curl.url,
+            "http://archive.ubuntu.com/ubuntu/pool/main/c/curl/curl_7.81.0-1ubuntu1.15_amd64.deb"
+        );
+
+        let tzdata = repo.select_latest("tzdata", packages()).unwrap();
+        assert_eq!(tzdata.package.architecture(), Some("all"));
+
+        assert!(repo.select_latest("wget", packages()).is_none());
+    }
+
+    #[test]
+    fn test_reverse_dependencies() {
+        let packages = PackagesReader::new(
+            "Package: curl
+Version: 7.88.1-10
+Filename: pool/main/c/curl/curl_7.88.1-10_amd64.deb
+Depends: libcurl4 (= 7.88.1-10), libssl3 (>= 3.0.0) | libssl1.1
+
+Package: wget
+Version: 1.21.3-1
+Filename: pool/main/w/wget/wget_1.21.3-1_amd64.deb
+Recommends: ca-certificates
+Depends: libssl3
+
+Package: libssl3
+Version: 3.0.11-1
+Filename: pool/main/o/openssl/libssl3_3.0.11-1_amd64.deb
+"
+            .as_bytes(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+        let names = |name| {
+            crate::repo::reverse_dependencies(&packages, name)
+                .into_iter()
+                .map(|p| p.name().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names("libssl3"), vec!["curl", "wget"]);
+        assert_eq!(names("libssl1.1"), vec!["curl"]);
+        assert_eq!(names("ca-certificates"), vec!["wget"]);
+        assert!(names("curl").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_dependencies() {
+        let packages = PackagesReader::new(
+            "Package: curl
+Version: 7.88.1-10
+Architecture: amd64
+Filename: pool/main/c/curl/curl_7.88.1-10_amd64.deb
+Size: 300000
+Installed-Size: 500
+Depends: libcurl4 (= 7.88.1-10), libc6 (>= 2.34)
+Recommends: ca-certificates
+
+Package: libcurl4
+Version: 7.88.1-10
+Architecture: amd64
+Filename: pool/main/c/curl/libcurl4_7.88.1-10_amd64.deb
+Size: 390000
+Installed-Size: 800
+Depends: libc6 (>= 2.17), libssl3 | libssl1.1
+
+Package: libcurl4
+Version: 7.88.1-9
+Architecture: amd64
+Filename: pool/main/c/curl/libcurl4_7.88.1-9_amd64.deb
+
+Package: libc6
+Version: 2.36-9
+Architecture: amd64
+Filename: pool/main/g/glibc/libc6_2.36-9_amd64.deb
+Size: 2750000
+Installed-Size: 12700
+
+Package: openssl-provider
+Version: 3.0.11-1
+Architecture: amd64
+Filename: pool/main/o/openssl/openssl-provider_3.0.11-1_amd64.deb
+Provides: libssl3
+
+Package: ca-certificates
+Version: 20230311
+Architecture: all
+Filename: pool/main/c/ca-certificates/ca-certificates_20230311_all.deb
+Depends: openssl (>= 1.1.1)
+"
+            .as_bytes(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        let resolver = crate::repo::DependencyResolver::new(&packages).arch("amd64");
+
+        let names = |resolved: Vec<&AptPackage>| {
+            resolved
+                .iter()
+                .map(|p| format!("{}={}", p.name(), p.version()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(resolver.resolve(&["curl"]).unwrap()),
+            vec![
+                "curl=7.88.1-10",
+                "libcurl4=7.88.1-10",
+                "libc6=2.36-9",
+                "openssl-provider=3.0.11-1"
+            ]
+        );
+
+        let size = resolver.closure_size(&["curl"]).unwrap();
+        assert_eq!(size.packages, 4);
+        assert_eq!(size.download_size, 3_440_000);
+        assert_eq!(size.installed_size, 14_000 * 1024);
+        assert_eq!(size.unknown_sizes, 1);
+
+        // The recommended ca-certificates needs openssl, which is missing.
+        let with_recommends = resolver.clone().recommends(true);
+        assert!(matches!(
+            with_recommends.resolve(&["curl"]),
+            Err(PkgError::UnsatisfiedDependency { package, .. }) if package == "ca-certificates"
+        ));
+        assert!(matches!(
+            resolver.resolve(&["wget"]),
+            Err(PkgError::PackageNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_relations() {
+        let relations = parse_relations(
+            "libc6 (>= 2.34), python3:any (<< 3.12), libssl3 | libssl1.1 (>> 1.1.0), perl (> 5)",
+        );
+        assert_eq!(relations.len(), 4);
+        assert_eq!(
+            relations[0][0],
+            Dependency {
+                name: "libc6".to_string(),
+                constraint: Some(VersionConstraint {
+                    op: VersionOp::Ge,
+                    version: "2.34".to_string(),
+                }),
+            }
+        );
+        assert_eq!(relations[1][0].name, "python3");
+        assert_eq!(
+            relations[1][0].constraint.as_ref().unwrap().op,
+            VersionOp::Lt
+        );
+        assert_eq!(relations[2].len(), 2);
+        assert_eq!(
+            relations[2][1].constraint.as_ref().unwrap().op,
+            VersionOp::Gt
+        );
+        assert_eq!(
+            relations[3][0].constraint.as_ref().unwrap().op,
+            VersionOp::Ge
+        );
+    }
+
+    #[test]
+    fn test_contents() {
+        let contents = "This file maps each file available in the Debian
+GNU/Linux system to the package from which it originates.
+
+FILE                                                    LOCATION
+usr/bin/curl                                            web/curl
+usr/share/doc/My Docs/readme.txt                        doc/example
+usr/share/man/man1/curl.1.gz                            web/curl,universe/web/curl-doc
+";
+        let entries = ContentsReader::new(contents.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].path, "usr/bin/curl");
+        assert_eq!(entries[1].path, "usr/share/doc/My Docs/readme.txt");
+        assert_eq!(
+            entries[2].packages().collect::<Vec<_>>(),
+            vec!["curl", "curl-doc"]
+        );
+
+        // Newer indexes have no header.
+        let mut reader = ContentsReader::new("usr/bin/wget  web/wget\n".as_bytes());
+        assert_eq!(reader.next().unwrap().unwrap().path, "usr/bin/wget");
+        assert!(reader.next().is_none());
+
+        let repo = AptRepo::new(
+            "http://deb.debian.org/debian",
+            "bookworm",
+            &["main"],
+            "amd64",
+        );
+        assert_eq!(
+            repo.contents_path("main"),
+            "dists/bookworm/main/Contents-amd64.gz"
+        );
+    }
+
+    #[test]
+    fn test_ppa() {
+        let repo = AptRepo::ppa("ppa:deadsnakes/ppa", "jammy", "amd64").unwrap();
+        assert_eq!(
+            repo.base_url(),
+            "https://ppa.launchpadcontent.net/deadsnakes/ppa/ubuntu"
+        );
+        assert_eq!(
+            repo.packages_path("main"),
+            "dists/jammy/main/binary-amd64/Packages.gz"
+        );
+
+        assert!(AptRepo::ppa("deadsnakes/ppa", "jammy", "amd64").is_err());
+        assert!(AptRepo::ppa("ppa:deadsnakes", "jammy", "amd64").is_err());
+        assert!(AptRepo::ppa("ppa:a/b/c", "jammy", "amd64").is_err());
+    }
+
+    const RELEASE: &str = "Origin: Test
+Suite: stable
+Codename: test
+Date: Wed, 14 Oct 2026 12:00:00 UTC
+Architectures: amd64
+Components: main
+SHA256:
+ ecb7b7ae8fbe85e7dbc779313b1b50a0f108055c1872d87c51ea3f6d00148cc8 31 main/binary-amd64/Packages
+";
+
+    #[cfg(feature = "verify")]
+    const IN_RELEASE: &str = "-----BEGIN PGP SIGNED MESSAGE-----
+Hash: SHA256
+
+Origin: Test
+Suite: stable
+Codename: test
+Date: Wed, 14 Oct 2026 12:00:00 UTC
+Architectures: amd64
+Components: main
+SHA256:
+ ecb7b7ae8fbe85e7dbc779313b1b50a0f108055c1872d87c51ea3f6d00148cc8 31 main/binary-amd64/Packages
+-----BEGIN PGP SIGNATURE-----
+
+iHUEARYIAB0WIQSHQKbVctTihJOvB7DaEbUuxe6VXQUCas+0ewAKCRDaEbUuxe6V
+XXHTAP43i1FLU4oGsFDFtjEJ09EmS/XCntEiy2vlc68bW5IBjAD/VB8izQve6dyA
+ToalHYPGkfL72Orq1Gcbf0gs38hUjg0=
+=tgGZ
+-----END PGP SIGNATURE-----
+";
+
+    #[test]
+    fn test_release() {
+        let release = AptRelease::parse(RELEASE).unwrap();
+        assert_eq!(release.codename(), Some("test"));
+        assert_eq!(release.suite(), Some("stable"));
+        assert_eq!(release.get("components"), Some("main"));
+        assert_eq!(release.files().len(), 1);
+        assert_eq!(release.file("main/binary-amd64/Packages").unwrap().size, 31);
+
+        assert!(AptRelease::parse("SHA256:\n abcd main/Packages\n").is_err());
+    }
+
+    #[test]
+    fn test_release_current() {
+        let now = httpdate::parse_http_date("Thu, 15 Oct 2026 12:00:00 GMT").unwrap();
+        let release = AptRelease::parse(RELEASE).unwrap();
+        assert_eq!(release.valid_until().unwrap(), None);
+        release.check_current("stable", now).unwrap();
+        release.check_current("test", now).unwrap();
+        assert!(matches!(
+            release.check_current("oldstable", now),
+            Err(PkgError::InvalidRepoData(_))
+        ));
+
+        let expiring = RELEASE.replace(
+            "Architectures:",
+            "Valid-Until: Wed, 21 Oct 2026 12:00:00 UTC\nArchitectures:",
+        );
+        let release = AptRelease::parse(&expiring).unwrap();
+        release.check_current("stable", now).unwrap();
+        let later = httpdate::parse_http_date("Thu, 22 Oct 2026 12:00:00 GMT").unwrap();
+        assert!(matches!(
+            release.check_current("stable", later),
+            Err(PkgError::InvalidRepoData(_))
+        ));
+
+        let invalid = RELEASE.replace("Architectures:", "Valid-Until: soon\nArchitectures:");
+        assert!(AptRelease::parse(&invalid).unwrap().valid_until().is_err());
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_release_check() {
+        let release = AptRelease::parse(RELEASE).unwrap();
+        let path = "main/binary-amd64/Packages";
+        release
+            .check(path, b"Package: hello\nVersion: 2.10-3\n")
+            .unwrap();
+        assert!(matches!(
+            release.check(path, b"Package: hello\nVersion: 2.10-4\n"),
+            Err(PkgError::ChecksumMismatch { algorithm, .. }) if algorithm == "sha256"
+        ));
+        assert!(matches!(
+            release.check("main/binary-arm64/Packages", b""),
+            Err(PkgError::RepoDataNotFound(_))
+        ));
+    }
+
+    #[cfg(all(feature = "verify", feature = "testing"))]
+    #[test]
+    fn test_fetch_release() {
+        use crate::testing::{MockResponse, MockTransport};
+        use crate::verify::tests::PUBLIC_KEY;
+
+        let keyring = Keyring::from_armored(PUBLIC_KEY).unwrap();
+        let repo = |dist: &str| {
+            AptRepo::new("https://deb.example.com", dist, &["main"], "amd64")
+                .with_keyring(keyring.clone())
+        };
+        let mock = MockTransport::new()
+            .with_body("https://deb.example.com/dists/stable/InRelease", IN_RELEASE)
+            .with_body("https://deb.example.com/dists/test/InRelease", IN_RELEASE)
+            .with_body(
+                "https://deb.example.com/dists/oldstable/InRelease",
+                IN_RELEASE,
+            )
+            .with_response(
+                "https://deb.example.com/dists/broken/InRelease",
+                MockResponse::new("").with_status(500),
+            )
+            .install();
+
+        assert_eq!(repo("stable").release().unwrap().suite(), Some("stable"));
+        assert_eq!(repo("test").release().unwrap().codename(), Some("test"));
+        assert!(matches!(
+            repo("oldstable").release(),
+            Err(PkgError::InvalidRepoData(_))
+        ));
+
+        // Only a missing InRelease falls back to Release and Release.gpg.
+        assert!(repo("broken").release().is_err());
+        assert!(!mock.requests().iter().any(|url| url.ends_with("/Release")));
+
+        // The Release file is only fetched once, however many indexes are.
+        let stable = repo("stable");
+        let before = mock.requests().len();
+        for _ in 0..2 {
+            assert!(matches!(
+                stable.packages("main"),
+                Err(PkgError::RepoDataNotFound(_))
+            ));
+        }
+        assert_eq!(mock.requests().len(), before + 1);
+    }
+
+    #[cfg(all(feature = "verify", feature = "http-blocking"))]
+    #[test]
+    fn test_open_checked() {
+        use std::io::Write as _;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(b"Package: hello\nVersion: 2.10-3\nFilename: pool/hello.deb\n")
+            .unwrap();
+        let index = encoder.finish().unwrap();
+        let file = ReleaseFile {
+            path: "main/binary-amd64/Packages.gz".to_string(),
+            size: index.len() as u64,
+            sha256: format!("{:x}", Sha256::digest(&index)),
+        };
+        let read = |file: ReleaseFile, data: Vec<u8>| {
+            let path = file.path.clone();
+            let (reader, check) = open_checked(file, std::io::Cursor::new(data));
+            open_packages(&path, reader)
+                .unwrap()
+                .with_check(Some(check))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        assert_eq!(read(file.clone(), index.clone()).unwrap().len(), 1);
+
+        let mut longer = index.clone();
+        longer.extend_from_slice(&[0; 100]);
+        assert!(matches!(
+            read(file.clone(), longer),
+            Err(PkgError::SizeMismatch { actual, .. }) if actual == file.size + 1
+        ));
+
+        let wrong = ReleaseFile {
+            sha256: "0".repeat(64),
+            ..file
+        };
+        assert!(matches!(
+            read(wrong, index),
+            Err(PkgError::ChecksumMismatch { algorithm, .. }) if algorithm == "sha256"
+        ));
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_release_signatures() {
+        use crate::verify::tests::PUBLIC_KEY;
+
+        const RELEASE_GPG: &str = "-----BEGIN PGP SIGNATURE-----
+
+iHUEABYIAB0WIQSHQKbVctTihJOvB7DaEbUuxe6VXQUCas+0ewAKCRDaEbUuxe6V
+XZQEAP4uKn9FfwHlKtHykXSa+8b40kD7wQWU+YrsTWWd4NcwDgD/bou52ed9dR6m
+DTpM9ejwNTmd1EFMmslsy8D/GZiLiAI=
+=0tJD
+-----END PGP SIGNATURE-----
+";
+
+        let keyring = Keyring::from_armored(PUBLIC_KEY).unwrap();
+
+        let (text, status) = keyring.verify_cleartext(IN_RELEASE).unwrap();
+        assert!(status.is_trusted());
+        assert_eq!(text, RELEASE);
+
+        let tampered = IN_RELEASE.replace("Suite: stable", "Suite: unstable");
+        let (_, status) = keyring.verify_cleartext(&tampered).unwrap();
+        assert!(!status.is_trusted());
+
+        assert!(keyring
+            .verify_armored(RELEASE_GPG, RELEASE.as_bytes())
+            .unwrap()
+            .is_trusted());
+    }
+}
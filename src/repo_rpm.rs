@@ -0,0 +1,1604 @@
+//! Support for Yum/DNF repository metadata (`repodata/`).
+//!
+//! A Yum repository describes its contents in `repodata/repomd.xml`, which
+//! points at (amongst other things) the compressed `primary.xml` listing
+//! every package in the repository. The primary listing can be very large,
+//! so it is parsed as a stream of packages rather than all at once.
+//!
+//! The optional `filelists.xml` lists the files in every package, allowing
+//! file-to-package queries without downloading any packages.
+//!
+//! Repository definitions from `.repo` files can be turned into concrete
+//! base URLs with [`RepoConfig`].
+#[cfg(feature = "http-blocking")]
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Read};
+#[cfg(feature = "http-blocking")]
+use std::rc::Rc;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+#[cfg(all(feature = "rpm", feature = "checksum"))]
+use crate::checksum::parse_checked;
+#[cfg(any(feature = "http-blocking", feature = "rpm"))]
+use crate::checksum::Algorithm;
+#[cfg(feature = "http-blocking")]
+use crate::checksum::DigestsReader;
+use crate::compression::Compression;
+#[cfg(feature = "http-blocking")]
+use crate::repo::SharedReader;
+use crate::repo::{join_url, Check, Dependency, Repo, RepoPackage, VersionConstraint, VersionOp};
+use crate::version::compare_rpm;
+use crate::xml::attribute;
+use crate::PkgError;
+
+/// A checksum as declared in repository metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    /// The checksum algorithm, e.g. `sha256`.
+    pub algorithm: String,
+
+    /// The hex-encoded checksum value.
+    pub value: String,
+}
+
+/// A single `<data>` entry in `repomd.xml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoMdData {
+    /// The type of the metadata file, e.g. `primary` or `filelists`.
+    pub data_type: String,
+
+    /// Location of the file relative to the repository base URL.
+    pub location_href: String,
+
+    /// Checksum of the file as stored (i.e. compressed).
+    pub checksum: Option<Checksum>,
+
+    /// Checksum of the file once decompressed.
+    pub open_checksum: Option<Checksum>,
+
+    /// Size of the file as stored.
+    pub size: Option<u64>,
+}
+
+/// The parsed contents of `repodata/repomd.xml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoMd {
+    /// The repository revision, if declared.
+    pub revision: Option<String>,
+
+    /// The metadata files the repository provides.
+    pub data: Vec<RepoMdData>,
+}
+
+impl RepoMd {
+    /// Parse `repomd.xml` from something that impls BufRead.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Self, PkgError> {
+        let mut reader = Reader::from_reader(reader);
+        let _ = reader.trim_text(true);
+
+        let mut repomd = RepoMd {
+            revision: None,
+            data: Vec::new(),
+        };
+        let mut current: Option<RepoMdData> = None;
+        let mut checksum_type: Option<String> = None;
+        let mut text_field: Option<Vec<u8>> = None;
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                    b"data" => {
+                        current = Some(RepoMdData {
+                            data_type: attribute(&e, b"type")?.unwrap_or_default(),
+                            location_href: String::new(),
+                            checksum: None,
+                            open_checksum: None,
+                            size: None,
+                        });
+                    }
+                    b"location" => {
+                        if let Some(data) = current.as_mut() {
+                            data.location_href = attribute(&e, b"href")?.unwrap_or_default();
+                        }
+                    }
+                    name @ (b"checksum" | b"open-checksum") => {
+                        checksum_type = attribute(&e, b"type")?;
+                        text_field = Some(name.to_vec());
+                    }
+                    name @ (b"revision" | b"size") => text_field = Some(name.to_vec()),
+                    _ => {}
+                },
+                Event::Text(t) => {
+                    let text = t.unescape()?.into_owned();
+                    match (text_field.as_deref(), current.as_mut()) {
+                        (Some(b"revision"), None) => repomd.revision = Some(text),
+                        (Some(b"size"), Some(data)) => data.size = Some(parse_number(&text)?),
+                        (Some(b"checksum"), Some(data)) => {
+                            data.checksum = Some(Checksum {
+                                algorithm: checksum_type.take().unwrap_or_default(),
+                                value: text,
+                            })
+                        }
+                        (Some(b"open-checksum"), Some(data)) => {
+                            data.open_checksum = Some(Checksum {
+                                algorithm: checksum_type.take().unwrap_or_default(),
+                                value: text,
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+                Event::End(e) => {
+                    text_field = None;
+                    if e.local_name().as_ref() == b"data" {
+                        if let Some(data) = current.take() {
+                            repomd.data.push(data);
+                        }
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        Ok(repomd)
+    }
+
+    /// Find the metadata file of the given type, e.g. `primary`.
+    pub fn find(&self, data_type: &str) -> Option<&RepoMdData> {
+        self.data.iter().find(|d| d.data_type == data_type)
+    }
+}
+
+#[cfg(feature = "http-blocking")]
+impl Checksum {
+    /// Look up the checksum algorithm, failing if it isn't one that
+    /// [`Algorithm`] supports.
+    fn algorithm(&self) -> Result<Algorithm, PkgError> {
+        Algorithm::from_name(&self.algorithm).ok_or_else(|| {
+            PkgError::InvalidRepoData(format!("unsupported checksum type {}", self.algorithm))
+        })
+    }
+}
+
+/// Decompress a metadata file described by `data`, hashing it as it is
+/// read. The returned check compares the file against its size and
+/// checksums in `repomd.xml` once it has been read to the end.
+///
+/// Fails if the file has no checksum, or if either checksum uses an
+/// unsupported algorithm.
+#[cfg(feature = "http-blocking")]
+fn open_checked<R: Read + 'static>(
+    data: &RepoMdData,
+    reader: R,
+) -> Result<(BufReader<Box<dyn Read>>, Check), PkgError> {
+    let checksum = data.checksum.as_ref().ok_or_else(|| {
+        PkgError::InvalidRepoData(format!("no checksum for {}", data.location_href))
+    })?;
+    let (algorithm, expected) = (checksum.algorithm()?, checksum.value.clone());
+    let open_checksum = match &data.open_checksum {
+        Some(open) => Some((open.algorithm()?, open.value.clone())),
+        None => None,
+    };
+    let open_algorithms: Vec<Algorithm> = open_checksum.iter().map(|(a, _)| *a).collect();
+
+    let stored = Rc::new(RefCell::new(DigestsReader::new(reader, &[algorithm])));
+    let decoder =
+        Compression::from_path(&data.location_href).decoder(SharedReader(Rc::clone(&stored)))?;
+    let decoded = Rc::new(RefCell::new(DigestsReader::new(decoder, &open_algorithms)));
+
+    let size = data.size;
+    let reader: Box<dyn Read> = Box::new(SharedReader(Rc::clone(&decoded)));
+    let check = move || {
+        // The decoder may stop at the end of the compressed stream, so read
+        // whatever follows it too.
+        let mut stored = stored.borrow_mut();
+        let digests = stored.finish()?;
+        if let Some(size) = size {
+            if size != stored.bytes_read() {
+                return Err(PkgError::SizeMismatch {
+                    expected: size,
+                    actual: stored.bytes_read(),
+                });
+            }
+        }
+        digests.check(algorithm, &expected)?;
+
+        if let Some((algorithm, expected)) = open_checksum {
+            decoded.borrow_mut().finish()?.check(algorithm, &expected)?;
+        }
+        Ok(())
+    };
+    Ok((BufReader::new(reader), Box::new(check)))
+}
+
+/// A package as described by the repository's primary metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpmRepoPackage {
+    /// Package name.
+    pub name: String,
+
+    /// Package architecture.
+    pub arch: String,
+
+    /// Package epoch, if declared.
+    pub epoch: Option<u32>,
+
+    /// Package version.
+    pub version: String,
+
+    /// Package release.
+    pub release: String,
+
+    /// Location of the package relative to the repository base URL.
+    pub location_href: String,
+
+    /// Checksum of the package file.
+    pub checksum: Option<Checksum>,
+
+    /// One line summary of the package.
+    pub summary: Option<String>,
+
+    /// Size of the package file in bytes.
+    pub size_package: Option<u64>,
+
+    /// Installed size of the package in bytes.
+    pub size_installed: Option<u64>,
+
+    /// Capabilities the package provides.
+    pub provides: Vec<Dependency>,
+
+    /// Capabilities the package requires.
+    pub requires: Vec<Dependency>,
+
+    /// Capabilities the package recommends.
+    pub recommends: Vec<Dependency>,
+
+    /// The files listed in the primary metadata. This is only a subset of
+    /// the package's files (e.g. those in `/etc` and `bin` directories),
+    /// used to satisfy file dependencies; see [`FilelistsReader`] for the
+    /// full list.
+    pub files: Vec<String>,
+}
+
+impl RpmRepoPackage {
+    /// Get the EVR (epoch:version-release) of the package, suitable for
+    /// passing to `version::compare_rpm`.
+    pub fn evr(&self) -> String {
+        format!(
+            "{}:{}-{}",
+            self.epoch.unwrap_or(0),
+            self.version,
+            self.release
+        )
+    }
+
+    /// Get the NEVRA (name-epoch:version-release.arch) of the package.
+    ///
+    /// Following rpm convention, the epoch is omitted when it is zero or
+    /// missing.
+    pub fn nevra(&self) -> String {
+        match self.epoch {
+            Some(epoch) if epoch != 0 => format!(
+                "{}-{}:{}-{}.{}",
+                self.name, epoch, self.version, self.release, self.arch
+            ),
+            _ => format!(
+                "{}-{}-{}.{}",
+                self.name, self.version, self.release, self.arch
+            ),
+        }
+    }
+}
+
+/// A streaming reader over the packages in a `primary.xml` file.
+///
+/// Each call to `next()` parses just enough of the document to produce the
+/// next package.
+pub struct PrimaryReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    done: bool,
+    check: Option<Check>,
+}
+
+impl<R: BufRead> PrimaryReader<R> {
+    /// Create a reader over uncompressed `primary.xml` data.
+    pub fn new(reader: R) -> Self {
+        let mut reader = Reader::from_reader(reader);
+        let _ = reader.trim_text(true);
+        Self {
+            reader,
+            buf: Vec::new(),
+            done: false,
+            check: None,
+        }
+    }
+
+    /// Run `check` once the end of the document is reached, yielding its
+    /// error as the last item.
+    #[cfg(feature = "http-blocking")]
+    fn with_check(mut self, check: Check) -> Self {
+        self.check = Some(check);
+        self
+    }
+
+    fn finish(&mut self) -> Result<Option<RpmRepoPackage>, PkgError> {
+        if let Some(check) = self.check.take() {
+            check()?;
+        }
+        Ok(None)
+    }
+
+    fn next_package(&mut self) -> Result<Option<RpmRepoPackage>, PkgError> {
+        let mut package: Option<RpmRepoPackage> = None;
+        let mut text_field: Option<Vec<u8>> = None;
+        let mut checksum_type: Option<String> = None;
+        let mut dependency_list: Option<Vec<u8>> = None;
+
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(e) | Event::Empty(e) => {
+                    match (e.local_name().as_ref(), package.as_mut()) {
+                        (b"package", None) => package = Some(empty_package()),
+                        (name @ (b"provides" | b"requires" | b"recommends"), Some(_)) => {
+                            dependency_list = Some(name.to_vec());
+                        }
+                        (b"entry", Some(pkg)) => {
+                            let entry = parse_entry(&e)?;
+                            match dependency_list.as_deref() {
+                                Some(b"provides") => pkg.provides.push(entry),
+                                Some(b"requires") => pkg.requires.push(entry),
+                                Some(b"recommends") => pkg.recommends.push(entry),
+                                _ => {}
+                            }
+                        }
+                        (b"checksum", Some(_)) => {
+                            checksum_type = attribute(&e, b"type")?;
+                            text_field = Some(b"checksum".to_vec());
+                        }
+                        (name @ (b"name" | b"arch" | b"summary" | b"file"), Some(_)) => {
+                            text_field = Some(name.to_vec())
+                        }
+                        (b"version", Some(pkg)) => {
+                            pkg.epoch = attribute(&e, b"epoch")?
+                                .map(|epoch| parse_number(&epoch))
+                                .transpose()?;
+                            pkg.version = attribute(&e, b"ver")?.unwrap_or_default();
+                            pkg.release = attribute(&e, b"rel")?.unwrap_or_default();
+                        }
+                        (b"location", Some(pkg)) => {
+                            pkg.location_href = attribute(&e, b"href")?.unwrap_or_default();
+                        }
+                        (b"size", Some(pkg)) => {
+                            pkg.size_package = attribute(&e, b"package")?
+                                .map(|size| parse_number(&size))
+                                .transpose()?;
+                            pkg.size_installed = attribute(&e, b"installed")?
+                                .map(|size| parse_number(&size))
+                                .transpose()?;
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Text(t) => {
+                    if let (Some(field), Some(pkg)) = (text_field.as_deref(), package.as_mut()) {
+                        let text = t.unescape()?.into_owned();
+                        match field {
+                            b"name" => pkg.name = text,
+                            b"arch" => pkg.arch = text,
+                            b"summary" => pkg.summary = Some(text),
+                            b"file" => pkg.files.push(text),
+                            b"checksum" => {
+                                pkg.checksum = Some(Checksum {
+                                    algorithm: checksum_type.take().unwrap_or_default(),
+                                    value: text,
+                                })
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Event::End(e) => {
+                    text_field = None;
+                    match e.local_name().as_ref() {
+                        b"package" => {
+                            if let Some(pkg) = package.take() {
+                                return Ok(Some(pkg));
+                            }
+                        }
+                        b"provides" | b"requires" | b"recommends" => dependency_list = None,
+                        _ => {}
+                    }
+                }
+                Event::Eof => return self.finish(),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PrimaryReader<R> {
+    type Item = Result<RpmRepoPackage, PkgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.next_package().transpose();
+
+        // Stop after the end of the document or the first error, as the
+        // underlying XML reader can't recover from malformed input.
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// The type of an entry in `filelists.xml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepoFileKind {
+    /// A regular file (or symlink).
+    File,
+
+    /// A directory.
+    Dir,
+
+    /// A file owned by the package but not included in it.
+    Ghost,
+}
+
+/// A file in a package, as listed in `filelists.xml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoFile {
+    /// The absolute path of the file.
+    pub path: String,
+
+    /// The type of the entry.
+    pub kind: RepoFileKind,
+}
+
+/// A package and its files, as listed in `filelists.xml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilelistsPackage {
+    /// The package checksum, matching the checksum in the primary metadata.
+    pub pkgid: String,
+
+    /// The package name.
+    pub name: String,
+
+    /// The package architecture.
+    pub arch: String,
+
+    /// The package epoch, if declared.
+    pub epoch: Option<u32>,
+
+    /// The package version.
+    pub version: String,
+
+    /// The package release.
+    pub release: String,
+
+    /// The files in the package.
+    pub files: Vec<RepoFile>,
+}
+
+/// A streaming reader over the packages in a `filelists.xml` file.
+pub struct FilelistsReader<R: BufRead> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    done: bool,
+    check: Option<Check>,
+}
+
+impl<R: BufRead> FilelistsReader<R> {
+    /// Create a reader over uncompressed `filelists.xml` data.
+    pub fn new(reader: R) -> Self {
+        let mut reader = Reader::from_reader(reader);
+        let _ = reader.trim_text(true);
+        Self {
+            reader,
+            buf: Vec::new(),
+            done: false,
+            check: None,
+        }
+    }
+
+    /// Run `check` once the end of the document is reached, yielding its
+    /// error as the last item.
+    #[cfg(feature = "http-blocking")]
+    fn with_check(mut self, check: Check) -> Self {
+        self.check = Some(check);
+        self
+    }
+
+    fn finish(&mut self) -> Result<Option<FilelistsPackage>, PkgError> {
+        if let Some(check) = self.check.take() {
+            check()?;
+        }
+        Ok(None)
+    }
+
+    fn next_package(&mut self) -> Result<Option<FilelistsPackage>, PkgError> {
+        let mut package: Option<FilelistsPackage> = None;
+        let mut file_kind: Option<RepoFileKind> = None;
+
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(e) | Event::Empty(e) => {
+                    match (e.local_name().as_ref(), package.as_mut()) {
+                        (b"package", None) => {
+                            package = Some(FilelistsPackage {
+                                pkgid: attribute(&e, b"pkgid")?.unwrap_or_default(),
+                                name: attribute(&e, b"name")?.unwrap_or_default(),
+                                arch: attribute(&e, b"arch")?.unwrap_or_default(),
+                                epoch: None,
+                                version: String::new(),
+                                release: String::new(),
+                                files: Vec::new(),
+                            })
+                        }
+                        (b"version", Some(pkg)) => {
+                            pkg.epoch = attribute(&e, b"epoch")?
+                                .map(|epoch| parse_number(&epoch))
+                                .transpose()?;
+                            pkg.version = attribute(&e, b"ver")?.unwrap_or_default();
+                            pkg.release = attribute(&e, b"rel")?.unwrap_or_default();
+                        }
+                        (b"file", Some(_)) => {
+                            file_kind = Some(match attribute(&e, b"type")?.as_deref() {
+                                Some("dir") => RepoFileKind::Dir,
+                                Some("ghost") => RepoFileKind::Ghost,
+                                _ => RepoFileKind::File,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Text(t) => {
+                    if let (Some(kind), Some(pkg)) = (file_kind.take(), package.as_mut()) {
+                        pkg.files.push(RepoFile {
+                            path: t.unescape()?.into_owned(),
+                            kind,
+                        });
+                    }
+                }
+                Event::End(e) => {
+                    file_kind = None;
+                    if e.local_name().as_ref() == b"package" {
+                        if let Some(pkg) = package.take() {
+                            return Ok(Some(pkg));
+                        }
+                    }
+                }
+                Event::Eof => return self.finish(),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for FilelistsReader<R> {
+    type Item = Result<FilelistsPackage, PkgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let result = self.next_package().transpose();
+
+        // As with the primary metadata, stop after the end of the document or
+        // the first error.
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// Wrap a (possibly compressed) `filelists.xml` stream in a
+/// `FilelistsReader`, using `location_href` to work out the compression.
+pub fn open_filelists<'a, R: Read + 'a>(
+    location_href: &str,
+    reader: R,
+) -> Result<FilelistsReader<BufReader<Box<dyn Read + 'a>>>, PkgError> {
+    let decoder = Compression::from_path(location_href).decoder(reader)?;
+    Ok(FilelistsReader::new(BufReader::new(decoder)))
+}
+
+/// A package resolved from a Yum repository, ready to be fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedRpmPackage {
+    /// The primary metadata entry for the package, including its checksum.
+    pub package: RpmRepoPackage,
+
+    /// The full download URL of the package.
+    pub url: String,
+}
+
+#[cfg(all(feature = "rpm", feature = "checksum"))]
+impl ResolvedRpmPackage {
+    /// Download the package and parse it, checking it against the size and
+    /// checksum declared in the primary metadata; see
+    /// [`ResolvedRpmPackage::read_checked`].
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn fetch(&self) -> Result<crate::rpm::RpmRemotePackage, PkgError> {
+        self.read_checked(crate::http::get_package(&self.url)?)
+    }
+
+    /// Parse a package read from `reader`, checking it against the size and
+    /// checksum declared in the primary metadata.
+    ///
+    /// The whole package is read, and fails with [`PkgError::SizeMismatch`]
+    /// or [`PkgError::ChecksumMismatch`] if it differs from the metadata.
+    /// Checksums in algorithms other than MD5 and the SHA-1 and SHA-2
+    /// families supported by [`Algorithm`] aren't checked.
+    pub fn read_checked<R: Read>(
+        &self,
+        reader: R,
+    ) -> Result<crate::rpm::RpmRemotePackage, PkgError> {
+        let checksum = self.package.checksum.as_ref().and_then(|checksum| {
+            Algorithm::from_name(&checksum.algorithm).map(|a| (a, checksum.value.as_str()))
+        });
+        parse_checked(reader, self.package.size_package, checksum, |reader| {
+            crate::rpm::RpmRemotePackage::new_from_read(reader)
+        })
+    }
+}
+
+/// A remote Yum/DNF repository.
+#[derive(Debug, Clone)]
+pub struct YumRepo {
+    base_url: String,
+}
+
+impl YumRepo {
+    /// Create a handle on the repository at `base_url`, i.e. the directory
+    /// containing `repodata/`.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    /// Get the repository base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Get the full download URL for a package in this repository.
+    pub fn package_url(&self, package: &RpmRepoPackage) -> String {
+        join_url(&self.base_url, &package.location_href)
+    }
+
+    /// Fetch and parse `repodata/repomd.xml`.
+    #[cfg(feature = "http-blocking")]
+    pub fn repomd(&self) -> Result<RepoMd, PkgError> {
+        let response = crate::http::get(&join_url(&self.base_url, "repodata/repomd.xml"))?;
+        RepoMd::parse(BufReader::new(response))
+    }
+
+    /// Fetch the primary metadata and return a streaming reader over the
+    /// packages it lists.
+    ///
+    /// The metadata is checked against its size and checksums in
+    /// `repomd.xml` as it is read, and the reader's last item is an error if
+    /// they differ. Checksums in unsupported algorithms are an error.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn packages(&self) -> Result<PrimaryReader<BufReader<Box<dyn Read>>>, PkgError> {
+        let repomd = self.repomd()?;
+        let primary = repomd
+            .find("primary")
+            .ok_or_else(|| PkgError::RepoDataNotFound("primary".to_string()))?;
+
+        let response = crate::http::get(&join_url(&self.base_url, &primary.location_href))?;
+        let (reader, check) = open_checked(primary, response)?;
+        Ok(PrimaryReader::new(reader).with_check(check))
+    }
+
+    /// Fetch the file lists and return a streaming reader over the packages
+    /// they describe, checked as for [`YumRepo::packages`]. Not every
+    /// repository publishes file lists.
+    #[cfg(feature = "http-blocking")]
+    pub fn filelists(&self) -> Result<FilelistsReader<BufReader<Box<dyn Read>>>, PkgError> {
+        let repomd = self.repomd()?;
+        let filelists = repomd
+            .find("filelists")
+            .ok_or_else(|| PkgError::RepoDataNotFound("filelists".to_string()))?;
+
+        let response = crate::http::get(&join_url(&self.base_url, &filelists.location_href))?;
+        let (reader, check) = open_checked(filelists, response)?;
+        Ok(FilelistsReader::new(reader).with_check(check))
+    }
+
+    /// Find the packages containing a file, e.g. `/usr/bin/curl`. Returns an
+    /// empty list if no package contains it.
+    #[cfg(feature = "http-blocking")]
+    pub fn find_file(&self, path: &str) -> Result<Vec<FilelistsPackage>, PkgError> {
+        let mut found = Vec::new();
+        for package in self.filelists()? {
+            let mut package = package?;
+            if package.files.iter().any(|f| f.path == path) {
+                // Only keep the matching entries, as file lists are large.
+                package.files.retain(|f| f.path == path);
+                found.push(package);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Find the newest version of the named package for an architecture,
+    /// using rpm version comparison (including epoch).
+    ///
+    /// Packages built for `arch` or for `noarch` are considered. Returns
+    /// `None` if the repository doesn't contain the package.
+    #[cfg(feature = "http-blocking")]
+    pub fn latest(&self, name: &str, arch: &str) -> Result<Option<ResolvedRpmPackage>, PkgError> {
+        let mut candidates = Vec::new();
+        for package in self.packages()? {
+            // Only keep matching entries, as the primary metadata can be very
+            // large.
+            let package = package?;
+            if package.name == name {
+                candidates.push(package);
+            }
+        }
+        Ok(self.select_latest(name, arch, candidates))
+    }
+
+    /// Pick the newest version of the named package from a set of primary
+    /// metadata entries.
+    #[cfg(any(feature = "http-blocking", test))]
+    fn select_latest<I>(&self, name: &str, arch: &str, packages: I) -> Option<ResolvedRpmPackage>
+    where
+        I: IntoIterator<Item = RpmRepoPackage>,
+    {
+        packages
+            .into_iter()
+            .filter(|p| p.name == name && (p.arch == arch || p.arch == "noarch"))
+            .max_by(|a, b| compare_rpm(&a.evr(), &b.evr()))
+            .map(|package| ResolvedRpmPackage {
+                url: self.package_url(&package),
+                package,
+            })
+    }
+}
+
+impl RepoPackage for RpmRepoPackage {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn full_version(&self) -> String {
+        self.evr()
+    }
+
+    fn arch(&self) -> Option<&str> {
+        Some(&self.arch)
+    }
+
+    fn compare_versions(a: &str, b: &str) -> std::cmp::Ordering {
+        compare_rpm(a, b)
+    }
+
+    /// Requirements on `rpmlib(...)` features are satisfied by rpm itself,
+    /// so are left out.
+    fn depends(&self) -> Vec<Vec<Dependency>> {
+        self.requires
+            .iter()
+            .filter(|dep| !dep.name.starts_with("rpmlib("))
+            .cloned()
+            .map(|dep| vec![dep])
+            .collect()
+    }
+
+    fn recommends(&self) -> Vec<Vec<Dependency>> {
+        self.recommends
+            .iter()
+            .cloned()
+            .map(|dep| vec![dep])
+            .collect()
+    }
+
+    fn provides(&self) -> Vec<Dependency> {
+        self.provides.clone()
+    }
+
+    fn download_size(&self) -> Option<u64> {
+        self.size_package
+    }
+
+    fn installed_size(&self) -> Option<u64> {
+        self.size_installed
+    }
+
+    fn has_file(&self, path: &str) -> bool {
+        self.files.iter().any(|file| file == path)
+    }
+}
+
+impl Repo for YumRepo {
+    type Package = RpmRepoPackage;
+
+    #[cfg(feature = "http-blocking")]
+    fn list_packages(&self) -> Result<Vec<RpmRepoPackage>, PkgError> {
+        self.packages()?.collect()
+    }
+}
+
+/// Wrap a (possibly compressed) `primary.xml` stream in a `PrimaryReader`,
+/// using `location_href` to work out the compression.
+pub fn open_primary<'a, R: Read + 'a>(
+    location_href: &str,
+    reader: R,
+) -> Result<PrimaryReader<BufReader<Box<dyn Read + 'a>>>, PkgError> {
+    let decoder = Compression::from_path(location_href).decoder(reader)?;
+    Ok(PrimaryReader::new(BufReader::new(decoder)))
+}
+
+fn empty_package() -> RpmRepoPackage {
+    RpmRepoPackage {
+        name: String::new(),
+        arch: String::new(),
+        epoch: None,
+        version: String::new(),
+        release: String::new(),
+        location_href: String::new(),
+        checksum: None,
+        summary: None,
+        size_package: None,
+        size_installed: None,
+        provides: Vec::new(),
+        requires: Vec::new(),
+        recommends: Vec::new(),
+        files: Vec::new(),
+    }
+}
+
+/// Parse an `<rpm:entry>` in a dependency list.
+fn parse_entry(element: &BytesStart<'_>) -> Result<Dependency, PkgError> {
+    let op = match attribute(element, b"flags")?.as_deref() {
+        Some("LT") => Some(VersionOp::Lt),
+        Some("LE") => Some(VersionOp::Le),
+        Some("EQ") => Some(VersionOp::Eq),
+        Some("GE") => Some(VersionOp::Ge),
+        Some("GT") => Some(VersionOp::Gt),
+        _ => None,
+    };
+
+    let constraint = match (op, attribute(element, b"ver")?) {
+        (Some(op), Some(ver)) => {
+            let mut version = ver;
+            if let Some(epoch) = attribute(element, b"epoch")? {
+                version = format!("{}:{}", epoch, version);
+            }
+            if let Some(rel) = attribute(element, b"rel")? {
+                version = format!("{}-{}", version, rel);
+            }
+            Some(VersionConstraint { op, version })
+        }
+        _ => None,
+    };
+
+    Ok(Dependency {
+        name: attribute(element, b"name")?.unwrap_or_default(),
+        constraint,
+    })
+}
+
+/// Parse a number from repository metadata.
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, PkgError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| PkgError::InvalidRepoData(format!("invalid number: {}", value)))
+}
+
+/// A repository definition from a `.repo` file, e.g.
+/// `/etc/yum.repos.d/fedora.repo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepoConfig {
+    /// The repository id, i.e. the section name.
+    pub id: String,
+
+    /// The human-readable repository name.
+    pub name: Option<String>,
+
+    /// Base URLs of the repository, if given directly.
+    pub baseurl: Vec<String>,
+
+    /// The URL of a mirror list, with one base URL per line.
+    pub mirrorlist: Option<String>,
+
+    /// The URL of a metalink describing `repomd.xml` on each mirror.
+    pub metalink: Option<String>,
+
+    /// Whether the repository is enabled.
+    pub enabled: bool,
+
+    /// Whether package signatures should be checked.
+    pub gpgcheck: bool,
+
+    /// URLs of the keys used to sign packages.
+    pub gpgkey: Vec<String>,
+}
+
+impl RepoConfig {
+    /// Parse every repository defined in a `.repo` file.
+    pub fn parse_repo_file(text: &str) -> Result<Vec<Self>, PkgError> {
+        let mut repos: Vec<RepoConfig> = Vec::new();
+        // The option currently being read, which may continue over several
+        // indented lines.
+        let mut key: Option<String> = None;
+
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with(';') {
+                continue;
+            }
+
+            if let Some(id) = trimmed.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                repos.push(RepoConfig::new(id.trim()));
+                key = None;
+                continue;
+            }
+
+            let repo = repos.last_mut().ok_or_else(|| {
+                PkgError::InvalidRepoData(format!("option outside a section: {}", trimmed))
+            })?;
+
+            if line.starts_with(char::is_whitespace) {
+                if let Some(key) = &key {
+                    repo.set(key, trimmed);
+                    continue;
+                }
+            }
+
+            let (name, value) = trimmed
+                .split_once('=')
+                .ok_or_else(|| PkgError::InvalidRepoData(format!("invalid line: {}", trimmed)))?;
+            let name = name.trim().to_lowercase();
+            repo.set(&name, value.trim());
+            key = Some(name);
+        }
+
+        Ok(repos)
+    }
+
+    fn new(id: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: None,
+            baseurl: Vec::new(),
+            mirrorlist: None,
+            metalink: None,
+            enabled: true,
+            gpgcheck: false,
+            gpgkey: Vec::new(),
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let list = || {
+            value
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|v| !v.is_empty())
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+        let flag = matches!(value.to_lowercase().as_str(), "1" | "yes" | "true" | "on");
+
+        match key {
+            "name" => self.name = Some(value.to_owned()),
+            "baseurl" => self.baseurl.extend(list()),
+            "mirrorlist" => self.mirrorlist = Some(value.to_owned()),
+            "metalink" => self.metalink = Some(value.to_owned()),
+            "enabled" => self.enabled = flag,
+            "gpgcheck" => self.gpgcheck = flag,
+            "gpgkey" => self.gpgkey.extend(list()),
+            _ => {}
+        }
+    }
+
+    /// Substitute variables such as `$releasever` and `$basearch` in the
+    /// repository URLs; see [`substitute_vars`].
+    pub fn substitute(&self, vars: &[(&str, &str)]) -> Self {
+        let sub = |value: &String| substitute_vars(value, vars);
+        Self {
+            id: self.id.clone(),
+            name: self.name.as_ref().map(sub),
+            baseurl: self.baseurl.iter().map(sub).collect(),
+            mirrorlist: self.mirrorlist.as_ref().map(sub),
+            metalink: self.metalink.as_ref().map(sub),
+            enabled: self.enabled,
+            gpgcheck: self.gpgcheck,
+            gpgkey: self.gpgkey.iter().map(sub).collect(),
+        }
+    }
+
+    /// Resolve the concrete base URLs of the repository, best first.
+    ///
+    /// Base URLs given directly are used as is. Otherwise the metalink, or
+    /// failing that the mirror list, is fetched and its HTTP(S) mirrors are
+    /// returned. Variables should already have been substituted.
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn resolve_baseurls(&self) -> Result<Vec<String>, PkgError> {
+        if !self.baseurl.is_empty() {
+            return Ok(self.baseurl.clone());
+        }
+
+        #[cfg(feature = "metalink")]
+        if let Some(metalink) = &self.metalink {
+            let response = crate::http::get(metalink)?;
+            let doc = crate::metalink::Metalink::parse(BufReader::new(response))?;
+            return Ok(metalink_baseurls(&doc));
+        }
+
+        if let Some(mirrorlist) = &self.mirrorlist {
+            let response = crate::http::get(mirrorlist)?;
+            return Ok(parse_mirrorlist(&response.text()?));
+        }
+
+        Err(PkgError::RepoDataNotFound(format!("{}: baseurl", self.id)))
+    }
+
+    /// Resolve the repository's base URLs and get a handle on the best one.
+    #[cfg(feature = "http-blocking")]
+    pub fn repo(&self) -> Result<YumRepo, PkgError> {
+        self.resolve_baseurls()?
+            .first()
+            .map(|base_url| YumRepo::new(base_url))
+            .ok_or_else(|| PkgError::RepoDataNotFound(format!("{}: mirrors", self.id)))
+    }
+}
+
+/// Substitute `$name` and `${name}` variables, e.g. `$releasever` and
+/// `$basearch`, as yum and dnf do. Unknown variables are left in place.
+pub fn substitute_vars(value: &str, vars: &[(&str, &str)]) -> String {
+    let lookup = |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        result.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        let (name, consumed) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+
+        match lookup(name) {
+            Some(replacement) if !name.is_empty() => result.push_str(replacement),
+            _ => result.push_str(&rest[pos..pos + 1 + consumed]),
+        }
+        rest = &after[consumed..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Parse a mirror list, returning the HTTP(S) base URLs it lists.
+pub fn parse_mirrorlist(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("http://") || line.starts_with("https://"))
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Get the HTTP(S) base URLs from a metalink describing `repomd.xml`, best
+/// first.
+#[cfg(feature = "metalink")]
+pub fn metalink_baseurls(metalink: &crate::metalink::Metalink) -> Vec<String> {
+    metalink
+        .files
+        .iter()
+        .filter(|file| file.name == "repomd.xml")
+        .flat_map(|file| file.urls.iter())
+        .map(|mirror| mirror.url.as_str())
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+        .map(|url| {
+            url.trim_end_matches("repomd.xml")
+                .trim_end_matches('/')
+                .trim_end_matches("repodata")
+                .trim_end_matches('/')
+                .to_owned()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPOMD: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<repomd xmlns="http://linux.duke.edu/metadata/repo" xmlns:rpm="http://linux.duke.edu/metadata/rpm">
+  <revision>1714000000</revision>
+  <data type="primary">
+    <checksum type="sha256">aaaa</checksum>
+    <open-checksum type="sha256">bbbb</open-checksum>
+    <location href="repodata/aaaa-primary.xml.gz"/>
+    <timestamp>1714000000</timestamp>
+    <size>1234</size>
+  </data>
+  <data type="filelists">
+    <checksum type="sha256">cccc</checksum>
+    <location href="repodata/cccc-filelists.xml.gz"/>
+  </data>
+</repomd>"#;
+
+    const PRIMARY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata xmlns="http://linux.duke.edu/metadata/common" xmlns:rpm="http://linux.duke.edu/metadata/rpm" packages="2">
+<package type="rpm">
+  <name>hello</name>
+  <arch>x86_64</arch>
+  <version epoch="0" ver="2.12.1" rel="4.fc40"/>
+  <checksum type="sha256" pkgid="YES">1111</checksum>
+  <summary>Prints a familiar, friendly greeting</summary>
+  <size package="86000" installed="190000" archive="191000"/>
+  <location href="Packages/h/hello-2.12.1-4.fc40.x86_64.rpm"/>
+  <format>
+    <rpm:license>GPL-3.0-or-later</rpm:license>
+    <rpm:provides>
+      <rpm:entry name="hello" flags="EQ" epoch="0" ver="2.12.1" rel="4.fc40"/>
+    </rpm:provides>
+    <rpm:requires>
+      <rpm:entry name="libc.so.6(GLIBC_2.34)(64bit)"/>
+      <rpm:entry name="tzdata" flags="GE" epoch="2" ver="2024a"/>
+    </rpm:requires>
+  </format>
+</package>
+<package type="rpm">
+  <name>tzdata</name>
+  <arch>noarch</arch>
+  <version epoch="2" ver="2024a" rel="5.fc40"/>
+  <checksum type="sha256" pkgid="YES">2222</checksum>
+  <summary>Timezone data &amp; rules</summary>
+  <location href="Packages/t/tzdata-2024a-5.fc40.noarch.rpm"/>
+</package>
+</metadata>"#;
+
+    #[test]
+    fn test_repomd() {
+        let repomd = RepoMd::parse(REPOMD.as_bytes()).unwrap();
+        assert_eq!(repomd.revision.as_deref(), Some("1714000000"));
+        assert_eq!(repomd.data.len(), 2);
+
+        let primary = repomd.find("primary").unwrap();
+        assert_eq!(primary.location_href, "repodata/aaaa-primary.xml.gz");
+        assert_eq!(primary.size, Some(1234));
+        assert_eq!(
+            primary.checksum,
+            Some(Checksum {
+                algorithm: "sha256".to_string(),
+                value: "aaaa".to_string()
+            })
+        );
+        assert_eq!(primary.open_checksum.as_ref().unwrap().value, "bbbb");
+        assert!(repomd.find("other").is_none());
+    }
+
+    #[test]
+    fn test_primary() {
+        let packages: Vec<_> = PrimaryReader::new(PRIMARY.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(packages.len(), 2);
+
+        let hello = &packages[0];
+        assert_eq!(hello.nevra(), "hello-2.12.1-4.fc40.x86_64");
+        assert_eq!(
+            hello.location_href,
+            "Packages/h/hello-2.12.1-4.fc40.x86_64.rpm"
+        );
+        assert_eq!(hello.checksum.as_ref().unwrap().value, "1111");
+        assert_eq!(hello.size_package, Some(86000));
+        assert_eq!(
+            hello.provides[0].constraint.as_ref().unwrap().version,
+            "0:2.12.1-4.fc40"
+        );
+        assert_eq!(hello.requires.len(), 2);
+        assert!(hello.requires[0].constraint.is_none());
+        assert_eq!(
+            hello.requires[1].constraint,
+            Some(VersionConstraint {
+                op: VersionOp::Ge,
+                version: "2:2024a".to_string(),
+            })
+        );
+
+        let tzdata = &packages[1];
+        assert_eq!(tzdata.nevra(), "tzdata-2:2024a-5.fc40.noarch");
+        assert_eq!(tzdata.summary.as_deref(), Some("Timezone data & rules"));
+        assert_eq!(
+            crate::repo::reverse_dependencies(&packages, "tzdata"),
+            vec![hello]
+        );
+
+        let repo = YumRepo::new("https://example.com/fedora/40/x86_64/");
+        assert_eq!(
+            repo.package_url(tzdata),
+            "https://example.com/fedora/40/x86_64/Packages/t/tzdata-2024a-5.fc40.noarch.rpm"
+        );
+    }
+
+    #[test]
+    fn test_primary_gzipped() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(PRIMARY.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let reader = open_primary("repodata/aaaa-primary.xml.gz", &compressed[..]).unwrap();
+        let names: Vec<_> = reader.map(|p| p.unwrap().name).collect();
+        assert_eq!(names, vec!["hello", "tzdata"]);
+    }
+
+    #[test]
+    fn test_resolve_dependencies() {
+        let primary = r#"<metadata xmlns="http://linux.duke.edu/metadata/common" xmlns:rpm="http://linux.duke.edu/metadata/rpm">
+<package type="rpm">
+  <name>curl</name><arch>x86_64</arch>
+  <version epoch="0" ver="7.76.1" rel="14.el9"/>
+  <format>
+    <rpm:requires>
+      <rpm:entry name="rpmlib(PayloadIsZstd)" flags="LE" epoch="0" ver="5.4.18" rel="1"/>
+      <rpm:entry name="libcurl(x86-64)" flags="GE" epoch="0" ver="7.76.1" rel="14.el9"/>
+      <rpm:entry name="/bin/sh"/>
+    </rpm:requires>
+  </format>
+</package>
+<package type="rpm">
+  <name>libcurl</name><arch>x86_64</arch>
+  <version epoch="0" ver="7.76.1" rel="14.el9"/>
+  <format><rpm:provides>
+    <rpm:entry name="libcurl(x86-64)" flags="EQ" epoch="0" ver="7.76.1" rel="14.el9"/>
+  </rpm:provides></format>
+</package>
+<package type="rpm">
+  <name>libcurl</name><arch>i686</arch>
+  <version epoch="0" ver="7.76.1" rel="14.el9"/>
+</package>
+<package type="rpm">
+  <name>bash</name><arch>x86_64</arch>
+  <version epoch="0" ver="5.1.8" rel="6.el9"/>
+  <format><file>/bin/sh</file><file>/usr/bin/bash</file></format>
+</package>
+</metadata>"#;
+        let packages: Vec<_> = PrimaryReader::new(primary.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let resolved = crate::repo::DependencyResolver::new(&packages)
+            .arch("x86_64")
+            .resolve(&["curl"])
+            .unwrap();
+        let nevras: Vec<_> = resolved.iter().map(|p| p.nevra()).collect();
+        assert_eq!(
+            nevras,
+            vec![
+                "curl-7.76.1-14.el9.x86_64",
+                "libcurl-7.76.1-14.el9.x86_64",
+                "bash-5.1.8-6.el9.x86_64"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filelists() {
+        let filelists = r#"<?xml version="1.0" encoding="UTF-8"?>
+<filelists xmlns="http://linux.duke.edu/metadata/filelists" packages="2">
+<package pkgid="aaaa" name="curl" arch="x86_64">
+  <version epoch="0" ver="7.76.1" rel="14.el9"/>
+  <file>/usr/bin/curl</file>
+  <file type="dir">/usr/share/doc/curl</file>
+</package>
+<package pkgid="bbbb" name="setup" arch="noarch">
+  <version epoch="0" ver="2.13.7" rel="9.el9"/>
+  <file type="ghost">/etc/environment</file>
+</package>
+</filelists>"#;
+
+        let packages = FilelistsReader::new(filelists.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].release, "14.el9");
+        assert_eq!(
+            packages[0].files[1],
+            RepoFile {
+                path: "/usr/share/doc/curl".to_string(),
+                kind: RepoFileKind::Dir,
+            }
+        );
+        assert_eq!(packages[1].files[0].kind, RepoFileKind::Ghost);
+    }
+
+    #[test]
+    fn test_select_latest() {
+        let mut packages: Vec<_> = PrimaryReader::new(PRIMARY.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        // Add a newer version with a lower epoch, and one for another arch.
+        let mut newer = packages[1].clone();
+        newer.epoch = Some(1);
+        newer.version = "2099a".to_string();
+        packages.push(newer);
+
+        let mut other_arch = packages[0].clone();
+        other_arch.arch = "aarch64".to_string();
+        other_arch.release = "5.fc40".to_string();
+        packages.push(other_arch);
+
+        let repo = YumRepo::new("https://example.com/repo");
+
+        let tzdata = repo
+            .select_latest("tzdata", "x86_64", packages.clone())
+            .unwrap();
+        assert_eq!(tzdata.package.evr(), "2:2024a-5.fc40");
+        assert_eq!(tzdata.package.checksum.unwrap().value, "2222");
+
+        let hello = repo
+            .select_latest("hello", "x86_64", packages.clone())
+            .unwrap();
+        assert_eq!(hello.package.release, "4.fc40");
+        assert_eq!(
+            hello.url,
+            "https://example.com/repo/Packages/h/hello-2.12.1-4.fc40.x86_64.rpm"
+        );
+
+        assert!(repo.select_latest("hello", "s390x", packages).is_none());
+    }
+
+    #[cfg(all(feature = "rpm", feature = "checksum"))]
+    #[test]
+    fn test_read_checked() {
+        use sha2::{Digest as _, Sha256};
+
+        let mut rpm = crate::rpm_header::tests::build_rpm(&[]);
+        rpm.extend_from_slice(b"payload");
+        let mut package = PrimaryReader::new(PRIMARY.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        package.size_package = Some(rpm.len() as u64);
+        package.checksum = Some(Checksum {
+            algorithm: "sha256".to_string(),
+            value: format!("{:x}", Sha256::digest(&rpm)),
+        });
+        let resolved = ResolvedRpmPackage {
+            package,
+            url: String::new(),
+        };
+
+        let remote = resolved.read_checked(rpm.as_slice()).unwrap();
+        assert_eq!(
+            crate::RemotePackage::package_name(&remote).unwrap(),
+            "hello"
+        );
+
+        let mut longer = rpm.clone();
+        longer.push(0);
+        assert!(matches!(
+            resolved.read_checked(longer.as_slice()),
+            Err(PkgError::SizeMismatch { .. })
+        ));
+        let mut tampered = rpm;
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            resolved.read_checked(tampered.as_slice()),
+            Err(PkgError::ChecksumMismatch { algorithm, .. }) if algorithm == "sha256"
+        ));
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_packages_checked() {
+        use std::io::Write as _;
+
+        use sha2::{Digest as _, Sha256};
+
+        use crate::testing::{MockResponse, MockTransport};
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(PRIMARY.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let repomd = |checksum_type: &str, open_checksum: &str, size: usize| {
+            format!(
+                r#"<repomd><data type="primary">
+                <checksum type="{}">{:x}</checksum>
+                <open-checksum type="sha256">{}</open-checksum>
+                <location href="repodata/primary.xml.gz"/>
+                <size>{}</size>
+                </data></repomd>"#,
+                checksum_type,
+                Sha256::digest(&compressed),
+                open_checksum,
+                size
+            )
+        };
+        let open_checksum = format!("{:x}", Sha256::digest(PRIMARY));
+        let _mock = MockTransport::new()
+            .with_body(
+                "https://example.com/repo/repodata/repomd.xml",
+                repomd("sha256", &open_checksum, compressed.len()),
+            )
+            .with_body(
+                "https://example.com/repo/repodata/repomd.xml",
+                repomd("sha256", &"0".repeat(64), compressed.len()),
+            )
+            .with_body(
+                "https://example.com/repo/repodata/repomd.xml",
+                repomd("sha256", &open_checksum, compressed.len() + 1),
+            )
+            .with_body(
+                "https://example.com/repo/repodata/repomd.xml",
+                repomd("sha3-256", &open_checksum, compressed.len()),
+            )
+            .with_response(
+                "https://example.com/repo/repodata/primary.xml.gz",
+                MockResponse::new(compressed.clone()),
+            )
+            .install();
+        let repo = YumRepo::new("https://example.com/repo");
+
+        let names: Vec<_> = repo.packages().unwrap().map(|p| p.unwrap().name).collect();
+        assert_eq!(names, vec!["hello", "tzdata"]);
+
+        // A mismatch is the last item, after the packages that were parsed.
+        let last = repo.packages().unwrap().last().unwrap();
+        assert!(matches!(
+            last,
+            Err(PkgError::ChecksumMismatch { algorithm, .. }) if algorithm == "sha256"
+        ));
+        let last = repo.packages().unwrap().last().unwrap();
+        assert!(matches!(last, Err(PkgError::SizeMismatch { .. })));
+
+        assert!(matches!(repo.packages(), Err(PkgError::InvalidRepoData(_))));
+    }
+
+    #[cfg(all(feature = "rpm", feature = "checksum", feature = "testing"))]
+    #[test]
+    fn test_fetch_content_encoding() {
+        use std::io::Write as _;
+
+        use sha2::{Digest as _, Sha256};
+
+        use crate::testing::{MockResponse, MockTransport, RpmBuilder};
+
+        let rpm = RpmBuilder::new("hello", "2.12.1", "4.fc40", "x86_64").build();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&rpm).unwrap();
+        let url = "https://example.com/repo/Packages/h/hello-2.12.1-4.fc40.x86_64.rpm";
+        let _mock = MockTransport::new()
+            .with_response(
+                url,
+                MockResponse::new(encoder.finish().unwrap())
+                    .with_header("Content-Encoding", "gzip"),
+            )
+            .install();
+
+        // The package is checked against the file, not the bytes as sent.
+        let mut package = PrimaryReader::new(PRIMARY.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        package.size_package = Some(rpm.len() as u64);
+        package.checksum = Some(Checksum {
+            algorithm: "sha256".to_string(),
+            value: format!("{:x}", Sha256::digest(&rpm)),
+        });
+        let resolved = ResolvedRpmPackage {
+            package,
+            url: url.to_string(),
+        };
+        let remote = resolved.fetch().unwrap();
+        assert_eq!(
+            crate::RemotePackage::package_name(&remote).unwrap(),
+            "hello"
+        );
+    }
+
+    const REPO_FILE: &str = "[fedora]
+name=Fedora $releasever - $basearch
+#baseurl=http://download.example/pub/fedora/linux/releases/$releasever/Everything/$basearch/os/
+metalink=https://mirrors.fedoraproject.org/metalink?repo=fedora-$releasever&arch=$basearch
+enabled=1
+gpgcheck=1
+gpgkey=file:///etc/pki/rpm-gpg/RPM-GPG-KEY-fedora-$releasever-$basearch
+
+[local]
+name=Local
+baseurl=http://mirror1.example/repo/
+  http://mirror2.example/repo/
+enabled=0
+";
+
+    #[test]
+    fn test_repo_file() {
+        let repos = RepoConfig::parse_repo_file(REPO_FILE).unwrap();
+        assert_eq!(repos.len(), 2);
+
+        let fedora = repos[0].substitute(&[("releasever", "39"), ("basearch", "x86_64")]);
+        assert_eq!(fedora.name.as_deref(), Some("Fedora 39 - x86_64"));
+        assert_eq!(
+            fedora.metalink.as_deref(),
+            Some("https://mirrors.fedoraproject.org/metalink?repo=fedora-39&arch=x86_64")
+        );
+        assert!(fedora.enabled && fedora.gpgcheck);
+        assert!(fedora.baseurl.is_empty());
+
+        let local = &repos[1];
+        assert!(!local.enabled);
+        assert_eq!(
+            local.baseurl,
+            vec![
+                "http://mirror1.example/repo/",
+                "http://mirror2.example/repo/"
+            ]
+        );
+
+        assert!(RepoConfig::parse_repo_file("name=orphan").is_err());
+    }
+
+    #[test]
+    fn test_substitute_vars() {
+        let vars = [("releasever", "39"), ("basearch", "aarch64")];
+        assert_eq!(
+            substitute_vars("/$releasever/${basearch}os/$unknown/$", &vars),
+            "/39/aarch64os/$unknown/$"
+        );
+
+        assert_eq!(
+            parse_mirrorlist("# comment\nhttps://a.example/os/\nrsync://b.example/os/\n"),
+            vec!["https://a.example/os/"]
+        );
+    }
+
+    #[cfg(feature = "metalink")]
+    #[test]
+    fn test_metalink_baseurls() {
+        let metalink = crate::metalink::Metalink::parse(
+            r#"<metalink version="3.0" xmlns="http://www.metalinker.org/">
+  <files><file name="repomd.xml"><resources>
+    <url protocol="rsync" preference="100">rsync://a.example/fedora/repodata/repomd.xml</url>
+    <url protocol="https" preference="99">https://b.example/fedora/repodata/repomd.xml</url>
+  </resources></file></files>
+</metalink>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            metalink_baseurls(&metalink),
+            vec!["https://b.example/fedora"]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+//! Tabular reports on many packages, for audits of mirror directories and
+//! the like. Rows can be rendered as CSV or as an aligned text table.
+use std::io::Read;
+
+use crate::checksum::{Algorithm, DigestsReader};
+use crate::{PkgError, RemotePackage};
+
+/// The column headings of a report.
+const HEADINGS: [&str; 6] = ["name", "version", "arch", "size", "sha256", "source"];
+
+/// One package in a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportRow {
+    /// The package name.
+    pub name: String,
+
+    /// The package version.
+    pub version: String,
+
+    /// The package architecture.
+    pub arch: String,
+
+    /// The size of the package file in bytes, if known.
+    pub size: Option<u64>,
+
+    /// The SHA-256 of the package file as hex, if known.
+    pub sha256: Option<String>,
+
+    /// Where the package came from, such as its URL.
+    pub source: Option<String>,
+}
+
+impl ReportRow {
+    /// Make a row from the metadata of a package, without the size or
+    /// checksum of the file.
+    pub fn from_package<P: RemotePackage + ?Sized>(package: &P) -> Result<Self, PkgError> {
+        Ok(Self {
+            name: package.package_name()?.to_owned(),
+            version: package.package_version()?.to_owned(),
+            arch: package.package_arch()?.to_owned(),
+            size: None,
+            sha256: None,
+            source: None,
+        })
+    }
+
+    /// Make a row from a package file, reading it to the end to get its
+    /// size and checksum.
+    pub fn from_read<R: Read>(reader: R, source: Option<&str>) -> Result<Self, PkgError> {
+        let mut reader = DigestsReader::new(reader, &[Algorithm::Sha256]);
+        let package = crate::from_read(&mut reader)?;
+        let digests = reader.finish()?;
+        Ok(Self {
+            size: Some(reader.bytes_read()),
+            sha256: digests.sha256().map(str::to_owned),
+            source: source.map(str::to_owned),
+            ..Self::from_package(package.as_ref())?
+        })
+    }
+
+    /// Make a row from a package URL; see [`ReportRow::from_read`].
+    ///
+    /// Uses a blocking client, so if using this in an async environment,
+    /// surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn from_url(url: &str) -> Result<Self, PkgError> {
+        Self::from_read(crate::http::get_package(url)?, Some(url))
+    }
+
+    /// The values of the row, in the order of [`HEADINGS`].
+    fn cells(&self) -> [String; 6] {
+        [
+            self.name.clone(),
+            self.version.clone(),
+            self.arch.clone(),
+            self.size.map(|size| size.to_string()).unwrap_or_default(),
+            self.sha256.clone().unwrap_or_default(),
+            self.source.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Render rows as CSV with a heading line, quoting values as RFC 4180
+/// describes. Unknown values are empty.
+pub fn to_csv(rows: &[ReportRow]) -> String {
+    let quote = |value: &str| {
+        if value.contains([',', '"', '\r', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_owned()
+        }
+    };
+
+    let mut csv = HEADINGS.join(",");
+    csv.push_str("\r\n");
+    for row in rows {
+        let cells: Vec<String> = row.cells().iter().map(|cell| quote(cell)).collect();
+        csv.push_str(&cells.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Render rows as a text table with a heading line, with the columns
+/// aligned. Unknown values are shown as `-`.
+pub fn to_table(rows: &[ReportRow]) -> String {
+    let mut lines = vec![HEADINGS.map(str::to_owned)];
+    for row in rows {
+        let mut cells = row.cells();
+        for cell in cells.iter_mut().filter(|cell| cell.is_empty()) {
+            *cell = "-".to_string();
+        }
+        lines.push(cells);
+    }
+
+    let mut widths = [0; 6];
+    for line in &lines {
+        for (width, cell) in widths.iter_mut().zip(line) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for line in &lines {
+        let cells: Vec<String> = line
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<1$}", cell, width))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two rows, one of them with every value known.
+    fn rows() -> Vec<ReportRow> {
+        vec![
+            ReportRow {
+                name: "hello".to_string(),
+                version: "2.10-3".to_string(),
+                arch: "amd64".to_string(),
+                size: Some(53_212),
+                sha256: Some("ab12".to_string()),
+                source: Some("https://example.com/hello_2.10-3_amd64.deb".to_string()),
+            },
+            ReportRow {
+                name: "tzdata".to_string(),
+                version: "2024a, \"5\"".to_string(),
+                arch: "all".to_string(),
+                size: None,
+                sha256: None,
+                source: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_to_csv() {
+        assert_eq!(
+            to_csv(&rows()),
+            "name,version,arch,size,sha256,source\r\n\
+             hello,2.10-3,amd64,53212,ab12,https://example.com/hello_2.10-3_amd64.deb\r\n\
+             tzdata,\"2024a, \"\"5\"\"\",all,,,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_to_table() {
+        assert_eq!(
+            to_table(&rows()),
+            "name    version     arch   size   sha256  source\n\
+             hello   2.10-3      amd64  53212  ab12    https://example.com/hello_2.10-3_amd64.deb\n\
+             tzdata  2024a, \"5\"  all    -      -       -\n"
+        );
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_from_read() {
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+        let row = ReportRow::from_read(rpm.as_slice(), Some("hello.rpm")).unwrap();
+        assert_eq!(row.name, "hello");
+        assert_eq!(row.version, "1.0");
+        assert_eq!(row.arch, "noarch");
+        assert_eq!(row.size, Some(rpm.len() as u64));
+        assert_eq!(row.sha256.as_ref().map(String::len), Some(64));
+        assert_eq!(row.source.as_deref(), Some("hello.rpm"));
+    }
+}
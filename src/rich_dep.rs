@@ -0,0 +1,483 @@
+//! Parsing of RPM rich (boolean) dependencies, e.g.
+//! `(foo >= 1.0 with bar)`.
+//!
+//! See <https://rpm-software-management.github.io/rpm/manual/boolean_dependencies.html>.
+//! Plain dependencies such as `foo >= 1.0` parse to a single
+//! [`RichDep::Capability`], so every requirement of a package can be handled
+//! the same way.
+//!
+//! ```
+//! use remote_package::rich_dep::RichDep;
+//!
+//! let dep: RichDep = "(python3(foo) >= 1.0 or bar)".parse()?;
+//! assert_eq!(dep.capabilities(), vec!["python3(foo)", "bar"]);
+//! assert!(dep.evaluate(|name, _constraint| name == "bar"));
+//! # Ok::<(), remote_package::PkgError>(())
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use crate::version::{VersionConstraint, VersionOp};
+use crate::PkgError;
+
+/// A dependency, possibly combining others with boolean operators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RichDep {
+    /// A single capability, with an optional version constraint.
+    Capability {
+        /// The package name or capability, e.g. `perl(Foo)`.
+        name: String,
+
+        /// The version constraint, if any.
+        constraint: Option<VersionConstraint>,
+    },
+
+    /// All of the operands are required.
+    And(Vec<RichDep>),
+
+    /// Any one of the operands is required.
+    Or(Vec<RichDep>),
+
+    /// A single package must satisfy all of the operands.
+    With(Vec<RichDep>),
+
+    /// A single package must satisfy the first operand but not the second.
+    Without(Box<RichDep>, Box<RichDep>),
+
+    /// `then` is required if `condition` is met, and otherwise `otherwise`
+    /// is, if given.
+    If {
+        /// What is required when the condition is met.
+        then: Box<RichDep>,
+
+        /// The condition, usually an installed package.
+        condition: Box<RichDep>,
+
+        /// What is required when the condition isn't met, if anything.
+        otherwise: Option<Box<RichDep>>,
+    },
+
+    /// `then` is required unless `condition` is met, and otherwise
+    /// `otherwise` is, if given.
+    Unless {
+        /// What is required when the condition isn't met.
+        then: Box<RichDep>,
+
+        /// The condition, usually an installed package.
+        condition: Box<RichDep>,
+
+        /// What is required when the condition is met, if anything.
+        otherwise: Option<Box<RichDep>>,
+    },
+}
+
+impl RichDep {
+    /// Whether this is a rich dependency rather than a single capability.
+    pub fn is_rich(&self) -> bool {
+        !matches!(self, RichDep::Capability { .. })
+    }
+
+    /// The names of every capability mentioned, in order, including those
+    /// only in conditions.
+    pub fn capabilities(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.visit(&mut |name| names.push(name));
+        names
+    }
+
+    fn visit<'a>(&'a self, f: &mut dyn FnMut(&'a str)) {
+        match self {
+            RichDep::Capability { name, .. } => f(name),
+            RichDep::And(operands) | RichDep::Or(operands) | RichDep::With(operands) => {
+                for operand in operands {
+                    operand.visit(f);
+                }
+            }
+            RichDep::Without(first, second) => {
+                first.visit(f);
+                second.visit(f);
+            }
+            RichDep::If {
+                then,
+                condition,
+                otherwise,
+            }
+            | RichDep::Unless {
+                then,
+                condition,
+                otherwise,
+            } => {
+                then.visit(f);
+                condition.visit(f);
+                if let Some(otherwise) = otherwise {
+                    otherwise.visit(f);
+                }
+            }
+        }
+    }
+
+    /// Whether the dependency is met, given whether each capability is.
+    ///
+    /// `with` and `without` are treated as `and` and `and not`, as whether a
+    /// single package provides the capabilities can't be told from names.
+    pub fn evaluate<F: Fn(&str, Option<&VersionConstraint>) -> bool>(&self, is_met: F) -> bool {
+        self.evaluate_with(&is_met)
+    }
+
+    fn evaluate_with(&self, is_met: &dyn Fn(&str, Option<&VersionConstraint>) -> bool) -> bool {
+        let branch = |met: bool, then: &RichDep, otherwise: &Option<Box<RichDep>>| {
+            if met {
+                then.evaluate_with(is_met)
+            } else {
+                otherwise
+                    .as_ref()
+                    .is_none_or(|otherwise| otherwise.evaluate_with(is_met))
+            }
+        };
+        match self {
+            RichDep::Capability { name, constraint } => is_met(name, constraint.as_ref()),
+            RichDep::And(operands) | RichDep::With(operands) => {
+                operands.iter().all(|operand| operand.evaluate_with(is_met))
+            }
+            RichDep::Or(operands) => operands.iter().any(|operand| operand.evaluate_with(is_met)),
+            RichDep::Without(first, second) => {
+                first.evaluate_with(is_met) && !second.evaluate_with(is_met)
+            }
+            RichDep::If {
+                then,
+                condition,
+                otherwise,
+            } => branch(condition.evaluate_with(is_met), then, otherwise),
+            RichDep::Unless {
+                then,
+                condition,
+                otherwise,
+            } => branch(!condition.evaluate_with(is_met), then, otherwise),
+        }
+    }
+}
+
+impl FromStr for RichDep {
+    type Err = PkgError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || PkgError::InvalidPackage(format!("invalid rich dependency: {}", s));
+        let mut parser = Parser {
+            tokens: tokenize(s).ok_or_else(invalid)?,
+            position: 0,
+        };
+        let dep = if parser.peek() == Some(Token::Open) {
+            parser.group()
+        } else {
+            parser.capability()
+        };
+        match dep {
+            Some(dep) if parser.peek().is_none() => Ok(dep),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for RichDep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, operands: &[RichDep], op: &str| {
+            write!(f, "(")?;
+            for (i, operand) in operands.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " {} ", op)?;
+                }
+                write!(f, "{}", operand)?;
+            }
+            write!(f, ")")
+        };
+        let conditional = |f: &mut fmt::Formatter<'_>,
+                           then: &RichDep,
+                           op: &str,
+                           condition: &RichDep,
+                           otherwise: &Option<Box<RichDep>>| {
+            write!(f, "({} {} {}", then, op, condition)?;
+            if let Some(otherwise) = otherwise {
+                write!(f, " else {}", otherwise)?;
+            }
+            write!(f, ")")
+        };
+        match self {
+            RichDep::Capability {
+                name,
+                constraint: Some(constraint),
+            } => write!(f, "{} {}", name, constraint),
+            RichDep::Capability { name, .. } => write!(f, "{}", name),
+            RichDep::And(operands) => join(f, operands, "and"),
+            RichDep::Or(operands) => join(f, operands, "or"),
+            RichDep::With(operands) => join(f, operands, "with"),
+            RichDep::Without(first, second) => write!(f, "({} without {})", first, second),
+            RichDep::If {
+                then,
+                condition,
+                otherwise,
+            } => conditional(f, then, "if", condition, otherwise),
+            RichDep::Unless {
+                then,
+                condition,
+                otherwise,
+            } => conditional(f, then, "unless", condition, otherwise),
+        }
+    }
+}
+
+/// A token of a rich dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Open,
+    Close,
+    Word(&'a str),
+}
+
+/// Split a dependency into parentheses and words. Parentheses inside a word,
+/// as in `perl(Foo)`, are part of it.
+fn tokenize(s: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            let _ = chars.next();
+        } else if c == '(' || c == ')' {
+            let _ = chars.next();
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+        } else {
+            let mut depth = 0;
+            let mut end = s.len();
+            while let Some(&(i, c)) = chars.peek() {
+                match c {
+                    '(' => depth += 1,
+                    ')' if depth == 0 => {
+                        end = i;
+                        break;
+                    }
+                    ')' => depth -= 1,
+                    c if c.is_whitespace() && depth == 0 => {
+                        end = i;
+                        break;
+                    }
+                    _ => {}
+                }
+                let _ = chars.next();
+            }
+            if depth != 0 {
+                return None;
+            }
+            tokens.push(Token::Word(&s[start..end]));
+        }
+    }
+    Some(tokens)
+}
+
+/// A recursive descent parser over the tokens of a rich dependency.
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    /// Parse a parenthesized expression.
+    fn group(&mut self) -> Option<RichDep> {
+        if self.next()? != Token::Open {
+            return None;
+        }
+        let first = self.operand()?;
+        let dep = match self.next()? {
+            Token::Close => return Some(first),
+            Token::Word(op @ ("and" | "or" | "with")) => {
+                let mut operands = vec![first, self.operand()?];
+                while self.peek() == Some(Token::Word(op)) {
+                    let _ = self.next();
+                    operands.push(self.operand()?);
+                }
+                match op {
+                    "and" => RichDep::And(operands),
+                    "or" => RichDep::Or(operands),
+                    _ => RichDep::With(operands),
+                }
+            }
+            Token::Word("without") => RichDep::Without(Box::new(first), Box::new(self.operand()?)),
+            Token::Word(op @ ("if" | "unless")) => {
+                let then = Box::new(first);
+                let condition = Box::new(self.operand()?);
+                let otherwise = if self.peek() == Some(Token::Word("else")) {
+                    let _ = self.next();
+                    Some(Box::new(self.operand()?))
+                } else {
+                    None
+                };
+                if op == "if" {
+                    RichDep::If {
+                        then,
+                        condition,
+                        otherwise,
+                    }
+                } else {
+                    RichDep::Unless {
+                        then,
+                        condition,
+                        otherwise,
+                    }
+                }
+            }
+            Token::Open | Token::Word(_) => return None,
+        };
+        if self.next()? != Token::Close {
+            return None;
+        }
+        Some(dep)
+    }
+
+    /// Parse a capability or a nested group.
+    fn operand(&mut self) -> Option<RichDep> {
+        match self.peek()? {
+            Token::Open => self.group(),
+            _ => self.capability(),
+        }
+    }
+
+    /// Parse a name with an optional version constraint, e.g. `foo >= 1.0`.
+    fn capability(&mut self) -> Option<RichDep> {
+        let name = match self.next()? {
+            Token::Word(name) if !is_keyword(name) => name.to_owned(),
+            _ => return None,
+        };
+        let op = match self.peek() {
+            Some(Token::Word(op)) => parse_op(op),
+            _ => None,
+        };
+        let constraint = match op {
+            Some(op) => {
+                let _ = self.next();
+                match self.next()? {
+                    Token::Word(version) if !is_keyword(version) => Some(VersionConstraint {
+                        op,
+                        version: version.to_owned(),
+                    }),
+                    _ => return None,
+                }
+            }
+            None => None,
+        };
+        Some(RichDep::Capability { name, constraint })
+    }
+}
+
+/// Whether a word is one of the boolean operators.
+fn is_keyword(word: &str) -> bool {
+    matches!(
+        word,
+        "and" | "or" | "if" | "else" | "unless" | "with" | "without"
+    )
+}
+
+/// Parse a version comparison operator.
+fn parse_op(op: &str) -> Option<VersionOp> {
+    match op {
+        "<" => Some(VersionOp::Lt),
+        "<=" => Some(VersionOp::Le),
+        "=" | "==" => Some(VersionOp::Eq),
+        ">=" => Some(VersionOp::Ge),
+        ">" => Some(VersionOp::Gt),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capability(name: &str, constraint: Option<(VersionOp, &str)>) -> RichDep {
+        RichDep::Capability {
+            name: name.to_string(),
+            constraint: constraint.map(|(op, version)| VersionConstraint {
+                op,
+                version: version.to_string(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "(foo >= 1.0 with bar)".parse::<RichDep>().unwrap(),
+            RichDep::With(vec![
+                capability("foo", Some((VersionOp::Ge, "1.0"))),
+                capability("bar", None),
+            ])
+        );
+        assert_eq!(
+            "(pkgconfig(glib-2.0) if (gtk3 or gtk4) else glib2)"
+                .parse::<RichDep>()
+                .unwrap(),
+            RichDep::If {
+                then: Box::new(capability("pkgconfig(glib-2.0)", None)),
+                condition: Box::new(RichDep::Or(vec![
+                    capability("gtk3", None),
+                    capability("gtk4", None),
+                ])),
+                otherwise: Some(Box::new(capability("glib2", None))),
+            }
+        );
+        assert_eq!(
+            "foo = 2:1.0-1".parse::<RichDep>().unwrap(),
+            capability("foo", Some((VersionOp::Eq, "2:1.0-1")))
+        );
+        assert!(!"foo".parse::<RichDep>().unwrap().is_rich());
+
+        for invalid in &[
+            "",
+            "(foo",
+            "(foo and bar or baz)",
+            "(foo >=)",
+            "(and foo)",
+            "foo bar",
+            "(foo(bar)",
+            "(foo without bar without baz)",
+        ] {
+            assert!(invalid.parse::<RichDep>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for dep in &[
+            "(foo >= 1.0 with bar)",
+            "(a and (b or c) and d)",
+            "(a unless b else c)",
+            "(a without b)",
+            "foo < 2",
+        ] {
+            assert_eq!(dep.parse::<RichDep>().unwrap().to_string(), *dep);
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let dep: RichDep = "((a or b) and (c if d else e) and (f without g))"
+            .parse()
+            .unwrap();
+        assert_eq!(dep.capabilities(), vec!["a", "b", "c", "d", "e", "f", "g"]);
+
+        let installed = |names: &'static [&'static str]| {
+            move |name: &str, _: Option<&VersionConstraint>| names.contains(&name)
+        };
+        assert!(dep.evaluate(installed(&["b", "e", "f"])));
+        assert!(dep.evaluate(installed(&["a", "c", "d", "f"])));
+        assert!(!dep.evaluate(installed(&["a", "d", "e", "f"])));
+        assert!(!dep.evaluate(installed(&["a", "e", "f", "g"])));
+    }
+}
@@ -1,13 +1,210 @@
 //! Support for remote RPM packages
+use std::collections::HashMap;
 use std::io::Read;
+use std::sync::Arc;
+#[cfg(feature = "http-blocking")]
+use std::time::Duration;
 
-use fez::{RPMPackageMetadata, RpmPkgReader};
+use fez::{IndexTag, RPMPackageMetadata, RpmPkgReader};
+use num_traits::FromPrimitive;
 
-use crate::{PkgError, RemotePackage};
+use crate::compression::Compression;
+use crate::limits::Limits;
+use crate::payload::{link_files, FileEntry, S_IFMT, S_IFREG};
+#[cfg(feature = "rich-deps")]
+use crate::rich_dep::RichDep;
+use crate::rpm_header::{
+    Header, RpmHeaders, RPMFILE_CONFIG, RPMSENSE_EQUAL, RPMSENSE_GREATER, RPMSENSE_LESS,
+    RPMSENSE_TRIGGERIN, RPMSENSE_TRIGGERPOSTUN, RPMSENSE_TRIGGERPREIN, RPMSENSE_TRIGGERUN,
+    RPMTAG_BASENAMES, RPMTAG_BUILDHOST, RPMTAG_BUILDTIME, RPMTAG_COOKIE, RPMTAG_DESCRIPTION,
+    RPMTAG_DIRINDEXES, RPMTAG_DIRNAMES, RPMTAG_DISTRIBUTION, RPMTAG_ENHANCEFLAGS,
+    RPMTAG_ENHANCENAME, RPMTAG_ENHANCEVERSION, RPMTAG_EPOCH, RPMTAG_FILEDEVICES,
+    RPMTAG_FILEDIGESTALGO, RPMTAG_FILEDIGESTS, RPMTAG_FILEFLAGS, RPMTAG_FILEGROUPNAME,
+    RPMTAG_FILEINODES, RPMTAG_FILELINKTOS, RPMTAG_FILEMODES, RPMTAG_FILEMTIMES, RPMTAG_FILESIZES,
+    RPMTAG_FILEUSERNAME, RPMTAG_LICENSE, RPMTAG_LONGFILESIZES, RPMTAG_OPTFLAGS,
+    RPMTAG_PAYLOADCOMPRESSOR, RPMTAG_PAYLOADDIGEST, RPMTAG_PAYLOADDIGESTALGO, RPMTAG_PLATFORM,
+    RPMTAG_POSTIN, RPMTAG_POSTINPROG, RPMTAG_POSTTRANS, RPMTAG_POSTTRANSPROG, RPMTAG_POSTUN,
+    RPMTAG_POSTUNPROG, RPMTAG_PREIN, RPMTAG_PREINPROG, RPMTAG_PRETRANS, RPMTAG_PRETRANSPROG,
+    RPMTAG_PREUN, RPMTAG_PREUNPROG, RPMTAG_RECOMMENDFLAGS, RPMTAG_RECOMMENDNAME,
+    RPMTAG_RECOMMENDVERSION, RPMTAG_REQUIREFLAGS, RPMTAG_REQUIRENAME, RPMTAG_REQUIREVERSION,
+    RPMTAG_SOURCERPM, RPMTAG_SUGGESTFLAGS, RPMTAG_SUGGESTNAME, RPMTAG_SUGGESTVERSION,
+    RPMTAG_SUMMARY, RPMTAG_SUPPLEMENTFLAGS, RPMTAG_SUPPLEMENTNAME, RPMTAG_SUPPLEMENTVERSION,
+    RPMTAG_TRIGGERFLAGS, RPMTAG_TRIGGERINDEX, RPMTAG_TRIGGERNAME, RPMTAG_TRIGGERSCRIPTPROG,
+    RPMTAG_TRIGGERSCRIPTS, RPMTAG_TRIGGERVERSION, RPMTAG_VENDOR, SIGTAG_SHA256,
+};
+#[cfg(feature = "verify")]
+use crate::rpm_header::{SIGTAG_DSA, SIGTAG_GPG, SIGTAG_PGP, SIGTAG_RSA};
+#[cfg(feature = "verify")]
+use crate::verify::{Keyring, TrustPolicy, VerificationStatus};
+use crate::{
+    Distribution, PackageRelation, PackageScript, PackageTrigger, PkgError, RelationKind,
+    RemotePackage,
+};
+
+/// The kinds of dependency, with the tags of their names, flags and versions.
+const RELATIONS: [(RelationKind, u32, u32, u32); 5] = [
+    (
+        RelationKind::Depends,
+        RPMTAG_REQUIRENAME,
+        RPMTAG_REQUIREFLAGS,
+        RPMTAG_REQUIREVERSION,
+    ),
+    (
+        RelationKind::Recommends,
+        RPMTAG_RECOMMENDNAME,
+        RPMTAG_RECOMMENDFLAGS,
+        RPMTAG_RECOMMENDVERSION,
+    ),
+    (
+        RelationKind::Suggests,
+        RPMTAG_SUGGESTNAME,
+        RPMTAG_SUGGESTFLAGS,
+        RPMTAG_SUGGESTVERSION,
+    ),
+    (
+        RelationKind::Supplements,
+        RPMTAG_SUPPLEMENTNAME,
+        RPMTAG_SUPPLEMENTFLAGS,
+        RPMTAG_SUPPLEMENTVERSION,
+    ),
+    (
+        RelationKind::Enhances,
+        RPMTAG_ENHANCENAME,
+        RPMTAG_ENHANCEFLAGS,
+        RPMTAG_ENHANCEVERSION,
+    ),
+];
+
+/// The scriptlets rpm runs, in the order they run on install and removal,
+/// with the tags of their contents and interpreter.
+const SCRIPTLETS: [(&str, u32, u32); 6] = [
+    ("%pretrans", RPMTAG_PRETRANS, RPMTAG_PRETRANSPROG),
+    ("%pre", RPMTAG_PREIN, RPMTAG_PREINPROG),
+    ("%post", RPMTAG_POSTIN, RPMTAG_POSTINPROG),
+    ("%preun", RPMTAG_PREUN, RPMTAG_PREUNPROG),
+    ("%postun", RPMTAG_POSTUN, RPMTAG_POSTUNPROG),
+    ("%posttrans", RPMTAG_POSTTRANS, RPMTAG_POSTTRANSPROG),
+];
+
+/// How and where an RPM was built, for checking that it can be reproduced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpmBuildInfo {
+    /// The host the package was built on.
+    pub build_host: Option<String>,
+
+    /// When the package was built, in seconds since the Unix epoch.
+    pub build_time: Option<u32>,
+
+    /// The file name of the source package, e.g. `hello-2.10-1.src.rpm`.
+    pub source_rpm: Option<String>,
+
+    /// The compiler flags used, e.g. `-O2 -g`.
+    pub opt_flags: Option<String>,
+
+    /// The build platform, e.g. `x86_64-redhat-linux-gnu`.
+    pub platform: Option<String>,
+
+    /// A value identifying the build, made from the build host and time.
+    pub cookie: Option<String>,
+
+    /// The hex digest of the compressed payload, and its algorithm, e.g.
+    /// `sha256`.
+    pub payload_digest: Option<(String, String)>,
+
+    /// The hex SHA-256 digest of the main header.
+    pub header_sha256: Option<String>,
+}
+
+/// The number of a main header tag, e.g. `1000` for the name. Tags can be
+/// given by number or as a [`fez::IndexTag`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TagId(pub u32);
+
+/// The highest tag number searched for by name.
+const MAX_NAMED_TAG: u32 = 6000;
+
+impl TagId {
+    /// Look up a tag by its name, e.g. `RPMTAG_SOURCERPM`, or without the
+    /// prefix and in any case, e.g. `sourcerpm`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_uppercase();
+        let name = name.strip_prefix("RPMTAG_").unwrap_or(&name);
+        (0..=MAX_NAMED_TAG)
+            .filter_map(|tag| IndexTag::from_u32(tag).map(|index_tag| (tag, index_tag)))
+            .find(|(_, index_tag)| index_tag.to_string().strip_prefix("RPMTAG_") == Some(name))
+            .map(|(tag, _)| TagId(tag))
+    }
+
+    /// The name of the tag, e.g. `RPMTAG_SOURCERPM`, if it's known.
+    pub fn name(self) -> Option<String> {
+        IndexTag::from_u32(self.0).map(|tag| tag.to_string())
+    }
+}
+
+impl From<u32> for TagId {
+    fn from(tag: u32) -> Self {
+        TagId(tag)
+    }
+}
+
+impl From<IndexTag> for TagId {
+    fn from(tag: IndexTag) -> Self {
+        TagId(tag as u32)
+    }
+}
+
+/// The value of a header tag, by its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagValue {
+    /// A string.
+    String(String),
+
+    /// An array of strings. For a translated string, these are the values
+    /// for each locale, the untranslated one first.
+    StringArray(Vec<String>),
+
+    /// An array of integers of any width, including characters.
+    IntArray(Vec<u64>),
+
+    /// Binary data.
+    Binary(Vec<u8>),
+}
+
+impl TagValue {
+    /// The value as a string: a string, or the first of an array.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            TagValue::String(value) => Some(value),
+            TagValue::StringArray(values) => values.first().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// The value as an integer: the first of an integer array.
+    pub fn as_int(&self) -> Option<u64> {
+        match self {
+            TagValue::IntArray(values) => values.first().copied(),
+            _ => None,
+        }
+    }
+}
 
 /// A structure representing a remote RPM package.
+///
+/// The parsed headers are shared, so clones are cheap.
+#[derive(Clone)]
 pub struct RpmRemotePackage {
-    metadata: RPMPackageMetadata,
+    metadata: Arc<RPMPackageMetadata>,
+
+    /// The raw headers, for the tags that fez doesn't expose.
+    signature: Arc<Header>,
+    header: Arc<Header>,
+
+    /// The size of the lead and headers.
+    bytes_consumed: u64,
+
+    #[cfg(feature = "verify")]
+    verification: Option<VerificationStatus>,
 }
 
 impl RpmRemotePackage {
@@ -15,27 +212,507 @@ impl RpmRemotePackage {
     ///
     /// Uses a blocking tokio client to download the remote package - if
     /// using this in an async environment, surround this with tokio::spawn_blocking.
-    #[cfg(feature = "http")]
+    #[cfg(feature = "http-blocking")]
     pub fn new_from_url(url: &str) -> Result<Self, PkgError> {
-        let client = reqwest::blocking::Client::new();
+        Self::fetch(url, None)
+    }
+
+    /// Attempts to create an `RpmRemotePackage` from a URL, waiting up to
+    /// `timeout` for the response and for each read of its body in place of
+    /// the read timeout of the client; see [`crate::from_url_with_timeout`].
+    ///
+    /// Uses a blocking tokio client to download the remote package - if
+    /// using this in an async environment, surround this with tokio::spawn_blocking.
+    #[cfg(feature = "http-blocking")]
+    pub fn new_from_url_with_timeout(url: &str, timeout: Duration) -> Result<Self, PkgError> {
+        Self::fetch(url, Some(timeout))
+    }
 
+    /// Download and parse a package for [`RpmRemotePackage::new_from_url`].
+    #[cfg(feature = "http-blocking")]
+    fn fetch(url: &str, timeout: Option<Duration>) -> Result<Self, PkgError> {
         // Send an HTTP request for the package and get the Response.
-        let response = client
-            .get(url)
-            .timeout(std::time::Duration::from_secs(10))
-            .send()?;
+        let request = crate::http::with_timeout(crate::http::client().get(url), timeout);
+        let response = crate::http::send(request)
+            .and_then(|response| Ok(response.error_for_status()?))
+            .map_err(|err| err.in_context(url, None))?;
 
         // blocking::Response impls Read, so we can pass it to new_from_read.
-        Self::new_from_read(response)
+        let mut transfer = crate::http::Transfer::from_response(response)
+            .map_err(|err| err.in_context(url, None))?;
+        Self::new_from_read(&mut transfer)
+            .map_err(|err| err.in_context(url, Some(transfer.bytes_transferred())))
     }
 
     /// Attempts to create a `RpmRemotePackage` from something that impls
     /// Read.
+    ///
+    /// Only the lead and headers are read, leaving the reader at the start
+    /// of the payload; see [`RpmRemotePackage::bytes_consumed`]. The default
+    /// [`Limits`] are applied to the headers.
     pub fn new_from_read<R: Read>(reader: R) -> Result<Self, PkgError> {
-        let mut package = RpmPkgReader::parse(reader)?;
+        Self::new_from_read_with_limits(reader, &Limits::default())
+    }
+
+    /// Attempts to create a `RpmRemotePackage` from something that impls
+    /// Read, applying the given limits to the lead and headers.
+    pub fn new_from_read_with_limits<R: Read>(
+        mut reader: R,
+        limits: &Limits,
+    ) -> Result<Self, PkgError> {
+        let headers = RpmHeaders::read(&mut reader, limits)?;
+        Self::from_headers(&headers)
+    }
+
+    /// Attempts to create an `RpmRemotePackage` from a URL, checking its
+    /// signature against a keyring; see
+    /// [`RpmRemotePackage::new_from_read_with_keyring`].
+    ///
+    /// Uses a blocking tokio client to download the remote package - if
+    /// using this in an async environment, surround this with tokio::spawn_blocking.
+    #[cfg(all(feature = "http-blocking", feature = "verify"))]
+    pub fn new_from_url_with_keyring<P: Into<TrustPolicy>>(
+        url: &str,
+        keyring: &Keyring,
+        policy: P,
+    ) -> Result<Self, PkgError> {
+        let response = crate::http::get_package(url)?;
+        Self::new_from_read_with_keyring(response, keyring, policy)
+    }
+
+    /// Attempts to create an `RpmRemotePackage` from something that impls
+    /// Read, checking its signature against a keyring.
+    ///
+    /// Header-only signatures are preferred, so only the headers need to be
+    /// read. Packages that are only signed over the header and payload are
+    /// read in full. Fails unless the signature meets `policy`, which may be
+    /// a [`TrustPolicy`] or a [`VerifyMode`](crate::verify::VerifyMode).
+    #[cfg(feature = "verify")]
+    pub fn new_from_read_with_keyring<R: Read, P: Into<TrustPolicy>>(
+        mut reader: R,
+        keyring: &Keyring,
+        policy: P,
+    ) -> Result<Self, PkgError> {
+        let headers = RpmHeaders::read(&mut reader, &Limits::default())?;
+        let status = policy
+            .into()
+            .apply(vec![verify_signature(&headers, reader, keyring)?])?;
+
+        let mut package = Self::from_headers(&headers)?;
+        package.verification = Some(status);
+        Ok(package)
+    }
+
+    /// The result of checking the package signature, if it was checked.
+    #[cfg(feature = "verify")]
+    pub fn verification_status(&self) -> Option<&VerificationStatus> {
+        self.verification.as_ref()
+    }
+
+    /// The number of bytes read to parse the package: the lead, the headers
+    /// and the padding between them. The payload starts at this offset.
+    pub fn bytes_consumed(&self) -> u64 {
+        self.bytes_consumed
+    }
+
+    /// Get the requirements of the package with their version constraints,
+    /// parsing rich dependencies such as `(foo >= 1.0 with bar)` into their
+    /// boolean expressions. Requirements on `rpmlib(...)` features are left
+    /// out.
+    #[cfg(feature = "rich-deps")]
+    pub fn requires(&self) -> Result<Vec<RichDep>, PkgError> {
+        self.relations(RelationKind::Depends)
+    }
+
+    /// Get the dependencies of a kind, such as the weak
+    /// [`RelationKind::Recommends`], parsed like [`RpmRemotePackage::requires`].
+    #[cfg(feature = "rich-deps")]
+    pub fn relations(&self, kind: RelationKind) -> Result<Vec<RichDep>, PkgError> {
+        relations(&self.header, kind)
+            .iter()
+            .map(|relation| relation.parse())
+            .collect()
+    }
+
+    /// Get the build environment and digests recorded in the headers.
+    pub fn build_info(&self) -> RpmBuildInfo {
+        let string = |tag| self.header.string(tag).map(str::to_owned);
+
+        // The payload digest algorithm defaults to SHA-256.
+        let algorithm = hash_algorithm(self.header.int32(RPMTAG_PAYLOADDIGESTALGO).unwrap_or(8));
+
+        RpmBuildInfo {
+            build_host: string(RPMTAG_BUILDHOST),
+            build_time: self.header.int32(RPMTAG_BUILDTIME),
+            source_rpm: string(RPMTAG_SOURCERPM),
+            opt_flags: string(RPMTAG_OPTFLAGS),
+            platform: string(RPMTAG_PLATFORM),
+            cookie: string(RPMTAG_COOKIE),
+            payload_digest: self
+                .header
+                .string_array(RPMTAG_PAYLOADDIGEST)
+                .first()
+                .map(|digest| (algorithm.to_string(), (*digest).to_owned())),
+            header_sha256: self.signature.string(SIGTAG_SHA256).map(str::to_owned),
+        }
+    }
+
+    /// Get the compression of the payload, or `None` if the compressor
+    /// named in the header isn't known.
+    ///
+    /// Packages that don't name a compressor have a gzipped payload.
+    pub fn payload_compression(&self) -> Option<Compression> {
+        match self.header.string(RPMTAG_PAYLOADCOMPRESSOR) {
+            None | Some("gzip") => Some(Compression::Gzip),
+            Some("xz") => Some(Compression::Xz),
+            Some("lzma") => Some(Compression::Lzma),
+            Some("zstd") => Some(Compression::Zstd),
+            Some("bzip2") => Some(Compression::Bzip2),
+            Some("identity") => Some(Compression::None),
+            Some(_) => None,
+        }
+    }
+
+    /// Get the value of any main header tag, e.g. `TagId(5011)` or
+    /// `IndexTag::RPMTAG_MODULARITYLABEL`, for fields this crate doesn't
+    /// expose. Returns `None` if the package doesn't have the tag.
+    pub fn get_tag<T: Into<TagId>>(&self, tag: T) -> Option<TagValue> {
+        self.header.value(tag.into().0)
+    }
+
+    /// Get the value of a main header tag by its name; see
+    /// [`TagId::from_name`].
+    pub fn get_tag_by_name(&self, name: &str) -> Option<TagValue> {
+        self.get_tag(TagId::from_name(name)?)
+    }
+
+    /// Get the one line summary in the language `lang`, falling back as
+    /// [`description_localized`](RemotePackage::description_localized)
+    /// does.
+    pub fn summary_localized(&self, lang: &str) -> Option<&str> {
+        self.header.i18n_string(RPMTAG_SUMMARY, lang)
+    }
+
+    /// Get the metadata of the files in the package from the main header,
+    /// without reading the payload.
+    ///
+    /// Owners are only recorded by name, so the numeric ids are `None`.
+    pub fn files(&self) -> Vec<FileEntry> {
+        files(&self.header)
+    }
+
+    /// Parse the metadata out of the raw headers.
+    fn from_headers(headers: &RpmHeaders) -> Result<Self, PkgError> {
+        let mut package = RpmPkgReader::parse(headers.as_bytes())?;
         let metadata = package.metadata()?;
 
-        Ok(Self { metadata })
+        Ok(Self {
+            metadata: Arc::new(metadata),
+            signature: Arc::new(headers.signature.clone()),
+            header: Arc::new(headers.header.clone()),
+            bytes_consumed: headers.as_bytes().len() as u64,
+            #[cfg(feature = "verify")]
+            verification: None,
+        })
+    }
+}
+
+/// Get the scriptlets in a main header.
+fn scriptlets(header: &Header) -> Vec<PackageScript> {
+    SCRIPTLETS
+        .iter()
+        .filter_map(|(name, script_tag, prog_tag)| {
+            let contents = header.string(*script_tag);
+            // Newer packages store the interpreter and its arguments as an
+            // array.
+            let interpreter = header.string(*prog_tag).map(str::to_owned).or_else(|| {
+                let prog = header.string_array(*prog_tag);
+                if prog.is_empty() {
+                    None
+                } else {
+                    Some(prog.join(" "))
+                }
+            });
+            if contents.is_none() && interpreter.is_none() {
+                return None;
+            }
+            Some(PackageScript {
+                name: (*name).to_owned(),
+                interpreter,
+                contents: contents.unwrap_or_default().to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// The trigger types rpm supports, by their dependency flag.
+const TRIGGERS: [(&str, u32); 4] = [
+    ("%triggerprein", RPMSENSE_TRIGGERPREIN),
+    ("%triggerin", RPMSENSE_TRIGGERIN),
+    ("%triggerun", RPMSENSE_TRIGGERUN),
+    ("%triggerpostun", RPMSENSE_TRIGGERPOSTUN),
+];
+
+/// Get the trigger conditions in a main header, with their scriptlets.
+fn triggers(header: &Header) -> Vec<PackageTrigger> {
+    let versions = header.string_array(RPMTAG_TRIGGERVERSION);
+    let flags = header.int32_array(RPMTAG_TRIGGERFLAGS);
+    let indexes = header.int32_array(RPMTAG_TRIGGERINDEX);
+    let scripts = header.string_array(RPMTAG_TRIGGERSCRIPTS);
+    let progs = header.string_array(RPMTAG_TRIGGERSCRIPTPROG);
+
+    header
+        .string_array(RPMTAG_TRIGGERNAME)
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let flags = flags.get(i).copied().unwrap_or_default();
+            let kind = TRIGGERS
+                .iter()
+                .find(|(_, flag)| flags & flag != 0)
+                .map_or("%trigger", |(kind, _)| kind);
+
+            let comparison = comparison(flags);
+            let condition = match versions.get(i) {
+                Some(version) if !comparison.is_empty() && !version.is_empty() => {
+                    format!("{} {} {}", name, comparison, version)
+                }
+                _ => (*name).to_owned(),
+            };
+
+            let script = indexes
+                .get(i)
+                .and_then(|&index| {
+                    scripts
+                        .get(index as usize)
+                        .map(|contents| (index, contents))
+                })
+                .map(|(index, contents)| PackageScript {
+                    name: kind.to_owned(),
+                    interpreter: progs
+                        .get(index as usize)
+                        .filter(|prog| !prog.is_empty())
+                        .map(|prog| (*prog).to_owned()),
+                    contents: (*contents).to_owned(),
+                });
+
+            PackageTrigger {
+                kind: kind.to_owned(),
+                condition,
+                script,
+            }
+        })
+        .collect()
+}
+
+/// Get the relations of a kind in a main header, as text such as
+/// `glibc >= 2.34` or a rich dependency. Requirements on `rpmlib(...)`
+/// features are satisfied by rpm itself, so are left out.
+fn relations(header: &Header, kind: RelationKind) -> Vec<String> {
+    let (_, name_tag, flags_tag, version_tag) = RELATIONS
+        .iter()
+        .find(|(relation_kind, ..)| *relation_kind == kind)
+        .copied()
+        .unwrap_or(RELATIONS[0]);
+    let flags = header.int32_array(flags_tag);
+    let versions = header.string_array(version_tag);
+
+    header
+        .string_array(name_tag)
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| !name.starts_with("rpmlib("))
+        .map(|(i, name)| {
+            let comparison = comparison(flags.get(i).copied().unwrap_or_default());
+            match versions.get(i) {
+                Some(version) if !comparison.is_empty() && !version.is_empty() => {
+                    format!("{} {} {}", name, comparison, version)
+                }
+                _ => (*name).to_owned(),
+            }
+        })
+        .collect()
+}
+
+/// Get the version comparison in dependency flags, e.g. `>=`, or an empty
+/// string if there is none.
+fn comparison(flags: u32) -> &'static str {
+    match flags & (RPMSENSE_LESS | RPMSENSE_GREATER | RPMSENSE_EQUAL) {
+        RPMSENSE_LESS => "<",
+        RPMSENSE_GREATER => ">",
+        RPMSENSE_EQUAL => "=",
+        f if f == RPMSENSE_LESS | RPMSENSE_EQUAL => "<=",
+        f if f == RPMSENSE_GREATER | RPMSENSE_EQUAL => ">=",
+        _ => "",
+    }
+}
+
+/// Find the dist tag in a release, e.g. `.el9_3` in `1.el9_3.1`: the last
+/// dot separated part that is letters followed by a digit.
+fn dist_tag(release: &str) -> Option<&str> {
+    release.rmatch_indices('.').find_map(|(dot, _)| {
+        let part = release[dot + 1..].split('.').next().unwrap_or_default();
+        let letters = part.bytes().take_while(u8::is_ascii_alphabetic).count();
+        let is_tag = letters > 0 && part[letters..].starts_with(|c: char| c.is_ascii_digit());
+        is_tag.then(|| &release[dot..dot + 1 + part.len()])
+    })
+}
+
+/// Get the paths of the files in a main header, in header order. Files with
+/// a bad directory index get an empty path, so the other file tags still
+/// line up.
+fn file_paths(header: &Header) -> Vec<String> {
+    let dirs = header.string_array(RPMTAG_DIRNAMES);
+    let dir_indexes = header.int32_array(RPMTAG_DIRINDEXES);
+
+    header
+        .string_array(RPMTAG_BASENAMES)
+        .iter()
+        .zip(dir_indexes.iter())
+        .map(|(name, dir)| {
+            dirs.get(*dir as usize)
+                .map(|dir| format!("{}{}", dir, name))
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Get the paths of the files in a main header marked `%config`.
+fn config_files(header: &Header) -> Vec<String> {
+    let flags = header.int32_array(RPMTAG_FILEFLAGS);
+
+    file_paths(header)
+        .into_iter()
+        .zip(flags.iter())
+        .filter(|(path, flags)| *flags & RPMFILE_CONFIG != 0 && !path.is_empty())
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Get the metadata of the files in a main header.
+fn files(header: &Header) -> Vec<FileEntry> {
+    let modes = header.int16_array(RPMTAG_FILEMODES);
+    let mtimes = header.int32_array(RPMTAG_FILEMTIMES);
+    let users = header.string_array(RPMTAG_FILEUSERNAME);
+    let groups = header.string_array(RPMTAG_FILEGROUPNAME);
+    let digests = header.string_array(RPMTAG_FILEDIGESTS);
+    let mut sizes = header.int64_array(RPMTAG_LONGFILESIZES);
+    if sizes.is_empty() {
+        sizes = header
+            .int32_array(RPMTAG_FILESIZES)
+            .into_iter()
+            .map(u64::from)
+            .collect();
+    }
+    let link_tos = header.string_array(RPMTAG_FILELINKTOS);
+    // File digests default to MD5, from before the algorithm was recorded.
+    let algorithm = hash_algorithm(header.int32(RPMTAG_FILEDIGESTALGO).unwrap_or(1));
+
+    let mut files: Vec<FileEntry> = file_paths(header)
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| FileEntry {
+            path,
+            mode: modes.get(i).copied().map(u32::from).unwrap_or_default(),
+            user: users.get(i).map(|user| (*user).to_owned()),
+            group: groups.get(i).map(|group| (*group).to_owned()),
+            uid: None,
+            gid: None,
+            size: sizes.get(i).copied().unwrap_or_default(),
+            mtime: mtimes.get(i).copied().map(u64::from).unwrap_or_default(),
+            // Anything but a regular file has an empty digest.
+            digest: digests
+                .get(i)
+                .filter(|digest| !digest.is_empty())
+                .map(|digest| (algorithm.to_owned(), (*digest).to_owned())),
+            link_target: link_tos
+                .get(i)
+                .filter(|target| !target.is_empty())
+                .map(|target| (*target).to_owned()),
+            hardlinks: Vec::new(),
+        })
+        .collect();
+
+    // Regular files on the same device and inode are hard links.
+    let devices = header.int32_array(RPMTAG_FILEDEVICES);
+    let inodes = header.int32_array(RPMTAG_FILEINODES);
+    let mut links: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        let key = match (devices.get(i), inodes.get(i)) {
+            (Some(&device), Some(&inode)) if file.mode & S_IFMT == S_IFREG => (device, inode),
+            _ => continue,
+        };
+        links.entry(key).or_default().push(i);
+    }
+    link_files(&mut files, links.into_values());
+    files
+}
+
+/// The name of an OpenPGP hash algorithm ID, as rpm records digest
+/// algorithms.
+fn hash_algorithm(id: u32) -> &'static str {
+    match id {
+        1 => "md5",
+        2 => "sha1",
+        8 => "sha256",
+        9 => "sha384",
+        10 => "sha512",
+        11 => "sha224",
+        _ => "unknown",
+    }
+}
+
+/// Check the strongest signature a package carries.
+#[cfg(feature = "verify")]
+fn verify_signature<R: Read>(
+    headers: &RpmHeaders,
+    payload: R,
+    keyring: &Keyring,
+) -> Result<VerificationStatus, PkgError> {
+    let signature = &headers.signature;
+    if let Some(sig) = signature
+        .binary(SIGTAG_RSA)
+        .or_else(|| signature.binary(SIGTAG_DSA))
+    {
+        return Ok(keyring.verify(sig, headers.header.as_bytes()));
+    }
+
+    // Older packages are only signed over the header and payload together.
+    if let Some(sig) = signature
+        .binary(SIGTAG_PGP)
+        .or_else(|| signature.binary(SIGTAG_GPG))
+    {
+        // Stream the payload through the hash rather than buffering it, but
+        // keep any read error so that it isn't reported as a bad signature.
+        let mut data = ErrorTrackingReader {
+            inner: headers.header.as_bytes().chain(payload),
+            error: None,
+        };
+        let status = keyring.verify_read(sig, &mut data);
+        return match data.error {
+            Some(err) => Err(err.into()),
+            None => Ok(status),
+        };
+    }
+
+    Ok(VerificationStatus::Unsigned)
+}
+
+/// A reader that remembers the first error its inner reader returned.
+#[cfg(feature = "verify")]
+struct ErrorTrackingReader<R> {
+    inner: R,
+    error: Option<std::io::Error>,
+}
+
+#[cfg(feature = "verify")]
+impl<R: Read> Read for ErrorTrackingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.inner.read(buf).inspect_err(|err| {
+            if self.error.is_none() {
+                self.error = Some(std::io::Error::new(err.kind(), err.to_string()));
+            }
+        })
     }
 }
 
@@ -60,19 +737,630 @@ impl RemotePackage for RpmRemotePackage {
     fn package_arch(&self) -> Result<&str, PkgError> {
         Ok(self.metadata.header.get_arch()?)
     }
+
+    fn package_license(&self) -> Option<&str> {
+        self.header.string(RPMTAG_LICENSE)
+    }
+
+    /// The `Epoch` header.
+    fn package_epoch(&self) -> Option<u32> {
+        self.header.int32(RPMTAG_EPOCH)
+    }
+
+    /// The description, or the summary if there is no description.
+    fn package_description(&self) -> Option<&str> {
+        self.header
+            .string(RPMTAG_DESCRIPTION)
+            .or_else(|| self.header.string(RPMTAG_SUMMARY))
+    }
+
+    /// The translated description, or the translated summary if there is no
+    /// description.
+    fn description_localized(&self, lang: &str) -> Option<&str> {
+        self.header
+            .i18n_string(RPMTAG_DESCRIPTION, lang)
+            .or_else(|| self.header.i18n_string(RPMTAG_SUMMARY, lang))
+    }
+
+    /// The `Vendor` header.
+    fn package_vendor(&self) -> Option<&str> {
+        self.header.string(RPMTAG_VENDOR)
+    }
+
+    /// Requirements on `rpmlib(...)` features are satisfied by rpm itself,
+    /// so are left out.
+    fn package_dependencies(&self) -> Vec<String> {
+        let mut dependencies: Vec<String> = Vec::new();
+        for name in self.header.string_array(RPMTAG_REQUIRENAME) {
+            if !name.starts_with("rpmlib(") && !dependencies.iter().any(|d| d == name) {
+                dependencies.push(name.to_owned());
+            }
+        }
+        dependencies
+    }
+
+    /// `Requires`, and the weak dependencies, with their version constraints
+    /// and rich dependencies as written.
+    fn package_relations(&self) -> Vec<PackageRelation> {
+        RELATIONS
+            .iter()
+            .flat_map(|(kind, ..)| {
+                relations(&self.header, *kind)
+                    .into_iter()
+                    .map(move |relation| PackageRelation {
+                        kind: *kind,
+                        relation,
+                    })
+            })
+            .collect()
+    }
+
+    /// The scriptlets other than triggers and file triggers.
+    fn package_scripts(&self) -> Vec<PackageScript> {
+        scriptlets(&self.header)
+    }
+
+    /// The classic package triggers, each with its scriptlet. File triggers
+    /// aren't included.
+    fn package_triggers(&self) -> Vec<PackageTrigger> {
+        triggers(&self.header)
+    }
+
+    /// The `DISTRIBUTION` header, and the dist tag in the release.
+    fn package_distribution(&self) -> Distribution {
+        Distribution {
+            name: self.header.string(RPMTAG_DISTRIBUTION).map(str::to_owned),
+            tag: self
+                .package_iteration()
+                .and_then(dist_tag)
+                .map(str::to_owned),
+        }
+    }
+
+    /// Files marked `%config`, including `%config(noreplace)`.
+    fn package_config_files(&self) -> Vec<String> {
+        config_files(&self.header)
+    }
+
+    fn package_files(&self) -> Vec<String> {
+        self.metadata
+            .header
+            .get_file_paths()
+            .map(|paths| {
+                paths
+                    .iter()
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rpm_header::tests::build_rpm;
 
-    #[cfg(feature = "http")]
+    /// A signature of the header built by `build_rpm`, made by the key in
+    /// the verify tests.
+    #[cfg(feature = "verify")]
+    const HEADER_SIGNATURE: &str = "887504001608001d1621048740a6d572d4e28493af07b0da11b52ec5ee955d05026acfb84d000a0910da11b52ec5ee955de3080100b593aec9698371717acc854f3c8ebbc81cda030d7b69c1e8829363da7f2499f300ff641339f9b5fc126768cdd87daa5267d7f1e453cf768ce25b063ecdf52b0d0c06";
+
+    #[cfg(feature = "testing")]
     #[test]
     fn test_package() {
-        // Kibana is huge but should finish very quickly if just getting metadata.
         let url = "https://artifacts.elastic.co/downloads/kibana/kibana-8.2.1-x86_64.rpm";
+        let rpm = crate::testing::RpmBuilder::new("kibana", "8.2.1", "1", "x86_64").build();
+        let _mock = crate::testing::MockTransport::new()
+            .with_body(url, rpm)
+            .install();
 
         let package = RpmRemotePackage::new_from_url(url).expect("Failed to download package");
         assert_eq!(package.package_name().unwrap(), "kibana");
     }
+
+    #[test]
+    fn test_package_from_read() {
+        let rpm = build_rpm(&[]);
+        let package = RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(package.package_iteration(), Some("1"));
+        assert_eq!(package.package_arch().unwrap(), "noarch");
+        assert_eq!(package.package_license(), Some("MIT"));
+        assert_eq!(package.package_dependencies(), vec!["glibc"]);
+
+        let build_info = package.build_info();
+        assert_eq!(
+            build_info.build_host.as_deref(),
+            Some("builder.example.com")
+        );
+        assert_eq!(build_info.build_time, Some(1_700_000_000));
+        assert_eq!(build_info.payload_digest, None);
+        assert!(package.package_files().is_empty());
+        assert_eq!(package.payload_compression(), Some(Compression::Gzip));
+        assert_eq!(package.package_distribution(), Distribution::default());
+        assert_eq!(package.package_vendor(), None);
+
+        // Clones share the parsed headers.
+        let clone = package.clone();
+        assert!(Arc::ptr_eq(&clone.header, &package.header));
+        assert_eq!(clone.package_name().unwrap(), "hello");
+    }
+
+    #[test]
+    fn test_package_distribution() {
+        use crate::rpm_header::{build_header, build_package, TYPE_INT32, TYPE_STRING};
+
+        let header = build_header(&[
+            (1000, TYPE_STRING, b"hello\0"),
+            (RPMTAG_EPOCH, TYPE_INT32, &2u32.to_be_bytes()),
+            (1001, TYPE_STRING, b"1.0\0"),
+            (1002, TYPE_STRING, b"1.el9_3\0"),
+            (RPMTAG_DISTRIBUTION, TYPE_STRING, b"Example Linux\0"),
+            (RPMTAG_VENDOR, TYPE_STRING, b"Example, Inc.\0"),
+            (1022, TYPE_STRING, b"noarch\0"),
+        ]);
+        let rpm = build_package(&[], &header);
+        let package = RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+        assert_eq!(
+            package.package_distribution(),
+            Distribution {
+                name: Some("Example Linux".to_string()),
+                tag: Some(".el9_3".to_string()),
+            }
+        );
+        assert_eq!(package.package_vendor(), Some("Example, Inc."));
+        assert_eq!(package.package_epoch(), Some(2));
+    }
+
+    #[test]
+    fn test_get_tag() {
+        use crate::rpm_header::{
+            build_header, build_package, TYPE_BIN, TYPE_INT16, TYPE_STRING, TYPE_STRING_ARRAY,
+        };
+
+        let header = build_header(&[
+            (1000, TYPE_STRING, b"hello\0"),
+            (1001, TYPE_STRING, b"1.0\0"),
+            (1002, TYPE_STRING, b"1\0"),
+            (1022, TYPE_STRING, b"noarch\0"),
+            (RPMTAG_FILEMODES, TYPE_INT16, &[0x81, 0xed, 0x41, 0xed]),
+            (RPMTAG_DIRNAMES, TYPE_STRING_ARRAY, b"/usr/bin/\0/etc/\0"),
+            (5096, TYPE_STRING, b"perl:5.32:8040020210925:9e7e3e1b\0"),
+            (5097, TYPE_BIN, b"\x01\x02"),
+        ]);
+        let rpm = build_package(&[], &header);
+        let package = RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+
+        assert_eq!(
+            package.get_tag(IndexTag::RPMTAG_FILEMODES),
+            Some(TagValue::IntArray(vec![0o100755, 0o40755]))
+        );
+        assert_eq!(
+            package.get_tag(RPMTAG_DIRNAMES),
+            Some(TagValue::StringArray(vec![
+                "/usr/bin/".to_string(),
+                "/etc/".to_string()
+            ]))
+        );
+        assert_eq!(
+            package.get_tag_by_name("modularitylabel").unwrap().as_str(),
+            Some("perl:5.32:8040020210925:9e7e3e1b")
+        );
+        assert_eq!(
+            package.get_tag_by_name("RPMTAG_PAYLOADDIGESTALT"),
+            Some(TagValue::Binary(vec![1, 2]))
+        );
+        assert_eq!(package.get_tag(RPMTAG_VENDOR), None);
+        assert_eq!(package.get_tag_by_name("nosuchtag"), None);
+
+        assert_eq!(TagId::from_name("Name"), Some(TagId(1000)));
+        assert_eq!(TagId(1000).name().as_deref(), Some("RPMTAG_NAME"));
+        assert_eq!(TagId(999_999).name(), None);
+    }
+
+    #[test]
+    fn test_localized() {
+        use crate::rpm_header::{
+            build_header, build_package, RPMTAG_HEADERI18NTABLE, TYPE_I18NSTRING, TYPE_STRING,
+            TYPE_STRING_ARRAY,
+        };
+
+        let header = build_header(&[
+            (RPMTAG_HEADERI18NTABLE, TYPE_STRING_ARRAY, b"C\0de\0pt_BR\0"),
+            (1000, TYPE_STRING, b"hello\0"),
+            (1001, TYPE_STRING, b"1.0\0"),
+            (1002, TYPE_STRING, b"1\0"),
+            (
+                RPMTAG_SUMMARY,
+                TYPE_I18NSTRING,
+                b"Say hello\0Hallo sagen\0Diga ol\xc3\xa1\0",
+            ),
+            (
+                RPMTAG_DESCRIPTION,
+                TYPE_I18NSTRING,
+                b"Prints a greeting.\0Gibt einen Gru\xc3\x9f aus.\0\0",
+            ),
+            (1022, TYPE_STRING, b"noarch\0"),
+        ]);
+        let rpm = build_package(&[], &header);
+        let package = RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+
+        assert_eq!(
+            package.description_localized("de_AT.UTF-8"),
+            Some("Gibt einen Gruß aus.")
+        );
+        assert_eq!(package.summary_localized("pt-BR"), Some("Diga olá"));
+        // An empty translation falls back to the untranslated value.
+        assert_eq!(
+            package.description_localized("pt_BR"),
+            Some("Prints a greeting.")
+        );
+        assert_eq!(package.summary_localized("fr"), Some("Say hello"));
+        assert_eq!(package.package_description(), Some("Prints a greeting."));
+    }
+
+    #[test]
+    fn test_relations() {
+        use crate::rpm_header::{
+            build_header, build_package, TYPE_INT32, TYPE_STRING, TYPE_STRING_ARRAY,
+        };
+
+        let flags: Vec<u8> = [0, RPMSENSE_GREATER | RPMSENSE_EQUAL, 0, RPMSENSE_LESS]
+            .iter()
+            .flat_map(|flags| flags.to_be_bytes())
+            .collect();
+        let header = build_header(&[
+            (1000, TYPE_STRING, b"hello\0"),
+            (1001, TYPE_STRING, b"1.0\0"),
+            (1002, TYPE_STRING, b"1\0"),
+            (1022, TYPE_STRING, b"noarch\0"),
+            (
+                RPMTAG_REQUIRENAME,
+                TYPE_STRING_ARRAY,
+                b"(foo >= 1.0 with bar)\0glibc\0rpmlib(CompressedFileNames)\0python3\0",
+            ),
+            (RPMTAG_REQUIREFLAGS, TYPE_INT32, &flags),
+            (
+                RPMTAG_REQUIREVERSION,
+                TYPE_STRING_ARRAY,
+                b"\x002.34\x003.0.4-1\x004\0",
+            ),
+            (
+                RPMTAG_RECOMMENDNAME,
+                TYPE_STRING_ARRAY,
+                b"(hello-doc if man)\0",
+            ),
+            (RPMTAG_SUPPLEMENTNAME, TYPE_STRING_ARRAY, b"hello-desktop\0"),
+        ]);
+        let rpm = build_package(&[], &header);
+        let package = RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+
+        let relation = |kind, relation: &str| PackageRelation {
+            kind,
+            relation: relation.to_string(),
+        };
+        assert_eq!(
+            package.package_relations(),
+            vec![
+                relation(RelationKind::Depends, "(foo >= 1.0 with bar)"),
+                relation(RelationKind::Depends, "glibc >= 2.34"),
+                relation(RelationKind::Depends, "python3 < 4"),
+                relation(RelationKind::Recommends, "(hello-doc if man)"),
+                relation(RelationKind::Supplements, "hello-desktop"),
+            ]
+        );
+
+        #[cfg(feature = "rich-deps")]
+        {
+            let requires = package.requires().unwrap();
+            assert!(requires[0].is_rich());
+            assert_eq!(requires[1].to_string(), "glibc >= 2.34");
+            let recommends = package.relations(RelationKind::Recommends).unwrap();
+            assert_eq!(recommends[0].capabilities(), vec!["hello-doc", "man"]);
+            assert!(package
+                .relations(RelationKind::Enhances)
+                .unwrap()
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn test_dist_tag() {
+        assert_eq!(dist_tag("1.el9"), Some(".el9"));
+        assert_eq!(dist_tag("1.el9_3.1"), Some(".el9_3"));
+        assert_eq!(dist_tag("3.fc40"), Some(".fc40"));
+        assert_eq!(dist_tag("1.git20230101.fc40"), Some(".fc40"));
+        assert_eq!(dist_tag("2.amzn2023.0.1"), Some(".amzn2023"));
+        assert_eq!(dist_tag("1.1"), None);
+        assert_eq!(dist_tag("1"), None);
+    }
+
+    #[test]
+    fn test_package_from_read_bounded() {
+        // Only the lead and headers are read, however large the payload.
+        let rpm = build_rpm(&[]);
+        let mut reader = rpm.as_slice().chain(std::io::repeat(0).take(u64::MAX));
+        let package = RpmRemotePackage::new_from_read(&mut reader).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(reader.get_ref().1.limit(), u64::MAX);
+        assert_eq!(package.bytes_consumed(), rpm.len() as u64);
+
+        // The reader is left at the start of the payload.
+        let mut file = rpm.clone();
+        file.extend_from_slice(b"payload");
+        let mut reader = file.as_slice();
+        let package = RpmRemotePackage::new_from_read(&mut reader).unwrap();
+        assert_eq!(reader, b"payload");
+        assert_eq!(package.bytes_consumed(), rpm.len() as u64);
+    }
+
+    #[test]
+    fn test_scriptlets() {
+        use crate::rpm_header::tests::{build_header, parse_header};
+        use crate::rpm_header::{TYPE_STRING, TYPE_STRING_ARRAY};
+
+        let header = parse_header(&build_header(&[
+            (RPMTAG_PREIN, TYPE_STRING, b"useradd hello\0"),
+            (RPMTAG_PREINPROG, TYPE_STRING, b"/bin/sh\0"),
+            (RPMTAG_POSTINPROG, TYPE_STRING_ARRAY, b"/sbin/ldconfig\0"),
+            (RPMTAG_POSTUN, TYPE_STRING, b"rm -rf /var/lib/hello\0"),
+        ]));
+        assert_eq!(
+            scriptlets(&header),
+            vec![
+                PackageScript {
+                    name: "%pre".to_string(),
+                    interpreter: Some("/bin/sh".to_string()),
+                    contents: "useradd hello".to_string(),
+                },
+                PackageScript {
+                    name: "%post".to_string(),
+                    interpreter: Some("/sbin/ldconfig".to_string()),
+                    contents: String::new(),
+                },
+                PackageScript {
+                    name: "%postun".to_string(),
+                    interpreter: None,
+                    contents: "rm -rf /var/lib/hello".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_triggers() {
+        use crate::rpm_header::tests::{build_header, parse_header};
+        use crate::rpm_header::{TYPE_INT32, TYPE_STRING_ARRAY};
+
+        let ints =
+            |values: &[u32]| -> Vec<u8> { values.iter().flat_map(|v| v.to_be_bytes()).collect() };
+        // Two conditions share the first script.
+        let flags = ints(&[
+            RPMSENSE_TRIGGERIN | RPMSENSE_GREATER | RPMSENSE_EQUAL,
+            RPMSENSE_TRIGGERIN,
+            RPMSENSE_TRIGGERPOSTUN,
+        ]);
+        let header = parse_header(&build_header(&[
+            (
+                RPMTAG_TRIGGERNAME,
+                TYPE_STRING_ARRAY,
+                b"glibc\0hello-data\0hello-data\0",
+            ),
+            (RPMTAG_TRIGGERVERSION, TYPE_STRING_ARRAY, b"2.17\0\0\0"),
+            (RPMTAG_TRIGGERFLAGS, TYPE_INT32, &flags),
+            (RPMTAG_TRIGGERINDEX, TYPE_INT32, &ints(&[0, 0, 1])),
+            (
+                RPMTAG_TRIGGERSCRIPTS,
+                TYPE_STRING_ARRAY,
+                b"hello --refresh\0print(1)\0",
+            ),
+            (
+                RPMTAG_TRIGGERSCRIPTPROG,
+                TYPE_STRING_ARRAY,
+                b"/bin/sh\0<lua>\0",
+            ),
+        ]));
+
+        let triggers = triggers(&header);
+        assert_eq!(triggers.len(), 3);
+        assert_eq!(
+            triggers[0],
+            PackageTrigger {
+                kind: "%triggerin".to_string(),
+                condition: "glibc >= 2.17".to_string(),
+                script: Some(PackageScript {
+                    name: "%triggerin".to_string(),
+                    interpreter: Some("/bin/sh".to_string()),
+                    contents: "hello --refresh".to_string(),
+                }),
+            }
+        );
+        assert_eq!(triggers[1].condition, "hello-data");
+        assert_eq!(triggers[1].script, triggers[0].script);
+        assert_eq!(triggers[2].kind, "%triggerpostun");
+        assert_eq!(
+            triggers[2].script.as_ref().unwrap().interpreter.as_deref(),
+            Some("<lua>")
+        );
+    }
+
+    #[test]
+    fn test_config_files() {
+        use crate::rpm_header::tests::{build_header, parse_header};
+        use crate::rpm_header::{TYPE_INT32, TYPE_STRING_ARRAY};
+
+        let ints =
+            |values: &[u32]| -> Vec<u8> { values.iter().flat_map(|v| v.to_be_bytes()).collect() };
+        // %config(noreplace) sets the noreplace flag as well.
+        let flags = ints(&[RPMFILE_CONFIG, 0, RPMFILE_CONFIG | 16]);
+        let dir_indexes = ints(&[0, 1, 0]);
+        let header = parse_header(&build_header(&[
+            (RPMTAG_FILEFLAGS, TYPE_INT32, &flags),
+            (RPMTAG_DIRINDEXES, TYPE_INT32, &dir_indexes),
+            (
+                RPMTAG_BASENAMES,
+                TYPE_STRING_ARRAY,
+                b"hello.conf\0hello\0hello.d\0",
+            ),
+            (RPMTAG_DIRNAMES, TYPE_STRING_ARRAY, b"/etc/\0/usr/bin/\0"),
+        ]));
+
+        assert_eq!(header.int32_array(RPMTAG_DIRINDEXES), vec![0, 1, 0]);
+        assert_eq!(
+            config_files(&header),
+            vec!["/etc/hello.conf", "/etc/hello.d"]
+        );
+    }
+
+    #[test]
+    fn test_files() {
+        use crate::rpm_header::tests::{build_header, parse_header};
+        use crate::rpm_header::{TYPE_INT16, TYPE_INT32, TYPE_INT64, TYPE_STRING_ARRAY};
+
+        let modes: Vec<u8> = [0o100755u16, 0o040755, 0o120777, 0o100755]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        let sizes: Vec<u8> = [5_000_000_000u64, 4096, 5, 5_000_000_000]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+        let ints =
+            |values: &[u32]| -> Vec<u8> { values.iter().flat_map(|v| v.to_be_bytes()).collect() };
+        let header = parse_header(&build_header(&[
+            (RPMTAG_DIRINDEXES, TYPE_INT32, &ints(&[0, 1, 0, 0])),
+            (
+                RPMTAG_BASENAMES,
+                TYPE_STRING_ARRAY,
+                b"hello\0hello\0hi\0hello2\0",
+            ),
+            (
+                RPMTAG_DIRNAMES,
+                TYPE_STRING_ARRAY,
+                b"/usr/bin/\0/usr/share/\0",
+            ),
+            (RPMTAG_FILEMODES, TYPE_INT16, &modes),
+            (
+                RPMTAG_FILEMTIMES,
+                TYPE_INT32,
+                &ints(&[1_700_000_000, 0, 0, 0]),
+            ),
+            (RPMTAG_LONGFILESIZES, TYPE_INT64, &sizes),
+            (
+                RPMTAG_FILEUSERNAME,
+                TYPE_STRING_ARRAY,
+                b"root\0root\0root\0root\0",
+            ),
+            (
+                RPMTAG_FILEGROUPNAME,
+                TYPE_STRING_ARRAY,
+                b"wheel\0root\0root\0wheel\0",
+            ),
+            (RPMTAG_FILEDIGESTS, TYPE_STRING_ARRAY, b"abcd\0\0\0abcd\0"),
+            (RPMTAG_FILEDIGESTALGO, TYPE_INT32, &ints(&[8])),
+            (RPMTAG_FILELINKTOS, TYPE_STRING_ARRAY, b"\0\0hello\0\0"),
+            (RPMTAG_FILEDEVICES, TYPE_INT32, &ints(&[1, 1, 1, 1])),
+            (RPMTAG_FILEINODES, TYPE_INT32, &ints(&[1, 2, 3, 1])),
+        ]));
+
+        let files = files(&header);
+        assert_eq!(files.len(), 4);
+        assert_eq!(
+            files[0],
+            FileEntry {
+                path: "/usr/bin/hello".to_string(),
+                mode: 0o100755,
+                user: Some("root".to_string()),
+                group: Some("wheel".to_string()),
+                uid: None,
+                gid: None,
+                size: 5_000_000_000,
+                mtime: 1_700_000_000,
+                digest: Some(("sha256".to_string(), "abcd".to_string())),
+                link_target: None,
+                hardlinks: vec!["/usr/bin/hello2".to_string()],
+            }
+        );
+        assert_eq!(
+            files[1],
+            FileEntry {
+                path: "/usr/share/hello".to_string(),
+                mode: 0o040755,
+                user: Some("root".to_string()),
+                group: Some("root".to_string()),
+                uid: None,
+                gid: None,
+                size: 4096,
+                mtime: 0,
+                digest: None,
+                link_target: None,
+                hardlinks: vec![],
+            }
+        );
+        assert_eq!(files[2].link_target.as_deref(), Some("hello"));
+        assert!(files[2].hardlinks.is_empty());
+        assert_eq!(files[3].hardlinks, vec!["/usr/bin/hello"]);
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_verify_signature() {
+        use crate::verify::tests::{unhex, KEY_ID, PUBLIC_KEY};
+        use crate::verify::VerifyMode;
+
+        // A detached signature of the test header by the test key.
+        let signature = unhex(HEADER_SIGNATURE);
+        let keyring = Keyring::from_armored(PUBLIC_KEY).unwrap();
+        let verify = |rpm: &[u8], keyring: &Keyring, mode: VerifyMode| {
+            RpmRemotePackage::new_from_read_with_keyring(rpm, keyring, mode)
+                .map(|p| p.verification_status().cloned().unwrap())
+        };
+
+        let signed = build_rpm(&[(SIGTAG_RSA, &signature)]);
+        assert_eq!(
+            verify(&signed, &keyring, VerifyMode::Strict).unwrap(),
+            VerificationStatus::Signed {
+                key_id: KEY_ID.to_string()
+            }
+        );
+        assert_eq!(
+            verify(&signed, &Keyring::new(), VerifyMode::Report).unwrap(),
+            VerificationStatus::UnknownKey {
+                key_id: KEY_ID.to_string()
+            }
+        );
+
+        // The legacy signature also covers the (here empty) payload.
+        let legacy = build_rpm(&[(SIGTAG_PGP, &signature)]);
+        assert!(verify(&legacy, &keyring, VerifyMode::Report)
+            .unwrap()
+            .is_trusted());
+        let mut with_payload = legacy.clone();
+        with_payload.extend_from_slice(b"payload");
+        assert!(matches!(
+            verify(&with_payload, &keyring, VerifyMode::Report).unwrap(),
+            VerificationStatus::BadSignature { .. }
+        ));
+
+        let unsigned = build_rpm(&[]);
+        assert_eq!(
+            verify(&unsigned, &keyring, VerifyMode::Report).unwrap(),
+            VerificationStatus::Unsigned
+        );
+        assert!(matches!(
+            verify(&unsigned, &keyring, VerifyMode::Strict),
+            Err(PkgError::SignatureError(_))
+        ));
+
+        let pinned = TrustPolicy::new().with_allowed_key("0123456789abcdef");
+        assert!(
+            RpmRemotePackage::new_from_read_with_keyring(&signed[..], &keyring, pinned).is_err()
+        );
+        let unsigned_ok = TrustPolicy::new().with_allow_unsigned(true);
+        assert!(
+            RpmRemotePackage::new_from_read_with_keyring(&unsigned[..], &keyring, unsigned_ok)
+                .is_ok()
+        );
+    }
 }
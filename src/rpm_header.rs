@@ -0,0 +1,643 @@
+//! A reader for the lead and headers at the start of an RPM file.
+//!
+//! fez parses the headers into typed values but doesn't expose the raw bytes
+//! or the signature tags, both of which are needed to check signatures.
+use std::io::Read;
+
+use crate::limits::{Guard, Limits};
+use crate::rpm::TagValue;
+use crate::PkgError;
+
+/// Size of the lead at the very start of the file.
+const LEAD_SIZE: usize = 96;
+
+/// Magic bytes at the start of the lead.
+const LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+
+/// Magic bytes and version at the start of each header.
+const HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+
+/// Size of the fixed header intro and of each index entry.
+const INTRO_SIZE: usize = 16;
+const ENTRY_SIZE: usize = 16;
+
+/// rpm refuses headers with more entries or data than this.
+const MAX_ENTRIES: u32 = 0xffff;
+const MAX_STORE_SIZE: u32 = 256 * 1024 * 1024;
+
+/// The RPM types of a character and of an 8-bit integer.
+pub(crate) const TYPE_CHAR: u32 = 1;
+pub(crate) const TYPE_INT8: u32 = 2;
+
+/// The RPM type of a 16-bit integer.
+pub(crate) const TYPE_INT16: u32 = 3;
+
+/// The RPM type of a 32-bit integer.
+pub(crate) const TYPE_INT32: u32 = 4;
+
+/// The RPM type of a 64-bit integer.
+pub(crate) const TYPE_INT64: u32 = 5;
+
+/// The RPM type of a string.
+pub(crate) const TYPE_STRING: u32 = 6;
+
+/// The RPM type of binary data.
+pub(crate) const TYPE_BIN: u32 = 7;
+
+/// The RPM type of an array of strings.
+pub(crate) const TYPE_STRING_ARRAY: u32 = 8;
+
+/// The RPM type of a string that may be translated.
+pub(crate) const TYPE_I18NSTRING: u32 = 9;
+
+/// Header tag of the locales of translated strings, in the order of their
+/// values.
+pub(crate) const RPMTAG_HEADERI18NTABLE: u32 = 100;
+
+/// Header tag of the package epoch.
+pub(crate) const RPMTAG_EPOCH: u32 = 1003;
+
+/// Header tags of the one-line summary and the description of the package.
+pub(crate) const RPMTAG_SUMMARY: u32 = 1004;
+pub(crate) const RPMTAG_DESCRIPTION: u32 = 1005;
+
+/// Header tags of the distribution and vendor the package belongs to.
+pub(crate) const RPMTAG_DISTRIBUTION: u32 = 1010;
+pub(crate) const RPMTAG_VENDOR: u32 = 1011;
+
+/// Header tag of the package license.
+pub(crate) const RPMTAG_LICENSE: u32 = 1014;
+
+/// Header tags of the names, flags and versions of the package
+/// requirements.
+pub(crate) const RPMTAG_REQUIRENAME: u32 = 1049;
+pub(crate) const RPMTAG_REQUIREFLAGS: u32 = 1048;
+pub(crate) const RPMTAG_REQUIREVERSION: u32 = 1050;
+
+/// Header tags of the names, versions and flags of the weak dependencies.
+pub(crate) const RPMTAG_RECOMMENDNAME: u32 = 5046;
+pub(crate) const RPMTAG_RECOMMENDVERSION: u32 = 5047;
+pub(crate) const RPMTAG_RECOMMENDFLAGS: u32 = 5048;
+pub(crate) const RPMTAG_SUGGESTNAME: u32 = 5049;
+pub(crate) const RPMTAG_SUGGESTVERSION: u32 = 5050;
+pub(crate) const RPMTAG_SUGGESTFLAGS: u32 = 5051;
+pub(crate) const RPMTAG_SUPPLEMENTNAME: u32 = 5052;
+pub(crate) const RPMTAG_SUPPLEMENTVERSION: u32 = 5053;
+pub(crate) const RPMTAG_SUPPLEMENTFLAGS: u32 = 5054;
+pub(crate) const RPMTAG_ENHANCENAME: u32 = 5055;
+pub(crate) const RPMTAG_ENHANCEVERSION: u32 = 5056;
+pub(crate) const RPMTAG_ENHANCEFLAGS: u32 = 5057;
+
+/// Header tags of the install and removal scriptlets, and their
+/// interpreters.
+pub(crate) const RPMTAG_PREIN: u32 = 1023;
+pub(crate) const RPMTAG_POSTIN: u32 = 1024;
+pub(crate) const RPMTAG_PREUN: u32 = 1025;
+pub(crate) const RPMTAG_POSTUN: u32 = 1026;
+pub(crate) const RPMTAG_PREINPROG: u32 = 1085;
+pub(crate) const RPMTAG_POSTINPROG: u32 = 1086;
+pub(crate) const RPMTAG_PREUNPROG: u32 = 1087;
+pub(crate) const RPMTAG_POSTUNPROG: u32 = 1088;
+pub(crate) const RPMTAG_PRETRANS: u32 = 1151;
+pub(crate) const RPMTAG_POSTTRANS: u32 = 1152;
+pub(crate) const RPMTAG_PRETRANSPROG: u32 = 1153;
+pub(crate) const RPMTAG_POSTTRANSPROG: u32 = 1154;
+
+/// Header tags of the trigger scriptlets, and the conditions they run on.
+/// Each condition has the index of its script.
+pub(crate) const RPMTAG_TRIGGERSCRIPTS: u32 = 1065;
+pub(crate) const RPMTAG_TRIGGERNAME: u32 = 1066;
+pub(crate) const RPMTAG_TRIGGERVERSION: u32 = 1067;
+pub(crate) const RPMTAG_TRIGGERFLAGS: u32 = 1068;
+pub(crate) const RPMTAG_TRIGGERINDEX: u32 = 1069;
+pub(crate) const RPMTAG_TRIGGERSCRIPTPROG: u32 = 1092;
+
+/// The dependency flags of version comparisons in dependencies and trigger
+/// conditions.
+pub(crate) const RPMSENSE_LESS: u32 = 1 << 1;
+pub(crate) const RPMSENSE_GREATER: u32 = 1 << 2;
+pub(crate) const RPMSENSE_EQUAL: u32 = 1 << 3;
+
+/// The dependency flags of the types of trigger.
+pub(crate) const RPMSENSE_TRIGGERIN: u32 = 1 << 16;
+pub(crate) const RPMSENSE_TRIGGERUN: u32 = 1 << 17;
+pub(crate) const RPMSENSE_TRIGGERPOSTUN: u32 = 1 << 18;
+pub(crate) const RPMSENSE_TRIGGERPREIN: u32 = 1 << 25;
+
+/// Header tags of the file list: the flags of each file, and its path
+/// split into a directory and base name.
+pub(crate) const RPMTAG_FILEFLAGS: u32 = 1037;
+pub(crate) const RPMTAG_DIRINDEXES: u32 = 1116;
+pub(crate) const RPMTAG_BASENAMES: u32 = 1117;
+pub(crate) const RPMTAG_DIRNAMES: u32 = 1118;
+
+/// Header tags of the metadata of each file. Sizes of files over 4GiB are
+/// only in the long sizes tag, and hard links share a device and inode.
+pub(crate) const RPMTAG_FILESIZES: u32 = 1028;
+pub(crate) const RPMTAG_FILEMODES: u32 = 1030;
+pub(crate) const RPMTAG_FILEMTIMES: u32 = 1034;
+pub(crate) const RPMTAG_FILEDIGESTS: u32 = 1035;
+pub(crate) const RPMTAG_FILELINKTOS: u32 = 1036;
+pub(crate) const RPMTAG_FILEUSERNAME: u32 = 1039;
+pub(crate) const RPMTAG_FILEGROUPNAME: u32 = 1040;
+pub(crate) const RPMTAG_FILEDEVICES: u32 = 1095;
+pub(crate) const RPMTAG_FILEINODES: u32 = 1096;
+pub(crate) const RPMTAG_LONGFILESIZES: u32 = 5008;
+pub(crate) const RPMTAG_FILEDIGESTALGO: u32 = 5011;
+
+/// The file flag marking a `%config` file.
+pub(crate) const RPMFILE_CONFIG: u32 = 1;
+
+/// Header tags describing how and where the package was built.
+pub(crate) const RPMTAG_BUILDTIME: u32 = 1006;
+pub(crate) const RPMTAG_BUILDHOST: u32 = 1007;
+pub(crate) const RPMTAG_SOURCERPM: u32 = 1044;
+pub(crate) const RPMTAG_COOKIE: u32 = 1094;
+pub(crate) const RPMTAG_OPTFLAGS: u32 = 1122;
+pub(crate) const RPMTAG_PLATFORM: u32 = 1132;
+
+/// Header tags of the digest of the compressed payload and its algorithm.
+pub(crate) const RPMTAG_PAYLOADDIGEST: u32 = 5092;
+pub(crate) const RPMTAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+/// The compression of the payload, e.g. `xz`.
+pub(crate) const RPMTAG_PAYLOADCOMPRESSOR: u32 = 1125;
+
+/// Signature tag of the SHA-256 digest of the main header.
+pub(crate) const SIGTAG_SHA256: u32 = 273;
+
+/// Signature tag of an OpenPGP DSA (or EdDSA) signature over the header.
+#[cfg(feature = "verify")]
+pub(crate) const SIGTAG_DSA: u32 = 267;
+
+/// Signature tag of an OpenPGP RSA signature over the header.
+#[cfg(any(feature = "verify", test))]
+pub(crate) const SIGTAG_RSA: u32 = 268;
+
+/// Signature tag of an OpenPGP RSA signature over the header and payload.
+#[cfg(any(feature = "verify", test))]
+pub(crate) const SIGTAG_PGP: u32 = 1002;
+
+/// Signature tag of an OpenPGP DSA signature over the header and payload.
+#[cfg(feature = "verify")]
+pub(crate) const SIGTAG_GPG: u32 = 1005;
+
+/// An entry in a header's index.
+#[derive(Debug, Clone, Copy)]
+struct IndexEntry {
+    tag: u32,
+    kind: u32,
+    offset: u32,
+    count: u32,
+}
+
+/// A header structure, kept as the bytes it was read from.
+#[derive(Debug, Clone)]
+pub(crate) struct Header {
+    bytes: Vec<u8>,
+    entries: Vec<IndexEntry>,
+}
+
+impl Header {
+    /// Read a header from its magic up to the end of its data store.
+    fn read<R: Read>(reader: &mut R, guard: &Guard<'_>) -> Result<Self, PkgError> {
+        let mut bytes = vec![0; INTRO_SIZE];
+        reader.read_exact(&mut bytes)?;
+        if bytes[..4] != HEADER_MAGIC {
+            return Err(PkgError::InvalidPackage("bad RPM header magic".to_string()));
+        }
+
+        let num_entries = be_u32(&bytes[8..]);
+        let store_size = be_u32(&bytes[12..]);
+        if num_entries > MAX_ENTRIES || store_size > MAX_STORE_SIZE {
+            return Err(PkgError::InvalidPackage(format!(
+                "RPM header too large: {} entries, {} bytes",
+                num_entries, store_size
+            )));
+        }
+
+        guard.add_entries(u64::from(num_entries))?;
+
+        // Read the rest without allocating it all up front, so that the guard
+        // can stop a header that claims to be larger than it is allowed.
+        let index_size = num_entries as usize * ENTRY_SIZE;
+        let rest = (index_size + store_size as usize) as u64;
+        if reader.take(rest).read_to_end(&mut bytes)? as u64 != rest {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let entries = bytes[INTRO_SIZE..INTRO_SIZE + index_size]
+            .chunks(ENTRY_SIZE)
+            .map(|entry| IndexEntry {
+                tag: be_u32(entry),
+                kind: be_u32(&entry[4..]),
+                offset: be_u32(&entry[8..]),
+                count: be_u32(&entry[12..]),
+            })
+            .collect();
+
+        Ok(Self { bytes, entries })
+    }
+
+    /// The header exactly as it appears in the file.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The size of the data store.
+    fn store_size(&self) -> usize {
+        self.bytes.len() - INTRO_SIZE - self.entries.len() * ENTRY_SIZE
+    }
+
+    /// Get the value of a binary tag.
+    pub(crate) fn binary(&self, tag: u32) -> Option<&[u8]> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| e.tag == tag && e.kind == TYPE_BIN)?;
+        let start = entry.offset as usize;
+        self.store()
+            .get(start..start.checked_add(entry.count as usize)?)
+    }
+
+    /// Get the value of a string tag. For a translated string, this is the
+    /// first, untranslated, value.
+    pub(crate) fn string(&self, tag: u32) -> Option<&str> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| e.tag == tag && (e.kind == TYPE_STRING || e.kind == TYPE_I18NSTRING))?;
+        let data = self.store().get(entry.offset as usize..)?;
+        let end = data.iter().position(|&b| b == 0)?;
+        std::str::from_utf8(&data[..end]).ok()
+    }
+
+    /// Get the value of any tag, whatever its type, or `None` if the header
+    /// doesn't have the tag or its value is cut short. Translated strings
+    /// give their values for each locale, the untranslated one first.
+    pub(crate) fn value(&self, tag: u32) -> Option<TagValue> {
+        let entry = self.entries.iter().find(|e| e.tag == tag)?;
+        let int_array = |width: usize| {
+            let start = entry.offset as usize;
+            let end = start.checked_add((entry.count as usize).checked_mul(width)?)?;
+            let data = self.store().get(start..end)?;
+            Some(TagValue::IntArray(
+                data.chunks(width)
+                    .map(|value| value.iter().fold(0, |n, &b| n << 8 | u64::from(b)))
+                    .collect(),
+            ))
+        };
+        let strings = |count: usize| {
+            let data = self.store().get(entry.offset as usize..)?;
+            let values: Vec<String> = data
+                .split(|&b| b == 0)
+                .take(count)
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+                .collect();
+            // The last value must be terminated.
+            (values.len() == count && data.iter().filter(|&&b| b == 0).count() >= count)
+                .then_some(values)
+        };
+        match entry.kind {
+            TYPE_CHAR | TYPE_INT8 => int_array(1),
+            TYPE_INT16 => int_array(2),
+            TYPE_INT32 => int_array(4),
+            TYPE_INT64 => int_array(8),
+            TYPE_STRING => strings(1).map(|mut values| TagValue::String(values.remove(0))),
+            TYPE_STRING_ARRAY | TYPE_I18NSTRING => {
+                strings(entry.count as usize).map(TagValue::StringArray)
+            }
+            TYPE_BIN => self.binary(tag).map(|data| TagValue::Binary(data.to_vec())),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a translated string tag in `lang`, falling back as
+    /// [`language_fallbacks`](crate::language_fallbacks) describes and then
+    /// to the untranslated value.
+    pub(crate) fn i18n_string(&self, tag: u32, lang: &str) -> Option<&str> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| e.tag == tag && (e.kind == TYPE_STRING || e.kind == TYPE_I18NSTRING))?;
+        let count = if entry.kind == TYPE_I18NSTRING {
+            entry.count as usize
+        } else {
+            1
+        };
+        let data = self.store().get(entry.offset as usize..)?;
+        let values: Vec<_> = data
+            .split(|&b| b == 0)
+            .take(count)
+            .map(|value| std::str::from_utf8(value).ok())
+            .collect();
+
+        let locales = self.string_array(RPMTAG_HEADERI18NTABLE);
+        crate::language_fallbacks(lang)
+            .iter()
+            .filter_map(|language| locales.iter().position(|locale| locale == language))
+            .filter_map(|index| values.get(index).copied().flatten())
+            .find(|value| !value.is_empty())
+            .or_else(|| self.string(tag))
+    }
+
+    /// Get the first value of a 32-bit integer tag.
+    pub(crate) fn int32(&self, tag: u32) -> Option<u32> {
+        let entry = self
+            .entries
+            .iter()
+            .find(|e| e.tag == tag && e.kind == TYPE_INT32 && e.count > 0)?;
+        let start = entry.offset as usize;
+        self.store().get(start..start.checked_add(4)?).map(be_u32)
+    }
+
+    /// Get the values of a 16-bit integer array tag.
+    pub(crate) fn int16_array(&self, tag: u32) -> Vec<u16> {
+        self.int_array(tag, TYPE_INT16, 2)
+            .into_iter()
+            .map(|value| value as u16)
+            .collect()
+    }
+
+    /// Get the values of a 32-bit integer array tag.
+    pub(crate) fn int32_array(&self, tag: u32) -> Vec<u32> {
+        self.int_array(tag, TYPE_INT32, 4)
+            .into_iter()
+            .map(|value| value as u32)
+            .collect()
+    }
+
+    /// Get the values of a 64-bit integer array tag.
+    pub(crate) fn int64_array(&self, tag: u32) -> Vec<u64> {
+        self.int_array(tag, TYPE_INT64, 8)
+    }
+
+    /// Get the values of an integer array tag of the given type, each
+    /// `width` bytes long.
+    fn int_array(&self, tag: u32, kind: u32, width: usize) -> Vec<u64> {
+        let entry = match self.entries.iter().find(|e| e.tag == tag && e.kind == kind) {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+        let start = entry.offset as usize;
+        let end = (entry.count as usize)
+            .checked_mul(width)
+            .and_then(|len| start.checked_add(len));
+        end.and_then(|end| self.store().get(start..end))
+            .map(|data| {
+                data.chunks(width)
+                    .map(|value| value.iter().fold(0, |n, &b| n << 8 | u64::from(b)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the values of a string array tag.
+    pub(crate) fn string_array(&self, tag: u32) -> Vec<&str> {
+        let entry = match self
+            .entries
+            .iter()
+            .find(|e| e.tag == tag && e.kind == TYPE_STRING_ARRAY)
+        {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+        let data = self
+            .store()
+            .get(entry.offset as usize..)
+            .unwrap_or_default();
+        data.split(|&b| b == 0)
+            .take(entry.count as usize)
+            .filter_map(|value| std::str::from_utf8(value).ok())
+            .collect()
+    }
+
+    /// The data store following the index.
+    fn store(&self) -> &[u8] {
+        &self.bytes[INTRO_SIZE + self.entries.len() * ENTRY_SIZE..]
+    }
+}
+
+/// The lead and headers of an RPM file.
+#[derive(Debug, Clone)]
+pub(crate) struct RpmHeaders {
+    /// Everything read from the file, in order.
+    raw: Vec<u8>,
+
+    /// The signature header.
+    pub(crate) signature: Header,
+
+    /// The main header.
+    pub(crate) header: Header,
+}
+
+impl RpmHeaders {
+    /// Read the lead and both headers, leaving the reader at the start of the
+    /// payload.
+    ///
+    /// The lead and headers count against the metadata size limit, and their
+    /// index entries against the entry limit.
+    pub(crate) fn read<R: Read>(reader: &mut R, limits: &Limits) -> Result<Self, PkgError> {
+        let guard = Guard::new(limits);
+        let result = Self::read_guarded(&mut guard.reader(reader), &guard);
+        guard.finish(result)
+    }
+
+    fn read_guarded<R: Read>(reader: &mut R, guard: &Guard<'_>) -> Result<Self, PkgError> {
+        let mut raw = vec![0; LEAD_SIZE];
+        reader.read_exact(&mut raw)?;
+        if raw[..4] != LEAD_MAGIC {
+            return Err(PkgError::InvalidPackage("bad RPM lead magic".to_string()));
+        }
+
+        let signature = Header::read(reader, guard)?;
+        raw.extend_from_slice(signature.as_bytes());
+
+        // The signature header is padded to a multiple of 8 bytes.
+        let mut padding = vec![0; (8 - signature.store_size() % 8) % 8];
+        reader.read_exact(&mut padding)?;
+        raw.extend_from_slice(&padding);
+
+        let header = Header::read(reader, guard)?;
+        raw.extend_from_slice(header.as_bytes());
+
+        Ok(Self {
+            raw,
+            signature,
+            header,
+        })
+    }
+
+    /// Everything from the start of the file to the end of the main header.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
+/// Read a big-endian u32 from the start of a slice.
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Build a header from `(tag, type, data)` entries.
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn build_header(entries: &[(u32, u32, &[u8])]) -> Vec<u8> {
+    let mut index = Vec::new();
+    let mut store = Vec::new();
+    for (tag, kind, data) in entries {
+        // Integers are aligned to their size.
+        let width = int_width(*kind);
+        store.resize(store.len().div_ceil(width) * width, 0);
+        for value in &[*tag, *kind, store.len() as u32, count(*kind, data)] {
+            index.extend_from_slice(&value.to_be_bytes());
+        }
+        store.extend_from_slice(data);
+    }
+
+    let mut header = HEADER_MAGIC.to_vec();
+    header.extend_from_slice(&[0; 4]);
+    header.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    header.extend_from_slice(&(store.len() as u32).to_be_bytes());
+    header.extend(index);
+    header.extend(store);
+    header
+}
+
+/// The index count of an entry: the number of values in an array,
+/// otherwise the length of the data.
+#[cfg(any(test, feature = "testing"))]
+fn count(kind: u32, data: &[u8]) -> u32 {
+    match kind {
+        TYPE_INT16 | TYPE_INT32 | TYPE_INT64 => (data.len() / int_width(kind)) as u32,
+        TYPE_STRING_ARRAY | TYPE_I18NSTRING => data.iter().filter(|&&b| b == 0).count() as u32,
+        _ => data.len() as u32,
+    }
+}
+
+/// The size of each value of an integer type, or 1 for other types.
+#[cfg(any(test, feature = "testing"))]
+fn int_width(kind: u32) -> usize {
+    match kind {
+        TYPE_INT16 => 2,
+        TYPE_INT32 => 4,
+        TYPE_INT64 => 8,
+        _ => 1,
+    }
+}
+
+/// Build the lead and signature of a package with the given signature
+/// tags, followed by `header`.
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn build_package(signatures: &[(u32, &[u8])], header: &[u8]) -> Vec<u8> {
+    let mut rpm = LEAD_MAGIC.to_vec();
+    // Version 3.0, binary package.
+    rpm.extend_from_slice(&[3, 0, 0, 0, 0, 1]);
+    rpm.extend_from_slice(&[0; 66]);
+    // Linux, header-style signature.
+    rpm.extend_from_slice(&[0, 1, 0, 5]);
+    rpm.extend_from_slice(&[0; 16]);
+
+    let signatures: Vec<_> = signatures
+        .iter()
+        .map(|(tag, data)| (*tag, TYPE_BIN, *data))
+        .collect();
+    let signature = build_header(&signatures);
+    let padding = (8 - (signature.len() - INTRO_SIZE) % 8) % 8;
+    rpm.extend(signature);
+    rpm.extend(vec![0; padding]);
+    rpm.extend_from_slice(header);
+    rpm
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    pub(crate) use super::build_header;
+    use super::*;
+
+    /// Parse a header built by [`build_header`].
+    pub(crate) fn parse_header(bytes: &[u8]) -> Header {
+        let limits = Limits::default();
+        Header::read(&mut &bytes[..], &Guard::new(&limits)).unwrap()
+    }
+
+    /// Build a minimal package with the given signature tags.
+    pub(crate) fn build_rpm(signatures: &[(u32, &[u8])]) -> Vec<u8> {
+        build_package(signatures, &test_header())
+    }
+
+    /// The main header of the package built by [`build_rpm`].
+    pub(crate) fn test_header() -> Vec<u8> {
+        build_header(&[
+            (1000, TYPE_STRING, b"hello\0"),
+            (1001, TYPE_STRING, b"1.0\0"),
+            (1002, TYPE_STRING, b"1\0"),
+            (RPMTAG_LICENSE, TYPE_STRING, b"MIT\0"),
+            (1022, TYPE_STRING, b"noarch\0"),
+            (
+                RPMTAG_REQUIRENAME,
+                TYPE_STRING_ARRAY,
+                b"glibc\0rpmlib(CompressedFileNames)\0",
+            ),
+            (
+                RPMTAG_BUILDTIME,
+                TYPE_INT32,
+                &1_700_000_000u32.to_be_bytes(),
+            ),
+            (RPMTAG_BUILDHOST, TYPE_STRING, b"builder.example.com\0"),
+        ])
+    }
+
+    #[test]
+    fn test_read_headers() {
+        let rpm = build_rpm(&[(SIGTAG_RSA, b"sig")]);
+        let mut reader = rpm.as_slice();
+        let headers = RpmHeaders::read(&mut reader, &Limits::default()).unwrap();
+
+        assert!(reader.is_empty());
+        assert_eq!(headers.as_bytes(), rpm.as_slice());
+        assert_eq!(headers.header.as_bytes(), test_header().as_slice());
+        assert_eq!(headers.signature.binary(SIGTAG_RSA), Some(&b"sig"[..]));
+        assert_eq!(headers.signature.binary(SIGTAG_PGP), None);
+        assert_eq!(headers.header.string(RPMTAG_LICENSE), Some("MIT"));
+        assert_eq!(headers.header.string(SIGTAG_RSA), None);
+        assert_eq!(
+            headers.header.string_array(RPMTAG_REQUIRENAME),
+            vec!["glibc", "rpmlib(CompressedFileNames)"]
+        );
+        assert_eq!(headers.header.int32(RPMTAG_BUILDTIME), Some(1_700_000_000));
+        assert_eq!(headers.header.int32(RPMTAG_BUILDHOST), None);
+
+        let mut truncated = &rpm[..rpm.len() - 1];
+        assert!(RpmHeaders::read(&mut truncated, &Limits::default()).is_err());
+        assert!(RpmHeaders::read(&mut &b"not an rpm"[..], &Limits::default()).is_err());
+    }
+
+    #[test]
+    fn test_read_headers_limits() {
+        let rpm = build_rpm(&[(SIGTAG_RSA, b"sig")]);
+
+        let limits = Limits {
+            max_metadata_size: Some(rpm.len() as u64 - 1),
+            ..Limits::unlimited()
+        };
+        assert!(matches!(
+            RpmHeaders::read(&mut rpm.as_slice(), &limits),
+            Err(PkgError::SizeLimitExceeded(_))
+        ));
+
+        // One signature entry and eight header entries.
+        let limits = Limits {
+            max_entries: Some(8),
+            ..Limits::unlimited()
+        };
+        assert!(matches!(
+            RpmHeaders::read(&mut rpm.as_slice(), &limits),
+            Err(PkgError::EntryLimitExceeded(8))
+        ));
+        let limits = Limits {
+            max_entries: Some(9),
+            ..Limits::unlimited()
+        };
+        assert!(RpmHeaders::read(&mut rpm.as_slice(), &limits).is_ok());
+    }
+}
@@ -0,0 +1,809 @@
+//! Support for describing remote packages as software bills of materials.
+//!
+//! [`spdx_document`] builds an [SPDX 2.3](https://spdx.github.io/spdx-spec/v2.3/)
+//! document for a single package, and [`cyclonedx_bom`] a
+//! [CycloneDX 1.5](https://cyclonedx.org/docs/1.5/json/) BOM including its
+//! declared dependencies. Both can be serialized to JSON with `to_json`.
+//!
+//! Packages are identified by their [`purl`], and by a best-effort
+//! [`Cpe`] for matching against NVD data.
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::checksum::{Algorithm, Digests};
+#[cfg(any(feature = "debian", feature = "rpm"))]
+use crate::RemotePackageType;
+use crate::{PkgError, RemotePackage};
+
+/// The value SPDX uses for fields that weren't determined.
+const NOASSERTION: &str = "NOASSERTION";
+
+/// The SPDX identifier of the described package.
+const PACKAGE_ID: &str = "SPDXRef-Package";
+
+/// An SPDX document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxDocument {
+    /// The SPDX version, `SPDX-2.3`.
+    pub spdx_version: String,
+
+    /// The license of the document itself, always `CC0-1.0`.
+    pub data_license: String,
+
+    /// The document identifier, `SPDXRef-DOCUMENT`.
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: String,
+
+    /// The document name.
+    pub name: String,
+
+    /// A URI identifying this document.
+    pub document_namespace: String,
+
+    /// When and by what the document was created.
+    pub creation_info: SpdxCreationInfo,
+
+    /// The packages described.
+    pub packages: Vec<SpdxPackage>,
+
+    /// The files contained in the packages.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<SpdxFile>,
+
+    /// Relationships between the document, packages and files.
+    pub relationships: Vec<SpdxRelationship>,
+}
+
+/// When and by what an SPDX document was created.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxCreationInfo {
+    /// The creation time, e.g. `2024-01-01T00:00:00Z`.
+    pub created: String,
+
+    /// The tools or people that created the document.
+    pub creators: Vec<String>,
+}
+
+/// A package in an SPDX document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxPackage {
+    /// The package name.
+    pub name: String,
+
+    /// The package identifier within the document.
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: String,
+
+    /// The full package version.
+    pub version_info: String,
+
+    /// Where the package can be downloaded from, or `NOASSERTION`.
+    pub download_location: String,
+
+    /// Whether the files of the package were analyzed. Always false, as the
+    /// file list comes from the package metadata.
+    pub files_analyzed: bool,
+
+    /// The license concluded for the package; always `NOASSERTION`.
+    pub license_concluded: String,
+
+    /// The license declared by the package, or `NOASSERTION`.
+    pub license_declared: String,
+
+    /// The copyright text; always `NOASSERTION`.
+    pub copyright_text: String,
+
+    /// Checksums of the package file.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub checksums: Vec<SpdxChecksum>,
+
+    /// References to the package elsewhere, such as its purl.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub external_refs: Vec<SpdxExternalRef>,
+}
+
+/// A checksum in an SPDX document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxChecksum {
+    /// The algorithm, e.g. `SHA256`.
+    pub algorithm: String,
+
+    /// The lowercase hex checksum.
+    pub checksum_value: String,
+}
+
+/// A reference from an SPDX package to an external identifier.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxExternalRef {
+    /// The category of reference, e.g. `PACKAGE-MANAGER`.
+    pub reference_category: String,
+
+    /// The type of reference, e.g. `purl`.
+    pub reference_type: String,
+
+    /// The reference itself.
+    pub reference_locator: String,
+}
+
+/// A file in an SPDX document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxFile {
+    /// The file path, relative to the root, e.g. `./usr/bin/hello`.
+    pub file_name: String,
+
+    /// The file identifier within the document.
+    #[serde(rename = "SPDXID")]
+    pub spdx_id: String,
+
+    /// Checksums of the file, which aren't known from package metadata.
+    pub checksums: Vec<SpdxChecksum>,
+
+    /// The license concluded for the file; always `NOASSERTION`.
+    pub license_concluded: String,
+
+    /// The copyright text; always `NOASSERTION`.
+    pub copyright_text: String,
+}
+
+/// A relationship between two elements of an SPDX document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpdxRelationship {
+    /// The identifier of the element the relationship is from.
+    #[serde(rename = "spdxElementId")]
+    pub spdx_element_id: String,
+
+    /// The relationship, e.g. `DESCRIBES` or `CONTAINS`.
+    pub relationship_type: String,
+
+    /// The identifier of the element the relationship is to.
+    pub related_spdx_element: String,
+}
+
+impl SpdxDocument {
+    /// Serialize the document in the SPDX JSON format.
+    pub fn to_json(&self) -> Result<String, PkgError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// A CycloneDX BOM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxBom {
+    /// Always `CycloneDX`.
+    pub bom_format: String,
+
+    /// The CycloneDX version, `1.5`.
+    pub spec_version: String,
+
+    /// The version of this BOM, starting at 1.
+    pub version: u32,
+
+    /// The BOM metadata, including the described package.
+    pub metadata: CycloneDxMetadata,
+
+    /// The declared dependencies of the package.
+    pub components: Vec<CycloneDxComponent>,
+
+    /// The dependency graph, from the package to its dependencies.
+    pub dependencies: Vec<CycloneDxDependency>,
+}
+
+/// The metadata of a CycloneDX BOM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxMetadata {
+    /// The creation time, e.g. `2024-01-01T00:00:00Z`.
+    pub timestamp: String,
+
+    /// The tools that created the BOM.
+    pub tools: CycloneDxTools,
+
+    /// The component the BOM describes.
+    pub component: CycloneDxComponent,
+}
+
+/// The tools that created a CycloneDX BOM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxTools {
+    /// The tools, as components.
+    pub components: Vec<CycloneDxComponent>,
+}
+
+/// A component in a CycloneDX BOM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxComponent {
+    /// The component type, e.g. `library` or `application`.
+    #[serde(rename = "type")]
+    pub component_type: String,
+
+    /// The identifier of the component within the BOM, which is its purl.
+    #[serde(rename = "bom-ref", skip_serializing_if = "Option::is_none")]
+    pub bom_ref: Option<String>,
+
+    /// The component name.
+    pub name: String,
+
+    /// The component version, which isn't known for declared dependencies.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// The package URL of the component.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub purl: Option<String>,
+
+    /// The CPE 2.3 name of the component.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpe: Option<String>,
+
+    /// The declared licenses of the component.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub licenses: Vec<CycloneDxLicenseChoice>,
+
+    /// Hashes of the component file.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hashes: Vec<CycloneDxHash>,
+}
+
+/// A license of a CycloneDX component.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxLicenseChoice {
+    /// The license.
+    pub license: CycloneDxLicense,
+}
+
+/// A license named as in the package metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxLicense {
+    /// The license name.
+    pub name: String,
+}
+
+/// A hash of a CycloneDX component.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxHash {
+    /// The algorithm, e.g. `SHA-256`.
+    pub alg: String,
+
+    /// The lowercase hex hash.
+    pub content: String,
+}
+
+/// The direct dependencies of a component in a CycloneDX BOM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CycloneDxDependency {
+    /// The `bom-ref` of the dependent component.
+    #[serde(rename = "ref")]
+    pub bom_ref: String,
+
+    /// The `bom-ref`s of the components it depends on.
+    pub depends_on: Vec<String>,
+}
+
+impl CycloneDxBom {
+    /// Serialize the BOM in the CycloneDX JSON format.
+    pub fn to_json(&self) -> Result<String, PkgError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Describe a package as a CycloneDX BOM, including hashes of the package
+/// file if they were computed, e.g. by [`crate::from_url_with_digests`].
+///
+/// Each declared dependency is listed as a component identified by an
+/// unversioned purl, since only the constraint on its version is known.
+pub fn cyclonedx_bom<P: RemotePackage + ?Sized>(
+    package: &P,
+    digests: Option<&Digests>,
+) -> Result<CycloneDxBom, PkgError> {
+    let kind = purl_type(package);
+    let bom_ref = purl(package)?;
+
+    let hashes = digests
+        .map(|digests| {
+            Algorithm::ALL
+                .iter()
+                .filter_map(|&algorithm| {
+                    digests.get(algorithm).map(|value| CycloneDxHash {
+                        alg: cyclonedx_algorithm(algorithm).to_string(),
+                        content: value.to_ascii_lowercase(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let components: Vec<CycloneDxComponent> = package
+        .package_dependencies()
+        .into_iter()
+        .map(|name| {
+            let purl = format!("pkg:{}/{}", kind, purl_encode(&name));
+            CycloneDxComponent {
+                component_type: "library".to_string(),
+                bom_ref: Some(purl.clone()),
+                name,
+                version: None,
+                purl: Some(purl),
+                cpe: None,
+                licenses: Vec::new(),
+                hashes: Vec::new(),
+            }
+        })
+        .collect();
+
+    let dependencies = vec![CycloneDxDependency {
+        bom_ref: bom_ref.clone(),
+        depends_on: components
+            .iter()
+            .filter_map(|component| component.bom_ref.clone())
+            .collect(),
+    }];
+
+    Ok(CycloneDxBom {
+        bom_format: "CycloneDX".to_string(),
+        spec_version: "1.5".to_string(),
+        version: 1,
+        metadata: CycloneDxMetadata {
+            timestamp: format_timestamp(now()),
+            tools: CycloneDxTools {
+                components: vec![CycloneDxComponent {
+                    component_type: "application".to_string(),
+                    bom_ref: None,
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                    purl: None,
+                    cpe: None,
+                    licenses: Vec::new(),
+                    hashes: Vec::new(),
+                }],
+            },
+            component: CycloneDxComponent {
+                component_type: "library".to_string(),
+                bom_ref: Some(bom_ref.clone()),
+                name: package.package_name()?.to_string(),
+                version: Some(full_version(package)?),
+                purl: Some(bom_ref),
+                cpe: Some(Cpe::from_package(package)?.to_string()),
+                licenses: package
+                    .package_license()
+                    .map(|name| CycloneDxLicenseChoice {
+                        license: CycloneDxLicense {
+                            name: name.to_string(),
+                        },
+                    })
+                    .into_iter()
+                    .collect(),
+                hashes,
+            },
+        },
+        components,
+        dependencies,
+    })
+}
+
+/// Describe a package as an SPDX document, including checksums of the
+/// package file if they were computed, e.g. by
+/// [`crate::from_url_with_digests`].
+///
+/// The declared license is copied from the package metadata as is, so may
+/// not be a valid SPDX license expression for older packages.
+pub fn spdx_document<P: RemotePackage + ?Sized>(
+    package: &P,
+    digests: Option<&Digests>,
+) -> Result<SpdxDocument, PkgError> {
+    let name = package.package_name()?;
+    let version = full_version(package)?;
+
+    let checksums = digests
+        .map(|digests| {
+            Algorithm::ALL
+                .iter()
+                .filter_map(|&algorithm| {
+                    digests.get(algorithm).map(|value| SpdxChecksum {
+                        algorithm: spdx_algorithm(algorithm).to_string(),
+                        checksum_value: value.to_ascii_lowercase(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let files: Vec<SpdxFile> = package
+        .package_files()
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| SpdxFile {
+            file_name: format!(".{}", path),
+            spdx_id: format!("SPDXRef-File-{}", i + 1),
+            checksums: Vec::new(),
+            license_concluded: NOASSERTION.to_string(),
+            copyright_text: NOASSERTION.to_string(),
+        })
+        .collect();
+
+    let mut relationships = vec![SpdxRelationship {
+        spdx_element_id: "SPDXRef-DOCUMENT".to_string(),
+        relationship_type: "DESCRIBES".to_string(),
+        related_spdx_element: PACKAGE_ID.to_string(),
+    }];
+    relationships.extend(files.iter().map(|file| SpdxRelationship {
+        spdx_element_id: PACKAGE_ID.to_string(),
+        relationship_type: "CONTAINS".to_string(),
+        related_spdx_element: file.spdx_id.clone(),
+    }));
+
+    let created = now();
+
+    Ok(SpdxDocument {
+        spdx_version: "SPDX-2.3".to_string(),
+        data_license: "CC0-1.0".to_string(),
+        spdx_id: "SPDXRef-DOCUMENT".to_string(),
+        name: format!("{}-{}", name, version),
+        document_namespace: format!("https://spdx.org/spdxdocs/{}-{}-{}", name, version, created),
+        creation_info: SpdxCreationInfo {
+            created: format_timestamp(created),
+            creators: vec![format!(
+                "Tool: remote-package-{}",
+                env!("CARGO_PKG_VERSION")
+            )],
+        },
+        packages: vec![SpdxPackage {
+            name: name.to_string(),
+            spdx_id: PACKAGE_ID.to_string(),
+            version_info: version,
+            download_location: NOASSERTION.to_string(),
+            files_analyzed: false,
+            license_concluded: NOASSERTION.to_string(),
+            license_declared: package.package_license().unwrap_or(NOASSERTION).to_string(),
+            copyright_text: NOASSERTION.to_string(),
+            checksums,
+            external_refs: vec![
+                SpdxExternalRef {
+                    reference_category: "PACKAGE-MANAGER".to_string(),
+                    reference_type: "purl".to_string(),
+                    reference_locator: purl(package)?,
+                },
+                SpdxExternalRef {
+                    reference_category: "SECURITY".to_string(),
+                    reference_type: "cpe23Type".to_string(),
+                    reference_locator: Cpe::from_package(package)?.to_string(),
+                },
+            ],
+        }],
+        files,
+        relationships,
+    })
+}
+
+/// Get the [package URL](https://github.com/package-url/purl-spec) of a
+/// package, e.g. `pkg:deb/hello@2.10-3?arch=amd64`.
+///
+/// The distribution that built a package isn't recorded in it, so the purl
+/// has no namespace.
+pub fn purl<P: RemotePackage + ?Sized>(package: &P) -> Result<String, PkgError> {
+    Ok(format!(
+        "pkg:{}/{}@{}?arch={}",
+        purl_type(package),
+        purl_encode(package.package_name()?),
+        purl_encode(&full_version(package)?),
+        purl_encode(package.package_arch()?)
+    ))
+}
+
+/// A CPE 2.3 name for an application, as used by the NVD.
+///
+/// Package metadata doesn't record the vendor names the NVD uses, so
+/// [`Cpe::from_package`] guesses that the vendor is named after the product;
+/// the `with_` methods override any part of the guess.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cpe {
+    /// The vendor, e.g. `gnu`.
+    pub vendor: String,
+
+    /// The product, e.g. `hello`.
+    pub product: String,
+
+    /// The upstream version, e.g. `2.10`, or `*` for any.
+    pub version: String,
+}
+
+impl Cpe {
+    /// Make a best-effort CPE for a package from its name and upstream
+    /// version, without any epoch, Debian revision or RPM release.
+    #[cfg_attr(
+        not(any(feature = "debian", feature = "rpm")),
+        allow(unreachable_code, unused_variables)
+    )]
+    pub fn from_package<P: RemotePackage + ?Sized>(package: &P) -> Result<Self, PkgError> {
+        let name = package.package_name()?.to_ascii_lowercase();
+        let version = package.package_version()?;
+        let version = version.split_once(':').map_or(version, |(_epoch, v)| v);
+        let version: &str = match package.package_type() {
+            #[cfg(feature = "debian")]
+            RemotePackageType::Deb => version.rsplit_once('-').map_or(version, |(v, _rev)| v),
+            #[cfg(feature = "rpm")]
+            RemotePackageType::Rpm => version,
+        };
+
+        Ok(Self {
+            vendor: name.clone(),
+            product: name,
+            version: version.to_string(),
+        })
+    }
+
+    /// Override the vendor.
+    pub fn with_vendor(mut self, vendor: &str) -> Self {
+        self.vendor = vendor.to_owned();
+        self
+    }
+
+    /// Override the product.
+    pub fn with_product(mut self, product: &str) -> Self {
+        self.product = product.to_owned();
+        self
+    }
+
+    /// Override the version.
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = version.to_owned();
+        self
+    }
+}
+
+/// Formats the CPE as a formatted string binding, e.g.
+/// `cpe:2.3:a:gnu:hello:2.10:*:*:*:*:*:*:*`.
+impl fmt::Display for Cpe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cpe:2.3:a:{}:{}:{}:*:*:*:*:*:*:*",
+            cpe_escape(&self.vendor),
+            cpe_escape(&self.product),
+            cpe_escape(&self.version)
+        )
+    }
+}
+
+/// Escape a CPE attribute for the formatted string binding. An empty or `*`
+/// value means any.
+fn cpe_escape(value: &str) -> String {
+    if value.is_empty() || value == "*" {
+        return "*".to_string();
+    }
+
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ' ' => escaped.push('_'),
+            c if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                escaped.push(c.to_ascii_lowercase())
+            }
+            c => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+        }
+    }
+    escaped
+}
+
+/// The purl type of a package.
+#[cfg_attr(not(any(feature = "debian", feature = "rpm")), allow(unreachable_code))]
+fn purl_type<P: RemotePackage + ?Sized>(package: &P) -> &'static str {
+    match package.package_type() {
+        #[cfg(feature = "debian")]
+        RemotePackageType::Deb => "deb",
+        #[cfg(feature = "rpm")]
+        RemotePackageType::Rpm => "rpm",
+    }
+}
+
+/// Get the version of a package including its iteration, where the package
+/// type keeps them separately.
+#[cfg_attr(
+    not(any(feature = "debian", feature = "rpm")),
+    allow(unreachable_code, unused_variables)
+)]
+fn full_version<P: RemotePackage + ?Sized>(package: &P) -> Result<String, PkgError> {
+    let version = package.package_version()?;
+    Ok(match package.package_type() {
+        // The Debian revision is already part of the version.
+        #[cfg(feature = "debian")]
+        RemotePackageType::Deb => version.to_string(),
+        #[cfg(feature = "rpm")]
+        RemotePackageType::Rpm => match package.package_iteration() {
+            Some(release) => format!("{}-{}", version, release),
+            None => version.to_string(),
+        },
+    })
+}
+
+/// The SPDX name of a checksum algorithm.
+fn spdx_algorithm(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::Md5 => "MD5",
+        Algorithm::Sha1 => "SHA1",
+        Algorithm::Sha256 => "SHA256",
+        Algorithm::Sha512 => "SHA512",
+    }
+}
+
+/// The CycloneDX name of a hash algorithm.
+fn cyclonedx_algorithm(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::Md5 => "MD5",
+        Algorithm::Sha1 => "SHA-1",
+        Algorithm::Sha256 => "SHA-256",
+        Algorithm::Sha512 => "SHA-512",
+    }
+}
+
+/// Percent-encode a purl component, leaving only unreserved characters.
+fn purl_encode(component: &str) -> String {
+    let mut encoded = String::with_capacity(component.len());
+    for byte in component.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'+' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The current time in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Format seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // Convert days since the epoch to a civil date (Howard Hinnant's
+    // algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_cpe() {
+        let cpe = Cpe {
+            vendor: "gnu".to_string(),
+            product: "Hello World".to_string(),
+            version: "2.10+dfsg".to_string(),
+        };
+        assert_eq!(
+            cpe.to_string(),
+            "cpe:2.3:a:gnu:hello_world:2.10\\+dfsg:*:*:*:*:*:*:*"
+        );
+        assert_eq!(
+            cpe.with_product("hello").with_version("").to_string(),
+            "cpe:2.3:a:gnu:hello:*:*:*:*:*:*:*:*"
+        );
+    }
+
+    #[test]
+    fn test_purl_encode() {
+        assert_eq!(purl_encode("1:2.10-3+b1"), "1%3A2.10-3+b1");
+        assert_eq!(purl_encode("x86_64"), "x86_64");
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_spdx_document() {
+        let rpm = crate::rpm_header::tests::build_rpm(&[]);
+        let (package, digests) =
+            crate::from_read_with_digests(rpm.as_slice(), &[Algorithm::Sha256]).unwrap();
+
+        let document = spdx_document(package.as_ref(), Some(&digests)).unwrap();
+        assert_eq!(document.name, "hello-1.0-1");
+        let spdx = &document.packages[0];
+        assert_eq!(spdx.version_info, "1.0-1");
+        assert_eq!(spdx.license_declared, "MIT");
+        assert_eq!(spdx.checksums[0].algorithm, "SHA256");
+        assert_eq!(
+            spdx.external_refs[0].reference_locator,
+            "pkg:rpm/hello@1.0-1?arch=noarch"
+        );
+        assert_eq!(
+            spdx.external_refs[1].reference_locator,
+            "cpe:2.3:a:hello:hello:1.0:*:*:*:*:*:*:*"
+        );
+        assert_eq!(document.relationships.len(), 1);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&package.to_spdx().unwrap().to_json().unwrap()).unwrap();
+        assert_eq!(json["spdxVersion"], "SPDX-2.3");
+        assert_eq!(json["packages"][0]["SPDXID"], PACKAGE_ID);
+        assert_eq!(
+            json["relationships"][0]["spdxElementId"],
+            "SPDXRef-DOCUMENT"
+        );
+        assert!(json["packages"][0].get("checksums").is_none());
+    }
+
+    #[cfg(feature = "debian")]
+    #[test]
+    fn test_cyclonedx_bom() {
+        let deb = crate::debian::tests::build_deb(&[]);
+        let (package, digests) =
+            crate::from_read_with_digests(deb.as_slice(), &[Algorithm::Sha256]).unwrap();
+
+        let bom = cyclonedx_bom(package.as_ref(), Some(&digests)).unwrap();
+        let component = &bom.metadata.component;
+        assert_eq!(
+            component.bom_ref.as_deref(),
+            Some("pkg:deb/hello@2.10-3?arch=amd64")
+        );
+        assert_eq!(component.hashes[0].alg, "SHA-256");
+        assert!(component.licenses.is_empty());
+        assert_eq!(
+            component.cpe.as_deref(),
+            Some("cpe:2.3:a:hello:hello:2.10:*:*:*:*:*:*:*")
+        );
+        let names: Vec<_> = bom.components.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["dpkg", "libc6", "python3", "python3-minimal"]);
+        assert_eq!(bom.dependencies[0].depends_on[1], "pkg:deb/libc6");
+
+        let json: serde_json::Value =
+            serde_json::from_str(&package.to_cyclonedx().unwrap().to_json().unwrap()).unwrap();
+        assert_eq!(json["bomFormat"], "CycloneDX");
+        assert_eq!(json["metadata"]["component"]["type"], "library");
+        assert_eq!(
+            json["dependencies"][0]["ref"],
+            component.bom_ref.as_deref().unwrap()
+        );
+        assert!(json["metadata"]["component"].get("hashes").is_none());
+        assert!(json["components"][0].get("version").is_none());
+    }
+}
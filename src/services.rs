@@ -0,0 +1,236 @@
+//! Detection of the services and other hooks a package installs, such as
+//! systemd units and cron jobs, which run code outside of the package's own
+//! commands.
+use std::io::Read;
+
+use crate::payload::{Entries, EntryKind};
+use crate::PkgError;
+
+/// The type of a service hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServiceKind {
+    /// A systemd unit, e.g. a `.service`, `.socket` or `.timer`.
+    SystemdUnit,
+
+    /// A System V init script.
+    InitScript,
+
+    /// A cron job, either in `/etc/cron.d` or a periodic script.
+    Cron,
+
+    /// A D-Bus service that can be activated on demand.
+    DbusService,
+
+    /// A D-Bus policy, allowing access to a bus name.
+    DbusPolicy,
+
+    /// A udev rule, run when devices are added or removed.
+    UdevRule,
+
+    /// A desktop entry started on login.
+    XdgAutostart,
+}
+
+/// A service hook found in a package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceHook {
+    /// The type of the hook.
+    pub kind: ServiceKind,
+
+    /// The installed path, e.g. `/lib/systemd/system/hello.service`.
+    pub path: String,
+}
+
+/// The directories systemd loads units from.
+const SYSTEMD_UNIT_DIRS: [&str; 6] = [
+    "/lib/systemd/system/",
+    "/usr/lib/systemd/system/",
+    "/etc/systemd/system/",
+    "/lib/systemd/user/",
+    "/usr/lib/systemd/user/",
+    "/etc/systemd/user/",
+];
+
+/// The suffixes of systemd unit files.
+const SYSTEMD_UNIT_SUFFIXES: [&str; 11] = [
+    ".service",
+    ".socket",
+    ".timer",
+    ".path",
+    ".mount",
+    ".automount",
+    ".swap",
+    ".target",
+    ".device",
+    ".slice",
+    ".scope",
+];
+
+/// The directories holding cron jobs.
+const CRON_DIRS: [&str; 6] = [
+    "/etc/cron.d/",
+    "/etc/cron.hourly/",
+    "/etc/cron.daily/",
+    "/etc/cron.weekly/",
+    "/etc/cron.monthly/",
+    "/var/spool/cron/",
+];
+
+/// Work out what kind of hook a file is from its installed path, if any.
+///
+/// Files in subdirectories of the hook directories, such as systemd drop-in
+/// `.d` directories, aren't counted.
+pub fn classify(path: &str) -> Option<ServiceKind> {
+    // The path of a file directly in one of `dirs`, relative to it.
+    let in_dir = |dirs: &[&str]| {
+        dirs.iter().find_map(|dir| {
+            path.strip_prefix(dir)
+                .filter(|name| !name.is_empty() && !name.contains('/'))
+        })
+    };
+
+    if let Some(name) = in_dir(&SYSTEMD_UNIT_DIRS) {
+        return SYSTEMD_UNIT_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+            .then_some(ServiceKind::SystemdUnit);
+    }
+    if in_dir(&["/etc/init.d/", "/etc/rc.d/init.d/"]).is_some() {
+        return Some(ServiceKind::InitScript);
+    }
+    // run-parts ignores files with dots in their names, apart from
+    // crontabs in `/etc/cron.d`.
+    if let Some(name) = in_dir(&CRON_DIRS) {
+        return (path.starts_with("/etc/cron.d/") || !name.contains('.'))
+            .then_some(ServiceKind::Cron);
+    }
+    if let Some(name) = in_dir(&[
+        "/usr/share/dbus-1/services/",
+        "/usr/share/dbus-1/system-services/",
+    ]) {
+        return name
+            .ends_with(".service")
+            .then_some(ServiceKind::DbusService);
+    }
+    if let Some(name) = in_dir(&[
+        "/usr/share/dbus-1/system.d/",
+        "/etc/dbus-1/system.d/",
+        "/usr/share/dbus-1/session.d/",
+        "/etc/dbus-1/session.d/",
+    ]) {
+        return name.ends_with(".conf").then_some(ServiceKind::DbusPolicy);
+    }
+    if let Some(name) = in_dir(&[
+        "/lib/udev/rules.d/",
+        "/usr/lib/udev/rules.d/",
+        "/etc/udev/rules.d/",
+    ]) {
+        return name.ends_with(".rules").then_some(ServiceKind::UdevRule);
+    }
+    if let Some(name) = in_dir(&["/etc/xdg/autostart/"]) {
+        return name
+            .ends_with(".desktop")
+            .then_some(ServiceKind::XdgAutostart);
+    }
+    None
+}
+
+/// Find the service hooks in the payload of a package.
+///
+/// Symlinks count as well as files, as units are often installed as links
+/// to another unit.
+pub fn find_service_hooks<R: Read>(reader: R) -> Result<Vec<ServiceHook>, PkgError> {
+    let mut hooks = Vec::new();
+    let mut entries = Entries::new(reader)?;
+    while let Some(entry) = entries.next_entry()? {
+        if !matches!(entry.kind(), EntryKind::File | EntryKind::Symlink(_)) {
+            continue;
+        }
+        if let Some(kind) = classify(entry.path()) {
+            hooks.push(ServiceHook {
+                kind,
+                path: entry.path().to_owned(),
+            });
+        }
+    }
+    Ok(hooks)
+}
+
+/// Find the service hooks in the payload of a package from a URL; see
+/// [`find_service_hooks`].
+///
+/// Uses a blocking client, so if using this in an async environment,
+/// surround this with tokio::spawn_blocking.
+#[cfg(feature = "http-blocking")]
+pub fn find_service_hooks_from_url(url: &str) -> Result<Vec<ServiceHook>, PkgError> {
+    find_service_hooks(crate::http::get_package(url)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            (
+                "/lib/systemd/system/hello.service",
+                Some(ServiceKind::SystemdUnit),
+            ),
+            (
+                "/usr/lib/systemd/user/hello.timer",
+                Some(ServiceKind::SystemdUnit),
+            ),
+            ("/lib/systemd/system/hello.service.d/override.conf", None),
+            ("/usr/lib/systemd/system/README", None),
+            ("/etc/init.d/hello", Some(ServiceKind::InitScript)),
+            ("/etc/cron.d/hello", Some(ServiceKind::Cron)),
+            ("/etc/cron.daily/hello", Some(ServiceKind::Cron)),
+            ("/etc/cron.daily/hello.dpkg-old", None),
+            (
+                "/usr/share/dbus-1/system-services/org.example.Hello.service",
+                Some(ServiceKind::DbusService),
+            ),
+            (
+                "/usr/share/dbus-1/system.d/org.example.Hello.conf",
+                Some(ServiceKind::DbusPolicy),
+            ),
+            (
+                "/lib/udev/rules.d/60-hello.rules",
+                Some(ServiceKind::UdevRule),
+            ),
+            (
+                "/etc/xdg/autostart/hello.desktop",
+                Some(ServiceKind::XdgAutostart),
+            ),
+            ("/usr/bin/hello", None),
+        ];
+        for (path, kind) in &cases {
+            assert_eq!(classify(path), *kind, "{}", path);
+        }
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_find_service_hooks() {
+        let rpm = crate::payload::tests::build_rpm_with_payload(&[
+            ("./usr/bin/hello", b"binary"),
+            ("./usr/lib/systemd/system/hello.service", b"[Service]\n"),
+            ("./etc/cron.d/hello", b"@daily root hello\n"),
+        ]);
+
+        assert_eq!(
+            find_service_hooks(rpm.as_slice()).unwrap(),
+            vec![
+                ServiceHook {
+                    kind: ServiceKind::SystemdUnit,
+                    path: "/usr/lib/systemd/system/hello.service".to_string(),
+                },
+                ServiceHook {
+                    kind: ServiceKind::Cron,
+                    path: "/etc/cron.d/hello".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,502 @@
+//! Helpers for testing code that uses this crate, without the network.
+//!
+//! A [`MockTransport`] answers the requests of the clients with
+//! canned responses, and [`DebBuilder`] and [`RpmBuilder`] build minimal
+//! valid packages in memory for it to serve.
+//!
+//! These are behind the `testing` feature, which is meant to be enabled
+//! only for tests:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! remote-package = { version = "0.3", features = ["testing"] }
+//! ```
+//!
+//! ```
+//! # #[cfg(feature = "rpm")]
+//! # {
+//! use remote_package::testing::{MockTransport, RpmBuilder};
+//!
+//! let rpm = RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+//! let mock = MockTransport::new()
+//!     .with_body("https://example.com/hello.rpm", rpm)
+//!     .install();
+//!
+//! let package = remote_package::from_url("https://example.com/hello.rpm").unwrap();
+//! assert_eq!(package.package_name().unwrap(), "hello");
+//! assert_eq!(mock.requests(), vec!["https://example.com/hello.rpm"]);
+//! # }
+//! ```
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use once_cell::sync::Lazy;
+
+use crate::http::{set_transport, Transport};
+use crate::PkgError;
+
+/// A canned response for a [`MockTransport`] to send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// A successful response with `body`.
+    pub fn new<B: Into<Vec<u8>>>(body: B) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    /// A `302 Found` redirect to `location`, which the transport follows as
+    /// the real client would.
+    pub fn redirect(location: &str) -> Self {
+        Self::new(Vec::new())
+            .with_status(302)
+            .with_header("Location", location)
+    }
+
+    /// Send the response with `status` rather than 200.
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Add a header to the response.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// The target of the response if it's a redirect.
+    fn location(&self) -> Option<&str> {
+        if !(300..400).contains(&self.status) {
+            return None;
+        }
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("location"))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Convert to a reqwest response from `url`.
+    fn to_response(&self, url: &str) -> Result<reqwest::blocking::Response, PkgError> {
+        let mut builder = http_crate::Response::builder().status(self.status);
+        if let Ok(url) = reqwest::Url::parse(url) {
+            builder = reqwest::ResponseBuilderExt::url(builder, url);
+        }
+        for (name, value) in &self.headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        let response = builder
+            .body(self.body.clone())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        Ok(response.into())
+    }
+}
+
+/// A [`Transport`] that answers requests with canned responses for each URL,
+/// and 404 Not Found for any other URL.
+#[derive(Debug, Default)]
+pub struct MockTransport {
+    responses: Vec<(String, MockResponse)>,
+    requests: Mutex<Vec<String>>,
+}
+
+impl MockTransport {
+    /// A transport with no responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer requests for `url` with `response`. Responses added for the
+    /// same URL are sent in turn, the last one repeating.
+    pub fn with_response(mut self, url: &str, response: MockResponse) -> Self {
+        self.responses.push((normalize(url), response));
+        self
+    }
+
+    /// Answer requests for `url` with a successful response of `body`.
+    pub fn with_body<B: Into<Vec<u8>>>(self, url: &str, body: B) -> Self {
+        self.with_response(url, MockResponse::new(body))
+    }
+
+    /// The URLs requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        lock(&self.requests).clone()
+    }
+
+    /// Send every request, from the blocking and async clients alike,
+    /// through this transport until the returned guard is dropped.
+    ///
+    /// Only one mock is installed at a time. Installing another waits for
+    /// the guard of this one to be dropped, so that tests running in
+    /// parallel don't answer each other's requests.
+    pub fn install(self) -> MockGuard {
+        let lock = lock(&INSTALLED);
+        let transport = Arc::new(self);
+        set_transport(Some(transport.clone()));
+        MockGuard {
+            transport,
+            _lock: lock,
+        }
+    }
+}
+
+impl Transport for MockTransport {
+    fn send(
+        &self,
+        request: reqwest::blocking::Request,
+    ) -> Result<reqwest::blocking::Response, PkgError> {
+        let mut url = request.url().to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let sent = {
+                let mut requests = lock(&self.requests);
+                requests.push(url.clone());
+                requests
+                    .iter()
+                    .filter(|requested| **requested == url)
+                    .count()
+                    - 1
+            };
+            let responses: Vec<&MockResponse> = self
+                .responses
+                .iter()
+                .filter(|(known, _)| *known == url)
+                .map(|(_, response)| response)
+                .collect();
+            let response = match responses.get(sent).or_else(|| responses.last()) {
+                Some(response) => response,
+                None => {
+                    return MockResponse::new(Vec::new())
+                        .with_status(404)
+                        .to_response(&url)
+                }
+            };
+            match response.location() {
+                Some(location) => {
+                    url = reqwest::Url::parse(&url)
+                        .and_then(|base| base.join(location))
+                        .map(String::from)
+                        .unwrap_or_else(|_| location.to_owned())
+                }
+                None => return response.to_response(&url),
+            }
+        }
+        let error = format!("too many redirects from {}", request.url());
+        Err(io::Error::other(error).into())
+    }
+}
+
+/// Keeps a [`MockTransport`] installed, until dropped.
+#[derive(Debug)]
+pub struct MockGuard {
+    transport: Arc<MockTransport>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl MockGuard {
+    /// The URLs requested so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.transport.requests()
+    }
+}
+
+impl Drop for MockGuard {
+    fn drop(&mut self) {
+        set_transport(None);
+    }
+}
+
+/// The most redirects followed for one request, as for the real client.
+const MAX_REDIRECTS: usize = 10;
+
+/// Held while a mock is installed.
+static INSTALLED: Lazy<Mutex<()>> = Lazy::new(Mutex::default);
+
+/// Lock `mutex`, ignoring a panic in an earlier holder, such as a failed
+/// test.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Normalize `url` as reqwest would before sending it.
+fn normalize(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .map(String::from)
+        .unwrap_or_else(|_| url.to_owned())
+}
+
+/// Builds a minimal Debian package in memory.
+///
+/// The control and data archives are uncompressed.
+#[cfg(feature = "debian")]
+#[derive(Debug, Clone)]
+pub struct DebBuilder {
+    fields: Vec<(String, String)>,
+    control_files: Vec<(String, Vec<u8>)>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+#[cfg(feature = "debian")]
+impl DebBuilder {
+    /// A package with the given name, version and architecture.
+    pub fn new(name: &str, version: &str, arch: &str) -> Self {
+        Self {
+            fields: Vec::new(),
+            control_files: Vec::new(),
+            files: Vec::new(),
+        }
+        .with_field("Package", name)
+        .with_field("Version", version)
+        .with_field("Architecture", arch)
+    }
+
+    /// Set a field of the control file, such as `Depends`, replacing any
+    /// earlier value.
+    ///
+    /// Continuation lines of a multi-line value must start with a space.
+    pub fn with_field(mut self, name: &str, value: &str) -> Self {
+        match self.fields.iter_mut().find(|(known, _)| known == name) {
+            Some(field) => field.1 = value.to_owned(),
+            None => self.fields.push((name.to_owned(), value.to_owned())),
+        }
+        self
+    }
+
+    /// Add a file to the control archive, such as `postinst` or `md5sums`.
+    pub fn with_control_file<B: Into<Vec<u8>>>(mut self, name: &str, contents: B) -> Self {
+        self.control_files
+            .push((format!("./{}", name), contents.into()));
+        self
+    }
+
+    /// Add a file installed at `path` to the data archive.
+    pub fn with_file<B: Into<Vec<u8>>>(mut self, path: &str, contents: B) -> Self {
+        self.files.push((
+            format!("./{}", path.trim_start_matches('/')),
+            contents.into(),
+        ));
+        self
+    }
+
+    /// Build the package.
+    ///
+    /// Panics if a path can't be stored in a tar archive, e.g. one with
+    /// `..`.
+    pub fn build(&self) -> Vec<u8> {
+        let control: String = self
+            .fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}\n", name, value))
+            .collect();
+        let mut control_files = vec![("./control", control.as_bytes())];
+        control_files.extend(
+            self.control_files
+                .iter()
+                .map(|(name, contents)| (name.as_str(), contents.as_slice())),
+        );
+        let files: Vec<_> = self
+            .files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_slice()))
+            .collect();
+
+        crate::debian::build_deb_members(&[
+            ("debian-binary", b"2.0\n"),
+            (
+                "control.tar",
+                &crate::debian::build_tar(control_files.iter().collect()),
+            ),
+            (
+                "data.tar",
+                &crate::debian::build_tar(files.iter().collect()),
+            ),
+        ])
+    }
+}
+
+/// Builds a minimal RPM package in memory.
+///
+/// The package is unsigned, and its payload is a gzipped cpio archive.
+#[cfg(feature = "rpm")]
+#[derive(Debug, Clone)]
+pub struct RpmBuilder {
+    name: String,
+    version: String,
+    release: String,
+    arch: String,
+    license: Option<String>,
+    requires: Vec<String>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+#[cfg(feature = "rpm")]
+impl RpmBuilder {
+    /// A package with the given name, version, release and architecture.
+    pub fn new(name: &str, version: &str, release: &str, arch: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            release: release.to_owned(),
+            arch: arch.to_owned(),
+            license: None,
+            requires: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Set the license of the package.
+    pub fn with_license(mut self, license: &str) -> Self {
+        self.license = Some(license.to_owned());
+        self
+    }
+
+    /// Add a dependency on `name`.
+    pub fn with_requires(mut self, name: &str) -> Self {
+        self.requires.push(name.to_owned());
+        self
+    }
+
+    /// Add a file installed at `path` to the payload.
+    pub fn with_file<B: Into<Vec<u8>>>(mut self, path: &str, contents: B) -> Self {
+        self.files.push((
+            format!("./{}", path.trim_start_matches('/')),
+            contents.into(),
+        ));
+        self
+    }
+
+    /// Build the package.
+    pub fn build(&self) -> Vec<u8> {
+        use std::io::Write;
+
+        use crate::rpm_header::{
+            build_header, build_package, RPMTAG_LICENSE, RPMTAG_PAYLOADCOMPRESSOR,
+            RPMTAG_REQUIRENAME, TYPE_STRING, TYPE_STRING_ARRAY,
+        };
+
+        let string = |value: &str| format!("{}\0", value).into_bytes();
+        let name = string(&self.name);
+        let version = string(&self.version);
+        let release = string(&self.release);
+        let arch = string(&self.arch);
+        let license = self.license.as_deref().map(string);
+        let requires: Vec<u8> = self.requires.iter().flat_map(|name| string(name)).collect();
+
+        let mut entries: Vec<(u32, u32, &[u8])> = vec![
+            (1000, TYPE_STRING, &name),
+            (1001, TYPE_STRING, &version),
+            (1002, TYPE_STRING, &release),
+        ];
+        if let Some(license) = &license {
+            entries.push((RPMTAG_LICENSE, TYPE_STRING, license));
+        }
+        entries.push((1022, TYPE_STRING, &arch));
+        if !requires.is_empty() {
+            entries.push((RPMTAG_REQUIRENAME, TYPE_STRING_ARRAY, &requires));
+        }
+        entries.push((1124, TYPE_STRING, b"cpio\0"));
+        entries.push((RPMTAG_PAYLOADCOMPRESSOR, TYPE_STRING, b"gzip\0"));
+
+        let files: Vec<_> = self
+            .files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_slice()))
+            .collect();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        // Writing to a Vec can't fail.
+        encoder
+            .write_all(&crate::payload::build_cpio(&files))
+            .unwrap();
+
+        let mut rpm = build_package(&[], &build_header(&entries));
+        rpm.extend(encoder.finish().unwrap());
+        rpm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_transport() {
+        let mock = MockTransport::new()
+            .with_response(
+                "https://example.com/a",
+                MockResponse::new("a").with_header("ETag", "\"1\""),
+            )
+            .with_response(
+                "https://example.com/b",
+                MockResponse::new("b").with_status(503),
+            )
+            .install();
+
+        let response = crate::http::get("https://example.com/a").unwrap();
+        assert_eq!(response.headers()["etag"], "\"1\"");
+        assert_eq!(response.text().unwrap(), "a");
+        assert!(crate::http::get("https://example.com/b").is_err());
+        assert!(crate::http::get("https://example.com/c").is_err());
+        assert_eq!(
+            mock.requests(),
+            vec![
+                "https://example.com/a",
+                "https://example.com/b",
+                "https://example.com/c"
+            ]
+        );
+    }
+
+    #[cfg(feature = "debian")]
+    #[test]
+    fn test_deb_builder() {
+        use crate::RemotePackage as _;
+
+        let deb = DebBuilder::new("hello", "1.0-1", "amd64")
+            .with_field("Depends", "libc6")
+            .with_field("Version", "1.0-2")
+            .with_control_file("postinst", "#!/bin/sh\n")
+            .with_file("/usr/bin/hello", "binary")
+            .build();
+        let package = crate::debian::DebianRemotePackage::new_from_read(deb.as_slice()).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(package.package_version().unwrap(), "1.0-2");
+        assert_eq!(package.package_arch().unwrap(), "amd64");
+        assert_eq!(package.package_dependencies(), vec!["libc6"]);
+        assert_eq!(
+            crate::payload::extract_file(deb.as_slice(), "/usr/bin/hello").unwrap(),
+            b"binary"
+        );
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_rpm_builder() {
+        use crate::RemotePackage as _;
+
+        let rpm = RpmBuilder::new("hello", "1.0", "1", "x86_64")
+            .with_license("MIT")
+            .with_requires("glibc")
+            .with_file("/usr/bin/hello", "binary")
+            .build();
+        let package = crate::rpm::RpmRemotePackage::new_from_read(rpm.as_slice()).unwrap();
+        assert_eq!(package.package_name().unwrap(), "hello");
+        assert_eq!(package.package_version().unwrap(), "1.0");
+        assert_eq!(package.package_iteration(), Some("1"));
+        assert_eq!(package.package_license(), Some("MIT"));
+        assert_eq!(package.package_dependencies(), vec!["glibc"]);
+
+        assert_eq!(
+            crate::payload::extract_file(rpm.as_slice(), "/usr/bin/hello").unwrap(),
+            b"binary"
+        );
+    }
+}
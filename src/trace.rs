@@ -0,0 +1,145 @@
+//! Spans and events emitted with the `tracing` feature.
+//!
+//! - `from_url` (info span, with the `url`) around fetching a package, with
+//!   a warning event carrying the `error` if it fails.
+//! - `request` (debug span, with the `method` and `url`) around each request
+//!   of the blocking client, with events for the response `status` or the
+//!   `error`, and for any wait under the rate limit.
+//! - A debug event with `bytes_transferred` and `content_length` when a
+//!   download is stopped.
+//! - `parse` (debug span) around parsing a package, with events for the
+//!   inferred `package_type`, and for the `elapsed` time and any `error`
+//!   once parsing ends. Parsing a prefix of a download may fail before more
+//!   of it arrives, so these errors are only debug events.
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// Measures how long something took, for the `elapsed` field of an event.
+///
+/// There is no clock in a browser, so nothing is measured on wasm32.
+#[derive(Debug)]
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+}
+
+impl Stopwatch {
+    /// Start measuring.
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+        }
+    }
+
+    /// The time since [`Stopwatch::start`], if it can be measured.
+    pub(crate) fn elapsed(&self) -> Option<Duration> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return Some(self.start.elapsed());
+
+        #[cfg(target_arch = "wasm32")]
+        return None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Records the names of spans and the messages of events.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl Recorder {
+        fn take(&self) -> Vec<String> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut recorded = self.0.lock().unwrap();
+            recorded.push(format!("span {}", span.metadata().name()));
+            Id::from_u64(recorded.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = Message(String::new());
+            event.record(&mut message);
+            self.0.lock().unwrap().push(message.0);
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+    }
+
+    /// Visits the fields of an event, keeping the message.
+    struct Message(String);
+
+    impl Visit for Message {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    #[cfg(feature = "rpm")]
+    #[test]
+    fn test_parse_events() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let rpm = crate::rpm_header::tests::build_rpm(&[]);
+            assert!(crate::from_read(rpm.as_slice()).is_ok());
+            assert!(crate::from_read(&b"not a package"[..]).is_err());
+        });
+        assert_eq!(
+            recorder.take(),
+            vec![
+                "span parse",
+                "inferred package type",
+                "parsed package",
+                "span parse",
+                "inferred package type",
+                "failed to parse package",
+            ]
+        );
+    }
+
+    #[cfg(all(feature = "testing", feature = "rpm"))]
+    #[test]
+    fn test_request_events() {
+        let rpm = crate::testing::RpmBuilder::new("hello", "1.0", "1", "noarch").build();
+        let _mock = crate::testing::MockTransport::new()
+            .with_body("https://example.com/hello.rpm", rpm)
+            .install();
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            assert!(crate::from_url("https://example.com/hello.rpm").is_ok());
+            assert!(crate::from_url("https://example.com/missing.rpm").is_err());
+        });
+        let recorded = recorder.take();
+        assert_eq!(
+            recorded[..3],
+            ["span from_url", "span request", "received response"]
+        );
+        assert!(recorded.contains(&"stopped download".to_string()));
+        assert_eq!(recorded.last().unwrap(), "failed to fetch package");
+    }
+}
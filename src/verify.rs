@@ -0,0 +1,719 @@
+//! Support for verifying OpenPGP signatures on packages and repositories.
+//!
+//! A [`Keyring`] holds the public keys that are trusted to sign packages,
+//! usually loaded from armored files such as `RPM-GPG-KEY-fedora`. A
+//! [`TrustPolicy`] decides which signatures are good enough.
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use pgp::composed::{Deserializable, DetachedSignature, SignedPublicKey};
+use pgp::packet::{Packet, PacketParser, Signature};
+use pgp::types::{KeyDetails, KeyVersion};
+
+use crate::PkgError;
+
+/// The start of an armored public key block.
+const PUBLIC_KEY_BEGIN: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+
+/// The start of a cleartext signed message.
+const SIGNED_MESSAGE_BEGIN: &str = "-----BEGIN PGP SIGNED MESSAGE-----";
+
+/// The start of an armored signature.
+const SIGNATURE_BEGIN: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// The result of checking a signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationStatus {
+    /// The signature is valid and was made by a key in the keyring.
+    Signed {
+        /// The id of the signing key, as 16 hex digits.
+        key_id: String,
+    },
+
+    /// There is no signature to check.
+    Unsigned,
+
+    /// The signature was made by a key that isn't in the keyring.
+    UnknownKey {
+        /// The id of the signing key, as 16 hex digits.
+        key_id: String,
+    },
+
+    /// The signature doesn't match the signed data.
+    BadSignature {
+        /// The id of the signing key, if the signature names one.
+        key_id: Option<String>,
+
+        /// Why the signature was rejected.
+        reason: String,
+    },
+}
+
+impl VerificationStatus {
+    /// Whether the signature was made by a trusted key.
+    pub fn is_trusted(&self) -> bool {
+        matches!(self, VerificationStatus::Signed { .. })
+    }
+}
+
+impl fmt::Display for VerificationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationStatus::Signed { key_id } => write!(f, "signed by key {}", key_id),
+            VerificationStatus::Unsigned => f.write_str("unsigned"),
+            VerificationStatus::UnknownKey { key_id } => {
+                write!(f, "signed by unknown key {}", key_id)
+            }
+            VerificationStatus::BadSignature {
+                key_id: Some(key_id),
+                reason,
+            } => write!(f, "bad signature by key {}: {}", key_id, reason),
+            VerificationStatus::BadSignature {
+                key_id: None,
+                reason,
+            } => write!(f, "bad signature: {}", reason),
+        }
+    }
+}
+
+/// How to treat signatures that can't be verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyMode {
+    /// Record the [`VerificationStatus`] but carry on regardless.
+    Report,
+
+    /// Fail with [`PkgError::SignatureError`] unless the signature was made
+    /// by a trusted key.
+    Strict,
+}
+
+/// Which signatures to accept, applied in the same way to packages and
+/// repository metadata.
+///
+/// A signature counts towards `required_signatures` if it was made by a key
+/// in the keyring and, unless `allowed_key_ids` is empty, by one of the
+/// listed keys. The default needs one trusted signature, like
+/// [`VerifyMode::Strict`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustPolicy {
+    /// The number of signatures, by different keys, that must count. Zero
+    /// accepts anything, like [`VerifyMode::Report`].
+    pub required_signatures: usize,
+
+    /// The keys that may sign, as 16 hex digit key ids or full
+    /// fingerprints. Empty allows any key in the keyring.
+    pub allowed_key_ids: Vec<String>,
+
+    /// Whether to accept data that isn't signed at all.
+    pub allow_unsigned: bool,
+}
+
+impl Default for TrustPolicy {
+    fn default() -> Self {
+        Self {
+            required_signatures: 1,
+            allowed_key_ids: Vec::new(),
+            allow_unsigned: false,
+        }
+    }
+}
+
+impl From<VerifyMode> for TrustPolicy {
+    fn from(mode: VerifyMode) -> Self {
+        match mode {
+            VerifyMode::Report => Self::default().with_required_signatures(0),
+            VerifyMode::Strict => Self::default(),
+        }
+    }
+}
+
+impl TrustPolicy {
+    /// Create the default policy, which needs one trusted signature.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require signatures by this many different trusted keys.
+    pub fn with_required_signatures(mut self, count: usize) -> Self {
+        self.required_signatures = count;
+        self
+    }
+
+    /// Only count signatures by this key, given as a key id or fingerprint.
+    /// Can be called more than once to allow several keys.
+    pub fn with_allowed_key(mut self, key_id: &str) -> Self {
+        self.allowed_key_ids.push(key_id.to_owned());
+        self
+    }
+
+    /// Accept data that isn't signed at all.
+    pub fn with_allow_unsigned(mut self, allow: bool) -> Self {
+        self.allow_unsigned = allow;
+        self
+    }
+
+    /// Whether a signature by this key counts. A key id is the last 16 hex
+    /// digits of the fingerprint.
+    fn is_allowed(&self, key_id: &str) -> bool {
+        self.allowed_key_ids.is_empty()
+            || self.allowed_key_ids.iter().any(|allowed| {
+                let allowed: String = allowed
+                    .trim_start_matches("0x")
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect();
+                allowed.len() >= key_id.len()
+                    && allowed[allowed.len() - key_id.len()..].eq_ignore_ascii_case(key_id)
+            })
+    }
+
+    /// Check the results of checking every signature on some data. Fails
+    /// with [`PkgError::SignatureError`] unless the policy is met.
+    pub fn check(&self, statuses: &[VerificationStatus]) -> Result<(), PkgError> {
+        if self.required_signatures == 0 {
+            return Ok(());
+        }
+        let unsigned = statuses
+            .iter()
+            .all(|status| *status == VerificationStatus::Unsigned);
+        if unsigned && self.allow_unsigned {
+            return Ok(());
+        }
+
+        let mut key_ids: Vec<&str> = statuses
+            .iter()
+            .filter_map(|status| match status {
+                VerificationStatus::Signed { key_id } if self.is_allowed(key_id) => {
+                    Some(key_id.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        key_ids.sort_unstable();
+        key_ids.dedup();
+        if key_ids.len() >= self.required_signatures {
+            return Ok(());
+        }
+
+        let reasons: Vec<String> = if statuses.is_empty() {
+            vec![VerificationStatus::Unsigned.to_string()]
+        } else {
+            statuses
+                .iter()
+                .map(|status| match status {
+                    VerificationStatus::Signed { key_id } if !self.is_allowed(key_id) => {
+                        format!("signed by key {}, which is not allowed", key_id)
+                    }
+                    status => status.to_string(),
+                })
+                .collect()
+        };
+        Err(PkgError::SignatureError(format!(
+            "{} of {} required signatures: {}",
+            key_ids.len(),
+            self.required_signatures,
+            reasons.join("; ")
+        )))
+    }
+
+    /// Apply the policy, returning the status that best describes the
+    /// signatures.
+    #[cfg(any(feature = "rpm", feature = "buildinfo", test))]
+    pub(crate) fn apply(
+        &self,
+        statuses: Vec<VerificationStatus>,
+    ) -> Result<VerificationStatus, PkgError> {
+        self.check(&statuses)?;
+        Ok(self.best(statuses))
+    }
+
+    /// The first status that counts towards the policy, or else the first
+    /// status.
+    fn best(&self, statuses: Vec<VerificationStatus>) -> VerificationStatus {
+        let counts = |status: &VerificationStatus| match status {
+            VerificationStatus::Signed { key_id } => self.is_allowed(key_id),
+            _ => false,
+        };
+        let best = statuses.iter().position(counts).unwrap_or(0);
+        statuses
+            .into_iter()
+            .nth(best)
+            .unwrap_or(VerificationStatus::Unsigned)
+    }
+}
+
+/// A set of trusted OpenPGP public keys.
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: Vec<SignedPublicKey>,
+}
+
+impl Keyring {
+    /// Create an empty keyring.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a keyring from armored public keys. The text may hold several
+    /// key blocks, as distribution keyring files often do.
+    pub fn from_armored(text: &str) -> Result<Self, PkgError> {
+        let mut keyring = Self::new();
+        keyring.add_armored(text)?;
+        Ok(keyring)
+    }
+
+    /// Add armored public keys to the keyring.
+    pub fn add_armored(&mut self, text: &str) -> Result<(), PkgError> {
+        let blocks: Vec<String> = text
+            .split(PUBLIC_KEY_BEGIN)
+            .skip(1)
+            .map(|block| format!("{}{}", PUBLIC_KEY_BEGIN, block))
+            .collect();
+        if blocks.is_empty() {
+            return Err(PkgError::SignatureError(
+                "no armored public keys found".to_string(),
+            ));
+        }
+
+        for block in &blocks {
+            let (keys, _headers) = SignedPublicKey::from_string_many(block)?;
+            for key in keys {
+                self.keys.push(key?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add binary (unarmored) public keys, as in the `.gpg` files under
+    /// `/usr/share/keyrings`.
+    pub fn add_binary(&mut self, bytes: &[u8]) -> Result<(), PkgError> {
+        let before = self.keys.len();
+        for key in SignedPublicKey::from_bytes_many(bytes)? {
+            self.keys.push(key?);
+        }
+        if self.keys.len() == before {
+            return Err(PkgError::SignatureError(
+                "no binary public keys found".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Create a keyring from a file of armored or binary public keys.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, PkgError> {
+        let mut keyring = Self::new();
+        keyring.add_file(path)?;
+        Ok(keyring)
+    }
+
+    /// Add the public keys in a file, which may be armored or binary.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), PkgError> {
+        let bytes = fs::read(path.as_ref())?;
+        let result = match std::str::from_utf8(&bytes) {
+            Ok(text) if text.contains(PUBLIC_KEY_BEGIN) => self.add_armored(text),
+            _ => self.add_binary(&bytes),
+        };
+        result.map_err(|e| PkgError::SignatureError(format!("{}: {}", path.as_ref().display(), e)))
+    }
+
+    /// Create a keyring from every key file in a directory; see
+    /// [`Keyring::add_dir`].
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<Self, PkgError> {
+        let mut keyring = Self::new();
+        keyring.add_dir(dir)?;
+        Ok(keyring)
+    }
+
+    /// Add the keys in every file in a directory, such as
+    /// `/etc/apt/trusted.gpg.d` or `/etc/pki/rpm-gpg`. Hidden files and
+    /// subdirectories are skipped, and files are read in name order.
+    pub fn add_dir<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), PkgError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if !hidden && entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+
+        for path in paths {
+            self.add_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// The number of keys in the keyring.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether the keyring has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The ids of the primary keys in the keyring, as 16 hex digits.
+    pub fn key_ids(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .map(|key| key_id_hex(&key.legacy_key_id()))
+            .collect()
+    }
+
+    /// Check a binary OpenPGP signature over `data`.
+    pub fn verify(&self, signature: &[u8], data: &[u8]) -> VerificationStatus {
+        self.verify_read(signature, data)
+    }
+
+    /// Check a binary OpenPGP signature over data read as a stream, so that
+    /// large signed data doesn't need to be held in memory.
+    pub(crate) fn verify_read<R: Read>(&self, signature: &[u8], data: R) -> VerificationStatus {
+        let signature = PacketParser::new(signature).find_map(|packet| match packet {
+            Ok(Packet::Signature(signature)) => Some(signature),
+            _ => None,
+        });
+        match signature {
+            Some(signature) => self.verify_signature(&signature, data),
+            None => VerificationStatus::BadSignature {
+                key_id: None,
+                reason: "no signature packet found".to_string(),
+            },
+        }
+    }
+
+    /// Check an armored detached signature, such as apt's `Release.gpg`,
+    /// over `data`.
+    pub fn verify_armored(
+        &self,
+        signature: &str,
+        data: &[u8],
+    ) -> Result<VerificationStatus, PkgError> {
+        let statuses = self.verify_armored_all(signature, data)?;
+        Ok(TrustPolicy::default().best(statuses))
+    }
+
+    /// Check every signature in an armored block over `data`. Debian's
+    /// `Release.gpg`, for example, is signed by several archive keys.
+    pub fn verify_armored_all(
+        &self,
+        signature: &str,
+        data: &[u8],
+    ) -> Result<Vec<VerificationStatus>, PkgError> {
+        let (signatures, _headers) = DetachedSignature::from_string_many(signature)?;
+        let mut statuses = Vec::new();
+        for signature in signatures {
+            statuses.push(self.verify_signature(&signature?.signature, data));
+        }
+        Ok(statuses)
+    }
+
+    /// Check a cleartext signed message, such as apt's `InRelease`,
+    /// returning the signed text along with the result.
+    pub fn verify_cleartext(
+        &self,
+        message: &str,
+    ) -> Result<(String, VerificationStatus), PkgError> {
+        let (text, statuses) = self.verify_cleartext_all(message)?;
+        Ok((text, TrustPolicy::default().best(statuses)))
+    }
+
+    /// Check every signature on a cleartext signed message, returning the
+    /// signed text along with the results.
+    pub fn verify_cleartext_all(
+        &self,
+        message: &str,
+    ) -> Result<(String, Vec<VerificationStatus>), PkgError> {
+        let message = CleartextMessage::parse(message)?;
+        let statuses =
+            self.verify_armored_all(&message.signature, message.canonical().as_bytes())?;
+        Ok((message.text(), statuses))
+    }
+
+    /// Check a parsed signature over `data` against the key it names.
+    pub(crate) fn verify_signature<R: Read>(
+        &self,
+        signature: &Signature,
+        mut data: R,
+    ) -> VerificationStatus {
+        let key_id = match issuer_key_id(signature) {
+            Some(key_id) => key_id,
+            None => {
+                return VerificationStatus::BadSignature {
+                    key_id: None,
+                    reason: "signature has no issuer".to_string(),
+                }
+            }
+        };
+        let issued = |key: &dyn KeyDetails| key_id_hex(&key.legacy_key_id()) == key_id;
+
+        // Packages are commonly signed by a subkey of the trusted key.
+        let result = self.keys.iter().find_map(|key| {
+            if issued(key) {
+                return Some(signature.verify(key, &mut data));
+            }
+            key.public_subkeys
+                .iter()
+                .find(|subkey| issued(*subkey))
+                .map(|subkey| signature.verify(subkey, &mut data))
+        });
+
+        match result {
+            Some(Ok(())) => VerificationStatus::Signed { key_id },
+            Some(Err(e)) => VerificationStatus::BadSignature {
+                key_id: Some(key_id),
+                reason: e.to_string(),
+            },
+            None => VerificationStatus::UnknownKey { key_id },
+        }
+    }
+}
+
+/// A message signed with the OpenPGP cleartext signature framework.
+struct CleartextMessage {
+    /// The signed lines, with dash-escaping removed.
+    lines: Vec<String>,
+
+    /// The armored signature.
+    signature: String,
+}
+
+impl CleartextMessage {
+    fn parse(message: &str) -> Result<Self, PkgError> {
+        let invalid = |reason: &str| PkgError::SignatureError(reason.to_string());
+
+        let mut lines = message.lines();
+        let _ = lines
+            .by_ref()
+            .find(|line| line.trim_end() == SIGNED_MESSAGE_BEGIN)
+            .ok_or_else(|| invalid("not a cleartext signed message"))?;
+
+        // Skip the armor headers, e.g. `Hash: SHA256`.
+        for line in lines.by_ref() {
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+
+        let mut text = Vec::new();
+        let mut signature = None;
+        for line in lines.by_ref() {
+            if line.trim_end() == SIGNATURE_BEGIN {
+                signature = Some(line.to_owned());
+                break;
+            }
+            text.push(line.strip_prefix("- ").unwrap_or(line).to_owned());
+        }
+
+        let mut signature = signature.ok_or_else(|| invalid("message has no signature"))?;
+        for line in lines {
+            signature.push('\n');
+            signature.push_str(line);
+        }
+
+        Ok(Self {
+            lines: text,
+            signature,
+        })
+    }
+
+    /// The signed text, as it should be read.
+    fn text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push('\n');
+        text
+    }
+
+    /// The signed text in the canonical form that is hashed: trailing
+    /// whitespace removed, lines joined with CRLF and no final line ending.
+    fn canonical(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.trim_end_matches(&[' ', '\t'][..]))
+            .collect::<Vec<_>>()
+            .join("\r\n")
+    }
+}
+
+/// The id of the key that made a signature, as 16 hex digits, from its
+/// issuer or, failing that, its issuer fingerprint.
+fn issuer_key_id(signature: &Signature) -> Option<String> {
+    if let Some(key_id) = signature.issuer_key_id().first() {
+        return Some(key_id_hex(key_id));
+    }
+    // A v6 key id is the start of its fingerprint, and older ones the end.
+    let fingerprint = signature.issuer_fingerprint().into_iter().next()?;
+    let bytes = fingerprint.as_bytes();
+    let key_id = match fingerprint.version() {
+        Some(KeyVersion::V6) => bytes.get(..8)?,
+        _ => bytes.get(bytes.len().checked_sub(8)?..)?,
+    };
+    Some(key_id.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Format a key id as 16 lowercase hex digits.
+fn key_id_hex(key_id: &pgp::types::KeyId) -> String {
+    key_id
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use pgp::ser::Serialize;
+
+    use super::*;
+
+    /// An ed25519 key generated for these tests.
+    pub(crate) const PUBLIC_KEY: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEas+z6BYJKwYBBAHaRw8BAQdABY+Gty2EayqOA9Y3UVNZJpCifml+ujAdi/XR
+7R6Q+Ti0JlJlbW90ZSBQYWNrYWdlIFRlc3QgPHRlc3RAZXhhbXBsZS5jb20+iJAE
+ExYIADgWIQSHQKbVctTihJOvB7DaEbUuxe6VXQUCas+z6AIbAwULCQgHAgYVCgkI
+CwIEFgIDAQIeAQIXgAAKCRDaEbUuxe6VXcLZAP90i5xxKUs/vuDfF4yW9VkpvcAE
+9q0514i2f7SyU2XolwEAqho09NUJ6B0zi9szszE38GO7gPhvpJnHEQvhZXvbWgc=
+=NAWQ
+-----END PGP PUBLIC KEY BLOCK-----
+";
+
+    /// The id of [`PUBLIC_KEY`].
+    pub(crate) const KEY_ID: &str = "da11b52ec5ee955d";
+
+    /// Decode a hex string.
+    pub(crate) fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// A detached signature of `hello world\n` by [`PUBLIC_KEY`].
+    const SIGNATURE: &str = "887504001608001d1621048740a6d572d4e28493af07b0da11b52ec5ee955d05026acfb3e8000a0910da11b52ec5ee955d049300fe2dbbc3b8821d955ac1faa0e9085cd1b64fec17d9da4b85d75ba0c9be21e7044400fc0a84f2eafec798e47ee1a76f6cf71bffdf78bfa8141144b6f6754e983f682c04";
+
+    #[test]
+    fn test_keyring() {
+        let keyring = Keyring::from_armored(PUBLIC_KEY).unwrap();
+        assert_eq!(keyring.len(), 1);
+        assert_eq!(keyring.key_ids(), vec![KEY_ID]);
+
+        let doubled = format!("{}\n{}", PUBLIC_KEY, PUBLIC_KEY);
+        assert_eq!(Keyring::from_armored(&doubled).unwrap().len(), 2);
+        assert!(Keyring::from_armored("not a key").is_err());
+    }
+
+    #[test]
+    fn test_verify() {
+        let keyring = Keyring::from_armored(PUBLIC_KEY).unwrap();
+        let signature = unhex(SIGNATURE);
+
+        assert_eq!(
+            keyring.verify(&signature, b"hello world\n"),
+            VerificationStatus::Signed {
+                key_id: KEY_ID.to_string()
+            }
+        );
+        assert!(matches!(
+            keyring.verify(&signature, b"hello world!\n"),
+            VerificationStatus::BadSignature {
+                key_id: Some(_),
+                ..
+            }
+        ));
+        assert_eq!(
+            Keyring::new().verify(&signature, b"hello world\n"),
+            VerificationStatus::UnknownKey {
+                key_id: KEY_ID.to_string()
+            }
+        );
+        assert!(matches!(
+            keyring.verify(b"garbage", b"hello world\n"),
+            VerificationStatus::BadSignature { key_id: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_trust_policy() {
+        let signed = |key_id: &str| VerificationStatus::Signed {
+            key_id: key_id.to_string(),
+        };
+        let unknown = VerificationStatus::UnknownKey {
+            key_id: "0123456789abcdef".to_string(),
+        };
+
+        let strict = TrustPolicy::from(VerifyMode::Strict);
+        assert!(strict.check(&[signed(KEY_ID)]).is_ok());
+        assert!(strict.check(&[unknown.clone(), signed(KEY_ID)]).is_ok());
+        assert!(strict.check(std::slice::from_ref(&unknown)).is_err());
+        assert!(strict.check(&[VerificationStatus::Unsigned]).is_err());
+        assert!(strict.check(&[]).is_err());
+        assert_eq!(
+            strict.apply(vec![unknown.clone(), signed(KEY_ID)]).unwrap(),
+            signed(KEY_ID)
+        );
+
+        let report = TrustPolicy::from(VerifyMode::Report);
+        assert_eq!(
+            report.apply(vec![unknown.clone()]).unwrap(),
+            unknown.clone()
+        );
+
+        let unsigned = TrustPolicy::new().with_allow_unsigned(true);
+        assert!(unsigned.check(&[VerificationStatus::Unsigned]).is_ok());
+        assert!(unsigned.check(&[unknown]).is_err());
+
+        // Keys can be allowed by fingerprint.
+        let pinned = TrustPolicy::new()
+            .with_allowed_key("8740 A6D5 72D4 E284 93AF 07B0 DA11 B52E C5EE 955D");
+        assert!(pinned.check(&[signed(KEY_ID)]).is_ok());
+        let error = pinned.check(&[signed("0123456789abcdef")]).unwrap_err();
+        assert!(error.to_string().contains("not allowed"));
+
+        let two = TrustPolicy::new().with_required_signatures(2);
+        assert!(two.check(&[signed(KEY_ID), signed(KEY_ID)]).is_err());
+        assert!(two
+            .check(&[signed(KEY_ID), signed("0123456789abcdef")])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_keyring_files() {
+        let dir = std::env::temp_dir().join(format!("remote-package-keys-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let binary = Keyring::from_armored(PUBLIC_KEY).unwrap().keys[0]
+            .to_bytes()
+            .unwrap();
+        fs::write(dir.join("test.asc"), PUBLIC_KEY).unwrap();
+        fs::write(dir.join("test.gpg"), &binary).unwrap();
+        fs::write(dir.join(".hidden"), "not a key").unwrap();
+
+        let keyring = Keyring::from_file(dir.join("test.gpg")).unwrap();
+        assert_eq!(keyring.key_ids(), vec![KEY_ID]);
+        assert_eq!(Keyring::from_dir(&dir).unwrap().len(), 2);
+
+        fs::write(dir.join("zzz.txt"), "not a key").unwrap();
+        let error = Keyring::from_dir(&dir).unwrap_err();
+        assert!(error.to_string().contains("zzz.txt"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cleartext_message() {
+        let message = CleartextMessage::parse(
+            "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\nfoo  \n- -bar\n-----BEGIN PGP SIGNATURE-----\n\nsig\n-----END PGP SIGNATURE-----\n",
+        )
+        .unwrap();
+        assert_eq!(message.text(), "foo  \n-bar\n");
+        assert_eq!(message.canonical(), "foo\r\n-bar");
+        assert!(message
+            .signature
+            .ends_with("sig\n-----END PGP SIGNATURE-----"));
+
+        assert!(CleartextMessage::parse("foo").is_err());
+    }
+}
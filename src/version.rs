@@ -0,0 +1,468 @@
+//! Package version comparison.
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A version comparison operator in a dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    /// Strictly earlier than (`<<` in Debian, `LT` in rpm).
+    Lt,
+
+    /// Earlier than or equal to.
+    Le,
+
+    /// Exactly equal to.
+    Eq,
+
+    /// Later than or equal to.
+    Ge,
+
+    /// Strictly later than (`>>` in Debian, `GT` in rpm).
+    Gt,
+}
+
+/// A version constraint on a dependency, e.g. `>= 3.0.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionConstraint {
+    /// The comparison operator.
+    pub op: VersionOp,
+
+    /// The version to compare against, in the repository's version format.
+    pub version: String,
+}
+
+impl VersionConstraint {
+    /// Whether `version` satisfies the constraint, using `compare` to order
+    /// versions.
+    pub fn matches(&self, version: &str, compare: fn(&str, &str) -> Ordering) -> bool {
+        let ordering = compare(version, &self.version);
+        match self.op {
+            VersionOp::Lt => ordering == Ordering::Less,
+            VersionOp::Le => ordering != Ordering::Greater,
+            VersionOp::Eq => ordering == Ordering::Equal,
+            VersionOp::Ge => ordering != Ordering::Less,
+            VersionOp::Gt => ordering == Ordering::Greater,
+        }
+    }
+}
+
+impl fmt::Display for VersionConstraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            VersionOp::Lt => "<",
+            VersionOp::Le => "<=",
+            VersionOp::Eq => "=",
+            VersionOp::Ge => ">=",
+            VersionOp::Gt => ">",
+        };
+        write!(f, "{} {}", op, self.version)
+    }
+}
+
+/// Compare two Debian package versions using dpkg's ordering rules.
+///
+/// Versions have the form `[epoch:]upstream_version[-debian_revision]`. The
+/// epoch is compared numerically, then the upstream version and revision are
+/// compared with the usual dpkg algorithm, where `~` sorts before anything
+/// (even the end of the string).
+pub fn compare_debian(a: &str, b: &str) -> Ordering {
+    let (a_epoch, a_upstream, a_revision) = split_debian(a);
+    let (b_epoch, b_upstream, b_revision) = split_debian(b);
+
+    a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| verrevcmp(a_upstream, b_upstream))
+        .then_with(|| verrevcmp(a_revision, b_revision))
+}
+
+/// Split a Debian version into its epoch, upstream version and revision.
+pub(crate) fn split_debian(version: &str) -> (u64, &str, &str) {
+    let (epoch, rest) = match version.split_once(':') {
+        Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+        None => (0, version),
+    };
+
+    match rest.rsplit_once('-') {
+        Some((upstream, revision)) => (epoch, upstream, revision),
+        None => (epoch, rest, ""),
+    }
+}
+
+/// Sort weight of a non-digit character in dpkg's ordering.
+fn order(c: Option<u8>) -> i32 {
+    match c {
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => i32::from(c),
+        Some(c) => i32::from(c) + 256,
+        None => 0,
+    }
+}
+
+/// The dpkg `verrevcmp` algorithm.
+fn verrevcmp(a: &str, b: &str) -> Ordering {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        // Compare the non-digit prefixes character by character.
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let ac = order(a.get(i).copied().filter(|c| !c.is_ascii_digit()));
+            let bc = order(b.get(j).copied().filter(|c| !c.is_ascii_digit()));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            if i < a.len() && !a[i].is_ascii_digit() {
+                i += 1;
+            }
+            if j < b.len() && !b[j].is_ascii_digit() {
+                j += 1;
+            }
+        }
+
+        // Then compare the digit runs numerically.
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+
+        let a_start = i;
+        let b_start = j;
+        while i < a.len() && a[i].is_ascii_digit() {
+            i += 1;
+        }
+        while j < b.len() && b[j].is_ascii_digit() {
+            j += 1;
+        }
+
+        let ordering = (i - a_start)
+            .cmp(&(j - b_start))
+            .then_with(|| a[a_start..i].cmp(&b[b_start..j]));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+/// Compare two RPM `[epoch:]version[-release]` strings using rpm's
+/// ordering rules.
+///
+/// A missing epoch is treated as zero, and a missing release compares equal
+/// to any release, matching how rpm compares dependency versions.
+pub fn compare_rpm(a: &str, b: &str) -> Ordering {
+    let (a_epoch, a_version, a_release) = split_rpm(a);
+    let (b_epoch, b_version, b_release) = split_rpm(b);
+
+    let ordering = a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| rpmvercmp(a_version, b_version));
+
+    match (a_release, b_release) {
+        (Some(a_release), Some(b_release)) => {
+            ordering.then_with(|| rpmvercmp(a_release, b_release))
+        }
+        _ => ordering,
+    }
+}
+
+/// Split an RPM EVR string into its epoch, version and release.
+fn split_rpm(evr: &str) -> (u64, &str, Option<&str>) {
+    let (epoch, rest) = match evr.split_once(':') {
+        Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+        None => (0, evr),
+    };
+
+    match rest.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// The rpm `rpmvercmp` algorithm, comparing a single version or release.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let is_separator = |c: u8| !c.is_ascii_alphanumeric() && c != b'~' && c != b'^';
+
+    let mut one = a.as_bytes();
+    let mut two = b.as_bytes();
+
+    loop {
+        while let Some((&c, rest)) = one.split_first() {
+            if !is_separator(c) {
+                break;
+            }
+            one = rest;
+        }
+        while let Some((&c, rest)) = two.split_first() {
+            if !is_separator(c) {
+                break;
+            }
+            two = rest;
+        }
+
+        // Tilde sorts before everything, including the end of the string.
+        if one.first() == Some(&b'~') || two.first() == Some(&b'~') {
+            if one.first() != Some(&b'~') {
+                return Ordering::Greater;
+            }
+            if two.first() != Some(&b'~') {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        // Caret sorts after the end of the string, but before anything else.
+        if one.first() == Some(&b'^') || two.first() == Some(&b'^') {
+            if one.is_empty() {
+                return Ordering::Less;
+            }
+            if two.is_empty() {
+                return Ordering::Greater;
+            }
+            if one.first() != Some(&b'^') {
+                return Ordering::Greater;
+            }
+            if two.first() != Some(&b'^') {
+                return Ordering::Less;
+            }
+            one = &one[1..];
+            two = &two[1..];
+            continue;
+        }
+
+        if one.is_empty() || two.is_empty() {
+            break;
+        }
+
+        // Grab the next segment of the same type from each string.
+        let is_numeric = one[0].is_ascii_digit();
+        let segment = |s: &[u8]| {
+            s.iter()
+                .position(|c| {
+                    if is_numeric {
+                        !c.is_ascii_digit()
+                    } else {
+                        !c.is_ascii_alphabetic()
+                    }
+                })
+                .unwrap_or(s.len())
+        };
+        let one_len = segment(one);
+        let two_len = segment(two);
+        let (one_segment, one_rest) = one.split_at(one_len);
+        let (two_segment, two_rest) = two.split_at(two_len);
+
+        // Numeric segments are always newer than alphabetic ones.
+        if two_segment.is_empty() {
+            return if is_numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let ordering = if is_numeric {
+            let trim = |s: &[u8]| {
+                let zeros = s.iter().take_while(|&&c| c == b'0').count();
+                s[zeros..].to_vec()
+            };
+            let one_segment = trim(one_segment);
+            let two_segment = trim(two_segment);
+            one_segment
+                .len()
+                .cmp(&two_segment.len())
+                .then_with(|| one_segment.cmp(&two_segment))
+        } else {
+            one_segment.cmp(two_segment)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        one = one_rest;
+        two = two_rest;
+    }
+
+    match (one.is_empty(), two.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (false, _) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+    }
+}
+
+/// Compare two Alpine package versions using apk's ordering rules.
+///
+/// Versions have the form `number{.number}[letter]{_suffix[number]}[-rN]`.
+/// Pre-release suffixes (`_alpha`, `_beta`, `_pre`, `_rc`) sort before the
+/// plain version and post-release suffixes (`_cvs`, `_svn`, `_git`, `_hg`,
+/// `_p`) after it.
+pub fn compare_apk(a: &str, b: &str) -> Ordering {
+    let a = ApkVersion::parse(a);
+    let b = ApkVersion::parse(b);
+
+    // Missing numbers sort first, so 1.2 < 1.2.1.
+    let numbers = a
+        .numbers
+        .iter()
+        .zip(&b.numbers)
+        .fold(Ordering::Equal, |ord, (x, y)| ord.then_with(|| x.cmp(y)));
+    numbers
+        .then_with(|| a.numbers.len().cmp(&b.numbers.len()))
+        .then_with(|| a.letter.cmp(&b.letter))
+        .then_with(|| {
+            let len = a.suffixes.len().max(b.suffixes.len());
+            (0..len).fold(Ordering::Equal, |ord, i| {
+                ord.then_with(|| {
+                    // The absence of a suffix ranks between pre- and
+                    // post-release suffixes.
+                    let x = a.suffixes.get(i).copied().unwrap_or((APK_NO_SUFFIX, 0));
+                    let y = b.suffixes.get(i).copied().unwrap_or((APK_NO_SUFFIX, 0));
+                    x.cmp(&y)
+                })
+            })
+        })
+        .then_with(|| a.revision.cmp(&b.revision))
+}
+
+/// The rank of a missing suffix among the apk version suffixes.
+const APK_NO_SUFFIX: usize = 4;
+
+/// The apk version suffixes in order, with the rank of no suffix between
+/// `rc` and `cvs`.
+const APK_SUFFIXES: [&str; 9] = ["alpha", "beta", "pre", "rc", "cvs", "svn", "git", "hg", "p"];
+
+/// The components of an apk version.
+struct ApkVersion {
+    numbers: Vec<u64>,
+    letter: Option<char>,
+    suffixes: Vec<(usize, u64)>,
+    revision: u64,
+}
+
+impl ApkVersion {
+    fn parse(version: &str) -> Self {
+        let (version, revision) = match version.rsplit_once("-r") {
+            Some((version, revision)) if revision.chars().all(|c| c.is_ascii_digit()) => {
+                (version, revision.parse().unwrap_or(0))
+            }
+            _ => (version, 0),
+        };
+
+        let mut parts = version.split('_');
+        let mut release = parts.next().unwrap_or_default();
+
+        let mut letter = None;
+        if let Some(last) = release.chars().last() {
+            if last.is_ascii_lowercase() {
+                letter = Some(last);
+                release = &release[..release.len() - 1];
+            }
+        }
+        let numbers = release.split('.').map(|n| n.parse().unwrap_or(0)).collect();
+
+        let suffixes = parts
+            .map(|suffix| {
+                let split = suffix
+                    .find(|c: char| c.is_ascii_digit())
+                    .unwrap_or(suffix.len());
+                let (name, number) = suffix.split_at(split);
+                // Post-release suffixes are ranked after the missing suffix.
+                let rank = match APK_SUFFIXES.iter().position(|s| *s == name) {
+                    Some(rank) if rank >= APK_NO_SUFFIX => rank + 1,
+                    Some(rank) => rank,
+                    None => APK_NO_SUFFIX,
+                };
+                (rank, number.parse().unwrap_or(0))
+            })
+            .collect();
+
+        Self {
+            numbers,
+            letter,
+            suffixes,
+            revision,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compare_debian() {
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "1.1", Ordering::Less),
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.0~rc1", "1.0", Ordering::Less),
+            ("1.0", "1.0+b1", Ordering::Less),
+            ("1:1.0", "2.0", Ordering::Greater),
+            ("7.81.0-1ubuntu1.15", "7.81.0-1ubuntu1.4", Ordering::Greater),
+            ("1.0-1", "1.0-1~bpo1", Ordering::Greater),
+            ("1.0a", "1.0", Ordering::Greater),
+            ("1.001", "1.1", Ordering::Equal),
+        ];
+
+        for (a, b, expected) in cases.iter() {
+            assert_eq!(compare_debian(a, b), *expected, "{} vs {}", a, b);
+            assert_eq!(compare_debian(b, a), expected.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    #[test]
+    fn test_compare_rpm() {
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "1.0.1", Ordering::Less),
+            ("2.0", "10", Ordering::Less),
+            ("1.0a", "1.0", Ordering::Greater),
+            ("1.0", "1a", Ordering::Greater),
+            ("1.0~rc1", "1.0", Ordering::Less),
+            ("1.0^git1", "1.0", Ordering::Greater),
+            ("1.0^git1", "1.0.1", Ordering::Less),
+            ("1.0-1.fc40", "1.0-2.fc40", Ordering::Less),
+            ("1:1.0-1", "2.0-1", Ordering::Greater),
+            ("0:1.0-1", "1.0-1", Ordering::Equal),
+            ("1.0", "1.0-5", Ordering::Equal),
+            ("1.01", "1.1", Ordering::Equal),
+        ];
+
+        for (a, b, expected) in cases.iter() {
+            assert_eq!(compare_rpm(a, b), *expected, "{} vs {}", a, b);
+            assert_eq!(compare_rpm(b, a), expected.reverse(), "{} vs {}", b, a);
+        }
+    }
+
+    #[test]
+    fn test_compare_apk() {
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.2", "1.2.1", Ordering::Less),
+            ("1.2.4-r1", "1.2.4-r10", Ordering::Less),
+            ("1.2.4_rc1", "1.2.4", Ordering::Less),
+            ("1.2.4_alpha2", "1.2.4_beta1", Ordering::Less),
+            ("1.2.4_git20230717-r4", "1.2.4-r4", Ordering::Greater),
+            ("1.2.4_p1", "1.2.4_git20230717", Ordering::Greater),
+            ("1.2a", "1.2", Ordering::Greater),
+            ("8.5.0-r0", "8.4.0-r3", Ordering::Greater),
+        ];
+
+        for (a, b, expected) in cases.iter() {
+            assert_eq!(compare_apk(a, b), *expected, "{} vs {}", a, b);
+            assert_eq!(compare_apk(b, a), expected.reverse(), "{} vs {}", b, a);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! Helpers shared by the XML metadata parsers.
+use quick_xml::events::BytesStart;
+
+use crate::PkgError;
+
+/// Get the unescaped value of an attribute, if present.
+pub(crate) fn attribute(element: &BytesStart<'_>, name: &[u8]) -> Result<Option<String>, PkgError> {
+    match element.try_get_attribute(name)? {
+        Some(attr) => Ok(Some(attr.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}